@@ -0,0 +1,364 @@
+//! Persistent activity feed.
+//!
+//! Every `Activity` event on the bus is numbered and appended to a log of
+//! JSON lines in the router's [`Storage`], so the dashboard's activity panel
+//! survives restarts. The newest `max_entries` records are kept in memory for
+//! queries; the log is compacted back to that many lines once it has grown to
+//! twice the limit.
+
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::events::{ActivityEvent, ActivityLevel, EventBus, WebEvent};
+use crate::router::RECEIVE_HEARTBEAT;
+use crate::storage::Storage;
+use crate::watchdog::Heartbeat;
+
+/// Most records a single query returns
+pub const MAX_QUERY_LIMIT: usize = 500;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActivityLogConfig {
+    /// Write activity to `path`; without it the feed only lives in memory
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Log name in the storage, relative to `storage.path`
+    #[serde(default = "default_path")]
+    pub path: String,
+    /// Records kept for queries
+    #[serde(default = "default_max_entries")]
+    pub max_entries: usize,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_path() -> String {
+    "activity.jsonl".to_string()
+}
+
+fn default_max_entries() -> usize {
+    1000
+}
+
+impl Default for ActivityLogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            path: default_path(),
+            max_entries: default_max_entries(),
+        }
+    }
+}
+
+/// One numbered entry of the feed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActivityRecord {
+    pub id: u64,
+    pub level: ActivityLevel,
+    pub message: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Filter and page for [`ActivityLog::query`]
+#[derive(Debug, Clone, Default)]
+pub struct ActivityQuery {
+    /// Lowest level to include
+    pub level: Option<ActivityLevel>,
+    pub since: Option<DateTime<Utc>>,
+    /// Only records older than this id, for fetching the next page
+    pub before: Option<u64>,
+    pub limit: usize,
+}
+
+/// A page of records, newest first
+#[derive(Debug, Clone, Serialize)]
+pub struct ActivityPage {
+    pub entries: Vec<ActivityRecord>,
+    /// Pass as `before` to fetch the following page
+    pub next_before: Option<u64>,
+}
+
+#[derive(Debug)]
+struct State {
+    records: VecDeque<ActivityRecord>,
+    next_id: u64,
+    lines_on_disk: usize,
+    /// The last write failed; further failures are not logged again
+    write_failed: bool,
+}
+
+#[derive(Debug)]
+pub struct ActivityLog {
+    /// Storage and name of the log the records are written to
+    storage: Option<(Arc<dyn Storage>, String)>,
+    max_entries: usize,
+    state: Mutex<State>,
+}
+
+impl ActivityLog {
+    /// Open the feed described by `config` in `storage`, loading the records
+    /// of earlier runs
+    pub fn open(config: &ActivityLogConfig, storage: Arc<dyn Storage>) -> Self {
+        let max_entries = config.max_entries.max(1);
+        if !config.enabled {
+            return Self::in_memory(max_entries);
+        }
+
+        let (records, lines_on_disk) = load(storage.as_ref(), &config.path, max_entries);
+        let next_id = records.back().map_or(1, |record| record.id + 1);
+        if !records.is_empty() {
+            info!(
+                "Loaded {} activity records from {}",
+                records.len(),
+                config.path
+            );
+        }
+        Self {
+            storage: Some((storage, config.path.clone())),
+            max_entries,
+            state: Mutex::new(State {
+                records,
+                next_id,
+                lines_on_disk,
+                write_failed: false,
+            }),
+        }
+    }
+
+    /// A feed that is not written to disk
+    pub fn in_memory(max_entries: usize) -> Self {
+        Self {
+            storage: None,
+            max_entries: max_entries.max(1),
+            state: Mutex::new(State {
+                records: VecDeque::new(),
+                next_id: 1,
+                lines_on_disk: 0,
+                write_failed: false,
+            }),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Number and store `event`
+    pub fn record(&self, event: &ActivityEvent) -> ActivityRecord {
+        let mut state = self.state();
+        let record = ActivityRecord {
+            id: state.next_id,
+            level: event.level.clone(),
+            message: event.message.clone(),
+            timestamp: event.timestamp,
+        };
+        state.next_id += 1;
+        state.records.push_back(record.clone());
+        while state.records.len() > self.max_entries {
+            state.records.pop_front();
+        }
+
+        if let Some((storage, path)) = &self.storage {
+            let compact = state.lines_on_disk + 1 >= self.max_entries * 2;
+            let written = if compact {
+                rewrite(storage.as_ref(), path, &state.records).map(|()| state.records.len())
+            } else {
+                append(storage.as_ref(), path, &record).map(|()| state.lines_on_disk + 1)
+            };
+            match written {
+                Ok(lines) => {
+                    state.lines_on_disk = lines;
+                    state.write_failed = false;
+                }
+                Err(err) if !state.write_failed => {
+                    warn!("Failed to write activity to {}: {}", path, err);
+                    state.write_failed = true;
+                }
+                Err(_) => {}
+            }
+        }
+        record
+    }
+
+    pub fn query(&self, query: &ActivityQuery) -> ActivityPage {
+        let limit = query.limit.clamp(1, MAX_QUERY_LIMIT);
+        let state = self.state();
+        let mut matching = state.records.iter().rev().filter(|record| {
+            query.before.is_none_or(|before| record.id < before)
+                && query
+                    .level
+                    .as_ref()
+                    .is_none_or(|level| record.level >= *level)
+                && query.since.is_none_or(|since| record.timestamp >= since)
+        });
+        let entries: Vec<ActivityRecord> = matching.by_ref().take(limit).cloned().collect();
+        let next_before = match matching.next() {
+            Some(_) => entries.last().map(|record| record.id),
+            None => None,
+        };
+        ActivityPage {
+            entries,
+            next_before,
+        }
+    }
+
+    /// Records kept from `from` up to and including `to`, oldest first
+    pub fn between(
+        &self,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Vec<ActivityRecord> {
+        self.state()
+            .records
+            .iter()
+            .filter(|record| {
+                from.is_none_or(|from| record.timestamp >= from)
+                    && to.is_none_or(|to| record.timestamp <= to)
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+fn load(
+    storage: &dyn Storage,
+    path: &str,
+    max_entries: usize,
+) -> (VecDeque<ActivityRecord>, usize) {
+    let lines = match storage.read_log(path) {
+        Ok(lines) => lines,
+        Err(err) => {
+            warn!("Failed to read {}: {}", path, err);
+            return Default::default();
+        }
+    };
+
+    let mut records = VecDeque::new();
+    for line in &lines {
+        match serde_json::from_str::<ActivityRecord>(line) {
+            Ok(record) => {
+                records.push_back(record);
+                if records.len() > max_entries {
+                    records.pop_front();
+                }
+            }
+            Err(err) => warn!("Skipping unreadable line in {}: {}", path, err),
+        }
+    }
+    (records, lines.len())
+}
+
+fn append(storage: &dyn Storage, path: &str, record: &ActivityRecord) -> std::io::Result<()> {
+    storage.append(path, &serde_json::to_string(record)?)
+}
+
+fn rewrite(
+    storage: &dyn Storage,
+    path: &str,
+    records: &VecDeque<ActivityRecord>,
+) -> std::io::Result<()> {
+    let lines = records
+        .iter()
+        .map(serde_json::to_string)
+        .collect::<Result<Vec<_>, _>>()?;
+    storage.rewrite_log(path, &lines)
+}
+
+/// Record activity events from the bus until it closes or the task is aborted
+pub async fn run(log: Arc<ActivityLog>, events: EventBus, heartbeat: Heartbeat) {
+    let mut receiver = events.subscribe("activity-log");
+    let mut liveness = tokio::time::interval(RECEIVE_HEARTBEAT);
+    loop {
+        tokio::select! {
+            _ = liveness.tick() => heartbeat.beat(),
+            event = receiver.recv() => match event {
+                Ok(WebEvent::Activity(activity)) => {
+                    log.record(&activity);
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Activity log fell behind; skipped {} events", skipped);
+                }
+                Err(RecvError::Closed) => return,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::FileStorage;
+    use std::fs;
+
+    fn event(level: ActivityLevel, message: &str) -> ActivityEvent {
+        ActivityEvent {
+            level,
+            message: message.to_string(),
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn records_survive_reopening_and_compaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = ActivityLogConfig {
+            enabled: true,
+            path: dir.path().join("activity.jsonl").display().to_string(),
+            max_entries: 3,
+        };
+
+        let storage: Arc<dyn Storage> = Arc::new(FileStorage::new(dir.path()));
+        let log = ActivityLog::open(&config, Arc::clone(&storage));
+        for n in 0..7 {
+            log.record(&event(ActivityLevel::Info, &format!("event {}", n)));
+        }
+        let lines = fs::read_to_string(&config.path).unwrap().lines().count();
+        assert!(lines < 6, "file compacted, {} lines", lines);
+
+        let reopened = ActivityLog::open(&config, storage);
+        let page = reopened.query(&ActivityQuery {
+            limit: 10,
+            ..Default::default()
+        });
+        let ids: Vec<u64> = page.entries.iter().map(|record| record.id).collect();
+        assert_eq!(ids, vec![7, 6, 5]);
+        assert_eq!(reopened.record(&event(ActivityLevel::Info, "next")).id, 8);
+    }
+
+    #[test]
+    fn queries_filter_and_paginate() {
+        let log = ActivityLog::in_memory(10);
+        log.record(&event(ActivityLevel::Info, "a"));
+        log.record(&event(ActivityLevel::Warn, "b"));
+        log.record(&event(ActivityLevel::Error, "c"));
+        log.record(&event(ActivityLevel::Warn, "d"));
+
+        let first = log.query(&ActivityQuery {
+            level: Some(ActivityLevel::Warn),
+            limit: 2,
+            ..Default::default()
+        });
+        let messages: Vec<&str> = first.entries.iter().map(|r| r.message.as_str()).collect();
+        assert_eq!(messages, vec!["d", "c"]);
+        assert_eq!(first.next_before, Some(3));
+
+        let second = log.query(&ActivityQuery {
+            level: Some(ActivityLevel::Warn),
+            before: first.next_before,
+            limit: 2,
+            ..Default::default()
+        });
+        assert_eq!(second.entries.len(), 1);
+        assert_eq!(second.entries[0].message, "b");
+        assert_eq!(second.next_before, None);
+    }
+}
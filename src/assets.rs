@@ -0,0 +1,85 @@
+//! Static files of the web UI, built into the binary.
+//!
+//! `/static` serves `web.static_dir` when it holds the requested file and
+//! these copies otherwise, so a lone binary still has a working dashboard
+//! while a checkout or package can override single files on disk. The page
+//! templates are embedded the same way in `web.rs`.
+
+/// An embedded file, by its path below `/static`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Asset {
+    pub path: &'static str,
+    pub content_type: &'static str,
+    pub body: &'static str,
+}
+
+const CSS: &str = "text/css; charset=utf-8";
+const JAVASCRIPT: &str = "text/javascript; charset=utf-8";
+
+pub const ASSETS: &[Asset] = &[
+    Asset {
+        path: "css/style.css",
+        content_type: CSS,
+        body: include_str!("../web/static/css/style.css"),
+    },
+    Asset {
+        path: "js/auth.js",
+        content_type: JAVASCRIPT,
+        body: include_str!("../web/static/js/auth.js"),
+    },
+    Asset {
+        path: "js/config.js",
+        content_type: JAVASCRIPT,
+        body: include_str!("../web/static/js/config.js"),
+    },
+    Asset {
+        path: "js/dashboard.js",
+        content_type: JAVASCRIPT,
+        body: include_str!("../web/static/js/dashboard.js"),
+    },
+    Asset {
+        path: "js/metrics.js",
+        content_type: JAVASCRIPT,
+        body: include_str!("../web/static/js/metrics.js"),
+    },
+    Asset {
+        path: "js/routes.js",
+        content_type: JAVASCRIPT,
+        body: include_str!("../web/static/js/routes.js"),
+    },
+];
+
+/// The embedded file at `path`, with or without a leading `/`
+pub fn get(path: &str) -> Option<&'static Asset> {
+    let path = path.trim_start_matches('/');
+    ASSETS.iter().find(|asset| asset.path == path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_file_in_web_static_is_embedded() {
+        fn files(dir: &std::path::Path, root: &std::path::Path, found: &mut Vec<String>) {
+            for entry in std::fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    files(&path, root, found);
+                } else {
+                    let relative = path.strip_prefix(root).unwrap();
+                    found.push(relative.to_string_lossy().replace('\\', "/"));
+                }
+            }
+        }
+
+        let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("web/static");
+        let mut found = Vec::new();
+        files(&root, &root, &mut found);
+        for path in found {
+            assert!(get(&path).is_some(), "web/static/{} is not embedded", path);
+        }
+        assert_eq!(get("/js/auth.js").unwrap().content_type, JAVASCRIPT);
+        assert!(get("js/missing.js").is_none());
+    }
+}
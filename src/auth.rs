@@ -2,19 +2,116 @@ use bcrypt::{hash, verify, DEFAULT_COST};
 use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
 
+use crate::last_error;
+use crate::storage::Storage;
+
+/// Storage keys of the accounts and of the tokens issued to them
+const USERS_KEY: &str = "auth/users.json";
+const TOKENS_KEY: &str = "auth/tokens.json";
+
+/// How long a stream ticket can be redeemed after it is issued
+pub const STREAM_TICKET_TTL: Duration = Duration::from_secs(30);
+
 /// Authentication configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthConfig {
     pub enabled: bool,
     pub jwt_secret: String,
     pub token_expiry_hours: u64,
+    /// Seconds a token is still accepted after it expires, absorbing clock
+    /// skew between clients and small steps of the system clock
+    #[serde(default = "default_token_leeway")]
+    pub token_leeway_seconds: u64,
     pub max_failed_attempts: u32,
     pub lockout_duration_minutes: u32,
     pub require_https: bool,
     pub allowed_origins: Vec<String>,
+    /// Per-endpoint overrides of the minimum role, first match wins
+    #[serde(default)]
+    pub permissions: Vec<PermissionRule>,
+    /// Roles for API clients authenticating with a TLS client certificate
+    #[serde(default)]
+    pub client_certificates: Vec<CertificateRole>,
+}
+
+/// Role granted to clients whose certificate names `identity`, matched
+/// exactly against the subject CN and the DNS, email and URI SANs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CertificateRole {
+    pub identity: String,
+    pub role: UserRole,
+}
+
+/// Minimum role for the API endpoints matching `endpoint`, written as
+/// `"METHOD /path"` in the route syntax, e.g. `"PUT /api/config"`. The
+/// method may be `*`; a path segment of `*` or `:name` matches any one
+/// segment and a trailing `**` matches the rest of the path.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PermissionRule {
+    pub endpoint: String,
+    pub role: UserRole,
+}
+
+impl PermissionRule {
+    /// Check the pattern syntax, returning a description of the problem
+    pub fn validate(&self) -> Result<(), String> {
+        let (_, path) = split_endpoint(&self.endpoint);
+        if !path.starts_with('/') {
+            return Err(format!(
+                "Permission endpoint '{}' must be 'METHOD /path'",
+                self.endpoint
+            ));
+        }
+        let segments: Vec<&str> = path.split('/').collect();
+        if segments[..segments.len() - 1].contains(&"**") {
+            return Err(format!(
+                "Permission endpoint '{}' may only end in '**'",
+                self.endpoint
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether the rule covers `endpoint`, a route as passed by a handler
+    pub fn matches(&self, endpoint: &str) -> bool {
+        let (method, path) = split_endpoint(&self.endpoint);
+        let (route_method, route_path) = split_endpoint(endpoint);
+        if method != "*" && !method.eq_ignore_ascii_case(route_method) {
+            return false;
+        }
+
+        let mut pattern = path.split('/');
+        let mut route = route_path.split('/');
+        loop {
+            match (pattern.next(), route.next()) {
+                (Some("**"), _) => return true,
+                (Some(expected), Some(actual)) => {
+                    let wildcard = expected == "*" || expected.starts_with(':');
+                    if !wildcard && expected != actual {
+                        return false;
+                    }
+                }
+                (None, None) => return true,
+                _ => return false,
+            }
+        }
+    }
+}
+
+/// Split `"METHOD /path"`; a bare path applies to every method
+fn split_endpoint(endpoint: &str) -> (&str, &str) {
+    match endpoint.trim().split_once(char::is_whitespace) {
+        Some((method, path)) => (method, path.trim()),
+        None => ("*", endpoint.trim()),
+    }
+}
+
+fn default_token_leeway() -> u64 {
+    60
 }
 
 impl Default for AuthConfig {
@@ -23,10 +120,13 @@ impl Default for AuthConfig {
             enabled: true,
             jwt_secret: Uuid::new_v4().to_string(),
             token_expiry_hours: 24,
+            token_leeway_seconds: default_token_leeway(),
             max_failed_attempts: 5,
             lockout_duration_minutes: 30,
             require_https: false,
             allowed_origins: vec!["http://localhost:8080".to_string()],
+            permissions: Vec::new(),
+            client_certificates: Vec::new(),
         }
     }
 }
@@ -66,6 +166,15 @@ impl UserRole {
     pub fn can_admin(&self) -> bool {
         matches!(self, UserRole::Admin)
     }
+
+    /// Whether this role may do what `required` may
+    pub fn satisfies(&self, required: &UserRole) -> bool {
+        match required {
+            UserRole::ReadOnly => self.can_read(),
+            UserRole::Operator => self.can_write(),
+            UserRole::Admin => self.can_admin(),
+        }
+    }
 }
 
 /// JWT Claims structure
@@ -95,6 +204,14 @@ pub struct LoginResponse {
     pub message: String,
 }
 
+/// One-time ticket opening an event stream, since `EventSource` cannot send
+/// the token in a header and URLs leak into logs and proxies
+#[derive(Debug, Serialize)]
+pub struct StreamTicket {
+    pub ticket: String,
+    pub expires_in: u64,
+}
+
 /// Public user information
 #[derive(Debug, Serialize)]
 pub struct UserInfo {
@@ -110,10 +227,31 @@ pub struct AuthManager {
     active_tokens: HashMap<String, Claims>,
     encoding_key: EncodingKey,
     decoding_key: DecodingKey,
+    /// Where accounts and tokens are kept across restarts and reloads
+    storage: Option<Arc<dyn Storage>>,
+    /// Unredeemed stream tickets with the token each stands for and its expiry
+    stream_tickets: HashMap<String, (String, Instant)>,
 }
 
 impl AuthManager {
+    /// A manager whose accounts and tokens only live in memory
     pub fn new(config: AuthConfig) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Self::open(config, None)
+    }
+
+    /// A manager that loads its accounts and tokens from `storage` and
+    /// writes every change back
+    pub fn with_storage(
+        config: AuthConfig,
+        storage: Arc<dyn Storage>,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Self::open(config, Some(storage))
+    }
+
+    fn open(
+        config: AuthConfig,
+        storage: Option<Arc<dyn Storage>>,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
         let encoding_key = EncodingKey::from_secret(config.jwt_secret.as_bytes());
         let decoding_key = DecodingKey::from_secret(config.jwt_secret.as_bytes());
 
@@ -123,14 +261,44 @@ impl AuthManager {
             active_tokens: HashMap::new(),
             encoding_key,
             decoding_key,
+            storage,
+            stream_tickets: HashMap::new(),
         };
 
+        if let Some(storage) = &manager.storage {
+            manager.users = storage.get_json(USERS_KEY)?.unwrap_or_default();
+            manager.active_tokens = storage.get_json(TOKENS_KEY)?.unwrap_or_default();
+            if !manager.users.is_empty() {
+                log::info!("Loaded {} user accounts", manager.users.len());
+            }
+        }
+
         // Create default admin user if none exists
-        manager.create_default_admin()?;
+        if manager.users.is_empty() {
+            manager.create_default_admin()?;
+            manager.save();
+        }
 
         Ok(manager)
     }
 
+    /// Write accounts and tokens to the storage, if there is one
+    fn save(&self) {
+        let Some(storage) = &self.storage else {
+            return;
+        };
+        let saved = storage
+            .put_json(USERS_KEY, &self.users)
+            .and_then(|()| storage.put_json(TOKENS_KEY, &self.active_tokens));
+        if let Err(err) = saved {
+            log::warn!("Failed to save user accounts: {}", err);
+            last_error::record(
+                last_error::AUTH,
+                format!("Failed to save user accounts: {}", err),
+            );
+        }
+    }
+
     fn create_default_admin(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         if self.users.is_empty() {
             let password_hash = hash("admin123", DEFAULT_COST)?;
@@ -152,6 +320,15 @@ impl AuthManager {
     }
 
     pub async fn authenticate(&mut self, request: LoginRequest) -> LoginResponse {
+        let response = self.attempt_login(request);
+        // Failed attempts and lockouts change the account as well
+        if self.config.enabled {
+            self.save();
+        }
+        response
+    }
+
+    fn attempt_login(&mut self, request: LoginRequest) -> LoginResponse {
         if !self.config.enabled {
             return LoginResponse {
                 success: false,
@@ -166,6 +343,10 @@ impl AuthManager {
             Some(user) => user,
             None => {
                 log::warn!("Login attempt for non-existent user: {}", request.username);
+                last_error::record(
+                    last_error::AUTH,
+                    format!("Login attempt for non-existent user: {}", request.username),
+                );
                 return LoginResponse {
                     success: false,
                     token: None,
@@ -230,6 +411,10 @@ impl AuthManager {
                     }
                     Err(e) => {
                         log::error!("Failed to generate token: {}", e);
+                        last_error::record(
+                            last_error::AUTH,
+                            format!("Failed to generate token: {}", e),
+                        );
                         LoginResponse {
                             success: false,
                             token: None,
@@ -248,6 +433,13 @@ impl AuthManager {
                     request.username,
                     user.failed_attempts
                 );
+                last_error::record(
+                    last_error::AUTH,
+                    format!(
+                        "Failed login attempt for user: {} (attempt {})",
+                        request.username, user.failed_attempts
+                    ),
+                );
 
                 // Lock account if too many failed attempts
                 if user.failed_attempts >= self.config.max_failed_attempts {
@@ -279,7 +471,7 @@ impl AuthManager {
     ) -> Result<String, jsonwebtoken::errors::Error> {
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
-            .unwrap()
+            .unwrap_or_default()
             .as_secs() as usize;
 
         let exp = now + (self.config.token_expiry_hours as usize * 3600);
@@ -301,6 +493,29 @@ impl AuthManager {
         Ok(token)
     }
 
+    /// Minimum role for `endpoint`: the first configured rule that matches,
+    /// otherwise the handler's `default`
+    pub fn required_role(&self, endpoint: &str, default: UserRole) -> UserRole {
+        self.config
+            .permissions
+            .iter()
+            .find(|rule| rule.matches(endpoint))
+            .map_or(default, |rule| rule.role.clone())
+    }
+
+    /// Role of a client presenting a certificate with `identities`: the
+    /// first configured mapping naming one of them
+    pub fn certificate_role(&self, identities: &[String]) -> Option<UserRole> {
+        if !self.config.enabled {
+            return None;
+        }
+        self.config
+            .client_certificates
+            .iter()
+            .find(|mapping| identities.contains(&mapping.identity))
+            .map(|mapping| mapping.role.clone())
+    }
+
     pub fn validate_token(&self, token: &str) -> Result<Claims, AuthError> {
         if !self.config.enabled {
             return Err(AuthError::Disabled);
@@ -308,6 +523,7 @@ impl AuthManager {
 
         let mut validation = Validation::new(Algorithm::HS256);
         validation.validate_exp = true;
+        validation.leeway = self.config.token_leeway_seconds;
 
         match decode::<Claims>(token, &self.decoding_key, &validation) {
             Ok(token_data) => {
@@ -339,10 +555,38 @@ impl AuthManager {
     pub fn logout(&mut self, token: &str) -> Result<(), AuthError> {
         let claims = self.validate_token(token)?;
         self.active_tokens.remove(&claims.jti);
+        self.save();
         log::info!("User {} logged out", claims.sub);
         Ok(())
     }
 
+    /// Issue a ticket standing in for `token` on one event stream request
+    pub fn issue_stream_ticket(&mut self, token: &str) -> Result<StreamTicket, AuthError> {
+        self.validate_token(token)?;
+        let now = Instant::now();
+        self.stream_tickets.retain(|_, (_, expires)| *expires > now);
+        let ticket = Uuid::new_v4().simple().to_string();
+        self.stream_tickets
+            .insert(ticket.clone(), (token.to_string(), now + STREAM_TICKET_TTL));
+        Ok(StreamTicket {
+            ticket,
+            expires_in: STREAM_TICKET_TTL.as_secs(),
+        })
+    }
+
+    /// Claims of the token a ticket was issued for; each ticket works once
+    pub fn redeem_stream_ticket(&mut self, ticket: &str) -> Result<Claims, AuthError> {
+        let (token, expires) = self
+            .stream_tickets
+            .remove(ticket)
+            .ok_or(AuthError::InvalidToken)?;
+        if expires <= Instant::now() {
+            return Err(AuthError::InvalidToken);
+        }
+        // The session may have ended since the ticket was issued
+        self.validate_token(&token)
+    }
+
     pub fn create_user(
         &mut self,
         username: String,
@@ -366,6 +610,7 @@ impl AuthManager {
         };
 
         self.users.insert(username.clone(), user);
+        self.save();
         log::info!("Created new user: {}", username);
         Ok(())
     }
@@ -383,6 +628,7 @@ impl AuthManager {
         }
 
         user.password_hash = hash(new_password, DEFAULT_COST)?;
+        self.save();
         log::info!("Password changed for user: {}", username);
         Ok(())
     }
@@ -398,6 +644,7 @@ impl AuthManager {
         // Revoke all active tokens for this user
         self.active_tokens
             .retain(|_, claims| claims.sub != username);
+        self.save();
 
         log::info!("Deactivated user: {}", username);
         Ok(())
@@ -417,10 +664,17 @@ impl AuthManager {
     pub fn cleanup_expired_tokens(&mut self) {
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
-            .unwrap()
+            .unwrap_or_default()
             .as_secs() as usize;
 
-        self.active_tokens.retain(|_, claims| claims.exp > now);
+        // Tokens within the leeway still validate, so they are kept as well
+        let leeway = self.config.token_leeway_seconds as usize;
+        let before = self.active_tokens.len();
+        self.active_tokens
+            .retain(|_, claims| claims.exp.saturating_add(leeway) > now);
+        if self.active_tokens.len() != before {
+            self.save();
+        }
     }
 }
 
@@ -443,27 +697,11 @@ pub enum AuthError {
 
 /// Permission checking middleware
 pub fn require_permission(required_role: UserRole) -> impl Fn(&Claims) -> Result<(), AuthError> {
-    move |claims: &Claims| match required_role {
-        UserRole::ReadOnly => {
-            if claims.role.can_read() {
-                Ok(())
-            } else {
-                Err(AuthError::InsufficientPermissions)
-            }
-        }
-        UserRole::Operator => {
-            if claims.role.can_write() {
-                Ok(())
-            } else {
-                Err(AuthError::InsufficientPermissions)
-            }
-        }
-        UserRole::Admin => {
-            if claims.role.can_admin() {
-                Ok(())
-            } else {
-                Err(AuthError::InsufficientPermissions)
-            }
+    move |claims: &Claims| {
+        if claims.role.satisfies(&required_role) {
+            Ok(())
+        } else {
+            Err(AuthError::InsufficientPermissions)
         }
     }
 }
@@ -527,10 +765,85 @@ mod tests {
         assert!(UserRole::ReadOnly.can_read());
     }
 
+    #[test]
+    fn test_permission_overrides() {
+        let rule = |endpoint: &str, role| PermissionRule {
+            endpoint: endpoint.to_string(),
+            role,
+        };
+        let manager = AuthManager::new(AuthConfig {
+            permissions: vec![
+                rule("PUT /api/config", UserRole::Operator),
+                rule("* /api/config/history/**", UserRole::Admin),
+                rule("POST /api/interfaces/*/disable", UserRole::Admin),
+            ],
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(
+            manager.required_role("PUT /api/config", UserRole::Admin),
+            UserRole::Operator
+        );
+        assert_eq!(
+            manager.required_role("GET /api/config/history/:version/diff", UserRole::Operator),
+            UserRole::Admin
+        );
+        assert_eq!(
+            manager.required_role("POST /api/interfaces/:name/disable", UserRole::Operator),
+            UserRole::Admin
+        );
+        assert_eq!(
+            manager.required_role("POST /api/interfaces/:name/enable", UserRole::Operator),
+            UserRole::Operator
+        );
+        assert_eq!(
+            manager.required_role("GET /api/config", UserRole::Operator),
+            UserRole::Operator
+        );
+
+        assert!(rule("/api/**", UserRole::Admin).validate().is_ok());
+        assert!(rule("PUT api/config", UserRole::Admin).validate().is_err());
+        assert!(rule("GET /api/**/x", UserRole::Admin).validate().is_err());
+    }
+
+    #[test]
+    fn test_certificate_roles() {
+        let manager = AuthManager::new(AuthConfig {
+            client_certificates: vec![
+                CertificateRole {
+                    identity: "ci.example.net".to_string(),
+                    role: UserRole::Operator,
+                },
+                CertificateRole {
+                    identity: "ops@example.net".to_string(),
+                    role: UserRole::Admin,
+                },
+            ],
+            ..Default::default()
+        })
+        .unwrap();
+
+        let identities = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            manager.certificate_role(&identities(&["deploy", "ci.example.net"])),
+            Some(UserRole::Operator)
+        );
+        assert_eq!(
+            manager.certificate_role(&identities(&["ops@example.net"])),
+            Some(UserRole::Admin)
+        );
+        assert_eq!(manager.certificate_role(&identities(&["ci.example"])), None);
+        assert!(UserRole::Admin.satisfies(&UserRole::Operator));
+        assert!(!UserRole::ReadOnly.satisfies(&UserRole::Operator));
+    }
+
     #[tokio::test]
     async fn test_account_lockout() {
-        let mut config = AuthConfig::default();
-        config.max_failed_attempts = 2;
+        let config = AuthConfig {
+            max_failed_attempts: 2,
+            ..Default::default()
+        };
         let mut auth_manager = AuthManager::new(config).unwrap();
 
         let request = LoginRequest {
@@ -551,4 +864,85 @@ mod tests {
         assert!(!response3.success);
         assert_eq!(response3.message, "Account is temporarily locked");
     }
+
+    #[tokio::test]
+    async fn accounts_and_tokens_survive_reopening() {
+        let storage: Arc<dyn Storage> = Arc::new(crate::storage::MemoryStorage::default());
+        // The default secret is random; reopening must reuse it for tokens to verify
+        let config = AuthConfig::default();
+        let mut auth_manager =
+            AuthManager::with_storage(config.clone(), Arc::clone(&storage)).unwrap();
+        auth_manager
+            .create_user("ops".to_string(), "secret".to_string(), UserRole::Operator)
+            .unwrap();
+        let token = auth_manager
+            .authenticate(LoginRequest {
+                username: "ops".to_string(),
+                password: "secret".to_string(),
+            })
+            .await
+            .token
+            .unwrap();
+
+        let mut reopened = AuthManager::with_storage(config, storage).unwrap();
+        assert_eq!(reopened.users.len(), 2);
+        assert_eq!(reopened.validate_token(&token).unwrap().sub, "ops");
+        reopened.logout(&token).unwrap();
+        assert!(matches!(
+            reopened.validate_token(&token),
+            Err(AuthError::TokenRevoked)
+        ));
+    }
+    #[test]
+    fn tokens_are_accepted_within_the_leeway() {
+        let mut auth_manager = AuthManager::new(AuthConfig::default()).unwrap();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as usize;
+        let claims = Claims {
+            sub: "admin".to_string(),
+            role: UserRole::Admin,
+            exp: now - 30,
+            iat: now - 3600,
+            jti: "expired".to_string(),
+        };
+        let token = encode(&Header::default(), &claims, &auth_manager.encoding_key).unwrap();
+        auth_manager
+            .active_tokens
+            .insert(claims.jti.clone(), claims);
+
+        assert!(auth_manager.validate_token(&token).is_ok());
+        auth_manager.cleanup_expired_tokens();
+        assert_eq!(auth_manager.active_tokens.len(), 1);
+
+        auth_manager.config.token_leeway_seconds = 0;
+        assert!(auth_manager.validate_token(&token).is_err());
+        auth_manager.cleanup_expired_tokens();
+        assert!(auth_manager.active_tokens.is_empty());
+    }
+
+    #[tokio::test]
+    async fn stream_tickets_work_once_and_end_with_the_session() {
+        let mut auth_manager = AuthManager::new(AuthConfig::default()).unwrap();
+        let token = auth_manager
+            .authenticate(LoginRequest {
+                username: "admin".to_string(),
+                password: "admin123".to_string(),
+            })
+            .await
+            .token
+            .unwrap();
+        assert!(auth_manager.issue_stream_ticket("not-a-token").is_err());
+
+        let ticket = auth_manager.issue_stream_ticket(&token).unwrap();
+        assert_eq!(ticket.expires_in, STREAM_TICKET_TTL.as_secs());
+        let claims = auth_manager.redeem_stream_ticket(&ticket.ticket).unwrap();
+        assert_eq!(claims.sub, "admin");
+        assert!(auth_manager.redeem_stream_ticket(&ticket.ticket).is_err());
+
+        let ticket = auth_manager.issue_stream_ticket(&token).unwrap();
+        auth_manager.logout(&token).unwrap();
+        assert!(auth_manager.redeem_stream_ticket(&ticket.ticket).is_err());
+    }
 }
@@ -1,6 +1,7 @@
 //! CLI formatting and user interface utilities
 
-use clap::{Parser, Subcommand};
+use crate::locale::{format_bytes, Locale, Text};
+use clap::{Args, Parser, Subcommand};
 use indicatif::{ProgressBar, ProgressStyle};
 use std::time::Duration;
 
@@ -20,6 +21,16 @@ pub enum Commands {
         /// Configuration file path
         #[arg(short, long, default_value = "rust-route.json")]
         config: String,
+        /// Activate one of the configuration's named profiles
+        #[arg(long)]
+        profile: Option<String>,
+        /// Never write to disk: configure from RUST_ROUTE_* variables and
+        /// --set on top of the configuration file, if there is one
+        #[arg(long)]
+        ephemeral: bool,
+        /// Override one setting, e.g. --set rip.port=5520
+        #[arg(long = "set", value_name = "KEY=VALUE", requires = "ephemeral")]
+        settings: Vec<String>,
     },
     /// Test router functionality
     Test {
@@ -34,6 +45,191 @@ pub enum Commands {
         #[command(subcommand)]
         action: ConfigAction,
     },
+    /// Flush learned state on a running router and request fresh updates
+    Clear {
+        #[command(subcommand)]
+        target: ClearTarget,
+        /// Base URL of the router's web API
+        #[arg(long, default_value = crate::client::DEFAULT_API_URL, global = true)]
+        url: String,
+        /// Bearer token when web authentication is enabled
+        #[arg(long, global = true)]
+        token: Option<String>,
+    },
+    /// Drain traffic away from a running router before maintenance
+    Maintenance {
+        #[command(subcommand)]
+        action: MaintenanceAction,
+        /// Base URL of the router's web API
+        #[arg(long, default_value = crate::client::DEFAULT_API_URL, global = true)]
+        url: String,
+        /// Bearer token when web authentication is enabled
+        #[arg(long, global = true)]
+        token: Option<String>,
+    },
+    /// Export configuration and static routes for another routing daemon
+    Export {
+        /// Target syntax: frr or bird
+        #[arg(short, long, default_value = "frr")]
+        format: String,
+        /// Render from a configuration file instead of a running router
+        #[arg(short, long)]
+        config: Option<String>,
+        /// Output file (stdout when omitted)
+        #[arg(short, long)]
+        output: Option<String>,
+        /// Base URL of the router's web API
+        #[arg(long, default_value = crate::client::DEFAULT_API_URL)]
+        url: String,
+        /// Bearer token when web authentication is enabled
+        #[arg(long)]
+        token: Option<String>,
+    },
+    /// Compare the route table of a running router with a peer's
+    Compare {
+        /// Base URL of the peer's web API, e.g. http://router-b:8080
+        #[arg(long)]
+        peer: String,
+        /// Bearer token for the peer when its web authentication is enabled
+        #[arg(long)]
+        peer_token: Option<String>,
+        /// Base URL of the router's web API
+        #[arg(long, default_value = crate::client::DEFAULT_API_URL)]
+        url: String,
+        /// Bearer token when web authentication is enabled
+        #[arg(long)]
+        token: Option<String>,
+    },
+    /// Export the metrics history of a running router for offline analysis
+    ExportMetrics {
+        #[command(flatten)]
+        range: HistoryRange,
+    },
+    /// Export the activity feed of a running router for offline analysis
+    ExportEvents {
+        #[command(flatten)]
+        range: HistoryRange,
+    },
+    /// Check the host environment for the configuration without starting
+    Preflight {
+        /// Configuration file path
+        #[arg(short, long, default_value = "rust-route.json")]
+        config: String,
+    },
+    /// Interface administration
+    Interface {
+        #[command(subcommand)]
+        action: InterfaceAction,
+    },
+    /// Run several routers on this host from a topology file
+    Lab {
+        #[command(subcommand)]
+        action: LabAction,
+    },
+}
+
+impl Commands {
+    /// Whether the command writes data to stdout, which the banner would
+    /// spoil for pipes
+    pub fn writes_to_stdout(&self) -> bool {
+        match self {
+            Commands::Export { output, .. } => output.is_none(),
+            Commands::ExportMetrics { range } | Commands::ExportEvents { range } => {
+                range.output.is_none()
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Time range, format and destination of a history export
+#[derive(Args)]
+pub struct HistoryRange {
+    /// Start of the range: an RFC 3339 time or an age such as 2h
+    #[arg(long)]
+    pub from: Option<String>,
+    /// End of the range: an RFC 3339 time or an age such as 30m
+    #[arg(long)]
+    pub to: Option<String>,
+    /// Output format: csv or json
+    #[arg(short, long, default_value = "csv")]
+    pub format: String,
+    /// Output file (stdout when omitted)
+    #[arg(short, long)]
+    pub output: Option<String>,
+    /// Base URL of the router's web API
+    #[arg(long, default_value = crate::client::DEFAULT_API_URL)]
+    pub url: String,
+    /// Bearer token when web authentication is enabled
+    #[arg(long)]
+    pub token: Option<String>,
+}
+
+#[derive(Subcommand)]
+pub enum LabAction {
+    /// Start a router process per router of the topology until Ctrl-C
+    Up {
+        /// Topology file (YAML)
+        topology: String,
+    },
+    /// Stop the processes of a lab whose runner is gone
+    Down {
+        /// Topology file (YAML)
+        topology: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ClearTarget {
+    /// Flush routes of one source (dynamic by default)
+    Routes {
+        /// Route source to flush: dynamic or static
+        #[arg(short, long, default_value = "dynamic")]
+        source: String,
+    },
+    /// Forget a neighbor and the routes learned from it
+    Neighbor {
+        /// Neighbor address
+        ip: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum MaintenanceAction {
+    /// Advertise every route with a high metric while keeping adjacencies
+    Enter {
+        /// Metric to advertise (two below infinity by default; infinity
+        /// withdraws the routes)
+        #[arg(short, long)]
+        metric: Option<u32>,
+        /// Note recorded in the activity feed
+        #[arg(short, long)]
+        reason: Option<String>,
+    },
+    /// Restore normal metrics
+    Exit,
+    /// Show whether maintenance mode is on
+    Status,
+}
+
+#[derive(Subcommand)]
+pub enum InterfaceAction {
+    /// Resume advertising and receiving on an interface
+    Enable {
+        /// Interface name
+        name: String,
+        /// Configuration file path
+        #[arg(short, long, default_value = "rust-route.json")]
+        config: String,
+    },
+    /// Stop advertising and receiving on an interface and poison its routes
+    Disable {
+        /// Interface name
+        name: String,
+        /// Configuration file path
+        #[arg(short, long, default_value = "rust-route.json")]
+        config: String,
+    },
 }
 
 #[derive(Subcommand)]
@@ -57,6 +253,17 @@ pub enum ConfigAction {
         #[arg(short, long)]
         output: Option<String>,
     },
+    /// Translate another daemon's configuration into RustRoute format
+    Import {
+        /// Configuration file to translate
+        file: String,
+        /// Source syntax (frr)
+        #[arg(short, long, default_value = "frr")]
+        format: String,
+        /// Output file path
+        #[arg(short, long, default_value = "rust-route.json")]
+        output: String,
+    },
     /// Restore configuration from backup
     Restore {
         /// Backup file to restore from
@@ -124,15 +331,21 @@ impl CliFormatter {
 
     /// Print router statistics
     pub fn print_statistics(stats: &RouterStatistics) {
+        let locale = Locale::current();
+        let rows = [
+            (Text::Uptime, stats.uptime.clone()),
+            (Text::PacketsSent, stats.packets_sent.to_string()),
+            (Text::PacketsReceived, stats.packets_received.to_string()),
+            (Text::Routes, stats.route_count.to_string()),
+            (Text::Neighbors, stats.neighbor_count.to_string()),
+            (Text::Memory, format_bytes(stats.memory_usage)),
+        ];
         println!("┌─────────────────────────────────────┐");
-        println!("│            路由器统计信息             │");
+        println!("│ {:^35} │", locale.text(Text::StatisticsTitle));
         println!("├─────────────────────────────────────┤");
-        println!("│ 运行时间    : {:20} │", stats.uptime);
-        println!("│ 发送包数    : {:20} │", stats.packets_sent);
-        println!("│ 接收包数    : {:20} │", stats.packets_received);
-        println!("│ 路由表条目  : {:20} │", stats.route_count);
-        println!("│ 邻居数量    : {:20} │", stats.neighbor_count);
-        println!("│ 内存使用    : {:20} │", stats.memory_usage);
+        for (label, value) in rows {
+            println!("│ {:<16}: {:>18} │", locale.text(label), value);
+        }
         println!("└─────────────────────────────────────┘");
     }
 }
@@ -0,0 +1,131 @@
+//! HTTP client used by CLI verbs that act on a running router
+
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use crate::{RustRouteError, RustRouteResult};
+
+pub const DEFAULT_API_URL: &str = "http://127.0.0.1:8080";
+
+#[derive(Debug, Deserialize)]
+struct Envelope<T> {
+    success: bool,
+    data: Option<T>,
+    message: String,
+}
+
+/// Thin wrapper around the router's REST API
+#[derive(Debug, Clone)]
+pub struct ApiClient {
+    base_url: String,
+    token: Option<String>,
+    http: reqwest::Client,
+}
+
+impl ApiClient {
+    pub fn new(base_url: impl Into<String>, token: Option<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            token,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// POST to `path` and unwrap the `ApiResponse` envelope
+    pub async fn post<T: DeserializeOwned>(&self, path: &str) -> RustRouteResult<T> {
+        self.post_query(path, &[]).await
+    }
+
+    /// POST to `path` with URL-encoded query parameters
+    pub async fn post_query<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, String)],
+    ) -> RustRouteResult<T> {
+        let url = self.url(path);
+        let request = self.http.post(&url).query(query);
+        let response = self.send(request, &url, path).await?;
+        Self::unwrap(response, &url).await
+    }
+
+    /// GET `path` and unwrap the `ApiResponse` envelope
+    pub async fn get<T: DeserializeOwned>(&self, path: &str) -> RustRouteResult<T> {
+        let url = self.url(path);
+        let response = self.send(self.http.get(&url), &url, path).await?;
+        Self::unwrap(response, &url).await
+    }
+
+    /// DELETE `path` and unwrap the `ApiResponse` envelope
+    pub async fn delete<T: DeserializeOwned>(&self, path: &str) -> RustRouteResult<T> {
+        let url = self.url(path);
+        let response = self.send(self.http.delete(&url), &url, path).await?;
+        Self::unwrap(response, &url).await
+    }
+
+    async fn unwrap<T: DeserializeOwned>(
+        response: reqwest::Response,
+        url: &str,
+    ) -> RustRouteResult<T> {
+        let envelope: Envelope<T> = response.json().await.map_err(|e| {
+            RustRouteError::ProtocolError(format!("Invalid response from {}: {}", url, e))
+        })?;
+
+        match envelope.data {
+            Some(data) if envelope.success => Ok(data),
+            _ => Err(RustRouteError::RoutingError(envelope.message)),
+        }
+    }
+
+    /// GET a plain-text resource such as a config export
+    pub async fn get_text(&self, path: &str) -> RustRouteResult<String> {
+        self.get_text_query(path, &[]).await
+    }
+
+    /// GET a plain-text resource with URL-encoded query parameters
+    pub async fn get_text_query(
+        &self,
+        path: &str,
+        query: &[(&str, String)],
+    ) -> RustRouteResult<String> {
+        let url = self.url(path);
+        let request = self.http.get(&url).query(query);
+        let response = self.send(request, &url, path).await?;
+
+        response.text().await.map_err(|e| {
+            RustRouteError::ProtocolError(format!("Invalid response from {}: {}", url, e))
+        })
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    async fn send(
+        &self,
+        mut request: reqwest::RequestBuilder,
+        url: &str,
+        path: &str,
+    ) -> RustRouteResult<reqwest::Response> {
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+
+        let response = request.send().await.map_err(|e| {
+            RustRouteError::NetworkError(format!("Failed to reach router at {}: {}", url, e))
+        })?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        Err(match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => RustRouteError::InvalidInput(
+                format!("{} rejected the request ({}); check --token", url, status),
+            ),
+            StatusCode::NOT_FOUND => RustRouteError::InvalidInput(format!("{} not found", path)),
+            _ => RustRouteError::NetworkError(format!("{} returned {}", url, status)),
+        })
+    }
+}
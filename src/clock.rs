@@ -0,0 +1,190 @@
+//! Detection of steps of the system clock.
+//!
+//! Route, neighbor and update timers run on the monotonic clock and carry
+//! on unaffected when the system clock is set, but session tokens, backup
+//! names and every timestamp shown to operators come from the wall clock.
+//! A watcher compares how far both clocks advanced between checks; when
+//! they disagree by more than `clock.jump_threshold` seconds it logs a
+//! warning and posts an activity entry, so an NTP step or a board booting
+//! with a dead RTC battery does not silently log everyone out. Token
+//! validation tolerates `auth.token_leeway_seconds` of skew on top.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::events::{ActivityLevel, EventBus};
+use crate::watchdog::Heartbeat;
+
+static STATUS: Mutex<ClockStatus> = Mutex::new(ClockStatus {
+    jumps: 0,
+    last_jump: None,
+});
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClockConfig {
+    /// Seconds between comparisons of the two clocks
+    pub check_interval: u64,
+    /// Seconds of disagreement reported as a jump; 0 turns detection off
+    pub jump_threshold: u64,
+}
+
+impl Default for ClockConfig {
+    fn default() -> Self {
+        Self {
+            check_interval: 10,
+            jump_threshold: 30,
+        }
+    }
+}
+
+impl ClockConfig {
+    /// Problems with the settings, as configuration errors
+    pub fn errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.jump_threshold > 0 && self.check_interval == 0 {
+            errors.push("clock.check_interval must be at least 1".to_string());
+        }
+        errors
+    }
+}
+
+/// A step of the wall clock against the monotonic clock
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClockJump {
+    /// Seconds the wall clock moved beyond the time that passed; negative
+    /// when it went back
+    pub offset_seconds: i64,
+    /// Wall-clock time after the jump
+    pub detected_at: DateTime<Utc>,
+}
+
+impl fmt::Display for ClockJump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "System clock jumped {} by {}s; session expiry and timestamps follow the new time",
+            if self.offset_seconds < 0 {
+                "back"
+            } else {
+                "forward"
+            },
+            self.offset_seconds.unsigned_abs()
+        )
+    }
+}
+
+/// Jumps seen since the router started, for `GET /api/status`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClockStatus {
+    pub jumps: u64,
+    pub last_jump: Option<ClockJump>,
+}
+
+pub fn status() -> ClockStatus {
+    STATUS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone()
+}
+
+/// Readings of both clocks at the last check
+#[derive(Debug, Clone, Copy)]
+pub struct ClockWatch {
+    monotonic: Instant,
+    wall: DateTime<Utc>,
+}
+
+impl ClockWatch {
+    pub fn new(monotonic: Instant, wall: DateTime<Utc>) -> Self {
+        Self { monotonic, wall }
+    }
+
+    /// The jump since the last check if the clocks disagree by more than
+    /// `threshold`; the readings become the reference for the next check
+    pub fn check(
+        &mut self,
+        monotonic: Instant,
+        wall: DateTime<Utc>,
+        threshold: Duration,
+    ) -> Option<ClockJump> {
+        let elapsed = monotonic.saturating_duration_since(self.monotonic);
+        let elapsed = chrono::Duration::from_std(elapsed).unwrap_or_default();
+        let drift = (wall - self.wall) - elapsed;
+        *self = Self::new(monotonic, wall);
+
+        let threshold = chrono::Duration::from_std(threshold).unwrap_or_default();
+        (drift.abs() > threshold).then(|| ClockJump {
+            offset_seconds: drift.num_seconds(),
+            detected_at: wall,
+        })
+    }
+}
+
+/// Compare the clocks every `config.check_interval` seconds, reporting jumps
+pub async fn watch(config: ClockConfig, events: EventBus, heartbeat: Heartbeat) {
+    let threshold = Duration::from_secs(config.jump_threshold);
+    let mut interval = tokio::time::interval(Duration::from_secs(config.check_interval.max(1)));
+    let mut clocks = ClockWatch::new(Instant::now(), Utc::now());
+    loop {
+        interval.tick().await;
+        heartbeat.beat();
+        if threshold.is_zero() {
+            continue;
+        }
+
+        let Some(jump) = clocks.check(Instant::now(), Utc::now(), threshold) else {
+            continue;
+        };
+        log::warn!("{}", jump);
+        events.publish_activity(ActivityLevel::Warn, jump.to_string());
+        let mut status = STATUS
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        status.jumps += 1;
+        status.last_jump = Some(jump);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_disagreements_beyond_the_threshold_are_jumps() {
+        let start = Instant::now();
+        let wall = Utc::now();
+        let threshold = Duration::from_secs(30);
+        let mut clocks = ClockWatch::new(start, wall);
+
+        // Both clocks advanced alike, give or take some NTP slewing
+        let later = start + Duration::from_secs(10);
+        assert!(clocks
+            .check(later, wall + chrono::Duration::seconds(12), threshold)
+            .is_none());
+
+        // Set an hour ahead
+        let jump = clocks
+            .check(
+                later + Duration::from_secs(10),
+                wall + chrono::Duration::seconds(3622),
+                threshold,
+            )
+            .unwrap();
+        assert_eq!(jump.offset_seconds, 3600);
+        assert!(jump.to_string().contains("forward by 3600s"));
+
+        // And back again
+        let jump = clocks
+            .check(
+                later + Duration::from_secs(20),
+                wall + chrono::Duration::seconds(32),
+                threshold,
+            )
+            .unwrap();
+        assert_eq!(jump.offset_seconds, -3600);
+    }
+}
@@ -0,0 +1,191 @@
+//! Route table comparison between two routers.
+//!
+//! `rust-route compare --peer` and `GET /api/routes/compare?peer=` fetch the
+//! peer's table through its API and line it up with ours by prefix. Prefixes
+//! known to one side only, or with a different metric or next hop, are the
+//! usual traces of a filter or a split horizon mistake.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::client::ApiClient;
+use crate::web::RouteInfo;
+use crate::{RustRouteError, RustRouteResult};
+
+/// A route as seen by one of the two routers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ComparedRoute {
+    pub next_hop: String,
+    pub metric: u32,
+    pub interface: String,
+}
+
+impl From<&RouteInfo> for ComparedRoute {
+    fn from(route: &RouteInfo) -> Self {
+        Self {
+            next_hop: route.next_hop.clone(),
+            metric: route.metric,
+            interface: route.interface.clone(),
+        }
+    }
+}
+
+/// A prefix present on one side only
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OneSidedRoute {
+    pub prefix: String,
+    #[serde(flatten)]
+    pub route: ComparedRoute,
+}
+
+/// A prefix both routers know, with what differs between them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RouteDifference {
+    pub prefix: String,
+    pub local: ComparedRoute,
+    pub peer: ComparedRoute,
+    pub metric_differs: bool,
+    pub next_hop_differs: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RouteComparison {
+    pub peer: String,
+    /// Prefixes both routers have with the same metric and next hop
+    pub matching: usize,
+    pub only_local: Vec<OneSidedRoute>,
+    pub only_peer: Vec<OneSidedRoute>,
+    pub differing: Vec<RouteDifference>,
+}
+
+impl RouteComparison {
+    /// Whether both tables hold the same routes
+    pub fn is_identical(&self) -> bool {
+        self.only_local.is_empty() && self.only_peer.is_empty() && self.differing.is_empty()
+    }
+}
+
+fn prefix_length(mask: &str) -> Option<u32> {
+    mask.parse::<std::net::Ipv4Addr>()
+        .ok()
+        .map(|mask| u32::from(mask).count_ones())
+}
+
+/// `destination/length`, or `destination/mask` if the mask does not parse
+fn prefix(route: &RouteInfo) -> String {
+    match prefix_length(&route.subnet_mask) {
+        Some(length) => format!("{}/{}", route.destination, length),
+        None => format!("{}/{}", route.destination, route.subnet_mask),
+    }
+}
+
+fn by_prefix(routes: &[RouteInfo]) -> BTreeMap<String, ComparedRoute> {
+    routes
+        .iter()
+        .map(|route| (prefix(route), ComparedRoute::from(route)))
+        .collect()
+}
+
+/// Line up `local` and the routes of `peer`, ordered by prefix
+pub fn compare(local: &[RouteInfo], peer_routes: &[RouteInfo], peer: &str) -> RouteComparison {
+    let local = by_prefix(local);
+    let mut remote = by_prefix(peer_routes);
+    let mut comparison = RouteComparison {
+        peer: peer.to_string(),
+        ..RouteComparison::default()
+    };
+
+    for (prefix, route) in local {
+        let Some(other) = remote.remove(&prefix) else {
+            comparison.only_local.push(OneSidedRoute { prefix, route });
+            continue;
+        };
+        let metric_differs = route.metric != other.metric;
+        let next_hop_differs = route.next_hop != other.next_hop;
+        if metric_differs || next_hop_differs {
+            comparison.differing.push(RouteDifference {
+                prefix,
+                local: route,
+                peer: other,
+                metric_differs,
+                next_hop_differs,
+            });
+        } else {
+            comparison.matching += 1;
+        }
+    }
+    comparison.only_peer = remote
+        .into_iter()
+        .map(|(prefix, route)| OneSidedRoute { prefix, route })
+        .collect();
+    comparison
+}
+
+/// Only plain HTTP(S) base URLs are fetched from
+pub fn validate_peer_url(peer: &str) -> RustRouteResult<()> {
+    if peer.starts_with("http://") || peer.starts_with("https://") {
+        Ok(())
+    } else {
+        Err(RustRouteError::InvalidInput(format!(
+            "Peer {} is not an http:// or https:// URL",
+            peer
+        )))
+    }
+}
+
+/// The route table of the router whose API is at `client`, without reverse
+/// DNS names
+pub async fn fetch_routes(client: &ApiClient) -> RustRouteResult<Vec<RouteInfo>> {
+    client.get("/api/routes?resolve=false").await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routing_table::RouteSource;
+
+    fn route(destination: &str, mask: &str, next_hop: &str, metric: u32) -> RouteInfo {
+        RouteInfo {
+            destination: destination.to_string(),
+            subnet_mask: mask.to_string(),
+            next_hop: next_hop.to_string(),
+            metric,
+            interface: "eth0".to_string(),
+            age_seconds: 0,
+            source: RouteSource::Dynamic,
+            learned_from: None,
+            next_hop_name: None,
+            learned_from_name: None,
+            learned_at: None,
+            last_updated: None,
+            next_hops: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn reports_one_sided_and_differing_prefixes() {
+        let local = vec![
+            route("10.0.0.0", "255.255.255.0", "0.0.0.0", 1),
+            route("10.1.0.0", "255.255.0.0", "192.0.2.2", 2),
+            route("10.2.0.0", "255.255.0.0", "192.0.2.2", 3),
+        ];
+        let peer = vec![
+            route("10.0.0.0", "255.255.255.0", "0.0.0.0", 1),
+            route("10.1.0.0", "255.255.0.0", "192.0.2.2", 4),
+            route("172.16.0.0", "255.240.0.0", "192.0.2.9", 2),
+        ];
+
+        let comparison = compare(&local, &peer, "http://router-b:8080");
+        assert!(!comparison.is_identical());
+        assert_eq!(comparison.matching, 1);
+        assert_eq!(comparison.only_local.len(), 1);
+        assert_eq!(comparison.only_local[0].prefix, "10.2.0.0/16");
+        assert_eq!(comparison.only_peer.len(), 1);
+        assert_eq!(comparison.only_peer[0].prefix, "172.16.0.0/12");
+        assert_eq!(comparison.differing.len(), 1);
+        let difference = &comparison.differing[0];
+        assert_eq!(difference.prefix, "10.1.0.0/16");
+        assert!(difference.metric_differs);
+        assert!(!difference.next_hop_differs);
+    }
+}
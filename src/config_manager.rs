@@ -2,19 +2,58 @@ use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
 use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use serde::{Deserialize, Serialize};
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, HashSet, VecDeque};
 use std::hash::{Hash, Hasher};
+use std::net::Ipv4Addr;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::{watch, RwLock};
 
 use log::warn;
 
+use crate::activity::ActivityLogConfig;
 use crate::auth::AuthConfig;
+use crate::clock::ClockConfig;
+use crate::decisions::DecisionTraceConfig;
+use crate::dns::ReverseDnsConfig;
+use crate::dump::DumpConfig;
+use crate::events::{EventBusConfig, OverflowPolicy};
+use crate::fleet::FleetConfig;
+use crate::gnmi::GnmiConfig;
+use crate::hooks::HooksConfig;
 use crate::ipv6::RipV6Config;
+use crate::last_error;
+use crate::link_cost::DEFAULT_REFERENCE_BANDWIDTH_MBPS;
+use crate::listen;
+use crate::locale::Locale;
+use crate::metrics_history::MetricsHistoryConfig;
+use crate::mqtt::MqttConfig;
+use crate::network::{PacingConfig, SendMode, SenderFilter};
+use crate::packet_auth::PacketAuthConfig;
+use crate::plugins::{PluginRegistry, PluginsConfig};
+use crate::policy::{Direction, PolicyConfig, PrefixRestriction, RoutePolicy};
+use crate::preflight::PreflightConfig;
+use crate::probe::ProbeConfig;
+use crate::protocol::PacketCodec;
+use crate::redistribute::RedistributeConfig;
+use crate::rip_auth::InterfaceAuthConfig;
+use crate::routing_table::{
+    SplitHorizon, TieBreak, DEFAULT_DISTANCE, DEFAULT_MAX_PATHS, DEFAULT_ROUTE_HISTORY,
+};
+use crate::runtime::RuntimeConfig;
+use crate::session::SameSite;
+use crate::snmp::SnmpConfig;
+use crate::storage::{StorageBackend, StorageConfig};
+use crate::streaming::EventStreamConfig;
+use crate::timers::{SlowPrefix, DEFAULT_GARBAGE_TIMEOUT, DEFAULT_ROUTE_TIMEOUT};
+use crate::topology::TopologyConfig;
+use crate::tunnel::TunnelConfig;
+use crate::watchdog::WatchdogConfig;
 use crate::web::WebConfig;
 
 const DEFAULT_HISTORY_LIMIT: usize = 20;
+/// Prefix of the environment variables read in ephemeral mode
+pub const ENV_PREFIX: &str = "RUST_ROUTE_";
 
 /// Main configuration structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,8 +65,73 @@ pub struct RouterConfig {
     pub web: WebConfig,
     pub auth: AuthConfig,
     pub logging: LoggingConfig,
+    /// Language of human-readable output
+    #[serde(default)]
+    pub locale: Locale,
     pub metrics: MetricsConfig,
     pub backup: BackupConfig,
+    #[serde(default)]
+    pub watchdog: WatchdogConfig,
+    #[serde(default)]
+    pub snmp: SnmpConfig,
+    #[serde(default)]
+    pub gnmi: GnmiConfig,
+    #[serde(default)]
+    pub mqtt: MqttConfig,
+    #[serde(default)]
+    pub event_bus: EventBusConfig,
+    /// Thread pools and CPU affinity, read at startup
+    #[serde(default)]
+    pub runtime: RuntimeConfig,
+    /// Where accounts, the activity feed and the metrics history are kept
+    #[serde(default)]
+    pub storage: StorageConfig,
+    #[serde(default)]
+    pub activity_log: ActivityLogConfig,
+    /// Diagnostic dumps written on SIGUSR1 or `POST /api/debug/dump`
+    #[serde(default)]
+    pub dump: DumpConfig,
+    /// Peers aggregated by the `/api/fleet/*` endpoints
+    #[serde(default)]
+    pub fleet: FleetConfig,
+    /// Detection of system clock jumps, read at startup
+    #[serde(default)]
+    pub clock: ClockConfig,
+    /// Environment checks run before startup
+    #[serde(default)]
+    pub preflight: PreflightConfig,
+    /// Reachability probes to learned next hops
+    #[serde(default)]
+    pub probes: ProbeConfig,
+    /// Names for neighbor and next hop addresses in API responses
+    #[serde(default)]
+    pub reverse_dns: ReverseDnsConfig,
+    /// Consolidated `TopologyChanged` events
+    #[serde(default)]
+    pub topology: TopologyConfig,
+    #[serde(default)]
+    pub event_stream: EventStreamConfig,
+    #[serde(default)]
+    pub hooks: HooksConfig,
+    #[serde(default)]
+    pub policy: PolicyConfig,
+    #[serde(default)]
+    pub plugins: PluginsConfig,
+    #[serde(default)]
+    pub tunnels: Vec<TunnelConfig>,
+    /// Routes installed with the connected routes and kept across restarts,
+    /// unlike those added through the API
+    #[serde(default)]
+    pub static_routes: Vec<StaticRouteConfig>,
+    /// Named variants of this configuration, e.g. `lab`, `demo`, `exam`.
+    /// Each is a partial configuration laid over the current one on
+    /// activation: objects are merged, anything else is replaced, so a
+    /// profile should set every section it changes.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, serde_json::Value>,
+    /// Profile activated last, with `--profile` or through the API
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_profile: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,7 +139,108 @@ pub struct InterfaceConfig {
     pub name: String,
     pub address: String,
     pub enabled: bool,
+    /// Added to the metric of routes received on the interface
     pub cost: u32,
+    /// Derive the cost from the link speed the OS reports instead, falling
+    /// back to `cost` while the speed is unknown
+    #[serde(default)]
+    pub auto_cost: bool,
+    /// Listen for updates but never send periodic advertisements
+    #[serde(default)]
+    pub passive: bool,
+    /// Point-to-point link such as WireGuard or GRE; implied by /31 and /32 addresses
+    #[serde(default)]
+    pub point_to_point: bool,
+    /// Far end of a point-to-point link, which receives updates by unicast.
+    /// Defaults to the other address of a /31.
+    #[serde(default)]
+    pub peer: Option<Ipv4Addr>,
+    /// Encoding for broadcasts and for neighbors not heard from yet; replies
+    /// follow whatever encoding each neighbor uses
+    #[serde(default)]
+    pub codec: PacketCodec,
+    /// Caps the size of sent packets; defaults to the MTU the OS reports
+    #[serde(default)]
+    pub mtu: Option<u16>,
+    /// Further addresses on the same link; their subnets are advertised and
+    /// packets to them accepted
+    #[serde(default)]
+    pub secondary_addresses: Vec<ipnet::Ipv4Net>,
+    /// `"multicast"` to 224.0.0.9, `"broadcast"` for neighbors that only
+    /// listen to broadcasts, or `{"unicast": [addresses]}`
+    #[serde(default)]
+    pub send_mode: SendMode,
+    /// Only process packets from senders in these networks, when non-empty
+    #[serde(default)]
+    pub accept_from: Vec<ipnet::Ipv4Net>,
+    /// Never process packets from senders in these networks
+    #[serde(default)]
+    pub reject_from: Vec<ipnet::Ipv4Net>,
+    /// Limits on the rate updates and other packets go out at
+    #[serde(default)]
+    pub pacing: PacingConfig,
+    /// Advertise the interface's own subnets; turn off to exchange routes on a
+    /// link, such as a management network, without leaking it
+    #[serde(default = "default_advertise_connected")]
+    pub advertise_connected: bool,
+}
+
+fn default_advertise_connected() -> bool {
+    true
+}
+
+/// A static route under `static_routes`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StaticRouteConfig {
+    pub prefix: ipnet::Ipv4Net,
+    pub next_hop: Ipv4Addr,
+    /// Interface or tunnel the next hop is reached through; the route is
+    /// only installed while it is enabled
+    pub interface: String,
+    #[serde(default = "default_static_metric")]
+    pub metric: u32,
+    /// Advertise the route to RIP neighbors
+    #[serde(default = "default_advertise_static")]
+    pub advertise: bool,
+}
+
+fn default_static_metric() -> u32 {
+    1
+}
+
+fn default_advertise_static() -> bool {
+    true
+}
+
+impl InterfaceConfig {
+    pub fn sender_filter(&self) -> SenderFilter {
+        SenderFilter {
+            accept_from: self.accept_from.clone(),
+            reject_from: self.reject_from.clone(),
+        }
+    }
+
+    pub fn ipv4_net(&self) -> Option<ipnet::Ipv4Net> {
+        match self.address.trim().parse::<ipnet::IpNet>() {
+            Ok(ipnet::IpNet::V4(net)) => Some(net),
+            _ => None,
+        }
+    }
+
+    pub fn is_point_to_point(&self) -> bool {
+        self.point_to_point
+            || self.peer.is_some()
+            || self.ipv4_net().is_some_and(|net| net.prefix_len() >= 31)
+    }
+
+    /// Unicast destination for updates on point-to-point links
+    pub fn link_peer(&self) -> Option<Ipv4Addr> {
+        if self.peer.is_some() {
+            return self.peer;
+        }
+        let net = self.ipv4_net()?;
+        (net.prefix_len() == 31).then(|| Ipv4Addr::from(u32::from(net.addr()) ^ 1))
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -43,10 +248,134 @@ pub struct RipConfig {
     pub enabled: bool,
     pub port: u16,
     pub update_interval: u64,
+    /// Seconds without a refresh before a learned route becomes unreachable
+    #[serde(default = "default_route_timeout")]
+    pub route_timeout: u64,
+    /// Seconds an unreachable route is kept before it is flushed
     pub garbage_collection_timeout: u64,
+    /// Seconds after a route times out during which only its original
+    /// neighbor may revive it; 0 disables hold-down
+    #[serde(default)]
+    pub holddown: u64,
     pub infinity_metric: u32,
     pub split_horizon: bool,
     pub poison_reverse: bool,
+    #[serde(default)]
+    pub packet_auth: PacketAuthConfig,
+    #[serde(default)]
+    pub next_hop_validation: NextHopValidationConfig,
+    /// Choice between equal-metric routes from different neighbors
+    #[serde(default)]
+    pub tie_break: TieBreak,
+    /// Link speed at and above which an interface with `auto_cost` costs 1;
+    /// each tenfold step below it costs one more
+    #[serde(default = "default_reference_bandwidth")]
+    pub reference_bandwidth_mbps: u64,
+    /// Equal-cost paths kept per prefix when `tie_break` is `ecmp`
+    #[serde(default = "default_max_paths")]
+    pub max_paths: usize,
+    /// Metric changes kept per prefix for `GET /api/routes/{prefix}/history`;
+    /// 0 turns the history off
+    #[serde(default = "default_route_history")]
+    pub route_history: usize,
+    /// Adjustments to routes learned from particular neighbors
+    #[serde(default)]
+    pub neighbors: Vec<NeighborConfig>,
+    /// Prefixes advertised only to the neighbors listed with them
+    #[serde(default)]
+    pub restricted_prefixes: Vec<PrefixRestriction>,
+    /// Seconds after startup during which routes are learned but nothing is
+    /// advertised, so peers never see a half-learned table
+    #[serde(default)]
+    pub startup_quiet_period: u64,
+    /// Prefixes left out of most periodic updates, e.g. large aggregates on
+    /// slow links
+    #[serde(default)]
+    pub slow_prefixes: Vec<SlowPrefix>,
+    /// Key chains authenticating the binary packets of particular interfaces
+    #[serde(default)]
+    pub authentication: Vec<InterfaceAuthConfig>,
+    /// Install learned routes in the kernel routing table (Linux only), so
+    /// traffic follows them; needs CAP_NET_ADMIN
+    #[serde(default)]
+    pub install_kernel_routes: bool,
+    /// Record why each received entry was accepted or rejected, for
+    /// `GET /api/debug/decisions`
+    #[serde(default)]
+    pub decision_trace: DecisionTraceConfig,
+    /// Advertise kernel routes of other sources and the subnets of
+    /// interfaces RIP does not run on (Linux only)
+    #[serde(default)]
+    pub redistribute: RedistributeConfig,
+}
+
+impl RipConfig {
+    pub fn split_horizon_mode(&self) -> SplitHorizon {
+        SplitHorizon::from_flags(self.split_horizon, self.poison_reverse)
+    }
+
+    /// The key chain of the interface named `interface`, if it has one
+    pub fn authentication_for(&self, interface: &str) -> Option<&InterfaceAuthConfig> {
+        self.authentication
+            .iter()
+            .find(|auth| auth.interface == interface)
+    }
+}
+
+/// Preference for the routes learned from one neighbor, e.g. to keep a
+/// backup peer's routes out of the table while the primary peer has them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NeighborConfig {
+    pub address: Ipv4Addr,
+    /// Added to the metric of every route learned from the neighbor
+    #[serde(default)]
+    pub metric_offset: u32,
+    /// Administrative distance of the neighbor's routes; a route with a lower
+    /// distance wins over any metric
+    #[serde(default = "default_distance")]
+    pub distance: u8,
+}
+
+fn default_distance() -> u8 {
+    DEFAULT_DISTANCE
+}
+
+fn default_reference_bandwidth() -> u64 {
+    DEFAULT_REFERENCE_BANDWIDTH_MBPS
+}
+
+fn default_max_paths() -> usize {
+    DEFAULT_MAX_PATHS
+}
+
+fn default_route_history() -> usize {
+    DEFAULT_ROUTE_HISTORY
+}
+
+fn default_route_timeout() -> u64 {
+    DEFAULT_ROUTE_TIMEOUT.as_secs()
+}
+
+/// Handling of a received next hop that is not on the receiving interface's subnet
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NextHopAction {
+    /// Route through the advertising neighbor instead, as RFC 2453 prescribes
+    #[default]
+    Fallback,
+    /// Drop the entry
+    Reject,
+    /// Install the next hop as received, logging and counting it
+    Flag,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NextHopValidationConfig {
+    #[serde(default)]
+    pub action: NextHopAction,
+    /// Also require a resolved entry in the kernel ARP cache (Linux only)
+    #[serde(default)]
+    pub require_arp: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -65,6 +394,9 @@ pub struct MetricsConfig {
     pub retention_days: u32,
     pub export_prometheus: bool,
     pub prometheus_port: u16,
+    /// Persistent samples for `export-metrics`
+    #[serde(default)]
+    pub history: MetricsHistoryConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -90,6 +422,16 @@ pub struct ConfigHistoryEntry {
     pub timestamp: DateTime<Utc>,
 }
 
+/// A change applied with a confirmation window; unless confirmed by
+/// `confirm_by`, the configuration from before it is restored
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingConfirm {
+    pub version: u32,
+    /// Version that was running before the unconfirmed changes
+    pub rollback_version: u32,
+    pub confirm_by: DateTime<Utc>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConfigDiff {
     pub version: u32,
@@ -107,15 +449,43 @@ impl Default for RouterConfig {
                 address: "192.168.1.1/24".to_string(),
                 enabled: true,
                 cost: 1,
+                auto_cost: false,
+                passive: false,
+                point_to_point: false,
+                peer: None,
+                codec: PacketCodec::default(),
+                mtu: None,
+                secondary_addresses: Vec::new(),
+                send_mode: SendMode::default(),
+                accept_from: Vec::new(),
+                reject_from: Vec::new(),
+                pacing: PacingConfig::default(),
+                advertise_connected: true,
             }],
             rip: RipConfig {
                 enabled: true,
                 port: 520,
                 update_interval: 30,
-                garbage_collection_timeout: 120,
+                route_timeout: DEFAULT_ROUTE_TIMEOUT.as_secs(),
+                garbage_collection_timeout: DEFAULT_GARBAGE_TIMEOUT.as_secs(),
+                holddown: 0,
                 infinity_metric: 16,
                 split_horizon: true,
                 poison_reverse: false,
+                packet_auth: PacketAuthConfig::default(),
+                next_hop_validation: NextHopValidationConfig::default(),
+                tie_break: TieBreak::default(),
+                reference_bandwidth_mbps: DEFAULT_REFERENCE_BANDWIDTH_MBPS,
+                max_paths: DEFAULT_MAX_PATHS,
+                route_history: DEFAULT_ROUTE_HISTORY,
+                neighbors: Vec::new(),
+                restricted_prefixes: Vec::new(),
+                slow_prefixes: Vec::new(),
+                startup_quiet_period: 0,
+                authentication: Vec::new(),
+                install_kernel_routes: false,
+                decision_trace: DecisionTraceConfig::default(),
+                redistribute: RedistributeConfig::default(),
             },
             ripv6: RipV6Config::default(),
             web: WebConfig::default(),
@@ -127,12 +497,14 @@ impl Default for RouterConfig {
                 max_files: 5,
                 console_output: true,
             },
+            locale: Locale::default(),
             metrics: MetricsConfig {
                 enabled: true,
                 collection_interval: 60,
                 retention_days: 30,
                 export_prometheus: false,
                 prometheus_port: 9090,
+                history: MetricsHistoryConfig::default(),
             },
             backup: BackupConfig {
                 enabled: true,
@@ -142,8 +514,205 @@ impl Default for RouterConfig {
                 include_routing_table: true,
                 compress: true,
             },
+            watchdog: WatchdogConfig::default(),
+            snmp: SnmpConfig::default(),
+            gnmi: GnmiConfig::default(),
+            mqtt: MqttConfig::default(),
+            event_bus: EventBusConfig::default(),
+            runtime: RuntimeConfig::default(),
+            storage: StorageConfig::default(),
+            activity_log: ActivityLogConfig::default(),
+            dump: DumpConfig::default(),
+            fleet: FleetConfig::default(),
+            clock: ClockConfig::default(),
+            preflight: PreflightConfig::default(),
+            probes: ProbeConfig::default(),
+            reverse_dns: ReverseDnsConfig::default(),
+            topology: TopologyConfig::default(),
+            event_stream: EventStreamConfig::default(),
+            hooks: HooksConfig::default(),
+            policy: PolicyConfig::default(),
+            plugins: PluginsConfig::default(),
+            tunnels: Vec::new(),
+            static_routes: Vec::new(),
+            profiles: BTreeMap::new(),
+            active_profile: None,
+        }
+    }
+}
+
+impl RouterConfig {
+    /// The static routes to install: those through an enabled interface or
+    /// a tunnel
+    pub fn active_static_routes(&self) -> impl Iterator<Item = &StaticRouteConfig> {
+        self.static_routes.iter().filter(|route| {
+            self.interfaces
+                .iter()
+                .find(|iface| iface.name == route.interface)
+                .map_or_else(
+                    || {
+                        self.tunnels
+                            .iter()
+                            .any(|tunnel| tunnel.name == route.interface)
+                    },
+                    |iface| iface.enabled,
+                )
+        })
+    }
+
+    /// Apply `(path, value)` settings such as `("rip.port", "5520")`. Path
+    /// segments name fields, or index lists such as `interfaces.0.cost`.
+    /// Values are JSON, except that strings may be given bare. A last
+    /// segment ending in `_file` that is not a field itself reads the value
+    /// of the field without the suffix from that file, so secrets can come
+    /// from mounted files: `auth.jwt_secret_file=/run/secrets/jwt`.
+    pub fn with_settings(&self, settings: &[(String, String)]) -> Result<RouterConfig> {
+        let mut value = serde_json::to_value(self).context("Failed to serialize config")?;
+        for (path, raw) in settings {
+            let segments: Vec<&str> = path.split('.').collect();
+            set_setting(&mut value, &segments, raw)
+                .with_context(|| format!("Invalid setting {}", path))?;
+        }
+        serde_json::from_value(value).context("Settings do not form a valid configuration")
+    }
+
+    /// This configuration with profile `name` laid over it
+    pub fn with_profile(&self, name: &str) -> Result<RouterConfig> {
+        let profile = self
+            .profiles
+            .get(name)
+            .with_context(|| format!("No profile named {}", name))?;
+        let serde_json::Value::Object(overlay) = profile else {
+            anyhow::bail!("Profile {} is not an object", name);
+        };
+        if overlay.contains_key("profiles") || overlay.contains_key("active_profile") {
+            anyhow::bail!("Profile {} may not define profiles itself", name);
+        }
+
+        let mut value = serde_json::to_value(self).context("Failed to serialize config")?;
+        merge_json(&mut value, profile);
+        let mut config: RouterConfig = serde_json::from_value(value)
+            .with_context(|| format!("Profile {} does not form a valid configuration", name))?;
+        config.active_profile = Some(name.to_string());
+        Ok(config)
+    }
+}
+
+/// Lay `overlay` over `base`: objects are merged key by key, other values
+/// replace what was there
+fn merge_json(base: &mut serde_json::Value, overlay: &serde_json::Value) {
+    use serde_json::Value;
+
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(key) {
+                    Some(existing) => merge_json(existing, value),
+                    None => {
+                        base.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay.clone(),
+    }
+}
+
+/// Settings named by `RUST_ROUTE_<PATH>` variables among `vars`, for
+/// [`RouterConfig::with_settings`]. Segments of the path are separated by
+/// `__`, so `RUST_ROUTE_RIP__PORT` sets `rip.port`; variables whose first
+/// segment is not a configuration section, such as `RUST_ROUTE_SIM_SEED`,
+/// are left alone.
+pub fn env_settings(vars: impl IntoIterator<Item = (String, String)>) -> Vec<(String, String)> {
+    let sections = match serde_json::to_value(RouterConfig::default()) {
+        Ok(serde_json::Value::Object(map)) => map,
+        _ => return Vec::new(),
+    };
+    let mut settings: Vec<(String, String)> = vars
+        .into_iter()
+        .filter_map(|(name, value)| {
+            let path = name.strip_prefix(ENV_PREFIX)?.to_ascii_lowercase();
+            let section = path.split("__").next()?;
+            let known = sections.contains_key(section)
+                || section
+                    .strip_suffix("_file")
+                    .is_some_and(|field| sections.contains_key(field));
+            known.then(|| (path.replace("__", "."), value))
+        })
+        .collect();
+    // List entries in index order, whatever order the environment has
+    settings.sort();
+    settings
+}
+
+/// `config` with everything that writes to disk turned off
+fn without_persistence(mut config: RouterConfig) -> RouterConfig {
+    config.storage.backend = StorageBackend::Memory;
+    config.activity_log.enabled = false;
+    config.backup.enabled = false;
+    config.logging.file_path = None;
+    if config.event_bus.overflow == OverflowPolicy::Spill {
+        config.event_bus.overflow = OverflowPolicy::DropOldest;
+    }
+    config
+}
+
+fn set_setting(root: &mut serde_json::Value, path: &[&str], raw: &str) -> Result<()> {
+    use serde_json::Value;
+
+    let (last, parents) = path.split_last().context("Empty setting name")?;
+    let mut node = root;
+    for segment in parents {
+        node = match node {
+            Value::Object(map) => map.get_mut(*segment),
+            Value::Array(items) => segment
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| items.get_mut(index)),
+            _ => None,
+        }
+        .with_context(|| format!("No setting named {}", segment))?;
+    }
+
+    let parse = |existing: Option<&Value>| match existing {
+        // String fields take the value verbatim, even when it looks numeric
+        Some(Value::String(_)) => Value::String(raw.to_string()),
+        _ => serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string())),
+    };
+    match node {
+        Value::Object(map) if map.contains_key(*last) => {
+            let value = parse(map.get(*last));
+            map.insert(last.to_string(), value);
+        }
+        Value::Object(map) => {
+            let field = last
+                .strip_suffix("_file")
+                .filter(|field| map.contains_key(*field))
+                .with_context(|| format!("No setting named {}", last))?;
+            let secret =
+                std::fs::read_to_string(raw).with_context(|| format!("Failed to read {}", raw))?;
+            let secret = secret.trim_end_matches(['\r', '\n']).to_string();
+            map.insert(field.to_string(), Value::String(secret));
         }
+        Value::Array(items) => {
+            let index: usize = last
+                .parse()
+                .with_context(|| format!("{} is not a list index", last))?;
+            let value = parse(items.get(index));
+            match index.cmp(&items.len()) {
+                std::cmp::Ordering::Less => items[index] = value,
+                std::cmp::Ordering::Equal => items.push(value),
+                std::cmp::Ordering::Greater => {
+                    return Err(anyhow::anyhow!(
+                        "Index {} is past the end of the list",
+                        index
+                    ))
+                }
+            }
+        }
+        _ => return Err(anyhow::anyhow!("{} is not a section", parents.join("."))),
     }
+    Ok(())
 }
 
 /// Configuration validation result
@@ -177,6 +746,12 @@ impl ValidationResult {
     }
 }
 
+impl Default for ValidationResult {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Configuration backup metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BackupMetadata {
@@ -190,13 +765,16 @@ pub struct BackupMetadata {
 
 /// Configuration manager with hot-reload support
 pub struct ConfigManager {
-    config_path: PathBuf,
+    /// Where changes are saved; `None` in ephemeral mode
+    config_path: Option<PathBuf>,
     current_config: Arc<RwLock<RouterConfig>>,
     config_version: Arc<RwLock<u32>>,
     change_sender: watch::Sender<RouterConfig>,
     history: Arc<RwLock<VecDeque<ConfigSnapshot>>>,
     history_limit: usize,
-    _watcher: RecommendedWatcher,
+    /// Unconfirmed change and the configuration to restore without it
+    pending: RwLock<Option<(PendingConfirm, RouterConfig)>>,
+    _watcher: Option<RecommendedWatcher>,
 }
 
 impl ConfigManager {
@@ -235,18 +813,86 @@ impl ConfigManager {
         )?;
 
         let manager = Self {
-            config_path,
+            config_path: Some(config_path),
             current_config,
             config_version,
             change_sender,
             history,
             history_limit: DEFAULT_HISTORY_LIMIT,
-            _watcher: watcher,
+            pending: RwLock::new(None),
+            _watcher: Some(watcher),
         };
 
         Ok((manager, change_receiver))
     }
 
+    /// A manager that never writes to disk: `config` is kept in memory only,
+    /// changes made through the API last until the process exits, and the
+    /// activity log, backups and log file are turned off
+    pub fn ephemeral(config: RouterConfig) -> Result<(Self, watch::Receiver<RouterConfig>)> {
+        let config = without_persistence(config);
+        let validation = Self::validate_config(&config);
+        if !validation.is_valid() {
+            return Err(anyhow::anyhow!(
+                "Configuration validation failed: {:?}",
+                validation.errors
+            ));
+        }
+
+        let history = VecDeque::from([ConfigSnapshot {
+            version: 1,
+            timestamp: Utc::now(),
+            config: config.clone(),
+        }]);
+        let (change_sender, change_receiver) = watch::channel(config.clone());
+        let manager = Self {
+            config_path: None,
+            current_config: Arc::new(RwLock::new(config)),
+            config_version: Arc::new(RwLock::new(1)),
+            change_sender,
+            history: Arc::new(RwLock::new(history)),
+            history_limit: DEFAULT_HISTORY_LIMIT,
+            pending: RwLock::new(None),
+            _watcher: None,
+        };
+        Ok((manager, change_receiver))
+    }
+
+    pub fn is_ephemeral(&self) -> bool {
+        self.config_path.is_none()
+    }
+
+    /// Configuration for ephemeral mode: `base`, or the defaults, with
+    /// `profile`, the settings of the `RUST_ROUTE_*` environment variables
+    /// and then `settings` applied
+    pub fn ephemeral_config(
+        base: Option<&Path>,
+        profile: Option<&str>,
+        vars: impl IntoIterator<Item = (String, String)>,
+        settings: &[String],
+    ) -> Result<RouterConfig> {
+        let config: RouterConfig = match base {
+            Some(path) => {
+                let content = std::fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                serde_json::from_str(&content).context("Failed to parse config JSON")?
+            }
+            None => RouterConfig::default(),
+        };
+        let config = match profile {
+            Some(name) => config.with_profile(name)?,
+            None => config,
+        };
+        let mut overrides = env_settings(vars);
+        for setting in settings {
+            let (path, value) = setting
+                .split_once('=')
+                .with_context(|| format!("Setting '{}' is not KEY=VALUE", setting))?;
+            overrides.push((path.trim().to_string(), value.to_string()));
+        }
+        config.with_settings(&overrides)
+    }
+
     async fn load_config(path: &Path) -> Result<RouterConfig> {
         let content = tokio::fs::read_to_string(path)
             .await
@@ -294,6 +940,13 @@ impl ConfigManager {
                                         for error in &validation.errors {
                                             log::error!("  - {}", error);
                                         }
+                                        last_error::record(
+                                            last_error::CONFIG_WATCHER,
+                                            format!(
+                                                "Invalid configuration: {}",
+                                                validation.errors.join("; ")
+                                            ),
+                                        );
                                         return;
                                     }
 
@@ -317,6 +970,10 @@ impl ConfigManager {
                                     // Notify subscribers
                                     if let Err(e) = change_sender.send(new_config.clone()) {
                                         log::error!("Failed to notify config change: {}", e);
+                                        last_error::record(
+                                            last_error::CONFIG_WATCHER,
+                                            format!("Failed to notify config change: {}", e),
+                                        );
                                     } else {
                                         log::info!("✅ Configuration reloaded successfully");
                                         let current_version = *config_version.read().await;
@@ -331,12 +988,20 @@ impl ConfigManager {
                                 }
                                 Err(e) => {
                                     log::error!("❌ Failed to reload configuration: {}", e);
+                                    last_error::record(
+                                        last_error::CONFIG_WATCHER,
+                                        format!("Failed to reload configuration: {:#}", e),
+                                    );
                                 }
                             }
                         }
                     }
                     Err(e) => {
                         log::error!("File watcher error: {}", e);
+                        last_error::record(
+                            last_error::CONFIG_WATCHER,
+                            format!("File watcher error: {}", e),
+                        );
                     }
                 }
             });
@@ -361,12 +1026,17 @@ impl ConfigManager {
         }
 
         // Save to file
-        let json =
-            serde_json::to_string_pretty(&new_config).context("Failed to serialize config")?;
-
-        tokio::fs::write(&self.config_path, json)
-            .await
-            .context("Failed to write config file")?;
+        let new_config = match &self.config_path {
+            Some(path) => {
+                let json = serde_json::to_string_pretty(&new_config)
+                    .context("Failed to serialize config")?;
+                tokio::fs::write(path, json)
+                    .await
+                    .context("Failed to write config file")?;
+                new_config
+            }
+            None => without_persistence(new_config),
+        };
 
         // Update in-memory config
         {
@@ -398,6 +1068,87 @@ impl ConfigManager {
         Ok(())
     }
 
+    /// Apply `new_config` and expect [`confirm`](Self::confirm) within
+    /// `window`. Further confirmed changes inside the window extend it and
+    /// still roll back to the configuration from before the first one.
+    pub async fn update_config_confirmed(
+        &self,
+        new_config: RouterConfig,
+        window: std::time::Duration,
+    ) -> Result<PendingConfirm> {
+        let earlier = self.pending.read().await.clone();
+        let (rollback_version, rollback) = match earlier {
+            Some((pending, rollback)) => (pending.rollback_version, rollback),
+            None => (self.get_config_version().await, self.get_config().await),
+        };
+        // A rejected change leaves an earlier window running
+        self.update_config(new_config).await?;
+
+        let pending = PendingConfirm {
+            version: self.get_config_version().await,
+            rollback_version,
+            confirm_by: Utc::now() + chrono::Duration::from_std(window).unwrap_or_default(),
+        };
+        *self.pending.write().await = Some((pending.clone(), rollback));
+        Ok(pending)
+    }
+
+    /// Keep the unconfirmed change, if there is one
+    pub async fn confirm(&self) -> Option<PendingConfirm> {
+        self.pending
+            .write()
+            .await
+            .take()
+            .map(|(pending, _)| pending)
+    }
+
+    pub async fn pending_confirm(&self) -> Option<PendingConfirm> {
+        self.pending
+            .read()
+            .await
+            .as_ref()
+            .map(|(pending, _)| pending.clone())
+    }
+
+    /// Restore the configuration from before change `version` if that change
+    /// is still unconfirmed and its window has passed. Returns the reverted
+    /// change.
+    pub async fn revert_unconfirmed(&self, version: u32) -> Result<Option<PendingConfirm>> {
+        let (pending, rollback) = {
+            let mut guard = self.pending.write().await;
+            match guard.as_ref() {
+                Some((pending, _))
+                    if pending.version == version && pending.confirm_by <= Utc::now() =>
+                {
+                    guard.take().expect("checked above")
+                }
+                _ => return Ok(None),
+            }
+        };
+        self.update_config(rollback).await?;
+        Ok(Some(pending))
+    }
+
+    /// Flip the administrative state of a configured interface and persist it.
+    /// Returns `false` when no interface with that name is configured.
+    pub async fn set_interface_enabled(&self, name: &str, enabled: bool) -> Result<bool> {
+        let mut config = self.get_config().await;
+        let Some(iface) = config
+            .interfaces
+            .iter_mut()
+            .find(|iface| iface.name == name)
+        else {
+            return Ok(false);
+        };
+        if iface.enabled == enabled {
+            return Ok(true);
+        }
+
+        iface.enabled = enabled;
+        self.update_config(config).await?;
+        Ok(true)
+    }
+
     pub async fn list_history(&self) -> Vec<ConfigHistoryEntry> {
         let history = self.history.read().await;
         history
@@ -432,6 +1183,14 @@ impl ConfigManager {
         })
     }
 
+    /// Lay profile `name` over the running configuration. The switch is an
+    /// ordinary change: it is saved, recorded in the history and can be
+    /// rolled back like any other.
+    pub async fn activate_profile(&self, name: &str) -> Result<()> {
+        let config = self.get_config().await.with_profile(name)?;
+        self.update_config(config).await
+    }
+
     pub async fn rollback_to(&self, version: u32) -> Result<()> {
         let snapshot = {
             let history = self.history.read().await;
@@ -473,6 +1232,17 @@ impl ConfigManager {
             result.add_warning("Router ID should be a valid IP address".to_string());
         }
 
+        for name in config.profiles.keys() {
+            if let Err(err) = config.with_profile(name) {
+                result.add_error(format!("{:#}", err));
+            }
+        }
+        if let Some(name) = &config.active_profile {
+            if !config.profiles.contains_key(name) {
+                result.add_warning(format!("Active profile {} is not defined", name));
+            }
+        }
+
         // Validate interfaces
         if config.interfaces.is_empty() {
             result.add_error("At least one interface must be configured".to_string());
@@ -493,9 +1263,136 @@ impl ConfigManager {
                     interface.name
                 ));
             }
+
+            if interface.enabled
+                && !interface.passive
+                && interface.is_point_to_point()
+                && interface.link_peer().is_none()
+            {
+                result.add_warning(format!(
+                    "Point-to-point interface {} has no peer; updates will not be sent",
+                    interface.name
+                ));
+            }
+
+            match interface.mtu {
+                Some(mtu) if mtu < 68 => result.add_error(format!(
+                    "Interface {} MTU {} is below the IPv4 minimum of 68",
+                    interface.name, mtu
+                )),
+                Some(mtu) if mtu < 576 => result.add_warning(format!(
+                    "Interface {} MTU {} leaves little room per update; packets will be split heavily",
+                    interface.name, mtu
+                )),
+                _ => {}
+            }
+
+            for accepted in &interface.accept_from {
+                if let Some(rejected) = interface
+                    .reject_from
+                    .iter()
+                    .find(|rejected| rejected.contains(accepted))
+                {
+                    result.add_warning(format!(
+                        "Interface {} accepts {} but rejects all of it through {}",
+                        interface.name, accepted, rejected
+                    ));
+                }
+            }
+
+            if let Some(primary) = interface.ipv4_net() {
+                for secondary in &interface.secondary_addresses {
+                    if secondary.addr() == primary.addr()
+                        || interface
+                            .secondary_addresses
+                            .iter()
+                            .filter(|net| net.addr() == secondary.addr())
+                            .count()
+                            > 1
+                    {
+                        result.add_error(format!(
+                            "Interface {} lists address {} more than once",
+                            interface.name,
+                            secondary.addr()
+                        ));
+                    } else if primary.contains(&secondary.addr()) {
+                        result.add_warning(format!(
+                            "Secondary address {} on {} is inside {} and adds no prefix",
+                            secondary, interface.name, primary
+                        ));
+                    }
+                }
+            }
+
+            if let SendMode::Unicast(destinations) = &interface.send_mode {
+                if destinations.is_empty() && interface.enabled && !interface.passive {
+                    result.add_error(format!(
+                        "Interface {} sends unicast updates but lists no destinations",
+                        interface.name
+                    ));
+                }
+                if let Ok(network) = interface.address.parse::<ipnet::Ipv4Net>() {
+                    for destination in destinations.iter().filter(|d| !network.contains(*d)) {
+                        result.add_warning(format!(
+                            "Interface {} sends updates to {}, which is outside {}",
+                            interface.name,
+                            destination,
+                            network.trunc()
+                        ));
+                    }
+                }
+            }
         }
 
         // Validate RIP configuration
+        for (index, neighbor) in config.rip.neighbors.iter().enumerate() {
+            if config.rip.neighbors[..index]
+                .iter()
+                .any(|earlier| earlier.address == neighbor.address)
+            {
+                result.add_warning(format!(
+                    "Neighbor {} is configured more than once; only the first entry applies",
+                    neighbor.address
+                ));
+            }
+            if neighbor.metric_offset >= config.rip.infinity_metric {
+                result.add_warning(format!(
+                    "Metric offset {} for neighbor {} makes all of its routes unreachable",
+                    neighbor.metric_offset, neighbor.address
+                ));
+            }
+        }
+
+        for restriction in &config.rip.restricted_prefixes {
+            if restriction.neighbors.is_empty() {
+                result.add_warning(format!(
+                    "Restricted prefix {} lists no neighbors and is never advertised",
+                    restriction.prefix
+                ));
+            }
+            if restriction.prefix.trunc() != restriction.prefix {
+                result.add_warning(format!(
+                    "Restricted prefix {} has host bits set; it covers {}",
+                    restriction.prefix,
+                    restriction.prefix.trunc()
+                ));
+            }
+        }
+
+        for slow in &config.rip.slow_prefixes {
+            if slow.interval >= config.rip.route_timeout {
+                result.add_error(format!(
+                    "Slow prefix {} is advertised every {}s, not before neighbors time it out after {}s",
+                    slow.prefix, slow.interval, config.rip.route_timeout
+                ));
+            } else if slow.interval <= config.rip.update_interval {
+                result.add_warning(format!(
+                    "Slow prefix {} has an interval of {}s, no longer than the update interval",
+                    slow.prefix, slow.interval
+                ));
+            }
+        }
+
         if config.rip.enabled {
             if config.rip.port == 0 {
                 result.add_error("RIP port cannot be 0".to_string());
@@ -505,13 +1402,118 @@ impl ConfigManager {
                 result.add_error("RIP update interval cannot be 0".to_string());
             }
 
+            if config.rip.route_timeout == 0 || config.rip.garbage_collection_timeout == 0 {
+                result
+                    .add_error("RIP route and garbage-collection timeouts cannot be 0".to_string());
+            } else if config.rip.route_timeout < config.rip.update_interval * 3 {
+                result.add_warning(format!(
+                    "RIP route timeout {}s is under three update intervals; routes may flap",
+                    config.rip.route_timeout
+                ));
+            }
+
+            if config.rip.holddown > config.rip.garbage_collection_timeout {
+                result.add_warning(
+                    "RIP hold-down outlasts garbage collection; routes are flushed before it ends"
+                        .to_string(),
+                );
+            }
+
             if config.rip.infinity_metric == 0 {
                 result.add_error("RIP infinity metric cannot be 0".to_string());
             }
 
+            if config.rip.poison_reverse && !config.rip.split_horizon {
+                result.add_warning(
+                    "rip.poison_reverse implies split horizon; routes are poisoned although split_horizon is off"
+                        .to_string(),
+                );
+            }
+
+            if config.rip.reference_bandwidth_mbps == 0 {
+                result.add_error("rip.reference_bandwidth_mbps must be at least 1".to_string());
+            }
+
+            if config.rip.max_paths == 0 {
+                result.add_error("rip.max_paths must be at least 1".to_string());
+            }
+
             if config.rip.infinity_metric > 16 {
                 result.add_warning("RIP infinity metric > 16 is non-standard".to_string());
             }
+
+            if config.rip.install_kernel_routes && !cfg!(target_os = "linux") {
+                result.add_warning(
+                    "rip.install_kernel_routes only works on Linux; routes stay out of the kernel"
+                        .to_string(),
+                );
+            }
+
+            let redistribute = &config.rip.redistribute;
+            for (name, source) in [
+                ("kernel", &redistribute.kernel),
+                ("connected", &redistribute.connected),
+            ] {
+                if source.enabled
+                    && (source.metric == 0 || source.metric >= config.rip.infinity_metric)
+                {
+                    result.add_error(format!(
+                        "rip.redistribute.{}.metric must be between 1 and {}",
+                        name,
+                        config.rip.infinity_metric.saturating_sub(1)
+                    ));
+                }
+            }
+            if redistribute.is_enabled() && !cfg!(target_os = "linux") {
+                result.add_warning(
+                    "rip.redistribute only works on Linux; no routes are redistributed".to_string(),
+                );
+            }
+
+            let packet_auth = &config.rip.packet_auth;
+            if packet_auth.enabled && packet_auth.key.is_empty() {
+                result.add_error("RIP packet authentication requires a key".to_string());
+            }
+            if packet_auth.enabled && packet_auth.accept_unsigned {
+                result.add_warning(
+                    "RIP packet authentication accepts unsigned packets; disable accept_unsigned once every neighbor signs"
+                        .to_string(),
+                );
+            }
+
+            let mut authenticated = HashSet::new();
+            for auth in &config.rip.authentication {
+                for error in auth.errors() {
+                    result.add_error(error);
+                }
+                if !authenticated.insert(auth.interface.as_str()) {
+                    result.add_error(format!(
+                        "rip.authentication lists interface {} twice",
+                        auth.interface
+                    ));
+                }
+                match config
+                    .interfaces
+                    .iter()
+                    .find(|iface| iface.name == auth.interface)
+                {
+                    None => result.add_error(format!(
+                        "rip.authentication names unknown interface {}",
+                        auth.interface
+                    )),
+                    Some(iface) if iface.codec == PacketCodec::Json => result.add_warning(format!(
+                        "Interface {} sends JSON; rip.authentication only covers binary packets, sign JSON with rip.packet_auth",
+                        auth.interface
+                    )),
+                    Some(_) => {}
+                }
+                if !auth.keys.is_empty() && auth.send_key(Utc::now()).is_none() {
+                    result.add_warning(format!(
+                        "No key in rip.authentication for {} is valid now; the interface cannot send",
+                        auth.interface
+                    ));
+                }
+            }
         }
 
         // Validate web configuration
@@ -522,7 +1524,89 @@ impl ConfigManager {
 
             if config.web.bind_address.is_empty() {
                 result.add_error("Web interface bind address cannot be empty".to_string());
+            } else if let Err(err) = listen::socket_addr(&config.web.bind_address, config.web.port)
+            {
+                result.add_error(format!("web.bind_address: {}", err));
+            }
+
+            let session = &config.web.session;
+            if session.cookies && session.same_site == SameSite::None && !session.secure {
+                result.add_error(
+                    "Session cookies with SameSite=None must be secure; browsers reject them otherwise"
+                        .to_string(),
+                );
+            } else if session.cookies && !session.secure {
+                result.add_warning(
+                    "Session cookies are not marked secure and will be sent over plain HTTP"
+                        .to_string(),
+                );
+            }
+
+            let tls = &config.web.tls;
+            if tls.enabled && (tls.cert_file.is_none() || tls.key_file.is_none()) {
+                result.add_error("web.tls requires cert_file and key_file".to_string());
+            }
+            if tls.require_client_cert && tls.client_ca_file.is_none() {
+                result.add_error("web.tls.require_client_cert requires client_ca_file".to_string());
             }
+
+            let rate_limit = &config.web.rate_limit;
+            if rate_limit.enabled
+                && rate_limit.requests_per_minute == 0
+                && rate_limit.global_requests_per_minute == 0
+            {
+                result.add_warning(
+                    "web.rate_limit is enabled but both limits are 0; nothing is limited"
+                        .to_string(),
+                );
+            }
+
+            if let Err(err) = crate::proxy::normalize(&config.web.base_path) {
+                result.add_error(format!("web.base_path is invalid: {}", err));
+            }
+        }
+
+        if config.event_bus.capacity == 0 {
+            result.add_error("event_bus.capacity must be at least 1".to_string());
+        }
+        if config.event_bus.overflow == OverflowPolicy::Spill {
+            if config.event_bus.spill_dir.trim().is_empty() {
+                result.add_error("event_bus.spill_dir cannot be empty with spill".to_string());
+            }
+            if config.event_bus.spill_limit == 0 {
+                result.add_warning("event_bus.spill_limit is 0; nothing will spill".to_string());
+            }
+        }
+
+        for error in config.runtime.errors() {
+            result.add_error(error);
+        }
+        for error in config.fleet.errors(&config.router_id) {
+            result.add_error(error);
+        }
+        for error in config.clock.errors() {
+            result.add_error(error);
+        }
+
+        if config.storage.backend != StorageBackend::Memory && config.storage.path.trim().is_empty()
+        {
+            result.add_error("storage.path cannot be empty".to_string());
+        }
+        if config.storage.backend == StorageBackend::Sqlite && !cfg!(feature = "sqlite") {
+            result.add_error(
+                "storage.backend is sqlite but rust-route was built without the sqlite feature"
+                    .to_string(),
+            );
+        }
+
+        if config.activity_log.enabled && config.activity_log.path.trim().is_empty() {
+            result.add_error("activity_log.path cannot be empty when enabled".to_string());
+        }
+        if config.activity_log.max_entries == 0 {
+            result.add_warning("activity_log.max_entries is 0; keeping 1 record".to_string());
+        }
+        if config.dump.directory.trim().is_empty() {
+            result.add_error("dump.directory cannot be empty".to_string());
         }
 
         // Validate authentication
@@ -536,6 +1620,19 @@ impl ConfigManager {
             }
         }
 
+        for rule in &config.auth.permissions {
+            if let Err(error) = rule.validate() {
+                result.add_error(error);
+            }
+        }
+
+        let client_ca = config.web.tls.enabled && config.web.tls.client_ca_file.is_some();
+        if !config.auth.client_certificates.is_empty() && !client_ca {
+            result.add_warning(
+                "auth.client_certificates has no effect without web.tls.client_ca_file".to_string(),
+            );
+        }
+
         if config.web.auth_enabled != config.auth.enabled {
             result.add_warning(
                 "web.auth_enabled and auth.enabled differ; authentication only activates when both are true"
@@ -544,12 +1641,268 @@ impl ConfigManager {
         }
 
         // Validate logging
-        if config.logging.level.is_empty() {
-            result.add_error("Log level cannot be empty".to_string());
-        } else {
-            let valid_levels = ["error", "warn", "info", "debug", "trace"];
-            if !valid_levels.contains(&config.logging.level.as_str()) {
-                result.add_error(format!("Invalid log level: {}", config.logging.level));
+        if let Err(crate::RustRouteError::InvalidInput(message)) =
+            crate::logging::parse_filter(&config.logging.level)
+        {
+            result.add_error(message);
+        }
+
+        if config.watchdog.enabled && config.watchdog.check_interval == 0 {
+            result.add_error("Watchdog check interval cannot be 0".to_string());
+        }
+
+        // Validate SNMP agent
+        if config.snmp.port == 0 {
+            result.add_error("SNMP port cannot be 0".to_string());
+        }
+        if let Err(err) = listen::socket_addr(&config.snmp.bind_address, config.snmp.port) {
+            result.add_error(format!("snmp.bind_address: {}", err));
+        }
+        for user in &config.snmp.v3_users {
+            if user.auth_password.len() < 8 {
+                result.add_error(format!(
+                    "SNMPv3 user {} needs an auth password of at least 8 characters",
+                    user.username
+                ));
+            }
+        }
+        if config.snmp.enabled
+            && config.snmp.community.is_empty()
+            && config.snmp.v3_users.is_empty()
+        {
+            result.add_warning(
+                "SNMP agent is enabled but has no community or v3 users; all requests will be dropped"
+                    .to_string(),
+            );
+        }
+
+        if config.gnmi.port == 0 {
+            result.add_error("gNMI port cannot be 0".to_string());
+        }
+        if let Err(err) = listen::socket_addr(&config.gnmi.bind_address, config.gnmi.port) {
+            result.add_error(format!("gnmi.bind_address: {}", err));
+        }
+
+        // Validate MQTT publisher
+        if config.mqtt.enabled {
+            if config.mqtt.broker_host.is_empty() {
+                result.add_error("MQTT broker host cannot be empty".to_string());
+            }
+            if config.mqtt.broker_port == 0 {
+                result.add_error("MQTT broker port cannot be 0".to_string());
+            }
+            if config.mqtt.neighbor_interval == 0 {
+                result.add_error("MQTT neighbor interval cannot be 0".to_string());
+            }
+        }
+        if config.mqtt.qos > 2 {
+            result.add_error(format!("Invalid MQTT QoS: {}", config.mqtt.qos));
+        }
+        if config.mqtt.tls.client_cert_file.is_some() != config.mqtt.tls.client_key_file.is_some() {
+            result.add_error(
+                "MQTT TLS client certificate and key must be configured together".to_string(),
+            );
+        }
+
+        // Validate reverse DNS
+        if config.reverse_dns.enabled && config.reverse_dns.timeout_ms == 0 {
+            result.add_error("Reverse DNS timeout cannot be 0".to_string());
+        }
+        for server in &config.reverse_dns.servers {
+            if crate::dns::parse_server(server).is_none() {
+                result.add_error(format!("Invalid reverse DNS server: {}", server));
+            }
+        }
+
+        // Validate event streaming sink
+        if config.event_stream.enabled {
+            if config.event_stream.servers.is_empty() {
+                result.add_error("Event stream requires at least one server".to_string());
+            }
+            if config.event_stream.topic.is_empty() {
+                result.add_error("Event stream topic cannot be empty".to_string());
+            }
+            if config.event_stream.batch_size == 0 {
+                result.add_error("Event stream batch size cannot be 0".to_string());
+            }
+            if config.event_stream.flush_interval_ms == 0 {
+                result.add_error("Event stream flush interval cannot be 0".to_string());
+            }
+            if config.event_stream.events.is_empty() {
+                result.add_warning("Event stream has no event types selected".to_string());
+            }
+        }
+
+        // Validate hook commands
+        if config.hooks.enabled {
+            if config.hooks.max_concurrent == 0 {
+                result.add_error("Hook concurrency limit cannot be 0".to_string());
+            }
+            if config.hooks.commands.is_empty() {
+                result.add_warning("Hooks are enabled but no commands are configured".to_string());
+            }
+        }
+        for hook in &config.hooks.commands {
+            if hook.command.is_empty() {
+                result.add_error(format!(
+                    "Hook for {} has an empty command",
+                    hook.event.as_str()
+                ));
+            }
+            if hook.timeout_secs.unwrap_or(config.hooks.timeout_secs) == 0 {
+                result.add_error(format!("Hook {} timeout cannot be 0", hook.command));
+            }
+        }
+
+        // Validate static routes
+        let mut static_prefixes = HashSet::new();
+        for route in &config.static_routes {
+            if !static_prefixes.insert(route.prefix.trunc()) {
+                result.add_error(format!(
+                    "Static route {} is configured more than once",
+                    route.prefix.trunc()
+                ));
+            }
+            if route.prefix.trunc() != route.prefix {
+                result.add_warning(format!(
+                    "Static route {} has host bits set; it covers {}",
+                    route.prefix,
+                    route.prefix.trunc()
+                ));
+            }
+            if route.metric == 0 || route.metric >= config.rip.infinity_metric {
+                result.add_error(format!(
+                    "Static route {} needs a metric from 1 to {}",
+                    route.prefix,
+                    config.rip.infinity_metric.saturating_sub(1)
+                ));
+            }
+            match config
+                .interfaces
+                .iter()
+                .find(|iface| iface.name == route.interface)
+            {
+                Some(iface) => {
+                    let on_link = iface
+                        .ipv4_net()
+                        .into_iter()
+                        .chain(iface.secondary_addresses.iter().copied())
+                        .any(|net| net.contains(&route.next_hop));
+                    if !on_link && !iface.is_point_to_point() {
+                        result.add_warning(format!(
+                            "Next hop {} of static route {} is not on {}",
+                            route.next_hop, route.prefix, route.interface
+                        ));
+                    }
+                }
+                None if config
+                    .tunnels
+                    .iter()
+                    .any(|tunnel| tunnel.name == route.interface) => {}
+                None => result.add_error(format!(
+                    "Static route {} uses unknown interface {}",
+                    route.prefix, route.interface
+                )),
+            }
+        }
+
+        // Validate prefix, distribute and offset lists
+        let known_interface = |name: &str| {
+            config.interfaces.iter().any(|iface| iface.name == name)
+                || config.tunnels.iter().any(|tunnel| tunnel.name == name)
+        };
+        let mut prefix_lists = HashSet::new();
+        for prefix_list in &config.policy.prefix_lists {
+            if !prefix_lists.insert(prefix_list.name.as_str()) {
+                result.add_error(format!(
+                    "Prefix list {} is defined more than once",
+                    prefix_list.name
+                ));
+            }
+            for rule in &prefix_list.rules {
+                let (min, max) = rule.lengths();
+                if min < rule.prefix.prefix_len() || min > max || max > 32 {
+                    result.add_error(format!(
+                        "Prefix list {}: lengths {}..{} do not fit {}",
+                        prefix_list.name, min, max, rule.prefix
+                    ));
+                }
+            }
+        }
+        for list in &config.policy.distribute_lists {
+            if !prefix_lists.contains(list.prefix_list.as_str()) {
+                result.add_error(format!(
+                    "Distribute list refers to unknown prefix list {}",
+                    list.prefix_list
+                ));
+            }
+            if let Some(interface) = &list.interface {
+                if !known_interface(interface) {
+                    result.add_warning(format!(
+                        "Distribute list {} applies to unknown interface {}",
+                        list.prefix_list, interface
+                    ));
+                }
+            }
+        }
+        for list in &config.policy.offset_lists {
+            if let Some(name) = &list.prefix_list {
+                if !prefix_lists.contains(name.as_str()) {
+                    result.add_error(format!(
+                        "Offset list refers to unknown prefix list {}",
+                        name
+                    ));
+                }
+            }
+            if let (Direction::Export, Some(neighbor)) = (list.direction, list.neighbor) {
+                result.add_error(format!(
+                    "Offset list for neighbor {} must be inbound; advertisements are shared by the neighbors on a link",
+                    neighbor
+                ));
+            }
+            if let Some(interface) = &list.interface {
+                if !known_interface(interface) {
+                    result.add_warning(format!(
+                        "Offset list applies to unknown interface {}",
+                        interface
+                    ));
+                }
+            }
+            if list.offset >= config.rip.infinity_metric {
+                result.add_warning(format!(
+                    "Offset {} makes every route it applies to unreachable",
+                    list.offset
+                ));
+            }
+        }
+
+        // Validate route policy scripts
+        if config.policy.max_operations == 0 {
+            result.add_error("Policy operation limit cannot be 0".to_string());
+        } else if let Err(err) = RoutePolicy::load(&config.policy) {
+            result.add_error(err.to_string());
+        }
+
+        // Validate RIP tunnels
+        let mut tunnel_names = HashSet::new();
+        for tunnel in &config.tunnels {
+            if let Err(err) = tunnel.validate() {
+                result.add_error(err);
+            }
+            if !tunnel_names.insert(tunnel.name.as_str())
+                || config
+                    .interfaces
+                    .iter()
+                    .any(|iface| iface.name == tunnel.name)
+            {
+                result.add_error(format!("Tunnel name {} is already in use", tunnel.name));
+            }
+        }
+
+        let registered = PluginRegistry::global().names();
+        for name in &config.plugins.disabled {
+            if !registered.contains(name) {
+                result.add_warning(format!("Disabled plugin {} is not registered", name));
             }
         }
 
@@ -685,7 +2038,7 @@ impl ConfigManager {
         }
 
         // Sort by timestamp, newest first
-        backups.sort_by(|a, b| b.1.timestamp.cmp(&a.1.timestamp));
+        backups.sort_by_key(|entry| std::cmp::Reverse(entry.1.timestamp));
 
         Ok(backups)
     }
@@ -726,10 +2079,70 @@ mod tests {
         assert!(result.is_valid());
     }
 
+    #[test]
+    fn test_point_to_point_peers() {
+        let mut iface = RouterConfig::default().interfaces.remove(0);
+        assert!(!iface.is_point_to_point());
+
+        iface.address = "10.99.0.1/31".to_string();
+        assert!(iface.is_point_to_point());
+        assert_eq!(iface.link_peer(), Some(Ipv4Addr::new(10, 99, 0, 0)));
+
+        iface.address = "10.99.0.1/32".to_string();
+        assert_eq!(iface.link_peer(), None);
+        let config = RouterConfig {
+            interfaces: vec![iface.clone()],
+            ..RouterConfig::default()
+        };
+        assert!(ConfigManager::validate_config(&config)
+            .warnings
+            .iter()
+            .any(|warning| warning.contains("has no peer")));
+
+        iface.peer = Some(Ipv4Addr::new(10, 99, 0, 2));
+        assert_eq!(iface.link_peer(), Some(Ipv4Addr::new(10, 99, 0, 2)));
+    }
+
+    #[test]
+    fn static_routes_need_a_known_interface_and_a_usable_metric() {
+        let route = |prefix: &str, next_hop: [u8; 4], interface: &str, metric| StaticRouteConfig {
+            prefix: prefix.parse().unwrap(),
+            next_hop: Ipv4Addr::from(next_hop),
+            interface: interface.to_string(),
+            metric,
+            advertise: true,
+        };
+        let mut config = RouterConfig {
+            static_routes: vec![route("10.20.0.0/16", [192, 168, 1, 254], "eth0", 2)],
+            ..RouterConfig::default()
+        };
+        let result = ConfigManager::validate_config(&config);
+        assert!(result.is_valid());
+        assert!(result.warnings.iter().all(|w| !w.contains("10.20.0.0/16")));
+
+        config.static_routes = vec![
+            route("10.20.0.0/16", [10, 0, 0, 1], "eth0", 2),
+            route("10.20.0.0/16", [192, 168, 1, 254], "eth0", 16),
+            route("10.30.0.0/16", [192, 168, 1, 254], "eth7", 1),
+        ];
+        let result = ConfigManager::validate_config(&config);
+        assert_eq!(result.errors.len(), 3);
+        assert!(result
+            .warnings
+            .iter()
+            .any(|warning| warning.contains("Next hop 10.0.0.1")));
+
+        // Routes through a disabled interface stay out of the table
+        config.interfaces[0].enabled = false;
+        assert_eq!(config.active_static_routes().count(), 0);
+    }
+
     #[test]
     fn test_invalid_config_validation() {
-        let mut config = RouterConfig::default();
-        config.router_id = "".to_string();
+        let mut config = RouterConfig {
+            router_id: "".to_string(),
+            ..Default::default()
+        };
         config.interfaces.clear();
 
         let result = ConfigManager::validate_config(&config);
@@ -737,6 +2150,121 @@ mod tests {
         assert!(result.errors.len() >= 2);
     }
 
+    #[test]
+    fn management_bind_addresses_may_be_ipv6() {
+        let mut config = RouterConfig::default();
+        config.web.bind_address = "::".to_string();
+        config.gnmi.bind_address = "[::1]".to_string();
+        config.snmp.bind_address = "0.0.0.0".to_string();
+        assert!(ConfigManager::validate_config(&config).is_valid());
+
+        config.web.bind_address = "localhost".to_string();
+        config.gnmi.bind_address = "::1:".to_string();
+        let result = ConfigManager::validate_config(&config);
+        assert!(result
+            .errors
+            .iter()
+            .any(|e| e.starts_with("web.bind_address")));
+        assert!(result
+            .errors
+            .iter()
+            .any(|e| e.starts_with("gnmi.bind_address")));
+    }
+
+    #[test]
+    fn settings_come_from_env_flags_and_secret_files() {
+        let dir = tempdir().unwrap();
+        let secret = dir.path().join("jwt");
+        std::fs::write(&secret, "mounted-secret-of-32-characters!\n").unwrap();
+        let vars = [
+            ("RUST_ROUTE_RIP__PORT", "5520"),
+            ("RUST_ROUTE_ROUTER_ID", "10.0.0.1"),
+            ("RUST_ROUTE_INTERFACES__0__COST", "3"),
+            ("RUST_ROUTE_AUTH__JWT_SECRET_FILE", secret.to_str().unwrap()),
+            ("RUST_ROUTE_SIM_SEED", "7"),
+            ("HOME", "/root"),
+        ]
+        .map(|(name, value)| (name.to_string(), value.to_string()));
+
+        let config = ConfigManager::ephemeral_config(
+            None,
+            None,
+            vars,
+            &[
+                "web.port=9090".to_string(),
+                "mqtt.tls.ca_file=/ca.pem".to_string(),
+            ],
+        )
+        .unwrap();
+        assert_eq!(config.rip.port, 5520);
+        assert_eq!(config.router_id, "10.0.0.1");
+        assert_eq!(config.interfaces[0].cost, 3);
+        assert_eq!(config.auth.jwt_secret, "mounted-secret-of-32-characters!");
+        assert_eq!(config.web.port, 9090);
+        // A field ending in _file is set, not read
+        assert_eq!(config.mqtt.tls.ca_file.as_deref(), Some("/ca.pem"));
+
+        let settings = |setting: &str| {
+            ConfigManager::ephemeral_config(None, None, Vec::new(), &[setting.to_string()])
+        };
+        assert!(settings("rip.prot=5520").is_err());
+        assert!(settings("rip.port=many").is_err());
+        assert!(settings("interfaces.5.cost=1").is_err());
+        assert!(settings("rip.port").is_err());
+    }
+
+    #[tokio::test]
+    async fn ephemeral_manager_never_writes() {
+        let dir = tempdir().unwrap();
+        let mut config = RouterConfig::default();
+        config.backup.backup_directory = dir.path().display().to_string();
+        let (manager, _) = ConfigManager::ephemeral(config.clone()).unwrap();
+        assert!(manager.is_ephemeral());
+        assert!(!manager.get_config().await.activity_log.enabled);
+
+        config.router_id = "192.168.2.1".to_string();
+        config.backup.enabled = true;
+        manager.update_config(config).await.unwrap();
+        assert_eq!(manager.get_config().await.router_id, "192.168.2.1");
+        assert_eq!(manager.list_history().await.len(), 2);
+        assert!(manager.create_backup("test".to_string()).await.is_err());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn profiles_switch_through_the_history() {
+        let mut config = RouterConfig::default();
+        config.profiles.insert(
+            "lab".to_string(),
+            serde_json::json!({"router_id": "10.0.0.1", "rip": {"update_interval": 5}}),
+        );
+        config.profiles.insert(
+            "exam".to_string(),
+            serde_json::json!({"router_id": "10.0.0.2", "rip": {"update_interval": 30}}),
+        );
+        config
+            .profiles
+            .insert("broken".to_string(), serde_json::json!({"rip": {"port": "x"}}));
+        assert!(!ConfigManager::validate_config(&config).is_valid());
+        config.profiles.remove("broken");
+
+        let (manager, _) = ConfigManager::ephemeral(config).unwrap();
+        manager.activate_profile("lab").await.unwrap();
+        let lab = manager.get_config().await;
+        assert_eq!(lab.router_id, "10.0.0.1");
+        assert_eq!(lab.rip.update_interval, 5);
+        // Untouched fields of a merged section keep their values
+        assert_eq!(lab.rip.port, 520);
+        assert_eq!(lab.active_profile.as_deref(), Some("lab"));
+
+        manager.activate_profile("exam").await.unwrap();
+        assert_eq!(manager.get_config().await.rip.update_interval, 30);
+        assert!(manager.activate_profile("demo").await.is_err());
+
+        manager.rollback_to(2).await.unwrap();
+        assert_eq!(manager.get_config().await.router_id, "10.0.0.1");
+    }
+
     #[tokio::test]
     async fn test_config_backup_restore() {
         let temp_dir = tempdir().unwrap();
@@ -770,4 +2298,51 @@ mod tests {
         let restored_config = manager.get_config().await;
         assert_eq!(restored_config.router_id, config.router_id);
     }
+
+    #[tokio::test]
+    async fn unconfirmed_changes_revert() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("config.json");
+        let config = RouterConfig::default();
+        let config_json = serde_json::to_string_pretty(&config).unwrap();
+        tokio::fs::write(&config_path, config_json).await.unwrap();
+        let (manager, _) = ConfigManager::new(&config_path).await.unwrap();
+
+        let mut first = config.clone();
+        first.router_id = "192.168.2.1".to_string();
+        let pending = manager
+            .update_config_confirmed(first.clone(), std::time::Duration::from_secs(60))
+            .await
+            .unwrap();
+        // Still inside the window
+        assert!(manager
+            .revert_unconfirmed(pending.version)
+            .await
+            .unwrap()
+            .is_none());
+
+        let mut second = first.clone();
+        second.router_id = "192.168.3.1".to_string();
+        let pending = manager
+            .update_config_confirmed(second, std::time::Duration::ZERO)
+            .await
+            .unwrap();
+        let reverted = manager.revert_unconfirmed(pending.version).await.unwrap();
+        assert_eq!(reverted, Some(pending));
+        // Both unconfirmed changes are undone
+        assert_eq!(manager.get_config().await.router_id, config.router_id);
+        assert!(manager.pending_confirm().await.is_none());
+
+        let pending = manager
+            .update_config_confirmed(first, std::time::Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(manager.confirm().await, Some(pending.clone()));
+        assert!(manager
+            .revert_unconfirmed(pending.version)
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(manager.get_config().await.router_id, "192.168.2.1");
+    }
 }
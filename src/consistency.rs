@@ -0,0 +1,342 @@
+//! Consistency audit of the routing table.
+//!
+//! Every `CHECK_INTERVAL`, and on `POST /api/diagnostics/check-table`, the
+//! table is compared with the interfaces of the host and the configuration:
+//! routes out of interfaces that no longer exist, next hops outside every
+//! connected subnet, prefixes held by routes of conflicting sources and
+//! enabled interfaces without their connected route. Each anomaly comes with
+//! a suggested fix; the periodic audit reports an anomaly once, when it first
+//! appears, and logs it again once it is resolved.
+
+use chrono::{DateTime, Utc};
+use ipnet::Ipv4Net;
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::net::Ipv4Addr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+use crate::events::{ActivityLevel, EventBus};
+use crate::router::Router;
+use crate::routing_table::{RouteSource, RoutingTable};
+use crate::watchdog::Heartbeat;
+
+/// How often the periodic audit runs
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnomalyKind {
+    /// The route points out of an interface that is neither configured, a
+    /// tunnel, nor present on the host
+    MissingInterface,
+    /// The next hop is in none of the connected subnets
+    OffLinkNextHop,
+    /// A connected prefix is held by a route of another source, or
+    /// equal-cost paths to a prefix come from different sources
+    ConflictingSources,
+    /// An enabled interface lacks the connected route to its subnet
+    MissingDirectRoute,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Anomaly {
+    pub kind: AnomalyKind,
+    pub prefix: Ipv4Net,
+    pub interface: String,
+    pub detail: String,
+    pub suggestion: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConsistencyReport {
+    /// No anomaly was found
+    pub consistent: bool,
+    pub checked_at: DateTime<Utc>,
+    pub routes_checked: usize,
+    pub anomalies: Vec<Anomaly>,
+}
+
+/// Audit `table` against the `connected` routes the enabled interfaces call
+/// for, the `interfaces` that exist and the `tunnels`, whose next hops are
+/// the far end of the tunnel rather than a connected address
+pub fn check(
+    table: &RoutingTable,
+    connected: &[(Ipv4Addr, Ipv4Addr, String)],
+    interfaces: &HashSet<String>,
+    tunnels: &HashSet<String>,
+) -> ConsistencyReport {
+    let infinity = table.timers().infinity;
+    let subnets: Vec<Ipv4Net> = connected
+        .iter()
+        .filter_map(|(network, mask, _)| Ipv4Net::with_netmask(*network, *mask).ok())
+        .collect();
+    let routes: Vec<_> = table
+        .get_all_routes()
+        .into_iter()
+        .filter(|route| route.metric < infinity)
+        .collect();
+
+    let mut anomalies = Vec::new();
+    for route in &routes {
+        let Ok(prefix) = Ipv4Net::with_netmask(route.destination, route.subnet_mask) else {
+            continue;
+        };
+        let prefix = prefix.trunc();
+        let anomaly = |kind, detail: String, suggestion: String| Anomaly {
+            kind,
+            prefix,
+            interface: route.interface.clone(),
+            detail,
+            suggestion,
+        };
+
+        let tunneled = tunnels.contains(&route.interface);
+        if !route.interface.is_empty() && !tunneled && !interfaces.contains(&route.interface) {
+            anomalies.push(anomaly(
+                AnomalyKind::MissingInterface,
+                format!(
+                    "{} {} points out of {}, which no longer exists",
+                    route.source.as_str(),
+                    prefix,
+                    route.interface
+                ),
+                format!(
+                    "Remove the route or restore interface {}; learned routes time out by themselves",
+                    route.interface
+                ),
+            ));
+        }
+
+        if matches!(route.source, RouteSource::Dynamic | RouteSource::Static)
+            && !route.next_hop.is_unspecified()
+            && !tunneled
+            && !subnets
+                .iter()
+                .any(|subnet| subnet.contains(&route.next_hop))
+        {
+            anomalies.push(anomaly(
+                AnomalyKind::OffLinkNextHop,
+                format!(
+                    "Next hop {} of {} is in no connected subnet",
+                    route.next_hop, prefix
+                ),
+                format!(
+                    "Check the address of {} and the next hop field the neighbor sends; rip.next_hop_validation can reject such routes",
+                    route.interface
+                ),
+            ));
+        }
+
+        let sources: HashSet<&str> = table
+            .equal_cost_paths(route.destination, route.subnet_mask)
+            .iter()
+            .map(|path| path.source.as_str())
+            .collect();
+        if sources.len() > 1 {
+            let mut sources: Vec<&str> = sources.into_iter().collect();
+            sources.sort_unstable();
+            anomalies.push(anomaly(
+                AnomalyKind::ConflictingSources,
+                format!(
+                    "Equal-cost paths to {} come from {}",
+                    prefix,
+                    sources.join(" and ")
+                ),
+                format!("Clear {} so it is relearned from a single source", prefix),
+            ));
+        }
+    }
+
+    for (network, mask, interface) in connected {
+        let Ok(prefix) = Ipv4Net::with_netmask(*network, *mask) else {
+            continue;
+        };
+        let installed = table
+            .equal_cost_paths(*network, *mask)
+            .first()
+            .copied()
+            .filter(|route| route.metric < infinity);
+        let anomaly = match installed {
+            Some(route) if route.source == RouteSource::Direct && route.interface == *interface => {
+                continue
+            }
+            Some(route) if route.source != RouteSource::Direct => Anomaly {
+                kind: AnomalyKind::ConflictingSources,
+                prefix,
+                interface: interface.clone(),
+                detail: format!(
+                    "{} is connected on {} but installed as a {} route via {}",
+                    prefix,
+                    interface,
+                    route.source.as_str(),
+                    route.interface
+                ),
+                suggestion: format!(
+                    "Check that {} is up with its configured address; a neighbor or static route should not cover the subnet",
+                    interface
+                ),
+            },
+            Some(route) if route.interface != *interface => Anomaly {
+                kind: AnomalyKind::MissingDirectRoute,
+                prefix,
+                interface: interface.clone(),
+                detail: format!(
+                    "{} is connected on {} but its direct route is installed via {}",
+                    prefix, interface, route.interface
+                ),
+                suggestion: format!(
+                    "Check that {} and {} do not share a subnet",
+                    interface, route.interface
+                ),
+            },
+            _ => Anomaly {
+                kind: AnomalyKind::MissingDirectRoute,
+                prefix,
+                interface: interface.clone(),
+                detail: format!("Connected route {} of {} is missing", prefix, interface),
+                suggestion: format!(
+                    "Check that {} is up with its configured address, then reload the configuration",
+                    interface
+                ),
+            },
+        };
+        anomalies.push(anomaly);
+    }
+
+    ConsistencyReport {
+        consistent: anomalies.is_empty(),
+        checked_at: Utc::now(),
+        routes_checked: routes.len(),
+        anomalies,
+    }
+}
+
+/// Audit the routing table every `CHECK_INTERVAL`, reporting each anomaly
+/// when it first appears and once it is gone
+pub async fn run(router: Arc<RwLock<Router>>, events: EventBus, heartbeat: Heartbeat) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    let mut reported: HashSet<(AnomalyKind, Ipv4Net, String)> = HashSet::new();
+    loop {
+        interval.tick().await;
+        heartbeat.beat();
+
+        let report = match router.read().await.check_consistency().await {
+            Ok(report) => report,
+            Err(err) => {
+                warn!("Routing table audit failed: {}", err);
+                continue;
+            }
+        };
+        let current: HashSet<_> = report
+            .anomalies
+            .iter()
+            .map(|anomaly| (anomaly.kind, anomaly.prefix, anomaly.interface.clone()))
+            .collect();
+        for anomaly in &report.anomalies {
+            let key = (anomaly.kind, anomaly.prefix, anomaly.interface.clone());
+            if reported.contains(&key) {
+                continue;
+            }
+            warn!("{}. {}", anomaly.detail, anomaly.suggestion);
+            events.publish_activity(
+                ActivityLevel::Warn,
+                format!("{}. {}", anomaly.detail, anomaly.suggestion),
+            );
+        }
+        for (kind, prefix, interface) in reported.difference(&current) {
+            info!("Resolved {:?} anomaly of {} on {}", kind, prefix, interface);
+        }
+        if !reported.is_empty() && current.is_empty() {
+            info!("Routing table is consistent again");
+        }
+        reported = current;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routing_table::Route;
+
+    fn learned(destination: [u8; 4], next_hop: [u8; 4], interface: &str) -> Route {
+        Route::new(
+            Ipv4Addr::from(destination),
+            Ipv4Addr::new(255, 255, 0, 0),
+            Ipv4Addr::from(next_hop),
+            2,
+            interface.to_string(),
+            RouteSource::Dynamic,
+            Some(Ipv4Addr::from(next_hop)),
+        )
+    }
+
+    fn kinds(report: &ConsistencyReport) -> Vec<(AnomalyKind, String)> {
+        report
+            .anomalies
+            .iter()
+            .map(|anomaly| (anomaly.kind, anomaly.prefix.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn the_audit_reports_each_kind_of_anomaly() {
+        let mask = Ipv4Addr::new(255, 255, 255, 0);
+        let connected = vec![
+            (Ipv4Addr::new(192, 168, 1, 0), mask, "eth0".to_string()),
+            (Ipv4Addr::new(192, 168, 2, 0), mask, "eth1".to_string()),
+            (Ipv4Addr::new(192, 168, 3, 0), mask, "eth2".to_string()),
+        ];
+        let interfaces = HashSet::from(["eth0", "eth1", "eth2"].map(String::from));
+        let tunnels = HashSet::from(["hq".to_string()]);
+
+        let mut table = RoutingTable::new();
+        for (network, mask, interface) in &connected[..2] {
+            table.install_direct_route(*network, *mask, interface.clone());
+        }
+        table.add_or_replace(learned([10, 1, 0, 0], [192, 168, 1, 2], "eth0"));
+        table.add_or_replace(learned([10, 9, 0, 0], [172, 16, 0, 1], "hq"));
+        let report = check(&table, &connected[..2], &interfaces, &tunnels);
+        assert!(report.consistent);
+        assert_eq!(report.routes_checked, 4);
+
+        table.add_or_replace(learned([10, 2, 0, 0], [10, 0, 0, 1], "eth0"));
+        table.add_or_replace(learned([10, 3, 0, 0], [192, 168, 2, 1], "eth9"));
+        let mut covering = learned([192, 168, 3, 0], [192, 168, 1, 2], "eth0");
+        covering.subnet_mask = mask;
+        table.add_or_replace(covering);
+        let report = check(&table, &connected, &interfaces, &tunnels);
+        assert!(!report.consistent);
+        assert_eq!(
+            kinds(&report),
+            vec![
+                (AnomalyKind::OffLinkNextHop, "10.2.0.0/16".to_string()),
+                (AnomalyKind::MissingInterface, "10.3.0.0/16".to_string()),
+                (
+                    AnomalyKind::ConflictingSources,
+                    "192.168.3.0/24".to_string()
+                ),
+            ]
+        );
+        assert!(report.anomalies[1].suggestion.contains("eth9"));
+
+        table.remove_route(Ipv4Addr::new(192, 168, 3, 0), mask);
+        let report = check(&table, &connected, &interfaces, &tunnels);
+        assert!(kinds(&report).contains(&(
+            AnomalyKind::MissingDirectRoute,
+            "192.168.3.0/24".to_string()
+        )));
+
+        table.install_direct_route(Ipv4Addr::new(192, 168, 3, 0), mask, "eth1".to_string());
+        let report = check(&table, &connected, &interfaces, &tunnels);
+        let anomaly = report
+            .anomalies
+            .iter()
+            .find(|anomaly| anomaly.interface == "eth2")
+            .unwrap();
+        assert_eq!(anomaly.kind, AnomalyKind::MissingDirectRoute);
+        assert!(anomaly.detail.contains("installed via eth1"));
+    }
+}
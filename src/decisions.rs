@@ -0,0 +1,252 @@
+//! Trace of routing decisions.
+//!
+//! With `rip.decision_trace.enabled` set, every entry of every received
+//! response is recorded together with the rule that installed, kept or
+//! rejected it, in a ring of the last `capacity` decisions. Asking
+//! `GET /api/debug/decisions?prefix=10.1.0.0/16` then tells why a route is,
+//! or is not, in the table.
+
+use chrono::{DateTime, Utc};
+use ipnet::Ipv4Net;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::net::Ipv4Addr;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DecisionTraceConfig {
+    pub enabled: bool,
+    /// Decisions kept; the oldest are dropped first
+    pub capacity: usize,
+}
+
+impl Default for DecisionTraceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            capacity: 1000,
+        }
+    }
+}
+
+/// The rule that decided what became of a received entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reason {
+    /// Installed; no route to the prefix was known
+    NewPrefix,
+    /// Replaced a route from a source of lower priority
+    PreferredSource,
+    /// Replaced a route with a higher distance or metric
+    BetterMetric,
+    /// Refreshed the installed route, which it matches
+    Refreshed,
+    /// Took the worse metric the neighbor of the installed route now
+    /// advertises, or failed over to an equal-cost alternate
+    MetricRaised,
+    /// The neighbor of the installed route advertised it unreachable; it
+    /// failed over to an equal-cost alternate or was invalidated
+    Withdrawn,
+    /// Replaced an equal route under `rip.tie_break`
+    TieBreak,
+    /// Kept as an equal-cost alternate to the installed route
+    EqualCostAlternate,
+    /// The prefix is held down after timing out
+    HeldDown,
+    /// A route from a source of higher priority is installed, or one from
+    /// another source that ties with it
+    LowerPrioritySource,
+    /// The installed route has a lower distance or metric
+    WorseMetric,
+    /// The installed route won under `rip.tie_break`
+    TieBreakLost,
+    /// The metric reached infinity
+    Unreachable,
+    /// The subnet mask is not contiguous, so it names no prefix
+    InvalidMask,
+    /// The next hop is not reachable on the interface and
+    /// `rip.next_hop_validation.action` is `reject`
+    InvalidNextHop,
+    /// Denied by an inbound distribute list of the interface
+    DistributeList,
+    /// Dropped, or given an unusable metric, by the import policy
+    ImportPolicy,
+    /// The packet lacked the authentication of the interface's key chain
+    AuthenticationFailed,
+}
+
+impl Reason {
+    /// Whether the installed route changed
+    pub fn changed(self) -> bool {
+        matches!(
+            self,
+            Reason::NewPrefix
+                | Reason::PreferredSource
+                | Reason::BetterMetric
+                | Reason::Refreshed
+                | Reason::MetricRaised
+                | Reason::Withdrawn
+                | Reason::TieBreak
+        )
+    }
+
+    /// Whether the route made it into the table, installed or as an alternate
+    pub fn accepted(self) -> bool {
+        self.changed() || self == Reason::EqualCostAlternate
+    }
+}
+
+/// What became of one received entry
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Decision {
+    pub timestamp: DateTime<Utc>,
+    pub prefix: Ipv4Net,
+    pub neighbor: Ipv4Addr,
+    pub interface: String,
+    /// Metric after the hop and any neighbor offset were added
+    pub metric: u32,
+    pub accepted: bool,
+    pub reason: Reason,
+}
+
+impl Decision {
+    pub fn new(
+        destination: Ipv4Addr,
+        subnet_mask: Ipv4Addr,
+        neighbor: Ipv4Addr,
+        interface: &str,
+        metric: u32,
+        reason: Reason,
+    ) -> Self {
+        let prefix_len = u32::from(subnet_mask).leading_ones() as u8;
+        Self {
+            timestamp: Utc::now(),
+            prefix: Ipv4Net::new(destination, prefix_len)
+                .map(|prefix| prefix.trunc())
+                .unwrap_or_default(),
+            neighbor,
+            interface: interface.to_string(),
+            metric,
+            accepted: reason.accepted(),
+            reason,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct TraceState {
+    config: DecisionTraceConfig,
+    decisions: VecDeque<Decision>,
+}
+
+/// Recent decisions, shared by the receive tasks and the web API
+#[derive(Debug, Clone, Default)]
+pub struct DecisionTrace {
+    state: Arc<Mutex<TraceState>>,
+}
+
+impl DecisionTrace {
+    pub fn new(config: &DecisionTraceConfig) -> Self {
+        let trace = Self::default();
+        trace.configure(config);
+        trace
+    }
+
+    /// Apply new settings; turning the trace off discards what it holds
+    pub fn configure(&self, config: &DecisionTraceConfig) {
+        let mut state = self.lock();
+        state.config = config.clone();
+        if !config.enabled {
+            state.decisions.clear();
+        }
+        while state.decisions.len() > config.capacity {
+            state.decisions.pop_front();
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.lock().config.enabled
+    }
+
+    pub fn record(&self, decision: Decision) {
+        let mut state = self.lock();
+        if !state.config.enabled || state.config.capacity == 0 {
+            return;
+        }
+        if state.decisions.len() >= state.config.capacity {
+            state.decisions.pop_front();
+        }
+        state.decisions.push_back(decision);
+    }
+
+    /// Up to `limit` decisions, newest first, for `prefix` and the prefixes
+    /// within it and from `neighbor`, when given
+    pub fn query(
+        &self,
+        prefix: Option<Ipv4Net>,
+        neighbor: Option<Ipv4Addr>,
+        limit: usize,
+    ) -> Vec<Decision> {
+        self.lock()
+            .decisions
+            .iter()
+            .rev()
+            .filter(|decision| prefix.is_none_or(|prefix| prefix.contains(&decision.prefix)))
+            .filter(|decision| neighbor.is_none_or(|neighbor| decision.neighbor == neighbor))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, TraceState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decision(destination: [u8; 4], prefix_len: u8, reason: Reason) -> Decision {
+        let mask = Ipv4Net::new(Ipv4Addr::UNSPECIFIED, prefix_len)
+            .unwrap()
+            .netmask();
+        Decision::new(
+            Ipv4Addr::from(destination),
+            mask,
+            Ipv4Addr::new(192, 168, 1, 2),
+            "eth0",
+            2,
+            reason,
+        )
+    }
+
+    #[test]
+    fn the_trace_keeps_the_latest_decisions_per_prefix() {
+        let trace = DecisionTrace::new(&DecisionTraceConfig {
+            enabled: true,
+            capacity: 3,
+        });
+        trace.record(decision([10, 1, 0, 0], 16, Reason::NewPrefix));
+        trace.record(decision([10, 1, 2, 0], 24, Reason::WorseMetric));
+        trace.record(decision([10, 2, 0, 0], 16, Reason::ImportPolicy));
+        trace.record(decision([10, 1, 0, 0], 16, Reason::Refreshed));
+
+        let within: Vec<Reason> = trace
+            .query(Some("10.1.0.0/16".parse().unwrap()), None, 10)
+            .iter()
+            .map(|decision| decision.reason)
+            .collect();
+        // The first decision fell out of the ring
+        assert_eq!(within, vec![Reason::Refreshed, Reason::WorseMetric]);
+        assert!(!trace.query(None, None, 10)[1].accepted);
+        assert_eq!(trace.query(None, None, 1).len(), 1);
+
+        trace.configure(&DecisionTraceConfig::default());
+        trace.record(decision([10, 3, 0, 0], 16, Reason::NewPrefix));
+        assert!(trace.query(None, None, 10).is_empty());
+    }
+}
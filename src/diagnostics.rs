@@ -0,0 +1,226 @@
+//! Runtime observability for "the router got slow" reports.
+//!
+//! Attaches to the metrics endpoint how many tasks are alive and which
+//! subsystems own them, how full the event bus is, and how long readers wait
+//! for the router's shared locks. Lock waits are sampled periodically by
+//! timing a read acquisition, so a writer holding a lock for long shows up
+//! as a rising wait without instrumenting every call site.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+use crate::events::{EventBus, SubscriberStats};
+use crate::router::Router;
+use crate::watchdog::{Heartbeat, Watchdog};
+
+/// Time between lock wait samples
+pub const LOCK_SAMPLE_INTERVAL: Duration = Duration::from_secs(5);
+/// Samples kept per lock; five minutes at the default interval
+const LOCK_SAMPLE_WINDOW: usize = 60;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RuntimeStats {
+    pub tasks: TaskStats,
+    pub channels: Vec<ChannelStats>,
+    pub locks: Vec<LockWaitStats>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TaskStats {
+    /// Tasks alive in the async runtime, supervised or not
+    pub alive: usize,
+    pub workers: usize,
+    /// Tasks waiting in the runtime's global queue
+    pub queued: usize,
+    /// Running supervised tasks by subsystem, the part of the task name
+    /// before the first `-`
+    pub by_subsystem: BTreeMap<String, usize>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChannelStats {
+    pub name: String,
+    pub capacity: usize,
+    pub queued: usize,
+    pub receivers: usize,
+    /// Per-subscriber backlog and losses, for channels that track them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subscribers: Vec<SubscriberStats>,
+}
+
+/// Read acquisition waits over the last samples, in microseconds
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LockWaitStats {
+    pub name: String,
+    pub samples: usize,
+    pub last_micros: u64,
+    pub mean_micros: u64,
+    pub max_micros: u64,
+}
+
+/// Recent lock wait samples, shared between the sampler and the web handlers
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    lock_waits: Arc<Mutex<BTreeMap<&'static str, VecDeque<Duration>>>>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_lock_wait(&self, name: &'static str, wait: Duration) {
+        let mut waits = self
+            .lock_waits
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let samples = waits.entry(name).or_default();
+        samples.push_back(wait);
+        while samples.len() > LOCK_SAMPLE_WINDOW {
+            samples.pop_front();
+        }
+    }
+
+    pub fn lock_waits(&self) -> Vec<LockWaitStats> {
+        let waits = self
+            .lock_waits
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        waits
+            .iter()
+            .map(|(name, samples)| {
+                let micros = |wait: &Duration| wait.as_micros() as u64;
+                let total: u64 = samples.iter().map(micros).sum();
+                LockWaitStats {
+                    name: name.to_string(),
+                    samples: samples.len(),
+                    last_micros: samples.back().map_or(0, micros),
+                    mean_micros: total / samples.len().max(1) as u64,
+                    max_micros: samples.iter().map(micros).max().unwrap_or(0),
+                }
+            })
+            .collect()
+    }
+
+    /// Current runtime statistics
+    pub fn collect(&self, watchdog: &Watchdog, events: &EventBus) -> RuntimeStats {
+        let mut by_subsystem = BTreeMap::new();
+        for task in watchdog.report().tasks.iter().filter(|task| task.running) {
+            let subsystem = task.name.split('-').next().unwrap_or(&task.name);
+            *by_subsystem.entry(subsystem.to_string()).or_insert(0) += 1;
+        }
+
+        let (alive, workers, queued) = match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                let metrics = handle.metrics();
+                (
+                    metrics.num_alive_tasks(),
+                    metrics.num_workers(),
+                    metrics.global_queue_depth(),
+                )
+            }
+            Err(_) => (0, 0, 0),
+        };
+
+        let bus = events.clients();
+        RuntimeStats {
+            tasks: TaskStats {
+                alive,
+                workers,
+                queued,
+                by_subsystem,
+            },
+            channels: vec![ChannelStats {
+                name: "event_bus".to_string(),
+                capacity: bus.capacity,
+                queued: bus.queued,
+                receivers: bus.subscribers,
+                subscribers: events.subscribers(),
+            }],
+            locks: self.lock_waits(),
+        }
+    }
+}
+
+async fn timed_read<T>(lock: &RwLock<T>) -> (tokio::sync::RwLockReadGuard<'_, T>, Duration) {
+    let started = Instant::now();
+    let guard = lock.read().await;
+    (guard, started.elapsed())
+}
+
+/// Sample read waits on the router, routing table and neighbor locks until
+/// the task is aborted
+pub async fn sample_locks(
+    router: Arc<RwLock<Router>>,
+    diagnostics: Diagnostics,
+    heartbeat: Heartbeat,
+) {
+    let mut interval = tokio::time::interval(LOCK_SAMPLE_INTERVAL);
+    loop {
+        interval.tick().await;
+        heartbeat.beat();
+
+        let (routing_table, neighbors) = {
+            let (guard, wait) = timed_read(&router).await;
+            diagnostics.record_lock_wait("router", wait);
+            (guard.routing_table(), guard.neighbors())
+        };
+        let (_, wait) = timed_read(&routing_table).await;
+        diagnostics.record_lock_wait("routing_table", wait);
+        let (_, wait) = timed_read(&neighbors).await;
+        diagnostics.record_lock_wait("neighbors", wait);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::watchdog::WatchdogConfig;
+
+    #[test]
+    fn lock_waits_summarize_recent_samples() {
+        let diagnostics = Diagnostics::new();
+        for micros in [100, 300, 200] {
+            diagnostics.record_lock_wait("router", Duration::from_micros(micros));
+        }
+        for _ in 0..LOCK_SAMPLE_WINDOW {
+            diagnostics.record_lock_wait("neighbors", Duration::from_micros(5));
+        }
+        diagnostics.record_lock_wait("neighbors", Duration::from_micros(65));
+
+        let waits = diagnostics.lock_waits();
+        assert_eq!(waits[0].name, "neighbors");
+        assert_eq!(waits[0].samples, LOCK_SAMPLE_WINDOW);
+        assert_eq!(waits[0].mean_micros, 6);
+        assert_eq!(
+            waits[1],
+            LockWaitStats {
+                name: "router".to_string(),
+                samples: 3,
+                last_micros: 200,
+                mean_micros: 200,
+                max_micros: 300,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn tasks_are_grouped_by_subsystem() {
+        let watchdog = Watchdog::new(WatchdogConfig::default());
+        let _eth0 = watchdog.register("receive-eth0", Duration::from_secs(1));
+        let _eth1 = watchdog.register("receive-eth1", Duration::from_secs(1));
+        let _web = watchdog.register("web-server", Duration::from_secs(1));
+        let events = EventBus::new(4);
+        let _receiver = events.subscribe("test");
+
+        let stats = Diagnostics::new().collect(&watchdog, &events);
+        assert_eq!(stats.tasks.by_subsystem["receive"], 2);
+        assert_eq!(stats.tasks.by_subsystem["web"], 1);
+        assert_eq!(stats.channels[0].capacity, 4);
+        assert_eq!(stats.channels[0].receivers, 1);
+        assert!(stats.tasks.workers >= 1);
+    }
+}
@@ -0,0 +1,479 @@
+//! Reverse DNS names for the addresses the API shows.
+//!
+//! With `reverse_dns.enabled`, `GET /api/neighbors` and `GET /api/routes`
+//! name neighbors and next hops, e.g. `core-sw1.lab` next to `10.0.0.2`.
+//! Names come from the hosts file, then from PTR queries to the configured
+//! servers or those in `/etc/resolv.conf`. Answers and failures alike are
+//! cached, so only the first request after a name expires waits for a
+//! lookup, and never longer than `timeout_ms`.
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::task::JoinSet;
+
+const DNS_PORT: u16 = 53;
+const TYPE_PTR: u16 = 12;
+const CLASS_IN: u16 = 1;
+const HOSTS_FILE: &str = "/etc/hosts";
+const RESOLV_CONF: &str = "/etc/resolv.conf";
+
+/// Reverse DNS settings, under `reverse_dns`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReverseDnsConfig {
+    /// Off by default because uncached lookups delay API responses
+    #[serde(default)]
+    pub enabled: bool,
+    /// Seconds a name is cached
+    #[serde(default = "default_ttl")]
+    pub ttl: u64,
+    /// Seconds an address without a name is cached
+    #[serde(default = "default_negative_ttl")]
+    pub negative_ttl: u64,
+    /// Longest wait for one lookup across all servers
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+    /// Name servers as `address` or `address:port`; the ones in
+    /// `/etc/resolv.conf` when empty
+    #[serde(default)]
+    pub servers: Vec<String>,
+    /// Look in `/etc/hosts` before asking the servers
+    #[serde(default = "default_hosts_file")]
+    pub hosts_file: bool,
+}
+
+fn default_ttl() -> u64 {
+    300
+}
+
+fn default_negative_ttl() -> u64 {
+    60
+}
+
+fn default_timeout_ms() -> u64 {
+    500
+}
+
+fn default_hosts_file() -> bool {
+    true
+}
+
+impl Default for ReverseDnsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ttl: default_ttl(),
+            negative_ttl: default_negative_ttl(),
+            timeout_ms: default_timeout_ms(),
+            servers: Vec::new(),
+            hosts_file: default_hosts_file(),
+        }
+    }
+}
+
+impl ReverseDnsConfig {
+    /// Configured servers with the DNS port filled in, skipping the ones that
+    /// do not parse
+    fn server_addresses(&self) -> Vec<SocketAddr> {
+        if !self.servers.is_empty() {
+            return self
+                .servers
+                .iter()
+                .filter_map(|s| parse_server(s))
+                .collect();
+        }
+        std::fs::read_to_string(RESOLV_CONF)
+            .map(|content| {
+                content
+                    .lines()
+                    .filter_map(|line| line.trim().strip_prefix("nameserver"))
+                    .filter_map(parse_server)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// A name server as `address` or `address:port`
+pub fn parse_server(server: &str) -> Option<SocketAddr> {
+    let server = server.trim();
+    server.parse::<SocketAddr>().ok().or_else(|| {
+        let ip = server.trim_matches(|c| c == '[' || c == ']');
+        ip.parse::<IpAddr>()
+            .ok()
+            .map(|ip| SocketAddr::new(ip, DNS_PORT))
+    })
+}
+
+#[derive(Debug, Clone)]
+struct CachedName {
+    name: Option<String>,
+    expires: Instant,
+}
+
+/// Cache of reverse lookups, shared by the API handlers
+#[derive(Debug, Clone, Default)]
+pub struct ReverseDns {
+    cache: Arc<Mutex<HashMap<IpAddr, CachedName>>>,
+}
+
+impl ReverseDns {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn cache(&self) -> std::sync::MutexGuard<'_, HashMap<IpAddr, CachedName>> {
+        self.cache
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Names of those of `addresses` that have one, looking up the ones not
+    /// cached concurrently. Empty while reverse DNS is disabled.
+    pub async fn names(
+        &self,
+        config: &ReverseDnsConfig,
+        addresses: impl IntoIterator<Item = IpAddr>,
+    ) -> HashMap<IpAddr, String> {
+        let mut names = HashMap::new();
+        if !config.enabled {
+            return names;
+        }
+        let now = Instant::now();
+        let mut missing = HashSet::new();
+        {
+            let cache = self.cache();
+            for address in addresses {
+                match cache.get(&address) {
+                    Some(cached) if cached.expires > now => {
+                        if let Some(name) = &cached.name {
+                            names.insert(address, name.clone());
+                        }
+                    }
+                    _ => {
+                        missing.insert(address);
+                    }
+                }
+            }
+        }
+        if missing.is_empty() {
+            return names;
+        }
+
+        let hosts = if config.hosts_file {
+            std::fs::read_to_string(HOSTS_FILE)
+                .map(|content| parse_hosts(&content))
+                .unwrap_or_default()
+        } else {
+            HashMap::new()
+        };
+        let servers = Arc::new(config.server_addresses());
+        let timeout = Duration::from_millis(config.timeout_ms);
+        let mut lookups = JoinSet::new();
+        for address in missing {
+            if let Some(name) = hosts.get(&address) {
+                let name = Some(name.clone());
+                lookups.spawn(async move { (address, name) });
+                continue;
+            }
+            let servers = Arc::clone(&servers);
+            lookups.spawn(async move {
+                let name = tokio::time::timeout(timeout, lookup_ptr(address, &servers))
+                    .await
+                    .ok()
+                    .flatten();
+                (address, name)
+            });
+        }
+
+        let mut resolved = Vec::new();
+        while let Some(result) = lookups.join_next().await {
+            if let Ok(found) = result {
+                resolved.push(found);
+            }
+        }
+        let now = Instant::now();
+        let mut cache = self.cache();
+        cache.retain(|_, cached| cached.expires > now);
+        for (address, name) in resolved {
+            let ttl = if name.is_some() {
+                config.ttl
+            } else {
+                config.negative_ttl
+            };
+            if let Some(name) = &name {
+                names.insert(address, name.clone());
+            }
+            cache.insert(
+                address,
+                CachedName {
+                    name,
+                    expires: now + Duration::from_secs(ttl),
+                },
+            );
+        }
+        names
+    }
+}
+
+/// First name of each address in a hosts file
+fn parse_hosts(content: &str) -> HashMap<IpAddr, String> {
+    let mut hosts = HashMap::new();
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut fields = line.split_whitespace();
+        let (Some(address), Some(name)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        if let Ok(address) = address.parse::<IpAddr>() {
+            hosts.entry(address).or_insert_with(|| name.to_string());
+        }
+    }
+    hosts
+}
+
+/// The `in-addr.arpa` or `ip6.arpa` name of `address`
+fn ptr_name(address: IpAddr) -> String {
+    match address {
+        IpAddr::V4(v4) => {
+            let [a, b, c, d] = v4.octets();
+            format!("{}.{}.{}.{}.in-addr.arpa", d, c, b, a)
+        }
+        IpAddr::V6(v6) => {
+            let mut name = String::with_capacity(72);
+            for byte in v6.octets().iter().rev() {
+                name.push_str(&format!("{:x}.{:x}.", byte & 0x0f, byte >> 4));
+            }
+            name.push_str("ip6.arpa");
+            name
+        }
+    }
+}
+
+/// A recursive PTR query for `name`
+fn encode_query(id: u16, name: &str) -> Vec<u8> {
+    let mut query = Vec::with_capacity(name.len() + 18);
+    query.extend_from_slice(&id.to_be_bytes());
+    // Recursion desired, one question
+    query.extend_from_slice(&[0x01, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0]);
+    for label in name.split('.').filter(|label| !label.is_empty()) {
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&TYPE_PTR.to_be_bytes());
+    query.extend_from_slice(&CLASS_IN.to_be_bytes());
+    query
+}
+
+/// Read the possibly compressed name at `pos`, returning it and the position
+/// after it in the message
+fn read_name(message: &[u8], mut pos: usize) -> Option<(String, usize)> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut end = None;
+    // Each pointer must go backwards, which also rules out loops
+    let mut limit = pos;
+    loop {
+        let len = *message.get(pos)? as usize;
+        match len {
+            0 => {
+                return Some((labels.join("."), end.unwrap_or(pos + 1)));
+            }
+            len if len & 0xc0 == 0xc0 => {
+                let target = ((len & 0x3f) << 8) | *message.get(pos + 1)? as usize;
+                if target >= limit {
+                    return None;
+                }
+                end.get_or_insert(pos + 2);
+                limit = target;
+                pos = target;
+            }
+            len if len < 64 => {
+                let label = message.get(pos + 1..pos + 1 + len)?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                pos += 1 + len;
+            }
+            _ => return None,
+        }
+    }
+}
+
+/// The PTR name in a response to query `id`, if it has one
+fn parse_ptr_response(message: &[u8], id: u16) -> Option<String> {
+    let header = message.get(..12)?;
+    let is_response = header[2] & 0x80 != 0;
+    let rcode = header[3] & 0x0f;
+    if u16::from_be_bytes([header[0], header[1]]) != id || !is_response || rcode != 0 {
+        return None;
+    }
+    let questions = u16::from_be_bytes([header[4], header[5]]);
+    let answers = u16::from_be_bytes([header[6], header[7]]);
+
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = read_name(message, pos)?.1 + 4;
+    }
+    for _ in 0..answers {
+        pos = read_name(message, pos)?.1;
+        let fixed = message.get(pos..pos + 10)?;
+        let record_type = u16::from_be_bytes([fixed[0], fixed[1]]);
+        let length = u16::from_be_bytes([fixed[8], fixed[9]]) as usize;
+        let data = pos + 10;
+        if record_type == TYPE_PTR {
+            let (name, _) = read_name(message, data)?;
+            return (!name.is_empty()).then_some(name);
+        }
+        pos = data + length;
+    }
+    None
+}
+
+/// Ask each server in turn for the PTR name of `address`
+async fn lookup_ptr(address: IpAddr, servers: &[SocketAddr]) -> Option<String> {
+    let name = ptr_name(address);
+    for server in servers {
+        let id = rand::random::<u16>();
+        let local: SocketAddr = match server {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let Ok(socket) = UdpSocket::bind(local).await else {
+            continue;
+        };
+        if let Err(err) = socket.send_to(&encode_query(id, &name), server).await {
+            debug!(
+                "Reverse lookup of {} via {} failed: {}",
+                address, server, err
+            );
+            continue;
+        }
+        let mut buffer = [0u8; 1500];
+        // Answers to other queries are ignored; the caller's timeout bounds the wait
+        loop {
+            match socket.recv_from(&mut buffer).await {
+                Ok((len, from)) if from == *server => {
+                    if u16::from_be_bytes([buffer[0], buffer[1]]) != id {
+                        continue;
+                    }
+                    if let Some(found) = parse_ptr_response(&buffer[..len], id) {
+                        return Some(found);
+                    }
+                    break;
+                }
+                Ok(_) => continue,
+                Err(_) => break,
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A response to `query` naming the address `name`, pointing back at the
+    /// question for the owner name as servers do
+    fn answer(query: &[u8], name: &str) -> Vec<u8> {
+        let mut response = query.to_vec();
+        response[2] |= 0x80;
+        response[7] = 1;
+        response.extend_from_slice(&[0xc0, 12]);
+        response.extend_from_slice(&TYPE_PTR.to_be_bytes());
+        response.extend_from_slice(&CLASS_IN.to_be_bytes());
+        response.extend_from_slice(&300u32.to_be_bytes());
+        let mut data = Vec::new();
+        for label in name.split('.') {
+            data.push(label.len() as u8);
+            data.extend_from_slice(label.as_bytes());
+        }
+        data.push(0);
+        response.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        response.extend_from_slice(&data);
+        response
+    }
+
+    #[test]
+    fn queries_and_answers_round_trip() {
+        assert_eq!(
+            ptr_name(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2))),
+            "2.0.0.10.in-addr.arpa"
+        );
+        assert!(ptr_name("2001:db8::1".parse().unwrap()).starts_with("1.0.0.0.0.0.0.0."));
+        assert!(ptr_name("2001:db8::1".parse().unwrap()).ends_with(".8.b.d.0.1.0.0.2.ip6.arpa"));
+
+        let query = encode_query(7, "2.0.0.10.in-addr.arpa");
+        let response = answer(&query, "core-sw1.lab");
+        assert_eq!(
+            parse_ptr_response(&response, 7).as_deref(),
+            Some("core-sw1.lab")
+        );
+        assert_eq!(parse_ptr_response(&response, 8), None);
+        let mut refused = response.clone();
+        refused[3] |= 5;
+        assert_eq!(parse_ptr_response(&refused, 7), None);
+        // A pointer to itself must not loop
+        assert_eq!(read_name(&[0xc0, 0], 0), None);
+    }
+
+    #[test]
+    fn hosts_files_give_the_first_name() {
+        let hosts =
+            parse_hosts("# lab\n10.0.0.2 core-sw1.lab core-sw1\n10.0.0.2 other\n::1 localhost6\n");
+        assert_eq!(
+            hosts[&IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2))],
+            "core-sw1.lab"
+        );
+        assert_eq!(hosts[&IpAddr::V6(Ipv6Addr::LOCALHOST)], "localhost6");
+    }
+
+    #[tokio::test]
+    async fn names_are_looked_up_once_and_cached() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let config = ReverseDnsConfig {
+            enabled: true,
+            servers: vec![server.local_addr().unwrap().to_string()],
+            hosts_file: false,
+            ..ReverseDnsConfig::default()
+        };
+        let queries = tokio::spawn(async move {
+            let mut buffer = [0u8; 512];
+            let mut answered = 0;
+            while let Ok(Ok((len, from))) =
+                tokio::time::timeout(Duration::from_millis(300), server.recv_from(&mut buffer))
+                    .await
+            {
+                let query = &buffer[..len];
+                let response = if query.windows(4).any(|w| w == b"\x012\x010") {
+                    answer(query, "core-sw1.lab")
+                } else {
+                    let mut nxdomain = query.to_vec();
+                    nxdomain[2] |= 0x80;
+                    nxdomain[3] |= 3;
+                    nxdomain
+                };
+                server.send_to(&response, from).await.unwrap();
+                answered += 1;
+            }
+            answered
+        });
+
+        let resolver = ReverseDns::new();
+        let named = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+        let unnamed = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3));
+        let names = resolver.names(&config, [named, unnamed]).await;
+        assert_eq!(names.get(&named).map(String::as_str), Some("core-sw1.lab"));
+        assert!(!names.contains_key(&unnamed));
+
+        // Both answers are cached, so the server hears nothing more
+        assert_eq!(resolver.names(&config, [named, unnamed]).await.len(), 1);
+        assert_eq!(queries.await.unwrap(), 2);
+
+        let disabled = ReverseDnsConfig::default();
+        assert!(resolver.names(&disabled, [named]).await.is_empty());
+    }
+}
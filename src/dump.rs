@@ -0,0 +1,303 @@
+//! Diagnostic state dumps for bug reports.
+//!
+//! On `SIGUSR1`, or `POST /api/debug/dump`, the router writes everything a
+//! bug report usually needs into one JSON file under `dump.directory`: the
+//! configuration with its secrets blanked out, the routing table, the
+//! neighbors, the counters, the recent activity, the health of the supervised
+//! tasks and the last error of each subsystem. The oldest dumps are removed
+//! once more than `dump.max_files` have been written.
+
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+use crate::activity::{ActivityQuery, ActivityRecord, MAX_QUERY_LIMIT};
+use crate::diagnostics::RuntimeStats;
+use crate::events::ActivityLevel;
+use crate::last_error::{self, SubsystemError};
+use crate::metrics::MetricsSnapshot;
+use crate::routing_table::RouteSnapshot;
+use crate::watchdog::ReadinessReport;
+use crate::web::AppState;
+
+/// Prefix of the dump file names
+const FILE_PREFIX: &str = "rust-route-dump-";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DumpConfig {
+    pub directory: String,
+    /// Dumps kept in `directory`; older ones are removed
+    pub max_files: usize,
+}
+
+impl Default for DumpConfig {
+    fn default() -> Self {
+        Self {
+            directory: "/var/lib/rust-route/dumps".to_string(),
+            max_files: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DumpedNeighbor {
+    pub address: IpAddr,
+    pub interface: Option<String>,
+    pub last_seen_seconds: u64,
+    pub learned_routes: usize,
+}
+
+/// Everything written to a dump file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateDump {
+    pub generated_at: DateTime<Utc>,
+    pub version: String,
+    pub router_id: String,
+    pub config_version: u32,
+    /// The running configuration, secrets replaced by `"<redacted>"`
+    pub config: serde_json::Value,
+    pub routes: Vec<RouteSnapshot>,
+    pub neighbors: Vec<DumpedNeighbor>,
+    pub metrics: MetricsSnapshot,
+    /// Newest first
+    pub activity: Vec<ActivityRecord>,
+    pub tasks: ReadinessReport,
+    pub runtime: RuntimeStats,
+    pub errors: Vec<SubsystemError>,
+}
+
+/// Where a dump was written
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DumpFile {
+    pub path: String,
+    pub bytes: usize,
+    pub generated_at: DateTime<Utc>,
+}
+
+/// Whether a configuration field named `name` holds a secret
+fn is_secret(name: &str) -> bool {
+    name == "key"
+        || name == "token"
+        || name == "community"
+        || ["secret", "password", "passphrase"]
+            .iter()
+            .any(|word| name.contains(word))
+}
+
+/// Blank out the non-empty string values of secret fields in `value`
+pub fn redact(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(fields) => {
+            for (name, field) in fields.iter_mut() {
+                match field {
+                    serde_json::Value::String(text) if is_secret(name) && !text.is_empty() => {
+                        *text = "<redacted>".to_string();
+                    }
+                    _ => redact(field),
+                }
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+/// Gather the state of the router behind `state`
+pub async fn collect(state: &AppState) -> StateDump {
+    let (config, neighbors) = {
+        let router = state.router.read().await;
+        (router.config_snapshot(), router.neighbors())
+    };
+    let mut neighbors: Vec<DumpedNeighbor> = neighbors
+        .read()
+        .await
+        .values()
+        .map(|neighbor| DumpedNeighbor {
+            address: neighbor.address,
+            interface: neighbor.interface.clone(),
+            last_seen_seconds: neighbor.last_seen.elapsed().as_secs(),
+            learned_routes: neighbor.learned_routes,
+        })
+        .collect();
+    neighbors.sort_by_key(|neighbor| neighbor.address);
+    let routes = state.routing_table.read().await.snapshot();
+
+    let mut config_tree = serde_json::to_value(&config).unwrap_or_default();
+    redact(&mut config_tree);
+
+    StateDump {
+        generated_at: Utc::now(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        router_id: config.router_id.clone(),
+        config_version: state.config_manager.get_config_version().await,
+        config: config_tree,
+        metrics: state.metrics.snapshot(neighbors.len(), routes.len()),
+        routes,
+        neighbors,
+        activity: state
+            .activity
+            .query(&ActivityQuery {
+                limit: MAX_QUERY_LIMIT,
+                ..ActivityQuery::default()
+            })
+            .entries,
+        tasks: state.watchdog.report(),
+        runtime: state.diagnostics.collect(&state.watchdog, &state.events),
+        errors: last_error::snapshot(),
+    }
+}
+
+/// Write `dump` as a timestamped file in `config.directory`, then remove the
+/// dumps beyond `config.max_files`
+pub async fn write(dump: &StateDump, config: &DumpConfig) -> std::io::Result<DumpFile> {
+    let directory = Path::new(&config.directory);
+    tokio::fs::create_dir_all(directory).await?;
+    let path = directory.join(format!(
+        "{}{}.json",
+        FILE_PREFIX,
+        dump.generated_at.format("%Y%m%dT%H%M%S%.3fZ")
+    ));
+    let content = serde_json::to_vec_pretty(dump)?;
+    tokio::fs::write(&path, &content).await?;
+
+    if let Err(err) = prune(directory, config.max_files.max(1)).await {
+        warn!(
+            "Failed to remove old dumps in {}: {}",
+            directory.display(),
+            err
+        );
+    }
+    Ok(DumpFile {
+        path: path.display().to_string(),
+        bytes: content.len(),
+        generated_at: dump.generated_at,
+    })
+}
+
+/// Remove all but the newest `keep` dumps; the timestamped names sort in
+/// creation order
+async fn prune(directory: &Path, keep: usize) -> std::io::Result<()> {
+    let mut dumps: Vec<PathBuf> = Vec::new();
+    let mut entries = tokio::fs::read_dir(directory).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with(FILE_PREFIX) && name.ends_with(".json") {
+            dumps.push(entry.path());
+        }
+    }
+    dumps.sort();
+    let excess = dumps.len().saturating_sub(keep);
+    for path in dumps.into_iter().take(excess) {
+        tokio::fs::remove_file(path).await?;
+    }
+    Ok(())
+}
+
+/// Collect and write a dump, reporting the outcome in the activity feed
+pub async fn dump(state: &AppState) -> std::io::Result<DumpFile> {
+    let config = state.router.read().await.config().dump.clone();
+    let dump = collect(state).await;
+    match write(&dump, &config).await {
+        Ok(file) => {
+            info!("State dump written to {}", file.path);
+            state.events.publish_activity(
+                ActivityLevel::Info,
+                format!("State dump written to {}", file.path),
+            );
+            Ok(file)
+        }
+        Err(err) => {
+            warn!(
+                "Failed to write state dump to {}: {}",
+                config.directory, err
+            );
+            state.events.publish_activity(
+                ActivityLevel::Warn,
+                format!("Failed to write state dump: {}", err),
+            );
+            Err(err)
+        }
+    }
+}
+
+/// Write a dump every time the process receives `SIGUSR1`
+#[cfg(unix)]
+pub fn spawn_signal_handler(state: AppState) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut signals = match signal(SignalKind::user_defined1()) {
+        Ok(signals) => signals,
+        Err(err) => {
+            warn!("State dumps on SIGUSR1 unavailable: {}", err);
+            return;
+        }
+    };
+    tokio::spawn(async move {
+        while signals.recv().await.is_some() {
+            info!("Received SIGUSR1; writing a state dump");
+            let _ = dump(&state).await;
+        }
+    });
+}
+
+/// Signals are not available; dumps are only written through the API
+#[cfg(not(unix))]
+pub fn spawn_signal_handler(_state: AppState) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secrets_are_redacted() {
+        let mut config = serde_json::json!({
+            "auth": {"jwt_secret": "s3cr3t", "token_expiry_hours": 24},
+            "rip": {"packet_auth": {"keys": [{"id": 1, "key": "k"}]}},
+            "mqtt": {"password": null, "client_key_file": "/etc/key.pem"},
+            "snmp": {"community": ""},
+            "fleet": {"peers": [{"name": "r2", "token": "t"}], "timeout_seconds": 5}
+        });
+        redact(&mut config);
+        assert_eq!(config["auth"]["jwt_secret"], "<redacted>");
+        assert_eq!(config["auth"]["token_expiry_hours"], 24);
+        assert_eq!(config["rip"]["packet_auth"]["keys"][0]["key"], "<redacted>");
+        assert_eq!(config["rip"]["packet_auth"]["keys"][0]["id"], 1);
+        assert!(config["mqtt"]["password"].is_null());
+        assert_eq!(config["mqtt"]["client_key_file"], "/etc/key.pem");
+        assert_eq!(config["snmp"]["community"], "");
+        assert_eq!(config["fleet"]["peers"][0]["token"], "<redacted>");
+        assert_eq!(config["fleet"]["peers"][0]["name"], "r2");
+    }
+
+    #[tokio::test]
+    async fn old_dumps_are_pruned() {
+        let dir = tempfile::tempdir().unwrap();
+        let unrelated = dir.path().join("notes.txt");
+        std::fs::write(&unrelated, "keep").unwrap();
+        for name in ["20260101T000000.000Z", "20260102T000000.000Z"] {
+            std::fs::write(
+                dir.path().join(format!("{}{}.json", FILE_PREFIX, name)),
+                "{}",
+            )
+            .unwrap();
+        }
+        prune(dir.path(), 1).await.unwrap();
+
+        let mut names: Vec<String> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                "notes.txt".to_string(),
+                format!("{}20260102T000000.000Z.json", FILE_PREFIX)
+            ]
+        );
+    }
+}
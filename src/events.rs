@@ -1,28 +1,266 @@
 use chrono::{DateTime, Utc};
-use serde::Serialize;
-use std::net::Ipv4Addr;
-use tokio::sync::broadcast;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, Weak};
+use std::time::Duration;
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+use tokio::sync::Notify;
 
+use ipnet::Ipv6Net;
+
+use crate::ipv6::RipV6Route;
+use crate::loop_detector::CountToInfinity;
 use crate::metrics::MetricsSnapshot;
-use crate::routing_table::RouteSource;
+use crate::routing_table::{Route, RouteSource};
+use crate::timers::wall_clock;
+
+/// What happens to a stream client that falls more than `capacity` events behind
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LagPolicy {
+    /// Tell the client how many events it missed and carry on
+    #[default]
+    Notify,
+    /// Tell the client how many events it missed and close the stream, so it
+    /// reconnects and reloads its state
+    Disconnect,
+}
+
+/// What the bus does with a new event for a subscriber that already has
+/// `capacity` events waiting
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    /// Drop the subscriber's oldest event; it is told how many it missed
+    #[default]
+    DropOldest,
+    /// Make the publisher wait up to `block_timeout_ms` for the subscriber to
+    /// catch up, then drop the oldest event
+    Block,
+    /// Write further events to a file under `spill_dir`, up to `spill_limit`
+    /// of them, and replay them in order once the subscriber catches up
+    Spill,
+}
+
+/// Event bus settings, under `event_bus`. They apply at startup.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventBusConfig {
+    /// Events buffered for each slow subscriber
+    #[serde(default = "default_capacity")]
+    pub capacity: usize,
+    #[serde(default)]
+    pub lag_policy: LagPolicy,
+    #[serde(default)]
+    pub overflow: OverflowPolicy,
+    /// Longest a publisher waits for room under the `block` policy
+    #[serde(default = "default_block_timeout_ms")]
+    pub block_timeout_ms: u64,
+    /// Directory of the spill files of the `spill` policy
+    #[serde(default = "default_spill_dir")]
+    pub spill_dir: String,
+    /// Events a subscriber may have on disk before further ones are dropped
+    #[serde(default = "default_spill_limit")]
+    pub spill_limit: usize,
+    /// Seconds between keep-alive comments on idle SSE connections
+    #[serde(default = "default_heartbeat_interval")]
+    pub heartbeat_interval: u64,
+}
+
+fn default_capacity() -> usize {
+    256
+}
+
+fn default_block_timeout_ms() -> u64 {
+    100
+}
+
+fn default_spill_dir() -> String {
+    "/var/lib/rust-route/event-spill".to_string()
+}
+
+fn default_spill_limit() -> usize {
+    100_000
+}
+
+fn default_heartbeat_interval() -> u64 {
+    15
+}
+
+impl Default for EventBusConfig {
+    fn default() -> Self {
+        Self {
+            capacity: default_capacity(),
+            lag_policy: LagPolicy::default(),
+            overflow: OverflowPolicy::default(),
+            block_timeout_ms: default_block_timeout_ms(),
+            spill_dir: default_spill_dir(),
+            spill_limit: default_spill_limit(),
+            heartbeat_interval: default_heartbeat_interval(),
+        }
+    }
+}
 
+/// Fans each published event out to a bounded queue per subscriber, so one
+/// slow subscriber only costs itself events
 #[derive(Clone)]
 pub struct EventBus {
-    sender: broadcast::Sender<WebEvent>,
+    shared: Arc<Shared>,
+    clients: Arc<Mutex<HashMap<u64, Arc<ClientEntry>>>>,
+    next_client: Arc<AtomicU64>,
+}
+
+struct Shared {
+    config: EventBusConfig,
+    queues: Mutex<Vec<Weak<Queue>>>,
+    next_queue: AtomicU64,
+}
+
+impl Shared {
+    /// Queues of subscribers that still exist, forgetting the others
+    fn queues(&self) -> Vec<Arc<Queue>> {
+        let mut queues = self
+            .queues
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        queues.retain(|queue| queue.strong_count() > 0);
+        queues.iter().filter_map(Weak::upgrade).collect()
+    }
+}
+
+impl Drop for Shared {
+    /// Subscribers see the bus close once every handle to it is gone
+    fn drop(&mut self) {
+        for queue in self.queues() {
+            queue.lock().closed = true;
+            queue.ready.notify_one();
+        }
+    }
 }
 
 impl EventBus {
     pub fn new(capacity: usize) -> Self {
-        let (sender, _) = broadcast::channel(capacity);
-        Self { sender }
+        Self::with_config(EventBusConfig {
+            capacity,
+            ..EventBusConfig::default()
+        })
+    }
+
+    pub fn with_config(config: EventBusConfig) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                config,
+                queues: Mutex::new(Vec::new()),
+                next_queue: AtomicU64::new(1),
+            }),
+            clients: Arc::new(Mutex::new(HashMap::new())),
+            next_client: Arc::new(AtomicU64::new(1)),
+        }
+    }
+
+    /// Subscribe to every event published from now on; `name` identifies the
+    /// subscriber in [`EventBus::subscribers`]
+    pub fn subscribe(&self, name: &str) -> Subscription {
+        let queue = Arc::new(Queue {
+            id: self.shared.next_queue.fetch_add(1, Ordering::Relaxed),
+            name: name.to_string(),
+            state: Mutex::new(QueueState::default()),
+            ready: Notify::new(),
+            room: Condvar::new(),
+            delivered: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            spilled: AtomicU64::new(0),
+        });
+        self.shared
+            .queues
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(Arc::downgrade(&queue));
+        Subscription { queue }
+    }
+
+    /// Subscribe a stream client that is listed in [`EventBus::clients`]
+    /// until the returned handle is dropped. An empty `filters` list
+    /// receives every event type.
+    pub fn subscribe_client(&self, filters: Vec<String>, lag_policy: LagPolicy) -> EventClient {
+        let id = self.next_client.fetch_add(1, Ordering::Relaxed);
+        let entry = Arc::new(ClientEntry {
+            connected_at: Utc::now(),
+            filters,
+            delivered: AtomicU64::new(0),
+            lag_events: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        });
+        self.client_map().insert(id, Arc::clone(&entry));
+        EventClient {
+            id,
+            receiver: self.subscribe(&format!("client-{}", id)),
+            entry,
+            lag_policy,
+            clients: Arc::clone(&self.clients),
+            closed: false,
+        }
     }
 
-    pub fn subscribe(&self) -> broadcast::Receiver<WebEvent> {
-        self.sender.subscribe()
+    /// Connected stream clients and their delivery counters
+    pub fn clients(&self) -> EventClientsReport {
+        let mut clients: Vec<EventClientInfo> = self
+            .client_map()
+            .iter()
+            .map(|(id, entry)| EventClientInfo {
+                id: *id,
+                connected_at: entry.connected_at,
+                filters: entry.filters.clone(),
+                delivered: entry.delivered.load(Ordering::Relaxed),
+                lag_events: entry.lag_events.load(Ordering::Relaxed),
+                dropped: entry.dropped.load(Ordering::Relaxed),
+            })
+            .collect();
+        clients.sort_by_key(|client| client.id);
+        let subscribers = self.subscribers();
+        EventClientsReport {
+            capacity: self.shared.config.capacity,
+            overflow: self.shared.config.overflow,
+            queued: subscribers.iter().map(|s| s.queued).max().unwrap_or(0),
+            subscribers: subscribers.len(),
+            clients,
+        }
+    }
+
+    /// Every subscriber, internal consumers and stream clients alike, with
+    /// its backlog and what it lost, in subscription order
+    pub fn subscribers(&self) -> Vec<SubscriberStats> {
+        self.shared
+            .queues()
+            .iter()
+            .map(|queue| {
+                let queued = queue.lock().events.len();
+                SubscriberStats {
+                    name: queue.name.clone(),
+                    queued,
+                    delivered: queue.delivered.load(Ordering::Relaxed),
+                    dropped: queue.dropped.load(Ordering::Relaxed),
+                    spilled: queue.spilled.load(Ordering::Relaxed),
+                }
+            })
+            .collect()
+    }
+
+    fn client_map(&self) -> std::sync::MutexGuard<'_, HashMap<u64, Arc<ClientEntry>>> {
+        self.clients
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
     }
 
     pub fn publish(&self, event: WebEvent) {
-        let _ = self.sender.send(event);
+        for queue in self.shared.queues() {
+            queue.push(event.clone(), &self.shared.config);
+        }
     }
 
     pub fn publish_activity<S: Into<String>>(&self, level: ActivityLevel, message: S) {
@@ -35,20 +273,347 @@ impl EventBus {
     }
 }
 
+/// Backlog and losses of one subscriber
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SubscriberStats {
+    pub name: String,
+    /// Events waiting in memory
+    pub queued: usize,
+    pub delivered: u64,
+    /// Events lost because the subscriber fell behind
+    pub dropped: u64,
+    /// Events that went through a spill file
+    pub spilled: u64,
+}
+
+struct Queue {
+    id: u64,
+    name: String,
+    state: Mutex<QueueState>,
+    /// Wakes the subscriber when an event arrives or the bus closes
+    ready: Notify,
+    /// Wakes publishers blocked on a full queue
+    room: Condvar,
+    delivered: AtomicU64,
+    dropped: AtomicU64,
+    spilled: AtomicU64,
+}
+
+#[derive(Default)]
+struct QueueState {
+    events: VecDeque<WebEvent>,
+    /// Events dropped since the subscriber was last told
+    missed: u64,
+    /// Events newer than those in memory, while spilling
+    spill: Option<SpillFile>,
+    closed: bool,
+}
+
+struct SpillFile {
+    path: PathBuf,
+    writer: File,
+    reader: BufReader<File>,
+    pending: usize,
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+impl Queue {
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn push(&self, event: WebEvent, config: &EventBusConfig) {
+        let capacity = config.capacity.max(1);
+        let mut state = self.lock();
+        let spilling = state.spill.is_some()
+            || (config.overflow == OverflowPolicy::Spill && state.events.len() >= capacity);
+        if spilling {
+            // Once events are on disk, newer ones follow them there to keep the order
+            if let Err(err) = self.spill(&mut state, &event, config) {
+                warn!("Event subscriber {} dropped an event: {}", self.name, err);
+                state.missed += 1;
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        } else {
+            if config.overflow == OverflowPolicy::Block && state.events.len() >= capacity {
+                let timeout = Duration::from_millis(config.block_timeout_ms);
+                state = self
+                    .room
+                    .wait_timeout_while(state, timeout, |state| {
+                        state.events.len() >= capacity && !state.closed
+                    })
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .0;
+            }
+            if state.events.len() >= capacity {
+                state.events.pop_front();
+                state.missed += 1;
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            state.events.push_back(event);
+        }
+        drop(state);
+        self.ready.notify_one();
+    }
+
+    fn spill(
+        &self,
+        state: &mut QueueState,
+        event: &WebEvent,
+        config: &EventBusConfig,
+    ) -> std::io::Result<()> {
+        if state.spill.is_none() {
+            fs::create_dir_all(&config.spill_dir)?;
+            let file_name: String = self
+                .name
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+                .collect();
+            let path = PathBuf::from(&config.spill_dir).join(format!(
+                "{}-{}-{}.jsonl",
+                std::process::id(),
+                self.id,
+                file_name
+            ));
+            let writer = File::create(&path)?;
+            let reader = BufReader::new(File::open(&path)?);
+            state.spill = Some(SpillFile {
+                path,
+                writer,
+                reader,
+                pending: 0,
+            });
+        }
+        let spill = state.spill.as_mut().expect("spill file was just opened");
+        if spill.pending >= config.spill_limit {
+            return Err(std::io::Error::other("spill limit reached"));
+        }
+        let mut line = serde_json::to_vec(event)?;
+        line.push(b'\n');
+        spill.writer.write_all(&line)?;
+        spill.pending += 1;
+        self.spilled.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Oldest spilled event, removing the file once it is drained
+    fn unspill(&self, state: &mut QueueState) -> Option<WebEvent> {
+        while let Some(spill) = state.spill.as_mut() {
+            if spill.pending == 0 {
+                state.spill = None;
+                break;
+            }
+            let mut line = String::new();
+            let read = spill.reader.read_line(&mut line);
+            spill.pending -= 1;
+            match read.map(|_| serde_json::from_str::<WebEvent>(&line)) {
+                Ok(Ok(event)) => return Some(event),
+                Ok(Err(err)) => {
+                    warn!(
+                        "Event subscriber {} lost a spilled event: {}",
+                        self.name, err
+                    );
+                    state.missed += 1;
+                }
+                Err(err) => {
+                    warn!(
+                        "Event subscriber {} lost its spill file: {}",
+                        self.name, err
+                    );
+                    state.missed += spill.pending as u64 + 1;
+                    state.spill = None;
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Events for one subscriber, gone from the bus once dropped
+pub struct Subscription {
+    queue: Arc<Queue>,
+}
+
+impl Subscription {
+    /// Next event, a count of events missed since the last call, or the
+    /// end of the bus once every event before it was received
+    pub async fn recv(&mut self) -> Result<WebEvent, RecvError> {
+        loop {
+            match self.try_recv() {
+                Ok(event) => return Ok(event),
+                Err(TryRecvError::Lagged(missed)) => return Err(RecvError::Lagged(missed)),
+                Err(TryRecvError::Closed) => return Err(RecvError::Closed),
+                Err(TryRecvError::Empty) => self.queue.ready.notified().await,
+            }
+        }
+    }
+
+    pub fn try_recv(&mut self) -> Result<WebEvent, TryRecvError> {
+        let mut state = self.queue.lock();
+        if state.missed > 0 {
+            return Err(TryRecvError::Lagged(std::mem::take(&mut state.missed)));
+        }
+        let event = match state.events.pop_front() {
+            Some(event) => {
+                self.queue.room.notify_all();
+                Some(event)
+            }
+            None => self.queue.unspill(&mut state),
+        };
+        match event {
+            Some(event) => {
+                self.queue.delivered.fetch_add(1, Ordering::Relaxed);
+                Ok(event)
+            }
+            // Losses found while reading the spill file come first
+            None if state.missed > 0 => {
+                Err(TryRecvError::Lagged(std::mem::take(&mut state.missed)))
+            }
+            None if state.closed => Err(TryRecvError::Closed),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ClientEntry {
+    connected_at: DateTime<Utc>,
+    filters: Vec<String>,
+    delivered: AtomicU64,
+    lag_events: AtomicU64,
+    dropped: AtomicU64,
+}
+
+/// What a stream client receives next
+#[derive(Debug, Clone)]
+pub enum Delivery {
+    Event(Box<WebEvent>),
+    /// The client fell behind and this many events were dropped for it
+    Lagged(u64),
+}
+
+/// Subscription of one stream client; unregisters itself when dropped
+pub struct EventClient {
+    id: u64,
+    receiver: Subscription,
+    entry: Arc<ClientEntry>,
+    lag_policy: LagPolicy,
+    clients: Arc<Mutex<HashMap<u64, Arc<ClientEntry>>>>,
+    closed: bool,
+}
+
+impl EventClient {
+    /// Next event matching the client's filters, a lag notice, or `None`
+    /// once the stream is over
+    pub async fn next(&mut self) -> Option<Delivery> {
+        if self.closed {
+            return None;
+        }
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) => {
+                    if !self.entry.filters.is_empty()
+                        && !self.entry.filters.iter().any(|f| f == event.kind())
+                    {
+                        continue;
+                    }
+                    self.entry.delivered.fetch_add(1, Ordering::Relaxed);
+                    return Some(Delivery::Event(Box::new(event)));
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    self.entry.lag_events.fetch_add(1, Ordering::Relaxed);
+                    self.entry.dropped.fetch_add(skipped, Ordering::Relaxed);
+                    self.closed = self.lag_policy == LagPolicy::Disconnect;
+                    return Some(Delivery::Lagged(skipped));
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+impl Drop for EventClient {
+    fn drop(&mut self) {
+        self.clients
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .remove(&self.id);
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EventClientInfo {
+    pub id: u64,
+    pub connected_at: DateTime<Utc>,
+    /// Event types the client asked for; empty means all
+    pub filters: Vec<String>,
+    pub delivered: u64,
+    /// Times the client fell behind
+    pub lag_events: u64,
+    /// Events dropped because the client fell behind
+    pub dropped: u64,
+}
+
 #[derive(Debug, Clone, Serialize)]
+pub struct EventClientsReport {
+    pub capacity: usize,
+    pub overflow: OverflowPolicy,
+    /// Events currently buffered for the slowest subscriber
+    pub queued: usize,
+    /// All receivers of the bus, including internal consumers such as hooks
+    pub subscribers: usize,
+    pub clients: Vec<EventClientInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", content = "data")]
 pub enum WebEvent {
     Metrics(MetricsEvent),
     Route(RouteEvent),
+    /// A learned route timed out and is now advertised as unreachable
+    RouteWithdrawn(RouteEvent),
+    Neighbor(NeighborEvent),
+    /// A prefix's metric keeps climbing across updates, the signature of a routing loop
+    CountToInfinity(CountToInfinityEvent),
     Activity(ActivityEvent),
+    /// Net neighbor and reachability changes once the network settled
+    TopologyChanged(TopologyChangedEvent),
+    /// A RIPng route was learned or changed
+    RouteV6(RouteV6Event),
+    /// A RIPng route timed out and is now advertised as unreachable
+    RouteV6Withdrawn(RouteV6Event),
 }
 
-#[derive(Debug, Clone, Serialize)]
+impl WebEvent {
+    /// Name of the event type, as serialized in its `type` field
+    pub fn kind(&self) -> &'static str {
+        match self {
+            WebEvent::Metrics(_) => "Metrics",
+            WebEvent::Route(_) => "Route",
+            WebEvent::RouteWithdrawn(_) => "RouteWithdrawn",
+            WebEvent::Neighbor(_) => "Neighbor",
+            WebEvent::CountToInfinity(_) => "CountToInfinity",
+            WebEvent::Activity(_) => "Activity",
+            WebEvent::TopologyChanged(_) => "TopologyChanged",
+            WebEvent::RouteV6(_) => "RouteV6",
+            WebEvent::RouteV6Withdrawn(_) => "RouteV6Withdrawn",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MetricsEvent {
     pub snapshot: MetricsSnapshot,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RouteEvent {
     pub destination: String,
     pub subnet_mask: String,
@@ -56,6 +621,10 @@ pub struct RouteEvent {
     pub interface: String,
     pub source: RouteSource,
     pub next_hop: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub learned_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_updated: Option<DateTime<Utc>>,
 }
 
 impl RouteEvent {
@@ -74,20 +643,291 @@ impl RouteEvent {
             metric,
             interface,
             source,
+            learned_at: None,
+            last_updated: None,
+        }
+    }
+
+    /// Event for `route`, with the wall-clock times it was learned and last updated
+    pub fn from_route(route: &Route) -> Self {
+        Self {
+            learned_at: Some(wall_clock(route.created_at)),
+            last_updated: Some(wall_clock(route.last_updated)),
+            ..Self::from_parts(
+                route.destination,
+                route.subnet_mask,
+                route.next_hop,
+                route.metric,
+                route.interface.clone(),
+                route.source,
+            )
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
+/// A RIPng route; RIPng neighbors are reported with [`NeighborEvent`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteV6Event {
+    pub prefix: Ipv6Net,
+    pub next_hop: Ipv6Addr,
+    pub metric: u32,
+    pub interface: String,
+    pub learned_from: Ipv6Addr,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl RouteV6Event {
+    pub fn from_route(route: &RipV6Route) -> Self {
+        Self {
+            prefix: route.prefix,
+            next_hop: route.next_hop,
+            metric: route.metric,
+            interface: route.interface.clone(),
+            learned_from: route.learned_from,
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NeighborEvent {
+    pub address: String,
+    pub interface: Option<String>,
+    pub state: NeighborState,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl NeighborEvent {
+    pub fn new(address: IpAddr, interface: Option<String>, state: NeighborState) -> Self {
+        Self {
+            address: address.to_string(),
+            interface,
+            state,
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CountToInfinityEvent {
+    pub destination: String,
+    pub subnet_mask: String,
+    pub interface: String,
+    pub neighbors: Vec<String>,
+    pub metric: u32,
+    /// Consecutive updates that raised the metric
+    pub rises: u32,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl CountToInfinityEvent {
+    pub fn new(report: &CountToInfinity, interface: String) -> Self {
+        Self {
+            destination: report.destination.to_string(),
+            subnet_mask: report.subnet_mask.to_string(),
+            interface,
+            neighbors: report.neighbors.iter().map(|n| n.to_string()).collect(),
+            metric: report.metric,
+            rises: report.rises,
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+/// What changed between two settled states of the network, with changes
+/// that reverted in between left out
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TopologyChangedEvent {
+    pub neighbors_added: Vec<String>,
+    pub neighbors_lost: Vec<String>,
+    /// Prefixes in CIDR notation
+    pub prefixes_reachable: Vec<String>,
+    pub prefixes_unreachable: Vec<String>,
+    /// The changes in one line, e.g. `1 neighbor lost, 3 prefixes unreachable`
+    pub summary: String,
+    /// When the first of these changes happened
+    pub since: DateTime<Utc>,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NeighborState {
+    Up,
+    Down,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ActivityEvent {
     pub level: ActivityLevel,
     pub message: String,
     pub timestamp: DateTime<Utc>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+/// Severity of an activity entry, ordered from least to most severe
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum ActivityLevel {
     Info,
     Warn,
     Error,
 }
+
+impl ActivityLevel {
+    /// Parse a level name, ignoring case
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "info" => Some(ActivityLevel::Info),
+            "warn" | "warning" => Some(ActivityLevel::Warn),
+            "error" => Some(ActivityLevel::Error),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ActivityLevel::Info => "info",
+            ActivityLevel::Warn => "warn",
+            ActivityLevel::Error => "error",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn activity(message: &str) -> WebEvent {
+        WebEvent::Activity(ActivityEvent {
+            level: ActivityLevel::Info,
+            message: message.to_string(),
+            timestamp: Utc::now(),
+        })
+    }
+
+    #[tokio::test]
+    async fn clients_are_tracked_and_filtered() {
+        let bus = EventBus::new(8);
+        let mut client = bus.subscribe_client(vec!["Activity".to_string()], LagPolicy::Notify);
+        bus.publish(WebEvent::Neighbor(NeighborEvent::new(
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            None,
+            NeighborState::Up,
+        )));
+        bus.publish(activity("hello"));
+
+        let Some(Delivery::Event(event)) = client.next().await else {
+            panic!("expected an event");
+        };
+        assert_eq!(event.kind(), "Activity");
+        let report = bus.clients();
+        assert_eq!(report.clients.len(), 1);
+        assert_eq!(report.clients[0].delivered, 1);
+
+        drop(client);
+        assert!(bus.clients().clients.is_empty());
+    }
+
+    #[tokio::test]
+    async fn lagging_clients_are_told_or_disconnected() {
+        let bus = EventBus::new(2);
+        let mut notified = bus.subscribe_client(Vec::new(), LagPolicy::Notify);
+        let mut disconnected = bus.subscribe_client(Vec::new(), LagPolicy::Disconnect);
+        for n in 0..5 {
+            bus.publish(activity(&n.to_string()));
+        }
+
+        assert!(matches!(notified.next().await, Some(Delivery::Lagged(3))));
+        assert!(matches!(notified.next().await, Some(Delivery::Event(_))));
+        assert!(matches!(
+            disconnected.next().await,
+            Some(Delivery::Lagged(3))
+        ));
+        assert!(disconnected.next().await.is_none());
+
+        let report = bus.clients();
+        assert_eq!(report.clients[0].dropped, 3);
+        assert_eq!(report.clients[1].lag_events, 1);
+    }
+
+    #[tokio::test]
+    async fn slow_subscribers_only_lose_their_own_events() {
+        let bus = EventBus::new(2);
+        let mut slow = bus.subscribe("slow");
+        let mut fast = bus.subscribe("fast");
+        for n in 0..3 {
+            bus.publish(activity(&n.to_string()));
+            fast.recv().await.unwrap();
+        }
+
+        assert!(matches!(slow.recv().await, Err(RecvError::Lagged(1))));
+        let stats = bus.subscribers();
+        assert_eq!(stats[0].name, "slow");
+        assert_eq!((stats[0].queued, stats[0].dropped), (2, 1));
+        assert_eq!((stats[1].delivered, stats[1].dropped), (3, 0));
+
+        drop(fast);
+        assert_eq!(bus.subscribers().len(), 1);
+        drop(bus);
+        assert!(slow.recv().await.is_ok());
+        assert!(slow.recv().await.is_ok());
+        assert!(matches!(slow.recv().await, Err(RecvError::Closed)));
+    }
+
+    #[test]
+    fn blocked_publishers_give_up_after_the_timeout() {
+        let bus = EventBus::with_config(EventBusConfig {
+            capacity: 1,
+            overflow: OverflowPolicy::Block,
+            block_timeout_ms: 20,
+            ..EventBusConfig::default()
+        });
+        let mut receiver = bus.subscribe("blocked");
+        bus.publish(activity("first"));
+        let publisher = {
+            let bus = bus.clone();
+            std::thread::spawn(move || bus.publish(activity("second")))
+        };
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(receiver.try_recv().is_ok());
+        publisher.join().unwrap();
+        assert!(receiver.try_recv().is_ok());
+
+        // Nobody makes room this time
+        bus.publish(activity("third"));
+        let started = std::time::Instant::now();
+        bus.publish(activity("fourth"));
+        assert!(started.elapsed() >= Duration::from_millis(20));
+        assert!(matches!(receiver.try_recv(), Err(TryRecvError::Lagged(1))));
+    }
+
+    #[test]
+    fn overflow_spills_to_disk_in_order() {
+        let dir = std::env::temp_dir().join(format!("rust-route-spill-{}", std::process::id()));
+        let bus = EventBus::with_config(EventBusConfig {
+            capacity: 2,
+            overflow: OverflowPolicy::Spill,
+            spill_dir: dir.to_string_lossy().into_owned(),
+            spill_limit: 3,
+            ..EventBusConfig::default()
+        });
+        let mut receiver = bus.subscribe("spilling");
+        for n in 0..6 {
+            bus.publish(activity(&n.to_string()));
+        }
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        let stats = &bus.subscribers()[0];
+        assert_eq!((stats.queued, stats.spilled, stats.dropped), (2, 3, 1));
+
+        assert!(matches!(receiver.try_recv(), Err(TryRecvError::Lagged(1))));
+        let mut messages = Vec::new();
+        while let Ok(WebEvent::Activity(event)) = receiver.try_recv() {
+            messages.push(event.message);
+        }
+        assert_eq!(messages, ["0", "1", "2", "3", "4"]);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+
+        // Back to memory once the file is drained
+        bus.publish(activity("5"));
+        assert!(receiver.try_recv().is_ok());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
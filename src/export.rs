@@ -0,0 +1,190 @@
+//! Offline exports of the metrics history and the activity feed.
+//!
+//! The CSV renderings are meant for spreadsheets: one header row, RFC 3339
+//! timestamps in UTC, and empty cells for values that were not known at the
+//! time.
+
+use chrono::{DateTime, Duration, Utc};
+use std::fmt::Write;
+
+use crate::activity::ActivityRecord;
+use crate::metrics_history::MetricsSample;
+
+/// Output formats of the history exports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "csv" => Some(Self::Csv),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+        }
+    }
+}
+
+/// Read an RFC 3339 time, or an age before `now` such as `90s`, `30m`, `2h`
+/// or `7d`
+pub fn parse_time(value: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Some(time.with_timezone(&Utc));
+    }
+    let split = value.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = value.split_at(split);
+    let amount: i64 = amount.parse().ok()?;
+    let age = match unit {
+        "s" => Duration::try_seconds(amount),
+        "m" => Duration::try_minutes(amount),
+        "h" => Duration::try_hours(amount),
+        "d" => Duration::try_days(amount),
+        _ => None,
+    }?;
+    now.checked_sub_signed(age)
+}
+
+const METRICS_COLUMNS: &[&str] = &[
+    "timestamp",
+    "uptime_seconds",
+    "packets_sent",
+    "packets_received",
+    "packets_dropped",
+    "routing_updates_sent",
+    "routing_updates_received",
+    "route_changes",
+    "queries_answered",
+    "count_to_infinity_detected",
+    "invalid_next_hops",
+    "solicited_responses",
+    "unsolicited_responses",
+    "convergence_time_seconds",
+    "neighbor_count",
+    "route_count",
+    "config_version",
+];
+
+/// Quote `value` if a spreadsheet would otherwise split or misread it
+fn field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn row(out: &mut String, cells: &[String]) {
+    let cells: Vec<String> = cells.iter().map(|cell| field(cell)).collect();
+    let _ = writeln!(out, "{}", cells.join(","));
+}
+
+/// One row per sample, oldest first as given
+pub fn metrics_csv(samples: &[MetricsSample]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "{}", METRICS_COLUMNS.join(","));
+    for sample in samples {
+        row(
+            &mut out,
+            &[
+                sample.timestamp.to_rfc3339(),
+                sample.uptime_seconds.to_string(),
+                sample.packets_sent.to_string(),
+                sample.packets_received.to_string(),
+                sample.packets_dropped.to_string(),
+                sample.routing_updates_sent.to_string(),
+                sample.routing_updates_received.to_string(),
+                sample.route_changes.to_string(),
+                sample.queries_answered.to_string(),
+                sample.count_to_infinity_detected.to_string(),
+                sample.invalid_next_hops.to_string(),
+                sample.solicited_responses.to_string(),
+                sample.unsolicited_responses.to_string(),
+                sample
+                    .convergence_time_seconds
+                    .map(|seconds| seconds.to_string())
+                    .unwrap_or_default(),
+                sample.neighbor_count.to_string(),
+                sample.route_count.to_string(),
+                sample.config_version.to_string(),
+            ],
+        );
+    }
+    out
+}
+
+/// One row per activity record, in the order given
+pub fn activity_csv(records: &[ActivityRecord]) -> String {
+    let mut out = String::from("id,timestamp,level,message\n");
+    for record in records {
+        row(
+            &mut out,
+            &[
+                record.id.to_string(),
+                record.timestamp.to_rfc3339(),
+                record.level.as_str().to_string(),
+                record.message.clone(),
+            ],
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::ActivityLevel;
+    use crate::metrics::MetricsSnapshot;
+    use chrono::TimeZone;
+
+    #[test]
+    fn times_are_rfc3339_or_ages() {
+        let now = Utc.with_ymd_and_hms(2026, 3, 2, 14, 0, 0).unwrap();
+        assert_eq!(parse_time("2026-03-02T15:00:00+01:00", now), Some(now));
+        assert_eq!(parse_time("2h", now), Some(now - Duration::hours(2)));
+        assert_eq!(parse_time("90s", now), Some(now - Duration::seconds(90)));
+        assert_eq!(parse_time("2w", now), None);
+        assert_eq!(parse_time("h", now), None);
+        assert_eq!(parse_time("yesterday", now), None);
+    }
+
+    #[test]
+    fn renders_rows_under_a_header_and_quotes_awkward_text() {
+        let at = Utc.with_ymd_and_hms(2026, 3, 2, 14, 0, 0).unwrap();
+        let snapshot = MetricsSnapshot {
+            packets_sent: 12,
+            neighbor_count: 2,
+            route_count: 5,
+            ..MetricsSnapshot::default()
+        };
+        let csv = metrics_csv(&[MetricsSample::new(&snapshot, at)]);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].split(',').count(), METRICS_COLUMNS.len());
+        assert_eq!(
+            lines[1],
+            "2026-03-02T14:00:00+00:00,0,12,0,0,0,0,0,0,0,0,0,0,,2,5,0"
+        );
+
+        let record = ActivityRecord {
+            id: 7,
+            level: ActivityLevel::Warn,
+            message: "Neighbor 192.0.2.1 lost, \"eth0\" down".to_string(),
+            timestamp: at,
+        };
+        assert_eq!(
+            activity_csv(&[record]),
+            "id,timestamp,level,message\n\
+             7,2026-03-02T14:00:00+00:00,warn,\"Neighbor 192.0.2.1 lost, \"\"eth0\"\" down\"\n"
+        );
+    }
+}
@@ -0,0 +1,540 @@
+//! Fleet view: one router aggregating the APIs of its peers.
+//!
+//! With `fleet.enabled`, the router queries every API in `fleet.peers` on
+//! behalf of its clients and answers with the combined result, so a small
+//! lab can be watched from a single dashboard:
+//!
+//! - `GET /api/fleet/status` summarizes each member, this router included,
+//!   and says which peers did not answer;
+//! - `GET /api/fleet/routes` lists the routes of all members, each tagged
+//!   with the router holding it;
+//! - `GET /api/fleet/events` streams the events of all members. The router
+//!   keeps a connection to the event stream of each peer, reconnecting
+//!   every `fleet.reconnect_interval` seconds after a failure, and relays
+//!   the peer events with the name of their router.
+//!
+//! Peers are polled only when a client asks; nothing is cached. The event
+//! relays are started with the router, so changing `fleet` takes a restart.
+
+use chrono::{DateTime, Utc};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+use crate::client::ApiClient;
+use crate::compare;
+use crate::web::{AppState, RouteInfo};
+use crate::{RustRouteError, RustRouteResult};
+
+/// Events relayed from peers that slow fleet stream clients may fall behind by
+const RELAY_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FleetConfig {
+    pub enabled: bool,
+    pub peers: Vec<FleetPeer>,
+    /// Seconds to wait for each peer's answer
+    pub timeout_seconds: u64,
+    /// Seconds between attempts to reach a peer's event stream
+    pub reconnect_interval: u64,
+}
+
+impl Default for FleetConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            peers: Vec::new(),
+            timeout_seconds: 5,
+            reconnect_interval: 10,
+        }
+    }
+}
+
+impl FleetConfig {
+    /// Problems with the settings, as configuration errors
+    pub fn errors(&self, router_id: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+        if !self.enabled {
+            return errors;
+        }
+        if self.timeout_seconds == 0 {
+            errors.push("fleet.timeout_seconds must be at least 1".to_string());
+        }
+        let mut names: Vec<&str> = Vec::new();
+        for peer in &self.peers {
+            if peer.name.trim().is_empty() {
+                errors.push(format!("fleet peer {} has no name", peer.url));
+            } else if peer.name == router_id {
+                errors.push(format!(
+                    "fleet peer name {} is this router's router_id",
+                    peer.name
+                ));
+            } else if names.contains(&peer.name.as_str()) {
+                errors.push(format!(
+                    "fleet peer name {} is used more than once",
+                    peer.name
+                ));
+            }
+            names.push(&peer.name);
+            if let Err(err) = compare::validate_peer_url(&peer.url) {
+                errors.push(format!("fleet peer {}: {}", peer.name, err));
+            }
+        }
+        errors
+    }
+}
+
+/// A router whose API the fleet view includes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FleetPeer {
+    /// Shown as `router` in the combined results
+    pub name: String,
+    /// Base URL of the peer's web interface, such as `http://10.0.0.2:8080`
+    pub url: String,
+    /// Bearer token for peers that require authentication; a read-only
+    /// account is enough
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+impl FleetPeer {
+    fn client(&self) -> ApiClient {
+        ApiClient::new(&self.url, self.token.clone())
+    }
+}
+
+/// State of the relay of a peer's event stream
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EventLink {
+    pub connected: bool,
+    /// When the stream last connected or failed
+    pub since: Option<DateTime<Utc>>,
+    pub error: Option<String>,
+}
+
+/// The headline numbers of a member's `/api/status`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MemberStatus {
+    pub router_id: String,
+    pub version: String,
+    pub uptime_seconds: u64,
+    pub route_count: u64,
+    pub neighbor_count: u64,
+}
+
+impl MemberStatus {
+    /// Pick the summary out of a peer's status, tolerating peers of other
+    /// versions that lack some of the fields
+    fn from_status(status: &serde_json::Value) -> Self {
+        let text = |value: &serde_json::Value| value.as_str().unwrap_or_default().to_string();
+        let number = |value: &serde_json::Value| value.as_u64().unwrap_or_default();
+        Self {
+            router_id: text(&status["router_id"]),
+            version: text(&status["version"]),
+            uptime_seconds: number(&status["uptime_seconds"]),
+            route_count: number(&status["route_count"]),
+            neighbor_count: number(&status["router_stats"]["neighbor_count"]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FleetMember {
+    pub name: String,
+    /// `None` for this router
+    pub url: Option<String>,
+    pub reachable: bool,
+    pub error: Option<String>,
+    pub status: Option<MemberStatus>,
+    /// Relay of the peer's events; `None` for this router
+    pub events: Option<EventLink>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FleetStatus {
+    pub members: Vec<FleetMember>,
+    pub reachable: usize,
+    pub unreachable: usize,
+}
+
+/// A route and the fleet member holding it
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FleetRoute {
+    pub router: String,
+    #[serde(flatten)]
+    pub route: RouteInfo,
+}
+
+/// A member that did not answer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemberError {
+    pub router: String,
+    pub error: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FleetRoutes {
+    pub routes: Vec<FleetRoute>,
+    pub unreachable: Vec<MemberError>,
+}
+
+/// An event of a fleet member, as sent on `/api/fleet/events`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FleetEvent {
+    pub router: String,
+    /// The event as the member serialized it; peers of other versions may
+    /// send types this router does not know
+    pub event: serde_json::Value,
+}
+
+/// Relayed peer events and the state of each relay
+#[derive(Debug, Clone)]
+pub struct Fleet {
+    sender: broadcast::Sender<FleetEvent>,
+    links: Arc<Mutex<HashMap<String, EventLink>>>,
+}
+
+impl Default for Fleet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Fleet {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(RELAY_CAPACITY);
+        Self {
+            sender,
+            links: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Peer events relayed from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<FleetEvent> {
+        self.sender.subscribe()
+    }
+
+    fn links(&self) -> std::sync::MutexGuard<'_, HashMap<String, EventLink>> {
+        self.links
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn link(&self, peer: &str) -> EventLink {
+        self.links().get(peer).cloned().unwrap_or_default()
+    }
+
+    fn set_link(&self, peer: &str, error: Option<String>) {
+        self.links().insert(
+            peer.to_string(),
+            EventLink {
+                connected: error.is_none(),
+                since: Some(Utc::now()),
+                error,
+            },
+        );
+    }
+}
+
+/// Answer of `client` for `path` within `timeout`
+async fn query<T: serde::de::DeserializeOwned>(
+    client: &ApiClient,
+    path: &str,
+    timeout: Duration,
+) -> RustRouteResult<T> {
+    tokio::time::timeout(timeout, client.get(path))
+        .await
+        .map_err(|_| RustRouteError::NetworkError(format!("{} timed out", path)))?
+}
+
+/// Ask every peer for `path` at once; results are in `peers` order
+async fn query_peers<T>(
+    peers: &[FleetPeer],
+    path: &'static str,
+    timeout: Duration,
+) -> Vec<RustRouteResult<T>>
+where
+    T: serde::de::DeserializeOwned + Send + 'static,
+{
+    let handles: Vec<_> = peers
+        .iter()
+        .map(|peer| {
+            let client = peer.client();
+            tokio::spawn(async move { query(&client, path, timeout).await })
+        })
+        .collect();
+    let mut results = Vec::with_capacity(handles.len());
+    for handle in handles {
+        results.push(handle.await.unwrap_or_else(|err| {
+            Err(RustRouteError::NetworkError(format!(
+                "query task failed: {}",
+                err
+            )))
+        }));
+    }
+    results
+}
+
+/// Summaries of this router and every peer
+pub async fn status(state: &AppState) -> FleetStatus {
+    let config = state.config_manager.get_config().await;
+    let local = {
+        let router = state.router.read().await;
+        let stats = router.statistics().await;
+        MemberStatus {
+            router_id: config.router_id.clone(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            uptime_seconds: stats.uptime_seconds,
+            route_count: stats.route_count as u64,
+            neighbor_count: stats.neighbor_count as u64,
+        }
+    };
+
+    let mut members = vec![FleetMember {
+        name: config.router_id.clone(),
+        url: None,
+        reachable: true,
+        error: None,
+        status: Some(local),
+        events: None,
+    }];
+    let timeout = Duration::from_secs(config.fleet.timeout_seconds.max(1));
+    let answers: Vec<RustRouteResult<serde_json::Value>> =
+        query_peers(&config.fleet.peers, "/api/status", timeout).await;
+    for (peer, answer) in config.fleet.peers.iter().zip(answers) {
+        let (status, error) = match answer {
+            Ok(status) => (Some(MemberStatus::from_status(&status)), None),
+            Err(err) => (None, Some(err.to_string())),
+        };
+        members.push(FleetMember {
+            name: peer.name.clone(),
+            url: Some(peer.url.clone()),
+            reachable: status.is_some(),
+            error,
+            status,
+            events: Some(state.fleet.link(&peer.name)),
+        });
+    }
+
+    let reachable = members.iter().filter(|member| member.reachable).count();
+    FleetStatus {
+        unreachable: members.len() - reachable,
+        reachable,
+        members,
+    }
+}
+
+/// The routes of this router and every peer that answered
+pub async fn routes(state: &AppState) -> FleetRoutes {
+    let config = state.config_manager.get_config().await;
+    let mut routes: Vec<FleetRoute> = state
+        .routing_table
+        .read()
+        .await
+        .snapshot()
+        .into_iter()
+        .map(|route| FleetRoute {
+            router: config.router_id.clone(),
+            route: RouteInfo::from(route),
+        })
+        .collect();
+
+    let mut unreachable = Vec::new();
+    let timeout = Duration::from_secs(config.fleet.timeout_seconds.max(1));
+    let answers: Vec<RustRouteResult<Vec<RouteInfo>>> =
+        query_peers(&config.fleet.peers, "/api/routes?resolve=false", timeout).await;
+    for (peer, answer) in config.fleet.peers.iter().zip(answers) {
+        match answer {
+            Ok(peer_routes) => routes.extend(peer_routes.into_iter().map(|route| FleetRoute {
+                router: peer.name.clone(),
+                route,
+            })),
+            Err(err) => unreachable.push(MemberError {
+                router: peer.name.clone(),
+                error: err.to_string(),
+            }),
+        }
+    }
+    FleetRoutes {
+        routes,
+        unreachable,
+    }
+}
+
+/// Splits a `text/event-stream` body into events
+#[derive(Debug, Default)]
+pub struct SseParser {
+    pending: Vec<u8>,
+    event: Option<String>,
+    data: Vec<String>,
+}
+
+impl SseParser {
+    /// Feed the next chunk of the body and return the events it completed,
+    /// as their `event` name, if any, and data. Comments such as keep-alives
+    /// are skipped.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<(Option<String>, String)> {
+        self.pending.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        while let Some(end) = self.pending.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+            if line.is_empty() {
+                if !self.data.is_empty() {
+                    events.push((self.event.take(), self.data.join("\n")));
+                    self.data.clear();
+                }
+                self.event = None;
+            } else if let Some(value) = field(line, "data") {
+                self.data.push(value.to_string());
+            } else if let Some(value) = field(line, "event") {
+                self.event = Some(value.to_string());
+            }
+        }
+        events
+    }
+}
+
+/// Value of `line` if it sets the SSE field `name`
+fn field<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let value = line.strip_prefix(name)?.strip_prefix(':')?;
+    Some(value.strip_prefix(' ').unwrap_or(value))
+}
+
+/// Relay the event stream of `peer` until it ends or fails
+async fn relay_once(peer: &FleetPeer, fleet: &Fleet) -> RustRouteResult<()> {
+    let url = format!("{}/api/events", peer.url.trim_end_matches('/'));
+    let mut request = reqwest::Client::new().get(&url);
+    if let Some(token) = &peer.token {
+        request = request.bearer_auth(token);
+    }
+    let mut response = request
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| RustRouteError::NetworkError(format!("{}: {}", url, e)))?;
+
+    info!("Relaying events of fleet peer {}", peer.name);
+    fleet.set_link(&peer.name, None);
+    let mut parser = SseParser::default();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| RustRouteError::NetworkError(format!("{}: {}", url, e)))?
+    {
+        for (name, data) in parser.push(&chunk) {
+            // Named events, such as `lagged`, are about the stream itself
+            if name.is_some() {
+                continue;
+            }
+            match serde_json::from_str(&data) {
+                Ok(event) => {
+                    let _ = fleet.sender.send(FleetEvent {
+                        router: peer.name.clone(),
+                        event,
+                    });
+                }
+                Err(err) => debug!("Ignoring event from {}: {}", peer.name, err),
+            }
+        }
+    }
+    Err(RustRouteError::NetworkError(format!(
+        "{} closed the stream",
+        url
+    )))
+}
+
+/// Start relaying the events of each peer, if fleet mode is enabled
+pub fn spawn(config: &FleetConfig, fleet: Fleet) {
+    if !config.enabled {
+        return;
+    }
+    let retry = Duration::from_secs(config.reconnect_interval.max(1));
+    for peer in config.peers.clone() {
+        let fleet = fleet.clone();
+        tokio::spawn(async move {
+            loop {
+                if let Err(err) = relay_once(&peer, &fleet).await {
+                    warn!("Event stream of fleet peer {} lost: {}", peer.name, err);
+                    fleet.set_link(&peer.name, Some(err.to_string()));
+                }
+                tokio::time::sleep(retry).await;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_streams_are_split_into_events() {
+        let mut parser = SseParser::default();
+        assert!(parser.push(b"data: {\"type\":").is_empty());
+        let events =
+            parser.push(b"\"Metrics\"}\n\n:keepalive\n\nevent: lagged\r\ndata: {}\r\n\r\n");
+        assert_eq!(
+            events,
+            vec![
+                (None, "{\"type\":\"Metrics\"}".to_string()),
+                (Some("lagged".to_string()), "{}".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn peer_status_is_summarized() {
+        let status = serde_json::json!({
+            "router_id": "r2",
+            "version": "0.3.0",
+            "uptime_seconds": 42,
+            "route_count": 7,
+            "router_stats": {"neighbor_count": 2}
+        });
+        assert_eq!(
+            MemberStatus::from_status(&status),
+            MemberStatus {
+                router_id: "r2".to_string(),
+                version: "0.3.0".to_string(),
+                uptime_seconds: 42,
+                route_count: 7,
+                neighbor_count: 2,
+            }
+        );
+        assert_eq!(
+            MemberStatus::from_status(&serde_json::json!({})),
+            MemberStatus::default()
+        );
+    }
+
+    #[test]
+    fn peers_need_distinct_names_and_http_urls() {
+        let peer = |name: &str, url: &str| FleetPeer {
+            name: name.to_string(),
+            url: url.to_string(),
+            token: None,
+        };
+        let config = FleetConfig {
+            enabled: true,
+            peers: vec![
+                peer("r2", "http://10.0.0.2:8080"),
+                peer("r2", "http://10.0.0.3:8080"),
+                peer("r1", "10.0.0.4:8080"),
+            ],
+            ..FleetConfig::default()
+        };
+        assert_eq!(config.errors("r1").len(), 3);
+        assert!(FleetConfig {
+            enabled: false,
+            ..config
+        }
+        .errors("r1")
+        .is_empty());
+    }
+}
@@ -0,0 +1,1477 @@
+//! gNMI management interface.
+//!
+//! `RouterConfig` is exposed as a YANG-style tree whose node names are the
+//! config fields in kebab-case. Lists are selected by key, e.g.
+//! `/interfaces/interface[name=eth0]/config/cost`; `config` containers are
+//! accepted but optional. Operational data lives under `/state` and is
+//! read-only. Set requests go through `ConfigManager::update_config`, so they
+//! are validated and persisted exactly like `PUT /api/config`.
+
+// Every RPC reports errors as `tonic::Status`, which is large by design.
+#![allow(clippy::result_large_err)]
+
+use async_stream::stream;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio_stream::Stream;
+use tonic::codegen::{empty_body, http, Body, BoxFuture, Context, Poll, Service, StdError};
+use tonic::metadata::MetadataMap;
+use tonic::{Code, Request, Response, Status, Streaming};
+
+use crate::auth::{require_permission, AuthError, LoginRequest, UserRole};
+use crate::config_manager::RouterConfig;
+use crate::events::ActivityLevel;
+use crate::listen;
+use crate::protocol::PacketCodec;
+use crate::web::AppState;
+use crate::{RustRouteError, RustRouteResult};
+
+use self::proto::*;
+
+const GNMI_VERSION: &str = "0.10.0";
+const DEFAULT_SAMPLE_INTERVAL: Duration = Duration::from_secs(10);
+const MIN_SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+const ON_CHANGE_POLL: Duration = Duration::from_secs(1);
+
+/// gNMI server configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GnmiConfig {
+    pub enabled: bool,
+    pub bind_address: String,
+    pub port: u16,
+}
+
+impl Default for GnmiConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind_address: "127.0.0.1".to_string(),
+            port: 9339,
+        }
+    }
+}
+
+/// Subset of the OpenConfig `gnmi.proto` (v0.10.0) messages used by the server
+pub mod proto {
+    use std::collections::HashMap;
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct PathElem {
+        #[prost(string, tag = "1")]
+        pub name: String,
+        #[prost(map = "string, string", tag = "2")]
+        pub key: HashMap<String, String>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Path {
+        #[prost(string, tag = "2")]
+        pub origin: String,
+        #[prost(message, repeated, tag = "3")]
+        pub elem: Vec<PathElem>,
+        #[prost(string, tag = "4")]
+        pub target: String,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct TypedValue {
+        #[prost(oneof = "typed_value::Value", tags = "1, 2, 3, 4, 5, 10, 11, 12, 14")]
+        pub value: Option<typed_value::Value>,
+    }
+
+    pub mod typed_value {
+        #[derive(Clone, PartialEq, ::prost::Oneof)]
+        pub enum Value {
+            #[prost(string, tag = "1")]
+            StringVal(String),
+            #[prost(int64, tag = "2")]
+            IntVal(i64),
+            #[prost(uint64, tag = "3")]
+            UintVal(u64),
+            #[prost(bool, tag = "4")]
+            BoolVal(bool),
+            #[prost(bytes = "vec", tag = "5")]
+            BytesVal(Vec<u8>),
+            #[prost(bytes = "vec", tag = "10")]
+            JsonVal(Vec<u8>),
+            #[prost(bytes = "vec", tag = "11")]
+            JsonIetfVal(Vec<u8>),
+            #[prost(string, tag = "12")]
+            AsciiVal(String),
+            #[prost(double, tag = "14")]
+            DoubleVal(f64),
+        }
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Update {
+        #[prost(message, optional, tag = "1")]
+        pub path: Option<Path>,
+        #[prost(message, optional, tag = "3")]
+        pub val: Option<TypedValue>,
+        #[prost(uint32, tag = "4")]
+        pub duplicates: u32,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Notification {
+        #[prost(int64, tag = "1")]
+        pub timestamp: i64,
+        #[prost(message, optional, tag = "2")]
+        pub prefix: Option<Path>,
+        #[prost(message, repeated, tag = "4")]
+        pub update: Vec<Update>,
+        #[prost(message, repeated, tag = "5")]
+        pub delete: Vec<Path>,
+        #[prost(bool, tag = "6")]
+        pub atomic: bool,
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Encoding {
+        Json = 0,
+        Bytes = 1,
+        Proto = 2,
+        Ascii = 3,
+        JsonIetf = 4,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ModelData {
+        #[prost(string, tag = "1")]
+        pub name: String,
+        #[prost(string, tag = "2")]
+        pub organization: String,
+        #[prost(string, tag = "3")]
+        pub version: String,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct CapabilityRequest {}
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct CapabilityResponse {
+        #[prost(message, repeated, tag = "1")]
+        pub supported_models: Vec<ModelData>,
+        #[prost(enumeration = "Encoding", repeated, tag = "2")]
+        pub supported_encodings: Vec<i32>,
+        #[prost(string, tag = "3")]
+        pub gnmi_version: String,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct GetRequest {
+        #[prost(message, optional, tag = "1")]
+        pub prefix: Option<Path>,
+        #[prost(message, repeated, tag = "2")]
+        pub path: Vec<Path>,
+        #[prost(enumeration = "get_request::DataType", tag = "3")]
+        pub r#type: i32,
+        #[prost(enumeration = "Encoding", tag = "5")]
+        pub encoding: i32,
+        #[prost(message, repeated, tag = "6")]
+        pub use_models: Vec<ModelData>,
+    }
+
+    pub mod get_request {
+        #[derive(
+            Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration,
+        )]
+        #[repr(i32)]
+        pub enum DataType {
+            All = 0,
+            Config = 1,
+            State = 2,
+            Operational = 3,
+        }
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct GetResponse {
+        #[prost(message, repeated, tag = "1")]
+        pub notification: Vec<Notification>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct SetRequest {
+        #[prost(message, optional, tag = "1")]
+        pub prefix: Option<Path>,
+        #[prost(message, repeated, tag = "2")]
+        pub delete: Vec<Path>,
+        #[prost(message, repeated, tag = "3")]
+        pub replace: Vec<Update>,
+        #[prost(message, repeated, tag = "4")]
+        pub update: Vec<Update>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct UpdateResult {
+        #[prost(message, optional, tag = "2")]
+        pub path: Option<Path>,
+        #[prost(enumeration = "update_result::Operation", tag = "4")]
+        pub op: i32,
+    }
+
+    pub mod update_result {
+        #[derive(
+            Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration,
+        )]
+        #[repr(i32)]
+        pub enum Operation {
+            Invalid = 0,
+            Delete = 1,
+            Replace = 2,
+            Update = 3,
+        }
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct SetResponse {
+        #[prost(message, optional, tag = "1")]
+        pub prefix: Option<Path>,
+        #[prost(message, repeated, tag = "2")]
+        pub response: Vec<UpdateResult>,
+        #[prost(int64, tag = "4")]
+        pub timestamp: i64,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Poll {}
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct SubscribeRequest {
+        #[prost(oneof = "subscribe_request::Request", tags = "1, 3")]
+        pub request: Option<subscribe_request::Request>,
+    }
+
+    pub mod subscribe_request {
+        #[derive(Clone, PartialEq, ::prost::Oneof)]
+        pub enum Request {
+            #[prost(message, tag = "1")]
+            Subscribe(super::SubscriptionList),
+            #[prost(message, tag = "3")]
+            Poll(super::Poll),
+        }
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct SubscribeResponse {
+        #[prost(oneof = "subscribe_response::Response", tags = "1, 3")]
+        pub response: Option<subscribe_response::Response>,
+    }
+
+    pub mod subscribe_response {
+        #[derive(Clone, PartialEq, ::prost::Oneof)]
+        pub enum Response {
+            #[prost(message, tag = "1")]
+            Update(super::Notification),
+            #[prost(bool, tag = "3")]
+            SyncResponse(bool),
+        }
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct SubscriptionList {
+        #[prost(message, optional, tag = "1")]
+        pub prefix: Option<Path>,
+        #[prost(message, repeated, tag = "2")]
+        pub subscription: Vec<Subscription>,
+        #[prost(enumeration = "subscription_list::Mode", tag = "5")]
+        pub mode: i32,
+        #[prost(bool, tag = "6")]
+        pub allow_aggregation: bool,
+        #[prost(message, repeated, tag = "7")]
+        pub use_models: Vec<ModelData>,
+        #[prost(enumeration = "Encoding", tag = "8")]
+        pub encoding: i32,
+        #[prost(bool, tag = "9")]
+        pub updates_only: bool,
+    }
+
+    pub mod subscription_list {
+        #[derive(
+            Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration,
+        )]
+        #[repr(i32)]
+        pub enum Mode {
+            Stream = 0,
+            Once = 1,
+            Poll = 2,
+        }
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Subscription {
+        #[prost(message, optional, tag = "1")]
+        pub path: Option<Path>,
+        #[prost(enumeration = "SubscriptionMode", tag = "2")]
+        pub mode: i32,
+        #[prost(uint64, tag = "3")]
+        pub sample_interval: u64,
+        #[prost(bool, tag = "4")]
+        pub suppress_redundant: bool,
+        #[prost(uint64, tag = "5")]
+        pub heartbeat_interval: u64,
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum SubscriptionMode {
+        TargetDefined = 0,
+        OnChange = 1,
+        Sample = 2,
+    }
+}
+
+/// Bind the configured gNMI endpoint
+pub async fn bind(config: &GnmiConfig) -> RustRouteResult<TcpListener> {
+    let addr = listen::socket_addr(&config.bind_address, config.port)
+        .map_err(|e| RustRouteError::ConfigError(format!("gnmi.bind_address: {}", e)))?;
+    listen::tcp(addr).map_err(|e| {
+        RustRouteError::NetworkError(format!("Failed to bind gNMI server on {}: {}", addr, e))
+    })
+}
+
+/// Serve gNMI on an already bound listener until the task is aborted
+pub async fn serve(state: AppState, listener: TcpListener) -> RustRouteResult<()> {
+    tonic::transport::Server::builder()
+        .add_service(GnmiServer::new(state))
+        .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener))
+        .await
+        .map_err(|e| RustRouteError::NetworkError(format!("gNMI server failed: {}", e)))
+}
+
+/// Implementation of the gNMI RPCs against the shared application state
+pub struct GnmiService {
+    state: AppState,
+}
+
+impl GnmiService {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+
+    pub async fn capabilities(&self, metadata: &MetadataMap) -> Result<CapabilityResponse, Status> {
+        self.authorize(metadata, UserRole::ReadOnly).await?;
+        Ok(CapabilityResponse {
+            supported_models: vec![ModelData {
+                name: "rust-route".to_string(),
+                organization: "RustRoute".to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+            }],
+            supported_encodings: vec![Encoding::Json as i32, Encoding::JsonIetf as i32],
+            gnmi_version: GNMI_VERSION.to_string(),
+        })
+    }
+
+    pub async fn get(
+        &self,
+        metadata: &MetadataMap,
+        request: GetRequest,
+    ) -> Result<GetResponse, Status> {
+        let encoding = check_encoding(request.encoding)?;
+        let paths = join_paths(request.prefix.as_ref(), &request.path);
+        self.authorize(metadata, required_role(&paths)).await?;
+
+        let mut tree = self.tree().await;
+        match get_request::DataType::try_from(request.r#type) {
+            Ok(get_request::DataType::Config) => {
+                if let Some(root) = tree.as_object_mut() {
+                    root.remove("state");
+                }
+            }
+            Ok(get_request::DataType::State | get_request::DataType::Operational) => {
+                tree = Value::Object(Map::from_iter(
+                    tree.get("state")
+                        .cloned()
+                        .map(|state| ("state".to_string(), state)),
+                ));
+            }
+            Ok(get_request::DataType::All) => {}
+            Err(_) => return Err(Status::invalid_argument("unknown data type")),
+        }
+
+        let mut updates = Vec::with_capacity(paths.len());
+        for path in &paths {
+            let value = locate(&mut tree, &path.elem, false)?;
+            updates.push(Update {
+                path: Some(path.clone()),
+                val: Some(typed_value(value, encoding)),
+                duplicates: 0,
+            });
+        }
+
+        Ok(GetResponse {
+            notification: vec![Notification {
+                timestamp: now_nanos(),
+                update: updates,
+                ..Default::default()
+            }],
+        })
+    }
+
+    pub async fn set(
+        &self,
+        metadata: &MetadataMap,
+        request: SetRequest,
+    ) -> Result<SetResponse, Status> {
+        self.authorize(metadata, UserRole::Admin).await?;
+        let prefix = request.prefix.as_ref();
+
+        let config = self.state.config_manager.get_config().await;
+        let mut tree = config_tree(&config)?;
+        let mut results = Vec::new();
+
+        for path in request
+            .delete
+            .iter()
+            .map(|path| join_path(prefix, Some(path)))
+        {
+            reject_state(&path)?;
+            delete(&mut tree, &path.elem)?;
+            results.push(update_result(path, update_result::Operation::Delete));
+        }
+        for (update, replace) in request
+            .replace
+            .iter()
+            .map(|update| (update, true))
+            .chain(request.update.iter().map(|update| (update, false)))
+        {
+            let path = join_path(prefix, update.path.as_ref());
+            reject_state(&path)?;
+            let value = json_value(update.val.as_ref())?;
+            let target = locate(&mut tree, &path.elem, true)?;
+            if replace {
+                let keys = path.elem.last().map(|elem| elem.key.clone());
+                *target = value;
+                restore_keys(target, keys.as_ref());
+            } else {
+                merge(target, value);
+            }
+            let op = if replace {
+                update_result::Operation::Replace
+            } else {
+                update_result::Operation::Update
+            };
+            results.push(update_result(path, op));
+        }
+
+        let new_config: RouterConfig = serde_json::from_value(rename_keys(tree, '-', '_'))
+            .map_err(|e| Status::invalid_argument(format!("Invalid configuration: {}", e)))?;
+        self.state
+            .config_manager
+            .update_config(new_config)
+            .await
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        self.state
+            .events
+            .publish_activity(ActivityLevel::Info, "Configuration updated via gNMI");
+
+        Ok(SetResponse {
+            prefix: request.prefix,
+            response: results,
+            timestamp: now_nanos(),
+        })
+    }
+
+    pub async fn subscribe(
+        self: Arc<Self>,
+        metadata: &MetadataMap,
+        mut requests: Streaming<SubscribeRequest>,
+    ) -> Result<SubscribeStream, Status> {
+        let list = match requests.message().await? {
+            Some(SubscribeRequest {
+                request: Some(subscribe_request::Request::Subscribe(list)),
+            }) => list,
+            _ => {
+                return Err(Status::invalid_argument(
+                    "first SubscribeRequest must carry a SubscriptionList",
+                ))
+            }
+        };
+        let encoding = check_encoding(list.encoding)?;
+        let paths: Vec<Path> = list
+            .subscription
+            .iter()
+            .map(|subscription| join_path(list.prefix.as_ref(), subscription.path.as_ref()))
+            .collect();
+        self.authorize(metadata, required_role(&paths)).await?;
+        let mode = subscription_list::Mode::try_from(list.mode)
+            .map_err(|_| Status::invalid_argument("unknown subscription mode"))?;
+
+        let service = self;
+        let output = stream! {
+            let mut last: Vec<Option<Value>> = vec![None; paths.len()];
+            if !list.updates_only {
+                match service.sample(&paths, &mut last, encoding, false).await {
+                    Ok(Some(notification)) => yield Ok(update_response(notification)),
+                    Ok(None) => {}
+                    Err(status) => {
+                        yield Err(status);
+                        return;
+                    }
+                }
+            }
+            yield Ok(SubscribeResponse {
+                response: Some(subscribe_response::Response::SyncResponse(true)),
+            });
+
+            match mode {
+                subscription_list::Mode::Once => {}
+                subscription_list::Mode::Poll => {
+                    while let Ok(Some(request)) = requests.message().await {
+                        if !matches!(request.request, Some(subscribe_request::Request::Poll(_))) {
+                            yield Err(Status::invalid_argument("expected Poll request"));
+                            return;
+                        }
+                        match service.sample(&paths, &mut last, encoding, false).await {
+                            Ok(Some(notification)) => yield Ok(update_response(notification)),
+                            Ok(None) => {}
+                            Err(status) => {
+                                yield Err(status);
+                                return;
+                            }
+                        }
+                        yield Ok(SubscribeResponse {
+                            response: Some(subscribe_response::Response::SyncResponse(true)),
+                        });
+                    }
+                }
+                subscription_list::Mode::Stream => {
+                    let plans: Vec<(Duration, bool)> =
+                        list.subscription.iter().map(stream_plan).collect();
+                    let tick = plans
+                        .iter()
+                        .map(|(interval, _)| *interval)
+                        .min()
+                        .unwrap_or(DEFAULT_SAMPLE_INTERVAL);
+                    let mut due: Vec<Duration> = plans.iter().map(|(interval, _)| *interval).collect();
+                    let mut ticker = tokio::time::interval(tick);
+                    ticker.tick().await;
+                    loop {
+                        ticker.tick().await;
+                        let mut updates = Vec::new();
+                        for (index, (interval, changes_only)) in plans.iter().enumerate() {
+                            due[index] = due[index].saturating_sub(tick);
+                            if !due[index].is_zero() {
+                                continue;
+                            }
+                            due[index] = *interval;
+                            match service
+                                .sample_one(&paths[index], &mut last[index], encoding, *changes_only)
+                                .await
+                            {
+                                Ok(Some(update)) => updates.push(update),
+                                Ok(None) => {}
+                                Err(status) => {
+                                    yield Err(status);
+                                    return;
+                                }
+                            }
+                        }
+                        if !updates.is_empty() {
+                            yield Ok(update_response(Notification {
+                                timestamp: now_nanos(),
+                                update: updates,
+                                ..Default::default()
+                            }));
+                        }
+                    }
+                }
+            }
+        };
+
+        Ok(Box::pin(output))
+    }
+
+    async fn sample(
+        &self,
+        paths: &[Path],
+        last: &mut [Option<Value>],
+        encoding: Encoding,
+        changes_only: bool,
+    ) -> Result<Option<Notification>, Status> {
+        let mut updates = Vec::new();
+        for (path, previous) in paths.iter().zip(last.iter_mut()) {
+            if let Some(update) = self
+                .sample_one(path, previous, encoding, changes_only)
+                .await?
+            {
+                updates.push(update);
+            }
+        }
+        if updates.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Notification {
+            timestamp: now_nanos(),
+            update: updates,
+            ..Default::default()
+        }))
+    }
+
+    async fn sample_one(
+        &self,
+        path: &Path,
+        previous: &mut Option<Value>,
+        encoding: Encoding,
+        changes_only: bool,
+    ) -> Result<Option<Update>, Status> {
+        let mut tree = self.tree().await;
+        let value = locate(&mut tree, &path.elem, false)?.clone();
+        if changes_only && previous.as_ref() == Some(&value) {
+            return Ok(None);
+        }
+        let update = Update {
+            path: Some(path.clone()),
+            val: Some(typed_value(&value, encoding)),
+            duplicates: 0,
+        };
+        *previous = Some(value);
+        Ok(Some(update))
+    }
+
+    /// Build the full tree: configuration at the root plus `/state`
+    async fn tree(&self) -> Value {
+        let config = self.state.config_manager.get_config().await;
+        let mut tree = config_tree(&config).unwrap_or_else(|_| Value::Object(Map::new()));
+        if let Some(root) = tree.as_object_mut() {
+            root.insert("state".to_string(), self.state_tree().await);
+        }
+        tree
+    }
+
+    async fn state_tree(&self) -> Value {
+        let (router_id, uptime, interfaces, neighbors) = {
+            let router = self.state.router.read().await;
+            (
+                router.router_id().to_string(),
+                router.uptime(),
+                router.network_interfaces(),
+                router.neighbors(),
+            )
+        };
+
+        let interfaces: Vec<Value> = interfaces
+            .iter()
+            .map(|iface| {
+                serde_json::json!({
+                    "name": iface.config.name,
+                    "address": iface.ip_address().to_string(),
+                    "subnet-mask": iface.subnet_mask().to_string(),
+                    "mtu": iface.config.mtu,
+                    "admin-status": if iface.is_admin_up() { "up" } else { "down" },
+                    "oper-status": if iface.is_up() { "up" } else { "down" },
+                    "updates-sent": iface.updates_sent(),
+                    "bad-packets": iface.bad_packets(),
+                    "rejected-packets": iface.rejected_packets(),
+                    "json-packets": iface.packets_received(PacketCodec::Json),
+                    "binary-packets": iface.packets_received(PacketCodec::Binary),
+                })
+            })
+            .collect();
+
+        let neighbors: Vec<Value> = {
+            let guard = neighbors.read().await;
+            let mut entries: Vec<_> = guard.values().collect();
+            entries.sort_by_key(|info| info.address);
+            entries
+                .into_iter()
+                .map(|info| {
+                    serde_json::json!({
+                        "address": info.address.to_string(),
+                        "interface": info.interface,
+                        "last-seen-seconds": info.last_seen.elapsed().as_secs(),
+                        "learned-routes": info.learned_routes,
+                    })
+                })
+                .collect()
+        };
+
+        let (routes, route_count) = {
+            let table = self.state.routing_table.read().await;
+            let mut routes = table.snapshot();
+            routes.sort_by(|a, b| {
+                (a.destination.parse::<IpAddr>().ok(), &a.subnet_mask)
+                    .cmp(&(b.destination.parse::<IpAddr>().ok(), &b.subnet_mask))
+            });
+            (routes, table.route_count())
+        };
+        let metrics = self.state.metrics.snapshot(neighbors.len(), route_count);
+
+        serde_json::json!({
+            "system": {
+                "router-id": router_id,
+                "version": env!("CARGO_PKG_VERSION"),
+                "uptime-seconds": uptime.as_secs(),
+            },
+            "interfaces": interfaces,
+            "neighbors": neighbors,
+            "routes": rename_keys(serde_json::to_value(routes).unwrap_or_default(), '_', '-'),
+            "metrics": rename_keys(serde_json::to_value(metrics).unwrap_or_default(), '_', '-'),
+        })
+    }
+
+    async fn authorize(&self, metadata: &MetadataMap, role: UserRole) -> Result<(), Status> {
+        let mut guard = self.state.auth.lock().await;
+        let Some(manager) = guard.as_mut() else {
+            return Ok(());
+        };
+
+        let header = |name: &str| {
+            metadata
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.trim().to_string())
+        };
+
+        let token = if let Some(value) = header("authorization") {
+            value
+                .strip_prefix("Bearer ")
+                .map(str::to_string)
+                .unwrap_or(value)
+        } else if let Some(token) = header("x-auth-token") {
+            token
+        } else if let (Some(username), Some(password)) = (header("username"), header("password")) {
+            manager
+                .authenticate(LoginRequest { username, password })
+                .await
+                .token
+                .ok_or_else(|| Status::unauthenticated("invalid credentials"))?
+        } else {
+            return Err(Status::unauthenticated("missing credentials"));
+        };
+
+        let claims = manager
+            .validate_token(&token)
+            .map_err(|_| Status::unauthenticated("invalid token"))?;
+        require_permission(role)(&claims).map_err(|err| match err {
+            AuthError::InsufficientPermissions => Status::permission_denied("insufficient role"),
+            _ => Status::unauthenticated("invalid token"),
+        })
+    }
+}
+
+pub type SubscribeStream = Pin<Box<dyn Stream<Item = Result<SubscribeResponse, Status>> + Send>>;
+
+/// tonic service dispatching the `gnmi.gNMI` RPCs
+#[derive(Clone)]
+pub struct GnmiServer {
+    inner: Arc<GnmiService>,
+}
+
+impl GnmiServer {
+    pub fn new(state: AppState) -> Self {
+        Self {
+            inner: Arc::new(GnmiService::new(state)),
+        }
+    }
+}
+
+impl tonic::server::NamedService for GnmiServer {
+    const NAME: &'static str = "gnmi.gNMI";
+}
+
+macro_rules! unary_method {
+    ($name:ident, $request:ty, $response:ty, |$service:ident, $metadata:ident, $message:ident| $body:expr) => {
+        struct $name(Arc<GnmiService>);
+
+        impl tonic::server::UnaryService<$request> for $name {
+            type Response = $response;
+            type Future = BoxFuture<Response<Self::Response>, Status>;
+
+            fn call(&mut self, request: Request<$request>) -> Self::Future {
+                let $service = Arc::clone(&self.0);
+                Box::pin(async move {
+                    let ($metadata, _, $message) = request.into_parts();
+                    let $metadata = &$metadata;
+                    $body.await.map(Response::new)
+                })
+            }
+        }
+    };
+}
+
+unary_method!(
+    CapabilitiesMethod,
+    CapabilityRequest,
+    CapabilityResponse,
+    |service, metadata, _message| service.capabilities(metadata)
+);
+unary_method!(
+    GetMethod,
+    GetRequest,
+    GetResponse,
+    |service, metadata, message| service.get(metadata, message)
+);
+unary_method!(
+    SetMethod,
+    SetRequest,
+    SetResponse,
+    |service, metadata, message| service.set(metadata, message)
+);
+
+struct SubscribeMethod(Arc<GnmiService>);
+
+impl tonic::server::StreamingService<SubscribeRequest> for SubscribeMethod {
+    type Response = SubscribeResponse;
+    type ResponseStream = SubscribeStream;
+    type Future = BoxFuture<Response<Self::ResponseStream>, Status>;
+
+    fn call(&mut self, request: Request<Streaming<SubscribeRequest>>) -> Self::Future {
+        let service = Arc::clone(&self.0);
+        Box::pin(async move {
+            let (metadata, _, requests) = request.into_parts();
+            service
+                .subscribe(&metadata, requests)
+                .await
+                .map(Response::new)
+        })
+    }
+}
+
+impl<B> Service<http::Request<B>> for GnmiServer
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<tonic::body::BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        let inner = Arc::clone(&self.inner);
+        match request.uri().path() {
+            "/gnmi.gNMI/Capabilities" => Box::pin(async move {
+                let mut grpc = tonic::server::Grpc::new(tonic::codec::ProstCodec::default());
+                Ok(grpc.unary(CapabilitiesMethod(inner), request).await)
+            }),
+            "/gnmi.gNMI/Get" => Box::pin(async move {
+                let mut grpc = tonic::server::Grpc::new(tonic::codec::ProstCodec::default());
+                Ok(grpc.unary(GetMethod(inner), request).await)
+            }),
+            "/gnmi.gNMI/Set" => Box::pin(async move {
+                let mut grpc = tonic::server::Grpc::new(tonic::codec::ProstCodec::default());
+                Ok(grpc.unary(SetMethod(inner), request).await)
+            }),
+            "/gnmi.gNMI/Subscribe" => Box::pin(async move {
+                let mut grpc = tonic::server::Grpc::new(tonic::codec::ProstCodec::default());
+                Ok(grpc.streaming(SubscribeMethod(inner), request).await)
+            }),
+            _ => Box::pin(async move {
+                let mut response = http::Response::new(empty_body());
+                let headers = response.headers_mut();
+                headers.insert(Status::GRPC_STATUS, (Code::Unimplemented as i32).into());
+                headers.insert(
+                    http::header::CONTENT_TYPE,
+                    tonic::metadata::GRPC_CONTENT_TYPE,
+                );
+                Ok(response)
+            }),
+        }
+    }
+}
+
+/// Start the gNMI server in the background if enabled
+pub async fn spawn(config: &GnmiConfig, state: AppState) {
+    if !config.enabled {
+        return;
+    }
+
+    let listener = match bind(config).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            error!("{}", err);
+            state.events.publish_activity(
+                ActivityLevel::Warn,
+                format!("gNMI server disabled: {}", err),
+            );
+            return;
+        }
+    };
+
+    if let Ok(addr) = listener.local_addr() {
+        info!("gNMI server listening on {}", addr);
+    }
+    tokio::spawn(async move {
+        if let Err(err) = serve(state, listener).await {
+            error!("{}", err);
+        }
+    });
+}
+
+fn config_tree(config: &RouterConfig) -> Result<Value, Status> {
+    serde_json::to_value(config)
+        .map(|value| rename_keys(value, '_', '-'))
+        .map_err(|e| Status::internal(format!("Failed to serialize configuration: {}", e)))
+}
+
+/// Convert object keys between the snake_case config and kebab-case paths
+fn rename_keys(value: Value, from: char, to: char) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, value)| {
+                    (
+                        key.replace(from, &to.to_string()),
+                        rename_keys(value, from, to),
+                    )
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .map(|item| rename_keys(item, from, to))
+                .collect(),
+        ),
+        other => other,
+    }
+}
+
+fn join_path(prefix: Option<&Path>, path: Option<&Path>) -> Path {
+    let mut joined = prefix.cloned().unwrap_or_default();
+    if let Some(path) = path {
+        joined.elem.extend(path.elem.iter().cloned());
+        if joined.origin.is_empty() {
+            joined.origin = path.origin.clone();
+        }
+    }
+    joined
+}
+
+fn join_paths(prefix: Option<&Path>, paths: &[Path]) -> Vec<Path> {
+    if paths.is_empty() {
+        return vec![join_path(prefix, None)];
+    }
+    paths
+        .iter()
+        .map(|path| join_path(prefix, Some(path)))
+        .collect()
+}
+
+fn is_state(path: &Path) -> bool {
+    path.elem.first().is_some_and(|elem| elem.name == "state")
+}
+
+fn required_role(paths: &[Path]) -> UserRole {
+    if paths.iter().all(is_state) {
+        UserRole::ReadOnly
+    } else {
+        UserRole::Operator
+    }
+}
+
+fn reject_state(path: &Path) -> Result<(), Status> {
+    if is_state(path) || path.elem.is_empty() {
+        return Err(Status::invalid_argument(format!(
+            "{} is not writable",
+            display_path(path)
+        )));
+    }
+    Ok(())
+}
+
+fn display_path(path: &Path) -> String {
+    let mut out = String::new();
+    for elem in &path.elem {
+        out.push('/');
+        out.push_str(&elem.name);
+        let mut keys: Vec<_> = elem.key.iter().collect();
+        keys.sort();
+        for (key, value) in keys {
+            out.push_str(&format!("[{}={}]", key, value));
+        }
+    }
+    if out.is_empty() {
+        out.push('/');
+    }
+    out
+}
+
+fn check_encoding(encoding: i32) -> Result<Encoding, Status> {
+    match Encoding::try_from(encoding) {
+        Ok(encoding @ (Encoding::Json | Encoding::JsonIetf)) => Ok(encoding),
+        _ => Err(Status::unimplemented(
+            "only JSON and JSON_IETF encodings are supported",
+        )),
+    }
+}
+
+fn stream_plan(subscription: &Subscription) -> (Duration, bool) {
+    match SubscriptionMode::try_from(subscription.mode) {
+        Ok(SubscriptionMode::Sample) => {
+            let interval = if subscription.sample_interval == 0 {
+                DEFAULT_SAMPLE_INTERVAL
+            } else {
+                Duration::from_nanos(subscription.sample_interval).max(MIN_SAMPLE_INTERVAL)
+            };
+            (interval, subscription.suppress_redundant)
+        }
+        // ON_CHANGE and TARGET_DEFINED poll the tree and only send differences
+        _ => (ON_CHANGE_POLL, true),
+    }
+}
+
+/// Walk `elems` from `node`. Object members are addressed by name, lists by
+/// the keys of the next element, and absent `config` containers are skipped.
+/// With `create`, a missing list entry is added holding only its keys.
+fn locate<'a>(
+    mut node: &'a mut Value,
+    elems: &[PathElem],
+    create: bool,
+) -> Result<&'a mut Value, Status> {
+    for elem in elems {
+        let skip = elem.name == "config"
+            && elem.key.is_empty()
+            && matches!(&*node, Value::Object(map) if !map.contains_key("config"));
+        if skip {
+            continue;
+        }
+
+        if node.is_object() {
+            node = match node.get_mut(elem.name.as_str()) {
+                Some(child) => child,
+                None => return Err(Status::not_found(format!("no such node: {}", elem.name))),
+            };
+            if elem.key.is_empty() {
+                continue;
+            }
+        }
+
+        let Value::Array(items) = node else {
+            return Err(Status::not_found(format!(
+                "{} is not a list or container",
+                elem.name
+            )));
+        };
+        if elem.key.is_empty() {
+            return Err(Status::invalid_argument(format!(
+                "list element {} requires keys",
+                elem.name
+            )));
+        }
+
+        let index = match items.iter().position(|item| matches_keys(item, &elem.key)) {
+            Some(index) => index,
+            None if create => {
+                items.push(Value::Object(Map::new()));
+                let index = items.len() - 1;
+                restore_keys(&mut items[index], Some(&elem.key));
+                index
+            }
+            None => {
+                return Err(Status::not_found(format!(
+                    "no list entry {}",
+                    display_path(&Path {
+                        elem: vec![elem.clone()],
+                        ..Default::default()
+                    })
+                )))
+            }
+        };
+        node = &mut items[index];
+    }
+    Ok(node)
+}
+
+fn matches_keys(item: &Value, keys: &HashMap<String, String>) -> bool {
+    keys.iter().all(|(key, expected)| match item.get(key) {
+        Some(Value::String(actual)) => actual == expected,
+        Some(other) => serde_json::from_str::<Value>(expected).is_ok_and(|value| value == *other),
+        None => false,
+    })
+}
+
+fn restore_keys(target: &mut Value, keys: Option<&HashMap<String, String>>) {
+    let (Some(map), Some(keys)) = (target.as_object_mut(), keys) else {
+        return;
+    };
+    for (key, value) in keys {
+        map.entry(key.clone())
+            .or_insert_with(|| Value::String(value.clone()));
+    }
+}
+
+fn delete(tree: &mut Value, elems: &[PathElem]) -> Result<(), Status> {
+    let Some((last, parents)) = elems.split_last() else {
+        return Err(Status::invalid_argument("cannot delete the root"));
+    };
+    let parent = locate(tree, parents, false)?;
+
+    if !last.key.is_empty() {
+        let list = match parent {
+            Value::Object(map) => map.get_mut(&last.name),
+            Value::Array(_) => Some(parent),
+            _ => None,
+        };
+        if let Some(Value::Array(items)) = list {
+            let before = items.len();
+            items.retain(|item| !matches_keys(item, &last.key));
+            if items.len() < before {
+                return Ok(());
+            }
+        }
+        return Err(Status::not_found(format!("no list entry {}", last.name)));
+    }
+
+    match parent
+        .as_object_mut()
+        .and_then(|map| map.get_mut(&last.name))
+    {
+        Some(value) => {
+            // serde restores defaults for optional leaves and rejects the rest
+            *value = Value::Null;
+            Ok(())
+        }
+        None => Err(Status::not_found(format!("no such node: {}", last.name))),
+    }
+}
+
+fn merge(target: &mut Value, value: Value) {
+    match (target, value) {
+        (Value::Object(existing), Value::Object(incoming)) => {
+            for (key, value) in incoming {
+                match existing.get_mut(&key) {
+                    Some(slot) => merge(slot, value),
+                    None => {
+                        existing.insert(key, value);
+                    }
+                }
+            }
+        }
+        (target, value) => *target = value,
+    }
+}
+
+fn typed_value(value: &Value, encoding: Encoding) -> TypedValue {
+    use typed_value::Value as Typed;
+
+    let value = match value {
+        Value::String(text) => Typed::StringVal(text.clone()),
+        Value::Bool(flag) => Typed::BoolVal(*flag),
+        Value::Number(number) => {
+            if let Some(unsigned) = number.as_u64() {
+                Typed::UintVal(unsigned)
+            } else if let Some(signed) = number.as_i64() {
+                Typed::IntVal(signed)
+            } else {
+                Typed::DoubleVal(number.as_f64().unwrap_or_default())
+            }
+        }
+        other => {
+            let bytes = serde_json::to_vec(other).unwrap_or_default();
+            if encoding == Encoding::Json {
+                Typed::JsonVal(bytes)
+            } else {
+                Typed::JsonIetfVal(bytes)
+            }
+        }
+    };
+    TypedValue { value: Some(value) }
+}
+
+fn json_value(value: Option<&TypedValue>) -> Result<Value, Status> {
+    use typed_value::Value as Typed;
+
+    let Some(value) = value.and_then(|value| value.value.as_ref()) else {
+        return Err(Status::invalid_argument("update is missing a value"));
+    };
+    Ok(match value {
+        Typed::StringVal(text) | Typed::AsciiVal(text) => Value::String(text.clone()),
+        Typed::IntVal(number) => Value::from(*number),
+        Typed::UintVal(number) => Value::from(*number),
+        Typed::BoolVal(flag) => Value::Bool(*flag),
+        Typed::DoubleVal(number) => Value::from(*number),
+        Typed::JsonVal(bytes) | Typed::JsonIetfVal(bytes) => serde_json::from_slice(bytes)
+            .map_err(|e| Status::invalid_argument(format!("invalid JSON value: {}", e)))?,
+        Typed::BytesVal(_) => return Err(Status::unimplemented("bytes values are not supported")),
+    })
+}
+
+fn update_result(path: Path, op: update_result::Operation) -> UpdateResult {
+    UpdateResult {
+        path: Some(path),
+        op: op as i32,
+    }
+}
+
+fn update_response(notification: Notification) -> SubscribeResponse {
+    SubscribeResponse {
+        response: Some(subscribe_response::Response::Update(notification)),
+    }
+}
+
+fn now_nanos() -> i64 {
+    chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activity::ActivityLog;
+    use crate::config_manager::ConfigManager;
+    use crate::diagnostics::Diagnostics;
+    use crate::events::EventBus;
+    use crate::fleet::Fleet;
+    use crate::metrics::Metrics;
+    use crate::metrics_history::MetricsHistory;
+    use crate::router::Router;
+    use crate::routing_table::RoutingTable;
+    use crate::watchdog::{Watchdog, WatchdogConfig};
+    use tokio::sync::{Mutex, RwLock};
+    use tokio_stream::StreamExt;
+
+    fn path(text: &str) -> Path {
+        let elem = text
+            .trim_matches('/')
+            .split('/')
+            .filter(|part| !part.is_empty())
+            .map(|part| {
+                let (name, keys) = part.split_once('[').unwrap_or((part, ""));
+                let key = keys
+                    .trim_end_matches(']')
+                    .split("][")
+                    .filter_map(|pair| pair.split_once('='))
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect();
+                PathElem {
+                    name: name.to_string(),
+                    key,
+                }
+            })
+            .collect();
+        Path {
+            elem,
+            ..Default::default()
+        }
+    }
+
+    async fn app_state(dir: &tempfile::TempDir) -> AppState {
+        let config_path = dir.path().join("config.json");
+        let mut config = RouterConfig::default();
+        config.rip.enabled = false;
+        tokio::fs::write(&config_path, serde_json::to_string(&config).unwrap())
+            .await
+            .unwrap();
+
+        let (manager, _changes) = ConfigManager::new(&config_path).await.unwrap();
+        let routing_table = Arc::new(RwLock::new(RoutingTable::new()));
+        let metrics = Metrics::new();
+        let router = Router::new(config, Arc::clone(&routing_table), metrics.clone())
+            .await
+            .unwrap();
+
+        AppState {
+            router: Arc::new(RwLock::new(router)),
+            routing_table,
+            metrics,
+            config_manager: Arc::new(manager),
+            events: EventBus::new(16),
+            auth: Arc::new(Mutex::new(None)),
+            watchdog: Watchdog::new(WatchdogConfig::default()),
+            activity: Arc::new(ActivityLog::in_memory(16)),
+            metrics_history: Arc::new(MetricsHistory::in_memory(1)),
+            diagnostics: Diagnostics::new(),
+            fleet: Fleet::new(),
+        }
+    }
+
+    #[test]
+    fn paths_resolve_keys_and_optional_config_containers() {
+        let mut tree = config_tree(&RouterConfig::default()).unwrap();
+
+        let cost = locate(
+            &mut tree,
+            &path("/interfaces/interface[name=eth0]/config/cost").elem,
+            false,
+        )
+        .unwrap();
+        assert_eq!(*cost, Value::from(1));
+
+        let interval = locate(&mut tree, &path("/rip/update-interval").elem, false).unwrap();
+        assert_eq!(*interval, Value::from(30));
+
+        let missing = locate(
+            &mut tree,
+            &path("/interfaces/interface[name=nope]").elem,
+            false,
+        );
+        assert_eq!(missing.unwrap_err().code(), Code::NotFound);
+    }
+
+    #[test]
+    fn edits_round_trip_into_router_config() {
+        let mut tree = config_tree(&RouterConfig::default()).unwrap();
+
+        merge(
+            locate(
+                &mut tree,
+                &path("/interfaces/interface[name=eth1]").elem,
+                true,
+            )
+            .unwrap(),
+            serde_json::json!({"address": "10.9.0.1/24", "enabled": true, "cost": 3}),
+        );
+        *locate(&mut tree, &path("/rip/config/update-interval").elem, false).unwrap() =
+            Value::from(15);
+        delete(&mut tree, &path("/interfaces/interface[name=eth0]").elem).unwrap();
+
+        let config: RouterConfig = serde_json::from_value(rename_keys(tree, '-', '_')).unwrap();
+        assert_eq!(config.rip.update_interval, 15);
+        assert_eq!(config.interfaces.len(), 1);
+        assert_eq!(config.interfaces[0].name, "eth1");
+        assert_eq!(config.interfaces[0].cost, 3);
+        assert!(!config.interfaces[0].passive);
+    }
+
+    #[tokio::test]
+    async fn grpc_get_set_and_subscribe_once() {
+        use tonic::codegen::http::uri::PathAndQuery;
+
+        let dir = tempfile::tempdir().unwrap();
+        let state = app_state(&dir).await;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(state.clone(), listener));
+
+        let channel = tonic::transport::Endpoint::from_shared(format!("http://{}", addr))
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        let mut client = tonic::client::Grpc::new(channel);
+
+        client.ready().await.unwrap();
+        let capabilities: CapabilityResponse = client
+            .unary(
+                Request::new(CapabilityRequest {}),
+                PathAndQuery::from_static("/gnmi.gNMI/Capabilities"),
+                tonic::codec::ProstCodec::default(),
+            )
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(capabilities.gnmi_version, GNMI_VERSION);
+
+        client.ready().await.unwrap();
+        let set = SetRequest {
+            update: vec![Update {
+                path: Some(path("/interfaces/interface[name=eth0]/config/cost")),
+                val: Some(TypedValue {
+                    value: Some(typed_value::Value::UintVal(5)),
+                }),
+                duplicates: 0,
+            }],
+            ..Default::default()
+        };
+        let response: SetResponse = client
+            .unary(
+                Request::new(set),
+                PathAndQuery::from_static("/gnmi.gNMI/Set"),
+                tonic::codec::ProstCodec::default(),
+            )
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.response.len(), 1);
+        assert_eq!(
+            state.config_manager.get_config().await.interfaces[0].cost,
+            5
+        );
+
+        client.ready().await.unwrap();
+        let rejected = client
+            .unary::<_, SetResponse, _>(
+                Request::new(SetRequest {
+                    replace: vec![Update {
+                        path: Some(path("/logging/level")),
+                        val: Some(TypedValue {
+                            value: Some(typed_value::Value::StringVal("loud".to_string())),
+                        }),
+                        duplicates: 0,
+                    }],
+                    ..Default::default()
+                }),
+                PathAndQuery::from_static("/gnmi.gNMI/Set"),
+                tonic::codec::ProstCodec::default(),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(rejected.code(), Code::InvalidArgument);
+
+        client.ready().await.unwrap();
+        let get = GetRequest {
+            path: vec![path("/state/system/router-id")],
+            encoding: Encoding::JsonIetf as i32,
+            ..Default::default()
+        };
+        let response: GetResponse = client
+            .unary(
+                Request::new(get),
+                PathAndQuery::from_static("/gnmi.gNMI/Get"),
+                tonic::codec::ProstCodec::default(),
+            )
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(
+            response.notification[0].update[0].val,
+            Some(TypedValue {
+                value: Some(typed_value::Value::StringVal("192.168.1.1".to_string())),
+            })
+        );
+
+        client.ready().await.unwrap();
+        let subscribe = SubscribeRequest {
+            request: Some(subscribe_request::Request::Subscribe(SubscriptionList {
+                subscription: vec![Subscription {
+                    path: Some(path("/rip")),
+                    ..Default::default()
+                }],
+                mode: subscription_list::Mode::Once as i32,
+                encoding: Encoding::JsonIetf as i32,
+                ..Default::default()
+            })),
+        };
+        let mut responses = client
+            .streaming::<_, SubscribeRequest, SubscribeResponse, _>(
+                Request::new(tokio_stream::once(subscribe)),
+                PathAndQuery::from_static("/gnmi.gNMI/Subscribe"),
+                tonic::codec::ProstCodec::default(),
+            )
+            .await
+            .unwrap()
+            .into_inner();
+        let first = responses.next().await.unwrap().unwrap();
+        let Some(subscribe_response::Response::Update(notification)) = first.response else {
+            panic!("expected an update first");
+        };
+        let Some(typed_value::Value::JsonIetfVal(bytes)) =
+            notification.update[0].val.clone().and_then(|v| v.value)
+        else {
+            panic!("expected JSON_IETF container");
+        };
+        let rip: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(rip["update-interval"], Value::from(30));
+        let second = responses.next().await.unwrap().unwrap();
+        assert_eq!(
+            second.response,
+            Some(subscribe_response::Response::SyncResponse(true))
+        );
+        assert!(responses.next().await.is_none());
+    }
+}
@@ -0,0 +1,172 @@
+//! Grafana simple-JSON datasource adapter.
+//!
+//! Grafana's JSON datasource (`simpod-json-datasource`, and the older
+//! `grafana-simple-json-datasource`) is pointed at `/api/grafana`: it checks
+//! the connection with `GET /api/grafana`, lists the series with
+//! `POST /api/grafana/search` and fetches them with
+//! `POST /api/grafana/query`. The series come from the metrics history, so
+//! dashboards can be built without running Prometheus.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::metrics_history::MetricsSample;
+
+/// Series offered to Grafana, as named in queries
+pub const SERIES: &[&str] = &[
+    "route_count",
+    "neighbor_count",
+    "packets_sent",
+    "packets_received",
+    "packets_dropped",
+    "routing_updates_sent",
+    "routing_updates_received",
+    "route_changes",
+];
+
+/// Value of the series `name` in `sample`
+fn value(sample: &MetricsSample, name: &str) -> Option<f64> {
+    let value = match name {
+        "route_count" => sample.route_count,
+        "neighbor_count" => sample.neighbor_count as u64,
+        "packets_sent" => sample.packets_sent,
+        "packets_received" => sample.packets_received,
+        "packets_dropped" => sample.packets_dropped,
+        "routing_updates_sent" => sample.routing_updates_sent,
+        "routing_updates_received" => sample.routing_updates_received,
+        "route_changes" => sample.route_changes,
+        _ => return None,
+    };
+    Some(value as f64)
+}
+
+/// Body of `POST /api/grafana/search`
+#[derive(Debug, Default, Deserialize)]
+pub struct SearchRequest {
+    #[serde(default)]
+    pub target: String,
+}
+
+/// Series whose name contains the search text
+pub fn search(request: &SearchRequest) -> Vec<&'static str> {
+    let needle = request.target.trim().to_ascii_lowercase();
+    SERIES
+        .iter()
+        .copied()
+        .filter(|name| name.contains(needle.as_str()))
+        .collect()
+}
+
+/// Body of `POST /api/grafana/query`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryRequest {
+    pub range: QueryRange,
+    #[serde(default)]
+    pub targets: Vec<QueryTarget>,
+    #[serde(default)]
+    pub max_data_points: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QueryRange {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QueryTarget {
+    #[serde(default)]
+    pub target: String,
+    /// Grafana hides targets the user disabled but still sends them
+    #[serde(default)]
+    pub hide: bool,
+}
+
+/// A time series as Grafana expects it: `[value, milliseconds since epoch]`
+/// pairs, oldest first
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TimeSeries {
+    pub target: String,
+    pub datapoints: Vec<(f64, i64)>,
+}
+
+/// The series asked for by `request`, built from `samples` taken within its
+/// range; unknown targets are left out
+pub fn query(request: &QueryRequest, samples: &[MetricsSample]) -> Vec<TimeSeries> {
+    let step = match request.max_data_points {
+        Some(max) if max > 0 && samples.len() > max => samples.len().div_ceil(max),
+        _ => 1,
+    };
+
+    request
+        .targets
+        .iter()
+        .filter(|target| !target.hide && SERIES.contains(&target.target.as_str()))
+        .map(|target| TimeSeries {
+            target: target.target.clone(),
+            datapoints: samples
+                .iter()
+                .step_by(step)
+                .filter_map(|sample| {
+                    value(sample, &target.target)
+                        .map(|value| (value, sample.timestamp.timestamp_millis()))
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::MetricsSnapshot;
+    use chrono::{Duration, TimeZone};
+
+    fn sample(at: DateTime<Utc>, route_count: u64) -> MetricsSample {
+        let snapshot = MetricsSnapshot {
+            route_count,
+            neighbor_count: 2,
+            ..MetricsSnapshot::default()
+        };
+        MetricsSample::new(&snapshot, at)
+    }
+
+    #[test]
+    fn search_filters_series_by_name() {
+        let all = search(&SearchRequest::default());
+        assert_eq!(all.len(), SERIES.len());
+        let packets = search(&SearchRequest {
+            target: "Packets".to_string(),
+        });
+        assert_eq!(
+            packets,
+            vec!["packets_sent", "packets_received", "packets_dropped"]
+        );
+    }
+
+    #[test]
+    fn query_returns_requested_series_thinned_to_max_points() {
+        let start = Utc.with_ymd_and_hms(2026, 3, 2, 14, 0, 0).unwrap();
+        let samples: Vec<MetricsSample> = (0..10)
+            .map(|i| sample(start + Duration::minutes(i), i as u64))
+            .collect();
+        let request: QueryRequest = serde_json::from_value(serde_json::json!({
+            "range": {"from": "2026-03-02T14:00:00Z", "to": "2026-03-02T15:00:00Z"},
+            "targets": [
+                {"target": "route_count"},
+                {"target": "neighbor_count", "hide": true},
+                {"target": "no_such_series"}
+            ],
+            "maxDataPoints": 5
+        }))
+        .unwrap();
+
+        let series = query(&request, &samples);
+        assert_eq!(series.len(), 1);
+        assert_eq!(series[0].target, "route_count");
+        let values: Vec<f64> = series[0].datapoints.iter().map(|(v, _)| *v).collect();
+        assert_eq!(values, vec![0.0, 2.0, 4.0, 6.0, 8.0]);
+        assert_eq!(series[0].datapoints[0].1, start.timestamp_millis());
+    }
+}
@@ -0,0 +1,316 @@
+//! External hook scripts.
+//!
+//! Runs operator-supplied commands when routes or neighbors change. Each
+//! command receives the event as JSON on stdin, the same envelope the event
+//! streaming sink produces, plus `RUST_ROUTE_EVENT` and `RUST_ROUTE_ROUTER_ID`
+//! in its environment. Commands run without a shell, at most
+//! `max_concurrent` at a time, and are killed once their timeout expires.
+
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::Semaphore;
+
+use crate::events::{EventBus, NeighborState, WebEvent};
+use crate::router::RECEIVE_HEARTBEAT;
+use crate::streaming::encode_event;
+use crate::watchdog::Heartbeat;
+use crate::{RustRouteError, RustRouteResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookEvent {
+    RouteUpdate,
+    RouteWithdrawn,
+    NeighborUp,
+    NeighborDown,
+    CountToInfinity,
+    Activity,
+    TopologyChanged,
+}
+
+impl HookEvent {
+    pub fn of(event: &WebEvent) -> Option<Self> {
+        match event {
+            WebEvent::Route(_) | WebEvent::RouteV6(_) => Some(HookEvent::RouteUpdate),
+            WebEvent::RouteWithdrawn(_) | WebEvent::RouteV6Withdrawn(_) => {
+                Some(HookEvent::RouteWithdrawn)
+            }
+            WebEvent::Neighbor(neighbor) => Some(match neighbor.state {
+                NeighborState::Up => HookEvent::NeighborUp,
+                NeighborState::Down => HookEvent::NeighborDown,
+            }),
+            WebEvent::CountToInfinity(_) => Some(HookEvent::CountToInfinity),
+            WebEvent::Activity(_) => Some(HookEvent::Activity),
+            WebEvent::TopologyChanged(_) => Some(HookEvent::TopologyChanged),
+            WebEvent::Metrics(_) => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HookEvent::RouteUpdate => "route_update",
+            HookEvent::RouteWithdrawn => "route_withdrawn",
+            HookEvent::NeighborUp => "neighbor_up",
+            HookEvent::NeighborDown => "neighbor_down",
+            HookEvent::CountToInfinity => "count_to_infinity",
+            HookEvent::Activity => "activity",
+            HookEvent::TopologyChanged => "topology_changed",
+        }
+    }
+}
+
+/// Hook runner configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HooksConfig {
+    pub enabled: bool,
+    /// Commands allowed to run at once; further events wait for a slot
+    pub max_concurrent: usize,
+    /// Default seconds before a command is killed
+    pub timeout_secs: u64,
+    #[serde(default)]
+    pub commands: Vec<HookCommand>,
+}
+
+impl Default for HooksConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_concurrent: 4,
+            timeout_secs: 30,
+            commands: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HookCommand {
+    pub event: HookEvent,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Overrides `HooksConfig::timeout_secs`
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+/// Run one command with `payload` on stdin, killing it after `timeout`
+pub async fn execute(
+    hook: &HookCommand,
+    event: HookEvent,
+    router_id: &str,
+    payload: &[u8],
+    timeout: Duration,
+) -> RustRouteResult<()> {
+    let mut child = Command::new(&hook.command)
+        .args(&hook.args)
+        .env("RUST_ROUTE_EVENT", event.as_str())
+        .env("RUST_ROUTE_ROUTER_ID", router_id)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| {
+            RustRouteError::ConfigError(format!("Failed to start hook {}: {}", hook.command, e))
+        })?;
+
+    let mut stdin = child.stdin.take();
+    let completed = tokio::time::timeout(timeout, async move {
+        if let Some(stdin) = stdin.as_mut() {
+            // Commands that ignore stdin may close it early; that's not a failure
+            let _ = stdin.write_all(payload).await;
+        }
+        drop(stdin);
+        child.wait_with_output().await
+    })
+    .await;
+
+    let output = match completed {
+        Ok(result) => result.map_err(|e| {
+            RustRouteError::NetworkError(format!("Hook {} failed: {}", hook.command, e))
+        })?,
+        Err(_) => {
+            return Err(RustRouteError::NetworkError(format!(
+                "Hook {} timed out after {}s",
+                hook.command,
+                timeout.as_secs()
+            )))
+        }
+    };
+
+    if !output.stdout.is_empty() {
+        debug!(
+            "Hook {} output: {}",
+            hook.command,
+            String::from_utf8_lossy(&output.stdout).trim_end()
+        );
+    }
+    if !output.status.success() {
+        return Err(RustRouteError::NetworkError(format!(
+            "Hook {} exited with {}: {}",
+            hook.command,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        )));
+    }
+    Ok(())
+}
+
+/// Dispatch events to hook commands until the bus closes or the task is aborted
+pub async fn run(config: HooksConfig, router_id: String, events: EventBus, heartbeat: Heartbeat) {
+    let slots = Arc::new(Semaphore::new(config.max_concurrent.max(1)));
+    let router_id: Arc<str> = Arc::from(router_id);
+    let mut receiver = events.subscribe("hooks");
+    let mut liveness = tokio::time::interval(RECEIVE_HEARTBEAT);
+    info!(
+        "Hook runner started with {} commands",
+        config.commands.len()
+    );
+
+    loop {
+        let event = tokio::select! {
+            _ = liveness.tick() => {
+                heartbeat.beat();
+                continue;
+            }
+            event = receiver.recv() => match event {
+                Ok(event) => event,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Hook runner fell behind; skipped {} events", skipped);
+                    continue;
+                }
+                Err(RecvError::Closed) => return,
+            },
+        };
+
+        let Some(kind) = HookEvent::of(&event) else {
+            continue;
+        };
+        let payload: Arc<[u8]> = Arc::from(encode_event(&router_id, &event));
+
+        for hook in config.commands.iter().filter(|hook| hook.event == kind) {
+            // Waiting here applies backpressure; the bus reports what we skip
+            let permit = match Arc::clone(&slots).acquire_owned().await {
+                Ok(permit) => permit,
+                Err(_) => return,
+            };
+            heartbeat.beat();
+
+            let hook = hook.clone();
+            let router_id = Arc::clone(&router_id);
+            let payload = Arc::clone(&payload);
+            let timeout = Duration::from_secs(hook.timeout_secs.unwrap_or(config.timeout_secs));
+            tokio::spawn(async move {
+                if let Err(err) = execute(&hook, kind, &router_id, &payload, timeout).await {
+                    warn!("{}", err);
+                }
+                drop(permit);
+            });
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::events::{NeighborEvent, RouteEvent};
+    use crate::routing_table::RouteSource;
+
+    fn shell(event: HookEvent, script: &str) -> HookCommand {
+        HookCommand {
+            event,
+            command: "/bin/sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+            timeout_secs: None,
+        }
+    }
+
+    #[test]
+    fn events_map_to_hook_types() {
+        let neighbor = WebEvent::Neighbor(NeighborEvent::new(
+            "10.0.0.2".parse().unwrap(),
+            None,
+            NeighborState::Down,
+        ));
+        assert_eq!(HookEvent::of(&neighbor), Some(HookEvent::NeighborDown));
+
+        let route = RouteEvent::from_parts(
+            "192.168.1.0".parse().unwrap(),
+            "255.255.255.0".parse().unwrap(),
+            "10.0.0.2".parse().unwrap(),
+            16,
+            "eth0".to_string(),
+            RouteSource::Dynamic,
+        );
+        assert_eq!(
+            HookEvent::of(&WebEvent::RouteWithdrawn(route)),
+            Some(HookEvent::RouteWithdrawn)
+        );
+        assert_eq!(
+            serde_json::to_string(&HookEvent::RouteWithdrawn).unwrap(),
+            "\"route_withdrawn\""
+        );
+    }
+
+    #[tokio::test]
+    async fn command_receives_event_on_stdin() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("event.json");
+        let hook = shell(
+            HookEvent::NeighborUp,
+            &format!(
+                "cat > {} && test \"$RUST_ROUTE_EVENT\" = neighbor_up",
+                out.display()
+            ),
+        );
+
+        execute(
+            &hook,
+            HookEvent::NeighborUp,
+            "10.0.0.1",
+            br#"{"type":"Neighbor"}"#,
+            Duration::from_secs(5),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(out).unwrap(),
+            r#"{"type":"Neighbor"}"#
+        );
+
+        let failing = shell(HookEvent::NeighborUp, "echo nope >&2; exit 3");
+        let err = execute(
+            &failing,
+            HookEvent::NeighborUp,
+            "10.0.0.1",
+            b"{}",
+            Duration::from_secs(5),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("nope"));
+    }
+
+    #[tokio::test]
+    async fn slow_commands_are_killed() {
+        let hook = shell(HookEvent::Activity, "sleep 10");
+        let started = std::time::Instant::now();
+        let err = execute(
+            &hook,
+            HookEvent::Activity,
+            "10.0.0.1",
+            b"{}",
+            Duration::from_millis(200),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("timed out"));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
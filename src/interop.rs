@@ -0,0 +1,712 @@
+//! Translation between RustRoute configuration and other routing daemons
+
+use ipnet::{IpNet, Ipv4Net};
+use std::fmt::Write;
+use std::net::Ipv4Addr;
+
+use crate::config_manager::{InterfaceConfig, RouterConfig};
+use crate::network::{PacingConfig, SendMode};
+use crate::policy::{Action, Direction, DistributeList, PrefixList, PrefixListRule};
+use crate::protocol::PacketCodec;
+use crate::routing_table::{Route, RouteSource};
+
+const FRR_COST_ACL: &str = "rustroute-any";
+
+/// Configuration dialects RustRoute can translate to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    /// FRRouting / Quagga `ripd`
+    Frr,
+    /// BIRD 2
+    Bird,
+}
+
+impl ConfigFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "frr" | "quagga" => Some(ConfigFormat::Frr),
+            "bird" => Some(ConfigFormat::Bird),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConfigFormat::Frr => "frr",
+            ConfigFormat::Bird => "bird",
+        }
+    }
+}
+
+/// Render interface settings, RIP parameters and static routes in `format`.
+/// Routes that are not static or are unreachable are ignored.
+pub fn export_config(config: &RouterConfig, routes: &[Route], format: ConfigFormat) -> String {
+    let interfaces: Vec<(&InterfaceConfig, Ipv4Net)> = config
+        .interfaces
+        .iter()
+        .filter_map(|iface| match iface.address.trim().parse::<IpNet>() {
+            Ok(IpNet::V4(net)) => Some((iface, net)),
+            _ => None,
+        })
+        .collect();
+
+    let mut static_routes: Vec<&Route> = routes
+        .iter()
+        .filter(|route| {
+            route.source == RouteSource::Static && route.metric < config.rip.infinity_metric
+        })
+        .collect();
+    static_routes.sort_by_key(|route| (route.destination, route.subnet_mask));
+
+    match format {
+        ConfigFormat::Frr => render_frr(config, &interfaces, &static_routes),
+        ConfigFormat::Bird => render_bird(config, &interfaces, &static_routes),
+    }
+}
+
+fn render_frr(
+    config: &RouterConfig,
+    interfaces: &[(&InterfaceConfig, Ipv4Net)],
+    static_routes: &[&Route],
+) -> String {
+    let rip = &config.rip;
+    let mut out = String::new();
+
+    let _ = writeln!(
+        out,
+        "! Generated by RustRoute {}",
+        env!("CARGO_PKG_VERSION")
+    );
+    if rip.port != 520 {
+        let _ = writeln!(
+            out,
+            "! RustRoute used UDP port {}; ripd always uses 520",
+            rip.port
+        );
+    }
+    if rip.infinity_metric != 16 {
+        let _ = writeln!(
+            out,
+            "! RustRoute used infinity metric {}; ripd always uses 16",
+            rip.infinity_metric
+        );
+    }
+    out.push_str("!\n");
+
+    for (iface, net) in interfaces {
+        let _ = writeln!(out, "interface {}", iface.name);
+        let _ = writeln!(out, " ip address {}", net);
+        for secondary in &iface.secondary_addresses {
+            let _ = writeln!(out, " ip address {}", secondary);
+        }
+        if !rip.split_horizon {
+            out.push_str(" no ip rip split-horizon\n");
+        } else if rip.poison_reverse {
+            out.push_str(" ip rip split-horizon poisoned-reverse\n");
+        }
+        if !iface.enabled {
+            out.push_str(" shutdown\n");
+        }
+        out.push_str("exit\n!\n");
+    }
+
+    for route in static_routes {
+        let _ = writeln!(
+            out,
+            "ip route {} {} {}",
+            prefix(route.destination, route.subnet_mask),
+            route.next_hop,
+            route.interface
+        );
+    }
+    if !static_routes.is_empty() {
+        out.push_str("!\n");
+    }
+
+    let costed: Vec<&(&InterfaceConfig, Ipv4Net)> = interfaces
+        .iter()
+        .filter(|(iface, _)| iface.cost > 1)
+        .collect();
+    if !costed.is_empty() {
+        let _ = writeln!(out, "access-list {} seq 5 permit any\n!", FRR_COST_ACL);
+    }
+
+    out.push_str("router rip\n version 2\n");
+    for (iface, _) in interfaces.iter().filter(|(iface, _)| iface.enabled) {
+        let _ = writeln!(out, " network {}", iface.name);
+    }
+    let _ = writeln!(
+        out,
+        " timers basic {} {} {}",
+        rip.update_interval, rip.route_timeout, rip.garbage_collection_timeout
+    );
+    for (iface, _) in interfaces.iter().filter(|(iface, _)| iface.passive) {
+        let _ = writeln!(out, " passive-interface {}", iface.name);
+    }
+    for (iface, _) in costed {
+        let _ = writeln!(
+            out,
+            " offset-list {} in {} {}",
+            FRR_COST_ACL,
+            iface.cost - 1,
+            iface.name
+        );
+    }
+    if !static_routes.is_empty() {
+        out.push_str(" redistribute static\n");
+    }
+    for (name, source) in [
+        ("kernel", &rip.redistribute.kernel),
+        ("connected", &rip.redistribute.connected),
+    ] {
+        if source.enabled {
+            let _ = writeln!(out, " redistribute {} metric {}", name, source.metric);
+        }
+    }
+    out.push_str("exit\n!\n");
+
+    out
+}
+
+fn render_bird(
+    config: &RouterConfig,
+    interfaces: &[(&InterfaceConfig, Ipv4Net)],
+    static_routes: &[&Route],
+) -> String {
+    let rip = &config.rip;
+    let mut out = String::new();
+
+    let _ = writeln!(
+        out,
+        "# Generated by RustRoute {}",
+        env!("CARGO_PKG_VERSION")
+    );
+    if let Ok(router_id) = config.router_id.parse::<Ipv4Addr>() {
+        let _ = writeln!(out, "router id {};", router_id);
+    }
+    out.push('\n');
+    out.push_str("protocol device {\n}\n\n");
+
+    let enabled: Vec<&(&InterfaceConfig, Ipv4Net)> = interfaces
+        .iter()
+        .filter(|(iface, _)| iface.enabled)
+        .collect();
+
+    out.push_str("protocol direct {\n    ipv4;\n");
+    for (iface, _) in &enabled {
+        let _ = writeln!(out, "    interface \"{}\";", iface.name);
+    }
+    out.push_str("}\n\n");
+
+    if !static_routes.is_empty() {
+        out.push_str("protocol static {\n    ipv4;\n");
+        for route in static_routes {
+            let _ = writeln!(
+                out,
+                "    route {} via {};",
+                prefix(route.destination, route.subnet_mask),
+                route.next_hop
+            );
+        }
+        out.push_str("}\n\n");
+    }
+
+    out.push_str("protocol rip rustroute {\n");
+    out.push_str("    ipv4 {\n        import all;\n        export all;\n    };\n");
+    let _ = writeln!(out, "    infinity {};", rip.infinity_metric);
+    for (iface, _) in &enabled {
+        let _ = writeln!(out, "    interface \"{}\" {{", iface.name);
+        let _ = writeln!(out, "        metric {};", iface.cost.max(1));
+        let mode = match iface.send_mode {
+            SendMode::Broadcast => "broadcast",
+            SendMode::Multicast | SendMode::Unicast(_) => "multicast",
+        };
+        let _ = writeln!(out, "        mode {};", mode);
+        let _ = writeln!(out, "        port {};", rip.port);
+        let _ = writeln!(out, "        update time {};", rip.update_interval);
+        let _ = writeln!(out, "        timeout time {};", rip.route_timeout);
+        let _ = writeln!(
+            out,
+            "        garbage time {};",
+            rip.garbage_collection_timeout
+        );
+        let _ = writeln!(out, "        split horizon {};", yes_no(rip.split_horizon));
+        let _ = writeln!(
+            out,
+            "        poison reverse {};",
+            yes_no(rip.poison_reverse)
+        );
+        out.push_str("    };\n");
+    }
+    out.push_str("}\n");
+
+    out
+}
+
+/// Result of translating a foreign configuration
+#[derive(Debug, Clone)]
+pub struct ImportReport {
+    pub config: RouterConfig,
+    /// Statements that were skipped or only partially translated
+    pub warnings: Vec<String>,
+}
+
+#[derive(Default)]
+struct FrrInterface {
+    name: String,
+    address: Option<Ipv4Net>,
+    secondary_addresses: Vec<Ipv4Net>,
+    shutdown: bool,
+}
+
+/// Parse an FRR/Quagga `ripd` configuration. Interfaces and `router rip`
+/// settings are translated; anything without a RustRoute equivalent is
+/// reported in [`ImportReport::warnings`]. Inbound offset-lists become the
+/// interface cost regardless of the access-list they reference. Prefix lists
+/// and the distribute-lists that reference them are imported; distribute-lists
+/// over access-lists are not.
+pub fn import_frr(text: &str) -> ImportReport {
+    let mut config = RouterConfig::default();
+    let mut warnings = Vec::new();
+    let mut interfaces: Vec<FrrInterface> = Vec::new();
+    let mut networks: Vec<String> = Vec::new();
+    let mut passive: Vec<String> = Vec::new();
+    let mut passive_default = false;
+    let mut offsets: Vec<(String, u32)> = Vec::new();
+    let mut split_horizon = true;
+    let mut poison_reverse = false;
+    // Rules with their list and sequence number, ordered once all are read
+    let mut prefix_rules: Vec<(String, u32, PrefixListRule)> = Vec::new();
+
+    enum Section {
+        Global,
+        Interface(usize),
+        RouterRip,
+        Other,
+    }
+    let mut section = Section::Global;
+
+    for (index, raw) in text.lines().enumerate() {
+        let line_no = index + 1;
+        let line = raw.trim();
+        if line.is_empty() || line.starts_with('!') || line.starts_with('#') {
+            continue;
+        }
+
+        let indented = raw.starts_with(' ') || raw.starts_with('\t');
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        if !indented {
+            match words.as_slice() {
+                ["interface", name, ..] => {
+                    interfaces.push(FrrInterface {
+                        name: name.to_string(),
+                        ..Default::default()
+                    });
+                    section = Section::Interface(interfaces.len() - 1);
+                    continue;
+                }
+                ["router", "rip"] => {
+                    section = Section::RouterRip;
+                    continue;
+                }
+                ["router", ..] => {
+                    warnings.push(format!("line {}: ignoring `{}` section", line_no, line));
+                    section = Section::Other;
+                    continue;
+                }
+                ["exit"] | ["end"] | ["exit-vrf"] => {
+                    section = Section::Global;
+                    continue;
+                }
+                _ => section = Section::Global,
+            }
+        }
+
+        match section {
+            Section::Interface(slot) => {
+                let iface = &mut interfaces[slot];
+                match words.as_slice() {
+                    ["ip", "address", cidr, ..] => match cidr.parse::<Ipv4Net>() {
+                        Ok(net) if iface.address.is_none() => iface.address = Some(net),
+                        Ok(net) => iface.secondary_addresses.push(net),
+                        Err(_) => {
+                            warnings.push(format!("line {}: invalid address {}", line_no, cidr))
+                        }
+                    },
+                    ["shutdown"] => iface.shutdown = true,
+                    ["no", "ip", "rip", "split-horizon"] => split_horizon = false,
+                    ["ip", "rip", "split-horizon", "poisoned-reverse"] => poison_reverse = true,
+                    ["description", ..] | ["exit"] => {}
+                    _ => warnings.push(format!("line {}: unsupported `{}`", line_no, line)),
+                }
+            }
+            Section::RouterRip => match words.as_slice() {
+                ["network", target] => networks.push(target.to_string()),
+                ["version", "2"] => {}
+                ["version", other] => warnings.push(format!(
+                    "line {}: RIP version {} is not supported; using version 2",
+                    line_no, other
+                )),
+                ["timers", "basic", update, timeout, garbage] => {
+                    match (update.parse(), timeout.parse(), garbage.parse()) {
+                        (Ok(update), Ok(timeout), Ok(garbage)) => {
+                            config.rip.update_interval = update;
+                            config.rip.route_timeout = timeout;
+                            config.rip.garbage_collection_timeout = garbage;
+                        }
+                        _ => warnings.push(format!("line {}: invalid timers", line_no)),
+                    }
+                }
+                ["passive-interface", "default"] => passive_default = true,
+                ["passive-interface", name] => passive.push(name.to_string()),
+                ["no", "passive-interface", name] => passive.retain(|p| p != name),
+                ["offset-list", _, "in", offset, name] => match offset.parse::<u32>() {
+                    Ok(offset) => offsets.push((name.to_string(), offset)),
+                    Err(_) => warnings.push(format!("line {}: invalid offset", line_no)),
+                },
+                ["distribute-list", "prefix", name, direction, rest @ ..] if rest.len() <= 1 => {
+                    let direction = match *direction {
+                        "in" => Direction::Import,
+                        "out" => Direction::Export,
+                        _ => {
+                            warnings.push(format!("line {}: unsupported `{}`", line_no, line));
+                            continue;
+                        }
+                    };
+                    config.policy.distribute_lists.push(DistributeList {
+                        prefix_list: name.to_string(),
+                        direction,
+                        interface: rest.first().map(|name| name.to_string()),
+                    });
+                }
+                ["distribute-list", ..] => warnings.push(format!(
+                    "line {}: distribute-lists over access-lists are not imported; use a prefix-list: `{}`",
+                    line_no, line
+                )),
+                ["exit"] => section = Section::Global,
+                _ => warnings.push(format!("line {}: unsupported `{}`", line_no, line)),
+            },
+            Section::Global => match words.as_slice() {
+                ["frr", ..]
+                | ["hostname", ..]
+                | ["password", ..]
+                | ["log", ..]
+                | ["service", ..]
+                | ["line", ..]
+                | ["access-list", ..] => {}
+                ["ip", "prefix-list", _, "description", ..] => {}
+                ["ip", "prefix-list", name, rest @ ..] => {
+                    let (seq, rule) = match rest {
+                        ["seq", seq, rule @ ..] => (seq.parse().ok(), rule),
+                        rule => (
+                            Some(
+                                prefix_rules
+                                    .iter()
+                                    .filter(|(list, _, _)| list == name)
+                                    .map(|(_, seq, _)| seq + 5)
+                                    .max()
+                                    .unwrap_or(5),
+                            ),
+                            rule,
+                        ),
+                    };
+                    match (seq, parse_prefix_rule(rule)) {
+                        (Some(seq), Some(rule)) => {
+                            prefix_rules.push((name.to_string(), seq, rule))
+                        }
+                        _ => warnings.push(format!(
+                            "line {}: invalid prefix-list `{}`",
+                            line_no, line
+                        )),
+                    }
+                }
+                ["ip", "route", ..] => warnings.push(format!(
+                    "line {}: static routes are not imported: `{}`",
+                    line_no, line
+                )),
+                _ => warnings.push(format!("line {}: unsupported `{}`", line_no, line)),
+            },
+            Section::Other => {}
+        }
+    }
+
+    prefix_rules.sort_by_key(|(_, seq, _)| *seq);
+    for (name, _, rule) in prefix_rules {
+        match config
+            .policy
+            .prefix_lists
+            .iter_mut()
+            .find(|list| list.name == name)
+        {
+            Some(list) => list.rules.push(rule),
+            None => config.policy.prefix_lists.push(PrefixList {
+                name,
+                rules: vec![rule],
+            }),
+        }
+    }
+    for list in &config.policy.distribute_lists {
+        if !config
+            .policy
+            .prefix_lists
+            .iter()
+            .any(|prefix_list| prefix_list.name == list.prefix_list)
+        {
+            warnings.push(format!(
+                "distribute-list references unknown prefix-list {}",
+                list.prefix_list
+            ));
+        }
+    }
+
+    let network_prefixes: Vec<Ipv4Net> = networks
+        .iter()
+        .filter_map(|target| target.parse::<Ipv4Net>().ok())
+        .collect();
+    for target in &networks {
+        if target.parse::<Ipv4Net>().is_err() && !interfaces.iter().any(|i| &i.name == target) {
+            warnings.push(format!("network {} names an unknown interface", target));
+        }
+    }
+    for prefix in &network_prefixes {
+        let covered = interfaces.iter().any(|iface| {
+            iface
+                .address
+                .is_some_and(|addr| prefix.contains(&addr.addr()))
+        });
+        if !covered {
+            warnings.push(format!(
+                "network {} matches no interface address and was skipped",
+                prefix
+            ));
+        }
+    }
+
+    config.interfaces = interfaces
+        .iter()
+        .filter_map(|iface| {
+            let Some(address) = iface.address else {
+                warnings.push(format!("interface {} has no address; skipped", iface.name));
+                return None;
+            };
+            let in_rip = networks.contains(&iface.name)
+                || network_prefixes
+                    .iter()
+                    .any(|prefix| prefix.contains(&address.addr()));
+            let offset = offsets
+                .iter()
+                .find(|(name, _)| name == &iface.name)
+                .map(|(_, offset)| *offset)
+                .unwrap_or(0);
+
+            Some(InterfaceConfig {
+                name: iface.name.clone(),
+                address: address.to_string(),
+                enabled: in_rip && !iface.shutdown,
+                cost: 1 + offset,
+                passive: passive_default || passive.contains(&iface.name),
+                point_to_point: false,
+                peer: None,
+                codec: PacketCodec::default(),
+                mtu: None,
+                secondary_addresses: iface.secondary_addresses.clone(),
+                send_mode: SendMode::default(),
+                accept_from: Vec::new(),
+                reject_from: Vec::new(),
+                pacing: PacingConfig::default(),
+                advertise_connected: true,
+                auto_cost: false,
+            })
+        })
+        .collect();
+
+    config.rip.split_horizon = split_horizon;
+    config.rip.poison_reverse = poison_reverse;
+    if let Some(first) = interfaces.iter().find_map(|iface| iface.address) {
+        config.router_id = first.addr().to_string();
+    }
+
+    ImportReport { config, warnings }
+}
+
+/// Parses `permit|deny PREFIX [ge N] [le N]` of an FRR prefix-list line.
+fn parse_prefix_rule(words: &[&str]) -> Option<PrefixListRule> {
+    let (action, prefix, mut bounds) = match words {
+        [action, prefix, bounds @ ..] => (*action, *prefix, bounds),
+        _ => return None,
+    };
+    let mut rule = PrefixListRule {
+        action: match action {
+            "permit" => Action::Permit,
+            "deny" => Action::Deny,
+            _ => return None,
+        },
+        prefix: prefix.parse().ok()?,
+        ge: None,
+        le: None,
+    };
+    while let [keyword, length, rest @ ..] = bounds {
+        let length = Some(length.parse().ok()?);
+        match *keyword {
+            "ge" => rule.ge = length,
+            "le" => rule.le = length,
+            _ => return None,
+        }
+        bounds = rest;
+    }
+    bounds.is_empty().then_some(rule)
+}
+
+fn prefix(destination: Ipv4Addr, mask: Ipv4Addr) -> String {
+    format!("{}/{}", destination, u32::from(mask).count_ones())
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> (RouterConfig, Vec<Route>) {
+        let mut config = RouterConfig::default();
+        config.interfaces.push(InterfaceConfig {
+            name: "eth1".to_string(),
+            address: "10.0.0.1/30".to_string(),
+            enabled: true,
+            cost: 3,
+            passive: false,
+            point_to_point: false,
+            peer: None,
+            codec: PacketCodec::default(),
+            mtu: None,
+            secondary_addresses: Vec::new(),
+            send_mode: SendMode::default(),
+            accept_from: Vec::new(),
+            reject_from: Vec::new(),
+            pacing: PacingConfig::default(),
+            advertise_connected: true,
+            auto_cost: false,
+        });
+        let routes = vec![Route::new(
+            Ipv4Addr::new(172, 16, 0, 0),
+            Ipv4Addr::new(255, 255, 0, 0),
+            Ipv4Addr::new(10, 0, 0, 2),
+            1,
+            "eth1".to_string(),
+            RouteSource::Static,
+            None,
+        )];
+        (config, routes)
+    }
+
+    #[test]
+    fn frr_export_contains_rip_stanza() {
+        let (config, routes) = sample();
+        let text = export_config(&config, &routes, ConfigFormat::Frr);
+
+        assert!(text.contains("interface eth0\n ip address 192.168.1.1/24\n"));
+        assert!(text.contains("ip route 172.16.0.0/16 10.0.0.2 eth1\n"));
+        assert!(text.contains(" network eth1\n"));
+        assert!(text.contains(" timers basic 30 180 120\n"));
+        assert!(text.contains(" offset-list rustroute-any in 2 eth1\n"));
+        assert!(text.contains(" redistribute static\n"));
+        assert!(!text.contains("redistribute kernel"));
+
+        let mut config = config;
+        config.rip.redistribute.connected.enabled = true;
+        let text = export_config(&config, &routes, ConfigFormat::Frr);
+        assert!(text.contains(" redistribute connected metric 1\n"));
+    }
+
+    #[test]
+    fn bird_export_lists_interfaces_and_statics() {
+        let (mut config, routes) = sample();
+        config.interfaces[0].enabled = false;
+        let text = export_config(&config, &routes, ConfigFormat::Bird);
+
+        assert!(text.contains("router id 192.168.1.1;"));
+        assert!(text.contains("route 172.16.0.0/16 via 10.0.0.2;"));
+        assert!(text.contains("interface \"eth1\" {\n        metric 3;"));
+        assert!(!text.contains("\"eth0\""));
+    }
+
+    #[test]
+    fn frr_import_translates_router_rip() {
+        let text = "\
+interface eth0
+ ip address 192.168.1.1/24
+!
+interface eth1
+ ip address 10.0.0.1/30
+ ip address 10.0.1.1/30
+ no ip rip split-horizon
+!
+interface eth2
+ ip address 172.16.0.1/24
+!
+ip prefix-list customers seq 10 permit 172.16.0.0/12 le 24
+ip prefix-list customers seq 5 deny 172.16.99.0/24
+!
+router rip
+ network 192.168.0.0/16
+ network eth1
+ timers basic 20 180 90
+ passive-interface eth0
+ offset-list all in 2 eth1
+ distribute-list private in eth1
+ distribute-list prefix customers out eth0
+exit
+";
+        let report = import_frr(text);
+        let config = &report.config;
+
+        assert_eq!(config.router_id, "192.168.1.1");
+        assert_eq!(config.rip.update_interval, 20);
+        assert_eq!(config.rip.garbage_collection_timeout, 90);
+        assert!(!config.rip.split_horizon);
+
+        let eth0 = &config.interfaces[0];
+        assert!(eth0.enabled && eth0.passive);
+        let eth1 = &config.interfaces[1];
+        assert!(eth1.enabled && !eth1.passive);
+        assert_eq!(eth1.cost, 3);
+        assert_eq!(
+            eth1.secondary_addresses,
+            vec!["10.0.1.1/30".parse::<Ipv4Net>().unwrap()]
+        );
+        assert!(!config.interfaces[2].enabled);
+
+        let customers = &config.policy.prefix_lists[0];
+        assert_eq!(customers.name, "customers");
+        assert_eq!(customers.rules[0].action, Action::Deny);
+        assert_eq!(customers.rules[1].le, Some(24));
+        let list = &config.policy.distribute_lists[0];
+        assert_eq!(list.prefix_list, "customers");
+        assert_eq!(list.direction, Direction::Export);
+        assert_eq!(list.interface.as_deref(), Some("eth0"));
+
+        assert_eq!(report.warnings.len(), 1);
+        assert!(report.warnings[0].contains("distribute-list private"));
+    }
+
+    #[test]
+    fn frr_export_round_trips_through_import() {
+        let (config, _) = sample();
+        let report = import_frr(&export_config(&config, &[], ConfigFormat::Frr));
+
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+        assert_eq!(report.config.interfaces.len(), 2);
+        assert_eq!(report.config.interfaces[1].cost, 3);
+        assert_eq!(
+            report.config.rip.update_interval,
+            config.rip.update_interval
+        );
+    }
+}
@@ -1,9 +1,12 @@
 use ipnet::Ipv6Net;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::net::{Ipv6Addr, SocketAddrV6};
+use std::net::{IpAddr, Ipv6Addr, SocketAddrV6};
+use std::time::{Duration, Instant};
 use tokio::net::UdpSocket;
 
+use crate::events::{EventBus, NeighborEvent, NeighborState, RouteV6Event, WebEvent};
+use crate::metrics::Metrics;
 use crate::{RustRouteError, RustRouteResult};
 
 /// IPv6 RIP configuration
@@ -30,8 +33,8 @@ impl Default for RipV6Config {
     }
 }
 
-/// IPv6 Route entry
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+/// IPv6 Route entry; its timers run on the monotonic clock
+#[derive(Debug, Clone, PartialEq)]
 pub struct RipV6Route {
     pub prefix: Ipv6Net,
     pub next_hop: Ipv6Addr,
@@ -39,8 +42,8 @@ pub struct RipV6Route {
     pub route_tag: u16,
     pub interface: String,
     pub learned_from: Ipv6Addr,
-    pub last_updated: std::time::SystemTime,
-    pub garbage_collection_timer: Option<std::time::SystemTime>,
+    pub last_updated: Instant,
+    pub garbage_collection_timer: Option<Instant>,
 }
 
 impl RipV6Route {
@@ -58,13 +61,13 @@ impl RipV6Route {
             route_tag: 0,
             interface,
             learned_from,
-            last_updated: std::time::SystemTime::now(),
+            last_updated: Instant::now(),
             garbage_collection_timer: None,
         }
     }
 
     pub fn age_seconds(&self) -> u64 {
-        self.last_updated.elapsed().unwrap_or_default().as_secs()
+        self.last_updated.elapsed().as_secs()
     }
 
     pub fn is_expired(&self, timeout: u64) -> bool {
@@ -76,13 +79,13 @@ impl RipV6Route {
     }
 
     pub fn mark_for_garbage_collection(&mut self) {
-        self.garbage_collection_timer = Some(std::time::SystemTime::now());
+        self.garbage_collection_timer = Some(Instant::now());
         self.metric = 16; // Mark as unreachable
     }
 
     pub fn should_be_deleted(&self, gc_timeout: u64) -> bool {
         if let Some(gc_time) = self.garbage_collection_timer {
-            gc_time.elapsed().unwrap_or_default().as_secs() > gc_timeout
+            gc_time.elapsed().as_secs() > gc_timeout
         } else {
             false
         }
@@ -149,7 +152,9 @@ impl RipV6RoutingTable {
             })
     }
 
-    pub fn update_timers(&mut self) {
+    /// Age the routes: expired ones are marked unreachable and returned,
+    /// those past garbage collection are deleted
+    pub fn update_timers(&mut self) -> Vec<RipV6Route> {
         let mut to_remove = Vec::new();
         let mut to_garbage_collect = Vec::new();
 
@@ -172,11 +177,14 @@ impl RipV6RoutingTable {
         }
 
         // Mark routes for garbage collection
+        let mut withdrawn = Vec::new();
         for prefix in to_garbage_collect {
             if let Some(route) = self.routes.get_mut(&prefix) {
                 route.mark_for_garbage_collection();
+                withdrawn.push(route.clone());
             }
         }
+        withdrawn
     }
 
     pub fn route_count(&self) -> usize {
@@ -255,6 +263,10 @@ pub struct RipV6Router {
     routing_table: RipV6RoutingTable,
     socket: Option<UdpSocket>,
     interfaces: HashMap<String, Ipv6Addr>,
+    /// When each neighbor was last heard from
+    neighbors: HashMap<Ipv6Addr, Instant>,
+    metrics: Metrics,
+    events: Option<EventBus>,
 }
 
 impl RipV6Router {
@@ -266,9 +278,76 @@ impl RipV6Router {
             routing_table,
             socket: None,
             interfaces: HashMap::new(),
+            neighbors: HashMap::new(),
+            metrics: Metrics::new(),
+            events: None,
+        }
+    }
+
+    /// Count traffic and state in `metrics` (under `ripng`) and publish
+    /// route and neighbor events on `events`, like the RIPv2 router does
+    pub fn with_observability(mut self, metrics: Metrics, events: EventBus) -> Self {
+        self.metrics = metrics;
+        self.events = Some(events);
+        self
+    }
+
+    fn publish(&self, event: WebEvent) {
+        if let Some(events) = &self.events {
+            events.publish(event);
+        }
+    }
+
+    fn neighbor_timeout(&self) -> Duration {
+        Duration::from_secs(self.config.update_interval * 6)
+    }
+
+    /// Note a packet from `address`, announcing it when it is new
+    fn heard_from(&mut self, address: Ipv6Addr) {
+        if self.neighbors.insert(address, Instant::now()).is_none() {
+            log::info!("New IPv6 RIP neighbor {}", address);
+            self.publish(WebEvent::Neighbor(NeighborEvent::new(
+                IpAddr::V6(address),
+                None,
+                NeighborState::Up,
+            )));
+            self.metrics.update_v6_neighbor_count(self.neighbors.len());
         }
     }
 
+    /// Run the route timers and forget silent neighbors, publishing what
+    /// was withdrawn or lost
+    pub fn update_timers(&mut self) {
+        for route in self.routing_table.update_timers() {
+            self.metrics.increment_v6_route_changes();
+            self.publish(WebEvent::RouteV6Withdrawn(RouteV6Event::from_route(&route)));
+        }
+        self.metrics
+            .update_v6_route_count(self.routing_table.route_count());
+
+        let timeout = self.neighbor_timeout();
+        let lost: Vec<Ipv6Addr> = self
+            .neighbors
+            .iter()
+            .filter(|(_, heard)| heard.elapsed() > timeout)
+            .map(|(address, _)| *address)
+            .collect();
+        for address in lost {
+            self.neighbors.remove(&address);
+            log::info!("IPv6 RIP neighbor {} timed out", address);
+            self.publish(WebEvent::Neighbor(NeighborEvent::new(
+                IpAddr::V6(address),
+                None,
+                NeighborState::Down,
+            )));
+        }
+        self.metrics.update_v6_neighbor_count(self.neighbors.len());
+    }
+
+    pub fn neighbor_count(&self) -> usize {
+        self.neighbors.len()
+    }
+
     pub async fn start(&mut self) -> RustRouteResult<()> {
         if !self.config.enabled {
             log::info!("IPv6 RIP is disabled");
@@ -333,6 +412,7 @@ impl RipV6Router {
                 .send_to(&data, destination)
                 .await
                 .map_err(|e| RustRouteError::NetworkError(format!("IPv6 send failed: {}", e)))?;
+            self.metrics.increment_v6_packets_sent();
             log::debug!(
                 "Sent {} IPv6 routes to {}",
                 packet.entries.len(),
@@ -348,7 +428,11 @@ impl RipV6Router {
         data: &[u8],
         source: SocketAddrV6,
     ) -> RustRouteResult<()> {
-        let packet = RipV6Packet::from_bytes(data)?;
+        self.metrics.increment_v6_packets_received();
+        let packet = RipV6Packet::from_bytes(data).inspect_err(|_| {
+            self.metrics.increment_v6_packets_dropped();
+        })?;
+        self.heard_from(*source.ip());
 
         match packet.command {
             1 => self.handle_request(source).await?,
@@ -378,20 +462,25 @@ impl RipV6Router {
         for entry in packet.entries {
             let route = RipV6Route::new(
                 entry.prefix,
-                source.ip().clone(),
+                *source.ip(),
                 entry.metric as u32,
                 "unknown".to_string(), // Would need to determine actual interface
-                source.ip().clone(),
+                *source.ip(),
             );
 
+            let event = RouteV6Event::from_route(&route);
             if self.routing_table.add_route(route) {
                 log::info!(
                     "Added/updated IPv6 route: {} via {}",
                     entry.prefix,
                     source.ip()
                 );
+                self.metrics.increment_v6_route_changes();
+                self.publish(WebEvent::RouteV6(event));
             }
         }
+        self.metrics
+            .update_v6_route_count(self.routing_table.route_count());
 
         Ok(())
     }
@@ -490,6 +579,46 @@ mod tests {
         assert_eq!(best_route.prefix, prefix1);
     }
 
+    #[tokio::test]
+    async fn responses_update_ripng_metrics_and_events() {
+        let metrics = Metrics::new();
+        let events = EventBus::new(16);
+        let mut subscription = events.subscribe("test");
+        let mut router =
+            RipV6Router::new(RipV6Config::default()).with_observability(metrics.clone(), events);
+
+        let route = RipV6Route::new(
+            "2001:db8:1::/48".parse().unwrap(),
+            "fe80::1".parse().unwrap(),
+            2,
+            "eth0".to_string(),
+            "fe80::1".parse().unwrap(),
+        );
+        let packet = RipV6Packet::new_response(vec![&route]);
+        let source = SocketAddrV6::new("fe80::1".parse().unwrap(), 521, 0, 0);
+        router
+            .process_received_packet(&packet.to_bytes().unwrap(), source)
+            .await
+            .unwrap();
+        assert!(router
+            .process_received_packet(b"not a packet", source)
+            .await
+            .is_err());
+
+        let ripng = metrics.snapshot(0, 0).ripng;
+        assert_eq!(ripng.packets_received, 2);
+        assert_eq!(ripng.packets_dropped, 1);
+        assert_eq!(ripng.route_count, 1);
+        assert_eq!(ripng.neighbor_count, 1);
+        // RIPv2 counters are left alone
+        assert_eq!(metrics.snapshot(0, 0).packets_received, 0);
+
+        let kinds: Vec<&str> = std::iter::from_fn(|| subscription.try_recv().ok())
+            .map(|event| event.kind())
+            .collect();
+        assert_eq!(kinds, vec!["Neighbor", "RouteV6"]);
+    }
+
     #[tokio::test]
     async fn test_ipv6_router_creation() {
         let config = RipV6Config::default();
@@ -0,0 +1,853 @@
+//! Installation of learned routes into the kernel routing table.
+//!
+//! With `rip.install_kernel_routes` set, routes learned over RIP are added
+//! to the main table of the Linux kernel over rtnetlink, follow their next
+//! hop as it changes and are removed once they expire, are withdrawn or the
+//! router stops. They carry the `rip` protocol, shown by
+//! `ip route show proto rip`, and `KERNEL_PRIORITY` as their metric: routes
+//! added by hand or by other daemons are never replaced, and the ones an
+//! earlier run left behind are recognized and removed at startup.
+//!
+//! The same dump of the main table lists the routes of other sources for
+//! `redistribute`.
+
+use ipnet::Ipv4Net;
+use log::{debug, info, warn};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::net::Ipv4Addr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::RwLock;
+
+use crate::events::{ActivityLevel, EventBus, WebEvent};
+use crate::routing_table::{Route, RouteSource, RoutingTable};
+use crate::watchdog::Heartbeat;
+use crate::{RustRouteError, RustRouteResult};
+
+/// `RTPROT_RIP` from `/etc/iproute2/rt_protos`
+pub const RTPROT_RIP: u8 = 189;
+/// Protocol of the subnet routes the kernel adds for interface addresses
+pub const RTPROT_KERNEL: u8 = 2;
+/// Kernel metric of installed routes
+pub const KERNEL_PRIORITY: u32 = 20;
+/// How often the kernel table is compared with the routing table when no
+/// route changes arrive, restoring routes the kernel dropped with a link
+pub const SYNC_INTERVAL: Duration = Duration::from_secs(10);
+
+const NLMSG_HEADER_LEN: usize = 16;
+const RTMSG_LEN: usize = 12;
+const NLMSG_ERROR: u16 = 2;
+const NLMSG_DONE: u16 = 3;
+const RTM_NEWROUTE: u16 = 24;
+const RTM_DELROUTE: u16 = 25;
+const RTM_GETROUTE: u16 = 26;
+const NLM_F_REQUEST: u16 = 0x1;
+const NLM_F_ACK: u16 = 0x4;
+const NLM_F_REPLACE: u16 = 0x100;
+const NLM_F_DUMP: u16 = 0x300;
+const NLM_F_CREATE: u16 = 0x400;
+const AF_INET: u8 = 2;
+const RT_TABLE_MAIN: u8 = 254;
+const RTN_UNICAST: u8 = 1;
+const RT_SCOPE_UNIVERSE: u8 = 0;
+const RT_SCOPE_LINK: u8 = 253;
+const RT_SCOPE_NOWHERE: u8 = 255;
+const RTA_DST: u16 = 1;
+const RTA_OIF: u16 = 4;
+const RTA_GATEWAY: u16 = 5;
+const RTA_PRIORITY: u16 = 6;
+const RTA_TABLE: u16 = 15;
+
+/// A route as the kernel holds it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KernelRoute {
+    pub destination: Ipv4Net,
+    /// Absent for neighbors reached directly on the interface
+    pub gateway: Option<Ipv4Addr>,
+    pub interface: String,
+}
+
+impl KernelRoute {
+    /// The kernel route for `route`, when it is a reachable route learned
+    /// over RIP
+    pub fn for_route(route: &Route, infinity: u32) -> Option<Self> {
+        if route.source != RouteSource::Dynamic || route.metric >= infinity {
+            return None;
+        }
+        let destination = Ipv4Net::with_netmask(route.destination, route.subnet_mask)
+            .ok()?
+            .trunc();
+        let gateway = Some(route.next_hop)
+            .filter(|hop| !hop.is_unspecified())
+            .or(route.learned_from);
+        Some(Self {
+            destination,
+            gateway,
+            interface: route.interface.clone(),
+        })
+    }
+}
+
+/// A route another source put in the kernel's main table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostRoute {
+    pub destination: Ipv4Net,
+    pub gateway: Option<Ipv4Addr>,
+    pub interface: String,
+    /// Added by the kernel for an address of the interface
+    pub connected: bool,
+}
+
+/// The unicast IPv4 routes of the kernel's main table, other than ours
+pub fn host_routes() -> RustRouteResult<Vec<HostRoute>> {
+    let mut netlink = Netlink::open().map_err(|err| {
+        RustRouteError::NetworkError(format!("Failed to open rtnetlink socket: {}", err))
+    })?;
+    let replies = netlink.dump().map_err(|err| {
+        RustRouteError::NetworkError(format!("Failed to read the kernel routing table: {}", err))
+    })?;
+    Ok(replies
+        .into_iter()
+        .filter_map(|reply| reply.host_route(interface_name))
+        .collect())
+}
+
+/// The routes the kernel should hold for `table`
+pub fn desired(table: &RoutingTable) -> BTreeMap<Ipv4Net, KernelRoute> {
+    let infinity = table.timers().infinity;
+    table
+        .get_all_routes()
+        .into_iter()
+        .filter_map(|route| KernelRoute::for_route(route, infinity))
+        .map(|route| (route.destination, route))
+        .collect()
+}
+
+/// Result of one synchronization
+#[derive(Debug, Default, PartialEq)]
+pub struct FibSync {
+    pub installed: usize,
+    pub removed: usize,
+    /// Prefixes the kernel refused, with its reason; retried on the next sync
+    pub failed: Vec<(Ipv4Net, String)>,
+}
+
+/// The routes this router installed in the kernel
+pub struct KernelFib {
+    netlink: Netlink,
+    installed: BTreeMap<Ipv4Net, KernelRoute>,
+}
+
+impl KernelFib {
+    pub fn open() -> RustRouteResult<Self> {
+        let netlink = Netlink::open().map_err(|err| {
+            RustRouteError::NetworkError(format!("Failed to open rtnetlink socket: {}", err))
+        })?;
+        Ok(Self {
+            netlink,
+            installed: BTreeMap::new(),
+        })
+    }
+
+    /// Prefixes of the routes of ours in the kernel table
+    fn kernel_routes(&mut self) -> RustRouteResult<BTreeSet<Ipv4Net>> {
+        let replies = self.netlink.dump().map_err(|err| {
+            RustRouteError::NetworkError(format!(
+                "Failed to read the kernel routing table: {}",
+                err
+            ))
+        })?;
+        Ok(replies
+            .into_iter()
+            .filter_map(|reply| reply.ours())
+            .collect())
+    }
+
+    /// Remove the routes an earlier run left in the kernel, returning how
+    /// many there were
+    pub fn flush_stale(&mut self) -> RustRouteResult<usize> {
+        let stale = self.kernel_routes()?;
+        for destination in &stale {
+            let route = KernelRoute {
+                destination: *destination,
+                gateway: None,
+                interface: String::new(),
+            };
+            self.netlink.delete(&route).map_err(|err| {
+                RustRouteError::NetworkError(format!(
+                    "Failed to remove stale kernel route {}: {}",
+                    destination, err
+                ))
+            })?;
+        }
+        Ok(stale.len())
+    }
+
+    /// Forget installed routes the kernel no longer has, such as those it
+    /// flushed when their interface went down, so the next sync adds them again
+    pub fn refresh(&mut self) -> RustRouteResult<()> {
+        let present = self.kernel_routes()?;
+        self.installed
+            .retain(|destination, _| present.contains(destination));
+        Ok(())
+    }
+
+    /// Bring the kernel table in line with `desired`
+    pub fn sync(&mut self, desired: &BTreeMap<Ipv4Net, KernelRoute>) -> FibSync {
+        let mut outcome = FibSync::default();
+        let withdrawn: Vec<KernelRoute> = self
+            .installed
+            .values()
+            .filter(|route| !desired.contains_key(&route.destination))
+            .cloned()
+            .collect();
+        for route in withdrawn {
+            match self.netlink.delete(&route) {
+                Ok(()) => {
+                    self.installed.remove(&route.destination);
+                    outcome.removed += 1;
+                }
+                Err(err) => outcome.failed.push((route.destination, err.to_string())),
+            }
+        }
+
+        for route in desired.values() {
+            if self.installed.get(&route.destination) == Some(route) {
+                continue;
+            }
+            match self.netlink.replace(route) {
+                Ok(()) => {
+                    self.installed.insert(route.destination, route.clone());
+                    outcome.installed += 1;
+                }
+                Err(err) => outcome.failed.push((route.destination, err.to_string())),
+            }
+        }
+        outcome
+    }
+
+    pub fn installed(&self) -> usize {
+        self.installed.len()
+    }
+}
+
+impl Drop for KernelFib {
+    fn drop(&mut self) {
+        let removed = self.sync(&BTreeMap::new()).removed;
+        if removed > 0 {
+            info!("Removed {} routes from the kernel routing table", removed);
+        }
+    }
+}
+
+/// Keep the kernel table in line with the routing table, syncing on every
+/// route change and every `SYNC_INTERVAL`
+pub async fn run(routing_table: Arc<RwLock<RoutingTable>>, events: EventBus, heartbeat: Heartbeat) {
+    let mut fib = match KernelFib::open() {
+        Ok(fib) => fib,
+        Err(err) => {
+            warn!("Kernel routes are not installed: {}", err);
+            events.publish_activity(
+                ActivityLevel::Error,
+                format!("Kernel routes are not installed: {}", err),
+            );
+            // Stay alive so the watchdog does not restart us over and over
+            loop {
+                heartbeat.beat();
+                tokio::time::sleep(SYNC_INTERVAL).await;
+            }
+        }
+    };
+    match fib.flush_stale() {
+        Ok(0) => {}
+        Ok(stale) => info!("Removed {} stale kernel routes of an earlier run", stale),
+        Err(err) => warn!("{}", err),
+    }
+
+    let mut receiver = events.subscribe("kernel-routes");
+    let mut interval = tokio::time::interval(SYNC_INTERVAL);
+    let mut failing = BTreeSet::new();
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                if let Err(err) = fib.refresh() {
+                    warn!("{}", err);
+                }
+            }
+            event = receiver.recv() => match event {
+                Ok(WebEvent::Route(_) | WebEvent::RouteWithdrawn(_)) => {}
+                Ok(_) => continue,
+                Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => return,
+            },
+        }
+        heartbeat.beat();
+
+        let desired = desired(&*routing_table.read().await);
+        let outcome = fib.sync(&desired);
+        if outcome.installed > 0 || outcome.removed > 0 {
+            debug!(
+                "Kernel routes: {} installed or changed, {} removed, {} in place",
+                outcome.installed,
+                outcome.removed,
+                fib.installed()
+            );
+        }
+        // Report each refusal once rather than on every retry
+        let now_failing: BTreeSet<Ipv4Net> =
+            outcome.failed.iter().map(|(prefix, _)| *prefix).collect();
+        for (prefix, reason) in &outcome.failed {
+            if !failing.contains(prefix) {
+                warn!("Kernel refused route {}: {}", prefix, reason);
+            }
+        }
+        failing = now_failing;
+    }
+}
+
+/// A message from the kernel, as far as it concerns us
+#[derive(Debug, PartialEq)]
+enum Reply {
+    /// Acknowledgment of a request, or its failure as a negative errno
+    Error {
+        sequence: u32,
+        code: i32,
+    },
+    Route {
+        destination: Ipv4Net,
+        table: u32,
+        protocol: u8,
+        priority: u32,
+        route_type: u8,
+        gateway: Option<Ipv4Addr>,
+        /// Index of the outgoing interface, 0 when the kernel gave none
+        interface_index: u32,
+    },
+    Done,
+    Other,
+}
+
+impl Reply {
+    /// The destination of a route of ours in the main table
+    fn ours(self) -> Option<Ipv4Net> {
+        match self {
+            Reply::Route {
+                destination,
+                table,
+                protocol: RTPROT_RIP,
+                priority: KERNEL_PRIORITY,
+                ..
+            } if table == u32::from(RT_TABLE_MAIN) => Some(destination),
+            _ => None,
+        }
+    }
+
+    /// A unicast route of another source in the main table, with the name
+    /// `interface_name` gives its outgoing interface
+    fn host_route(self, interface_name: impl Fn(u32) -> Option<String>) -> Option<HostRoute> {
+        match self {
+            Reply::Route {
+                destination,
+                table,
+                protocol,
+                route_type: RTN_UNICAST,
+                gateway,
+                interface_index,
+                ..
+            } if table == u32::from(RT_TABLE_MAIN) && protocol != RTPROT_RIP => Some(HostRoute {
+                destination: destination.trunc(),
+                gateway,
+                interface: interface_name(interface_index)?,
+                connected: protocol == RTPROT_KERNEL && gateway.is_none(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+fn push_attribute(message: &mut Vec<u8>, kind: u16, data: &[u8]) {
+    let length = 4 + data.len();
+    message.extend_from_slice(&(length as u16).to_ne_bytes());
+    message.extend_from_slice(&kind.to_ne_bytes());
+    message.extend_from_slice(data);
+    message.resize(message.len() + (align(length) - length), 0);
+}
+
+fn align(length: usize) -> usize {
+    (length + 3) & !3
+}
+
+fn header(kind: u16, flags: u16, sequence: u32) -> Vec<u8> {
+    let mut message = Vec::with_capacity(64);
+    message.extend_from_slice(&0u32.to_ne_bytes());
+    message.extend_from_slice(&kind.to_ne_bytes());
+    message.extend_from_slice(&flags.to_ne_bytes());
+    message.extend_from_slice(&sequence.to_ne_bytes());
+    message.extend_from_slice(&0u32.to_ne_bytes());
+    message
+}
+
+fn finish(mut message: Vec<u8>) -> Vec<u8> {
+    let length = (message.len() as u32).to_ne_bytes();
+    message[..4].copy_from_slice(&length);
+    message
+}
+
+/// `RTM_NEWROUTE` replacing any route of ours to the prefix, or
+/// `RTM_DELROUTE` when `interface_index` is `None`
+fn route_message(sequence: u32, route: &KernelRoute, interface_index: Option<u32>) -> Vec<u8> {
+    let (kind, flags, scope, route_type) = match interface_index {
+        Some(_) => (
+            RTM_NEWROUTE,
+            NLM_F_REQUEST | NLM_F_ACK | NLM_F_CREATE | NLM_F_REPLACE,
+            if route.gateway.is_some() {
+                RT_SCOPE_UNIVERSE
+            } else {
+                RT_SCOPE_LINK
+            },
+            RTN_UNICAST,
+        ),
+        None => (RTM_DELROUTE, NLM_F_REQUEST | NLM_F_ACK, RT_SCOPE_NOWHERE, 0),
+    };
+    let mut message = header(kind, flags, sequence);
+    message.extend_from_slice(&[
+        AF_INET,
+        route.destination.prefix_len(),
+        0,
+        0,
+        RT_TABLE_MAIN,
+        RTPROT_RIP,
+        scope,
+        route_type,
+    ]);
+    message.extend_from_slice(&0u32.to_ne_bytes());
+    push_attribute(&mut message, RTA_DST, &route.destination.network().octets());
+    push_attribute(&mut message, RTA_PRIORITY, &KERNEL_PRIORITY.to_ne_bytes());
+    if let Some(index) = interface_index {
+        if let Some(gateway) = route.gateway {
+            push_attribute(&mut message, RTA_GATEWAY, &gateway.octets());
+        }
+        push_attribute(&mut message, RTA_OIF, &index.to_ne_bytes());
+    }
+    finish(message)
+}
+
+/// `RTM_GETROUTE` dumping the IPv4 routes of every table
+fn dump_message(sequence: u32) -> Vec<u8> {
+    let mut message = header(RTM_GETROUTE, NLM_F_REQUEST | NLM_F_DUMP, sequence);
+    message.extend_from_slice(&[AF_INET, 0, 0, 0, 0, 0, 0, 0]);
+    message.extend_from_slice(&0u32.to_ne_bytes());
+    finish(message)
+}
+
+fn read_u32(data: &[u8]) -> Option<u32> {
+    Some(u32::from_ne_bytes(data.get(..4)?.try_into().ok()?))
+}
+
+fn read_u16(data: &[u8]) -> Option<u16> {
+    Some(u16::from_ne_bytes(data.get(..2)?.try_into().ok()?))
+}
+
+/// Split a datagram from the kernel into its messages
+fn parse_replies(mut data: &[u8]) -> Vec<Reply> {
+    let mut replies = Vec::new();
+    while data.len() >= NLMSG_HEADER_LEN {
+        let length = read_u32(data).unwrap_or(0) as usize;
+        if length < NLMSG_HEADER_LEN || length > data.len() {
+            break;
+        }
+        let kind = read_u16(&data[4..]).unwrap_or(0);
+        let sequence = read_u32(&data[8..]).unwrap_or(0);
+        let payload = &data[NLMSG_HEADER_LEN..length];
+        replies.push(match kind {
+            NLMSG_ERROR => Reply::Error {
+                sequence,
+                code: read_u32(payload).map_or(0, |code| code as i32),
+            },
+            NLMSG_DONE => Reply::Done,
+            RTM_NEWROUTE => parse_route(payload).unwrap_or(Reply::Other),
+            _ => Reply::Other,
+        });
+        data = &data[align(length).min(data.len())..];
+    }
+    replies
+}
+
+fn parse_route(payload: &[u8]) -> Option<Reply> {
+    let header = payload.get(..RTMSG_LEN)?;
+    if header[0] != AF_INET {
+        return Some(Reply::Other);
+    }
+    let prefix_len = header[1];
+    let mut table = u32::from(header[4]);
+    let protocol = header[5];
+    let route_type = header[7];
+    let mut destination = Ipv4Addr::UNSPECIFIED;
+    let mut priority = 0;
+    let mut gateway = None;
+    let mut interface_index = 0;
+
+    let mut attributes = &payload[RTMSG_LEN..];
+    while attributes.len() >= 4 {
+        let length = usize::from(read_u16(attributes)?);
+        if length < 4 || length > attributes.len() {
+            break;
+        }
+        let kind = read_u16(&attributes[2..])?;
+        let data = &attributes[4..length];
+        match kind {
+            RTA_DST if data.len() == 4 => {
+                destination = Ipv4Addr::new(data[0], data[1], data[2], data[3])
+            }
+            RTA_GATEWAY if data.len() == 4 => {
+                gateway = Some(Ipv4Addr::new(data[0], data[1], data[2], data[3]))
+            }
+            RTA_OIF => interface_index = read_u32(data)?,
+            RTA_TABLE => table = read_u32(data)?,
+            RTA_PRIORITY => priority = read_u32(data)?,
+            _ => {}
+        }
+        attributes = &attributes[align(length).min(attributes.len())..];
+    }
+    Some(Reply::Route {
+        destination: Ipv4Net::new(destination, prefix_len).ok()?,
+        table,
+        protocol,
+        priority,
+        route_type,
+        gateway,
+        interface_index,
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn interface_name(index: u32) -> Option<String> {
+    if index == 0 {
+        return None;
+    }
+    let mut name = [0 as libc::c_char; libc::IF_NAMESIZE];
+    // SAFETY: `name` has room for the IF_NAMESIZE bytes if_indextoname writes
+    let found = unsafe { libc::if_indextoname(index, name.as_mut_ptr()) };
+    if found.is_null() {
+        return None;
+    }
+    // SAFETY: on success `name` holds a NUL-terminated interface name
+    let name = unsafe { std::ffi::CStr::from_ptr(name.as_ptr()) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn interface_name(_index: u32) -> Option<String> {
+    None
+}
+
+/// A route netlink socket
+#[cfg(target_os = "linux")]
+struct Netlink {
+    fd: std::os::fd::OwnedFd,
+    sequence: u32,
+}
+
+#[cfg(target_os = "linux")]
+impl Netlink {
+    fn open() -> io::Result<Self> {
+        use std::os::fd::FromRawFd;
+
+        // SAFETY: plain socket(2) call; the descriptor is owned from here on
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+                libc::NETLINK_ROUTE,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `fd` was just returned by socket(2) and nothing else owns it
+        let fd = unsafe { std::os::fd::OwnedFd::from_raw_fd(fd) };
+
+        // A kernel that never answers must not stall the sync task
+        let timeout = libc::timeval {
+            tv_sec: 5,
+            tv_usec: 0,
+        };
+        // SAFETY: the option value is a live timeval and its size is passed along
+        let result = unsafe {
+            libc::setsockopt(
+                std::os::fd::AsRawFd::as_raw_fd(&fd),
+                libc::SOL_SOCKET,
+                libc::SO_RCVTIMEO,
+                &timeout as *const libc::timeval as *const libc::c_void,
+                std::mem::size_of::<libc::timeval>() as libc::socklen_t,
+            )
+        };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { fd, sequence: 0 })
+    }
+
+    fn next_sequence(&mut self) -> u32 {
+        self.sequence = self.sequence.wrapping_add(1);
+        self.sequence
+    }
+
+    fn send(&self, message: &[u8]) -> io::Result<()> {
+        use std::os::fd::AsRawFd;
+
+        // SAFETY: `message` is a live buffer of the length passed; the kernel
+        // is the default destination of an unbound netlink socket
+        let sent = unsafe {
+            libc::send(
+                self.fd.as_raw_fd(),
+                message.as_ptr() as *const libc::c_void,
+                message.len(),
+                0,
+            )
+        };
+        if sent < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    fn receive(&self) -> io::Result<Vec<Reply>> {
+        use std::os::fd::AsRawFd;
+
+        let mut buffer = vec![0u8; 32 * 1024];
+        // SAFETY: the kernel writes at most `buffer.len()` bytes into it
+        let received = unsafe {
+            libc::recv(
+                self.fd.as_raw_fd(),
+                buffer.as_mut_ptr() as *mut libc::c_void,
+                buffer.len(),
+                0,
+            )
+        };
+        if received < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(parse_replies(&buffer[..received as usize]))
+    }
+
+    /// Send `message` and wait for its acknowledgment
+    fn request(&mut self, sequence: u32, message: &[u8]) -> io::Result<()> {
+        self.send(message)?;
+        loop {
+            for reply in self.receive()? {
+                match reply {
+                    Reply::Error {
+                        sequence: acked,
+                        code,
+                    } if acked == sequence => {
+                        return match code {
+                            0 => Ok(()),
+                            code => Err(io::Error::from_raw_os_error(-code)),
+                        };
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn replace(&mut self, route: &KernelRoute) -> io::Result<()> {
+        let name = std::ffi::CString::new(route.interface.as_str())
+            .map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+        // SAFETY: `name` is a NUL-terminated string that outlives the call
+        let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        if index == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no interface named {}", route.interface),
+            ));
+        }
+        let sequence = self.next_sequence();
+        self.request(sequence, &route_message(sequence, route, Some(index)))
+    }
+
+    /// Remove our route to the prefix; one already gone counts as removed
+    fn delete(&mut self, route: &KernelRoute) -> io::Result<()> {
+        let sequence = self.next_sequence();
+        match self.request(sequence, &route_message(sequence, route, None)) {
+            Err(err) if err.raw_os_error() == Some(libc::ESRCH) => Ok(()),
+            result => result,
+        }
+    }
+
+    fn dump(&mut self) -> io::Result<Vec<Reply>> {
+        let sequence = self.next_sequence();
+        self.send(&dump_message(sequence))?;
+        let mut routes = Vec::new();
+        loop {
+            for reply in self.receive()? {
+                match reply {
+                    Reply::Done => return Ok(routes),
+                    Reply::Error { code, .. } if code != 0 => {
+                        return Err(io::Error::from_raw_os_error(-code))
+                    }
+                    route @ Reply::Route { .. } => routes.push(route),
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// rtnetlink is specific to Linux
+#[cfg(not(target_os = "linux"))]
+struct Netlink;
+
+#[cfg(not(target_os = "linux"))]
+impl Netlink {
+    fn open() -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "kernel routes can only be installed on Linux",
+        ))
+    }
+
+    fn replace(&mut self, _route: &KernelRoute) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    fn delete(&mut self, _route: &KernelRoute) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    fn dump(&mut self) -> io::Result<Vec<Reply>> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_reachable_rip_routes_are_installed() {
+        let mut table = RoutingTable::new();
+        table.install_direct_route(
+            Ipv4Addr::new(192, 168, 1, 0),
+            Ipv4Addr::new(255, 255, 255, 0),
+            "eth0".to_string(),
+        );
+        let mut learned = Route::new(
+            Ipv4Addr::new(10, 1, 0, 0),
+            Ipv4Addr::new(255, 255, 0, 0),
+            Ipv4Addr::new(192, 168, 1, 2),
+            2,
+            "eth0".to_string(),
+            RouteSource::Dynamic,
+            Some(Ipv4Addr::new(192, 168, 1, 2)),
+        );
+        table.add_or_replace(learned.clone());
+        learned.destination = Ipv4Addr::new(10, 2, 0, 0);
+        learned.metric = 16;
+        table.add_or_replace(learned);
+
+        let desired = desired(&table);
+        let prefixes: Vec<String> = desired.keys().map(|prefix| prefix.to_string()).collect();
+        assert_eq!(prefixes, vec!["10.1.0.0/16"]);
+        assert_eq!(
+            desired.values().next().unwrap().gateway,
+            Some(Ipv4Addr::new(192, 168, 1, 2))
+        );
+    }
+
+    #[test]
+    fn route_messages_round_trip_through_the_parser() {
+        let route = KernelRoute {
+            destination: "10.1.0.0/16".parse().unwrap(),
+            gateway: Some(Ipv4Addr::new(192, 168, 1, 2)),
+            interface: "eth0".to_string(),
+        };
+        let mut message = route_message(7, &route, Some(3));
+        assert_eq!(read_u32(&message).unwrap() as usize, message.len());
+        assert_eq!(read_u16(&message[4..]), Some(RTM_NEWROUTE));
+        // 16 header + 12 rtmsg + destination, priority, gateway and interface
+        assert_eq!(message.len(), 16 + 12 + 4 * 8);
+
+        // Dumps list routes in the same layout
+        let error = {
+            let mut ack = header(NLMSG_ERROR, 0, 7);
+            ack.extend_from_slice(&(-17i32).to_ne_bytes());
+            finish(ack)
+        };
+        message.extend_from_slice(&error);
+        let replies = parse_replies(&message);
+        assert_eq!(
+            replies[0],
+            Reply::Route {
+                destination: route.destination,
+                table: u32::from(RT_TABLE_MAIN),
+                protocol: RTPROT_RIP,
+                priority: KERNEL_PRIORITY,
+                route_type: RTN_UNICAST,
+                gateway: route.gateway,
+                interface_index: 3,
+            }
+        );
+        assert_eq!(
+            replies[1],
+            Reply::Error {
+                sequence: 7,
+                code: -17
+            }
+        );
+
+        let delete = route_message(8, &route, None);
+        assert_eq!(read_u16(&delete[4..]), Some(RTM_DELROUTE));
+        assert_eq!(delete.len(), 16 + 12 + 2 * 8);
+        assert_eq!(parse_replies(&delete)[0], Reply::Other);
+        assert_eq!(
+            Reply::Route {
+                destination: route.destination,
+                table: 100,
+                protocol: RTPROT_RIP,
+                priority: KERNEL_PRIORITY,
+                route_type: RTN_UNICAST,
+                gateway: route.gateway,
+                interface_index: 3,
+            }
+            .ours(),
+            None
+        );
+    }
+
+    #[test]
+    fn host_routes_leave_out_ours_and_other_tables() {
+        let name = |index: u32| (index == 2).then(|| "eth1".to_string());
+        let reply = |protocol: u8, table: u8, gateway: Option<Ipv4Addr>| Reply::Route {
+            destination: "10.5.0.0/16".parse().unwrap(),
+            table: u32::from(table),
+            protocol,
+            priority: 0,
+            route_type: RTN_UNICAST,
+            gateway,
+            interface_index: 2,
+        };
+
+        let connected = reply(RTPROT_KERNEL, RT_TABLE_MAIN, None).host_route(name);
+        assert!(connected.unwrap().connected);
+        let static_route = reply(4, RT_TABLE_MAIN, Some(Ipv4Addr::new(10, 0, 0, 1)))
+            .host_route(name)
+            .unwrap();
+        assert!(!static_route.connected);
+        assert_eq!(static_route.interface, "eth1");
+
+        assert_eq!(
+            reply(RTPROT_RIP, RT_TABLE_MAIN, None).host_route(name),
+            None
+        );
+        assert_eq!(reply(RTPROT_KERNEL, 255, None).host_route(name), None);
+        let unknown_interface = |_: u32| None;
+        assert_eq!(
+            reply(4, RT_TABLE_MAIN, None).host_route(unknown_interface),
+            None
+        );
+    }
+}
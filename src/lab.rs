@@ -0,0 +1,630 @@
+//! Labs of several real routers on one host.
+//!
+//! `rust-route lab up topology.yaml` writes a configuration for each router
+//! of the topology into the lab directory, starts one `rust-route start`
+//! process per router and stops them all again on Ctrl-C; `lab down` stops
+//! the processes of a lab whose runner did not get to. Every link is a /31
+//! on the loopback range 127.100.0.0/16, so the routers exchange real RIP
+//! datagrams on one unprivileged port without root, and each router's web
+//! API listens on its own port.
+//!
+//! ```yaml
+//! name: triangle
+//! routers:
+//!   - name: r1
+//!     networks: [10.1.0.0/24]
+//!   - name: r2
+//!   - name: r3
+//! links:
+//!   - [r1, r2]
+//!   - [r2, r3]
+//!   - [r3, r1]
+//! ```
+//!
+//! Stub networks become passive interfaces. Their addresses do not exist on
+//! the host, so a router logs that it cannot bind them and still advertises
+//! them.
+//!
+//! With `namespaces: true`, which needs root, each router runs in a network
+//! namespace of its own instead. Links become veth pairs numbered as /31s
+//! from 172.31.0.0/16, so updates cross real interfaces, and stub networks
+//! get interfaces of their own that the routers bind like any other.
+//! A management veth pair per router, from 172.30.0.0/16, makes its web API
+//! reachable from the host.
+
+use ipnet::Ipv4Net;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+
+use crate::config_manager::{InterfaceConfig, RouterConfig};
+use crate::{RustRouteError, RustRouteResult};
+
+/// First address of the loopback range the links are numbered from
+const LINK_BASE: Ipv4Addr = Ipv4Addr::new(127, 100, 0, 0);
+/// /31 links that fit in 127.100.0.0/16
+const MAX_LINKS: usize = 32768;
+/// Links between namespaces, numbered as /30s
+const NETNS_LINK_BASE: Ipv4Addr = Ipv4Addr::new(172, 31, 0, 0);
+/// Management links between the host and each namespace, as /30s
+const NETNS_MGMT_BASE: Ipv4Addr = Ipv4Addr::new(172, 30, 0, 0);
+/// /30 links or routers that fit in a /16
+const MAX_NETNS: usize = 16384;
+/// Interface name of the management link inside a namespace
+const MGMT_INTERFACE: &str = "mgmt";
+/// Records the running processes for `lab down`
+const STATE_FILE: &str = "lab-state.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Topology {
+    #[serde(default = "default_name")]
+    pub name: String,
+    /// RIP port shared by every router
+    #[serde(default = "default_rip_port")]
+    pub rip_port: u16,
+    /// Web API port of the first router; the others count up from it
+    #[serde(default = "default_web_port")]
+    pub web_port: u16,
+    /// Where configurations, logs and state go; `lab-<name>` next to the
+    /// topology file by default
+    #[serde(default)]
+    pub directory: Option<PathBuf>,
+    pub routers: Vec<LabRouter>,
+    /// Pairs of router names
+    #[serde(default)]
+    pub links: Vec<(String, String)>,
+    /// Run each router in its own network namespace, connected by veth pairs
+    #[serde(default)]
+    pub namespaces: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LabRouter {
+    pub name: String,
+    /// Stub networks the router originates
+    #[serde(default)]
+    pub networks: Vec<Ipv4Net>,
+}
+
+fn default_name() -> String {
+    "lab".to_string()
+}
+
+fn default_rip_port() -> u16 {
+    5520
+}
+
+fn default_web_port() -> u16 {
+    18080
+}
+
+impl Topology {
+    /// Read and check a topology file
+    pub fn load(path: &Path) -> RustRouteResult<Self> {
+        let content = fs::read_to_string(path).map_err(|err| {
+            RustRouteError::ConfigError(format!("Cannot read {}: {}", path.display(), err))
+        })?;
+        let mut topology: Topology = serde_yaml::from_str(&content).map_err(|err| {
+            RustRouteError::ConfigError(format!("Invalid topology {}: {}", path.display(), err))
+        })?;
+        if topology.directory.is_none() {
+            let parent = path.parent().unwrap_or(Path::new("."));
+            topology.directory = Some(parent.join(format!("lab-{}", topology.name)));
+        }
+        topology.validate()?;
+        Ok(topology)
+    }
+
+    pub fn validate(&self) -> RustRouteResult<()> {
+        let invalid = |message: String| Err(RustRouteError::ConfigError(message));
+        if self.routers.is_empty() {
+            return invalid("A topology needs at least one router".to_string());
+        }
+        let valid_name = |name: &str| {
+            !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        };
+        if !valid_name(&self.name) {
+            return invalid(format!(
+                "Lab name {:?} may only use letters, digits, - and _",
+                self.name
+            ));
+        }
+        let mut names = HashSet::new();
+        for router in &self.routers {
+            if !valid_name(&router.name) {
+                return invalid(format!(
+                    "Router name {:?} may only use letters, digits, - and _",
+                    router.name
+                ));
+            }
+            if !names.insert(router.name.as_str()) {
+                return invalid(format!("Router {} is listed twice", router.name));
+            }
+        }
+        if self.links.len() > MAX_LINKS {
+            return invalid(format!("A lab has at most {} links", MAX_LINKS));
+        }
+        if self.namespaces && self.routers.len() > MAX_NETNS {
+            return invalid(format!(
+                "A lab with namespaces has at most {} routers",
+                MAX_NETNS
+            ));
+        }
+        for (a, b) in &self.links {
+            for end in [a, b] {
+                if !names.contains(end.as_str()) {
+                    return invalid(format!("Link {}-{} names unknown router {}", a, b, end));
+                }
+            }
+            if a == b {
+                return invalid(format!("Link {}-{} connects a router to itself", a, b));
+            }
+        }
+        if usize::from(self.web_port) + self.routers.len() > usize::from(u16::MAX) {
+            return invalid(format!(
+                "web_port {} leaves no room for {} routers",
+                self.web_port,
+                self.routers.len()
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn directory(&self) -> PathBuf {
+        self.directory
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(format!("lab-{}", self.name)))
+    }
+
+    /// Namespace of `router` when the lab runs in namespaces
+    pub fn namespace(&self, router: &str) -> String {
+        format!("rr-{}-{}", self.name, router)
+    }
+
+    /// Addresses of both ends of link `index`
+    fn link_addresses(&self, index: usize) -> (Ipv4Addr, Ipv4Addr) {
+        let base = if self.namespaces {
+            NETNS_LINK_BASE
+        } else {
+            LINK_BASE
+        };
+        let base = u32::from(base) + 2 * index as u32;
+        (Ipv4Addr::from(base), Ipv4Addr::from(base + 1))
+    }
+
+    /// Host and namespace side of the management link of router `index`
+    fn mgmt_addresses(index: usize) -> (Ipv4Addr, Ipv4Addr) {
+        let base = u32::from(NETNS_MGMT_BASE) + 4 * index as u32;
+        (Ipv4Addr::from(base + 1), Ipv4Addr::from(base + 2))
+    }
+
+    /// First host address of a stub network, where the router sits
+    fn stub_address(network: &Ipv4Net) -> String {
+        let host = network.hosts().next().unwrap_or(network.addr());
+        format!("{}/{}", host, network.prefix_len())
+    }
+
+    /// `ip` commands that build the namespaces, veth pairs and dummy
+    /// interfaces of the lab
+    pub fn netns_setup(&self) -> Vec<Vec<String>> {
+        let mut commands = Vec::new();
+        let mut run = |args: &[&str]| {
+            commands.push(args.iter().map(|arg| arg.to_string()).collect());
+        };
+        for (index, router) in self.routers.iter().enumerate() {
+            let ns = self.namespace(&router.name);
+            let host_side = format!("rrm{}", index);
+            let (host, inside) = Self::mgmt_addresses(index);
+            run(&["netns", "add", &ns]);
+            run(&["-n", &ns, "link", "set", "lo", "up"]);
+            let peer = format!("{}n", host_side);
+            run(&[
+                "link", "add", &host_side, "type", "veth", "peer", "name", &peer,
+            ]);
+            run(&["link", "set", &peer, "netns", &ns]);
+            run(&["-n", &ns, "link", "set", &peer, "name", MGMT_INTERFACE]);
+            run(&["addr", "add", &format!("{}/30", host), "dev", &host_side]);
+            run(&["link", "set", &host_side, "up"]);
+            run(&[
+                "-n",
+                &ns,
+                "addr",
+                "add",
+                &format!("{}/30", inside),
+                "dev",
+                MGMT_INTERFACE,
+            ]);
+            run(&["-n", &ns, "link", "set", MGMT_INTERFACE, "up"]);
+            for (stub, network) in router.networks.iter().enumerate() {
+                let name = format!("stub{}", stub);
+                // A veth pair rather than a dummy interface, which not every
+                // kernel has; the peer only keeps the link up
+                let peer = format!("{}p", name);
+                run(&[
+                    "-n", &ns, "link", "add", &name, "type", "veth", "peer", "name", &peer,
+                ]);
+                run(&["-n", &ns, "link", "set", &peer, "up"]);
+                run(&[
+                    "-n",
+                    &ns,
+                    "addr",
+                    "add",
+                    &Self::stub_address(network),
+                    "dev",
+                    &name,
+                ]);
+                run(&["-n", &ns, "link", "set", &name, "up"]);
+            }
+        }
+        for (index, (a, b)) in self.links.iter().enumerate() {
+            let name = format!("lab{}", index);
+            let (first, second) = self.link_addresses(index);
+            let (ns_a, ns_b) = (self.namespace(a), self.namespace(b));
+            // Both ends are created on the host and then moved, since older
+            // iproute2 cannot place a new veth peer in a namespace
+            let ends = [format!("rrl{}a", index), format!("rrl{}b", index)];
+            run(&[
+                "link", "add", &ends[0], "type", "veth", "peer", "name", &ends[1],
+            ]);
+            for ((ns, address), end) in [(&ns_a, first), (&ns_b, second)].into_iter().zip(&ends) {
+                let address = format!("{}/31", address);
+                run(&["link", "set", end, "netns", ns]);
+                run(&["-n", ns, "link", "set", end, "name", &name]);
+                run(&["-n", ns, "addr", "add", &address, "dev", &name]);
+                run(&["-n", ns, "link", "set", &name, "up"]);
+            }
+        }
+        commands
+    }
+
+    /// Configuration of each router, in topology order
+    pub fn router_configs(&self) -> Vec<(String, RouterConfig)> {
+        let directory = self.directory();
+        self.routers
+            .iter()
+            .enumerate()
+            .map(|(index, router)| {
+                let mut config = RouterConfig::default();
+                let template = config.interfaces[0].clone();
+                let interface = |name: String, address: String| InterfaceConfig {
+                    name,
+                    address,
+                    ..template.clone()
+                };
+
+                let mut interfaces = Vec::new();
+                for (link, (a, b)) in self.links.iter().enumerate() {
+                    let (first, second) = self.link_addresses(link);
+                    let address = if *a == router.name {
+                        first
+                    } else if *b == router.name {
+                        second
+                    } else {
+                        continue;
+                    };
+                    interfaces.push(InterfaceConfig {
+                        point_to_point: true,
+                        ..interface(format!("lab{}", link), format!("{}/31", address))
+                    });
+                }
+                if let Some(first) = interfaces.first().and_then(InterfaceConfig::ipv4_net) {
+                    config.router_id = first.addr().to_string();
+                }
+                for (stub, network) in router.networks.iter().enumerate() {
+                    interfaces.push(InterfaceConfig {
+                        passive: true,
+                        ..interface(format!("stub{}", stub), Self::stub_address(network))
+                    });
+                }
+                config.interfaces = interfaces;
+
+                let home = directory.join(&router.name);
+                config.rip.port = self.rip_port;
+                config.web.port = self.web_port + index as u16;
+                if self.namespaces {
+                    config.web.bind_address = Self::mgmt_addresses(index).1.to_string();
+                }
+                config.logging.file_path = None;
+                config.backup.backup_directory = home.join("backups").display().to_string();
+                config.storage.path = home.display().to_string();
+                (router.name.clone(), config)
+            })
+            .collect()
+    }
+}
+
+/// A router process of a running lab
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LabProcess {
+    pub name: String,
+    pub pid: u32,
+    pub web_url: String,
+    pub log: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct LabState {
+    processes: Vec<LabProcess>,
+    /// Namespaces to delete when the lab goes down
+    #[serde(default)]
+    namespaces: Vec<String>,
+}
+
+/// The processes of a lab started by [`Lab::up`]
+#[derive(Debug)]
+pub struct Lab {
+    directory: PathBuf,
+    processes: Vec<LabProcess>,
+    children: Vec<Child>,
+    namespaces: Vec<String>,
+}
+
+impl Lab {
+    /// Write the configurations and start a router process for each, using
+    /// the `rust-route` binary at `binary`
+    pub fn up(topology: &Topology, binary: &Path) -> RustRouteResult<Self> {
+        let directory = topology.directory();
+        if directory.join(STATE_FILE).exists() {
+            return Err(RustRouteError::ConfigError(format!(
+                "A lab is already running in {}; run `rust-route lab down` first",
+                directory.display()
+            )));
+        }
+
+        let io = |err: std::io::Error| RustRouteError::ConfigError(err.to_string());
+        let mut lab = Lab {
+            directory: directory.clone(),
+            processes: Vec::new(),
+            children: Vec::new(),
+            namespaces: Vec::new(),
+        };
+        if topology.namespaces {
+            if !is_root() {
+                return Err(RustRouteError::ConfigError(
+                    "A lab with namespaces needs root; run with sudo or set namespaces: false"
+                        .to_string(),
+                ));
+            }
+            lab.namespaces = topology
+                .routers
+                .iter()
+                .map(|router| topology.namespace(&router.name))
+                .collect();
+            for args in topology.netns_setup() {
+                if let Err(err) = ip(&args) {
+                    lab.down();
+                    return Err(err);
+                }
+            }
+        }
+        for (name, config) in topology.router_configs() {
+            let home = directory.join(&name);
+            fs::create_dir_all(&home).map_err(io)?;
+            let config_path = home.join("rust-route.json");
+            let content = serde_json::to_string_pretty(&config)
+                .map_err(|err| RustRouteError::ConfigError(err.to_string()))?;
+            fs::write(&config_path, content).map_err(io)?;
+
+            let log = home.join("router.log");
+            let output = File::create(&log).map_err(io)?;
+            let mut command = if topology.namespaces {
+                let mut command = Command::new("ip");
+                command
+                    .args(["netns", "exec", &topology.namespace(&name)])
+                    .arg(binary);
+                command
+            } else {
+                Command::new(binary)
+            };
+            let child = command
+                .arg("start")
+                .arg("--config")
+                .arg(&config_path)
+                .stdin(Stdio::null())
+                .stdout(output.try_clone().map_err(io)?)
+                .stderr(output)
+                .spawn();
+            let child = match child {
+                Ok(child) => child,
+                Err(err) => {
+                    lab.down();
+                    return Err(RustRouteError::ConfigError(format!(
+                        "Cannot start {} for router {}: {}",
+                        binary.display(),
+                        name,
+                        err
+                    )));
+                }
+            };
+            lab.processes.push(LabProcess {
+                name,
+                pid: child.id(),
+                web_url: format!("http://{}:{}", config.web.bind_address, config.web.port),
+                log,
+            });
+            lab.children.push(child);
+        }
+
+        let state = serde_json::to_string_pretty(&LabState {
+            processes: lab.processes.clone(),
+            namespaces: lab.namespaces.clone(),
+        })
+        .map_err(|err| RustRouteError::ConfigError(err.to_string()))?;
+        fs::write(directory.join(STATE_FILE), state).map_err(io)?;
+        Ok(lab)
+    }
+
+    pub fn processes(&self) -> &[LabProcess] {
+        &self.processes
+    }
+
+    /// Names of routers whose process has exited
+    pub fn exited(&mut self) -> Vec<String> {
+        self.children
+            .iter_mut()
+            .zip(&self.processes)
+            .filter_map(|(child, process)| {
+                (!matches!(child.try_wait(), Ok(None))).then(|| process.name.clone())
+            })
+            .collect()
+    }
+
+    /// Stop every router, remove its namespace and forget the lab's state
+    pub fn down(mut self) {
+        for child in &mut self.children {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        delete_namespaces(&self.namespaces);
+        let _ = fs::remove_file(self.directory.join(STATE_FILE));
+    }
+}
+
+/// Stop the processes recorded for the lab of `topology`, returning how many
+/// were signalled
+pub fn down(topology: &Topology) -> RustRouteResult<usize> {
+    let path = topology.directory().join(STATE_FILE);
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(err) => return Err(RustRouteError::ConfigError(err.to_string())),
+    };
+    let state: LabState = serde_json::from_str(&content)
+        .map_err(|err| RustRouteError::ConfigError(format!("{}: {}", path.display(), err)))?;
+
+    let mut stopped = 0;
+    for process in &state.processes {
+        let killed = Command::new("kill")
+            .arg(process.pid.to_string())
+            .stderr(Stdio::null())
+            .status();
+        if killed.is_ok_and(|status| status.success()) {
+            stopped += 1;
+        }
+    }
+    delete_namespaces(&state.namespaces);
+    let _ = fs::remove_file(&path);
+    Ok(stopped)
+}
+
+/// Run `ip` with `args`, failing with its error output
+fn ip(args: &[String]) -> RustRouteResult<()> {
+    let output = Command::new("ip")
+        .args(args)
+        .output()
+        .map_err(|err| RustRouteError::ConfigError(format!("Cannot run ip: {}", err)))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    Err(RustRouteError::ConfigError(format!(
+        "ip {} failed: {}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr).trim()
+    )))
+}
+
+/// Deleting a namespace also removes the veth pairs that end in it
+fn delete_namespaces(namespaces: &[String]) {
+    for ns in namespaces {
+        let _ = ip(&["netns".to_string(), "del".to_string(), ns.clone()]);
+    }
+}
+
+/// Whether the process runs as root, from `/proc/self/status`
+fn is_root() -> bool {
+    std::fs::read_to_string("/proc/self/status")
+        .ok()
+        .and_then(|status| {
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix("Uid:"))
+                .and_then(|uids| uids.split_whitespace().nth(1).map(|euid| euid == "0"))
+        })
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRIANGLE: &str = "
+name: triangle
+routers:
+  - name: r1
+    networks: [10.1.0.0/24]
+  - name: r2
+  - name: r3
+links:
+  - [r1, r2]
+  - [r2, r3]
+  - [r3, r1]
+";
+
+    #[test]
+    fn topology_becomes_one_config_per_router() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("triangle.yaml");
+        fs::write(&path, TRIANGLE).unwrap();
+        let topology = Topology::load(&path).unwrap();
+        assert_eq!(topology.directory(), dir.path().join("lab-triangle"));
+
+        let configs = topology.router_configs();
+        let (name, r1) = &configs[0];
+        assert_eq!(name, "r1");
+        let addresses: Vec<&str> = r1.interfaces.iter().map(|i| i.address.as_str()).collect();
+        assert_eq!(
+            addresses,
+            vec!["127.100.0.0/31", "127.100.0.5/31", "10.1.0.1/24"]
+        );
+        assert!(r1.interfaces[2].passive);
+        assert_eq!(r1.router_id, "127.100.0.0");
+        assert_eq!(configs[2].1.web.port, 18082);
+        assert!(
+            configs
+                .iter()
+                .all(|(_, config)| config.rip.port == 5520
+                    && config.interfaces[0].is_point_to_point())
+        );
+    }
+
+    #[test]
+    fn namespaces_get_veth_pairs_and_management_links() {
+        let mut topology: Topology = serde_yaml::from_str(TRIANGLE).unwrap();
+        topology.namespaces = true;
+        let configs = topology.router_configs();
+        let r2 = &configs[1].1;
+        assert_eq!(r2.interfaces[0].address, "172.31.0.1/31");
+        assert_eq!(r2.web.bind_address, "172.30.0.6");
+
+        let commands: Vec<String> = topology
+            .netns_setup()
+            .iter()
+            .map(|args| args.join(" "))
+            .collect();
+        assert!(commands.contains(&"netns add rr-triangle-r1".to_string()));
+        assert!(commands.contains(&"link set rrl1b netns rr-triangle-r3".to_string()));
+        assert!(commands.contains(&"-n rr-triangle-r3 link set rrl1b name lab1".to_string()));
+        assert!(commands.contains(&"-n rr-triangle-r1 addr add 10.1.0.1/24 dev stub0".to_string()));
+    }
+
+    #[test]
+    fn invalid_topologies_are_rejected() {
+        let mut topology: Topology = serde_yaml::from_str(TRIANGLE).unwrap();
+        topology.links.push(("r1".to_string(), "r9".to_string()));
+        assert!(topology.validate().is_err());
+
+        topology.links.pop();
+        topology.links.push(("r2".to_string(), "r2".to_string()));
+        assert!(topology.validate().is_err());
+
+        topology.links.pop();
+        topology.routers.push(topology.routers[0].clone());
+        assert!(topology.validate().is_err());
+    }
+}
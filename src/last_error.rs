@@ -0,0 +1,120 @@
+//! The most recent error of each subsystem.
+//!
+//! Subsystems record failures here as well as logging them, and
+//! `GET /api/status/errors` lists the last one of each with when it first
+//! and last happened and how often, so a stale dashboard can be explained
+//! without access to the logs. Interfaces record under
+//! `interface:<name>`; the others under a fixed name such as `config-watcher`,
+//! `auth`, `discovery` or `web`.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::sync::{Mutex, OnceLock};
+
+pub const CONFIG_WATCHER: &str = "config-watcher";
+pub const AUTH: &str = "auth";
+pub const DISCOVERY: &str = "discovery";
+pub const WEB: &str = "web";
+
+static REGISTRY: OnceLock<ErrorRegistry> = OnceLock::new();
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubsystemError {
+    pub subsystem: String,
+    /// The latest error
+    pub message: String,
+    /// Errors recorded since startup
+    pub count: u64,
+    pub first_at: DateTime<Utc>,
+    pub last_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default)]
+struct ErrorRegistry {
+    errors: Mutex<BTreeMap<String, SubsystemError>>,
+}
+
+impl ErrorRegistry {
+    fn record(&self, subsystem: &str, message: String, at: DateTime<Utc>) {
+        let mut errors = self
+            .errors
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let entry = errors
+            .entry(subsystem.to_string())
+            .or_insert_with(|| SubsystemError {
+                subsystem: subsystem.to_string(),
+                message: String::new(),
+                count: 0,
+                first_at: at,
+                last_at: at,
+            });
+        entry.message = message;
+        entry.count += 1;
+        entry.last_at = at;
+    }
+
+    fn snapshot(&self) -> Vec<SubsystemError> {
+        let mut errors: Vec<SubsystemError> = self
+            .errors
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .values()
+            .cloned()
+            .collect();
+        errors.sort_by_key(|error| std::cmp::Reverse(error.last_at));
+        errors
+    }
+}
+
+fn registry() -> &'static ErrorRegistry {
+    REGISTRY.get_or_init(ErrorRegistry::default)
+}
+
+/// Subsystem name of an interface
+pub fn interface(name: &str) -> String {
+    format!("interface:{}", name)
+}
+
+/// Record `message` as the latest error of `subsystem`
+pub fn record(subsystem: &str, message: impl Display) {
+    registry().record(subsystem, message.to_string(), Utc::now());
+}
+
+/// Last error of every subsystem that had one, most recent first
+pub fn snapshot() -> Vec<SubsystemError> {
+    registry().snapshot()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn keeps_the_latest_error_and_counts_all() {
+        let registry = ErrorRegistry::default();
+        let start = Utc::now();
+        registry.record(AUTH, "Invalid token".to_string(), start);
+        registry.record(
+            &interface("eth0"),
+            "Failed to send packet: Network is unreachable".to_string(),
+            start + Duration::seconds(1),
+        );
+        registry.record(
+            AUTH,
+            "Token expired".to_string(),
+            start + Duration::seconds(2),
+        );
+
+        let errors = registry.snapshot();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].subsystem, "auth");
+        assert_eq!(errors[0].message, "Token expired");
+        assert_eq!(errors[0].count, 2);
+        assert_eq!(errors[0].first_at, start);
+        assert_eq!(errors[1].subsystem, "interface:eth0");
+    }
+}
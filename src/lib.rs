@@ -3,20 +3,73 @@
 //! This library implements a simple and practical RIP routing protocol
 //! focused on core functionality and ease of use.
 
+pub mod activity;
+pub mod assets;
 pub mod auth;
 pub mod cli;
+pub mod client;
+pub mod clock;
+pub mod compare;
 pub mod config_manager;
+pub mod consistency;
+pub mod decisions;
+pub mod diagnostics;
+pub mod dns;
+pub mod dump;
 pub mod events;
+pub mod export;
+pub mod fleet;
+pub mod gnmi;
+pub mod grafana;
+pub mod hooks;
+pub mod interop;
 pub mod ipv6;
+pub mod kernel;
+pub mod lab;
+pub mod last_error;
+pub mod link_cost;
+pub mod listen;
+pub mod locale;
+pub mod logging;
+pub mod loop_detector;
 pub mod metrics;
+pub mod metrics_history;
+pub mod mqtt;
 pub mod network;
 pub mod network_discovery;
+pub mod packet_auth;
+pub mod plugins;
+pub mod policy;
+pub mod prefix_trie;
+pub mod preflight;
+pub mod prelude;
+pub mod probe;
 pub mod protocol;
+pub mod proxy;
+pub mod rate_limit;
+pub mod redistribute;
+pub mod rip_auth;
 pub mod router;
 pub mod routing_table;
+pub mod runtime;
+pub mod selftest;
+pub mod session;
+pub mod simulation;
+pub mod snmp;
+pub mod storage;
+pub mod streaming;
+pub mod sysstats;
 pub mod testing;
+pub mod timers;
+pub mod tls;
+pub mod topology;
+pub mod tunnel;
+pub mod watchdog;
 pub mod web;
 
+pub use config_manager::{InterfaceConfig, RouterConfig};
+pub use router::Router;
+
 use std::error::Error;
 use std::fmt;
 
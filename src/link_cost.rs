@@ -0,0 +1,146 @@
+//! Interface costs, optionally derived from link speed.
+//!
+//! The cost of an interface is added to the metric of every route received
+//! on it, as RIP otherwise adds 1. It is the interface's `cost`, unless
+//! `auto_cost` is set: then it follows the link speed the OS reports, the
+//! figure `ethtool` shows, one more for every tenfold step below
+//! `rip.reference_bandwidth_mbps`. Speeds are read every `POLL_INTERVAL`; a
+//! new cost reprices the routes learned on the interface and advertises them
+//! in a triggered update.
+
+use log::info;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock as StdRwLock};
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+use crate::config_manager::{InterfaceConfig, RouterConfig};
+use crate::events::{ActivityLevel, EventBus, RouteEvent, WebEvent};
+use crate::network;
+use crate::router::Router;
+use crate::watchdog::Heartbeat;
+
+/// How often link speeds are read
+pub const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Link speed at and above which an interface costs 1
+pub const DEFAULT_REFERENCE_BANDWIDTH_MBPS: u64 = 10_000;
+
+/// Cost of a link of `speed_mbps`: 1 at `reference_mbps` and above, one more
+/// for every tenfold step below it
+pub fn derive(speed_mbps: u64, reference_mbps: u64) -> u32 {
+    let mut cost = 1;
+    let mut speed = speed_mbps.max(1);
+    while speed.saturating_mul(10) <= reference_mbps {
+        speed *= 10;
+        cost += 1;
+    }
+    cost
+}
+
+/// Cost of `interface`, derived from `speed_mbps` when `auto_cost` is set
+/// and the speed is known
+pub fn cost_of(interface: &InterfaceConfig, speed_mbps: Option<u64>, reference_mbps: u64) -> u32 {
+    match speed_mbps {
+        Some(speed) if interface.auto_cost => derive(speed, reference_mbps),
+        _ => interface.cost.max(1),
+    }
+}
+
+/// Cost of every enabled interface of `config`, reading link speeds for
+/// those with `auto_cost`
+pub fn costs(config: &RouterConfig) -> Vec<(String, u32)> {
+    config
+        .interfaces
+        .iter()
+        .filter(|interface| interface.enabled)
+        .map(|interface| {
+            let speed = interface
+                .auto_cost
+                .then(|| network::utils::os_link_speed(&interface.name))
+                .flatten();
+            let cost = cost_of(interface, speed, config.rip.reference_bandwidth_mbps);
+            (interface.name.clone(), cost)
+        })
+        .collect()
+}
+
+/// Cost of each interface, shared by the router and its receive tasks
+#[derive(Debug, Clone, Default)]
+pub struct InterfaceCosts {
+    costs: Arc<StdRwLock<HashMap<String, u32>>>,
+}
+
+impl InterfaceCosts {
+    /// Cost of `interface`; 1 for tunnels and interfaces without one
+    pub fn get(&self, interface: &str) -> u32 {
+        self.costs
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(interface)
+            .copied()
+            .unwrap_or(1)
+    }
+
+    /// Set the cost of `interface`, returning the previous one
+    pub fn set(&self, interface: &str, cost: u32) -> u32 {
+        self.costs
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .insert(interface.to_string(), cost)
+            .unwrap_or(1)
+    }
+}
+
+/// Follow configured costs and link speeds every `POLL_INTERVAL`
+pub async fn run(router: Arc<RwLock<Router>>, events: EventBus, heartbeat: Heartbeat) {
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        heartbeat.beat();
+
+        let router = router.read().await;
+        let infinity = router.rip_config().infinity_metric;
+        for (interface, old, new, routes) in router.update_interface_costs().await {
+            info!("Cost of {} changed from {} to {}", interface, old, new);
+            events.publish_activity(
+                ActivityLevel::Info,
+                format!("Cost of {} changed from {} to {}", interface, old, new),
+            );
+            for route in routes {
+                let event = RouteEvent::from_route(&route);
+                events.publish(if route.metric < infinity {
+                    WebEvent::Route(event)
+                } else {
+                    WebEvent::RouteWithdrawn(event)
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn costs_rise_by_one_per_tenfold_drop_in_speed() {
+        let reference = DEFAULT_REFERENCE_BANDWIDTH_MBPS;
+        assert_eq!(derive(100_000, reference), 1);
+        assert_eq!(derive(10_000, reference), 1);
+        assert_eq!(derive(2_500, reference), 1);
+        assert_eq!(derive(1_000, reference), 2);
+        assert_eq!(derive(100, reference), 3);
+        assert_eq!(derive(10, reference), 4);
+        assert_eq!(derive(0, reference), 5);
+
+        let mut interface = RouterConfig::default().interfaces.remove(0);
+        interface.cost = 3;
+        assert_eq!(cost_of(&interface, Some(100), reference), 3);
+        interface.auto_cost = true;
+        assert_eq!(cost_of(&interface, Some(100), reference), 3);
+        assert_eq!(cost_of(&interface, Some(10_000), reference), 1);
+        // Without a reported speed the configured cost applies
+        assert_eq!(cost_of(&interface, None, reference), 3);
+    }
+}
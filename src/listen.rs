@@ -0,0 +1,103 @@
+//! Listening sockets of the management plane.
+//!
+//! The web server, gNMI and the SNMP agent take a `bind_address` that is an
+//! IPv4 address, or an IPv6 address with or without brackets. Binding to the
+//! IPv6 unspecified address `::` accepts IPv4 clients as well, whatever the
+//! system's `bindv6only` default; any other IPv6 address binds IPv6 only.
+
+use socket2::{Domain, Protocol, Socket, Type};
+use std::net::{IpAddr, SocketAddr};
+use tokio::net::{TcpListener, UdpSocket};
+
+/// Connections waiting to be accepted before the kernel refuses more
+const BACKLOG: i32 = 1024;
+
+/// The socket address for `bind_address` and `port`, accepting `::1` and
+/// `[::1]` alike
+pub fn socket_addr(bind_address: &str, port: u16) -> Result<SocketAddr, String> {
+    let address = bind_address.trim();
+    let address = address
+        .strip_prefix('[')
+        .and_then(|inner| inner.strip_suffix(']'))
+        .unwrap_or(address);
+    address
+        .parse::<IpAddr>()
+        .map(|ip| SocketAddr::new(ip, port))
+        .map_err(|_| format!("'{}' is not an IPv4 or IPv6 address", bind_address))
+}
+
+/// Whether a listener bound to `addr` also accepts IPv4 clients
+pub fn is_dual_stack(addr: &SocketAddr) -> bool {
+    matches!(addr, SocketAddr::V6(v6) if v6.ip().is_unspecified())
+}
+
+fn socket(addr: SocketAddr, kind: Type, protocol: Protocol) -> std::io::Result<Socket> {
+    let socket = Socket::new(Domain::for_address(addr), kind, Some(protocol))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(!is_dual_stack(&addr))?;
+    }
+    socket.set_nonblocking(true)?;
+    Ok(socket)
+}
+
+/// A TCP listener on `addr`
+pub fn tcp(addr: SocketAddr) -> std::io::Result<TcpListener> {
+    let socket = socket(addr, Type::STREAM, Protocol::TCP)?;
+    // Restarting must not wait for connections of the old process in TIME_WAIT
+    socket.set_reuse_address(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(BACKLOG)?;
+    TcpListener::from_std(socket.into())
+}
+
+/// A UDP socket on `addr`
+pub fn udp(addr: SocketAddr) -> std::io::Result<UdpSocket> {
+    let socket = socket(addr, Type::DGRAM, Protocol::UDP)?;
+    socket.bind(&addr.into())?;
+    UdpSocket::from_std(socket.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    #[test]
+    fn bind_addresses_of_either_family_are_accepted() {
+        assert_eq!(
+            socket_addr("0.0.0.0", 8080).unwrap(),
+            SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 8080)
+        );
+        assert_eq!(
+            socket_addr("::", 8080).unwrap(),
+            SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 8080)
+        );
+        let loopback = socket_addr("[::1]", 8080).unwrap();
+        assert_eq!(loopback, socket_addr("::1", 8080).unwrap());
+        assert_eq!(loopback.to_string(), "[::1]:8080");
+        assert!(socket_addr("", 8080).is_err());
+        assert!(socket_addr("localhost", 8080).is_err());
+        assert!(socket_addr("[127.0.0.1", 8080).is_err());
+
+        assert!(is_dual_stack(&socket_addr("[::]", 0).unwrap()));
+        assert!(!is_dual_stack(&loopback));
+        assert!(!is_dual_stack(&socket_addr("0.0.0.0", 0).unwrap()));
+    }
+
+    #[tokio::test]
+    async fn a_listener_on_the_unspecified_ipv6_address_accepts_ipv4_clients() {
+        let listener = match tcp(socket_addr("::", 0).unwrap()) {
+            Ok(listener) => listener,
+            // Hosts without IPv6 cannot run this test
+            Err(_) => return,
+        };
+        let port = listener.local_addr().unwrap().port();
+
+        let client =
+            tokio::net::TcpStream::connect(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), port));
+        let (connected, accepted) = tokio::join!(client, listener.accept());
+        connected.unwrap();
+        let (_, peer) = accepted.unwrap();
+        assert_eq!(peer.ip().to_canonical(), IpAddr::from(Ipv4Addr::LOCALHOST));
+    }
+}
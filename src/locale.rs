@@ -0,0 +1,113 @@
+//! Language of human-readable output.
+//!
+//! Output defaults to English so logs and CLI tables stay parsable; the
+//! original Chinese strings remain available with `"locale": "zh"`. Values
+//! meant for machines, such as `uptime_seconds`, never depend on the locale.
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+static CURRENT: AtomicU8 = AtomicU8::new(Locale::En as u8);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    En = 0,
+    Zh = 1,
+}
+
+/// Fixed strings that have a translation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Text {
+    StatisticsTitle,
+    Uptime,
+    PacketsSent,
+    PacketsReceived,
+    Routes,
+    Neighbors,
+    Memory,
+}
+
+impl Locale {
+    /// Locale used where no configuration is at hand, such as the CLI tables
+    pub fn current() -> Self {
+        match CURRENT.load(Ordering::Relaxed) {
+            1 => Locale::Zh,
+            _ => Locale::En,
+        }
+    }
+
+    pub fn set_current(locale: Self) {
+        CURRENT.store(locale as u8, Ordering::Relaxed);
+    }
+
+    /// `3725` becomes `1h 2m 5s`, or `1时2分5秒` in Chinese
+    pub fn format_duration(self, seconds: u64) -> String {
+        let hours = seconds / 3600;
+        let minutes = (seconds % 3600) / 60;
+        let secs = seconds % 60;
+        match self {
+            Locale::En => format!("{}h {}m {}s", hours, minutes, secs),
+            Locale::Zh => format!("{}时{}分{}秒", hours, minutes, secs),
+        }
+    }
+
+    pub fn text(self, text: Text) -> &'static str {
+        match (self, text) {
+            (Locale::En, Text::StatisticsTitle) => "Router statistics",
+            (Locale::En, Text::Uptime) => "Uptime",
+            (Locale::En, Text::PacketsSent) => "Packets sent",
+            (Locale::En, Text::PacketsReceived) => "Packets received",
+            (Locale::En, Text::Routes) => "Routes",
+            (Locale::En, Text::Neighbors) => "Neighbors",
+            (Locale::En, Text::Memory) => "Memory",
+            (Locale::Zh, Text::StatisticsTitle) => "路由器统计信息",
+            (Locale::Zh, Text::Uptime) => "运行时间",
+            (Locale::Zh, Text::PacketsSent) => "发送包数",
+            (Locale::Zh, Text::PacketsReceived) => "接收包数",
+            (Locale::Zh, Text::Routes) => "路由表条目",
+            (Locale::Zh, Text::Neighbors) => "邻居数量",
+            (Locale::Zh, Text::Memory) => "内存使用",
+        }
+    }
+}
+
+/// `1536` becomes `1.5 KiB`; binary units, independent of the locale
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn human_formats_default_to_english() {
+        assert_eq!(Locale::default().format_duration(3725), "1h 2m 5s");
+        assert_eq!(Locale::Zh.format_duration(3725), "1时2分5秒");
+        assert_eq!(Locale::En.format_duration(0), "0h 0m 0s");
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(25 * 1024 * 1024), "25.0 MiB");
+    }
+
+    #[test]
+    fn locale_round_trips_through_config() {
+        let locale: Locale = serde_json::from_str("\"zh\"").unwrap();
+        assert_eq!(locale, Locale::Zh);
+        assert_eq!(Locale::Zh.text(Text::Routes), "路由表条目");
+        assert_eq!(serde_json::to_string(&Locale::En).unwrap(), "\"en\"");
+    }
+}
@@ -0,0 +1,224 @@
+//! Log output whose filter can change while the router runs.
+//!
+//! Records are formatted by `env_logger` but checked against a filter held
+//! here, so `PUT /api/logging/level` and a changed `logging.level` take
+//! effect at once, without a restart that would lose the routing state.
+//! Filters use the `RUST_LOG` syntax: a level such as `debug`, or
+//! directives such as `info,rust_route::router=debug`. When `RUST_LOG` is
+//! set it wins over `logging.level` until that setting is changed.
+
+use env_logger::filter::{Builder as FilterBuilder, Filter};
+use log::{LevelFilter, Log, Metadata, Record};
+use serde::Serialize;
+use std::sync::{OnceLock, RwLock};
+
+use crate::{RustRouteError, RustRouteResult};
+
+const DEFAULT_FILTER: &str = "info";
+
+static LOGGER: OnceLock<ReloadableLogger> = OnceLock::new();
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LogLevel {
+    /// Filter in effect
+    pub level: String,
+    /// Last `logging.level` seen in the configuration
+    pub configured: Option<String>,
+}
+
+/// Parse a filter, rejecting directives whose level is unknown rather than
+/// reading them as module names the way `RUST_LOG` does
+pub fn parse_filter(spec: &str) -> RustRouteResult<Filter> {
+    let spec = spec.trim();
+    if spec.is_empty() {
+        return Err(RustRouteError::InvalidInput(
+            "Log level cannot be empty".to_string(),
+        ));
+    }
+    for directive in spec.split('/').next().unwrap_or(spec).split(',') {
+        let level = directive.rsplit('=').next().unwrap_or(directive).trim();
+        if level.parse::<LevelFilter>().is_err() {
+            return Err(RustRouteError::InvalidInput(format!(
+                "Invalid log level: {}",
+                directive.trim()
+            )));
+        }
+    }
+    Ok(FilterBuilder::new().parse(spec).build())
+}
+
+#[derive(Debug)]
+struct State {
+    spec: String,
+    filter: Filter,
+    configured: Option<String>,
+    /// `RUST_LOG` chose the filter and no configured level was applied yet
+    from_env: bool,
+}
+
+struct ReloadableLogger {
+    output: env_logger::Logger,
+    state: RwLock<State>,
+}
+
+impl ReloadableLogger {
+    fn new(spec: Option<String>) -> Self {
+        let from_env = spec.is_some();
+        let (spec, filter) = spec
+            .and_then(|spec| Some((spec.clone(), parse_filter(&spec).ok()?)))
+            .unwrap_or_else(|| {
+                let filter = FilterBuilder::new().parse(DEFAULT_FILTER).build();
+                (DEFAULT_FILTER.to_string(), filter)
+            });
+        let output = env_logger::Builder::new()
+            .filter_level(LevelFilter::Trace)
+            .format_timestamp_secs()
+            .build();
+        Self {
+            output,
+            state: RwLock::new(State {
+                spec,
+                filter,
+                configured: None,
+                from_env,
+            }),
+        }
+    }
+
+    fn state(&self) -> std::sync::RwLockReadGuard<'_, State> {
+        self.state
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn state_mut(&self) -> std::sync::RwLockWriteGuard<'_, State> {
+        self.state
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn current(&self) -> LogLevel {
+        let state = self.state();
+        LogLevel {
+            level: state.spec.clone(),
+            configured: state.configured.clone(),
+        }
+    }
+
+    fn set(&self, spec: &str) -> RustRouteResult<LogLevel> {
+        let filter = parse_filter(spec)?;
+        {
+            let mut state = self.state_mut();
+            // The global maximum lets disabled log calls skip formatting
+            log::set_max_level(filter.filter());
+            state.filter = filter;
+            state.spec = spec.trim().to_string();
+        }
+        Ok(self.current())
+    }
+
+    fn apply_config(&self, level: &str) -> RustRouteResult<bool> {
+        let keep_env = {
+            let mut state = self.state_mut();
+            if state.configured.as_deref() == Some(level) {
+                return Ok(false);
+            }
+            let keep_env = state.from_env && state.configured.is_none();
+            state.configured = Some(level.to_string());
+            keep_env
+        };
+        if keep_env {
+            return Ok(false);
+        }
+        self.set(level)?;
+        Ok(true)
+    }
+}
+
+impl Log for ReloadableLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.state().filter.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.state().filter.matches(record) {
+            self.output.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.output.flush();
+    }
+}
+
+fn logger() -> &'static ReloadableLogger {
+    LOGGER.get_or_init(|| ReloadableLogger::new(std::env::var("RUST_LOG").ok()))
+}
+
+/// Install the logger, filtering by `RUST_LOG` or at `info`
+pub fn init() {
+    let logger = logger();
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(logger.state().filter.filter());
+    }
+}
+
+pub fn current() -> LogLevel {
+    logger().current()
+}
+
+/// Switch to `spec` until the next change
+pub fn set_level(spec: &str) -> RustRouteResult<LogLevel> {
+    logger().set(spec)
+}
+
+/// Apply `logging.level` from a loaded or reloaded configuration. Only a
+/// changed setting takes effect, so reloads do not undo a level set through
+/// the API; returns whether the filter changed.
+pub fn apply_config(level: &str) -> RustRouteResult<bool> {
+    logger().apply_config(level)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Level;
+
+    fn enabled(logger: &ReloadableLogger, level: Level, target: &str) -> bool {
+        logger.enabled(&Metadata::builder().level(level).target(target).build())
+    }
+
+    #[test]
+    fn filters_switch_at_runtime() {
+        let logger = ReloadableLogger::new(None);
+        assert!(!enabled(&logger, Level::Debug, "rust_route::router"));
+
+        logger.set("warn,rust_route::router=debug").unwrap();
+        assert!(enabled(&logger, Level::Debug, "rust_route::router"));
+        assert!(!enabled(&logger, Level::Info, "rust_route::web"));
+        assert!(logger.set("degub").is_err());
+        assert!(logger.set("rust_route=loud").is_err());
+        assert_eq!(logger.current().level, "warn,rust_route::router=debug");
+    }
+
+    #[test]
+    fn only_changed_configuration_applies() {
+        let logger = ReloadableLogger::new(Some("trace".to_string()));
+        // RUST_LOG wins over the level configured at startup
+        assert!(!logger.apply_config("info").unwrap());
+        assert!(enabled(&logger, Level::Trace, "rust_route"));
+
+        logger.set("error").unwrap();
+        assert!(!logger.apply_config("info").unwrap());
+        assert_eq!(logger.current().level, "error");
+
+        assert!(logger.apply_config("debug").unwrap());
+        assert_eq!(
+            logger.current(),
+            LogLevel {
+                level: "debug".to_string(),
+                configured: Some("debug".to_string()),
+            }
+        );
+    }
+}
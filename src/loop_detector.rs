@@ -0,0 +1,145 @@
+//! Counting-to-infinity detection.
+//!
+//! When two routers keep relearning a lost prefix from each other, the
+//! metric they advertise for it rises by a hop or two with every exchange
+//! until it reaches infinity. The detector tracks the metric each neighbor
+//! advertises per prefix and reports a prefix once one neighbor has raised
+//! it on [`CLIMB_THRESHOLD`] consecutive updates.
+
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::sync::{Arc, Mutex};
+
+/// Consecutive rising updates before a prefix is reported
+pub const CLIMB_THRESHOLD: u32 = 3;
+
+#[derive(Debug, Clone, Copy)]
+struct Climb {
+    metric: u32,
+    rises: u32,
+    reported: bool,
+}
+
+type Prefix = (Ipv4Addr, Ipv4Addr);
+
+/// A prefix caught counting to infinity
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CountToInfinity {
+    pub destination: Ipv4Addr,
+    pub subnet_mask: Ipv4Addr,
+    /// Neighbors whose advertised metric is currently climbing
+    pub neighbors: Vec<Ipv4Addr>,
+    pub metric: u32,
+    pub rises: u32,
+}
+
+/// Per-prefix, per-neighbor metric trends, shared by the receive tasks
+#[derive(Debug, Clone, Default)]
+pub struct LoopDetector {
+    prefixes: Arc<Mutex<HashMap<Prefix, HashMap<Ipv4Addr, Climb>>>>,
+}
+
+impl LoopDetector {
+    /// Record the metric `neighbor` advertised for a prefix, after adding the
+    /// link cost. Returns a report the first time the climb crosses the
+    /// threshold; the prefix is reported again only after the climb stops.
+    pub fn observe(
+        &self,
+        destination: Ipv4Addr,
+        subnet_mask: Ipv4Addr,
+        neighbor: Ipv4Addr,
+        metric: u32,
+        infinity: u32,
+    ) -> Option<CountToInfinity> {
+        let mut prefixes = self
+            .prefixes
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let key = (destination, subnet_mask);
+
+        // Reaching infinity ends the episode; the regular timers take over
+        if metric >= infinity {
+            if let Some(neighbors) = prefixes.get_mut(&key) {
+                neighbors.remove(&neighbor);
+                if neighbors.is_empty() {
+                    prefixes.remove(&key);
+                }
+            }
+            return None;
+        }
+
+        let neighbors = prefixes.entry(key).or_default();
+        let climb = neighbors.entry(neighbor).or_insert(Climb {
+            metric,
+            rises: 0,
+            reported: false,
+        });
+        if metric > climb.metric {
+            climb.rises += 1;
+        } else {
+            climb.rises = 0;
+            climb.reported = false;
+        }
+        climb.metric = metric;
+
+        if climb.rises < CLIMB_THRESHOLD || climb.reported {
+            return None;
+        }
+        climb.reported = true;
+        let rises = climb.rises;
+
+        let mut climbing: Vec<Ipv4Addr> = neighbors
+            .iter()
+            .filter(|(_, climb)| climb.rises > 0)
+            .map(|(address, _)| *address)
+            .collect();
+        climbing.sort();
+        Some(CountToInfinity {
+            destination,
+            subnet_mask,
+            neighbors: climbing,
+            metric,
+            rises,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEST: Ipv4Addr = Ipv4Addr::new(10, 9, 0, 0);
+    const MASK: Ipv4Addr = Ipv4Addr::new(255, 255, 0, 0);
+
+    #[test]
+    fn reports_a_steady_climb_once() {
+        let detector = LoopDetector::default();
+        let b = Ipv4Addr::new(192, 168, 1, 2);
+        let c = Ipv4Addr::new(192, 168, 1, 3);
+
+        assert!(detector.observe(DEST, MASK, c, 4, 16).is_none());
+        assert!(detector.observe(DEST, MASK, c, 6, 16).is_none());
+        for metric in [3, 5, 7] {
+            assert!(detector.observe(DEST, MASK, b, metric, 16).is_none());
+        }
+
+        let report = detector.observe(DEST, MASK, b, 9, 16).unwrap();
+        assert_eq!(report.neighbors, vec![b, c]);
+        assert_eq!(report.metric, 9);
+        assert_eq!(report.rises, CLIMB_THRESHOLD);
+
+        assert!(detector.observe(DEST, MASK, b, 11, 16).is_none());
+        assert!(detector.observe(DEST, MASK, b, 16, 16).is_none());
+    }
+
+    #[test]
+    fn stable_or_improving_metrics_reset_the_climb() {
+        let detector = LoopDetector::default();
+        let neighbor = Ipv4Addr::new(10, 0, 0, 2);
+
+        for metric in [2, 3, 4, 4, 5, 6, 2, 3, 4] {
+            assert!(detector.observe(DEST, MASK, neighbor, metric, 16).is_none());
+        }
+        assert!(detector.observe(DEST, MASK, neighbor, 5, 16).is_some());
+    }
+}
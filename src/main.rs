@@ -4,62 +4,231 @@ use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::{Mutex, RwLock};
 
-use rust_route::protocol::RipCommand;
 use rust_route::{
+    activity::{self, ActivityLog},
     auth::AuthManager,
-    cli::{Cli, ConfigAction},
+    cli::{
+        ClearTarget, Cli, ConfigAction, HistoryRange, InterfaceAction, LabAction, MaintenanceAction,
+    },
+    client::ApiClient,
+    clock, compare,
     config_manager::{ConfigManager, RouterConfig},
-    events::{ActivityLevel, EventBus, MetricsEvent, RouteEvent, WebEvent},
+    consistency,
+    diagnostics::{self, Diagnostics, LOCK_SAMPLE_INTERVAL},
+    dump,
+    events::{ActivityLevel, EventBus, MetricsEvent, WebEvent},
+    export::{self, ExportFormat},
+    fleet::{self, Fleet},
+    gnmi, hooks,
+    interop::{export_config, import_frr, ConfigFormat},
+    kernel,
+    lab::{self, Lab, Topology},
+    last_error, link_cost,
+    locale::Locale,
+    logging,
     metrics::Metrics,
-    protocol::RipPacket,
-    router::{handle_rip_response, Router},
-    routing_table::{Route, RoutingTable},
-    web::WebServer,
+    metrics_history::{self, MetricsHistory},
+    mqtt,
+    plugins::{self, PluginRegistry},
+    preflight::{self, PreflightReport, Severity},
+    probe, redistribute,
+    router::{
+        self, ensure_receive_tasks, ClearOutcome, Router, Supervisor, LINK_POLL_INTERVAL,
+        RECEIVE_HEARTBEAT,
+    },
+    routing_table::RoutingTable,
+    runtime::{self, RuntimeConfig},
+    selftest::{self, CheckStatus},
+    snmp::SnmpAgent,
+    storage::{self, Storage},
+    streaming,
+    timers::{self, AdvertiseSchedule, RipTimers},
+    topology, tunnel,
+    watchdog::Watchdog,
+    web::{AppState, MaintenanceStatus, WebServer},
 };
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
-        .format_timestamp_secs()
-        .init();
+fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    logging::init();
 
     let cli = Cli::parse();
-    print_banner();
+    let runtime = runtime::build(&runtime_config(&cli))?;
+    runtime.block_on(run(cli))
+}
+
+/// Runtime settings of the router `cli` starts, the defaults for the other
+/// commands
+fn runtime_config(cli: &Cli) -> RuntimeConfig {
+    match &cli.command {
+        Some(rust_route::cli::Commands::Start {
+            config,
+            profile,
+            ephemeral: true,
+            settings,
+        }) => {
+            let path = std::path::Path::new(config);
+            ConfigManager::ephemeral_config(
+                path.exists().then_some(path),
+                profile.as_deref(),
+                std::env::vars(),
+                settings,
+            )
+            .map(|config| config.runtime)
+            .unwrap_or_default()
+        }
+        Some(rust_route::cli::Commands::Start { config, .. }) => {
+            RuntimeConfig::peek(std::path::Path::new(config))
+        }
+        None => RuntimeConfig::peek(std::path::Path::new("rust-route.json")),
+        Some(_) => RuntimeConfig::default(),
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if !cli
+        .command
+        .as_ref()
+        .is_some_and(|command| command.writes_to_stdout())
+    {
+        print_banner();
+    }
 
     match cli.command {
-        Some(rust_route::cli::Commands::Start { config }) => {
-            start_router(config).await?;
+        Some(rust_route::cli::Commands::Start {
+            config,
+            profile,
+            ephemeral,
+            settings,
+        }) => {
+            start_router(config, profile, ephemeral, settings).await?;
         }
         Some(rust_route::cli::Commands::Config { action }) => {
             handle_config_command(action).await?;
         }
-        Some(rust_route::cli::Commands::Test { .. }) => {
-            run_tests().await?;
+        Some(rust_route::cli::Commands::Clear { target, url, token }) => {
+            handle_clear_command(target, url, token).await?;
+        }
+        Some(rust_route::cli::Commands::Maintenance { action, url, token }) => {
+            handle_maintenance_command(action, url, token).await?;
+        }
+        Some(rust_route::cli::Commands::Export {
+            format,
+            config,
+            output,
+            url,
+            token,
+        }) => {
+            handle_export_command(format, config, output, url, token).await?;
+        }
+        Some(rust_route::cli::Commands::Compare {
+            peer,
+            peer_token,
+            url,
+            token,
+        }) => {
+            handle_compare_command(peer, peer_token, url, token).await?;
+        }
+        Some(rust_route::cli::Commands::ExportMetrics { range }) => {
+            handle_history_export("/api/metrics/history", "Metrics", range).await?;
+        }
+        Some(rust_route::cli::Commands::ExportEvents { range }) => {
+            handle_history_export("/api/activity/export", "Events", range).await?;
+        }
+        Some(rust_route::cli::Commands::Preflight { config }) => {
+            handle_preflight_command(config).await?;
+        }
+        Some(rust_route::cli::Commands::Interface { action }) => {
+            handle_interface_command(action).await?;
+        }
+        Some(rust_route::cli::Commands::Lab { action }) => {
+            handle_lab_command(action).await?;
+        }
+        Some(rust_route::cli::Commands::Test { test_name }) => {
+            run_tests(test_name).await?;
         }
         Some(rust_route::cli::Commands::Benchmark) => {
             run_benchmarks().await?;
         }
         None => {
-            start_router("rust-route.json".to_string()).await?;
+            start_router("rust-route.json".to_string(), None, false, Vec::new()).await?;
         }
     }
 
     Ok(())
 }
 
-async fn start_router(config_path: String) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    info!("🚀 Starting RustRoute with config: {}", config_path);
-
-    let (manager, mut config_receiver) = ConfigManager::new(&config_path).await?;
+async fn start_router(
+    config_path: String,
+    profile: Option<String>,
+    ephemeral: bool,
+    settings: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (manager, config_receiver) = if ephemeral {
+        let path = std::path::Path::new(&config_path);
+        let base = path.exists().then_some(path);
+        info!(
+            "🚀 Starting RustRoute in ephemeral mode from {}",
+            base.map_or("defaults".to_string(), |path| path.display().to_string())
+        );
+        let config = ConfigManager::ephemeral_config(
+            base,
+            profile.as_deref(),
+            std::env::vars(),
+            &settings,
+        )?;
+        ConfigManager::ephemeral(config)?
+    } else {
+        info!("🚀 Starting RustRoute with config: {}", config_path);
+        let (manager, receiver) = ConfigManager::new(&config_path).await?;
+        if let Some(name) = &profile {
+            info!("🎛️  Activating profile {}", name);
+            manager.activate_profile(name).await?;
+        }
+        (manager, receiver)
+    };
     let manager = Arc::new(manager);
     let initial_config = manager.get_config().await;
+    Locale::set_current(initial_config.locale);
+    if let Err(err) = logging::apply_config(&initial_config.logging.level) {
+        warn!("Keeping the current log level: {}", err);
+    }
     let config_version = manager.get_config_version().await;
 
     let routing_table = Arc::new(RwLock::new(RoutingTable::new()));
     let metrics = Metrics::new();
     metrics.set_config_version(config_version);
 
-    let event_bus = EventBus::new(256);
+    let event_bus = EventBus::with_config(initial_config.event_bus.clone());
+    let watchdog = Watchdog::new(initial_config.watchdog.clone());
+
+    // Durable state shared by accounts, the activity feed and metrics history
+    let storage = storage::open(&initial_config.storage)?;
+
+    // Persist the activity feed; started first so startup activity is kept
+    let activity_log = Arc::new(ActivityLog::open(
+        &initial_config.activity_log,
+        Arc::clone(&storage),
+    ));
+    {
+        let activity_log = Arc::clone(&activity_log);
+        let events = event_bus.clone();
+        watchdog.spawn("activity-log", RECEIVE_HEARTBEAT, move |heartbeat| {
+            activity::run(Arc::clone(&activity_log), events.clone(), heartbeat)
+        });
+    }
+
+    // Report environment problems before interfaces are bound
+    if initial_config.preflight.enabled {
+        let report = preflight::run(&initial_config);
+        log_preflight(&report, &event_bus);
+        if report.has_errors() && initial_config.preflight.strict {
+            return Err(format!(
+                "Preflight found {} errors; fix them or unset preflight.strict",
+                report.error_count()
+            )
+            .into());
+        }
+    }
 
     let auth_state: Arc<Mutex<Option<AuthManager>>> = Arc::new(Mutex::new(None));
     let auth_active = initial_config.auth.enabled && initial_config.web.auth_enabled;
@@ -77,7 +246,7 @@ async fn start_router(config_path: String) -> Result<(), Box<dyn std::error::Err
     {
         let mut guard = auth_state.lock().await;
         if auth_active {
-            match AuthManager::new(initial_config.auth.clone()) {
+            match AuthManager::with_storage(initial_config.auth.clone(), Arc::clone(&storage)) {
                 Ok(manager) => {
                     *guard = Some(manager);
                     event_bus.publish_activity(ActivityLevel::Info, "Authentication enabled");
@@ -92,328 +261,479 @@ async fn start_router(config_path: String) -> Result<(), Box<dyn std::error::Err
         }
     }
 
-    let router = Router::new(
+    let mut router = Router::new(
         initial_config.clone(),
         Arc::clone(&routing_table),
         metrics.clone(),
     )
     .await?;
+    router.set_supervisor(Supervisor {
+        watchdog: watchdog.clone(),
+        events: event_bus.clone(),
+    });
     let router = Arc::new(RwLock::new(router));
 
     let initial_route_count = routing_table.read().await.route_count();
     metrics.update_route_count(initial_route_count);
 
     // Watch for configuration changes
-    let router_for_config = Arc::clone(&router);
-    let routing_table_for_config = Arc::clone(&routing_table);
-    let metrics_for_config = metrics.clone();
-    let manager_for_config = Arc::clone(&manager);
-
-    let event_bus_for_config = event_bus.clone();
-    let auth_state_for_config = Arc::clone(&auth_state);
-    tokio::spawn(async move {
-        while config_receiver.changed().await.is_ok() {
-            let new_config = config_receiver.borrow().clone();
-            match router_for_config
-                .write()
-                .await
-                .apply_config(new_config.clone())
-                .await
-            {
-                Ok(_) => {
-                    let version = manager_for_config.get_config_version().await;
-                    metrics_for_config.set_config_version(version);
-                    let route_count = routing_table_for_config.read().await.route_count();
-                    metrics_for_config.update_route_count(route_count);
-                    info!("✅ Configuration change applied successfully");
-                    event_bus_for_config
-                        .publish_activity(ActivityLevel::Info, "Configuration reloaded from disk");
-
-                    {
-                        let mut auth_guard = auth_state_for_config.lock().await;
-                        let auth_enabled = new_config.auth.enabled;
-                        let web_auth_enabled = new_config.web.auth_enabled;
-                        let auth_active = auth_enabled && web_auth_enabled;
-
-                        if auth_active {
-                            match AuthManager::new(new_config.auth.clone()) {
-                                Ok(manager) => {
-                                    *auth_guard = Some(manager);
-                                    event_bus_for_config.publish_activity(
-                                        ActivityLevel::Info,
-                                        "Authentication settings updated",
-                                    );
-                                }
-                                Err(err) => {
-                                    *auth_guard = None;
-                                    event_bus_for_config.publish_activity(
-                                        ActivityLevel::Error,
-                                        format!(
-                                            "Failed to update authentication settings: {}",
-                                            err
-                                        ),
-                                    );
-                                }
-                            }
-                        } else {
-                            if auth_guard.is_some() {
-                                *auth_guard = None;
-                                event_bus_for_config.publish_activity(
-                                    ActivityLevel::Warn,
-                                    "Authentication disabled via configuration",
-                                );
-                            }
+    {
+        let router = Arc::clone(&router);
+        let metrics = metrics.clone();
+        let manager = Arc::clone(&manager);
+        let events = event_bus.clone();
+        let auth_state = Arc::clone(&auth_state);
+        let storage = Arc::clone(&storage);
+        let supervisor = watchdog.clone();
+        watchdog.spawn("config-watcher", RECEIVE_HEARTBEAT, move |heartbeat| {
+            let mut config_receiver = config_receiver.clone();
+            let router = Arc::clone(&router);
+            let metrics = metrics.clone();
+            let manager = Arc::clone(&manager);
+            let events = events.clone();
+            let auth_state = Arc::clone(&auth_state);
+            let storage = Arc::clone(&storage);
+            let supervisor = supervisor.clone();
+            async move {
+                loop {
+                    heartbeat.beat();
+                    match tokio::time::timeout(RECEIVE_HEARTBEAT, config_receiver.changed()).await {
+                        Err(_) => continue,
+                        Ok(Err(_)) => break,
+                        Ok(Ok(())) => {}
+                    }
 
-                            if auth_enabled != web_auth_enabled {
-                                warn!(
-                                    "Authentication configuration mismatch: auth.enabled={}, web.auth_enabled={}. Authentication will remain disabled.",
-                                    auth_enabled, web_auth_enabled
-                                );
-                                event_bus_for_config.publish_activity(
-                                    ActivityLevel::Warn,
-                                    "Authentication mismatch detected; enable both auth.enabled and web.auth_enabled to require login",
-                                );
-                            }
-                        }
+                    let new_config = config_receiver.borrow().clone();
+                    apply_config_change(
+                        &router,
+                        &metrics,
+                        &manager,
+                        &events,
+                        &auth_state,
+                        &storage,
+                        new_config,
+                    )
+                    .await;
+
+                    // Interfaces enabled at runtime need their own receive loop
+                    let guard = router.read().await;
+                    if guard.rip_enabled() {
+                        ensure_receive_tasks(
+                            &supervisor,
+                            &guard.network_interfaces(),
+                            &guard.rip_context(events.clone()),
+                        );
                     }
                 }
-                Err(err) => {
-                    error!("Failed to apply new configuration: {}", err);
-                    event_bus_for_config.publish_activity(
-                        ActivityLevel::Error,
-                        format!("Failed to apply configuration: {}", err),
-                    );
-                }
             }
-        }
-    });
+        });
+    }
 
     // Periodically recompute route counts and clean neighbors
-    let routing_table_for_metrics = Arc::clone(&routing_table);
-    let metrics_updater = metrics.clone();
-    let router_for_metrics_events = Arc::clone(&router);
-    let events_for_metrics = event_bus.clone();
-    tokio::spawn(async move {
-        let mut interval = tokio::time::interval(Duration::from_secs(30));
-        loop {
-            interval.tick().await;
-            let count = routing_table_for_metrics.read().await.route_count();
-            metrics_updater.update_route_count(count);
-
-            let neighbor_count = {
-                let neighbors_arc = {
-                    let router_guard = router_for_metrics_events.read().await;
-                    router_guard.neighbors()
-                };
-                let count = neighbors_arc.read().await.len();
-                count
-            };
-
-            let snapshot = metrics_updater.snapshot(neighbor_count, count);
-            events_for_metrics.publish(WebEvent::Metrics(MetricsEvent { snapshot }));
-        }
-    });
+    {
+        let routing_table = Arc::clone(&routing_table);
+        let metrics = metrics.clone();
+        let router = Arc::clone(&router);
+        let events = event_bus.clone();
+        let period = Duration::from_secs(30);
+        watchdog.spawn("metrics-publisher", period, move |heartbeat| {
+            let routing_table = Arc::clone(&routing_table);
+            let metrics = metrics.clone();
+            let router = Arc::clone(&router);
+            let events = events.clone();
+            async move {
+                let mut interval = tokio::time::interval(period);
+                loop {
+                    interval.tick().await;
+                    heartbeat.beat();
+                    let count = routing_table.read().await.route_count();
+                    metrics.update_route_count(count);
+
+                    let neighbor_count = {
+                        let neighbors_arc = {
+                            let router_guard = router.read().await;
+                            router_guard.neighbors()
+                        };
+                        let count = neighbors_arc.read().await.len();
+                        count
+                    };
+
+                    let snapshot = metrics.snapshot(neighbor_count, count);
+                    events.publish(WebEvent::Metrics(MetricsEvent { snapshot }));
+                }
+            }
+        });
+    }
+
+    // Keep samples of the counters for export-metrics
+    let metrics_history = Arc::new(MetricsHistory::open(
+        &initial_config.metrics,
+        Arc::clone(&storage),
+    ));
+    {
+        let metrics_history = Arc::clone(&metrics_history);
+        let router = Arc::clone(&router);
+        let metrics = metrics.clone();
+        watchdog.spawn("metrics-history", RECEIVE_HEARTBEAT, move |heartbeat| {
+            metrics_history::run(
+                Arc::clone(&metrics_history),
+                Arc::clone(&router),
+                metrics.clone(),
+                heartbeat,
+            )
+        });
+    }
+
+    // Steps of the system clock
+    {
+        let config = initial_config.clock.clone();
+        let events = event_bus.clone();
+        let interval = Duration::from_secs(config.check_interval.max(1));
+        watchdog.spawn("clock-watch", interval, move |heartbeat| {
+            clock::watch(config.clone(), events.clone(), heartbeat)
+        });
+    }
+
+    // Lock contention sampling for the metrics endpoint
+    let diagnostics = Diagnostics::new();
+    {
+        let router = Arc::clone(&router);
+        let diagnostics = diagnostics.clone();
+        watchdog.spawn("lock-sampler", LOCK_SAMPLE_INTERVAL, move |heartbeat| {
+            diagnostics::sample_locks(Arc::clone(&router), diagnostics.clone(), heartbeat)
+        });
+    }
 
     let rip_settings_snapshot = {
         let guard = router.read().await;
         (
             guard.rip_enabled(),
-            guard.rip_config().clone(),
-            guard.router_uuid(),
+            guard.rip_context(event_bus.clone()),
             guard.network_interfaces(),
-            guard.neighbors(),
         )
     };
 
-    let (rip_enabled, rip_config, router_uuid, interfaces, neighbors_arc) = rip_settings_snapshot;
+    let (rip_enabled, rip_context, interfaces) = rip_settings_snapshot;
+    let tunnels: Vec<_> = if initial_config.rip.enabled {
+        initial_config
+            .tunnels
+            .iter()
+            .filter(|tunnel| tunnel.enabled)
+            .cloned()
+            .collect()
+    } else {
+        Vec::new()
+    };
 
-    if rip_enabled {
-        let rip_config = Arc::new(rip_config);
+    if rip_enabled || !tunnels.is_empty() {
+        let rip_config = rip_context.rip_config.clone();
 
-        // Periodic neighbor cleanup based on RIP timers
-        let router_for_cleanup = Arc::clone(&router);
-        let rip_config_for_cleanup = rip_config.clone();
-        tokio::spawn(async move {
-            let mut interval = tokio::time::interval(Duration::from_secs(60));
-            loop {
-                interval.tick().await;
-                router_for_cleanup
-                    .read()
-                    .await
-                    .cleanup_neighbors(Duration::from_secs(
-                        rip_config_for_cleanup.garbage_collection_timeout.max(60),
-                    ))
-                    .await;
-            }
-        });
+        // Route and neighbor timers
+        {
+            let router = Arc::clone(&router);
+            let events = event_bus.clone();
+            let period = RipTimers::from_config(&rip_config).sweep_interval();
+            watchdog.spawn("rip-timers", period, move |heartbeat| {
+                timers::run(Arc::clone(&router), events.clone(), heartbeat)
+            });
+        }
 
-        // Periodic routing updates
-        let routing_table_for_updates = Arc::clone(&routing_table);
-        let metrics_for_updates = metrics.clone();
-        let interfaces_for_updates = interfaces.clone();
-        let rip_config_for_updates = rip_config.clone();
-        tokio::spawn(async move {
-            let mut interval = tokio::time::interval(Duration::from_secs(
-                rip_config_for_updates.update_interval.max(5),
-            ));
-            loop {
-                interval.tick().await;
-
-                for iface in &interfaces_for_updates {
-                    let routes: Vec<Route> = {
-                        let table = routing_table_for_updates.read().await;
-                        table
-                            .get_routes_for_advertising(&iface.config.name)
-                            .into_iter()
-                            .cloned()
-                            .collect()
-                    };
+        // Withdraw routes as soon as a link drops
+        {
+            let router = Arc::clone(&router);
+            let events = event_bus.clone();
+            watchdog.spawn("link-monitor", LINK_POLL_INTERVAL, move |heartbeat| {
+                router::watch_links(Arc::clone(&router), events.clone(), heartbeat)
+            });
+        }
 
-                    if routes.is_empty() {
-                        continue;
-                    }
+        // Probe learned next hops; idle until probes.enabled is set
+        {
+            let router = Arc::clone(&router);
+            let events = event_bus.clone();
+            watchdog.spawn("probe-next-hops", RECEIVE_HEARTBEAT, move |heartbeat| {
+                probe::run(Arc::clone(&router), events.clone(), heartbeat)
+            });
+        }
+
+        // Consolidated topology change events
+        {
+            let router = Arc::clone(&router);
+            let events = event_bus.clone();
+            watchdog.spawn("topology", RECEIVE_HEARTBEAT, move |heartbeat| {
+                topology::run(Arc::clone(&router), events.clone(), heartbeat)
+            });
+        }
+
+        // Learned routes in the kernel routing table
+        if rip_config.install_kernel_routes {
+            let routing_table = Arc::clone(&routing_table);
+            let events = event_bus.clone();
+            watchdog.spawn("kernel-routes", kernel::SYNC_INTERVAL, move |heartbeat| {
+                kernel::run(Arc::clone(&routing_table), events.clone(), heartbeat)
+            });
+        }
 
-                    let packet = RipPacket::new_update(router_uuid, routes);
-                    if let Err(err) = iface.send_packet(&packet).await {
-                        warn!(
-                            "Failed to broadcast routes on {}: {}",
-                            iface.config.name, err
+        // Routes of the host; idle until rip.redistribute enables a source
+        {
+            let router = Arc::clone(&router);
+            let events = event_bus.clone();
+            watchdog.spawn(
+                "redistribute",
+                redistribute::SCAN_INTERVAL,
+                move |heartbeat| redistribute::run(Arc::clone(&router), events.clone(), heartbeat),
+            );
+        }
+
+        // Interface costs following configuration and link speed
+        {
+            let router = Arc::clone(&router);
+            let events = event_bus.clone();
+            watchdog.spawn("link-costs", link_cost::POLL_INTERVAL, move |heartbeat| {
+                link_cost::run(Arc::clone(&router), events.clone(), heartbeat)
+            });
+        }
+
+        // Audit of the routing table against the interfaces of the host
+        {
+            let router = Arc::clone(&router);
+            let events = event_bus.clone();
+            watchdog.spawn(
+                "table-consistency",
+                consistency::CHECK_INTERVAL,
+                move |heartbeat| consistency::run(Arc::clone(&router), events.clone(), heartbeat),
+            );
+        }
+
+        // Periodic routing updates
+        {
+            let routing_table = Arc::clone(&routing_table);
+            let metrics = metrics.clone();
+            let router = Arc::clone(&router);
+            let period = Duration::from_secs(rip_config.update_interval.max(5));
+            watchdog.spawn("rip-updates", period, move |heartbeat| {
+                let routing_table = Arc::clone(&routing_table);
+                let metrics = metrics.clone();
+                let router = Arc::clone(&router);
+                async move {
+                    // Learn for the quiet period, then send the full table at once
+                    let quiet = router.read().await.quiet_remaining();
+                    if let Some(quiet) = quiet {
+                        info!(
+                            "Learning routes for {}s before advertising",
+                            quiet.as_secs_f64().ceil()
                         );
-                        continue;
+                        router::pause(quiet, &heartbeat).await;
+                        info!("Startup quiet period over; advertising routes");
                     }
+                    let mut interval = tokio::time::interval(period);
+                    // A paced table can take longer to send than the period
+                    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+                    let mut schedule = AdvertiseSchedule::default();
+                    loop {
+                        interval.tick().await;
+                        heartbeat.beat();
 
-                    metrics_for_updates.increment_packets_sent();
-                    metrics_for_updates.increment_routing_updates_sent();
-                }
-            }
-        });
+                        let (interfaces, policy, slow_prefixes, infinity, split_horizon) = {
+                            let router = router.read().await;
+                            let rip = &router.config().rip;
+                            (
+                                router.advertising_interfaces(),
+                                router.policy(),
+                                rip.slow_prefixes.clone(),
+                                rip.infinity_metric,
+                                rip.split_horizon_mode(),
+                            )
+                        };
+                        for iface in &interfaces {
+                            let routes = routing_table.read().await.advertise(
+                                &iface.config.name,
+                                split_horizon,
+                                infinity,
+                            );
+                            let routes = schedule.due(
+                                &slow_prefixes,
+                                &iface.config.name,
+                                routes,
+                                period,
+                                std::time::Instant::now(),
+                                infinity,
+                            );
 
-        // Routing table maintenance (timeouts & garbage collection)
-        let routing_table_for_timers = Arc::clone(&routing_table);
-        let metrics_for_timers = metrics.clone();
-        let rip_config_for_timers = rip_config.clone();
-        tokio::spawn(async move {
-            let mut interval = tokio::time::interval(Duration::from_secs(
-                (rip_config_for_timers.update_interval.max(5)) * 2,
-            ));
-            loop {
-                interval.tick().await;
-
-                let mut table = routing_table_for_timers.write().await;
-                table.process_timeouts();
-                table.garbage_collect();
-                metrics_for_timers.update_route_count(table.route_count());
-            }
-        });
+                            if routes.is_empty() {
+                                continue;
+                            }
 
-        // Packet receive loops per interface
-        for iface in interfaces {
-            let iface_clone = Arc::clone(&iface);
-            let routing_table_for_iface = Arc::clone(&routing_table);
-            let metrics_for_iface = metrics.clone();
-            let neighbors_for_iface = Arc::clone(&neighbors_arc);
-            let rip_config_for_iface = rip_config.clone();
-            let iface_name = iface.config.name.clone();
-            let events_for_iface = event_bus.clone();
-
-            tokio::spawn(async move {
-                loop {
-                    match iface_clone.receive_packet().await {
-                        Ok((packet, sender)) => {
-                            metrics_for_iface.increment_packets_received();
-                            match packet.command {
-                                RipCommand::Request => {
-                                    let routes: Vec<Route> = {
-                                        let table = routing_table_for_iface.read().await;
-                                        table
-                                            .get_routes_for_advertising(&iface_name)
-                                            .into_iter()
-                                            .cloned()
-                                            .collect()
-                                    };
-
-                                    let response = RipPacket::new_update(router_uuid, routes);
-                                    if let Err(err) =
-                                        iface_clone.send_packet_to(&response, sender).await
-                                    {
-                                        warn!(
-                                            "Failed to reply RIP request on {}: {}",
-                                            iface_name, err
-                                        );
-                                    } else {
-                                        metrics_for_iface.increment_packets_sent();
-                                        metrics_for_iface.increment_routing_updates_sent();
-                                    }
+                            let advertisement = policy.export(routes, &iface.config.name);
+                            let send = router::send_advertisement(iface, &advertisement);
+                            tokio::pin!(send);
+                            let sent = loop {
+                                tokio::select! {
+                                    sent = &mut send => break sent,
+                                    _ = tokio::time::sleep(RECEIVE_HEARTBEAT) => heartbeat.beat(),
                                 }
-                                RipCommand::Response => {
-                                    match handle_rip_response(
-                                        Arc::clone(&routing_table_for_iface),
-                                        Arc::clone(&neighbors_for_iface),
-                                        metrics_for_iface.clone(),
-                                        rip_config_for_iface.clone(),
-                                        iface_name.clone(),
-                                        packet,
-                                        sender,
-                                    )
-                                    .await
-                                    {
-                                        Ok(routes) => {
-                                            for route in routes {
-                                                events_for_iface.publish(WebEvent::Route(
-                                                    RouteEvent::from_parts(
-                                                        route.destination,
-                                                        route.subnet_mask,
-                                                        route.next_hop,
-                                                        route.metric,
-                                                        route.interface.clone(),
-                                                        route.source,
-                                                    ),
-                                                ));
-                                            }
-                                        }
-                                        Err(err) => {
-                                            warn!(
-                                                "Failed to process RIP response on {}: {}",
-                                                iface_name, err
-                                            );
-                                            events_for_iface.publish_activity(
-                                                ActivityLevel::Warn,
-                                                format!(
-                                                    "Failed to process RIP response on {}: {}",
-                                                    iface_name, err
-                                                ),
-                                            );
-                                        }
-                                    }
+                            };
+                            match sent {
+                                Ok(true) => {}
+                                Ok(false) => continue,
+                                Err(err) => {
+                                    warn!(
+                                        "Failed to broadcast routes on {}: {}",
+                                        iface.config.name, err
+                                    );
+                                    continue;
                                 }
                             }
-                        }
-                        Err(err) => {
-                            warn!("Error receiving packet on {}: {}", iface_name, err);
-                            tokio::time::sleep(Duration::from_secs(1)).await;
+
+                            iface.record_update_sent();
+                            metrics.increment_packets_sent();
+                            metrics.increment_routing_updates_sent();
                         }
                     }
                 }
             });
         }
+
+        // Packet receive loops per interface
+        ensure_receive_tasks(&watchdog, &interfaces, &rip_context);
+
+        // Ask neighbors for their tables rather than wait for their next update
+        let requests_sent = router.read().await.request_tables(None).await;
+        if requests_sent > 0 {
+            info!("Requested full tables on {} interfaces", requests_sent);
+        }
+
+        // RIP over TCP/TLS tunnels
+        let update_interval = Duration::from_secs(rip_config.update_interval.max(5));
+        for tunnel_config in tunnels {
+            let ctx = rip_context.clone();
+            let task_name = format!("tunnel-{}", tunnel_config.name);
+            watchdog.spawn(&task_name, RECEIVE_HEARTBEAT, move |heartbeat| {
+                tunnel::run(
+                    tunnel_config.clone(),
+                    ctx.clone(),
+                    update_interval,
+                    heartbeat,
+                )
+            });
+        }
     } else {
         info!("RIP networking disabled or no active interfaces; skipping UDP tasks");
     }
 
+    // MQTT telemetry publisher
+    if initial_config.mqtt.enabled {
+        let mqtt_config = initial_config.mqtt.clone();
+        let router = Arc::clone(&router);
+        let events = event_bus.clone();
+        watchdog.spawn("mqtt-publisher", RECEIVE_HEARTBEAT, move |heartbeat| {
+            mqtt::run(
+                mqtt_config.clone(),
+                Arc::clone(&router),
+                events.clone(),
+                heartbeat,
+            )
+        });
+    }
+
+    // Event sink plugins
+    let sinks = PluginRegistry::global().event_sinks(&initial_config.plugins);
+    if !sinks.is_empty() {
+        let router_id = initial_config.router_id.clone();
+        let events = event_bus.clone();
+        watchdog.spawn("plugin-sinks", RECEIVE_HEARTBEAT, move |heartbeat| {
+            plugins::run_sinks(sinks.clone(), router_id.clone(), events.clone(), heartbeat)
+        });
+    }
+
+    // External hook scripts
+    if initial_config.hooks.enabled {
+        let hooks_config = initial_config.hooks.clone();
+        let router_id = initial_config.router_id.clone();
+        let events = event_bus.clone();
+        watchdog.spawn("hook-runner", RECEIVE_HEARTBEAT, move |heartbeat| {
+            hooks::run(
+                hooks_config.clone(),
+                router_id.clone(),
+                events.clone(),
+                heartbeat,
+            )
+        });
+    }
+
+    // Event streaming sink
+    if initial_config.event_stream.enabled {
+        let stream_config = initial_config.event_stream.clone();
+        let router_id = initial_config.router_id.clone();
+        let events = event_bus.clone();
+        watchdog.spawn("event-stream", RECEIVE_HEARTBEAT, move |heartbeat| {
+            streaming::run(
+                stream_config.clone(),
+                router_id.clone(),
+                events.clone(),
+                heartbeat,
+            )
+        });
+    }
+
+    // SNMP agent
+    if initial_config.snmp.enabled {
+        let agent = SnmpAgent::new(
+            initial_config.snmp.clone(),
+            Arc::clone(&router),
+            Arc::clone(&routing_table),
+            metrics.clone(),
+        )
+        .await;
+        match agent.bind().await {
+            Ok(socket) => {
+                if let Ok(addr) = socket.local_addr() {
+                    info!("SNMP agent listening on {}", addr);
+                }
+                let agent = Arc::new(agent);
+                let socket = Arc::new(socket);
+                watchdog.spawn("snmp-agent", RECEIVE_HEARTBEAT, move |heartbeat| {
+                    Arc::clone(&agent).serve(Arc::clone(&socket), heartbeat)
+                });
+            }
+            Err(err) => {
+                warn!("{}", err);
+                event_bus
+                    .publish_activity(ActivityLevel::Warn, format!("SNMP agent disabled: {}", err));
+            }
+        }
+    }
+
+    let app_state = AppState {
+        router: Arc::clone(&router),
+        routing_table: Arc::clone(&routing_table),
+        metrics: metrics.clone(),
+        config_manager: Arc::clone(&manager),
+        events: event_bus.clone(),
+        auth: Arc::clone(&auth_state),
+        watchdog: watchdog.clone(),
+        activity: activity_log,
+        metrics_history,
+        diagnostics: diagnostics.clone(),
+        fleet: Fleet::new(),
+    };
+
+    // gNMI management interface
+    gnmi::spawn(&initial_config.gnmi, app_state.clone()).await;
+
+    // State dumps for bug reports
+    dump::spawn_signal_handler(app_state.clone());
+
+    // Relays of the fleet peers' events
+    fleet::spawn(&initial_config.fleet, app_state.fleet.clone());
+
     // Launch web interface
-    let web_server = WebServer::new(
-        Arc::clone(&router),
-        Arc::clone(&routing_table),
-        metrics.clone(),
-        Arc::clone(&manager),
-        initial_config.web.clone(),
-        event_bus.clone(),
-        Arc::clone(&auth_state),
-    );
+    let web_server = WebServer::new(app_state, initial_config.web.clone());
 
     let web_handle = tokio::spawn(async move {
         if let Err(err) = web_server.start().await {
             error!("Web server error: {}", err);
+            last_error::record(last_error::WEB, &err);
         }
     });
 
@@ -426,8 +746,137 @@ async fn start_router(config_path: String) -> Result<(), Box<dyn std::error::Err
                 error!("Web server stopped unexpectedly: {}", err);
             }
         }
+        stalled = watchdog.monitor(event_bus.clone()) => {
+            error!("Watchdog: task {} stalled; exiting for supervisor restart", stalled);
+            return Err(format!("watchdog detected stalled task {}", stalled).into());
+        }
+    }
+
+    Ok(())
+}
+
+async fn apply_config_change(
+    router: &Arc<RwLock<Router>>,
+    metrics: &Metrics,
+    manager: &Arc<ConfigManager>,
+    events: &EventBus,
+    auth_state: &Arc<Mutex<Option<AuthManager>>>,
+    storage: &Arc<dyn Storage>,
+    new_config: RouterConfig,
+) {
+    match router.write().await.apply_config(new_config.clone()).await {
+        Ok(_) => {
+            Locale::set_current(new_config.locale);
+            match logging::apply_config(&new_config.logging.level) {
+                Ok(true) => {
+                    info!("Log level set to {}", new_config.logging.level);
+                    events.publish_activity(
+                        ActivityLevel::Info,
+                        format!("Log level set to {}", new_config.logging.level),
+                    );
+                }
+                Ok(false) => {}
+                Err(err) => warn!("Keeping the current log level: {}", err),
+            }
+            let version = manager.get_config_version().await;
+            metrics.set_config_version(version);
+            let routing_table = router.read().await.routing_table();
+            let route_count = routing_table.read().await.route_count();
+            metrics.update_route_count(route_count);
+            info!("✅ Configuration change applied successfully");
+            events.publish_activity(ActivityLevel::Info, "Configuration reloaded from disk");
+
+            let mut auth_guard = auth_state.lock().await;
+            let auth_enabled = new_config.auth.enabled;
+            let web_auth_enabled = new_config.web.auth_enabled;
+            let auth_active = auth_enabled && web_auth_enabled;
+
+            if auth_active {
+                match AuthManager::with_storage(new_config.auth.clone(), Arc::clone(storage)) {
+                    Ok(manager) => {
+                        *auth_guard = Some(manager);
+                        events.publish_activity(
+                            ActivityLevel::Info,
+                            "Authentication settings updated",
+                        );
+                    }
+                    Err(err) => {
+                        *auth_guard = None;
+                        events.publish_activity(
+                            ActivityLevel::Error,
+                            format!("Failed to update authentication settings: {}", err),
+                        );
+                    }
+                }
+            } else {
+                if auth_guard.is_some() {
+                    *auth_guard = None;
+                    events.publish_activity(
+                        ActivityLevel::Warn,
+                        "Authentication disabled via configuration",
+                    );
+                }
+
+                if auth_enabled != web_auth_enabled {
+                    warn!(
+                        "Authentication configuration mismatch: auth.enabled={}, web.auth_enabled={}. Authentication will remain disabled.",
+                        auth_enabled, web_auth_enabled
+                    );
+                    events.publish_activity(
+                        ActivityLevel::Warn,
+                        "Authentication mismatch detected; enable both auth.enabled and web.auth_enabled to require login",
+                    );
+                }
+            }
+        }
+        Err(err) => {
+            error!("Failed to apply new configuration: {}", err);
+            events.publish_activity(
+                ActivityLevel::Error,
+                format!("Failed to apply configuration: {}", err),
+            );
+        }
     }
+}
+
+fn log_preflight(report: &PreflightReport, events: &EventBus) {
+    for finding in &report.findings {
+        let level = match finding.severity {
+            Severity::Warning => {
+                warn!("Preflight: {} ({})", finding.message, finding.hint);
+                ActivityLevel::Warn
+            }
+            Severity::Error => {
+                error!("Preflight: {} ({})", finding.message, finding.hint);
+                ActivityLevel::Error
+            }
+        };
+        events.publish_activity(level, format!("Preflight: {}", finding.message));
+    }
+}
+
+async fn handle_preflight_command(
+    config_path: String,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let content = tokio::fs::read_to_string(&config_path).await?;
+    let config: RouterConfig = serde_json::from_str(&content)?;
+    let report = preflight::run(&config);
 
+    if report.findings.is_empty() {
+        println!("✅ All preflight checks passed");
+        return Ok(());
+    }
+    for finding in &report.findings {
+        let icon = match finding.severity {
+            Severity::Warning => "⚠️ ",
+            Severity::Error => "❌",
+        };
+        println!("{} [{}] {}", icon, finding.check, finding.message);
+        println!("     → {}", finding.hint);
+    }
+    if report.has_errors() {
+        std::process::exit(1);
+    }
     Ok(())
 }
 
@@ -462,6 +911,39 @@ async fn handle_config_command(
             tokio::fs::write(&output, json).await?;
             println!("✅ Default configuration generated: {}", output);
         }
+        ConfigAction::Import {
+            file,
+            format,
+            output,
+        } => {
+            if ConfigFormat::parse(&format) != Some(ConfigFormat::Frr) {
+                return Err(format!("Unsupported import format: {}", format).into());
+            }
+
+            let content = tokio::fs::read_to_string(&file).await?;
+            let report = import_frr(&content);
+            for warning in &report.warnings {
+                println!("⚠️  {}", warning);
+            }
+
+            let validation = ConfigManager::validate_config(&report.config);
+            if !validation.is_valid() {
+                println!("❌ Imported configuration is invalid");
+                for error in validation.errors {
+                    println!("  - {}", error);
+                }
+                std::process::exit(1);
+            }
+
+            let json = serde_json::to_string_pretty(&report.config)?;
+            tokio::fs::write(&output, json).await?;
+            println!(
+                "✅ Imported {} interfaces from {}: {}",
+                report.config.interfaces.len(),
+                file,
+                output
+            );
+        }
         ConfigAction::Backup { config, output } => {
             let (manager, _) = ConfigManager::new(&config).await?;
             let backup_path = manager.create_backup("Manual backup".to_string()).await?;
@@ -481,49 +963,300 @@ async fn handle_config_command(
     Ok(())
 }
 
-async fn run_tests() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    println!("🧪 Running RustRoute tests...");
-    test_routing_table().await?;
-    test_config_validation().await?;
-    test_metrics_flow().await?;
-    println!("✅ All tests passed!");
+async fn handle_interface_command(
+    action: InterfaceAction,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (name, config, enabled) = match action {
+        InterfaceAction::Enable { name, config } => (name, config, true),
+        InterfaceAction::Disable { name, config } => (name, config, false),
+    };
+
+    let (manager, _) = ConfigManager::new(&config).await?;
+    if !manager.set_interface_enabled(&name, enabled).await? {
+        println!("❌ Interface {} not found in {}", name, config);
+        std::process::exit(1);
+    }
+
+    println!(
+        "✅ Interface {} {}; a running router applies the change on reload",
+        name,
+        if enabled { "enabled" } else { "disabled" }
+    );
     Ok(())
 }
 
-async fn test_routing_table() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    use std::net::Ipv4Addr;
+async fn handle_lab_command(
+    action: LabAction,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    match action {
+        LabAction::Up { topology } => {
+            let topology = Topology::load(std::path::Path::new(&topology))?;
+            let mut lab = Lab::up(&topology, &std::env::current_exe()?)?;
+            println!(
+                "🧪 Lab {} running in {}",
+                topology.name,
+                topology.directory().display()
+            );
+            for process in lab.processes() {
+                println!(
+                    "  {:<12} pid {:<8} {}  log {}",
+                    process.name,
+                    process.pid,
+                    process.web_url,
+                    process.log.display()
+                );
+            }
+            println!("Press Ctrl-C to stop the lab");
 
-    println!("  Testing routing table...");
-    let mut table = RoutingTable::new();
-    table.install_direct_route(
-        Ipv4Addr::new(192, 168, 1, 0),
-        Ipv4Addr::new(255, 255, 255, 0),
-        "eth0".to_string(),
+            let mut poll = tokio::time::interval(Duration::from_secs(1));
+            loop {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => break,
+                    _ = poll.tick() => {
+                        let exited = lab.exited();
+                        if !exited.is_empty() {
+                            println!("❌ Router process exited: {}", exited.join(", "));
+                            break;
+                        }
+                    }
+                }
+            }
+            lab.down();
+            println!("✅ Lab {} stopped", topology.name);
+        }
+        LabAction::Down { topology } => {
+            let topology = Topology::load(std::path::Path::new(&topology))?;
+            let stopped = lab::down(&topology)?;
+            println!("✅ Stopped {} router processes", stopped);
+        }
+    }
+    Ok(())
+}
+
+async fn handle_clear_command(
+    target: ClearTarget,
+    url: String,
+    token: Option<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let client = ApiClient::new(url, token);
+    let (what, outcome): (String, ClearOutcome) = match target {
+        ClearTarget::Routes { source } => {
+            let path = format!("/api/routes/clear?source={}", source);
+            (format!("{} routes", source), client.post(&path).await?)
+        }
+        ClearTarget::Neighbor { ip } => {
+            let path = format!("/api/neighbors/{}/clear", ip);
+            (format!("neighbor {}", ip), client.post(&path).await?)
+        }
+    };
+
+    println!(
+        "✅ Cleared {}: {} routes removed, {} RIP requests sent",
+        what, outcome.routes_removed, outcome.requests_sent
     );
-    assert_eq!(table.route_count(), 1);
-    println!("    ✓ Direct route installation works");
     Ok(())
 }
 
-async fn test_config_validation() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    println!("  Testing configuration validation...");
-    let config = RouterConfig::default();
-    let validation = ConfigManager::validate_config(&config);
-    assert!(validation.is_valid());
-    println!("    ✓ Default config is valid");
+async fn handle_maintenance_command(
+    action: MaintenanceAction,
+    url: String,
+    token: Option<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let client = ApiClient::new(url, token);
+    let status: MaintenanceStatus = match action {
+        MaintenanceAction::Enter { metric, reason } => {
+            let mut query = Vec::new();
+            if let Some(metric) = metric {
+                query.push(("metric", metric.to_string()));
+            }
+            if let Some(reason) = reason {
+                query.push(("reason", reason));
+            }
+            client.post_query("/api/maintenance", &query).await?
+        }
+        MaintenanceAction::Exit => client.delete("/api/maintenance").await?,
+        MaintenanceAction::Status => client.get("/api/maintenance").await?,
+    };
+
+    match (&status.maintenance, status.interfaces_updated) {
+        (Some(maintenance), Some(updated)) => println!(
+            "🚧 Maintenance mode on: advertising metric {} on {} interfaces",
+            maintenance.metric, updated
+        ),
+        (Some(maintenance), None) => println!(
+            "🚧 Maintenance mode on since {}: advertising metric {}{}",
+            maintenance.since.to_rfc3339(),
+            maintenance.metric,
+            maintenance
+                .reason
+                .as_deref()
+                .map(|reason| format!(" ({})", reason))
+                .unwrap_or_default()
+        ),
+        (None, Some(updated)) => println!(
+            "✅ Maintenance mode off: normal metrics sent on {} interfaces",
+            updated
+        ),
+        (None, None) => println!("✅ Maintenance mode is off"),
+    }
     Ok(())
 }
 
-async fn test_metrics_flow() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    println!("  Testing metrics flow...");
-    let metrics = Metrics::new();
-    metrics.increment_packets_sent();
-    metrics.increment_packets_received();
-    metrics.update_route_count(4);
-    let snapshot = metrics.snapshot(1, 4);
-    assert_eq!(snapshot.packets_sent, 1);
-    assert_eq!(snapshot.route_count, 4);
-    println!("    ✓ Metrics snapshot looks good");
+async fn handle_export_command(
+    format: String,
+    config: Option<String>,
+    output: Option<String>,
+    url: String,
+    token: Option<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let Some(export_format) = ConfigFormat::parse(&format) else {
+        return Err(format!("Unsupported export format: {}", format).into());
+    };
+
+    let rendered = if let Some(path) = config {
+        // Offline rendering has no runtime state, so static routes are not included
+        let content = tokio::fs::read_to_string(&path).await?;
+        let config: RouterConfig = serde_json::from_str(&content)?;
+        export_config(&config, &[], export_format)
+    } else {
+        let client = ApiClient::new(url, token);
+        client
+            .get_text(&format!("/api/export?format={}", export_format.as_str()))
+            .await?
+    };
+
+    match output {
+        Some(path) => {
+            tokio::fs::write(&path, rendered).await?;
+            println!("✅ {} configuration exported: {}", format, path);
+        }
+        None => print!("{}", rendered),
+    }
+    Ok(())
+}
+
+async fn handle_compare_command(
+    peer: String,
+    peer_token: Option<String>,
+    url: String,
+    token: Option<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    compare::validate_peer_url(&peer)?;
+    let local = compare::fetch_routes(&ApiClient::new(url, token)).await?;
+    let remote = compare::fetch_routes(&ApiClient::new(peer.clone(), peer_token)).await?;
+    let comparison = compare::compare(&local, &remote, &peer);
+
+    if comparison.is_identical() {
+        println!(
+            "✅ Route tables match: {} prefixes with the same metric and next hop",
+            comparison.matching
+        );
+        return Ok(());
+    }
+
+    println!(
+        "🔍 {} prefixes match; {} only here, {} only on {}, {} differ",
+        comparison.matching,
+        comparison.only_local.len(),
+        comparison.only_peer.len(),
+        peer,
+        comparison.differing.len()
+    );
+    for route in &comparison.only_local {
+        println!(
+            "  < {:<18} via {} metric {} ({})",
+            route.prefix, route.route.next_hop, route.route.metric, route.route.interface
+        );
+    }
+    for route in &comparison.only_peer {
+        println!(
+            "  > {:<18} via {} metric {} ({})",
+            route.prefix, route.route.next_hop, route.route.metric, route.route.interface
+        );
+    }
+    for difference in &comparison.differing {
+        println!(
+            "  ~ {:<18} here via {} metric {}, peer via {} metric {}",
+            difference.prefix,
+            difference.local.next_hop,
+            difference.local.metric,
+            difference.peer.next_hop,
+            difference.peer.metric
+        );
+    }
+    Ok(())
+}
+
+async fn handle_history_export(
+    path: &str,
+    what: &str,
+    range: HistoryRange,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let Some(format) = ExportFormat::parse(&range.format) else {
+        return Err(format!("Unsupported export format: {}", range.format).into());
+    };
+
+    let now = chrono::Utc::now();
+    let mut query = vec![("format", format.as_str().to_string())];
+    for (name, value) in [("from", &range.from), ("to", &range.to)] {
+        if let Some(value) = value {
+            let time = export::parse_time(value, now).ok_or_else(|| {
+                format!(
+                    "--{} {} is neither an RFC 3339 time nor an age such as 2h",
+                    name, value
+                )
+            })?;
+            query.push((name, time.to_rfc3339()));
+        }
+    }
+
+    let client = ApiClient::new(range.url, range.token);
+    let rendered = client.get_text_query(path, &query).await?;
+    match range.output {
+        Some(output) => {
+            tokio::fs::write(&output, rendered).await?;
+            println!("✅ {} exported: {}", what, output);
+        }
+        None => print!("{}", rendered),
+    }
+    Ok(())
+}
+
+async fn run_tests(
+    test_name: Option<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if let Some(name) = &test_name {
+        if !selftest::CHECKS.contains(&name.as_str()) {
+            return Err(format!(
+                "Unknown test {}; choose one of {}",
+                name,
+                selftest::CHECKS.join(", ")
+            )
+            .into());
+        }
+    }
+
+    println!("🧪 Running RustRoute tests...");
+    let report = selftest::run(&RouterConfig::default(), &[]);
+    let mut failed = 0;
+    for check in &report.checks {
+        if test_name.as_ref().is_some_and(|name| name != check.name) {
+            continue;
+        }
+        let mark = match check.status {
+            CheckStatus::Pass => "✓",
+            CheckStatus::Fail => {
+                failed += 1;
+                "✗"
+            }
+            CheckStatus::Skip => "-",
+        };
+        println!("    {} {}: {}", mark, check.name, check.detail);
+    }
+    if failed > 0 {
+        return Err(format!("{} tests failed", failed).into());
+    }
+    println!("✅ All tests passed!");
     Ok(())
 }
 
@@ -6,8 +6,13 @@ use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
+use crate::diagnostics::RuntimeStats;
+use crate::probe::NextHopStats;
+use crate::protocol::RipCommand;
+use crate::routing_table::TableMemory;
+
 /// Snapshot of router metrics that can be serialized and exposed via the API
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct MetricsSnapshot {
     pub packets_sent: u64,
     pub packets_received: u64,
@@ -15,6 +20,21 @@ pub struct MetricsSnapshot {
     pub routing_updates_sent: u64,
     pub routing_updates_received: u64,
     pub route_changes: u64,
+    /// RIP requests answered with a response
+    #[serde(default)]
+    pub queries_answered: u64,
+    /// Prefixes caught counting to infinity
+    #[serde(default)]
+    pub count_to_infinity_detected: u64,
+    /// Received next hops that failed validation
+    #[serde(default)]
+    pub invalid_next_hops: u64,
+    /// Responses answering a Request this router sent
+    #[serde(default)]
+    pub solicited_responses: u64,
+    /// Periodic and triggered updates neighbors sent on their own
+    #[serde(default)]
+    pub unsolicited_responses: u64,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub convergence_time_seconds: Option<u64>,
     pub neighbor_count: usize,
@@ -22,23 +42,166 @@ pub struct MetricsSnapshot {
     pub uptime_seconds: u64,
     pub route_count: u64,
     pub config_version: u32,
+    /// RIPng counters; the fields above cover RIPv2 only
+    #[serde(default)]
+    pub ripng: RipngMetrics,
+    /// Received RIPv2 packets by type and version, and why others were dropped
+    #[serde(default)]
+    pub packets: PacketStats,
+    /// Routing table footprint, included by the metrics endpoint
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub table_memory: Option<TableMemory>,
+    /// Task, channel and lock statistics, included by the metrics endpoint
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runtime: Option<Box<RuntimeStats>>,
+    /// Round-trip times and losses of probed next hops, included by the
+    /// metrics endpoint while probes are enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_hops: Option<Box<[NextHopStats]>>,
 }
 
-impl Default for MetricsSnapshot {
-    fn default() -> Self {
-        Self {
-            packets_sent: 0,
-            packets_received: 0,
-            packets_dropped: 0,
-            routing_updates_sent: 0,
-            routing_updates_received: 0,
-            route_changes: 0,
-            convergence_time_seconds: None,
-            neighbor_count: 0,
-            active_routes: 0,
-            uptime_seconds: 0,
-            route_count: 0,
-            config_version: 0,
+/// RIPng traffic and state
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RipngMetrics {
+    pub packets_sent: u64,
+    pub packets_received: u64,
+    /// Packets that could not be parsed
+    pub packets_dropped: u64,
+    pub route_changes: u64,
+    pub route_count: u64,
+    pub neighbor_count: u64,
+}
+
+#[derive(Debug, Default)]
+struct RipngCollector {
+    packets_sent: AtomicU64,
+    packets_received: AtomicU64,
+    packets_dropped: AtomicU64,
+    route_changes: AtomicU64,
+    route_count: AtomicU64,
+    neighbor_count: AtomicU64,
+}
+
+impl RipngCollector {
+    fn reset(&self) {
+        self.packets_sent.store(0, Ordering::Relaxed);
+        self.packets_received.store(0, Ordering::Relaxed);
+        self.packets_dropped.store(0, Ordering::Relaxed);
+        self.route_changes.store(0, Ordering::Relaxed);
+        self.route_count.store(0, Ordering::Relaxed);
+        self.neighbor_count.store(0, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> RipngMetrics {
+        RipngMetrics {
+            packets_sent: self.packets_sent.load(Ordering::Relaxed),
+            packets_received: self.packets_received.load(Ordering::Relaxed),
+            packets_dropped: self.packets_dropped.load(Ordering::Relaxed),
+            route_changes: self.route_changes.load(Ordering::Relaxed),
+            route_count: self.route_count.load(Ordering::Relaxed),
+            neighbor_count: self.neighbor_count.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Why a received RIPv2 packet was dropped before it was processed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketDrop {
+    /// Could not be decoded or failed validation
+    Malformed,
+    /// Larger than the MTU of the interface
+    Oversized,
+    /// Failed the key chain or packet signing of the interface
+    Unauthenticated,
+    /// Refused by the `accept_from`/`reject_from` sender filter
+    Filtered,
+}
+
+/// Received RIP packets by the reason they were dropped, and by command once
+/// accepted. Versions are counted for every packet that decodes, so RIPv1
+/// packets show up even though they are dropped as malformed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PacketStats {
+    pub requests: u64,
+    pub responses: u64,
+    pub version1: u64,
+    pub version2: u64,
+    pub malformed: u64,
+    pub oversized: u64,
+    pub unauthenticated: u64,
+    pub filtered: u64,
+}
+
+/// Counters behind [`PacketStats`], kept globally and per interface
+#[derive(Debug, Default)]
+pub struct PacketStatsCollector {
+    requests: AtomicU64,
+    responses: AtomicU64,
+    version1: AtomicU64,
+    version2: AtomicU64,
+    malformed: AtomicU64,
+    oversized: AtomicU64,
+    unauthenticated: AtomicU64,
+    filtered: AtomicU64,
+}
+
+impl PacketStatsCollector {
+    /// Count the version of a decoded packet; other versions than 1 and 2
+    /// only count as malformed
+    pub fn record_version(&self, version: u8) {
+        match version {
+            1 => self.version1.fetch_add(1, Ordering::Relaxed),
+            2 => self.version2.fetch_add(1, Ordering::Relaxed),
+            _ => 0,
+        };
+    }
+
+    /// Count a packet that passed validation, authentication and filtering
+    pub fn record_accepted(&self, command: RipCommand) {
+        match command {
+            RipCommand::Request => &self.requests,
+            RipCommand::Response => &self.responses,
+        }
+        .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_dropped(&self, drop: PacketDrop) {
+        match drop {
+            PacketDrop::Malformed => &self.malformed,
+            PacketDrop::Oversized => &self.oversized,
+            PacketDrop::Unauthenticated => &self.unauthenticated,
+            PacketDrop::Filtered => &self.filtered,
+        }
+        .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn reset(&self) {
+        for counter in [
+            &self.requests,
+            &self.responses,
+            &self.version1,
+            &self.version2,
+            &self.malformed,
+            &self.oversized,
+            &self.unauthenticated,
+            &self.filtered,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+
+    pub fn snapshot(&self) -> PacketStats {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        PacketStats {
+            requests: load(&self.requests),
+            responses: load(&self.responses),
+            version1: load(&self.version1),
+            version2: load(&self.version2),
+            malformed: load(&self.malformed),
+            oversized: load(&self.oversized),
+            unauthenticated: load(&self.unauthenticated),
+            filtered: load(&self.filtered),
         }
     }
 }
@@ -51,6 +214,11 @@ struct MetricsCollector {
     routing_updates_sent: AtomicU64,
     routing_updates_received: AtomicU64,
     route_changes: AtomicU64,
+    queries_answered: AtomicU64,
+    count_to_infinity_detected: AtomicU64,
+    invalid_next_hops: AtomicU64,
+    solicited_responses: AtomicU64,
+    unsolicited_responses: AtomicU64,
     convergence_start: Mutex<Option<Instant>>,
     convergence_time: Mutex<Option<Duration>>,
 }
@@ -64,6 +232,11 @@ impl MetricsCollector {
             routing_updates_sent: AtomicU64::new(0),
             routing_updates_received: AtomicU64::new(0),
             route_changes: AtomicU64::new(0),
+            queries_answered: AtomicU64::new(0),
+            count_to_infinity_detected: AtomicU64::new(0),
+            invalid_next_hops: AtomicU64::new(0),
+            solicited_responses: AtomicU64::new(0),
+            unsolicited_responses: AtomicU64::new(0),
             convergence_start: Mutex::new(None),
             convergence_time: Mutex::new(None),
         }
@@ -99,6 +272,28 @@ impl MetricsCollector {
         }
     }
 
+    fn increment_queries_answered(&self) {
+        self.queries_answered.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn increment_count_to_infinity(&self) {
+        self.count_to_infinity_detected
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn increment_invalid_next_hops(&self) {
+        self.invalid_next_hops.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn increment_responses_received(&self, solicited: bool) {
+        let counter = if solicited {
+            &self.solicited_responses
+        } else {
+            &self.unsolicited_responses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
     fn mark_convergence_complete(&self) {
         let mut start_guard = self.convergence_start.lock().expect("lock poisoned");
         if let Some(start) = *start_guard {
@@ -116,6 +311,11 @@ impl MetricsCollector {
         self.routing_updates_sent.store(0, Ordering::Relaxed);
         self.routing_updates_received.store(0, Ordering::Relaxed);
         self.route_changes.store(0, Ordering::Relaxed);
+        self.queries_answered.store(0, Ordering::Relaxed);
+        self.count_to_infinity_detected.store(0, Ordering::Relaxed);
+        self.invalid_next_hops.store(0, Ordering::Relaxed);
+        self.solicited_responses.store(0, Ordering::Relaxed);
+        self.unsolicited_responses.store(0, Ordering::Relaxed);
         *self.convergence_start.lock().expect("lock poisoned") = None;
         *self.convergence_time.lock().expect("lock poisoned") = None;
     }
@@ -134,6 +334,11 @@ impl MetricsCollector {
             routing_updates_sent: self.routing_updates_sent.load(Ordering::Relaxed),
             routing_updates_received: self.routing_updates_received.load(Ordering::Relaxed),
             route_changes: self.route_changes.load(Ordering::Relaxed),
+            queries_answered: self.queries_answered.load(Ordering::Relaxed),
+            count_to_infinity_detected: self.count_to_infinity_detected.load(Ordering::Relaxed),
+            invalid_next_hops: self.invalid_next_hops.load(Ordering::Relaxed),
+            solicited_responses: self.solicited_responses.load(Ordering::Relaxed),
+            unsolicited_responses: self.unsolicited_responses.load(Ordering::Relaxed),
             convergence_time_seconds: convergence_seconds,
             neighbor_count,
             active_routes,
@@ -145,6 +350,8 @@ impl MetricsCollector {
 #[derive(Debug)]
 struct MetricsInner {
     collector: MetricsCollector,
+    ripng: RipngCollector,
+    packets: PacketStatsCollector,
     route_count: AtomicU64,
     config_version: AtomicU32,
     start_time: Mutex<Instant>,
@@ -161,6 +368,8 @@ impl MetricsInner {
 
     fn reset(&self) {
         self.collector.reset();
+        self.ripng.reset();
+        self.packets.reset();
         self.route_count.store(0, Ordering::Relaxed);
         *self.start_time.lock().expect("lock poisoned") = Instant::now();
     }
@@ -177,6 +386,8 @@ impl Metrics {
         Self {
             inner: Arc::new(MetricsInner {
                 collector: MetricsCollector::new(),
+                ripng: RipngCollector::default(),
+                packets: PacketStatsCollector::default(),
                 route_count: AtomicU64::new(0),
                 config_version: AtomicU32::new(1),
                 start_time: Mutex::new(Instant::now()),
@@ -208,10 +419,82 @@ impl Metrics {
         self.inner.collector.increment_route_changes();
     }
 
+    pub fn increment_queries_answered(&self) {
+        self.inner.collector.increment_queries_answered();
+    }
+
+    pub fn increment_count_to_infinity(&self) {
+        self.inner.collector.increment_count_to_infinity();
+    }
+
+    pub fn increment_invalid_next_hops(&self) {
+        self.inner.collector.increment_invalid_next_hops();
+    }
+
+    /// Count a received response, split by whether it answered our Request
+    pub fn increment_responses_received(&self, solicited: bool) {
+        self.inner.collector.increment_responses_received(solicited);
+    }
+
     pub fn mark_convergence_complete(&self) {
         self.inner.collector.mark_convergence_complete();
     }
 
+    pub fn record_packet_version(&self, version: u8) {
+        self.inner.packets.record_version(version);
+    }
+
+    /// Count a received packet that passed every check, by command
+    pub fn record_packet_accepted(&self, command: RipCommand) {
+        self.inner.packets.record_accepted(command);
+    }
+
+    pub fn record_packet_dropped(&self, drop: PacketDrop) {
+        self.inner.packets.record_dropped(drop);
+    }
+
+    pub fn increment_v6_packets_sent(&self) {
+        self.inner
+            .ripng
+            .packets_sent
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn increment_v6_packets_received(&self) {
+        self.inner
+            .ripng
+            .packets_received
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn increment_v6_packets_dropped(&self) {
+        self.inner
+            .ripng
+            .packets_dropped
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn increment_v6_route_changes(&self) {
+        self.inner
+            .ripng
+            .route_changes
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn update_v6_route_count(&self, route_count: usize) {
+        self.inner
+            .ripng
+            .route_count
+            .store(route_count as u64, Ordering::Relaxed);
+    }
+
+    pub fn update_v6_neighbor_count(&self, neighbor_count: usize) {
+        self.inner
+            .ripng
+            .neighbor_count
+            .store(neighbor_count as u64, Ordering::Relaxed);
+    }
+
     pub fn update_route_count(&self, route_count: usize) {
         self.inner
             .route_count
@@ -236,10 +519,18 @@ impl Metrics {
         snapshot.route_count = self.inner.route_count.load(Ordering::Relaxed);
         snapshot.config_version = self.inner.config_version.load(Ordering::Relaxed);
         snapshot.uptime_seconds = self.uptime_seconds();
+        snapshot.ripng = self.inner.ripng.snapshot();
+        snapshot.packets = self.inner.packets.snapshot();
         snapshot
     }
 }
 
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Performance monitor for recording historical metrics
 #[derive(Debug)]
 pub struct PerformanceMonitor {
@@ -400,6 +691,42 @@ mod tests {
         assert_eq!(snapshot.config_version, 7);
     }
 
+    #[test]
+    fn packets_are_counted_by_type_version_and_drop_reason() {
+        let metrics = Metrics::new();
+        metrics.record_packet_version(1);
+        metrics.record_packet_dropped(PacketDrop::Malformed);
+        for command in [
+            RipCommand::Request,
+            RipCommand::Response,
+            RipCommand::Response,
+        ] {
+            metrics.record_packet_version(2);
+            metrics.record_packet_accepted(command);
+        }
+        metrics.record_packet_version(2);
+        metrics.record_packet_dropped(PacketDrop::Unauthenticated);
+        metrics.record_packet_dropped(PacketDrop::Filtered);
+
+        let packets = metrics.snapshot(0, 0).packets;
+        assert_eq!(
+            packets,
+            PacketStats {
+                requests: 1,
+                responses: 2,
+                version1: 1,
+                version2: 4,
+                malformed: 1,
+                unauthenticated: 1,
+                filtered: 1,
+                ..PacketStats::default()
+            }
+        );
+
+        metrics.reset();
+        assert_eq!(metrics.snapshot(0, 0).packets, PacketStats::default());
+    }
+
     #[test]
     fn packet_loss_rate_calculation() {
         let monitor = PerformanceMonitor::new(Duration::from_secs(1));
@@ -0,0 +1,324 @@
+//! Persistent metrics history.
+//!
+//! Every `metrics.collection_interval` seconds the router's counters are
+//! appended to a log of JSON lines in the router's [`Storage`], so a run can
+//! be exported and analyzed after the fact with `rust-route export-metrics`.
+//! Samples older than `metrics.retention_days` are dropped; the log is
+//! compacted once half of its lines have expired.
+
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+use crate::config_manager::MetricsConfig;
+use crate::metrics::{Metrics, MetricsSnapshot};
+use crate::router::{self, Router};
+use crate::storage::Storage;
+use crate::watchdog::Heartbeat;
+
+/// Most samples kept regardless of retention, about 70 days at the default
+/// interval
+const MAX_SAMPLES: usize = 100_000;
+
+/// Where the history is kept, under `metrics.history`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetricsHistoryConfig {
+    /// Write samples to `path`; without it the history only lives in memory
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Log name in the storage, relative to `storage.path`
+    #[serde(default = "default_path")]
+    pub path: String,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_path() -> String {
+    "metrics.jsonl".to_string()
+}
+
+impl Default for MetricsHistoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            path: default_path(),
+        }
+    }
+}
+
+/// Counters of the router at one point in time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetricsSample {
+    pub timestamp: DateTime<Utc>,
+    pub uptime_seconds: u64,
+    pub packets_sent: u64,
+    pub packets_received: u64,
+    pub packets_dropped: u64,
+    pub routing_updates_sent: u64,
+    pub routing_updates_received: u64,
+    pub route_changes: u64,
+    pub queries_answered: u64,
+    pub count_to_infinity_detected: u64,
+    pub invalid_next_hops: u64,
+    pub solicited_responses: u64,
+    pub unsolicited_responses: u64,
+    #[serde(default)]
+    pub convergence_time_seconds: Option<u64>,
+    pub neighbor_count: usize,
+    pub route_count: u64,
+    pub config_version: u32,
+}
+
+impl MetricsSample {
+    pub fn new(snapshot: &MetricsSnapshot, timestamp: DateTime<Utc>) -> Self {
+        Self {
+            timestamp,
+            uptime_seconds: snapshot.uptime_seconds,
+            packets_sent: snapshot.packets_sent,
+            packets_received: snapshot.packets_received,
+            packets_dropped: snapshot.packets_dropped,
+            routing_updates_sent: snapshot.routing_updates_sent,
+            routing_updates_received: snapshot.routing_updates_received,
+            route_changes: snapshot.route_changes,
+            queries_answered: snapshot.queries_answered,
+            count_to_infinity_detected: snapshot.count_to_infinity_detected,
+            invalid_next_hops: snapshot.invalid_next_hops,
+            solicited_responses: snapshot.solicited_responses,
+            unsolicited_responses: snapshot.unsolicited_responses,
+            convergence_time_seconds: snapshot.convergence_time_seconds,
+            neighbor_count: snapshot.neighbor_count,
+            route_count: snapshot.route_count,
+            config_version: snapshot.config_version,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    samples: VecDeque<MetricsSample>,
+    lines_on_disk: usize,
+    /// The last write failed; further failures are not logged again
+    write_failed: bool,
+}
+
+#[derive(Debug)]
+pub struct MetricsHistory {
+    /// Storage and name of the log the samples are written to
+    storage: Option<(Arc<dyn Storage>, String)>,
+    retention: chrono::Duration,
+    state: Mutex<State>,
+}
+
+impl MetricsHistory {
+    /// Open the history described by `config` in `storage`, loading the
+    /// samples of earlier runs that are still within retention
+    pub fn open(config: &MetricsConfig, storage: Arc<dyn Storage>) -> Self {
+        let mut history = Self::in_memory(config.retention_days);
+        if !config.history.enabled {
+            return history;
+        }
+
+        let path = config.history.path.clone();
+        let (samples, lines_on_disk) = load(storage.as_ref(), &path);
+        if !samples.is_empty() {
+            info!("Loaded {} metrics samples from {}", samples.len(), path);
+        }
+        let state = history.state.get_mut().unwrap_or_else(|e| e.into_inner());
+        state.samples = samples;
+        state.lines_on_disk = lines_on_disk;
+        prune(state, history.retention, Utc::now());
+        history.storage = Some((storage, path));
+        history
+    }
+
+    /// A history that is not written to disk
+    pub fn in_memory(retention_days: u32) -> Self {
+        Self {
+            storage: None,
+            retention: chrono::Duration::days(retention_days.max(1) as i64),
+            state: Mutex::new(State {
+                samples: VecDeque::new(),
+                lines_on_disk: 0,
+                write_failed: false,
+            }),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Store `sample`, dropping the samples that have fallen out of retention
+    pub fn record(&self, sample: MetricsSample) {
+        let mut state = self.state();
+        let now = sample.timestamp;
+        state.samples.push_back(sample);
+        prune(&mut state, self.retention, now);
+
+        let Some((storage, path)) = &self.storage else {
+            return;
+        };
+        let compact = state.lines_on_disk + 1 >= state.samples.len() * 2;
+        let written = if compact {
+            rewrite(storage.as_ref(), path, &state.samples).map(|()| state.samples.len())
+        } else {
+            let sample = state.samples.back().expect("sample was just added");
+            append(storage.as_ref(), path, sample).map(|()| state.lines_on_disk + 1)
+        };
+        match written {
+            Ok(lines) => {
+                state.lines_on_disk = lines;
+                state.write_failed = false;
+            }
+            Err(err) if !state.write_failed => {
+                warn!("Failed to write metrics to {}: {}", path, err);
+                state.write_failed = true;
+            }
+            Err(_) => {}
+        }
+    }
+
+    /// Samples taken from `from` up to and including `to`, oldest first
+    pub fn between(
+        &self,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Vec<MetricsSample> {
+        self.state()
+            .samples
+            .iter()
+            .filter(|sample| {
+                from.is_none_or(|from| sample.timestamp >= from)
+                    && to.is_none_or(|to| sample.timestamp <= to)
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+fn prune(state: &mut State, retention: chrono::Duration, now: DateTime<Utc>) {
+    let oldest = now - retention;
+    while state
+        .samples
+        .front()
+        .is_some_and(|sample| sample.timestamp < oldest)
+        || state.samples.len() > MAX_SAMPLES
+    {
+        state.samples.pop_front();
+    }
+}
+
+fn load(storage: &dyn Storage, path: &str) -> (VecDeque<MetricsSample>, usize) {
+    let lines = match storage.read_log(path) {
+        Ok(lines) => lines,
+        Err(err) => {
+            warn!("Failed to read {}: {}", path, err);
+            return Default::default();
+        }
+    };
+
+    let mut samples = VecDeque::new();
+    for line in &lines {
+        match serde_json::from_str::<MetricsSample>(line) {
+            Ok(sample) => samples.push_back(sample),
+            Err(err) => warn!("Skipping unreadable line in {}: {}", path, err),
+        }
+    }
+    (samples, lines.len())
+}
+
+fn append(storage: &dyn Storage, path: &str, sample: &MetricsSample) -> std::io::Result<()> {
+    storage.append(path, &serde_json::to_string(sample)?)
+}
+
+fn rewrite(
+    storage: &dyn Storage,
+    path: &str,
+    samples: &VecDeque<MetricsSample>,
+) -> std::io::Result<()> {
+    let lines = samples
+        .iter()
+        .map(serde_json::to_string)
+        .collect::<Result<Vec<_>, _>>()?;
+    storage.rewrite_log(path, &lines)
+}
+
+/// Sample the router's counters into `history` until the task is aborted
+pub async fn run(
+    history: Arc<MetricsHistory>,
+    router: Arc<RwLock<Router>>,
+    metrics: Metrics,
+    heartbeat: Heartbeat,
+) {
+    loop {
+        let (enabled, interval, neighbors, routing_table) = {
+            let router = router.read().await;
+            let config = &router.config().metrics;
+            (
+                config.enabled,
+                config.collection_interval.max(1),
+                router.neighbors(),
+                router.routing_table(),
+            )
+        };
+        router::pause(Duration::from_secs(interval), &heartbeat).await;
+        if !enabled {
+            continue;
+        }
+
+        let neighbor_count = neighbors.read().await.len();
+        let route_count = routing_table.read().await.route_count();
+        let snapshot = metrics.snapshot(neighbor_count, route_count);
+        history.record(MetricsSample::new(&snapshot, Utc::now()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_manager::RouterConfig;
+    use crate::storage::FileStorage;
+    use chrono::Duration;
+
+    fn sample(at: DateTime<Utc>, packets_sent: u64) -> MetricsSample {
+        let snapshot = MetricsSnapshot {
+            packets_sent,
+            ..MetricsSnapshot::default()
+        };
+        MetricsSample::new(&snapshot, at)
+    }
+
+    #[test]
+    fn samples_survive_reopening_within_retention() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = RouterConfig::default().metrics;
+        config.retention_days = 1;
+        let storage: Arc<dyn Storage> = Arc::new(FileStorage::new(dir.path()));
+
+        let now = Utc::now();
+        let history = MetricsHistory::open(&config, Arc::clone(&storage));
+        history.record(sample(now - Duration::days(2), 1));
+        history.record(sample(now - Duration::hours(2), 2));
+        history.record(sample(now - Duration::hours(1), 3));
+        history.record(sample(now, 4));
+        // The first sample expired as soon as the newer ones arrived
+        assert_eq!(history.between(None, None).len(), 3);
+
+        assert!(dir.path().join("metrics.jsonl").exists());
+        let reopened = MetricsHistory::open(&config, storage);
+        let sent: Vec<u64> = reopened
+            .between(Some(now - Duration::minutes(90)), Some(now))
+            .iter()
+            .map(|sample| sample.packets_sent)
+            .collect();
+        assert_eq!(sent, vec![3, 4]);
+    }
+}
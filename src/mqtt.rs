@@ -0,0 +1,292 @@
+//! MQTT telemetry publisher.
+//!
+//! Forwards metrics snapshots and route changes from the event bus, and
+//! periodically publishes the neighbor table, to a broker for deployments
+//! where MQTT is the telemetry bus.
+
+use log::{debug, info, warn};
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS, TlsConfiguration, Transport};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::RwLock;
+
+use crate::events::{EventBus, WebEvent};
+use crate::router::{Router, RECEIVE_HEARTBEAT};
+use crate::watchdog::Heartbeat;
+use crate::{RustRouteError, RustRouteResult};
+
+const REQUEST_QUEUE: usize = 64;
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// MQTT publisher configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MqttConfig {
+    pub enabled: bool,
+    pub broker_host: String,
+    pub broker_port: u16,
+    /// Defaults to `rust-route-<router_id>`
+    #[serde(default)]
+    pub client_id: Option<String>,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// 0 (at most once), 1 (at least once) or 2 (exactly once)
+    pub qos: u8,
+    pub keep_alive_secs: u64,
+    /// Seconds between neighbor table publications
+    pub neighbor_interval: u64,
+    #[serde(default)]
+    pub topics: MqttTopics,
+    #[serde(default)]
+    pub tls: MqttTlsConfig,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            broker_host: "localhost".to_string(),
+            broker_port: 1883,
+            client_id: None,
+            username: None,
+            password: None,
+            qos: 0,
+            keep_alive_secs: 30,
+            neighbor_interval: 30,
+            topics: MqttTopics::default(),
+            tls: MqttTlsConfig::default(),
+        }
+    }
+}
+
+/// Topic templates; `{router_id}` is replaced with the router ID
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MqttTopics {
+    pub metrics: String,
+    pub routes: String,
+    pub neighbors: String,
+}
+
+impl Default for MqttTopics {
+    fn default() -> Self {
+        Self {
+            metrics: "rust-route/{router_id}/metrics".to_string(),
+            routes: "rust-route/{router_id}/routes".to_string(),
+            neighbors: "rust-route/{router_id}/neighbors".to_string(),
+        }
+    }
+}
+
+/// TLS settings; without `ca_file` the platform trust store is used
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct MqttTlsConfig {
+    pub enabled: bool,
+    #[serde(default)]
+    pub ca_file: Option<String>,
+    #[serde(default)]
+    pub client_cert_file: Option<String>,
+    #[serde(default)]
+    pub client_key_file: Option<String>,
+}
+
+impl MqttConfig {
+    pub fn qos_level(&self) -> QoS {
+        match self.qos {
+            0 => QoS::AtMostOnce,
+            1 => QoS::AtLeastOnce,
+            _ => QoS::ExactlyOnce,
+        }
+    }
+
+    /// Build connection options, reading TLS material from disk
+    pub fn options(&self, router_id: &str) -> RustRouteResult<MqttOptions> {
+        let client_id = self
+            .client_id
+            .clone()
+            .unwrap_or_else(|| format!("rust-route-{}", router_id));
+        let mut options = MqttOptions::new(client_id, &self.broker_host, self.broker_port);
+        options.set_keep_alive(Duration::from_secs(self.keep_alive_secs.max(5)));
+
+        if let Some(username) = &self.username {
+            options.set_credentials(username, self.password.clone().unwrap_or_default());
+        }
+
+        if self.tls.enabled {
+            let read = |path: &str| {
+                std::fs::read(path).map_err(|e| {
+                    RustRouteError::ConfigError(format!("Failed to read {}: {}", path, e))
+                })
+            };
+            let client_auth = match (&self.tls.client_cert_file, &self.tls.client_key_file) {
+                (Some(cert), Some(key)) => Some((read(cert)?, read(key)?)),
+                _ => None,
+            };
+            let tls = match &self.tls.ca_file {
+                Some(ca) => TlsConfiguration::Simple {
+                    ca: read(ca)?,
+                    alpn: None,
+                    client_auth,
+                },
+                None => TlsConfiguration::default(),
+            };
+            options.set_transport(Transport::tls_with_config(tls));
+        }
+
+        Ok(options)
+    }
+}
+
+/// Expand a topic template for this router
+pub fn topic(template: &str, router_id: &str) -> String {
+    template.replace("{router_id}", router_id)
+}
+
+#[derive(Debug, Serialize)]
+struct NeighborState {
+    address: IpAddr,
+    interface: Option<String>,
+    last_seen_seconds: u64,
+    learned_routes: usize,
+}
+
+/// Publish telemetry until the task is aborted. Broker outages are retried
+/// by the MQTT event loop; messages produced meanwhile are dropped once the
+/// request queue is full.
+pub async fn run(
+    config: MqttConfig,
+    router: Arc<RwLock<Router>>,
+    events: EventBus,
+    heartbeat: Heartbeat,
+) {
+    let router_id = router.read().await.router_id().to_string();
+    let options = match config.options(&router_id) {
+        Ok(options) => options,
+        Err(err) => {
+            warn!("MQTT publisher disabled: {}", err);
+            return;
+        }
+    };
+
+    let (client, mut eventloop) = AsyncClient::new(options, REQUEST_QUEUE);
+    let qos = config.qos_level();
+    let metrics_topic = topic(&config.topics.metrics, &router_id);
+    let routes_topic = topic(&config.topics.routes, &router_id);
+    let neighbors_topic = topic(&config.topics.neighbors, &router_id);
+
+    let mut receiver = events.subscribe("mqtt");
+    let mut neighbor_timer =
+        tokio::time::interval(Duration::from_secs(config.neighbor_interval.max(1)));
+    let mut liveness = tokio::time::interval(RECEIVE_HEARTBEAT);
+
+    let publish = |topic: &str, retain: bool, payload: Vec<u8>| {
+        if let Err(err) = client.try_publish(topic, qos, retain, payload) {
+            debug!("Dropping MQTT message for {}: {}", topic, err);
+        }
+    };
+
+    loop {
+        tokio::select! {
+            _ = liveness.tick() => heartbeat.beat(),
+            polled = eventloop.poll() => match polled {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    info!("MQTT connected to {}:{}", config.broker_host, config.broker_port);
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    warn!("MQTT connection error: {}; retrying", err);
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                }
+            },
+            event = receiver.recv() => match event {
+                Ok(WebEvent::Metrics(event)) => {
+                    if let Ok(payload) = serde_json::to_vec(&event.snapshot) {
+                        publish(&metrics_topic, true, payload);
+                    }
+                }
+                Ok(WebEvent::Route(event)) | Ok(WebEvent::RouteWithdrawn(event)) => {
+                    if let Ok(payload) = serde_json::to_vec(&event) {
+                        publish(&routes_topic, false, payload);
+                    }
+                }
+                Ok(WebEvent::RouteV6(event)) | Ok(WebEvent::RouteV6Withdrawn(event)) => {
+                    if let Ok(payload) = serde_json::to_vec(&event) {
+                        publish(&routes_topic, false, payload);
+                    }
+                }
+                Ok(WebEvent::Neighbor(_))
+                | Ok(WebEvent::CountToInfinity(_))
+                | Ok(WebEvent::Activity(_))
+                | Ok(WebEvent::TopologyChanged(_)) => {}
+                Err(RecvError::Lagged(skipped)) => {
+                    debug!("MQTT publisher skipped {} events", skipped);
+                }
+                Err(RecvError::Closed) => return,
+            },
+            _ = neighbor_timer.tick() => {
+                let neighbors = router.read().await.neighbors();
+                let mut table: Vec<NeighborState> = neighbors
+                    .read()
+                    .await
+                    .values()
+                    .map(|info| NeighborState {
+                        address: info.address,
+                        interface: info.interface.clone(),
+                        last_seen_seconds: info.last_seen.elapsed().as_secs(),
+                        learned_routes: info.learned_routes,
+                    })
+                    .collect();
+                table.sort_by_key(|entry| entry.address);
+                if let Ok(payload) = serde_json::to_vec(&table) {
+                    publish(&neighbors_topic, true, payload);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn topics_expand_router_id() {
+        let topics = MqttTopics::default();
+        assert_eq!(
+            topic(&topics.metrics, "10.0.0.1"),
+            "rust-route/10.0.0.1/metrics"
+        );
+        assert_eq!(topic("fixed/topic", "10.0.0.1"), "fixed/topic");
+    }
+
+    #[test]
+    fn options_reflect_config() {
+        let config = MqttConfig {
+            username: Some("telemetry".to_string()),
+            password: Some("secret".to_string()),
+            qos: 1,
+            ..MqttConfig::default()
+        };
+        let options = config.options("10.0.0.1").unwrap();
+        assert_eq!(options.client_id(), "rust-route-10.0.0.1");
+        assert_eq!(options.broker_address(), ("localhost".to_string(), 1883));
+        assert_eq!(
+            options.credentials(),
+            Some(("telemetry".to_string(), "secret".to_string()))
+        );
+        assert_eq!(config.qos_level(), QoS::AtLeastOnce);
+
+        let missing_ca = MqttConfig {
+            tls: MqttTlsConfig {
+                enabled: true,
+                ca_file: Some("/nonexistent/ca.pem".to_string()),
+                ..MqttTlsConfig::default()
+            },
+            ..MqttConfig::default()
+        };
+        assert!(missing_ca.options("10.0.0.1").is_err());
+    }
+}
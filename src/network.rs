@@ -1,11 +1,117 @@
 //! Network interface and communication handling for RustRoute
 
-use crate::protocol::RipPacket;
+use crate::last_error;
+use crate::metrics::{Metrics, PacketDrop, PacketStats, PacketStatsCollector};
+use crate::packet_auth::{AuthAlert, PacketAuth};
+use crate::protocol::{
+    PacketBuilder, PacketCodec, RipCommand, RipPacket, DEFAULT_INFINITY, MAX_ENTRIES,
+};
+use crate::rip_auth::{InterfaceAuthConfig, KeyChain};
 use crate::{RustRouteError, RustRouteResult};
+use chrono::{DateTime, Utc};
+use ipnet::Ipv4Net;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::task::Poll;
+use std::time::{Duration, Instant};
 use tokio::net::UdpSocket as TokioUdpSocket;
 
+/// IPv4 and UDP header bytes in every datagram
+const IP_UDP_HEADERS: usize = 28;
+/// Room left in JSON packets for the signature when signing is enabled
+const SIGNATURE_RESERVE: usize = 128;
+/// After sending a Request, the first response from each neighbor within
+/// this window counts as solicited
+pub const SOLICITATION_WINDOW: Duration = Duration::from_secs(5);
+
+/// Where an interface sends its updates. Point-to-point links send to their
+/// peer in either group mode.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SendMode {
+    /// The subnet broadcast address, for neighbors that only listen to
+    /// broadcasts such as RIPv1-style implementations
+    Broadcast,
+    /// The interface's multicast group, 224.0.0.9 for RIPv2
+    #[default]
+    Multicast,
+    /// Each listed neighbor, for segments that carry no broadcast
+    Unicast(Vec<Ipv4Addr>),
+}
+
+/// Which senders an interface accepts packets from
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SenderFilter {
+    pub accept_from: Vec<Ipv4Net>,
+    pub reject_from: Vec<Ipv4Net>,
+}
+
+impl SenderFilter {
+    /// Rejections win; a non-empty accept list admits only the senders it covers
+    pub fn permits(&self, sender: IpAddr) -> bool {
+        let IpAddr::V4(sender) = sender else {
+            return self.accept_from.is_empty();
+        };
+        if self.reject_from.iter().any(|net| net.contains(&sender)) {
+            return false;
+        }
+        self.accept_from.is_empty() || self.accept_from.iter().any(|net| net.contains(&sender))
+    }
+}
+
+/// Send budget of an interface, so large tables go out spread over time
+/// instead of in one burst; a rate of 0 leaves that dimension unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PacingConfig {
+    #[serde(default)]
+    pub packets_per_second: u32,
+    /// Counted over the UDP payload
+    #[serde(default)]
+    pub bytes_per_second: u64,
+}
+
+impl PacingConfig {
+    pub fn is_limited(&self) -> bool {
+        self.packets_per_second > 0 || self.bytes_per_second > 0
+    }
+
+    /// Time one datagram of `bytes` uses up of the budget
+    fn cost(&self, bytes: usize) -> Duration {
+        let per_packet = match self.packets_per_second {
+            0 => Duration::ZERO,
+            rate => Duration::from_secs_f64(1.0 / f64::from(rate)),
+        };
+        let per_byte = match self.bytes_per_second {
+            0 => Duration::ZERO,
+            rate => Duration::from_secs_f64(bytes as f64 / rate as f64),
+        };
+        per_packet.max(per_byte)
+    }
+}
+
+/// Spaces datagrams by the cost of the ones before them. A datagram after
+/// an idle spell goes out at once.
+#[derive(Debug, Default)]
+struct Pacer {
+    config: PacingConfig,
+    next_send: Option<Instant>,
+}
+
+impl Pacer {
+    /// Book a datagram of `bytes` and return how long to wait before sending it
+    fn reserve(&mut self, now: Instant, bytes: usize) -> Duration {
+        if !self.config.is_limited() {
+            return Duration::ZERO;
+        }
+        let start = self.next_send.filter(|next| *next > now).unwrap_or(now);
+        self.next_send = Some(start + self.config.cost(bytes));
+        start - now
+    }
+}
+
 /// Network interface configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InterfaceConfig {
@@ -16,6 +122,17 @@ pub struct InterfaceConfig {
     pub port: u16,
     pub mtu: u16,
     pub enabled: bool,
+    /// Send updates by unicast to `peer` instead of broadcasting, and
+    /// advertise ourselves as the next hop
+    pub point_to_point: bool,
+    pub peer: Option<Ipv4Addr>,
+    /// Encoding for broadcasts and neighbors whose encoding is not yet known
+    pub codec: PacketCodec,
+    pub send_mode: SendMode,
+    /// Further addresses on the link, each with its own socket
+    pub secondary_addresses: Vec<Ipv4Net>,
+    /// Received packets with a greater metric are rejected
+    pub infinity_metric: u32,
 }
 
 impl Default for InterfaceConfig {
@@ -28,6 +145,12 @@ impl Default for InterfaceConfig {
             port: 520,                                      // Standard RIP port
             mtu: 1500,
             enabled: true,
+            point_to_point: false,
+            peer: None,
+            codec: PacketCodec::default(),
+            send_mode: SendMode::default(),
+            secondary_addresses: Vec::new(),
+            infinity_metric: DEFAULT_INFINITY,
         }
     }
 }
@@ -36,21 +159,441 @@ impl Default for InterfaceConfig {
 #[derive(Debug)]
 pub struct NetworkInterface {
     pub config: InterfaceConfig,
-    socket: Option<TokioUdpSocket>,
+    /// Address and mask the socket is bound to; starts out as configured
+    /// and follows the OS when the interface is renumbered
+    address: RwLock<(Ipv4Addr, Ipv4Addr)>,
+    socket: RwLock<Option<Arc<TokioUdpSocket>>>,
+    secondary_sockets: Vec<(Ipv4Net, Arc<TokioUdpSocket>)>,
+    /// Bound to the multicast group and joined on this interface only
+    group_socket: RwLock<Option<Arc<TokioUdpSocket>>>,
+    admin_up: AtomicBool,
+    link_up: AtomicBool,
+    updates_sent: AtomicU64,
+    bad_packets: AtomicU64,
+    sender_filter: Mutex<SenderFilter>,
+    rejected_packets: AtomicU64,
+    rejected_senders: Mutex<HashSet<IpAddr>>,
+    json_packets: AtomicU64,
+    binary_packets: AtomicU64,
+    packet_stats: PacketStatsCollector,
+    /// Router-wide counters the packet statistics are added to as well
+    metrics: Option<Metrics>,
+    neighbor_codecs: Mutex<HashMap<IpAddr, PacketCodec>>,
+    solicitation: Mutex<Option<Solicitation>>,
+    auth: Option<Arc<PacketAuth>>,
+    /// Authenticates binary packets when `rip.authentication` lists the interface
+    key_chain: KeyChain,
+    datagrams_sent: AtomicU64,
+    bytes_sent: AtomicU64,
+    send_errors: AtomicU64,
+    datagrams_received: AtomicU64,
+    bytes_received: AtomicU64,
+    traffic_times: Mutex<TrafficTimes>,
+    pacer: Mutex<Pacer>,
+    pacing_delay_ms: AtomicU64,
+}
+
+/// When the interface last sent and received, and when its counters were
+/// last cleared
+#[derive(Debug, Default)]
+struct TrafficTimes {
+    last_sent: Option<DateTime<Utc>>,
+    last_received: Option<DateTime<Utc>>,
+    cleared_at: Option<DateTime<Utc>>,
+}
+
+/// RIP traffic counted by the interface itself since start or the last clear,
+/// as opposed to the kernel's totals for the whole device
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InterfaceCounters {
+    pub datagrams_sent: u64,
+    pub bytes_sent: u64,
+    pub send_errors: u64,
+    pub updates_sent: u64,
+    /// Datagrams read from the sockets, before decoding
+    pub datagrams_received: u64,
+    pub bytes_received: u64,
+    pub json_packets: u64,
+    pub binary_packets: u64,
+    pub bad_packets: u64,
+    pub rejected_packets: u64,
+    /// Received packets by type and version, and why others were dropped
+    #[serde(default)]
+    pub packets: PacketStats,
+    /// Time sends waited for the pacing budget
+    pub pacing_delay_ms: u64,
+    pub last_sent: Option<DateTime<Utc>>,
+    pub last_received: Option<DateTime<Utc>>,
+    pub cleared_at: Option<DateTime<Utc>>,
+}
+
+/// The last Request sent and the neighbors that have answered it
+#[derive(Debug)]
+struct Solicitation {
+    sent_at: Instant,
+    answered: HashSet<IpAddr>,
+}
+
+/// Deliver only the groups joined on `socket` itself. Linux otherwise hands
+/// a socket bound to a group the datagrams of every interface on which any
+/// socket joined it, so each interface would also hear the other links.
+#[cfg(target_os = "linux")]
+fn only_joined_groups(socket: &socket2::Socket) -> std::io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let disabled: libc::c_int = 0;
+    // SAFETY: the option value is a live c_int and its size is passed along
+    let result = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::IPPROTO_IP,
+            libc::IP_MULTICAST_ALL,
+            &disabled as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+/// `IP_MULTICAST_ALL` is specific to Linux
+#[cfg(not(target_os = "linux"))]
+fn only_joined_groups(_socket: &socket2::Socket) -> std::io::Result<()> {
+    Ok(())
 }
 
 impl NetworkInterface {
     /// Create a new network interface
     pub fn new(config: InterfaceConfig) -> Self {
+        let admin_up = AtomicBool::new(config.enabled);
+        let address = RwLock::new((config.ip_address, config.subnet_mask));
         Self {
             config,
-            socket: None,
+            address,
+            socket: RwLock::new(None),
+            secondary_sockets: Vec::new(),
+            group_socket: RwLock::new(None),
+            admin_up,
+            link_up: AtomicBool::new(true),
+            updates_sent: AtomicU64::new(0),
+            bad_packets: AtomicU64::new(0),
+            sender_filter: Mutex::new(SenderFilter::default()),
+            rejected_packets: AtomicU64::new(0),
+            rejected_senders: Mutex::new(HashSet::new()),
+            json_packets: AtomicU64::new(0),
+            binary_packets: AtomicU64::new(0),
+            packet_stats: PacketStatsCollector::default(),
+            metrics: None,
+            neighbor_codecs: Mutex::new(HashMap::new()),
+            solicitation: Mutex::new(None),
+            auth: None,
+            key_chain: KeyChain::default(),
+            datagrams_sent: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+            send_errors: AtomicU64::new(0),
+            datagrams_received: AtomicU64::new(0),
+            bytes_received: AtomicU64::new(0),
+            traffic_times: Mutex::new(TrafficTimes::default()),
+            pacer: Mutex::new(Pacer::default()),
+            pacing_delay_ms: AtomicU64::new(0),
         }
     }
 
-    /// Initialize the network interface
-    pub async fn initialize(&mut self) -> RustRouteResult<()> {
-        let bind_addr = SocketAddr::new(IpAddr::V4(self.config.ip_address), self.config.port);
+    /// Sign outgoing packets and verify incoming ones with `auth`
+    pub fn with_auth(mut self, auth: Arc<PacketAuth>) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    /// Add the packet statistics of the interface to `metrics` as well
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Authentication thresholds crossed by senders since the last call
+    pub fn take_auth_alerts(&self) -> Vec<AuthAlert> {
+        self.auth
+            .as_ref()
+            .map(|auth| auth.take_alerts())
+            .unwrap_or_default()
+    }
+
+    /// Count a routing update sent out of this interface
+    pub fn record_update_sent(&self) {
+        self.updates_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a received packet that could not be decoded or failed validation
+    pub fn record_bad_packet(&self) {
+        self.bad_packets.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_dropped(&self, drop: PacketDrop) {
+        self.packet_stats.record_dropped(drop);
+        if let Some(metrics) = &self.metrics {
+            metrics.record_packet_dropped(drop);
+        }
+    }
+
+    /// Count a received packet that passed every check, the sender filter
+    /// included
+    pub fn record_packet_accepted(&self, command: RipCommand) {
+        self.packet_stats.record_accepted(command);
+        if let Some(metrics) = &self.metrics {
+            metrics.record_packet_accepted(command);
+        }
+    }
+
+    pub fn packet_stats(&self) -> PacketStats {
+        self.packet_stats.snapshot()
+    }
+
+    /// Note that a whole-table Request went out of this interface
+    pub fn record_request_sent(&self) {
+        *self
+            .solicitation
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(Solicitation {
+            sent_at: Instant::now(),
+            answered: HashSet::new(),
+        });
+    }
+
+    /// Whether a response from `sender` answers the last Request, counting
+    /// only its first response within [`SOLICITATION_WINDOW`]
+    pub fn take_solicited(&self, sender: IpAddr) -> bool {
+        let mut solicitation = self
+            .solicitation
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        match solicitation.as_mut() {
+            Some(pending) if pending.sent_at.elapsed() <= SOLICITATION_WINDOW => {
+                pending.answered.insert(sender)
+            }
+            _ => false,
+        }
+    }
+
+    pub fn updates_sent(&self) -> u64 {
+        self.updates_sent.load(Ordering::Relaxed)
+    }
+
+    pub fn bad_packets(&self) -> u64 {
+        self.bad_packets.load(Ordering::Relaxed)
+    }
+
+    /// Packets received in each encoding
+    pub fn packets_received(&self, codec: PacketCodec) -> u64 {
+        match codec {
+            PacketCodec::Json => self.json_packets.load(Ordering::Relaxed),
+            PacketCodec::Binary => self.binary_packets.load(Ordering::Relaxed),
+        }
+    }
+
+    fn traffic_times(&self) -> std::sync::MutexGuard<'_, TrafficTimes> {
+        self.traffic_times
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn counters(&self) -> InterfaceCounters {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        let times = self.traffic_times();
+        InterfaceCounters {
+            datagrams_sent: load(&self.datagrams_sent),
+            bytes_sent: load(&self.bytes_sent),
+            send_errors: load(&self.send_errors),
+            updates_sent: load(&self.updates_sent),
+            datagrams_received: load(&self.datagrams_received),
+            bytes_received: load(&self.bytes_received),
+            json_packets: load(&self.json_packets),
+            binary_packets: load(&self.binary_packets),
+            bad_packets: load(&self.bad_packets),
+            rejected_packets: load(&self.rejected_packets),
+            packets: self.packet_stats.snapshot(),
+            pacing_delay_ms: load(&self.pacing_delay_ms),
+            last_sent: times.last_sent,
+            last_received: times.last_received,
+            cleared_at: times.cleared_at,
+        }
+    }
+
+    /// Reset every counter of the interface, including those SNMP and gNMI
+    /// report, and forget the last traffic times
+    pub fn clear_counters(&self) {
+        for counter in [
+            &self.datagrams_sent,
+            &self.bytes_sent,
+            &self.send_errors,
+            &self.updates_sent,
+            &self.datagrams_received,
+            &self.bytes_received,
+            &self.json_packets,
+            &self.binary_packets,
+            &self.bad_packets,
+            &self.rejected_packets,
+            &self.pacing_delay_ms,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+        self.packet_stats.reset();
+        *self.traffic_times() = TrafficTimes {
+            cleared_at: Some(Utc::now()),
+            ..TrafficTimes::default()
+        };
+    }
+
+    /// Whether the interface has a bound socket
+    pub fn is_bound(&self) -> bool {
+        self.socket().is_ok()
+    }
+
+    /// Encoding last heard from `neighbor`, used when replying to it
+    pub fn neighbor_codec(&self, neighbor: IpAddr) -> Option<PacketCodec> {
+        self.codecs().get(&neighbor).copied()
+    }
+
+    fn codecs(&self) -> std::sync::MutexGuard<'_, HashMap<IpAddr, PacketCodec>> {
+        self.neighbor_codecs
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Whether the interface is administratively enabled for RIP exchange
+    pub fn is_admin_up(&self) -> bool {
+        self.admin_up.load(Ordering::Relaxed)
+    }
+
+    /// Administratively enable or disable the interface without closing its socket.
+    /// Returns the previous state.
+    pub fn set_admin_up(&self, up: bool) -> bool {
+        self.admin_up.swap(up, Ordering::Relaxed)
+    }
+
+    /// Replace the accept and reject lists, forgetting previously rejected senders
+    pub fn set_sender_filter(&self, filter: SenderFilter) {
+        *self
+            .sender_filter
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = filter;
+        self.rejected_senders
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clear();
+    }
+
+    /// Replace the key chain binary packets are authenticated with
+    pub fn set_key_chain(&self, config: Option<InterfaceAuthConfig>) {
+        self.key_chain.configure(config);
+    }
+
+    pub fn set_pacing(&self, pacing: PacingConfig) {
+        self.pacer
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .config = pacing;
+    }
+
+    /// Wait until the pacing budget allows a datagram of `bytes`
+    async fn pace(&self, bytes: usize) {
+        let delay = self
+            .pacer
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .reserve(Instant::now(), bytes);
+        if !delay.is_zero() {
+            self.pacing_delay_ms
+                .fetch_add(delay.as_millis() as u64, Ordering::Relaxed);
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Whether packets from `sender` may be processed on this interface
+    pub fn permits_sender(&self, sender: IpAddr) -> bool {
+        self.sender_filter
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .permits(sender)
+    }
+
+    /// Count a packet refused by the sender filter. Returns whether it is the
+    /// first one from `sender`.
+    pub fn record_rejected_sender(&self, sender: IpAddr) -> bool {
+        self.rejected_packets.fetch_add(1, Ordering::Relaxed);
+        self.record_dropped(PacketDrop::Filtered);
+        self.rejected_senders
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .insert(sender)
+    }
+
+    /// Packets refused by the sender filter
+    pub fn rejected_packets(&self) -> u64 {
+        self.rejected_packets.load(Ordering::Relaxed)
+    }
+
+    /// Whether the link under the interface was last seen up
+    pub fn is_link_up(&self) -> bool {
+        self.link_up.load(Ordering::Relaxed)
+    }
+
+    /// Record the operational state of the link. Returns the previous state.
+    pub fn set_link_up(&self, up: bool) -> bool {
+        self.link_up.swap(up, Ordering::Relaxed)
+    }
+
+    /// Administratively enabled with the link up
+    pub fn is_up(&self) -> bool {
+        self.is_admin_up() && self.is_link_up()
+    }
+
+    /// Address the socket is bound to
+    pub fn ip_address(&self) -> Ipv4Addr {
+        self.address
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .0
+    }
+
+    pub fn subnet_mask(&self) -> Ipv4Addr {
+        self.address
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .1
+    }
+
+    fn socket(&self) -> RustRouteResult<Arc<TokioUdpSocket>> {
+        self.socket
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+            .ok_or_else(|| RustRouteError::NetworkError("Interface not initialized".to_string()))
+    }
+
+    /// Socket whose address shares a subnet with `destination`, so replies
+    /// and broadcasts carry the source address neighbors there expect
+    fn socket_for(&self, destination: IpAddr) -> RustRouteResult<Arc<TokioUdpSocket>> {
+        if let IpAddr::V4(destination) = destination {
+            let primary = utils::in_same_subnet(self.ip_address(), destination, self.subnet_mask());
+            if !primary {
+                if let Some((_, socket)) = self
+                    .secondary_sockets
+                    .iter()
+                    .find(|(net, _)| net.contains(&destination))
+                {
+                    return Ok(Arc::clone(socket));
+                }
+            }
+        }
+        self.socket()
+    }
+
+    async fn bind_socket(&self, ip_address: Ipv4Addr) -> RustRouteResult<TokioUdpSocket> {
+        let bind_addr = SocketAddr::new(IpAddr::V4(ip_address), self.config.port);
 
         let socket = TokioUdpSocket::bind(bind_addr)
             .await
@@ -60,104 +603,428 @@ impl NetworkInterface {
         socket
             .set_broadcast(true)
             .map_err(|e| RustRouteError::NetworkError(format!("Failed to set broadcast: {}", e)))?;
+        if self.config.send_mode == SendMode::Multicast {
+            // Keep updates on the link and leave through this interface,
+            // without looping them back to our own group socket
+            socket.set_multicast_ttl_v4(1).map_err(|e| {
+                RustRouteError::NetworkError(format!("Failed to set multicast TTL: {}", e))
+            })?;
+            socket.set_multicast_loop_v4(false).map_err(|e| {
+                RustRouteError::NetworkError(format!("Failed to disable multicast loop: {}", e))
+            })?;
+            socket2::SockRef::from(&socket)
+                .set_multicast_if_v4(&ip_address)
+                .map_err(|e| {
+                    RustRouteError::NetworkError(format!(
+                        "Failed to set multicast interface: {}",
+                        e
+                    ))
+                })?;
+        }
+        Ok(socket)
+    }
+
+    /// Whether the interface listens to its multicast group; every
+    /// interface does except those reaching neighbors only by unicast
+    fn joins_group(&self) -> bool {
+        !self.config.point_to_point && !matches!(self.config.send_mode, SendMode::Unicast(_))
+    }
 
-        self.socket = Some(socket);
+    /// Socket receiving the multicast group on the interface at `ip_address`.
+    /// A socket bound to a unicast address never sees datagrams sent to the
+    /// group, so this one is bound to the group itself and shares the port
+    /// with the group sockets of the other interfaces.
+    fn bind_group_socket(&self, ip_address: Ipv4Addr) -> std::io::Result<TokioUdpSocket> {
+        use socket2::{Domain, Protocol, Socket, Type};
+
+        let group = self.config.multicast_address;
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+        socket.set_reuse_address(true)?;
+        socket.set_nonblocking(true)?;
+        socket.bind(&SocketAddr::new(IpAddr::V4(group), self.config.port).into())?;
+        socket.join_multicast_v4(&group, &ip_address)?;
+        only_joined_groups(&socket)?;
+        TokioUdpSocket::from_std(socket.into())
+    }
+
+    /// Join the multicast group on `ip_address`, replacing the membership of
+    /// a previous address. Without it the interface still sends, and hears
+    /// neighbors that unicast or broadcast.
+    fn join_group(&self, ip_address: Ipv4Addr) {
+        if !self.joins_group() {
+            return;
+        }
+        let socket = match self.bind_group_socket(ip_address) {
+            Ok(socket) => Some(Arc::new(socket)),
+            Err(err) => {
+                let message = format!(
+                    "Interface {} could not join {} on {}: {}",
+                    self.config.name, self.config.multicast_address, ip_address, err
+                );
+                log::warn!("{}", message);
+                last_error::record(&last_error::interface(&self.config.name), message);
+                None
+            }
+        };
+        *self
+            .group_socket
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = socket;
+    }
+
+    fn group_socket(&self) -> Option<Arc<TokioUdpSocket>> {
+        self.group_socket
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// Initialize the network interface
+    pub async fn initialize(&mut self) -> RustRouteResult<()> {
+        let ip_address = self.ip_address();
+        let socket = self.bind_socket(ip_address).await?;
+        *self
+            .socket
+            .get_mut()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(Arc::new(socket));
+
+        let mut secondary_sockets = Vec::new();
+        for net in &self.config.secondary_addresses {
+            let socket = self.bind_socket(net.addr()).await?;
+            secondary_sockets.push((*net, Arc::new(socket)));
+        }
+        self.secondary_sockets = secondary_sockets;
+        self.join_group(ip_address);
 
         log::info!(
             "Network interface {} initialized on {}:{}",
             self.config.name,
-            self.config.ip_address,
+            ip_address,
             self.config.port
         );
+        if !self.secondary_sockets.is_empty() {
+            log::info!(
+                "Network interface {} also listening on {:?}",
+                self.config.name,
+                self.config.secondary_addresses
+            );
+        }
 
         Ok(())
     }
 
-    /// Send a RIPER packet
-    pub async fn send_packet(&self, packet: &RipPacket) -> RustRouteResult<()> {
-        let socket = self
+    /// Move the socket to a new address, such as after DHCP renumbered the
+    /// interface. The old socket stays in place when the new address cannot
+    /// be bound; a receive already waiting on it finishes on the old socket.
+    pub async fn rebind(&self, ip_address: Ipv4Addr, subnet_mask: Ipv4Addr) -> RustRouteResult<()> {
+        let socket = self.bind_socket(ip_address).await?;
+        *self
             .socket
-            .as_ref()
-            .ok_or_else(|| RustRouteError::NetworkError("Interface not initialized".to_string()))?;
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(Arc::new(socket));
+        self.join_group(ip_address);
+        let previous = std::mem::replace(
+            &mut *self
+                .address
+                .write()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()),
+            (ip_address, subnet_mask),
+        );
 
-        // Serialize packet to JSON
-        let json_data = packet.to_json().map_err(|e| {
-            RustRouteError::ProtocolError(format!("Failed to serialize packet: {}", e))
-        })?;
+        log::info!(
+            "Network interface {} rebound from {} to {}:{}",
+            self.config.name,
+            previous.0,
+            ip_address,
+            self.config.port
+        );
+        Ok(())
+    }
 
-        // Send to broadcast address
-        let broadcast_addr = self.get_broadcast_address();
-        let target = SocketAddr::new(IpAddr::V4(broadcast_addr), self.config.port);
+    /// Whether updates go to a single unicast peer
+    pub fn is_point_to_point(&self) -> bool {
+        self.config.point_to_point
+    }
 
-        socket
-            .send_to(json_data.as_bytes(), target)
-            .await
-            .map_err(|e| RustRouteError::NetworkError(format!("Failed to send packet: {}", e)))?;
+    /// Where updates are sent, according to the send mode. Broadcast
+    /// interfaces on point-to-point links send to the peer; an empty list
+    /// means the interface cannot advertise.
+    pub fn update_destinations(&self) -> Vec<Ipv4Addr> {
+        match &self.config.send_mode {
+            SendMode::Broadcast | SendMode::Multicast if self.config.point_to_point => {
+                self.config.peer.into_iter().collect()
+            }
+            SendMode::Broadcast => std::iter::once(self.get_broadcast_address())
+                .chain(
+                    self.secondary_sockets
+                        .iter()
+                        .map(|(net, _)| net.broadcast())
+                        .filter(|broadcast| *broadcast != self.get_broadcast_address()),
+                )
+                .collect(),
+            SendMode::Multicast => vec![self.config.multicast_address],
+            SendMode::Unicast(neighbors) => neighbors.clone(),
+        }
+    }
 
-        log::debug!(
-            "Sent packet to {} on interface {}",
-            target,
-            self.config.name
-        );
+    /// Whether updates reach every neighbor through a single datagram
+    fn sends_to_group(&self) -> bool {
+        match self.config.send_mode {
+            SendMode::Broadcast | SendMode::Multicast => !self.config.point_to_point,
+            SendMode::Unicast(_) => false,
+        }
+    }
+
+    fn encode(&self, packet: &RipPacket, codec: PacketCodec) -> RustRouteResult<Vec<u8>> {
+        let mut packet = packet.clone();
+        // A next hop other than ourselves means nothing across a point-to-point link
+        if self.config.point_to_point {
+            for entry in &mut packet.entries {
+                entry.next_hop = Ipv4Addr::UNSPECIFIED;
+            }
+        }
+        match codec {
+            PacketCodec::Json => {
+                if let Some(auth) = &self.auth {
+                    auth.sign(&mut packet)?;
+                }
+                packet.to_json().map(String::into_bytes).map_err(|e| {
+                    RustRouteError::ProtocolError(format!("Failed to serialize packet: {}", e))
+                })
+            }
+            PacketCodec::Binary => self.key_chain.encode(&packet),
+        }
+    }
+
+    /// Room for RIP data in one datagram, after the IPv4 and UDP headers
+    fn max_payload(&self) -> usize {
+        (self.config.mtu as usize).saturating_sub(IP_UDP_HEADERS)
+    }
+
+    /// Encode `packet` as one or more datagrams that each fit the MTU and
+    /// hold at most `MAX_ENTRIES` entries
+    fn encode_all(&self, packet: &RipPacket, codec: PacketCodec) -> RustRouteResult<Vec<Vec<u8>>> {
+        let payload = self.max_payload();
+        let builder = match codec {
+            PacketCodec::Binary => PacketBuilder::new(payload)
+                .overhead(4 + self.key_chain.overhead())
+                .max_entries(MAX_ENTRIES - usize::from(self.key_chain.is_enabled())),
+            PacketCodec::Json => {
+                let empty = RipPacket {
+                    entries: Vec::new(),
+                    auth: None,
+                    ..packet.clone()
+                };
+                let base = serde_json::to_vec(&empty).map_or(0, |json| json.len());
+                let reserve = if self.auth.as_ref().is_some_and(|auth| auth.is_enabled()) {
+                    SIGNATURE_RESERVE
+                } else {
+                    0
+                };
+                PacketBuilder::new(payload).overhead(base + reserve)
+            }
+        };
+        let sizes: Vec<usize> = match codec {
+            PacketCodec::Binary => vec![20; packet.entries.len()],
+            PacketCodec::Json => packet
+                .entries
+                .iter()
+                .map(|entry| serde_json::to_vec(entry).map_or(0, |json| json.len()) + 1)
+                .collect(),
+        };
+        let batches = builder.split(packet, &sizes);
+
+        let mut datagrams = Vec::with_capacity(batches.len());
+        for batch in &batches {
+            let data = self.encode(batch, codec)?;
+            if data.len() > payload {
+                log::warn!(
+                    "{} byte packet exceeds the {} byte MTU of {} and will be fragmented",
+                    data.len(),
+                    self.config.mtu,
+                    self.config.name
+                );
+            }
+            datagrams.push(data);
+        }
+        Ok(datagrams)
+    }
+
+    fn decode(data: &[u8]) -> RustRouteResult<(RipPacket, PacketCodec)> {
+        let codec = PacketCodec::sniff(data).ok_or_else(|| {
+            RustRouteError::ProtocolError("Unrecognized packet encoding".to_string())
+        })?;
+        let packet = match codec {
+            PacketCodec::Json => {
+                let json_str = std::str::from_utf8(data).map_err(|e| {
+                    RustRouteError::ProtocolError(format!("Invalid UTF-8 in packet: {}", e))
+                })?;
+                RipPacket::from_json(json_str).map_err(|e| {
+                    RustRouteError::ProtocolError(format!("Failed to deserialize packet: {}", e))
+                })?
+            }
+            PacketCodec::Binary => RipPacket::from_bytes(data)?,
+        };
+        Ok((packet, codec))
+    }
+
+    /// Send a RIPER packet
+    pub async fn send_packet(&self, packet: &RipPacket) -> RustRouteResult<()> {
+        let destinations = self.update_destinations();
+        if destinations.is_empty() {
+            return Err(RustRouteError::NetworkError(format!(
+                "Interface {} has no update destination",
+                self.config.name
+            )));
+        }
+        if !self.sends_to_group() {
+            for destination in destinations {
+                let target = SocketAddr::new(IpAddr::V4(destination), self.config.port);
+                self.send_packet_to(packet, target).await?;
+            }
+            return Ok(());
+        }
+
+        // One broadcast per subnet, or the multicast group
+        for destination in destinations {
+            let target = SocketAddr::new(IpAddr::V4(destination), self.config.port);
+            self.send_encoded(packet, target, self.config.codec).await?;
+        }
+        // Neighbors speaking the other encoding cannot read the broadcast
+        let others: Vec<(IpAddr, PacketCodec)> = self
+            .codecs()
+            .iter()
+            .filter(|(_, codec)| **codec != self.config.codec)
+            .map(|(addr, codec)| (*addr, *codec))
+            .collect();
+        for (neighbor, codec) in others {
+            let target = SocketAddr::new(neighbor, self.config.port);
+            self.send_encoded(packet, target, codec).await?;
+        }
         Ok(())
     }
 
-    /// Send a packet to a specific destination
+    /// Send a packet to a specific destination, in the encoding it last used
     pub async fn send_packet_to(
         &self,
         packet: &RipPacket,
         destination: SocketAddr,
     ) -> RustRouteResult<()> {
-        let socket = self
-            .socket
-            .as_ref()
-            .ok_or_else(|| RustRouteError::NetworkError("Interface not initialized".to_string()))?;
+        let codec = self
+            .neighbor_codec(destination.ip())
+            .unwrap_or(self.config.codec);
+        self.send_encoded(packet, destination, codec).await
+    }
 
-        let json_data = packet.to_json().map_err(|e| {
-            RustRouteError::ProtocolError(format!("Failed to serialize packet: {}", e))
-        })?;
+    async fn send_encoded(
+        &self,
+        packet: &RipPacket,
+        destination: SocketAddr,
+        codec: PacketCodec,
+    ) -> RustRouteResult<()> {
+        let socket = self.socket_for(destination.ip())?;
 
-        socket
-            .send_to(json_data.as_bytes(), destination)
-            .await
-            .map_err(|e| RustRouteError::NetworkError(format!("Failed to send packet: {}", e)))?;
+        let datagrams = self.encode_all(packet, codec)?;
+        for data in &datagrams {
+            self.pace(data.len()).await;
+            if let Err(e) = socket.send_to(data, destination).await {
+                self.send_errors.fetch_add(1, Ordering::Relaxed);
+                let err = RustRouteError::NetworkError(format!(
+                    "Failed to send packet to {}: {}",
+                    destination, e
+                ));
+                last_error::record(&last_error::interface(&self.config.name), &err);
+                return Err(err);
+            }
+            self.datagrams_sent.fetch_add(1, Ordering::Relaxed);
+            self.bytes_sent
+                .fetch_add(data.len() as u64, Ordering::Relaxed);
+        }
+        self.traffic_times().last_sent = Some(Utc::now());
 
         log::debug!(
-            "Sent packet to {} on interface {}",
+            "Sent {} packet to {} on interface {} in {} datagram(s)",
+            codec.as_str(),
             destination,
-            self.config.name
+            self.config.name,
+            datagrams.len()
         );
         Ok(())
     }
 
     /// Receive a RIPER packet
     pub async fn receive_packet(&self) -> RustRouteResult<(RipPacket, SocketAddr)> {
-        let socket = self
-            .socket
-            .as_ref()
-            .ok_or_else(|| RustRouteError::NetworkError("Interface not initialized".to_string()))?;
+        let mut sockets = vec![self.socket()?];
+        sockets.extend(
+            self.secondary_sockets
+                .iter()
+                .map(|(_, socket)| Arc::clone(socket)),
+        );
+        sockets.extend(self.group_socket());
 
-        let mut buffer = vec![0u8; self.config.mtu as usize];
-        let (bytes_received, sender_addr) = socket.recv_from(&mut buffer).await.map_err(|e| {
-            RustRouteError::NetworkError(format!("Failed to receive packet: {}", e))
-        })?;
+        // Packets may arrive on any of the interface's addresses. The extra
+        // byte tells datagrams larger than the MTU from those that fit.
+        let mtu = self.config.mtu as usize;
+        let mut buffer = vec![0u8; mtu + 1];
+        let (bytes_received, sender_addr) = std::future::poll_fn(|cx| {
+            for socket in &sockets {
+                let mut read = tokio::io::ReadBuf::new(&mut buffer);
+                if let Poll::Ready(result) = socket.poll_recv_from(cx, &mut read) {
+                    return Poll::Ready(result.map(|sender| (read.filled().len(), sender)));
+                }
+            }
+            Poll::Pending
+        })
+        .await
+        .map_err(|e| RustRouteError::NetworkError(format!("Failed to receive packet: {}", e)))?;
+
+        self.datagrams_received.fetch_add(1, Ordering::Relaxed);
+        self.bytes_received
+            .fetch_add(bytes_received as u64, Ordering::Relaxed);
+        self.traffic_times().last_received = Some(Utc::now());
 
+        if bytes_received > mtu {
+            self.record_dropped(PacketDrop::Oversized);
+            return Err(RustRouteError::ProtocolError(format!(
+                "Packet from {} exceeds the MTU of {} bytes",
+                sender_addr, mtu
+            )));
+        }
         buffer.truncate(bytes_received);
-        let json_str = String::from_utf8(buffer).map_err(|e| {
-            RustRouteError::ProtocolError(format!("Invalid UTF-8 in packet: {}", e))
+        let (packet, codec) = Self::decode(&buffer).inspect_err(|_| {
+            self.record_dropped(PacketDrop::Malformed);
         })?;
+        self.packet_stats.record_version(packet.version);
+        if let Some(metrics) = &self.metrics {
+            metrics.record_packet_version(packet.version);
+        }
 
-        let packet = RipPacket::from_json(&json_str).map_err(|e| {
-            RustRouteError::ProtocolError(format!("Failed to deserialize packet: {}", e))
+        // Validate packet
+        packet.validate(self.config.infinity_metric).map_err(|e| {
+            self.record_dropped(PacketDrop::Malformed);
+            RustRouteError::ProtocolError(format!("Invalid packet: {}", e))
         })?;
 
-        // Validate packet
-        packet
-            .validate()
-            .map_err(|e| RustRouteError::ProtocolError(format!("Invalid packet: {}", e)))?;
+        // Binary packets on an interface with a key chain carry their own
+        // authentication; everything else may be signed as JSON is
+        let verified = if codec == PacketCodec::Binary && self.key_chain.is_enabled() {
+            self.key_chain.verify(&buffer, &packet, sender_addr.ip())
+        } else if let Some(auth) = &self.auth {
+            auth.verify(&packet, sender_addr.ip())
+        } else {
+            Ok(())
+        };
+        verified.inspect_err(|_| self.record_dropped(PacketDrop::Unauthenticated))?;
+
+        match codec {
+            PacketCodec::Json => self.json_packets.fetch_add(1, Ordering::Relaxed),
+            PacketCodec::Binary => self.binary_packets.fetch_add(1, Ordering::Relaxed),
+        };
+        self.codecs().insert(sender_addr.ip(), codec);
 
         log::debug!(
-            "Received packet from {} on interface {}",
+            "Received {} packet from {} on interface {}",
+            codec.as_str(),
             sender_addr,
             self.config.name
         );
@@ -166,8 +1033,8 @@ impl NetworkInterface {
 
     /// Get the broadcast address for this interface
     pub fn get_broadcast_address(&self) -> Ipv4Addr {
-        let ip = u32::from(self.config.ip_address);
-        let mask = u32::from(self.config.subnet_mask);
+        let ip = u32::from(self.ip_address());
+        let mask = u32::from(self.subnet_mask());
         let network = ip & mask;
         let broadcast = network | (!mask);
         Ipv4Addr::from(broadcast)
@@ -175,28 +1042,33 @@ impl NetworkInterface {
 
     /// Get the network address for this interface
     pub fn get_network_address(&self) -> Ipv4Addr {
-        let ip = u32::from(self.config.ip_address);
-        let mask = u32::from(self.config.subnet_mask);
+        let ip = u32::from(self.ip_address());
+        let mask = u32::from(self.subnet_mask());
         let network = ip & mask;
         Ipv4Addr::from(network)
     }
 
-    /// Check if an IP address is in the same subnet
+    /// Check if an IP address is in the subnet of any of the interface's addresses
     pub fn is_in_subnet(&self, addr: Ipv4Addr) -> bool {
-        let ip = u32::from(self.config.ip_address);
-        let mask = u32::from(self.config.subnet_mask);
+        let ip = u32::from(self.ip_address());
+        let mask = u32::from(self.subnet_mask());
         let addr_u32 = u32::from(addr);
 
         (ip & mask) == (addr_u32 & mask)
+            || self
+                .config
+                .secondary_addresses
+                .iter()
+                .any(|net| net.contains(&addr))
     }
 
     /// Get interface statistics
     pub fn get_stats(&self) -> InterfaceStats {
         InterfaceStats {
             name: self.config.name.clone(),
-            ip_address: self.config.ip_address,
-            subnet_mask: self.config.subnet_mask,
-            is_active: self.socket.is_some(),
+            ip_address: self.ip_address(),
+            subnet_mask: self.subnet_mask(),
+            is_active: self.socket().is_ok() && self.is_up(),
             mtu: self.config.mtu,
         }
     }
@@ -306,6 +1178,151 @@ pub mod utils {
         }
     }
 
+    /// MTU the OS reports for `interface`, where `/sys/class/net` is available
+    pub fn os_mtu(interface: &str) -> Option<u16> {
+        std::fs::read_to_string(format!("/sys/class/net/{}/mtu", interface))
+            .ok()?
+            .trim()
+            .parse()
+            .ok()
+    }
+
+    /// Link speed in Mb/s the OS reports for `interface`, as `ethtool` shows
+    /// it; `None` when unknown, as for virtual interfaces and downed links
+    pub fn os_link_speed(interface: &str) -> Option<u64> {
+        let speed = std::fs::read_to_string(format!("/sys/class/net/{}/speed", interface)).ok()?;
+        parse_speed(&speed)
+    }
+
+    /// The kernel reports -1, or 0 for some drivers, when the speed is unknown
+    pub(super) fn parse_speed(speed: &str) -> Option<u64> {
+        speed.trim().parse::<u64>().ok().filter(|&speed| speed > 0)
+    }
+
+    /// Operational state the OS reports for `interface`; `None` when unknown,
+    /// as for loopback and most tunnels
+    pub fn os_link_up(interface: &str) -> Option<bool> {
+        let state =
+            std::fs::read_to_string(format!("/sys/class/net/{}/operstate", interface)).ok()?;
+        parse_operstate(&state)
+    }
+
+    pub(super) fn parse_operstate(state: &str) -> Option<bool> {
+        match state.trim() {
+            "up" => Some(true),
+            "down" | "lowerlayerdown" | "notpresent" => Some(false),
+            _ => None,
+        }
+    }
+
+    /// IPv4 neighbors with a resolved entry in the kernel ARP cache. Empty
+    /// where `/proc/net/arp` is unavailable.
+    pub fn arp_neighbors() -> std::collections::HashSet<Ipv4Addr> {
+        std::fs::read_to_string("/proc/net/arp")
+            .map(|table| parse_arp_table(&table))
+            .unwrap_or_default()
+    }
+
+    pub(super) fn parse_arp_table(table: &str) -> std::collections::HashSet<Ipv4Addr> {
+        const ATF_COM: u32 = 0x2;
+        table
+            .lines()
+            .skip(1)
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                let address = fields.next()?.parse().ok()?;
+                let flags = fields.nth(1)?.trim_start_matches("0x");
+                let flags = u32::from_str_radix(flags, 16).ok()?;
+                (flags & ATF_COM != 0).then_some(address)
+            })
+            .collect()
+    }
+
+    /// IPv4 addresses the OS has assigned to `interface`, read from
+    /// `/proc/net/route` and `/proc/net/fib_trie`. `None` when the OS does not
+    /// expose them or does not know the interface; an empty list when the
+    /// interface has no address, such as after losing its DHCP lease.
+    pub fn os_ipv4_addresses(interface: &str) -> Option<Vec<Ipv4Net>> {
+        if !std::path::Path::new("/sys/class/net")
+            .join(interface)
+            .exists()
+        {
+            return None;
+        }
+        let routes = std::fs::read_to_string("/proc/net/route").ok()?;
+        let trie = std::fs::read_to_string("/proc/net/fib_trie").ok()?;
+        Some(assigned_addresses(
+            &parse_connected_routes(&routes, interface),
+            &parse_local_addresses(&trie),
+        ))
+    }
+
+    /// Every IPv4 address assigned on the host, where `/proc/net/fib_trie`
+    /// is available
+    pub fn os_local_addresses() -> Option<HashSet<Ipv4Addr>> {
+        let trie = std::fs::read_to_string("/proc/net/fib_trie").ok()?;
+        Some(parse_local_addresses(&trie))
+    }
+
+    /// Gateway-less routes of `interface` in `/proc/net/route`, which the
+    /// kernel adds for each subnet an address is assigned in
+    pub(super) fn parse_connected_routes(table: &str, interface: &str) -> Vec<Ipv4Net> {
+        let hex_address = |field: &str| {
+            u32::from_str_radix(field, 16)
+                .ok()
+                .map(|value| Ipv4Addr::from(value.to_le_bytes()))
+        };
+        table
+            .lines()
+            .skip(1)
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                if fields.len() < 8 || fields[0] != interface {
+                    return None;
+                }
+                let destination = hex_address(fields[1])?;
+                let gateway = hex_address(fields[2])?;
+                let mask = hex_address(fields[7])?;
+                if !gateway.is_unspecified() || mask.is_unspecified() {
+                    return None;
+                }
+                Ipv4Net::with_netmask(destination, mask).ok()
+            })
+            .collect()
+    }
+
+    /// Host addresses marked `/32 host LOCAL` in `/proc/net/fib_trie`
+    pub(super) fn parse_local_addresses(trie: &str) -> HashSet<Ipv4Addr> {
+        let mut locals = HashSet::new();
+        let mut last_leaf = None;
+        for line in trie.lines().map(str::trim) {
+            if let Some(leaf) = line.strip_prefix("|-- ") {
+                last_leaf = leaf.parse::<Ipv4Addr>().ok();
+            } else if line.starts_with("/32 host LOCAL") {
+                locals.extend(last_leaf);
+            }
+        }
+        locals
+    }
+
+    pub(super) fn assigned_addresses(
+        connected: &[Ipv4Net],
+        locals: &HashSet<Ipv4Addr>,
+    ) -> Vec<Ipv4Net> {
+        let mut assigned: Vec<Ipv4Net> = connected
+            .iter()
+            .flat_map(|net| {
+                locals
+                    .iter()
+                    .filter(|local| net.contains(*local))
+                    .filter_map(|local| Ipv4Net::new(*local, net.prefix_len()).ok())
+            })
+            .collect();
+        assigned.sort();
+        assigned.dedup();
+        assigned
+    }
+
     /// Check if two IP addresses are in the same subnet
     pub fn in_same_subnet(ip1: Ipv4Addr, ip2: Ipv4Addr, mask: Ipv4Addr) -> bool {
         let ip1_u32 = u32::from(ip1);
@@ -320,6 +1337,7 @@ pub mod utils {
 mod tests {
     use super::utils::*;
     use super::*;
+    use crate::protocol::RipEntry;
 
     #[test]
     fn test_broadcast_address() {
@@ -349,6 +1367,504 @@ mod tests {
         assert!(!interface.is_in_subnet(Ipv4Addr::new(192, 168, 2, 20)));
     }
 
+    #[test]
+    fn test_admin_state_toggle() {
+        let interface = NetworkInterface::new(InterfaceConfig::default());
+        assert!(interface.is_admin_up());
+
+        assert!(interface.set_admin_up(false));
+        assert!(!interface.is_admin_up());
+        assert!(!interface.get_stats().is_active);
+    }
+
+    #[test]
+    fn test_link_state_tracking() {
+        let interface = NetworkInterface::new(InterfaceConfig::default());
+        assert!(interface.is_up());
+        assert!(interface.set_link_up(false));
+        assert!(interface.is_admin_up());
+        assert!(!interface.is_up());
+
+        assert_eq!(super::utils::parse_operstate("up\n"), Some(true));
+        assert_eq!(
+            super::utils::parse_operstate("lowerlayerdown\n"),
+            Some(false)
+        );
+        assert_eq!(super::utils::parse_operstate("unknown\n"), None);
+        assert_eq!(super::utils::parse_speed("1000\n"), Some(1000));
+        assert_eq!(super::utils::parse_speed("-1\n"), None);
+        assert_eq!(super::utils::parse_speed("0\n"), None);
+    }
+
+    #[test]
+    fn test_pacing_spaces_datagrams() {
+        let mut pacer = Pacer::default();
+        let now = Instant::now();
+        assert_eq!(pacer.reserve(now, 512), Duration::ZERO);
+        assert_eq!(pacer.reserve(now, 512), Duration::ZERO);
+
+        pacer.config = PacingConfig {
+            packets_per_second: 10,
+            bytes_per_second: 1000,
+        };
+        pacer.next_send = None;
+        assert_eq!(pacer.reserve(now, 50), Duration::ZERO);
+        // 50 bytes fit the byte budget sooner than the packet rate allows
+        assert_eq!(pacer.reserve(now, 500), Duration::from_millis(100));
+        // 500 bytes take half a second of the byte budget
+        assert_eq!(pacer.reserve(now, 50), Duration::from_millis(600));
+
+        // An idle spell does not bank budget
+        let later = now + Duration::from_secs(5);
+        assert_eq!(pacer.reserve(later, 50), Duration::ZERO);
+        assert_eq!(pacer.reserve(later, 50), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_sender_filter() {
+        let interface = NetworkInterface::new(InterfaceConfig::default());
+        let peer = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 2));
+        let stranger = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 66));
+        assert!(interface.permits_sender(stranger));
+
+        interface.set_sender_filter(SenderFilter {
+            accept_from: vec!["192.168.1.0/24".parse().unwrap()],
+            reject_from: vec!["192.168.1.64/28".parse().unwrap()],
+        });
+        assert!(interface.permits_sender(peer));
+        assert!(!interface.permits_sender(stranger));
+        assert!(!interface.permits_sender(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))));
+
+        assert!(interface.record_rejected_sender(stranger));
+        assert!(!interface.record_rejected_sender(stranger));
+        assert_eq!(interface.rejected_packets(), 2);
+    }
+
+    #[test]
+    fn test_point_to_point_destination() {
+        let interface = NetworkInterface::new(InterfaceConfig {
+            name: "wg0".to_string(),
+            ip_address: Ipv4Addr::new(10, 99, 0, 1),
+            subnet_mask: Ipv4Addr::new(255, 255, 255, 255),
+            point_to_point: true,
+            peer: Some(Ipv4Addr::new(10, 99, 0, 2)),
+            ..Default::default()
+        });
+        assert_eq!(
+            interface.update_destinations(),
+            vec![Ipv4Addr::new(10, 99, 0, 2)]
+        );
+
+        let mut packet = RipPacket::new_request();
+        packet.entries[0].next_hop = Ipv4Addr::new(192, 168, 1, 254);
+        let sent = RipPacket::from_bytes(&interface.encode(&packet, PacketCodec::Binary).unwrap())
+            .unwrap();
+        assert!(sent.entries[0].next_hop.is_unspecified());
+
+        let unpeered = NetworkInterface::new(InterfaceConfig {
+            point_to_point: true,
+            ..Default::default()
+        });
+        assert!(unpeered.update_destinations().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_replies_follow_neighbor_codec() {
+        let mut interface = NetworkInterface::new(InterfaceConfig {
+            name: "lo".to_string(),
+            ip_address: Ipv4Addr::LOCALHOST,
+            subnet_mask: Ipv4Addr::new(255, 0, 0, 0),
+            port: 0,
+            ..Default::default()
+        });
+        interface.initialize().await.unwrap();
+        let local = interface.socket().unwrap().local_addr().unwrap();
+
+        let peer = TokioUdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let request = RipPacket::new_request();
+        peer.send_to(&request.to_bytes().unwrap(), local)
+            .await
+            .unwrap();
+        let (_, sender) = interface.receive_packet().await.unwrap();
+        assert_eq!(interface.packets_received(PacketCodec::Binary), 1);
+        assert_eq!(
+            interface.neighbor_codec(sender.ip()),
+            Some(PacketCodec::Binary)
+        );
+
+        interface
+            .send_packet_to(&RipPacket::new_response(Vec::new()), sender)
+            .await
+            .unwrap();
+        let mut buffer = [0u8; 512];
+        let (len, _) = peer.recv_from(&mut buffer).await.unwrap();
+        assert_eq!(
+            PacketCodec::sniff(&buffer[..len]),
+            Some(PacketCodec::Binary)
+        );
+
+        let json = format!("  {}", request.to_json().unwrap());
+        peer.send_to(json.as_bytes(), local).await.unwrap();
+        interface.receive_packet().await.unwrap();
+        assert_eq!(interface.packets_received(PacketCodec::Json), 1);
+        assert_eq!(
+            interface.neighbor_codec(sender.ip()),
+            Some(PacketCodec::Json)
+        );
+
+        peer.send_to(b"\x07garbage", local).await.unwrap();
+        assert!(interface.receive_packet().await.is_err());
+        assert_eq!(interface.packet_stats().malformed, 1);
+
+        // With a key chain, unauthenticated binary packets are dropped
+        let auth = InterfaceAuthConfig {
+            interface: "lo".to_string(),
+            mode: crate::rip_auth::AuthMode::Md5,
+            keys: vec![crate::rip_auth::AuthKey {
+                id: 7,
+                key: "secret".to_string(),
+                valid_from: None,
+                valid_until: None,
+            }],
+        };
+        interface.set_key_chain(Some(auth.clone()));
+        peer.send_to(&request.to_bytes().unwrap(), local)
+            .await
+            .unwrap();
+        assert!(interface.receive_packet().await.is_err());
+        assert_eq!(interface.packet_stats().unauthenticated, 1);
+        let signed = crate::rip_auth::sign(&request, auth.mode, &auth.keys[0], 1).unwrap();
+        peer.send_to(&signed, local).await.unwrap();
+        let (received, _) = interface.receive_packet().await.unwrap();
+        assert!(received.authentication.is_some());
+    }
+
+    #[test]
+    fn test_solicited_responses() {
+        let interface = NetworkInterface::new(InterfaceConfig::default());
+        let neighbor = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 2));
+        assert!(!interface.take_solicited(neighbor));
+
+        interface.record_request_sent();
+        assert!(interface.take_solicited(neighbor));
+        assert!(!interface.take_solicited(neighbor));
+        assert!(interface.take_solicited(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 3))));
+
+        interface.record_request_sent();
+        assert!(interface.take_solicited(neighbor));
+    }
+
+    #[tokio::test]
+    async fn test_rebind_moves_the_socket() {
+        let mut interface = NetworkInterface::new(InterfaceConfig {
+            name: "lo".to_string(),
+            ip_address: Ipv4Addr::LOCALHOST,
+            subnet_mask: Ipv4Addr::new(255, 0, 0, 0),
+            port: 0,
+            ..Default::default()
+        });
+        interface.initialize().await.unwrap();
+
+        let renumbered = Ipv4Addr::new(127, 0, 0, 5);
+        interface
+            .rebind(renumbered, Ipv4Addr::new(255, 255, 255, 0))
+            .await
+            .unwrap();
+        assert_eq!(interface.ip_address(), renumbered);
+        assert_eq!(
+            interface.get_broadcast_address(),
+            Ipv4Addr::new(127, 0, 0, 255)
+        );
+        let local = interface.socket().unwrap().local_addr().unwrap();
+        assert_eq!(local.ip(), IpAddr::V4(renumbered));
+
+        // An address the host does not have leaves the socket alone
+        assert!(interface
+            .rebind(
+                Ipv4Addr::new(192, 0, 2, 200),
+                Ipv4Addr::new(255, 255, 255, 0)
+            )
+            .await
+            .is_err());
+        assert_eq!(interface.ip_address(), renumbered);
+    }
+
+    #[tokio::test]
+    async fn test_secondary_addresses() {
+        let mut interface = NetworkInterface::new(InterfaceConfig {
+            name: "lo".to_string(),
+            ip_address: Ipv4Addr::LOCALHOST,
+            subnet_mask: Ipv4Addr::new(255, 255, 255, 0),
+            port: 0,
+            secondary_addresses: vec!["127.0.9.1/24".parse().unwrap()],
+            send_mode: SendMode::Broadcast,
+            ..Default::default()
+        });
+        interface.initialize().await.unwrap();
+        assert_eq!(
+            interface.update_destinations(),
+            vec![Ipv4Addr::new(127, 0, 0, 255), Ipv4Addr::new(127, 0, 9, 255)]
+        );
+        assert!(interface.is_in_subnet(Ipv4Addr::new(127, 0, 9, 20)));
+
+        // Each subnet hears from the address it shares with the neighbor
+        let secondary = interface.secondary_sockets[0].1.local_addr().unwrap();
+        let neighbor = TokioUdpSocket::bind("127.0.9.2:0").await.unwrap();
+        interface
+            .send_packet_to(
+                &RipPacket::new_response(Vec::new()),
+                neighbor.local_addr().unwrap(),
+            )
+            .await
+            .unwrap();
+        let mut buffer = [0u8; 512];
+        let (_, source) = neighbor.recv_from(&mut buffer).await.unwrap();
+        assert_eq!(source, secondary);
+
+        // and packets to the secondary address are received
+        neighbor
+            .send_to(&RipPacket::new_request().to_bytes().unwrap(), secondary)
+            .await
+            .unwrap();
+        let (_, sender) = interface.receive_packet().await.unwrap();
+        assert_eq!(sender, neighbor.local_addr().unwrap());
+    }
+
+    #[test]
+    fn test_os_address_parsing() {
+        let routes =
+            "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT\n\
+                      eth0\t00000000\t010200C0\t0003\t0\t0\t0\t00000000\t0\t0\t0\n\
+                      eth0\t000200C0\t00000000\t0001\t0\t0\t0\t00FFFFFF\t0\t0\t0\n\
+                      eth1\t0000000A\t00000000\t0001\t0\t0\t0\t000000FF\t0\t0\t0\n";
+        let connected = super::utils::parse_connected_routes(routes, "eth0");
+        assert_eq!(connected, vec!["192.0.2.0/24".parse::<Ipv4Net>().unwrap()]);
+
+        let trie = "Main:\n  +-- 0.0.0.0/0 3 0 5\n     +-- 192.0.2.0/24 2 0 2\n\
+                    \x20          |-- 192.0.2.0\n              /24 link UNICAST\n\
+                    \x20          |-- 192.0.2.2\n              /32 host LOCAL\n\
+                    \x20       |-- 192.0.2.255\n           /32 link BROADCAST\n\
+                    \x20    |-- 10.0.0.1\n        /32 host LOCAL\n";
+        let locals = super::utils::parse_local_addresses(trie);
+        assert_eq!(locals.len(), 2);
+        assert_eq!(
+            super::utils::assigned_addresses(&connected, &locals),
+            vec!["192.0.2.2/24".parse::<Ipv4Net>().unwrap()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unicast_send_mode() {
+        let first = TokioUdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let port = first.local_addr().unwrap().port();
+        let second = TokioUdpSocket::bind(("127.0.0.2", port)).await.unwrap();
+
+        let send_mode: SendMode =
+            serde_json::from_str(r#"{"unicast": ["127.0.0.1", "127.0.0.2"]}"#).unwrap();
+        let mut interface = NetworkInterface::new(InterfaceConfig {
+            name: "lo".to_string(),
+            ip_address: Ipv4Addr::new(127, 0, 0, 3),
+            subnet_mask: Ipv4Addr::new(255, 0, 0, 0),
+            port,
+            send_mode,
+            ..Default::default()
+        });
+        interface.initialize().await.unwrap();
+        interface
+            .send_packet(&RipPacket::new_response(Vec::new()))
+            .await
+            .unwrap();
+
+        let mut buffer = [0u8; 512];
+        for peer in [&first, &second] {
+            let (_, sender) = peer.recv_from(&mut buffer).await.unwrap();
+            assert_eq!(sender.ip(), IpAddr::V4(Ipv4Addr::new(127, 0, 0, 3)));
+        }
+
+        interface.config.send_mode = SendMode::Multicast;
+        assert_eq!(
+            interface.update_destinations(),
+            vec![Ipv4Addr::new(224, 0, 0, 9)]
+        );
+        interface.config.send_mode = SendMode::Unicast(Vec::new());
+        assert!(interface.update_destinations().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_multicast_group_membership() {
+        let group = Ipv4Addr::new(224, 0, 0, 9);
+        let mut interface = NetworkInterface::new(InterfaceConfig {
+            name: "lo".to_string(),
+            ip_address: Ipv4Addr::LOCALHOST,
+            subnet_mask: Ipv4Addr::new(255, 0, 0, 0),
+            port: 0,
+            ..Default::default()
+        });
+        assert_eq!(interface.config.send_mode, SendMode::Multicast);
+        assert_eq!(interface.update_destinations(), vec![group]);
+        interface.initialize().await.unwrap();
+        let local = interface
+            .group_socket()
+            .expect("joined the group")
+            .local_addr()
+            .unwrap();
+        assert_eq!(local.ip(), IpAddr::V4(group));
+
+        // A neighbor's update to the group reaches the interface
+        let neighbor = TokioUdpSocket::bind("127.0.0.1:0").await.unwrap();
+        socket2::SockRef::from(&neighbor)
+            .set_multicast_if_v4(&Ipv4Addr::LOCALHOST)
+            .unwrap();
+        neighbor
+            .send_to(
+                &RipPacket::new_request().to_bytes().unwrap(),
+                (group, local.port()),
+            )
+            .await
+            .unwrap();
+        let (_, sender) = interface.receive_packet().await.unwrap();
+        assert_eq!(sender, neighbor.local_addr().unwrap());
+
+        // Interfaces reaching their neighbors by unicast stay out of the group
+        let mut unicast = NetworkInterface::new(InterfaceConfig {
+            name: "lo".to_string(),
+            ip_address: Ipv4Addr::LOCALHOST,
+            subnet_mask: Ipv4Addr::new(255, 0, 0, 0),
+            port: 0,
+            send_mode: SendMode::Unicast(vec![Ipv4Addr::new(127, 0, 0, 2)]),
+            ..Default::default()
+        });
+        unicast.initialize().await.unwrap();
+        assert!(unicast.group_socket().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_counters_and_clear() {
+        let peer = TokioUdpSocket::bind("127.0.0.6:0").await.unwrap();
+        let port = peer.local_addr().unwrap().port();
+        let mut interface = NetworkInterface::new(InterfaceConfig {
+            name: "lo".to_string(),
+            ip_address: Ipv4Addr::new(127, 0, 0, 7),
+            subnet_mask: Ipv4Addr::new(255, 0, 0, 0),
+            port,
+            ..Default::default()
+        });
+        interface.initialize().await.unwrap();
+        interface
+            .send_packet_to(
+                &RipPacket::new_response(Vec::new()),
+                peer.local_addr().unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Echo the update back to the interface
+        let mut buffer = [0u8; 512];
+        let (len, sender) = peer.recv_from(&mut buffer).await.unwrap();
+        peer.send_to(&buffer[..len], sender).await.unwrap();
+        interface.receive_packet().await.unwrap();
+
+        let counters = interface.counters();
+        assert_eq!(counters.datagrams_sent, 1);
+        assert_eq!(counters.bytes_sent, len as u64);
+        assert_eq!(counters.datagrams_received, 1);
+        assert_eq!(counters.json_packets, 1);
+        assert_eq!(counters.packets.version2, 1);
+        assert!(counters.last_sent.is_some() && counters.last_received.is_some());
+
+        // Datagrams larger than the MTU are dropped whole
+        let oversized = vec![b' '; interface.config.mtu as usize + 1];
+        peer.send_to(&oversized, sender).await.unwrap();
+        assert!(interface.receive_packet().await.is_err());
+        assert_eq!(interface.packet_stats().oversized, 1);
+
+        interface.clear_counters();
+        let cleared = interface.counters();
+        assert_eq!(cleared.datagrams_sent, 0);
+        assert_eq!(cleared.json_packets, 0);
+        assert_eq!(cleared.packets, PacketStats::default());
+        assert!(cleared.last_received.is_none() && cleared.cleared_at.is_some());
+        assert!(interface.is_bound());
+    }
+
+    #[test]
+    fn test_updates_split_to_fit_mtu() {
+        let entries: Vec<RipEntry> = (0..40)
+            .map(|i| {
+                RipEntry::new(
+                    Ipv4Addr::new(10, i, 0, 0),
+                    Ipv4Addr::new(255, 255, 0, 0),
+                    Ipv4Addr::UNSPECIFIED,
+                    1,
+                )
+            })
+            .collect();
+        let packet = RipPacket::new_response(entries);
+        let interface = NetworkInterface::new(InterfaceConfig {
+            mtu: 576,
+            ..Default::default()
+        });
+
+        let binary = interface.encode_all(&packet, PacketCodec::Binary).unwrap();
+        // (576 - 28 - 4) / 20 = 27 entries would fit, but a packet holds 25
+        assert_eq!(binary.len(), 2);
+        assert_eq!(RipPacket::from_bytes(&binary[0]).unwrap().entries.len(), 25);
+        assert_eq!(RipPacket::from_bytes(&binary[1]).unwrap().entries.len(), 15);
+
+        // Authentication takes an entry and a 36 byte trailer from each
+        interface.set_key_chain(Some(InterfaceAuthConfig {
+            interface: "eth0".to_string(),
+            mode: crate::rip_auth::AuthMode::Sha256,
+            keys: vec![crate::rip_auth::AuthKey {
+                id: 1,
+                key: "secret".to_string(),
+                valid_from: None,
+                valid_until: None,
+            }],
+        }));
+        let signed = interface.encode_all(&packet, PacketCodec::Binary).unwrap();
+        assert_eq!(RipPacket::from_bytes(&signed[0]).unwrap().entries.len(), 24);
+        assert!(signed.iter().all(|datagram| datagram.len() <= 548));
+        interface.set_key_chain(None);
+
+        // The entry limit applies however large the MTU
+        let jumbo = NetworkInterface::new(InterfaceConfig {
+            mtu: 9000,
+            ..Default::default()
+        });
+        let binary = jumbo.encode_all(&packet, PacketCodec::Binary).unwrap();
+        let counts: Vec<usize> = binary
+            .iter()
+            .map(|datagram| RipPacket::from_bytes(datagram).unwrap().entries.len())
+            .collect();
+        assert_eq!(counts, vec![25, 15]);
+
+        let json = interface.encode_all(&packet, PacketCodec::Json).unwrap();
+        assert!(json.len() > 2);
+        let mut received = 0;
+        for datagram in &json {
+            assert!(datagram.len() <= 548);
+            let text = std::str::from_utf8(datagram).unwrap();
+            received += RipPacket::from_json(text).unwrap().entries.len();
+        }
+        assert_eq!(received, 40);
+
+        let small = interface
+            .encode_all(&RipPacket::new_request(), PacketCodec::Json)
+            .unwrap();
+        assert_eq!(small.len(), 1);
+    }
+
+    #[test]
+    fn test_arp_table_parsing() {
+        let table = "IP address       HW type     Flags       HW address            Mask     Device\n\
+                     192.168.1.1      0x1         0x2         52:54:00:12:35:02     *        eth0\n\
+                     192.168.1.9      0x1         0x0         00:00:00:00:00:00     *        eth0\n";
+        let resolved = super::utils::parse_arp_table(table);
+        assert!(resolved.contains(&Ipv4Addr::new(192, 168, 1, 1)));
+        assert!(!resolved.contains(&Ipv4Addr::new(192, 168, 1, 9)));
+    }
+
     #[test]
     fn test_prefix_conversion() {
         assert_eq!(mask_to_prefix_length(Ipv4Addr::new(255, 255, 255, 0)), 24);
@@ -6,6 +6,8 @@ use std::time::{Duration, SystemTime};
 use tokio::sync::RwLock;
 use tokio::time::interval;
 
+use crate::last_error;
+
 /// Network topology discovery configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DiscoveryConfig {
@@ -158,6 +160,7 @@ impl NetworkDiscovery {
 
                 if let Err(e) = discovery.perform_discovery().await {
                     log::error!("Discovery error: {}", e);
+                    last_error::record(last_error::DISCOVERY, &e);
                 }
             }
         });
@@ -0,0 +1,543 @@
+//! HMAC signing and replay protection for the JSON packet transport.
+//!
+//! Every outgoing packet carries a sequence number and an HMAC-SHA256 over
+//! that number and the unsigned packet JSON. Receivers remember the highest
+//! sequence accepted from each neighbor and drop anything at or below it,
+//! so a captured update cannot be replayed later. Sequences start from the
+//! wall clock in microseconds, which keeps them increasing across restarts;
+//! windows are therefore never reset, even when a neighbor times out.
+//! Binary-encoded packets cannot carry a signature and count as unsigned.
+//!
+//! Failed checks are counted per source. A run of `alert_threshold`
+//! failures raises an alert, and with `lockout_threshold` set a source that
+//! keeps failing is ignored for `lockout_seconds` without being checked.
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+
+use crate::protocol::RipPacket;
+use crate::{RustRouteError, RustRouteResult};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Packet authentication settings, under `rip.packet_auth`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PacketAuthConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Shared secret; every router on a link must use the same key
+    #[serde(default)]
+    pub key: String,
+    /// Accept unsigned packets while keys are being rolled out
+    #[serde(default)]
+    pub accept_unsigned: bool,
+    /// Consecutive failures from one source that raise an alert; 0 never
+    /// alerts
+    #[serde(default = "default_alert_threshold")]
+    pub alert_threshold: u32,
+    /// Consecutive failures after which a source is ignored for
+    /// `lockout_seconds`; 0 never ignores a source
+    #[serde(default)]
+    pub lockout_threshold: u32,
+    #[serde(default = "default_lockout_seconds")]
+    pub lockout_seconds: u64,
+}
+
+fn default_alert_threshold() -> u32 {
+    5
+}
+
+fn default_lockout_seconds() -> u64 {
+    300
+}
+
+impl Default for PacketAuthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            key: String::new(),
+            accept_unsigned: false,
+            alert_threshold: default_alert_threshold(),
+            lockout_threshold: 0,
+            lockout_seconds: default_lockout_seconds(),
+        }
+    }
+}
+
+/// Signature carried in a JSON packet
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PacketSignature {
+    pub sequence: u64,
+    /// Hex-encoded HMAC-SHA256
+    pub hmac: String,
+}
+
+/// Authentication failures of one source
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuthFailures {
+    pub address: IpAddr,
+    /// Failures since the router started or the source was cleared
+    pub total: u64,
+    /// Failures since the last packet that passed
+    pub consecutive: u32,
+    /// Packets dropped unchecked while the source was locked out
+    pub ignored: u64,
+    pub last_failure: DateTime<Utc>,
+    pub last_reason: String,
+    /// Until when packets from the source are ignored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locked_until: Option<DateTime<Utc>>,
+}
+
+/// A threshold crossed by a source, for the activity feed
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuthAlert {
+    /// `failures` consecutive failures, the last for `reason`
+    Failures {
+        address: IpAddr,
+        failures: u32,
+        reason: String,
+    },
+    /// Packets from `address` are ignored for `seconds`
+    LockedOut { address: IpAddr, seconds: u64 },
+}
+
+impl fmt::Display for AuthAlert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthAlert::Failures {
+                address,
+                failures,
+                reason,
+            } => write!(
+                f,
+                "{} RIP packets in a row from {} failed authentication (last: {})",
+                failures, address, reason
+            ),
+            AuthAlert::LockedOut { address, seconds } => write!(
+                f,
+                "Ignoring RIP packets from {} for {}s after repeated authentication failures",
+                address, seconds
+            ),
+        }
+    }
+}
+
+struct Settings {
+    key: Vec<u8>,
+    accept_unsigned: bool,
+    alert_threshold: u32,
+    lockout_threshold: u32,
+    lockout: Duration,
+}
+
+#[derive(Debug)]
+struct FailureState {
+    failures: AuthFailures,
+    locked_until: Option<Instant>,
+}
+
+/// Signing key, outgoing sequence counter, per-neighbor replay windows and
+/// failure counters, shared by every interface
+pub struct PacketAuth {
+    settings: RwLock<Option<Settings>>,
+    sequence: AtomicU64,
+    last_seen: Mutex<HashMap<IpAddr, u64>>,
+    failures: Mutex<HashMap<IpAddr, FailureState>>,
+    alerts: Mutex<Vec<AuthAlert>>,
+}
+
+impl fmt::Debug for PacketAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PacketAuth")
+            .field("enabled", &self.is_enabled())
+            .finish_non_exhaustive()
+    }
+}
+
+impl PacketAuth {
+    pub fn new(config: &PacketAuthConfig) -> Self {
+        let start = chrono::Utc::now().timestamp_micros().max(0) as u64;
+        let auth = Self {
+            settings: RwLock::new(None),
+            sequence: AtomicU64::new(start),
+            last_seen: Mutex::new(HashMap::new()),
+            failures: Mutex::new(HashMap::new()),
+            alerts: Mutex::new(Vec::new()),
+        };
+        auth.configure(config);
+        auth
+    }
+
+    /// Apply new settings; sequence numbers, replay windows and failure
+    /// counters are kept
+    pub fn configure(&self, config: &PacketAuthConfig) {
+        let settings = config.enabled.then(|| Settings {
+            key: config.key.as_bytes().to_vec(),
+            accept_unsigned: config.accept_unsigned,
+            alert_threshold: config.alert_threshold,
+            lockout_threshold: config.lockout_threshold,
+            lockout: Duration::from_secs(config.lockout_seconds),
+        });
+        *self
+            .settings
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = settings;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.read_settings().is_some()
+    }
+
+    fn read_settings(&self) -> std::sync::RwLockReadGuard<'_, Option<Settings>> {
+        self.settings
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Attach a fresh signature to `packet`; a no-op when signing is disabled
+    pub fn sign(&self, packet: &mut RipPacket) -> RustRouteResult<()> {
+        let settings = self.read_settings();
+        let Some(settings) = settings.as_ref() else {
+            return Ok(());
+        };
+        packet.auth = None;
+        let sequence = self.sequence.fetch_add(1, Ordering::Relaxed) + 1;
+        let digest = mac(&settings.key, sequence, packet)?
+            .finalize()
+            .into_bytes();
+        packet.auth = Some(PacketSignature {
+            sequence,
+            hmac: digest.iter().map(|b| format!("{:02x}", b)).collect(),
+        });
+        Ok(())
+    }
+
+    /// Check the signature and sequence of a packet received from `sender`,
+    /// counting the failure if it does not pass
+    pub fn verify(&self, packet: &RipPacket, sender: IpAddr) -> RustRouteResult<()> {
+        let settings = self.read_settings();
+        let Some(settings) = settings.as_ref() else {
+            return Ok(());
+        };
+        if let Some(remaining) = self.locked_out(sender) {
+            return Err(auth_error(format!(
+                "ignoring {} for another {}s after repeated failures",
+                sender,
+                remaining.as_secs().max(1)
+            )));
+        }
+        match self.check(settings, packet, sender) {
+            Ok(()) => {
+                if let Some(state) = self.failures().get_mut(&sender) {
+                    state.failures.consecutive = 0;
+                }
+                Ok(())
+            }
+            Err(err) => {
+                self.record_failure(settings, sender, &err);
+                Err(err)
+            }
+        }
+    }
+
+    fn check(
+        &self,
+        settings: &Settings,
+        packet: &RipPacket,
+        sender: IpAddr,
+    ) -> RustRouteResult<()> {
+        let Some(signature) = &packet.auth else {
+            if settings.accept_unsigned {
+                return Ok(());
+            }
+            return Err(auth_error(format!("unsigned packet from {}", sender)));
+        };
+
+        let expected = decode_hex(&signature.hmac)
+            .ok_or_else(|| auth_error(format!("malformed signature from {}", sender)))?;
+        let mut unsigned = packet.clone();
+        unsigned.auth = None;
+        mac(&settings.key, signature.sequence, &unsigned)?
+            .verify_slice(&expected)
+            .map_err(|_| auth_error(format!("bad signature from {}", sender)))?;
+
+        let mut last_seen = self
+            .last_seen
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let last = last_seen.entry(sender).or_insert(0);
+        if signature.sequence <= *last {
+            return Err(auth_error(format!(
+                "replayed packet from {} (sequence {} <= {})",
+                sender, signature.sequence, last
+            )));
+        }
+        *last = signature.sequence;
+        Ok(())
+    }
+
+    fn failures(&self) -> std::sync::MutexGuard<'_, HashMap<IpAddr, FailureState>> {
+        self.failures
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Time left on a lockout of `sender`, counting the packet as ignored
+    fn locked_out(&self, sender: IpAddr) -> Option<Duration> {
+        let mut failures = self.failures();
+        let state = failures.get_mut(&sender)?;
+        let remaining = state
+            .locked_until?
+            .checked_duration_since(Instant::now())
+            .filter(|remaining| !remaining.is_zero());
+        match remaining {
+            Some(remaining) => {
+                state.failures.ignored += 1;
+                Some(remaining)
+            }
+            None => {
+                // Expired; the source gets a fresh run of attempts
+                state.locked_until = None;
+                state.failures.locked_until = None;
+                state.failures.consecutive = 0;
+                None
+            }
+        }
+    }
+
+    fn record_failure(&self, settings: &Settings, sender: IpAddr, err: &RustRouteError) {
+        let now = Utc::now();
+        let reason = match err {
+            RustRouteError::ProtocolError(message) => message
+                .strip_prefix("Packet authentication failed: ")
+                .unwrap_or(message)
+                .to_string(),
+            other => other.to_string(),
+        };
+
+        let mut failures = self.failures();
+        let state = failures.entry(sender).or_insert_with(|| FailureState {
+            failures: AuthFailures {
+                address: sender,
+                total: 0,
+                consecutive: 0,
+                ignored: 0,
+                last_failure: now,
+                last_reason: String::new(),
+                locked_until: None,
+            },
+            locked_until: None,
+        });
+        state.failures.total += 1;
+        state.failures.consecutive += 1;
+        state.failures.last_failure = now;
+        state.failures.last_reason = reason.clone();
+        let consecutive = state.failures.consecutive;
+
+        let mut alerts = Vec::new();
+        if settings.alert_threshold > 0 && consecutive == settings.alert_threshold {
+            alerts.push(AuthAlert::Failures {
+                address: sender,
+                failures: consecutive,
+                reason,
+            });
+        }
+        if settings.lockout_threshold > 0
+            && consecutive >= settings.lockout_threshold
+            && !settings.lockout.is_zero()
+        {
+            state.locked_until = Some(Instant::now() + settings.lockout);
+            state.failures.locked_until =
+                Some(now + chrono::Duration::from_std(settings.lockout).unwrap_or_default());
+            alerts.push(AuthAlert::LockedOut {
+                address: sender,
+                seconds: settings.lockout.as_secs(),
+            });
+        }
+        drop(failures);
+
+        if !alerts.is_empty() {
+            self.alerts
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .extend(alerts);
+        }
+    }
+
+    /// Thresholds crossed since the last call
+    pub fn take_alerts(&self) -> Vec<AuthAlert> {
+        std::mem::take(
+            &mut *self
+                .alerts
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()),
+        )
+    }
+
+    /// Failure counters of `address`, if it ever failed
+    pub fn failures_of(&self, address: IpAddr) -> Option<AuthFailures> {
+        self.failures()
+            .get(&address)
+            .map(|state| state.failures.clone())
+    }
+
+    /// Failure counters of every source that failed, by address
+    pub fn all_failures(&self) -> Vec<AuthFailures> {
+        let mut all: Vec<AuthFailures> = self
+            .failures()
+            .values()
+            .map(|state| state.failures.clone())
+            .collect();
+        all.sort_by_key(|failures| failures.address);
+        all
+    }
+
+    /// Forget the failures of `address`, lifting a lockout
+    pub fn clear_failures(&self, address: IpAddr) -> bool {
+        self.failures().remove(&address).is_some()
+    }
+}
+
+fn auth_error(message: String) -> RustRouteError {
+    RustRouteError::ProtocolError(format!("Packet authentication failed: {}", message))
+}
+
+/// HMAC over the sequence number and the unsigned packet JSON
+fn mac(key: &[u8], sequence: u64, packet: &RipPacket) -> RustRouteResult<HmacSha256> {
+    let body = serde_json::to_vec(packet)
+        .map_err(|e| RustRouteError::ProtocolError(format!("Failed to serialize packet: {}", e)))?;
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(&sequence.to_be_bytes());
+    mac.update(&body);
+    Ok(mac)
+}
+
+pub(crate) fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn config(key: &str) -> PacketAuthConfig {
+        PacketAuthConfig {
+            enabled: true,
+            key: key.to_string(),
+            ..PacketAuthConfig::default()
+        }
+    }
+
+    fn signed(auth: &PacketAuth) -> RipPacket {
+        let mut packet = RipPacket::new_request();
+        auth.sign(&mut packet).unwrap();
+        // Signatures must survive the trip through JSON
+        RipPacket::from_json(&packet.to_json().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn replayed_and_tampered_packets_are_rejected() {
+        let sender = PacketAuth::new(&config("lab-secret"));
+        let receiver = PacketAuth::new(&config("lab-secret"));
+        let neighbor = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 2));
+
+        let first = signed(&sender);
+        let second = signed(&sender);
+        receiver.verify(&first, neighbor).unwrap();
+        receiver.verify(&second, neighbor).unwrap();
+        assert!(receiver.verify(&first, neighbor).is_err());
+        assert!(receiver.verify(&second, neighbor).is_err());
+
+        // Replay windows are per neighbor
+        let other = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 3));
+        receiver.verify(&first, other).unwrap();
+
+        let mut tampered = signed(&sender);
+        tampered.entries[0].metric = 1;
+        assert!(receiver.verify(&tampered, neighbor).is_err());
+
+        let stranger = PacketAuth::new(&config("other-secret"));
+        assert!(receiver.verify(&signed(&stranger), neighbor).is_err());
+    }
+
+    #[test]
+    fn repeated_failures_alert_and_lock_the_source_out() {
+        let receiver = PacketAuth::new(&PacketAuthConfig {
+            alert_threshold: 2,
+            lockout_threshold: 3,
+            ..config("lab-secret")
+        });
+        let stranger = PacketAuth::new(&config("other-secret"));
+        let sender = PacketAuth::new(&config("lab-secret"));
+        let neighbor = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 2));
+
+        assert!(receiver.verify(&signed(&stranger), neighbor).is_err());
+        receiver.verify(&signed(&sender), neighbor).unwrap();
+        let failures = receiver.failures_of(neighbor).unwrap();
+        assert_eq!((failures.total, failures.consecutive), (1, 0));
+        assert_eq!(failures.last_reason, "bad signature from 192.168.1.2");
+        assert!(receiver.take_alerts().is_empty());
+
+        for _ in 0..3 {
+            assert!(receiver.verify(&signed(&stranger), neighbor).is_err());
+        }
+        let alerts = receiver.take_alerts();
+        assert_eq!(alerts.len(), 2);
+        assert!(matches!(alerts[0], AuthAlert::Failures { failures: 2, .. }));
+        assert_eq!(
+            alerts[1],
+            AuthAlert::LockedOut {
+                address: neighbor,
+                seconds: 300
+            }
+        );
+
+        // Even valid packets are ignored until the lockout ends or is cleared
+        assert!(receiver.verify(&signed(&sender), neighbor).is_err());
+        let failures = receiver.failures_of(neighbor).unwrap();
+        assert_eq!((failures.total, failures.ignored), (4, 1));
+        assert!(failures.locked_until.is_some());
+
+        assert!(receiver.clear_failures(neighbor));
+        receiver.verify(&signed(&sender), neighbor).unwrap();
+        assert!(receiver.all_failures().is_empty());
+    }
+
+    #[test]
+    fn unsigned_packets_follow_the_rollout_setting() {
+        let neighbor = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+        let unsigned = RipPacket::new_request();
+
+        let strict = PacketAuth::new(&config("k"));
+        assert!(strict.verify(&unsigned, neighbor).is_err());
+
+        strict.configure(&PacketAuthConfig {
+            accept_unsigned: true,
+            ..config("k")
+        });
+        strict.verify(&unsigned, neighbor).unwrap();
+
+        let disabled = PacketAuth::new(&PacketAuthConfig::default());
+        let mut packet = RipPacket::new_request();
+        disabled.sign(&mut packet).unwrap();
+        assert!(packet.auth.is_none());
+        assert!(!packet.to_json().unwrap().contains("auth"));
+    }
+}
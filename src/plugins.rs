@@ -0,0 +1,269 @@
+//! Compile-time plugin registry.
+//!
+//! Third-party crates extend import policy, export policy and telemetry by
+//! implementing [`RoutePolicyPlugin`] or [`EventSinkPlugin`] and registering
+//! an instance on [`PluginRegistry::global`] before the router starts. Rust
+//! has no stable ABI, so plugins are linked into the binary rather than
+//! loaded from shared objects; registered plugins can be switched off with
+//! `plugins.disabled` in the configuration.
+//!
+//! Route policy plugins run after the policy script, in registration order.
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, OnceLock, RwLock};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::events::{EventBus, WebEvent};
+use crate::policy::PolicyRoute;
+use crate::router::RECEIVE_HEARTBEAT;
+use crate::watchdog::Heartbeat;
+use crate::{RustRouteError, RustRouteResult};
+
+/// Import/export route filter. Return `None` to deny a route; the default
+/// implementations accept routes unchanged.
+pub trait RoutePolicyPlugin: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn import(&self, route: PolicyRoute) -> Option<PolicyRoute> {
+        Some(route)
+    }
+
+    fn export(&self, route: PolicyRoute) -> Option<PolicyRoute> {
+        Some(route)
+    }
+}
+
+/// Receiver for every event published on the event bus. Called from a
+/// shared dispatch task, so slow work should be handed off to a spawned task.
+pub trait EventSinkPlugin: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn handle(&self, router_id: &str, event: &WebEvent);
+}
+
+/// Plugin configuration
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct PluginsConfig {
+    /// Names of registered plugins to leave inactive
+    #[serde(default)]
+    pub disabled: Vec<String>,
+}
+
+#[derive(Default)]
+pub struct PluginRegistry {
+    route_policies: RwLock<Vec<Arc<dyn RoutePolicyPlugin>>>,
+    event_sinks: RwLock<Vec<Arc<dyn EventSinkPlugin>>>,
+}
+
+impl PluginRegistry {
+    /// Process-wide registry consulted by the router and the daemon
+    pub fn global() -> &'static PluginRegistry {
+        static REGISTRY: OnceLock<PluginRegistry> = OnceLock::new();
+        REGISTRY.get_or_init(PluginRegistry::default)
+    }
+
+    pub fn register_route_policy<P>(&self, plugin: P) -> RustRouteResult<()>
+    where
+        P: RoutePolicyPlugin + 'static,
+    {
+        self.ensure_unique(plugin.name())?;
+        write(&self.route_policies).push(Arc::new(plugin));
+        Ok(())
+    }
+
+    pub fn register_event_sink<S>(&self, plugin: S) -> RustRouteResult<()>
+    where
+        S: EventSinkPlugin + 'static,
+    {
+        self.ensure_unique(plugin.name())?;
+        write(&self.event_sinks).push(Arc::new(plugin));
+        Ok(())
+    }
+
+    fn ensure_unique(&self, name: &str) -> RustRouteResult<()> {
+        if self.names().iter().any(|existing| existing == name) {
+            return Err(RustRouteError::InvalidInput(format!(
+                "Plugin {} is already registered",
+                name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Names of every registered plugin, policies first
+    pub fn names(&self) -> Vec<String> {
+        let policies = read(&self.route_policies);
+        let sinks = read(&self.event_sinks);
+        policies
+            .iter()
+            .map(|plugin| plugin.name().to_string())
+            .chain(sinks.iter().map(|plugin| plugin.name().to_string()))
+            .collect()
+    }
+
+    pub fn route_policies(&self, config: &PluginsConfig) -> Vec<Arc<dyn RoutePolicyPlugin>> {
+        read(&self.route_policies)
+            .iter()
+            .filter(|plugin| !config.disabled.iter().any(|name| name == plugin.name()))
+            .cloned()
+            .collect()
+    }
+
+    pub fn event_sinks(&self, config: &PluginsConfig) -> Vec<Arc<dyn EventSinkPlugin>> {
+        read(&self.event_sinks)
+            .iter()
+            .filter(|plugin| !config.disabled.iter().any(|name| name == plugin.name()))
+            .cloned()
+            .collect()
+    }
+}
+
+fn read<T>(lock: &RwLock<T>) -> std::sync::RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn write<T>(lock: &RwLock<T>) -> std::sync::RwLockWriteGuard<'_, T> {
+    lock.write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Deliver events to sink plugins until the bus closes or the task is aborted
+pub async fn run_sinks(
+    sinks: Vec<Arc<dyn EventSinkPlugin>>,
+    router_id: String,
+    events: EventBus,
+    heartbeat: Heartbeat,
+) {
+    let mut receiver = events.subscribe("plugins");
+    let mut liveness = tokio::time::interval(RECEIVE_HEARTBEAT);
+    let names: Vec<&str> = sinks.iter().map(|sink| sink.name()).collect();
+    info!("Event sink plugins active: {}", names.join(", "));
+
+    loop {
+        tokio::select! {
+            _ = liveness.tick() => heartbeat.beat(),
+            event = receiver.recv() => match event {
+                Ok(event) => {
+                    for sink in &sinks {
+                        sink.handle(&router_id, &event);
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Event sink plugins skipped {} events", skipped);
+                }
+                Err(RecvError::Closed) => return,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::ActivityLevel;
+    use crate::policy::{Direction, PolicyHandle};
+    use crate::routing_table::RouteSource;
+    use crate::watchdog::{Watchdog, WatchdogConfig};
+    use std::net::Ipv4Addr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    struct PreferLocal;
+
+    impl RoutePolicyPlugin for PreferLocal {
+        fn name(&self) -> &str {
+            "prefer-local"
+        }
+
+        fn import(&self, mut route: PolicyRoute) -> Option<PolicyRoute> {
+            if route.destination.octets()[0] != 10 {
+                return None;
+            }
+            route.metric = 1;
+            Some(route)
+        }
+    }
+
+    struct Counter(Arc<AtomicUsize>);
+
+    impl EventSinkPlugin for Counter {
+        fn name(&self) -> &str {
+            "counter"
+        }
+
+        fn handle(&self, _router_id: &str, _event: &WebEvent) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn route(destination: Ipv4Addr) -> PolicyRoute {
+        PolicyRoute {
+            destination,
+            subnet_mask: Ipv4Addr::new(255, 255, 0, 0),
+            next_hop: Ipv4Addr::new(192, 168, 1, 2),
+            metric: 4,
+            tag: 0,
+            neighbor: None,
+            interface: "eth0".to_string(),
+            source: RouteSource::Dynamic,
+        }
+    }
+
+    #[test]
+    fn policy_plugins_join_the_chain() {
+        let registry = PluginRegistry::default();
+        registry.register_route_policy(PreferLocal).unwrap();
+        assert!(registry.register_route_policy(PreferLocal).is_err());
+
+        let handle = PolicyHandle::default();
+        assert!(handle.active(Direction::Import).is_none());
+        handle.set_plugins(registry.route_policies(&PluginsConfig::default()));
+
+        let chain = handle.active(Direction::Import).unwrap();
+        let local = chain
+            .evaluate(Direction::Import, route(Ipv4Addr::new(10, 1, 0, 0)))
+            .unwrap();
+        assert_eq!(local.metric, 1);
+        assert!(chain
+            .evaluate(Direction::Import, route(Ipv4Addr::new(172, 16, 0, 0)))
+            .is_none());
+        assert_eq!(
+            chain
+                .evaluate(Direction::Export, route(Ipv4Addr::new(172, 16, 0, 0)))
+                .unwrap()
+                .metric,
+            4
+        );
+
+        let disabled = PluginsConfig {
+            disabled: vec!["prefer-local".to_string()],
+        };
+        assert!(registry.route_policies(&disabled).is_empty());
+    }
+
+    #[tokio::test]
+    async fn sinks_receive_bus_events() {
+        let registry = PluginRegistry::default();
+        let seen = Arc::new(AtomicUsize::new(0));
+        registry
+            .register_event_sink(Counter(Arc::clone(&seen)))
+            .unwrap();
+
+        let bus = EventBus::new(16);
+        let watchdog = Watchdog::new(WatchdogConfig::default());
+        let task = tokio::spawn(run_sinks(
+            registry.event_sinks(&PluginsConfig::default()),
+            "10.0.0.1".to_string(),
+            bus.clone(),
+            watchdog.register("plugin-sinks", RECEIVE_HEARTBEAT),
+        ));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        bus.publish_activity(ActivityLevel::Info, "one");
+        bus.publish_activity(ActivityLevel::Info, "two");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        task.abort();
+        assert_eq!(seen.load(Ordering::SeqCst), 2);
+    }
+}
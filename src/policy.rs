@@ -0,0 +1,1092 @@
+//! Scripted route policy.
+//!
+//! Optional Rhai scripts evaluated for every route received from a neighbor
+//! (import) and every route advertised on an interface (export). A script
+//! sees a `route` map with `prefix`, `destination`, `mask`, `prefix_len`,
+//! `metric`, `tag`, `next_hop`, `neighbor`, `interface`, `source` and
+//! `direction`. It may rewrite `route.metric`, `route.tag` and
+//! `route.next_hop`, and finishes with `true`/`"accept"` (or no value) to
+//! accept or `false`/`"deny"` to drop the route. Scripts that fail at
+//! runtime deny the route.
+//!
+//! Before any script, distribute lists filter routes by prefix: a named
+//! prefix list applied to the routes received (`in`) or advertised (`out`)
+//! on an interface, e.g. to refuse 10.0.0.0/8 from the neighbors on eth1.
+//! Offset lists add to the metric of the routes received from an interface
+//! or neighbor, or advertised on an interface, steering traffic without
+//! touching the routers at the other end.
+
+use chrono::{DateTime, Utc};
+use ipnet::Ipv4Net;
+use log::warn;
+use rhai::{Dynamic, Engine, Map, Scope, AST};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::net::Ipv4Addr;
+use std::sync::{Arc, RwLock};
+
+use crate::plugins::RoutePolicyPlugin;
+use crate::protocol::RipEntry;
+use crate::routing_table::{Route, RouteSource};
+use crate::{RustRouteError, RustRouteResult};
+
+/// Route policy configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyConfig {
+    /// Script applied to routes learned from neighbors
+    #[serde(default)]
+    pub import_script: Option<String>,
+    /// Script applied to routes before they are advertised
+    #[serde(default)]
+    pub export_script: Option<String>,
+    /// Upper bound on script operations per route, guarding against runaway loops
+    pub max_operations: u64,
+    /// Named prefix lists, referenced by the distribute lists
+    #[serde(default)]
+    pub prefix_lists: Vec<PrefixList>,
+    /// Prefix lists applied to the routes received or advertised on an interface
+    #[serde(default)]
+    pub distribute_lists: Vec<DistributeList>,
+    /// Metrics added to the routes received or advertised on an interface
+    #[serde(default)]
+    pub offset_lists: Vec<OffsetList>,
+}
+
+impl Default for PolicyConfig {
+    fn default() -> Self {
+        Self {
+            import_script: None,
+            export_script: None,
+            max_operations: 100_000,
+            prefix_lists: Vec::new(),
+            distribute_lists: Vec::new(),
+            offset_lists: Vec::new(),
+        }
+    }
+}
+
+impl PolicyConfig {
+    /// The distribute lists with their prefix lists; a list naming an unknown
+    /// prefix list is left out, which validation reports
+    pub fn resolve_distribute_lists(&self) -> Vec<(DistributeList, PrefixList)> {
+        self.distribute_lists
+            .iter()
+            .filter_map(|list| {
+                let prefix_list = self
+                    .prefix_lists
+                    .iter()
+                    .find(|prefix_list| prefix_list.name == list.prefix_list)?;
+                Some((list.clone(), prefix_list.clone()))
+            })
+            .collect()
+    }
+
+    /// The offset lists with their prefix lists, if any; a list naming an
+    /// unknown prefix list is left out, which validation reports
+    pub fn resolve_offset_lists(&self) -> Vec<(OffsetList, Option<PrefixList>)> {
+        self.offset_lists
+            .iter()
+            .filter_map(|list| {
+                let prefix_list = match &list.prefix_list {
+                    Some(name) => Some(
+                        self.prefix_lists
+                            .iter()
+                            .find(|prefix_list| prefix_list.name == *name)?
+                            .clone(),
+                    ),
+                    None => None,
+                };
+                Some((list.clone(), prefix_list))
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Permit,
+    Deny,
+}
+
+/// One rule of a prefix list. It matches `prefix` itself or, with `ge` or
+/// `le`, the prefixes inside it whose length lies in that range
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrefixListRule {
+    pub action: Action,
+    pub prefix: Ipv4Net,
+    /// Shortest prefix length matched; the length of `prefix` when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ge: Option<u8>,
+    /// Longest prefix length matched; 32 when only `ge` is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub le: Option<u8>,
+}
+
+impl PrefixListRule {
+    /// The prefix lengths matched
+    pub fn lengths(&self) -> (u8, u8) {
+        let len = self.prefix.prefix_len();
+        match (self.ge, self.le) {
+            (None, None) => (len, len),
+            (ge, le) => (ge.unwrap_or(len), le.unwrap_or(32)),
+        }
+    }
+
+    fn matches(&self, prefix: &Ipv4Net) -> bool {
+        let (min, max) = self.lengths();
+        self.prefix.trunc().contains(prefix) && (min..=max).contains(&prefix.prefix_len())
+    }
+}
+
+/// Rules checked in order: the first that matches a prefix decides, and a
+/// prefix none matches is denied
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrefixList {
+    pub name: String,
+    pub rules: Vec<PrefixListRule>,
+}
+
+impl PrefixList {
+    pub fn permits(&self, prefix: &Ipv4Net) -> bool {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(prefix))
+            .is_some_and(|rule| rule.action == Action::Permit)
+    }
+}
+
+/// A prefix list applied to the routes crossing an interface one way
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DistributeList {
+    pub prefix_list: String,
+    /// `in` filters received routes, `out` advertised ones
+    pub direction: Direction,
+    /// Every interface when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interface: Option<String>,
+}
+
+impl DistributeList {
+    fn applies_to(&self, direction: Direction, interface: &str) -> bool {
+        self.direction == direction
+            && self
+                .interface
+                .as_deref()
+                .is_none_or(|name| name == interface)
+    }
+}
+
+/// A metric added to the routes crossing an interface one way, like a Cisco
+/// offset list
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OffsetList {
+    /// `in` adds to received routes, `out` to advertised ones
+    pub direction: Direction,
+    pub offset: u32,
+    /// Every interface when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interface: Option<String>,
+    /// Only the routes received from this neighbor; `in` lists only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub neighbor: Option<Ipv4Addr>,
+    /// Only the prefixes this prefix list permits; every prefix when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix_list: Option<String>,
+}
+
+/// The offset lists in force, with the metric they cannot raise a route past
+#[derive(Debug, Default)]
+struct Offsets {
+    lists: Vec<(OffsetList, Option<PrefixList>)>,
+    infinity: u32,
+}
+
+impl Offsets {
+    /// The offsets of every list matching the route, added up
+    fn total(
+        &self,
+        direction: Direction,
+        interface: &str,
+        neighbor: Option<Ipv4Addr>,
+        destination: Ipv4Addr,
+        subnet_mask: Ipv4Addr,
+    ) -> u32 {
+        let prefix = Ipv4Net::with_netmask(destination, subnet_mask).map(|net| net.trunc());
+        self.lists
+            .iter()
+            .filter(|(list, prefix_list)| {
+                list.direction == direction
+                    && list
+                        .interface
+                        .as_deref()
+                        .is_none_or(|name| name == interface)
+                    && list.neighbor.is_none_or(|ip| neighbor == Some(ip))
+                    && prefix_list.as_ref().is_none_or(|prefix_list| {
+                        prefix
+                            .as_ref()
+                            .is_ok_and(|prefix| prefix_list.permits(prefix))
+                    })
+            })
+            .fold(0, |total, (list, _)| total.saturating_add(list.offset))
+    }
+}
+
+/// Whether every distribute list for `direction` on `interface` permits the
+/// prefix; a malformed mask matches no rule
+fn distributes(
+    lists: &[(DistributeList, PrefixList)],
+    direction: Direction,
+    interface: &str,
+    destination: Ipv4Addr,
+    subnet_mask: Ipv4Addr,
+) -> bool {
+    let prefix = Ipv4Net::with_netmask(destination, subnet_mask).map(|net| net.trunc());
+    lists
+        .iter()
+        .filter(|(list, _)| list.applies_to(direction, interface))
+        .all(|(_, prefix_list)| {
+            prefix
+                .as_ref()
+                .is_ok_and(|prefix| prefix_list.permits(prefix))
+        })
+}
+
+/// A prefix advertised only to some neighbors, under `rip.restricted_prefixes`.
+/// Routes inside `prefix` are restricted with it, so advertising the block
+/// in pieces cannot leak it; an aggregate that covers it is advertised as usual.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrefixRestriction {
+    pub prefix: Ipv4Net,
+    /// Neighbors allowed the prefix, by the address their updates come from
+    pub neighbors: Vec<Ipv4Addr>,
+}
+
+impl PrefixRestriction {
+    fn covers(&self, entry: &RipEntry) -> bool {
+        Ipv4Net::with_netmask(entry.ip_address, entry.subnet_mask)
+            .is_ok_and(|net| self.prefix.contains(&net.trunc()))
+    }
+}
+
+/// Entries exported on one interface, split by the prefix restrictions
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Advertisement {
+    /// What every neighbor may receive
+    pub shared: Vec<RipEntry>,
+    /// Restricted entries with the neighbors allowed each
+    pub restricted: Vec<(RipEntry, Vec<Ipv4Addr>)>,
+}
+
+impl Advertisement {
+    /// Neighbors allowed at least one restricted entry
+    pub fn neighbors(&self) -> BTreeSet<Ipv4Addr> {
+        self.restricted
+            .iter()
+            .flat_map(|(_, neighbors)| neighbors.iter().copied())
+            .collect()
+    }
+
+    /// The restricted entries `neighbor` may receive
+    pub fn restricted_to(&self, neighbor: Ipv4Addr) -> Vec<RipEntry> {
+        self.restricted
+            .iter()
+            .filter(|(_, neighbors)| neighbors.contains(&neighbor))
+            .map(|(entry, _)| entry.clone())
+            .collect()
+    }
+
+    /// Everything `neighbor` may receive
+    pub fn for_neighbor(&self, neighbor: Ipv4Addr) -> Vec<RipEntry> {
+        let mut entries = self.shared.clone();
+        entries.extend(self.restricted_to(neighbor));
+        entries
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    #[serde(rename = "in")]
+    Import,
+    #[serde(rename = "out")]
+    Export,
+}
+
+impl Direction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Import => "import",
+            Direction::Export => "export",
+        }
+    }
+}
+
+/// The route attributes a policy script can inspect and rewrite
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyRoute {
+    pub destination: Ipv4Addr,
+    pub subnet_mask: Ipv4Addr,
+    pub next_hop: Ipv4Addr,
+    pub metric: u32,
+    pub tag: u16,
+    pub neighbor: Option<Ipv4Addr>,
+    /// Receiving interface on import, advertising interface on export
+    pub interface: String,
+    pub source: RouteSource,
+}
+
+impl PolicyRoute {
+    pub fn from_route(route: &Route, interface: &str) -> Self {
+        Self {
+            destination: route.destination,
+            subnet_mask: route.subnet_mask,
+            next_hop: route.next_hop,
+            metric: route.metric,
+            tag: route.tag,
+            neighbor: route.learned_from,
+            interface: interface.to_string(),
+            source: route.source,
+        }
+    }
+
+    fn to_map(&self, direction: Direction) -> Map {
+        let prefix_len = u32::from(self.subnet_mask).count_ones();
+        let text = |value: String| Dynamic::from(value);
+        let mut map = Map::new();
+        map.insert(
+            "prefix".into(),
+            text(format!("{}/{}", self.destination, prefix_len)),
+        );
+        map.insert("destination".into(), text(self.destination.to_string()));
+        map.insert("mask".into(), text(self.subnet_mask.to_string()));
+        map.insert("prefix_len".into(), Dynamic::from(prefix_len as i64));
+        map.insert("metric".into(), Dynamic::from(self.metric as i64));
+        map.insert("tag".into(), Dynamic::from(self.tag as i64));
+        map.insert("next_hop".into(), text(self.next_hop.to_string()));
+        map.insert(
+            "neighbor".into(),
+            self.neighbor
+                .map(|ip| text(ip.to_string()))
+                .unwrap_or(Dynamic::UNIT),
+        );
+        map.insert("interface".into(), text(self.interface.clone()));
+        map.insert("source".into(), text(self.source.as_str().to_string()));
+        map.insert("direction".into(), text(direction.as_str().to_string()));
+        map
+    }
+
+    fn apply_map(&mut self, map: &Map) -> Result<(), String> {
+        if let Some(value) = map.get("metric") {
+            let metric = value.as_int().map_err(|_| "metric must be an integer")?;
+            self.metric = u32::try_from(metric).map_err(|_| "metric out of range")?;
+        }
+        if let Some(value) = map.get("tag") {
+            let tag = value.as_int().map_err(|_| "tag must be an integer")?;
+            self.tag = u16::try_from(tag).map_err(|_| "tag out of range")?;
+        }
+        if let Some(value) = map.get("next_hop") {
+            let next_hop = value
+                .clone()
+                .into_string()
+                .map_err(|_| "next_hop must be a string")?;
+            self.next_hop = next_hop
+                .parse()
+                .map_err(|_| format!("invalid next_hop {}", next_hop))?;
+        }
+        Ok(())
+    }
+}
+
+/// Compiled import/export scripts
+pub struct RoutePolicy {
+    engine: Engine,
+    import: Option<AST>,
+    export: Option<AST>,
+}
+
+impl std::fmt::Debug for RoutePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RoutePolicy")
+            .field("import", &self.import.is_some())
+            .field("export", &self.export.is_some())
+            .finish()
+    }
+}
+
+impl RoutePolicy {
+    /// Read and compile the configured scripts; `None` when no script is set
+    pub fn load(config: &PolicyConfig) -> RustRouteResult<Option<Self>> {
+        if config.import_script.is_none() && config.export_script.is_none() {
+            return Ok(None);
+        }
+
+        let mut engine = Engine::new();
+        engine.set_max_operations(config.max_operations);
+
+        let compile = |path: &Option<String>| -> RustRouteResult<Option<AST>> {
+            let Some(path) = path else {
+                return Ok(None);
+            };
+            let source = std::fs::read_to_string(path).map_err(|e| {
+                RustRouteError::ConfigError(format!("Failed to read policy {}: {}", path, e))
+            })?;
+            Self::compile(&engine, &source)
+                .map(Some)
+                .map_err(|e| RustRouteError::ConfigError(format!("Policy {}: {}", path, e)))
+        };
+        let import = compile(&config.import_script)?;
+        let export = compile(&config.export_script)?;
+
+        Ok(Some(Self {
+            engine,
+            import,
+            export,
+        }))
+    }
+
+    /// Build a policy from script source, mainly for tests and tooling
+    pub fn from_source(
+        import: Option<&str>,
+        export: Option<&str>,
+        max_operations: u64,
+    ) -> RustRouteResult<Self> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(max_operations);
+        let compile = |source: Option<&str>| {
+            source
+                .map(|source| Self::compile(&engine, source))
+                .transpose()
+                .map_err(RustRouteError::ConfigError)
+        };
+        let import = compile(import)?;
+        let export = compile(export)?;
+        Ok(Self {
+            engine,
+            import,
+            export,
+        })
+    }
+
+    fn compile(engine: &Engine, source: &str) -> Result<AST, String> {
+        engine.compile(source).map_err(|e| e.to_string())
+    }
+
+    /// Run the script for `direction`; returns the possibly rewritten route,
+    /// or `None` when it is denied
+    pub fn evaluate(&self, direction: Direction, route: PolicyRoute) -> Option<PolicyRoute> {
+        let ast = match direction {
+            Direction::Import => self.import.as_ref(),
+            Direction::Export => self.export.as_ref(),
+        };
+        let Some(ast) = ast else {
+            return Some(route);
+        };
+
+        let prefix = format!("{}/{}", route.destination, route.subnet_mask);
+        match self.run(ast, direction, route) {
+            Ok(verdict) => verdict,
+            Err(err) => {
+                warn!(
+                    "{} policy failed for {}; denying route: {}",
+                    direction.as_str(),
+                    prefix,
+                    err
+                );
+                None
+            }
+        }
+    }
+
+    fn run(
+        &self,
+        ast: &AST,
+        direction: Direction,
+        mut route: PolicyRoute,
+    ) -> Result<Option<PolicyRoute>, String> {
+        let mut scope = Scope::new();
+        scope.push("route", route.to_map(direction));
+        let result: Dynamic = self
+            .engine
+            .eval_ast_with_scope(&mut scope, ast)
+            .map_err(|e| e.to_string())?;
+
+        let accept = if result.is_unit() {
+            true
+        } else if let Ok(accept) = result.as_bool() {
+            accept
+        } else if result.is_string() {
+            match result.into_string()?.as_str() {
+                "accept" => true,
+                "deny" => false,
+                other => return Err(format!("unknown verdict \"{}\"", other)),
+            }
+        } else {
+            return Err(format!("unexpected result of type {}", result.type_name()));
+        };
+        if !accept {
+            return Ok(None);
+        }
+
+        let map = scope
+            .get_value::<Map>("route")
+            .ok_or("route must remain a map")?;
+        route.apply_map(&map)?;
+        Ok(Some(route))
+    }
+
+    pub fn has_import(&self) -> bool {
+        self.import.is_some()
+    }
+
+    pub fn has_export(&self) -> bool {
+        self.export.is_some()
+    }
+}
+
+/// The script and plugins applied to one route, in that order
+#[derive(Clone, Default)]
+pub struct PolicyChain {
+    script: Option<Arc<RoutePolicy>>,
+    plugins: Vec<Arc<dyn RoutePolicyPlugin>>,
+}
+
+impl std::fmt::Debug for PolicyChain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let plugins: Vec<&str> = self.plugins.iter().map(|plugin| plugin.name()).collect();
+        f.debug_struct("PolicyChain")
+            .field("script", &self.script)
+            .field("plugins", &plugins)
+            .finish()
+    }
+}
+
+impl PolicyChain {
+    fn applies_to(&self, direction: Direction) -> bool {
+        let scripted = self.script.as_ref().is_some_and(|script| match direction {
+            Direction::Import => script.has_import(),
+            Direction::Export => script.has_export(),
+        });
+        scripted || !self.plugins.is_empty()
+    }
+
+    /// Run the route through the script, then each plugin; `None` once any denies it
+    pub fn evaluate(&self, direction: Direction, route: PolicyRoute) -> Option<PolicyRoute> {
+        let mut route = match &self.script {
+            Some(script) => script.evaluate(direction, route)?,
+            None => route,
+        };
+        for plugin in &self.plugins {
+            route = match direction {
+                Direction::Import => plugin.import(route)?,
+                Direction::Export => plugin.export(route)?,
+            };
+        }
+        Some(route)
+    }
+}
+
+/// Maintenance mode: every route is advertised with at least `metric`, so
+/// neighbors move their traffic to other paths while adjacencies stay up
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Maintenance {
+    pub metric: u32,
+    pub since: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl Maintenance {
+    /// Metric advertised when none is given: two below infinity, so the
+    /// router is still a last resort once neighbors add their own hop
+    pub fn default_metric(infinity: u32) -> u32 {
+        infinity.saturating_sub(2).max(1)
+    }
+}
+
+/// Shared, swappable policy so configuration reloads reach running tasks
+#[derive(Debug, Clone, Default)]
+pub struct PolicyHandle {
+    inner: Arc<RwLock<PolicyChain>>,
+    maintenance: Arc<RwLock<Option<Maintenance>>>,
+    restrictions: Arc<RwLock<Vec<PrefixRestriction>>>,
+    distribute_lists: Arc<RwLock<Vec<(DistributeList, PrefixList)>>>,
+    offsets: Arc<RwLock<Offsets>>,
+}
+
+impl PolicyHandle {
+    pub fn new(policy: Option<RoutePolicy>) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(PolicyChain {
+                script: policy.map(Arc::new),
+                plugins: Vec::new(),
+            })),
+            maintenance: Arc::default(),
+            restrictions: Arc::default(),
+            distribute_lists: Arc::default(),
+            offsets: Arc::default(),
+        }
+    }
+
+    pub fn current(&self) -> Option<Arc<RoutePolicy>> {
+        self.read().script.clone()
+    }
+
+    pub fn replace(&self, policy: Option<RoutePolicy>) {
+        self.write().script = policy.map(Arc::new);
+    }
+
+    pub fn set_plugins(&self, plugins: Vec<Arc<dyn RoutePolicyPlugin>>) {
+        self.write().plugins = plugins;
+    }
+
+    /// The chain to apply in `direction`, or `None` when nothing is configured
+    pub fn active(&self, direction: Direction) -> Option<PolicyChain> {
+        let chain = self.read();
+        chain.applies_to(direction).then(|| chain.clone())
+    }
+
+    pub fn maintenance(&self) -> Option<Maintenance> {
+        self.maintenance
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// Enter maintenance mode, or leave it with `None`; returns the previous mode
+    pub fn set_maintenance(&self, maintenance: Option<Maintenance>) -> Option<Maintenance> {
+        std::mem::replace(
+            &mut *self
+                .maintenance
+                .write()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()),
+            maintenance,
+        )
+    }
+
+    pub fn set_restrictions(&self, restrictions: Vec<PrefixRestriction>) {
+        *self
+            .restrictions
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = restrictions;
+    }
+
+    pub fn set_distribute_lists(&self, config: &PolicyConfig) {
+        *self
+            .distribute_lists
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = config.resolve_distribute_lists();
+    }
+
+    /// Whether the distribute lists let `destination`/`subnet_mask` be
+    /// received (`Import`) or advertised (`Export`) on `interface`
+    pub fn distributes(
+        &self,
+        direction: Direction,
+        interface: &str,
+        destination: Ipv4Addr,
+        subnet_mask: Ipv4Addr,
+    ) -> bool {
+        let lists = self
+            .distribute_lists
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        distributes(&lists, direction, interface, destination, subnet_mask)
+    }
+
+    /// Apply the offset lists of `config`; advertised metrics stop at `infinity`
+    pub fn set_offset_lists(&self, config: &PolicyConfig, infinity: u32) {
+        *self
+            .offsets
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Offsets {
+            lists: config.resolve_offset_lists(),
+            infinity,
+        };
+    }
+
+    /// The metric the offset lists add to `destination`/`subnet_mask` when
+    /// received from `neighbor` (`Import`) or advertised (`Export`) on `interface`
+    pub fn offset(
+        &self,
+        direction: Direction,
+        interface: &str,
+        neighbor: Option<Ipv4Addr>,
+        destination: Ipv4Addr,
+        subnet_mask: Ipv4Addr,
+    ) -> u32 {
+        self.offsets
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .total(direction, interface, neighbor, destination, subnet_mask)
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, PolicyChain> {
+        self.inner
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, PolicyChain> {
+        self.inner
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Apply the outbound distribute lists, the export policy, the outbound
+    /// offset lists, then maintenance mode, to routes advertised on
+    /// `interface`; restricted prefixes are left out
+    pub fn export_entries(&self, routes: Vec<Route>, interface: &str) -> Vec<RipEntry> {
+        self.export(routes, interface).shared
+    }
+
+    /// Like [`PolicyHandle::export_entries`], keeping the restricted prefixes
+    /// apart with the neighbors allowed them
+    pub fn export(&self, routes: Vec<Route>, interface: &str) -> Advertisement {
+        let chain = self.active(Direction::Export);
+        let floor = self
+            .maintenance()
+            .map_or(0, |maintenance| maintenance.metric);
+        let restrictions = self
+            .restrictions
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let lists = self
+            .distribute_lists
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let offsets = self
+            .offsets
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut advertisement = Advertisement::default();
+        for route in routes {
+            if !distributes(
+                &lists,
+                Direction::Export,
+                interface,
+                route.destination,
+                route.subnet_mask,
+            ) {
+                continue;
+            }
+            let route = PolicyRoute::from_route(&route, interface);
+            let route = match &chain {
+                Some(chain) => match chain.evaluate(Direction::Export, route) {
+                    Some(route) => route,
+                    None => continue,
+                },
+                None => route,
+            };
+            let mut metric = route.metric;
+            // Unreachable routes stay at infinity
+            if metric < offsets.infinity {
+                let offset = offsets.total(
+                    Direction::Export,
+                    interface,
+                    None,
+                    route.destination,
+                    route.subnet_mask,
+                );
+                metric = metric.saturating_add(offset).min(offsets.infinity);
+            }
+            let entry = RipEntry {
+                address_family: 2,
+                route_tag: route.tag,
+                ip_address: route.destination,
+                subnet_mask: route.subnet_mask,
+                next_hop: route.next_hop,
+                metric: metric.max(floor),
+            };
+            let mut allowed: Option<Vec<Ipv4Addr>> = None;
+            for restriction in restrictions.iter().filter(|r| r.covers(&entry)) {
+                // Nested restrictions all apply
+                let neighbors = allowed.get_or_insert_with(|| restriction.neighbors.clone());
+                neighbors.retain(|neighbor| restriction.neighbors.contains(neighbor));
+            }
+            match allowed {
+                Some(neighbors) => advertisement.restricted.push((entry, neighbors)),
+                None => advertisement.shared.push(entry),
+            }
+        }
+        advertisement
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> PolicyRoute {
+        PolicyRoute {
+            destination: Ipv4Addr::new(192, 168, 10, 0),
+            subnet_mask: Ipv4Addr::new(255, 255, 255, 0),
+            next_hop: Ipv4Addr::new(10, 0, 0, 2),
+            metric: 2,
+            tag: 0,
+            neighbor: Some(Ipv4Addr::new(10, 0, 0, 2)),
+            interface: "eth0".to_string(),
+            source: RouteSource::Dynamic,
+        }
+    }
+
+    #[test]
+    fn scripts_accept_deny_and_modify() {
+        let policy = RoutePolicy::from_source(
+            Some(
+                r#"
+                if route.prefix_len > 24 { return "deny"; }
+                if route.neighbor == "10.0.0.2" {
+                    route.metric += 3;
+                    route.tag = 42;
+                }
+                "#,
+            ),
+            Some(r#"route.interface != "eth0""#),
+            10_000,
+        )
+        .unwrap();
+
+        let imported = policy.evaluate(Direction::Import, sample()).unwrap();
+        assert_eq!(imported.metric, 5);
+        assert_eq!(imported.tag, 42);
+
+        let mut host = sample();
+        host.subnet_mask = Ipv4Addr::new(255, 255, 255, 255);
+        assert!(policy.evaluate(Direction::Import, host).is_none());
+
+        assert!(policy.evaluate(Direction::Export, sample()).is_none());
+        let mut other = sample();
+        other.interface = "eth1".to_string();
+        assert_eq!(
+            policy.evaluate(Direction::Export, other.clone()),
+            Some(other)
+        );
+    }
+
+    #[test]
+    fn failing_scripts_deny() {
+        let policy = RoutePolicy::from_source(
+            Some("loop { }"),
+            Some(r#"route.next_hop = "not-an-ip"; true"#),
+            1_000,
+        )
+        .unwrap();
+        assert!(policy.evaluate(Direction::Import, sample()).is_none());
+        assert!(policy.evaluate(Direction::Export, sample()).is_none());
+
+        assert!(RoutePolicy::from_source(Some("if {"), None, 1_000).is_err());
+    }
+
+    #[test]
+    fn maintenance_raises_advertised_metrics() {
+        let handle = PolicyHandle::default();
+        let mask = Ipv4Addr::new(255, 255, 255, 0);
+        let routes = vec![
+            Route::new_direct(Ipv4Addr::new(10, 0, 0, 0), mask, "eth0".to_string()),
+            Route::new(
+                Ipv4Addr::new(10, 9, 0, 0),
+                mask,
+                Ipv4Addr::new(10, 0, 0, 2),
+                16,
+                "eth0".to_string(),
+                RouteSource::Dynamic,
+                Some(Ipv4Addr::new(10, 0, 0, 2)),
+            ),
+        ];
+        let metrics = |handle: &PolicyHandle| -> Vec<u32> {
+            handle
+                .export_entries(routes.clone(), "eth1")
+                .iter()
+                .map(|entry| entry.metric)
+                .collect()
+        };
+
+        assert!(handle
+            .set_maintenance(Some(Maintenance {
+                metric: 15,
+                since: Utc::now(),
+                reason: None,
+            }))
+            .is_none());
+        assert_eq!(metrics(&handle), vec![15, 16]);
+        assert!(handle.set_maintenance(None).is_some());
+        assert_eq!(metrics(&handle), vec![1, 16]);
+    }
+
+    #[test]
+    fn restricted_prefixes_reach_only_their_neighbors() {
+        let handle = PolicyHandle::default();
+        let peer = Ipv4Addr::new(10, 0, 0, 2);
+        let other = Ipv4Addr::new(10, 0, 0, 3);
+        handle.set_restrictions(vec![
+            PrefixRestriction {
+                prefix: "10.99.0.0/16".parse().unwrap(),
+                neighbors: vec![peer, other],
+            },
+            PrefixRestriction {
+                prefix: "10.99.7.0/24".parse().unwrap(),
+                neighbors: vec![peer],
+            },
+        ]);
+        let direct = |destination: [u8; 4], prefix_len: u8| {
+            let net = Ipv4Net::new(destination.into(), prefix_len).unwrap();
+            Route::new_direct(net.network(), net.netmask(), "eth0".to_string())
+        };
+        let routes = vec![
+            direct([10, 99, 0, 0], 16),
+            direct([10, 99, 7, 0], 24),
+            // An aggregate covering the restricted block is not restricted
+            direct([10, 0, 0, 0], 8),
+        ];
+
+        let advertisement = handle.export(routes.clone(), "eth1");
+        let prefixes = |entries: Vec<RipEntry>| -> Vec<String> {
+            entries
+                .iter()
+                .map(|entry| {
+                    Ipv4Net::with_netmask(entry.ip_address, entry.subnet_mask)
+                        .unwrap()
+                        .to_string()
+                })
+                .collect()
+        };
+        assert_eq!(prefixes(advertisement.shared.clone()), ["10.0.0.0/8"]);
+        assert_eq!(
+            advertisement.neighbors().into_iter().collect::<Vec<_>>(),
+            [peer, other]
+        );
+        assert_eq!(
+            prefixes(advertisement.restricted_to(peer)),
+            ["10.99.0.0/16", "10.99.7.0/24"]
+        );
+        assert_eq!(
+            prefixes(advertisement.for_neighbor(other)),
+            ["10.0.0.0/8", "10.99.0.0/16"]
+        );
+        assert_eq!(handle.export_entries(routes, "eth1").len(), 1);
+    }
+
+    #[test]
+    fn distribute_lists_filter_by_prefix_list_direction_and_interface() {
+        let rule = |action, prefix: &str, ge, le| PrefixListRule {
+            action,
+            prefix: prefix.parse().unwrap(),
+            ge,
+            le,
+        };
+        let prefix_list = PrefixList {
+            name: "edge".to_string(),
+            rules: vec![
+                rule(Action::Permit, "10.1.0.0/16", None, None),
+                rule(Action::Deny, "10.0.0.0/8", None, Some(32)),
+                rule(Action::Permit, "192.168.0.0/16", Some(24), Some(28)),
+            ],
+        };
+        let permits = |prefix: &str| prefix_list.permits(&prefix.parse().unwrap());
+        assert!(permits("10.1.0.0/16"));
+        assert!(!permits("10.1.2.0/24"));
+        assert!(!permits("10.0.0.0/8"));
+        assert!(permits("192.168.5.0/24"));
+        assert!(!permits("192.168.0.0/16"));
+        assert!(!permits("192.168.5.0/30"));
+        // No rule matches
+        assert!(!permits("172.16.0.0/12"));
+
+        let handle = PolicyHandle::default();
+        handle.set_distribute_lists(&PolicyConfig {
+            prefix_lists: vec![prefix_list.clone()],
+            distribute_lists: vec![DistributeList {
+                prefix_list: "edge".to_string(),
+                direction: Direction::Export,
+                interface: Some("eth1".to_string()),
+            }],
+            ..PolicyConfig::default()
+        });
+        let mask = Ipv4Addr::new(255, 255, 255, 0);
+        let routes = vec![
+            Route::new_direct(Ipv4Addr::new(192, 168, 5, 0), mask, "eth0".to_string()),
+            Route::new_direct(Ipv4Addr::new(10, 1, 2, 0), mask, "eth0".to_string()),
+        ];
+        assert_eq!(handle.export_entries(routes.clone(), "eth1").len(), 1);
+        assert_eq!(handle.export_entries(routes, "eth2").len(), 2);
+        assert!(handle.distributes(Direction::Import, "eth1", Ipv4Addr::new(10, 1, 2, 0), mask));
+    }
+
+    #[test]
+    fn offset_lists_raise_matching_metrics_up_to_infinity() {
+        let neighbor = Ipv4Addr::new(10, 0, 0, 2);
+        let handle = PolicyHandle::default();
+        handle.set_offset_lists(
+            &PolicyConfig {
+                prefix_lists: vec![PrefixList {
+                    name: "lab".to_string(),
+                    rules: vec![PrefixListRule {
+                        action: Action::Permit,
+                        prefix: "10.9.0.0/16".parse().unwrap(),
+                        ge: None,
+                        le: Some(32),
+                    }],
+                }],
+                offset_lists: vec![
+                    OffsetList {
+                        direction: Direction::Export,
+                        offset: 5,
+                        interface: Some("eth1".to_string()),
+                        neighbor: None,
+                        prefix_list: None,
+                    },
+                    OffsetList {
+                        direction: Direction::Import,
+                        offset: 2,
+                        interface: None,
+                        neighbor: Some(neighbor),
+                        prefix_list: None,
+                    },
+                    OffsetList {
+                        direction: Direction::Import,
+                        offset: 1,
+                        interface: Some("eth0".to_string()),
+                        neighbor: None,
+                        prefix_list: Some("lab".to_string()),
+                    },
+                ],
+                ..PolicyConfig::default()
+            },
+            16,
+        );
+        let mask = Ipv4Addr::new(255, 255, 255, 0);
+        let lab = Ipv4Addr::new(10, 9, 1, 0);
+        let other = Ipv4Addr::new(172, 16, 1, 0);
+        assert_eq!(
+            handle.offset(Direction::Import, "eth0", Some(neighbor), lab, mask),
+            3
+        );
+        assert_eq!(
+            handle.offset(Direction::Import, "eth0", Some(neighbor), other, mask),
+            2
+        );
+        assert_eq!(handle.offset(Direction::Import, "eth2", None, lab, mask), 0);
+
+        let routes = vec![
+            Route::new_direct(lab, mask, "eth0".to_string()),
+            Route::new(
+                other,
+                mask,
+                neighbor,
+                14,
+                "eth0".to_string(),
+                RouteSource::Dynamic,
+                Some(neighbor),
+            ),
+        ];
+        let metrics = |interface: &str| -> Vec<u32> {
+            handle
+                .export_entries(routes.clone(), interface)
+                .iter()
+                .map(|entry| entry.metric)
+                .collect()
+        };
+        assert_eq!(metrics("eth1"), vec![6, 16]);
+        assert_eq!(metrics("eth2"), vec![1, 14]);
+    }
+}
@@ -0,0 +1,425 @@
+//! Path-compressed binary trie of IPv4 prefixes.
+//!
+//! Each node holds a prefix and, below it, the prefixes that extend it by a
+//! 0 or a 1 bit. Nodes without a value exist only where two branches part,
+//! so the depth is bounded by 33 and a longest-prefix match visits one node
+//! per prefix length on the way down instead of every route in the table.
+//! Iteration yields prefixes in ascending order of network and then length.
+
+use ipnet::Ipv4Net;
+use std::net::Ipv4Addr;
+
+#[derive(Debug, Clone)]
+struct Node<V> {
+    prefix: Ipv4Net,
+    value: Option<V>,
+    children: [Option<Box<Node<V>>>; 2],
+}
+
+impl<V> Node<V> {
+    fn new(prefix: Ipv4Net, value: Option<V>) -> Box<Self> {
+        Box::new(Self {
+            prefix,
+            value,
+            children: [None, None],
+        })
+    }
+}
+
+/// Bit `index` of `address`, counting from the most significant
+fn bit(address: Ipv4Addr, index: u8) -> usize {
+    ((u32::from(address) >> (31 - index)) & 1) as usize
+}
+
+/// The child of `node` that `prefix`, which lies below it, belongs under
+fn branch(node: &Ipv4Net, prefix: &Ipv4Net) -> usize {
+    bit(prefix.network(), node.prefix_len())
+}
+
+/// Longest prefix shared by `a` and `b`
+fn common(a: &Ipv4Net, b: &Ipv4Net) -> Ipv4Net {
+    let differing = u32::from(a.network()) ^ u32::from(b.network());
+    let len = (differing.leading_zeros() as u8)
+        .min(a.prefix_len())
+        .min(b.prefix_len());
+    Ipv4Net::new(a.network(), len)
+        .map(|net| net.trunc())
+        .unwrap_or_default()
+}
+
+/// Map from IPv4 prefixes to values with longest-prefix match. Keys are
+/// stored truncated to their prefix length.
+#[derive(Debug, Clone)]
+pub struct PrefixTrie<V> {
+    root: Option<Box<Node<V>>>,
+    len: usize,
+    nodes: usize,
+}
+
+impl<V> Default for PrefixTrie<V> {
+    fn default() -> Self {
+        Self {
+            root: None,
+            len: 0,
+            nodes: 0,
+        }
+    }
+}
+
+impl<V> PrefixTrie<V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Nodes allocated, including those that only join two branches
+    pub fn node_count(&self) -> usize {
+        self.nodes
+    }
+
+    /// Heap bytes taken by the nodes
+    pub fn heap_bytes(&self) -> usize {
+        self.nodes * std::mem::size_of::<Node<V>>()
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Insert `value` under `prefix`, returning the value it replaced
+    pub fn insert(&mut self, prefix: Ipv4Net, value: V) -> Option<V> {
+        let prefix = prefix.trunc();
+        let mut slot = &mut self.root;
+        loop {
+            let Some(node) = slot else {
+                *slot = Some(Node::new(prefix, Some(value)));
+                self.len += 1;
+                self.nodes += 1;
+                return None;
+            };
+            if node.prefix == prefix {
+                let replaced = node.value.replace(value);
+                if replaced.is_none() {
+                    self.len += 1;
+                }
+                return replaced;
+            }
+            if node.prefix.contains(&prefix) {
+                let child = branch(&node.prefix, &prefix);
+                slot = &mut slot.as_mut().unwrap().children[child];
+                continue;
+            }
+
+            // `prefix` goes above the node, or beside it under a new fork
+            let existing = slot.take().unwrap();
+            let mut parent = if prefix.contains(&existing.prefix) {
+                Node::new(prefix, Some(value))
+            } else {
+                let fork = common(&prefix, &existing.prefix);
+                let mut fork = Node::new(fork, None);
+                fork.children[branch(&fork.prefix, &prefix)] = Some(Node::new(prefix, Some(value)));
+                self.nodes += 1;
+                fork
+            };
+            let child = branch(&parent.prefix, &existing.prefix);
+            parent.children[child] = Some(existing);
+            *slot = Some(parent);
+            self.len += 1;
+            self.nodes += 1;
+            return None;
+        }
+    }
+
+    fn node(&self, prefix: &Ipv4Net) -> Option<&Node<V>> {
+        let prefix = prefix.trunc();
+        let mut next = self.root.as_deref();
+        while let Some(node) = next {
+            if node.prefix == prefix {
+                return Some(node);
+            }
+            if !node.prefix.contains(&prefix) {
+                return None;
+            }
+            next = node.children[branch(&node.prefix, &prefix)].as_deref();
+        }
+        None
+    }
+
+    pub fn get(&self, prefix: &Ipv4Net) -> Option<&V> {
+        self.node(prefix)?.value.as_ref()
+    }
+
+    pub fn get_mut(&mut self, prefix: &Ipv4Net) -> Option<&mut V> {
+        let prefix = prefix.trunc();
+        let mut next = self.root.as_deref_mut();
+        while let Some(node) = next {
+            if node.prefix == prefix {
+                return node.value.as_mut();
+            }
+            if !node.prefix.contains(&prefix) {
+                return None;
+            }
+            let child = branch(&node.prefix, &prefix);
+            next = node.children[child].as_deref_mut();
+        }
+        None
+    }
+
+    pub fn contains_key(&self, prefix: &Ipv4Net) -> bool {
+        self.get(prefix).is_some()
+    }
+
+    /// Remove the value under exactly `prefix`
+    pub fn remove(&mut self, prefix: &Ipv4Net) -> Option<V> {
+        let removed = Self::remove_from(&mut self.root, &prefix.trunc(), &mut self.nodes);
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    fn remove_from(
+        slot: &mut Option<Box<Node<V>>>,
+        prefix: &Ipv4Net,
+        nodes: &mut usize,
+    ) -> Option<V> {
+        let node = slot.as_mut()?;
+        let removed = if node.prefix == *prefix {
+            node.value.take()
+        } else if node.prefix.contains(prefix) {
+            let child = branch(&node.prefix, prefix);
+            Self::remove_from(&mut node.children[child], prefix, nodes)
+        } else {
+            None
+        };
+        if removed.is_some() {
+            Self::compact(slot, nodes);
+        }
+        removed
+    }
+
+    /// Drop a node left without a value that no longer parts two branches
+    fn compact(slot: &mut Option<Box<Node<V>>>, nodes: &mut usize) {
+        let replacement = match slot {
+            Some(node) if node.value.is_none() => match &mut node.children {
+                [Some(_), Some(_)] => return,
+                [zero, one] => zero.take().or_else(|| one.take()),
+            },
+            _ => return,
+        };
+        *slot = replacement;
+        *nodes -= 1;
+    }
+
+    /// The value under the longest prefix containing `address`
+    pub fn longest_match(&self, address: Ipv4Addr) -> Option<(Ipv4Net, &V)> {
+        let mut best = None;
+        let mut next = self.root.as_deref();
+        while let Some(node) = next {
+            if !node.prefix.contains(&address) {
+                break;
+            }
+            if let Some(value) = &node.value {
+                best = Some((node.prefix, value));
+            }
+            if node.prefix.prefix_len() == 32 {
+                break;
+            }
+            next = node.children[bit(address, node.prefix.prefix_len())].as_deref();
+        }
+        best
+    }
+
+    /// Entries in ascending order of network, then prefix length
+    pub fn iter(&self) -> Iter<'_, V> {
+        Iter {
+            stack: self.root.as_deref().into_iter().collect(),
+            remaining: self.len,
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, V> {
+        IterMut {
+            stack: self.root.as_deref_mut().into_iter().collect(),
+            remaining: self.len,
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = Ipv4Net> + '_ {
+        self.iter().map(|(prefix, _)| prefix)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, value)| value)
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut V> {
+        self.iter_mut().map(|(_, value)| value)
+    }
+
+    /// Keep only the entries `keep` accepts
+    pub fn retain(&mut self, mut keep: impl FnMut(&Ipv4Net, &mut V) -> bool) {
+        let mut removed = 0;
+        Self::retain_in(&mut self.root, &mut keep, &mut removed, &mut self.nodes);
+        self.len -= removed;
+    }
+
+    fn retain_in(
+        slot: &mut Option<Box<Node<V>>>,
+        keep: &mut impl FnMut(&Ipv4Net, &mut V) -> bool,
+        removed: &mut usize,
+        nodes: &mut usize,
+    ) {
+        let Some(node) = slot else {
+            return;
+        };
+        let prefix = node.prefix;
+        if let Some(value) = &mut node.value {
+            if !keep(&prefix, value) {
+                node.value = None;
+                *removed += 1;
+            }
+        }
+        for child in &mut node.children {
+            Self::retain_in(child, keep, removed, nodes);
+        }
+        Self::compact(slot, nodes);
+    }
+}
+
+pub struct Iter<'a, V> {
+    stack: Vec<&'a Node<V>>,
+    remaining: usize,
+}
+
+impl<'a, V> Iterator for Iter<'a, V> {
+    type Item = (Ipv4Net, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.stack.pop() {
+            let [zero, one] = &node.children;
+            self.stack.extend(one.as_deref());
+            self.stack.extend(zero.as_deref());
+            if let Some(value) = &node.value {
+                self.remaining -= 1;
+                return Some((node.prefix, value));
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+pub struct IterMut<'a, V> {
+    stack: Vec<&'a mut Node<V>>,
+    remaining: usize,
+}
+
+impl<'a, V> Iterator for IterMut<'a, V> {
+    type Item = (Ipv4Net, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.stack.pop() {
+            let Node {
+                prefix,
+                value,
+                children: [zero, one],
+            } = node;
+            self.stack.extend(one.as_deref_mut());
+            self.stack.extend(zero.as_deref_mut());
+            if let Some(value) = value.as_mut() {
+                self.remaining -= 1;
+                return Some((*prefix, value));
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn net(prefix: &str) -> Ipv4Net {
+        prefix.parse().unwrap()
+    }
+
+    #[test]
+    fn lookups_find_the_longest_matching_prefix() {
+        let mut trie = PrefixTrie::new();
+        for (prefix, value) in [
+            ("10.0.0.0/8", 8),
+            ("10.1.0.0/16", 16),
+            ("10.1.2.0/24", 24),
+            ("10.2.0.0/16", 2),
+            ("0.0.0.0/0", 0),
+            ("10.1.2.3/32", 32),
+        ] {
+            assert_eq!(trie.insert(net(prefix), value), None);
+        }
+        assert_eq!(trie.insert(net("10.1.9.9/16"), 17), Some(16));
+        assert_eq!(trie.len(), 6);
+
+        let lookup = |address: [u8; 4]| trie.longest_match(address.into()).map(|(_, v)| *v);
+        assert_eq!(lookup([10, 1, 2, 3]), Some(32));
+        assert_eq!(lookup([10, 1, 2, 4]), Some(24));
+        assert_eq!(lookup([10, 1, 3, 1]), Some(17));
+        assert_eq!(lookup([10, 3, 0, 1]), Some(8));
+        assert_eq!(lookup([192, 168, 0, 1]), Some(0));
+        assert_eq!(trie.get(&net("10.1.0.0/16")), Some(&17));
+        assert_eq!(trie.get(&net("10.1.0.0/17")), None);
+
+        let keys: Vec<String> = trie.keys().map(|prefix| prefix.to_string()).collect();
+        assert_eq!(
+            keys,
+            vec![
+                "0.0.0.0/0",
+                "10.0.0.0/8",
+                "10.1.0.0/16",
+                "10.1.2.0/24",
+                "10.1.2.3/32",
+                "10.2.0.0/16"
+            ]
+        );
+    }
+
+    #[test]
+    fn removal_collapses_the_nodes_left_behind() {
+        let mut trie = PrefixTrie::new();
+        trie.insert(net("10.1.0.0/16"), 1);
+        trie.insert(net("10.2.0.0/16"), 2);
+        trie.insert(net("10.3.0.0/16"), 3);
+        // Each pair of siblings meets at a fork without a value
+        assert_eq!(trie.node_count(), 5);
+
+        assert_eq!(trie.remove(&net("10.2.0.0/16")), Some(2));
+        assert_eq!(trie.remove(&net("10.2.0.0/16")), None);
+        assert_eq!(trie.node_count(), 3);
+        assert_eq!(trie.longest_match(Ipv4Addr::new(10, 2, 0, 1)), None);
+
+        for value in trie.values_mut() {
+            *value *= 10;
+        }
+        trie.retain(|prefix, value| {
+            assert_eq!(prefix.prefix_len(), 16);
+            *value > 10
+        });
+        assert_eq!(trie.len(), 1);
+        assert_eq!(trie.node_count(), 1);
+        assert_eq!(trie.get(&net("10.3.0.0/16")), Some(&30));
+    }
+}
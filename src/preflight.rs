@@ -0,0 +1,304 @@
+//! Checks of the host environment before the router starts its tasks.
+//!
+//! A RIP port held by another daemon, a missing capability or an address the
+//! host does not have otherwise only surfaces as warnings repeated for every
+//! packet. Each finding carries a hint on how to fix it; with `strict` set,
+//! any error stops startup.
+
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::ErrorKind;
+use std::net::{SocketAddr, UdpSocket};
+use std::path::Path;
+
+use crate::config_manager::RouterConfig;
+use crate::network::SendMode;
+
+/// Routing daemons that may also speak RIP on the same hosts
+const CONFLICTING_DAEMONS: [&str; 4] = ["ripd", "routed", "bird", "gated"];
+/// `IFF_MULTICAST` in `/sys/class/net/<name>/flags`
+const IFF_MULTICAST: u32 = 0x1000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PreflightConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Refuse to start when a check reports an error
+    #[serde(default)]
+    pub strict: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl Default for PreflightConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            strict: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Finding {
+    pub check: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    /// What to change to make the check pass
+    pub hint: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PreflightReport {
+    pub findings: Vec<Finding>,
+}
+
+impl PreflightReport {
+    pub fn has_errors(&self) -> bool {
+        self.error_count() > 0
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.findings
+            .iter()
+            .filter(|finding| finding.severity == Severity::Error)
+            .count()
+    }
+
+    fn push(
+        &mut self,
+        check: &'static str,
+        severity: Severity,
+        message: String,
+        hint: impl Into<String>,
+    ) {
+        // Problems shared by every interface, such as a missing privilege,
+        // are reported once
+        if self
+            .findings
+            .iter()
+            .any(|finding| finding.message == message)
+        {
+            return;
+        }
+        self.findings.push(Finding {
+            check,
+            severity,
+            message,
+            hint: hint.into(),
+        });
+    }
+}
+
+/// Run every check that applies to `config`
+pub fn run(config: &RouterConfig) -> PreflightReport {
+    let mut report = PreflightReport::default();
+    check_clock(&mut report, Utc::now());
+    check_interfaces(&mut report, config);
+    if config.rip.enabled {
+        check_rip_ports(&mut report, config);
+        check_conflicting_daemons(&mut report, &running_processes());
+    }
+    report
+}
+
+/// A clock far in the past breaks token expiry, certificate checks and
+/// every timestamp the router reports
+fn check_clock(report: &mut PreflightReport, now: DateTime<Utc>) {
+    let earliest = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+    if now < earliest {
+        report.push(
+            "clock",
+            Severity::Warning,
+            format!("System clock reads {}, which predates this build", now),
+            "Synchronize the clock, for example by enabling NTP",
+        );
+    }
+}
+
+fn check_interfaces(report: &mut PreflightReport, config: &RouterConfig) {
+    let can_inspect = Path::new("/sys/class/net").is_dir();
+    for iface in config.interfaces.iter().filter(|iface| iface.enabled) {
+        if iface.address.trim().parse::<ipnet::IpNet>().is_err() {
+            report.push(
+                "interface",
+                Severity::Error,
+                format!(
+                    "Interface {} has an invalid address {:?}",
+                    iface.name, iface.address
+                ),
+                "Use CIDR notation such as 192.168.1.1/24",
+            );
+        }
+        if !can_inspect {
+            continue;
+        }
+
+        let Some(flags) = interface_flags(&iface.name) else {
+            report.push(
+                "interface",
+                Severity::Warning,
+                format!("Interface {} does not exist on this host", iface.name),
+                "Check interfaces[].name; MTU and link state fall back to defaults",
+            );
+            continue;
+        };
+        if iface.send_mode == SendMode::Multicast && flags & IFF_MULTICAST == 0 {
+            report.push(
+                "multicast",
+                Severity::Error,
+                format!("Interface {} does not support multicast", iface.name),
+                format!(
+                    "Run `ip link set {} multicast on` or use \"send_mode\": \"broadcast\"",
+                    iface.name
+                ),
+            );
+        }
+    }
+}
+
+/// Bind the RIP port on every IPv4 interface, the way the router will
+fn check_rip_ports(report: &mut PreflightReport, config: &RouterConfig) {
+    let port = config.rip.port;
+    for iface in config.interfaces.iter().filter(|iface| iface.enabled) {
+        for net in iface.ipv4_net().iter().chain(&iface.secondary_addresses) {
+            let address = SocketAddr::new(net.addr().into(), port);
+            let Err(err) = UdpSocket::bind(address) else {
+                continue;
+            };
+
+            let (message, hint) = match err.kind() {
+                ErrorKind::AddrInUse => (
+                    format!(
+                        "UDP port {} on {} ({}) is already in use",
+                        port,
+                        net.addr(),
+                        iface.name
+                    ),
+                    "Stop the other RIP daemon or choose another rip.port".to_string(),
+                ),
+                ErrorKind::PermissionDenied => (
+                    format!("Not permitted to bind UDP port {}", port),
+                    "Run as root or grant CAP_NET_BIND_SERVICE with setcap".to_string(),
+                ),
+                ErrorKind::AddrNotAvailable => (
+                    format!(
+                        "{} ({}) is not assigned on this host",
+                        net.addr(),
+                        iface.name
+                    ),
+                    format!(
+                        "Add the address to {} or correct interfaces[].address",
+                        iface.name
+                    ),
+                ),
+                _ => (
+                    format!("Cannot bind {} for {}: {}", address, iface.name, err),
+                    "Check the interface address and rip.port".to_string(),
+                ),
+            };
+            report.push("port", Severity::Error, message, hint);
+        }
+    }
+}
+
+fn check_conflicting_daemons(report: &mut PreflightReport, processes: &[String]) {
+    for daemon in CONFLICTING_DAEMONS {
+        if processes.iter().any(|name| name == daemon) {
+            report.push(
+                "daemons",
+                Severity::Warning,
+                format!("{} is running and may also speak RIP", daemon),
+                format!(
+                    "Stop {} or keep it off the interfaces RustRoute uses",
+                    daemon
+                ),
+            );
+        }
+    }
+}
+
+fn interface_flags(name: &str) -> Option<u32> {
+    let flags = std::fs::read_to_string(format!("/sys/class/net/{}/flags", name)).ok()?;
+    u32::from_str_radix(flags.trim().trim_start_matches("0x"), 16).ok()
+}
+
+/// Command names of the other processes on the host, where `/proc` has them
+fn running_processes() -> Vec<String> {
+    let own = std::process::id().to_string();
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            name != own && name.bytes().all(|b| b.is_ascii_digit())
+        })
+        .filter_map(|entry| std::fs::read_to_string(entry.path().join("comm")).ok())
+        .map(|comm| comm.trim().to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_manager::InterfaceConfig;
+
+    #[test]
+    fn ports_in_use_are_errors() {
+        let taken = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut config = RouterConfig::default();
+        config.rip.port = taken.local_addr().unwrap().port();
+        config.interfaces = vec![
+            InterfaceConfig {
+                name: "lo".to_string(),
+                address: "127.0.0.1/8".to_string(),
+                ..config.interfaces[0].clone()
+            },
+            InterfaceConfig {
+                name: "bad".to_string(),
+                address: "not-an-address".to_string(),
+                ..config.interfaces[0].clone()
+            },
+        ];
+
+        let mut report = PreflightReport::default();
+        check_rip_ports(&mut report, &config);
+        check_interfaces(&mut report, &config);
+        assert_eq!(report.findings[0].check, "port");
+        assert!(report.findings[0].message.contains("already in use"));
+        assert!(report
+            .findings
+            .iter()
+            .any(|finding| finding.message.contains("invalid address")));
+        assert!(report.has_errors());
+    }
+
+    #[test]
+    fn clock_and_daemon_findings_are_warnings() {
+        let mut report = PreflightReport::default();
+        check_clock(&mut report, Utc::now());
+        assert!(report.findings.is_empty());
+
+        check_clock(
+            &mut report,
+            Utc.with_ymd_and_hms(1970, 1, 1, 0, 0, 0).unwrap(),
+        );
+        let processes = vec!["sshd".to_string(), "ripd".to_string(), "ripd".to_string()];
+        check_conflicting_daemons(&mut report, &processes);
+        let checks: Vec<&str> = report.findings.iter().map(|f| f.check).collect();
+        assert_eq!(checks, vec!["clock", "daemons"]);
+        assert!(!report.has_errors());
+    }
+}
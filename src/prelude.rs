@@ -0,0 +1,10 @@
+//! The types most programs embedding the router need, for a single
+//! `use rust_route::prelude::*;`
+
+pub use crate::config_manager::{ConfigManager, InterfaceConfig, RipConfig, RouterConfig};
+pub use crate::events::{ActivityLevel, EventBus, WebEvent};
+pub use crate::metrics::Metrics;
+pub use crate::protocol::{RipCommand, RipEntry, RipPacket};
+pub use crate::router::Router;
+pub use crate::routing_table::{Route, RouteSource, RoutingTable};
+pub use crate::{RustRouteError, RustRouteResult};
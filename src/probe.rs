@@ -0,0 +1,481 @@
+//! Reachability probes to the next hops of learned routes.
+//!
+//! A RIP neighbor keeps advertising until its routes time out, even when
+//! the path through it has stopped forwarding. With `probes.enabled` the
+//! router sends an ICMP echo to every next hop of an installed learned
+//! route each `interval` seconds and keeps the round-trip times and losses
+//! of the last `window` probes, shown by `GET /api/neighbors` and
+//! `GET /api/metrics`. With `demote` set, a next hop that misses
+//! `demote_after` probes in a row has its routes demoted in the routing
+//! table until it answers again.
+//!
+//! Probes use an unprivileged ICMP socket where `net.ipv4.ping_group_range`
+//! allows one and a raw socket otherwise, which needs `CAP_NET_RAW`.
+
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::sync::RwLock;
+use tokio::task::JoinSet;
+use tokio::time::Instant;
+
+use crate::events::{ActivityLevel, EventBus};
+use crate::router::{Router, RECEIVE_HEARTBEAT};
+use crate::routing_table::RouteSource;
+use crate::watchdog::Heartbeat;
+use crate::{RustRouteError, RustRouteResult};
+
+const ICMP_ECHO_REQUEST: u8 = 8;
+const ICMP_ECHO_REPLY: u8 = 0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProbeConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Seconds between probe rounds
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+    /// Probes kept per next hop for the loss and RTT figures
+    #[serde(default = "default_window")]
+    pub window: usize,
+    /// Demote the routes of next hops that stop answering
+    #[serde(default)]
+    pub demote: bool,
+    /// Consecutive lost probes before a next hop is demoted
+    #[serde(default = "default_demote_after")]
+    pub demote_after: u32,
+}
+
+fn default_interval() -> u64 {
+    10
+}
+
+fn default_timeout_ms() -> u64 {
+    1000
+}
+
+fn default_window() -> usize {
+    20
+}
+
+fn default_demote_after() -> u32 {
+    5
+}
+
+impl Default for ProbeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval: default_interval(),
+            timeout_ms: default_timeout_ms(),
+            window: default_window(),
+            demote: false,
+            demote_after: default_demote_after(),
+        }
+    }
+}
+
+/// Probe results of one next hop
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NextHopStats {
+    pub address: Ipv4Addr,
+    /// Probes in the window
+    pub probes: usize,
+    pub lost: usize,
+    pub loss_percent: f64,
+    pub last_rtt_ms: Option<f64>,
+    pub mean_rtt_ms: Option<f64>,
+    pub consecutive_failures: u32,
+    pub demoted: bool,
+    pub last_probe: DateTime<Utc>,
+}
+
+/// Change of a next hop's demotion caused by a probe
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    Demoted,
+    Restored,
+}
+
+#[derive(Debug, Clone)]
+struct History {
+    samples: VecDeque<Option<Duration>>,
+    consecutive_failures: u32,
+    demoted: bool,
+    last_probe: DateTime<Utc>,
+}
+
+/// Probe results per next hop, shared between the prober and the API
+#[derive(Debug, Clone, Default)]
+pub struct NextHopProbes {
+    hops: Arc<Mutex<BTreeMap<Ipv4Addr, History>>>,
+}
+
+impl NextHopProbes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn hops(&self) -> std::sync::MutexGuard<'_, BTreeMap<Ipv4Addr, History>> {
+        self.hops
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Record a probe of `address`, `None` when it went unanswered
+    pub fn record(
+        &self,
+        address: Ipv4Addr,
+        rtt: Option<Duration>,
+        config: &ProbeConfig,
+    ) -> Option<Transition> {
+        let mut hops = self.hops();
+        let history = hops.entry(address).or_insert_with(|| History {
+            samples: VecDeque::new(),
+            consecutive_failures: 0,
+            demoted: false,
+            last_probe: Utc::now(),
+        });
+        history.samples.push_back(rtt);
+        while history.samples.len() > config.window.max(1) {
+            history.samples.pop_front();
+        }
+        history.last_probe = Utc::now();
+        match rtt {
+            Some(_) => history.consecutive_failures = 0,
+            None => history.consecutive_failures += 1,
+        }
+
+        let demote = config.demote && history.consecutive_failures >= config.demote_after.max(1);
+        if demote && !history.demoted {
+            history.demoted = true;
+            Some(Transition::Demoted)
+        } else if rtt.is_some() && history.demoted {
+            history.demoted = false;
+            Some(Transition::Restored)
+        } else {
+            None
+        }
+    }
+
+    /// Forget next hops no longer in use, returning those that were demoted
+    pub fn retain(&self, active: &HashSet<Ipv4Addr>) -> Vec<Ipv4Addr> {
+        let mut hops = self.hops();
+        let demoted = hops
+            .iter()
+            .filter(|(address, history)| history.demoted && !active.contains(address))
+            .map(|(address, _)| *address)
+            .collect();
+        hops.retain(|address, _| active.contains(address));
+        demoted
+    }
+
+    pub fn get(&self, address: Ipv4Addr) -> Option<NextHopStats> {
+        self.hops()
+            .get(&address)
+            .map(|history| stats(address, history))
+    }
+
+    pub fn all(&self) -> Vec<NextHopStats> {
+        self.hops()
+            .iter()
+            .map(|(address, history)| stats(*address, history))
+            .collect()
+    }
+}
+
+fn stats(address: Ipv4Addr, history: &History) -> NextHopStats {
+    let millis = |rtt: &Duration| rtt.as_secs_f64() * 1000.0;
+    let answered: Vec<f64> = history.samples.iter().flatten().map(millis).collect();
+    let probes = history.samples.len();
+    let lost = probes - answered.len();
+    NextHopStats {
+        address,
+        probes,
+        lost,
+        loss_percent: lost as f64 * 100.0 / probes.max(1) as f64,
+        last_rtt_ms: history
+            .samples
+            .back()
+            .copied()
+            .flatten()
+            .map(|rtt| millis(&rtt)),
+        mean_rtt_ms: (!answered.is_empty())
+            .then(|| answered.iter().sum::<f64>() / answered.len() as f64),
+        consecutive_failures: history.consecutive_failures,
+        demoted: history.demoted,
+        last_probe: history.last_probe,
+    }
+}
+
+/// Send an ICMP echo request to `target` and wait up to `timeout` for the
+/// reply. `Ok(None)` means no reply came; errors mean no probe could be sent.
+pub async fn ping(
+    target: Ipv4Addr,
+    sequence: u16,
+    timeout: Duration,
+) -> RustRouteResult<Option<Duration>> {
+    let (socket, raw) = icmp_socket().map_err(|err| {
+        RustRouteError::NetworkError(format!(
+            "Cannot open an ICMP socket ({}); allow it with net.ipv4.ping_group_range or CAP_NET_RAW",
+            err
+        ))
+    })?;
+    // Ping sockets replace the identifier with their own and only deliver
+    // replies to it; raw sockets see every ICMP packet
+    let identifier = std::process::id() as u16;
+    let request = echo_request(identifier, sequence);
+    let started = Instant::now();
+    socket
+        .send_to(&request, SocketAddr::new(target.into(), 0))
+        .await
+        .map_err(|err| RustRouteError::NetworkError(format!("Probe to {}: {}", target, err)))?;
+
+    let deadline = started + timeout;
+    let mut buffer = [0u8; 1500];
+    loop {
+        let received = tokio::time::timeout_at(deadline, socket.recv_from(&mut buffer)).await;
+        let Ok(Ok((len, from))) = received else {
+            return Ok(None);
+        };
+        if from.ip() != IpAddr::V4(target) {
+            continue;
+        }
+        let mut reply = &buffer[..len];
+        if raw {
+            let header = usize::from(reply.first().map_or(0, |byte| byte & 0x0f)) * 4;
+            reply = reply.get(header..).unwrap_or_default();
+        }
+        let matches = reply.len() >= 8
+            && reply[0] == ICMP_ECHO_REPLY
+            && u16::from_be_bytes([reply[6], reply[7]]) == sequence
+            && (!raw || u16::from_be_bytes([reply[4], reply[5]]) == identifier);
+        if matches {
+            return Ok(Some(started.elapsed()));
+        }
+    }
+}
+
+fn icmp_socket() -> std::io::Result<(UdpSocket, bool)> {
+    let (socket, raw) = match Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::ICMPV4)) {
+        Ok(socket) => (socket, false),
+        Err(_) => (
+            Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::ICMPV4))?,
+            true,
+        ),
+    };
+    socket.set_nonblocking(true)?;
+    Ok((UdpSocket::from_std(socket.into())?, raw))
+}
+
+fn echo_request(identifier: u16, sequence: u16) -> Vec<u8> {
+    let mut packet = vec![ICMP_ECHO_REQUEST, 0, 0, 0];
+    packet.extend_from_slice(&identifier.to_be_bytes());
+    packet.extend_from_slice(&sequence.to_be_bytes());
+    packet.extend_from_slice(b"rust-route probe");
+    let checksum = checksum(&packet);
+    packet[2..4].copy_from_slice(&checksum.to_be_bytes());
+    packet
+}
+
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|pair| u32::from(u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)])))
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Next hops of installed learned routes that are still reachable
+async fn active_next_hops(router: &Arc<RwLock<Router>>) -> (HashSet<Ipv4Addr>, ProbeConfig) {
+    let (table, config, infinity) = {
+        let router = router.read().await;
+        let config = router.config();
+        (
+            router.routing_table(),
+            config.probes.clone(),
+            config.rip.infinity_metric,
+        )
+    };
+    let hops = table
+        .read()
+        .await
+        .get_all_routes()
+        .into_iter()
+        .filter(|route| route.source == RouteSource::Dynamic && route.metric < infinity)
+        .map(|route| route.next_hop)
+        .filter(|hop| !hop.is_unspecified())
+        .collect();
+    (hops, config)
+}
+
+/// Probe the next hops of `router` until the task is aborted
+pub async fn run(router: Arc<RwLock<Router>>, events: EventBus, heartbeat: Heartbeat) {
+    let mut sequence: u16 = 0;
+    let mut socket_warned = false;
+    loop {
+        heartbeat.beat();
+        let (hops, config) = active_next_hops(&router).await;
+        let probes = router.read().await.probes();
+        let interval = Duration::from_secs(config.interval.max(1));
+        if !config.enabled {
+            // Release demotions when probing is switched off
+            restore(&router, &probes, &events, &HashSet::new()).await;
+            tokio::time::sleep(RECEIVE_HEARTBEAT).await;
+            continue;
+        }
+        restore(&router, &probes, &events, &hops).await;
+
+        let round = Instant::now();
+        sequence = sequence.wrapping_add(1);
+        let timeout = Duration::from_millis(config.timeout_ms.max(1));
+        let mut pings = JoinSet::new();
+        for hop in hops {
+            pings.spawn(async move { (hop, ping(hop, sequence, timeout).await) });
+        }
+        let mut demoted = Vec::new();
+        while let Some(Ok((hop, result))) = pings.join_next().await {
+            let rtt = match result {
+                Ok(rtt) => rtt,
+                Err(err) => {
+                    if !socket_warned {
+                        warn!("Next hop probes disabled: {}", err);
+                        socket_warned = true;
+                    }
+                    continue;
+                }
+            };
+            match probes.record(hop, rtt, &config) {
+                Some(Transition::Demoted) => demoted.push(hop),
+                Some(Transition::Restored) => set_demoted(&router, &events, hop, false).await,
+                None => {}
+            }
+        }
+        for hop in &demoted {
+            set_demoted(&router, &events, *hop, true).await;
+        }
+        if !demoted.is_empty() {
+            // Alternatives through other neighbors replace the demoted routes
+            // as soon as they are advertised
+            router.read().await.request_tables(None).await;
+        }
+
+        let next_round = round + interval;
+        while Instant::now() < next_round {
+            tokio::time::sleep_until(next_round.min(Instant::now() + RECEIVE_HEARTBEAT)).await;
+            heartbeat.beat();
+        }
+    }
+}
+
+/// Forget next hops outside `active`, restoring those that were demoted
+async fn restore(
+    router: &Arc<RwLock<Router>>,
+    probes: &NextHopProbes,
+    events: &EventBus,
+    active: &HashSet<Ipv4Addr>,
+) {
+    for hop in probes.retain(active) {
+        set_demoted(router, events, hop, false).await;
+    }
+}
+
+async fn set_demoted(
+    router: &Arc<RwLock<Router>>,
+    events: &EventBus,
+    hop: Ipv4Addr,
+    demoted: bool,
+) {
+    let table = router.read().await.routing_table();
+    let routes = table.write().await.set_demoted(hop, demoted);
+    let message = if demoted {
+        format!(
+            "Next hop {} stopped answering probes; demoted {} routes",
+            hop, routes
+        )
+    } else {
+        format!(
+            "Next hop {} answers probes again; restored {} routes",
+            hop, routes
+        )
+    };
+    if demoted {
+        warn!("{}", message);
+        events.publish_activity(ActivityLevel::Warn, message);
+    } else {
+        info!("{}", message);
+        events.publish_activity(ActivityLevel::Info, message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn consecutive_losses_demote_until_a_reply() {
+        let config = ProbeConfig {
+            enabled: true,
+            window: 4,
+            demote: true,
+            demote_after: 3,
+            ..ProbeConfig::default()
+        };
+        let probes = NextHopProbes::new();
+        let hop = Ipv4Addr::new(192, 168, 0, 2);
+        let reply = Some(Duration::from_millis(2));
+
+        assert_eq!(probes.record(hop, reply, &config), None);
+        assert_eq!(probes.record(hop, None, &config), None);
+        assert_eq!(probes.record(hop, None, &config), None);
+        assert_eq!(probes.record(hop, None, &config), Some(Transition::Demoted));
+        assert_eq!(probes.record(hop, None, &config), None);
+        let stats = probes.get(hop).unwrap();
+        assert_eq!((stats.probes, stats.lost), (4, 4));
+        assert_eq!(stats.loss_percent, 100.0);
+        assert!(stats.demoted && stats.mean_rtt_ms.is_none());
+
+        assert_eq!(
+            probes.record(hop, reply, &config),
+            Some(Transition::Restored)
+        );
+        let stats = probes.get(hop).unwrap();
+        assert_eq!(stats.last_rtt_ms, Some(2.0));
+        assert_eq!(stats.consecutive_failures, 0);
+
+        probes.record(hop, None, &config);
+        let mut demoted = config.clone();
+        demoted.demote_after = 1;
+        probes.record(hop, None, &demoted);
+        assert_eq!(probes.retain(&HashSet::new()), vec![hop]);
+        assert!(probes.all().is_empty());
+    }
+
+    #[tokio::test]
+    async fn loopback_answers_probes() {
+        // Hosts that allow neither ping nor raw sockets cannot run this
+        let Ok(rtt) = ping(Ipv4Addr::LOCALHOST, 1, Duration::from_secs(1)).await else {
+            return;
+        };
+        assert!(rtt.is_some());
+    }
+
+    #[test]
+    fn echo_requests_carry_a_valid_checksum() {
+        let request = echo_request(0x1234, 7);
+        assert_eq!(request[0], ICMP_ECHO_REQUEST);
+        assert_eq!(&request[4..8], &[0x12, 0x34, 0, 7]);
+        assert_eq!(checksum(&request), 0);
+    }
+}
@@ -1,16 +1,47 @@
 //! RIP protocol implementation
 
+use crate::packet_auth::PacketSignature;
 use crate::RustRouteResult;
 use serde::{Deserialize, Serialize};
 use std::net::Ipv4Addr;
 
 /// RIP packet types
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum RipCommand {
     Request = 1,
     Response = 2,
 }
 
+/// Wire encoding of a UDP packet
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PacketCodec {
+    /// Legacy JSON framing
+    #[default]
+    Json,
+    /// RFC 2453 binary layout
+    Binary,
+}
+
+impl PacketCodec {
+    /// Guess the encoding of a datagram from its first byte: JSON opens with
+    /// `{` (possibly after whitespace), binary with a RIP command byte
+    pub fn sniff(data: &[u8]) -> Option<Self> {
+        match data.iter().find(|byte| !byte.is_ascii_whitespace())? {
+            b'{' => Some(PacketCodec::Json),
+            _ if matches!(data[0], 1 | 2) => Some(PacketCodec::Binary),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PacketCodec::Json => "json",
+            PacketCodec::Binary => "binary",
+        }
+    }
+}
+
 /// RIP route entry
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RipEntry {
@@ -29,8 +60,46 @@ pub struct RipPacket {
     pub version: u8,
     pub reserved: u16,
     pub entries: Vec<RipEntry>,
+    /// Present when JSON packet signing is enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth: Option<PacketSignature>,
+    /// Authentication entry of a binary packet; JSON packets use `auth`
+    #[serde(skip)]
+    pub authentication: Option<RipAuthentication>,
 }
 
+/// Address family of an authentication entry or trailer
+pub const AUTH_FAMILY: u16 = 0xFFFF;
+
+const AUTH_TYPE_PLAINTEXT: u16 = 2;
+const AUTH_TYPE_CRYPTOGRAPHIC: u16 = 3;
+const AUTH_TRAILER_TYPE: u16 = 1;
+
+/// Authentication entry in front of the routes of a binary packet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RipAuthentication {
+    /// Type 2 (RFC 2453): a password, zero-padded to 16 bytes
+    Plaintext { password: [u8; 16] },
+    /// Type 3 (RFC 2082, RFC 4822): the key and sequence number of the
+    /// digest in the trailer after the routes
+    Cryptographic {
+        /// Bytes in front of the trailer
+        packet_length: u16,
+        key_id: u8,
+        /// Bytes of digest the entry announces
+        auth_data_len: u8,
+        sequence: u32,
+        digest: Vec<u8>,
+    },
+}
+
+/// Metric meaning unreachable, unless `rip.infinity_metric` says otherwise
+pub const DEFAULT_INFINITY: u32 = 16;
+
+/// Most entries a RIPv2 packet may carry; the authentication entry of a
+/// binary packet counts against it
+pub const MAX_ENTRIES: usize = 25;
+
 impl RipPacket {
     /// Create a new RIP request packet
     pub fn new_request() -> Self {
@@ -46,6 +115,8 @@ impl RipPacket {
                 next_hop: Ipv4Addr::new(0, 0, 0, 0),
                 metric: 16,
             }],
+            auth: None,
+            authentication: None,
         }
     }
 
@@ -56,6 +127,8 @@ impl RipPacket {
             version: 2,
             reserved: 0,
             entries,
+            auth: None,
+            authentication: None,
         }
     }
 
@@ -64,7 +137,7 @@ impl RipPacket {
         let mut buffer = Vec::new();
 
         // Command (1 byte)
-        buffer.push(self.command.clone() as u8);
+        buffer.push(self.command as u8);
 
         // Version (1 byte)
         buffer.push(self.version);
@@ -72,6 +145,31 @@ impl RipPacket {
         // Reserved (2 bytes)
         buffer.extend_from_slice(&self.reserved.to_be_bytes());
 
+        // Authentication entry (20 bytes)
+        match &self.authentication {
+            Some(RipAuthentication::Plaintext { password }) => {
+                buffer.extend_from_slice(&AUTH_FAMILY.to_be_bytes());
+                buffer.extend_from_slice(&AUTH_TYPE_PLAINTEXT.to_be_bytes());
+                buffer.extend_from_slice(password);
+            }
+            Some(RipAuthentication::Cryptographic {
+                packet_length,
+                key_id,
+                auth_data_len,
+                sequence,
+                ..
+            }) => {
+                buffer.extend_from_slice(&AUTH_FAMILY.to_be_bytes());
+                buffer.extend_from_slice(&AUTH_TYPE_CRYPTOGRAPHIC.to_be_bytes());
+                buffer.extend_from_slice(&packet_length.to_be_bytes());
+                buffer.push(*key_id);
+                buffer.push(*auth_data_len);
+                buffer.extend_from_slice(&sequence.to_be_bytes());
+                buffer.extend_from_slice(&[0; 8]);
+            }
+            None => {}
+        }
+
         // Entries
         for entry in &self.entries {
             // Address family (2 bytes)
@@ -93,6 +191,13 @@ impl RipPacket {
             buffer.extend_from_slice(&entry.metric.to_be_bytes());
         }
 
+        // Authentication trailer
+        if let Some(RipAuthentication::Cryptographic { digest, .. }) = &self.authentication {
+            buffer.extend_from_slice(&AUTH_FAMILY.to_be_bytes());
+            buffer.extend_from_slice(&AUTH_TRAILER_TYPE.to_be_bytes());
+            buffer.extend_from_slice(digest);
+        }
+
         Ok(buffer)
     }
 
@@ -117,10 +222,10 @@ impl RipPacket {
         let version = data[1];
         let reserved = u16::from_be_bytes([data[2], data[3]]);
 
+        let (authentication, mut offset, end) = Self::parse_authentication(data)?;
         let mut entries = Vec::new();
-        let mut offset = 4;
 
-        while offset + 20 <= data.len() {
+        while offset + 20 <= end {
             let address_family = u16::from_be_bytes([data[offset], data[offset + 1]]);
             let route_tag = u16::from_be_bytes([data[offset + 2], data[offset + 3]]);
             let ip_address = Ipv4Addr::from([
@@ -165,16 +270,63 @@ impl RipPacket {
             version,
             reserved,
             entries,
+            auth: None,
+            authentication,
         })
     }
 
-    /// Create a new RIP update packet with routes
+    /// The authentication entry of a binary packet if it has one, with the
+    /// offset of the first route and the end of the routes
+    fn parse_authentication(
+        data: &[u8],
+    ) -> RustRouteResult<(Option<RipAuthentication>, usize, usize)> {
+        if data.len() < 24 || u16::from_be_bytes([data[4], data[5]]) != AUTH_FAMILY {
+            return Ok((None, 4, data.len()));
+        }
+        let auth_type = u16::from_be_bytes([data[6], data[7]]);
+        match auth_type {
+            AUTH_TYPE_PLAINTEXT => {
+                let mut password = [0; 16];
+                password.copy_from_slice(&data[8..24]);
+                Ok((
+                    Some(RipAuthentication::Plaintext { password }),
+                    24,
+                    data.len(),
+                ))
+            }
+            AUTH_TYPE_CRYPTOGRAPHIC => {
+                let packet_length = u16::from_be_bytes([data[8], data[9]]);
+                let end = packet_length as usize;
+                let trailer = [AUTH_FAMILY.to_be_bytes(), AUTH_TRAILER_TYPE.to_be_bytes()].concat();
+                if end < 24 || end + 4 > data.len() || data[end..end + 4] != trailer[..] {
+                    return Err(crate::RustRouteError::ProtocolError(
+                        "Missing authentication trailer".to_string(),
+                    ));
+                }
+                let authentication = RipAuthentication::Cryptographic {
+                    packet_length,
+                    key_id: data[10],
+                    auth_data_len: data[11],
+                    sequence: u32::from_be_bytes([data[12], data[13], data[14], data[15]]),
+                    digest: data[end + 4..].to_vec(),
+                };
+                Ok((Some(authentication), 24, end))
+            }
+            other => Err(crate::RustRouteError::ProtocolError(format!(
+                "Unsupported authentication type {}",
+                other
+            ))),
+        }
+    }
+
+    /// Create a new RIP update packet with routes; interfaces send it as
+    /// several packets when it holds more than `MAX_ENTRIES`
     pub fn new_update(_router_id: uuid::Uuid, routes: Vec<crate::routing_table::Route>) -> Self {
         let entries = routes
             .into_iter()
             .map(|route| RipEntry {
                 address_family: 2, // IP
-                route_tag: 0,
+                route_tag: route.tag,
                 ip_address: route.destination,
                 subnet_mask: route.subnet_mask,
                 next_hop: route.next_hop,
@@ -182,12 +334,7 @@ impl RipPacket {
             })
             .collect();
 
-        Self {
-            command: RipCommand::Response,
-            version: 2,
-            reserved: 0,
-            entries,
-        }
+        Self::new_response(entries)
     }
 
     /// Serialize packet to JSON
@@ -204,8 +351,8 @@ impl RipPacket {
         })
     }
 
-    /// Validate packet contents
-    pub fn validate(&self) -> crate::RustRouteResult<()> {
+    /// Validate packet contents; metrics above `infinity` are invalid
+    pub fn validate(&self, infinity: u32) -> crate::RustRouteResult<()> {
         if self.version != 2 {
             return Err(crate::RustRouteError::ProtocolError(
                 "Invalid RIP version".to_string(),
@@ -213,7 +360,7 @@ impl RipPacket {
         }
 
         for entry in &self.entries {
-            if entry.metric > 16 {
+            if entry.metric > infinity {
                 return Err(crate::RustRouteError::ProtocolError(
                     "Invalid metric value".to_string(),
                 ));
@@ -242,3 +389,67 @@ impl RipEntry {
         }
     }
 }
+
+/// Shares the entries of a packet out over as many packets as it takes for
+/// each to hold at most `max_entries` and fit `max_bytes` once encoded
+#[derive(Debug, Clone, Copy)]
+pub struct PacketBuilder {
+    max_entries: usize,
+    max_bytes: usize,
+    overhead: usize,
+}
+
+impl PacketBuilder {
+    /// Packets of at most `max_bytes` and `MAX_ENTRIES` entries
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            max_entries: MAX_ENTRIES,
+            max_bytes,
+            overhead: 0,
+        }
+    }
+
+    /// Entries per packet, at least one
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries.max(1);
+        self
+    }
+
+    /// Bytes each packet takes besides its entries
+    pub fn overhead(mut self, overhead: usize) -> Self {
+        self.overhead = overhead;
+        self
+    }
+
+    /// Copies of `packet` without authentication, filled greedily with its
+    /// entries in order; `sizes` holds the encoded size of each entry. An
+    /// entry too large for any packet still gets one of its own, and a
+    /// packet without entries yields one empty packet.
+    pub fn split(&self, packet: &RipPacket, sizes: &[usize]) -> Vec<RipPacket> {
+        let mut batches: Vec<Vec<RipEntry>> = Vec::new();
+        let mut current = Vec::new();
+        let mut used = self.overhead;
+        for (entry, size) in packet.entries.iter().zip(sizes) {
+            let full = current.len() >= self.max_entries || used + size > self.max_bytes;
+            if !current.is_empty() && full {
+                batches.push(std::mem::take(&mut current));
+                used = self.overhead;
+            }
+            used += size;
+            current.push(entry.clone());
+        }
+        if !current.is_empty() || batches.is_empty() {
+            batches.push(current);
+        }
+
+        batches
+            .into_iter()
+            .map(|entries| RipPacket {
+                entries,
+                auth: None,
+                authentication: None,
+                ..packet.clone()
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,160 @@
+//! Serving the web UI from a path prefix behind a reverse proxy.
+//!
+//! With `web.base_path` set to e.g. `/routers/r1`, every page, asset and
+//! API route is served below that prefix, `/` redirects to it, and the
+//! pages link to it, so several routers can share one proxy host. Requests
+//! from an address in `web.trusted_proxies` may also carry the standard
+//! forwarding headers: `X-Forwarded-For` replaces the peer address used for
+//! rate limiting, and `X-Forwarded-Prefix` names a prefix the proxy strips
+//! before passing the request on. Those headers are ignored from any other
+//! client, which could otherwise pick its own address.
+
+use axum::http::HeaderMap;
+use ipnet::IpNet;
+use std::net::IpAddr;
+
+pub const FORWARDED_FOR_HEADER: &str = "x-forwarded-for";
+pub const FORWARDED_PREFIX_HEADER: &str = "x-forwarded-prefix";
+
+/// Placeholder in the page templates for the prefix of their links
+const BASE_PATH_META: &str = r#"<meta name="base-path" content="">"#;
+
+/// Prefix the client sees in front of the routes, without a trailing `/`;
+/// empty when the UI is served from the root
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PathPrefix(pub String);
+
+/// `path` as a prefix, with a leading and without a trailing `/`, or an
+/// error naming the problem. An empty path or `/` is the root.
+pub fn normalize(path: &str) -> Result<String, String> {
+    let trimmed = path.trim().trim_end_matches('/');
+    if trimmed.is_empty() {
+        return Ok(String::new());
+    }
+    if !trimmed.starts_with('/') {
+        return Err(format!("'{}' does not start with '/'", path));
+    }
+    if trimmed.contains("//") {
+        return Err(format!("'{}' has an empty segment", path));
+    }
+    if let Some(bad) = trimmed
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '/' | '-' | '_' | '.' | '~')))
+    {
+        return Err(format!("'{}' contains '{}'", path, bad));
+    }
+    if trimmed
+        .split('/')
+        .any(|segment| segment == "." || segment == "..")
+    {
+        return Err(format!("'{}' has a relative segment", path));
+    }
+    Ok(trimmed.to_string())
+}
+
+/// Whether `peer` is one of the `trusted` proxies
+pub fn is_trusted(trusted: &[IpNet], peer: IpAddr) -> bool {
+    // A dual-stack listener sees IPv4 peers as IPv4-mapped IPv6 addresses
+    let peer = peer.to_canonical();
+    trusted.iter().any(|net| net.contains(&peer))
+}
+
+/// The client behind a trusted proxy: the right-most `X-Forwarded-For`
+/// address that is not itself a trusted proxy, since each proxy appends the
+/// address it received the request from
+pub fn forwarded_client(headers: &HeaderMap, trusted: &[IpNet]) -> Option<IpAddr> {
+    let addresses: Vec<IpAddr> = headers
+        .get_all(FORWARDED_FOR_HEADER)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|address| address.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    addresses
+        .iter()
+        .rev()
+        .find(|address| !is_trusted(trusted, **address))
+        .or(addresses.first())
+        .copied()
+}
+
+/// The prefix a trusted proxy strips before forwarding, if it names a valid one
+pub fn forwarded_prefix(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get(FORWARDED_PREFIX_HEADER)?.to_str().ok()?;
+    normalize(value).ok()
+}
+
+/// `template` with its root-relative links and the `base-path` meta tag the
+/// scripts read pointing below `prefix`
+pub fn render_page(template: &str, prefix: &PathPrefix) -> String {
+    if prefix.0.is_empty() {
+        return template.to_string();
+    }
+    template
+        .replace(
+            BASE_PATH_META,
+            &format!(r#"<meta name="base-path" content="{}">"#, prefix.0),
+        )
+        .replace(r#"href="/"#, &format!(r#"href="{}/"#, prefix.0))
+        .replace(r#"src="/"#, &format!(r#"src="{}/"#, prefix.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn base_paths_are_normalized() {
+        assert_eq!(normalize("").unwrap(), "");
+        assert_eq!(normalize("/").unwrap(), "");
+        assert_eq!(normalize("/routers/r1/").unwrap(), "/routers/r1");
+        assert!(normalize("routers").is_err());
+        assert!(normalize("/routers//r1").is_err());
+        assert!(normalize("/routers/../admin").is_err());
+        assert!(normalize("/r1\"><script>").is_err());
+    }
+
+    #[test]
+    fn forwarded_headers_name_the_client_and_prefix() {
+        let trusted: Vec<IpNet> = vec!["10.0.0.0/8".parse().unwrap()];
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            FORWARDED_FOR_HEADER,
+            HeaderValue::from_static("203.0.113.9, 198.51.100.4, 10.0.0.2"),
+        );
+        assert_eq!(
+            forwarded_client(&headers, &trusted),
+            Some("198.51.100.4".parse().unwrap())
+        );
+        headers.insert(FORWARDED_FOR_HEADER, HeaderValue::from_static("10.0.0.3"));
+        assert_eq!(
+            forwarded_client(&headers, &trusted),
+            Some("10.0.0.3".parse().unwrap())
+        );
+        headers.insert(FORWARDED_FOR_HEADER, HeaderValue::from_static("bogus"));
+        assert_eq!(forwarded_client(&headers, &trusted), None);
+
+        headers.insert(FORWARDED_PREFIX_HEADER, HeaderValue::from_static("/r1/"));
+        assert_eq!(forwarded_prefix(&headers).as_deref(), Some("/r1"));
+    }
+
+    #[test]
+    fn pages_link_below_the_prefix() {
+        let template = concat!(
+            r#"<meta name="base-path" content="">"#,
+            r#"<link href="/static/css/style.css"><a href="/routes">"#,
+            r#"<a href="https://example.com"><script src="/static/js/auth.js"></script>"#
+        );
+        let prefix = PathPrefix("/routers/r1".to_string());
+        assert_eq!(
+            render_page(template, &prefix),
+            concat!(
+                r#"<meta name="base-path" content="/routers/r1">"#,
+                r#"<link href="/routers/r1/static/css/style.css"><a href="/routers/r1/routes">"#,
+                r#"<a href="https://example.com"><script src="/routers/r1/static/js/auth.js"></script>"#
+            )
+        );
+        assert_eq!(render_page(template, &PathPrefix::default()), template);
+    }
+}
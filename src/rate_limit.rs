@@ -0,0 +1,272 @@
+//! Request rate limits for the HTTP API.
+//!
+//! With `web.rate_limit.enabled`, every `/api/` request takes a token from
+//! the bucket of its client and from a bucket shared by all clients. Buckets
+//! hold a minute's worth of requests and refill continuously, so a client
+//! may burst up to its limit and then proceeds at the configured rate. A
+//! request finding either bucket empty is answered `429 Too Many Requests`
+//! with `Retry-After`; every limited response carries the `X-RateLimit-*`
+//! headers of the bucket that is closer to running out.
+//!
+//! Clients are told apart by the user of a valid token, then by client
+//! certificate, then by peer address, so a dashboard cannot escape its limit
+//! by logging in again.
+
+use axum::http::{HeaderMap, HeaderValue};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+pub const LIMIT_HEADER: &str = "x-ratelimit-limit";
+pub const REMAINING_HEADER: &str = "x-ratelimit-remaining";
+pub const RESET_HEADER: &str = "x-ratelimit-reset";
+
+/// Idle clients are forgotten once this many are tracked
+const MAX_TRACKED_CLIENTS: usize = 4096;
+
+/// Rate limit settings, under `web.rate_limit`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Requests per minute for each user, certificate or address; 0 for no
+    /// limit
+    #[serde(default = "default_client_per_minute")]
+    pub requests_per_minute: u32,
+    /// Requests per minute across all clients; 0 for no limit
+    #[serde(default = "default_global_per_minute")]
+    pub global_requests_per_minute: u32,
+}
+
+fn default_client_per_minute() -> u32 {
+    120
+}
+
+fn default_global_per_minute() -> u32 {
+    600
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            requests_per_minute: default_client_per_minute(),
+            global_requests_per_minute: default_global_per_minute(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn full(per_minute: u32, now: Instant) -> Self {
+        Self {
+            tokens: per_minute as f64,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, per_minute: u32, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * per_minute as f64 / 60.0).min(per_minute as f64);
+        self.updated = now;
+    }
+
+    /// State of this bucket as seen by headers
+    fn quota(&self, per_minute: u32) -> Quota {
+        let rate = per_minute as f64 / 60.0;
+        Quota {
+            limit: per_minute,
+            remaining: self.tokens.floor() as u32,
+            reset_seconds: ((per_minute as f64 - self.tokens) / rate).ceil() as u64,
+            retry_after_seconds: ((1.0 - self.tokens).max(0.0) / rate).ceil().max(1.0) as u64,
+        }
+    }
+}
+
+/// Remaining requests of one bucket
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Quota {
+    pub limit: u32,
+    pub remaining: u32,
+    /// Seconds until the bucket is full again
+    pub reset_seconds: u64,
+    /// Seconds until the next request would be admitted
+    pub retry_after_seconds: u64,
+}
+
+/// Outcome of one request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decision {
+    pub allowed: bool,
+    /// The bucket closer to running out, or none without any limit
+    pub quota: Option<Quota>,
+}
+
+impl Decision {
+    /// Add the `X-RateLimit-*` headers, and `Retry-After` for a rejection
+    pub fn apply_headers(&self, headers: &mut HeaderMap) {
+        let Some(quota) = self.quota else {
+            return;
+        };
+        headers.insert(LIMIT_HEADER, HeaderValue::from(quota.limit));
+        headers.insert(REMAINING_HEADER, HeaderValue::from(quota.remaining));
+        headers.insert(RESET_HEADER, HeaderValue::from(quota.reset_seconds));
+        if !self.allowed {
+            headers.insert(
+                axum::http::header::RETRY_AFTER,
+                HeaderValue::from(quota.retry_after_seconds),
+            );
+        }
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    global: Bucket,
+    clients: HashMap<String, Bucket>,
+}
+
+#[derive(Debug)]
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    state: Mutex<State>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        let now = Instant::now();
+        Self {
+            state: Mutex::new(State {
+                global: Bucket::full(config.global_requests_per_minute, now),
+                clients: HashMap::new(),
+            }),
+            config,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Admit or reject a request from `client`; a rejected request takes no
+    /// token, so clients that keep retrying recover at the configured rate
+    pub fn check(&self, client: &str) -> Decision {
+        self.check_at(client, Instant::now())
+    }
+
+    fn check_at(&self, client: &str, now: Instant) -> Decision {
+        let per_client = self.config.requests_per_minute;
+        let global_limit = self.config.global_requests_per_minute;
+        if !self.config.enabled || (per_client == 0 && global_limit == 0) {
+            return Decision {
+                allowed: true,
+                quota: None,
+            };
+        }
+
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.clients.len() >= MAX_TRACKED_CLIENTS && !state.clients.contains_key(client) {
+            state.clients.retain(|_, bucket| {
+                bucket.refill(per_client, now);
+                bucket.tokens < per_client as f64
+            });
+        }
+        let mut buckets: Vec<(u32, &mut Bucket)> = Vec::with_capacity(2);
+        let State { global, clients } = &mut *state;
+        if global_limit > 0 {
+            buckets.push((global_limit, global));
+        }
+        if per_client > 0 {
+            let bucket = clients
+                .entry(client.to_string())
+                .or_insert_with(|| Bucket::full(per_client, now));
+            buckets.push((per_client, bucket));
+        }
+
+        for (limit, bucket) in buckets.iter_mut() {
+            bucket.refill(*limit, now);
+        }
+        let allowed = buckets.iter().all(|(_, bucket)| bucket.tokens >= 1.0);
+        if allowed {
+            for (_, bucket) in buckets.iter_mut() {
+                bucket.tokens -= 1.0;
+            }
+        }
+        let quota = buckets
+            .iter()
+            .map(|(limit, bucket)| bucket.quota(*limit))
+            .min_by_key(|quota| quota.remaining);
+        Decision { allowed, quota }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn limiter(client: u32, global: u32) -> RateLimiter {
+        RateLimiter::new(RateLimitConfig {
+            enabled: true,
+            requests_per_minute: client,
+            global_requests_per_minute: global,
+        })
+    }
+
+    #[test]
+    fn clients_burst_to_their_limit_then_refill() {
+        let limiter = limiter(3, 0);
+        let start = Instant::now();
+        for remaining in [2, 1, 0] {
+            let decision = limiter.check_at("user:ops", start);
+            assert!(decision.allowed);
+            assert_eq!(decision.quota.unwrap().remaining, remaining);
+        }
+        let rejected = limiter.check_at("user:ops", start);
+        assert!(!rejected.allowed);
+        assert_eq!(rejected.quota.unwrap().retry_after_seconds, 20);
+        // Other clients have buckets of their own
+        assert!(limiter.check_at("addr:192.0.2.7", start).allowed);
+
+        // Three per minute is one every 20 seconds
+        assert!(
+            !limiter
+                .check_at("user:ops", start + Duration::from_secs(19))
+                .allowed
+        );
+        assert!(
+            limiter
+                .check_at("user:ops", start + Duration::from_secs(20))
+                .allowed
+        );
+
+        let mut headers = HeaderMap::new();
+        rejected.apply_headers(&mut headers);
+        assert_eq!(headers[LIMIT_HEADER], "3");
+        assert_eq!(headers[REMAINING_HEADER], "0");
+        assert_eq!(headers[RESET_HEADER], "60");
+        assert_eq!(headers[axum::http::header::RETRY_AFTER], "20");
+    }
+
+    #[test]
+    fn the_global_limit_covers_all_clients() {
+        let limiter = limiter(10, 2);
+        let now = Instant::now();
+        assert!(limiter.check_at("a", now).allowed);
+        assert!(limiter.check_at("b", now).allowed);
+        let rejected = limiter.check_at("c", now);
+        assert!(!rejected.allowed);
+        assert_eq!(rejected.quota.unwrap().limit, 2);
+        // The rejection took nothing from the client's own bucket
+        assert_eq!(limiter.state.lock().unwrap().clients["c"].tokens, 10.0);
+
+        let disabled = RateLimiter::new(RateLimitConfig::default());
+        assert_eq!(disabled.check("a").quota, None);
+    }
+}
@@ -0,0 +1,269 @@
+//! Redistribution of the host's own routes into RIP.
+//!
+//! `rip.redistribute.kernel` advertises the routes other daemons or the
+//! administrator added to the kernel's main table, and
+//! `rip.redistribute.connected` the subnets of interfaces RIP does not run
+//! on. Each source is advertised with its own metric and route tag. The
+//! kernel table is read every `SCAN_INTERVAL` (Linux only); a route that
+//! leaves it is advertised as unreachable until garbage collection removes
+//! it, as is every redistributed route once its source is switched off.
+
+use ipnet::Ipv4Net;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::net::Ipv4Addr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+use crate::events::{ActivityLevel, EventBus, RouteEvent, WebEvent};
+use crate::kernel::{self, HostRoute};
+use crate::router::Router;
+use crate::routing_table::{RedistributeProtocol, Route, RouteSource, RoutingTable};
+use crate::watchdog::Heartbeat;
+
+/// How often the kernel table is read
+pub const SCAN_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How routes of one source are advertised
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedistributeSource {
+    pub enabled: bool,
+    pub metric: u32,
+    /// RIPv2 route tag, letting neighbors tell these routes apart
+    pub tag: u16,
+}
+
+impl Default for RedistributeSource {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            metric: 1,
+            tag: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedistributeConfig {
+    /// Routes of other sources in the kernel's main table
+    pub kernel: RedistributeSource,
+    /// Subnets of interfaces RIP does not run on
+    pub connected: RedistributeSource,
+}
+
+impl RedistributeConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.kernel.enabled || self.connected.enabled
+    }
+
+    pub fn source(&self, protocol: RedistributeProtocol) -> &RedistributeSource {
+        match protocol {
+            RedistributeProtocol::Kernel => &self.kernel,
+            RedistributeProtocol::Connected => &self.connected,
+        }
+    }
+}
+
+/// The routes to redistribute from `host_routes`, leaving out the subnets of
+/// `rip_interfaces`, which RIP advertises as connected routes already
+pub fn desired(
+    config: &RedistributeConfig,
+    host_routes: &[HostRoute],
+    rip_interfaces: &HashSet<String>,
+) -> BTreeMap<Ipv4Net, Route> {
+    let mut desired = BTreeMap::new();
+    for host_route in host_routes {
+        let protocol = if host_route.connected {
+            RedistributeProtocol::Connected
+        } else {
+            RedistributeProtocol::Kernel
+        };
+        let source = config.source(protocol);
+        if !source.enabled
+            || (host_route.connected && rip_interfaces.contains(&host_route.interface))
+        {
+            continue;
+        }
+        let mut route = Route::new(
+            host_route.destination.network(),
+            host_route.destination.netmask(),
+            Ipv4Addr::UNSPECIFIED,
+            source.metric,
+            host_route.interface.clone(),
+            RouteSource::Redistributed(protocol),
+            None,
+        );
+        route.tag = source.tag;
+        // Of several kernel routes to a prefix the first one listed is used
+        desired.entry(host_route.destination).or_insert(route);
+    }
+    desired
+}
+
+/// Bring the redistributed routes of `table` in line with `desired`,
+/// returning the routes that were added, changed or poisoned
+pub fn sync(table: &mut RoutingTable, desired: &BTreeMap<Ipv4Net, Route>) -> Vec<Route> {
+    let infinity = table.timers().infinity;
+    let current: Vec<Route> = table
+        .get_all_routes()
+        .into_iter()
+        .filter(|route| {
+            matches!(route.source, RouteSource::Redistributed(_)) && route.metric < infinity
+        })
+        .cloned()
+        .collect();
+
+    let mut changed = Vec::new();
+    let mut unchanged = HashSet::new();
+    for route in current {
+        let Ok(prefix) = Ipv4Net::with_netmask(route.destination, route.subnet_mask) else {
+            continue;
+        };
+        match desired.get(&prefix.trunc()) {
+            Some(wanted)
+                if wanted.source == route.source
+                    && wanted.metric == route.metric
+                    && wanted.tag == route.tag
+                    && wanted.interface == route.interface =>
+            {
+                unchanged.insert(prefix.trunc());
+            }
+            // Installed again below with its new attributes
+            Some(_) => {
+                table.remove_route(route.destination, route.subnet_mask);
+            }
+            None => changed.extend(table.poison_route(route.destination, route.subnet_mask)),
+        }
+    }
+
+    for (prefix, route) in desired {
+        if !unchanged.contains(prefix) && table.add_or_replace(route.clone()) {
+            changed.push(route.clone());
+        }
+    }
+    changed
+}
+
+/// Redistribute the routes of the host into the routing table every
+/// `SCAN_INTERVAL`; idle until `rip.redistribute` enables a source
+pub async fn run(router: Arc<RwLock<Router>>, events: EventBus, heartbeat: Heartbeat) {
+    let mut interval = tokio::time::interval(SCAN_INTERVAL);
+    let mut failing = false;
+    loop {
+        interval.tick().await;
+        heartbeat.beat();
+
+        let router = router.read().await;
+        let config = router.rip_config().redistribute.clone();
+        let wanted = if config.is_enabled() {
+            let host_routes = match kernel::host_routes() {
+                Ok(host_routes) => host_routes,
+                Err(err) => {
+                    // Report the failure once rather than on every scan
+                    if !failing {
+                        warn!("Routes are not redistributed: {}", err);
+                        events.publish_activity(
+                            ActivityLevel::Warn,
+                            format!("Routes are not redistributed: {}", err),
+                        );
+                    }
+                    failing = true;
+                    continue;
+                }
+            };
+            if failing {
+                info!("Redistributing routes of the host again");
+            }
+            failing = false;
+            let rip_interfaces: HashSet<String> = router
+                .config()
+                .interfaces
+                .iter()
+                .filter(|interface| interface.enabled)
+                .map(|interface| interface.name.clone())
+                .collect();
+            desired(&config, &host_routes, &rip_interfaces)
+        } else {
+            BTreeMap::new()
+        };
+
+        let infinity = router.rip_config().infinity_metric;
+        for route in router.redistribute(&wanted).await {
+            let event = RouteEvent::from_route(&route);
+            events.publish(if route.metric < infinity {
+                WebEvent::Route(event)
+            } else {
+                WebEvent::RouteWithdrawn(event)
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn host_route(destination: &str, interface: &str, connected: bool) -> HostRoute {
+        HostRoute {
+            destination: destination.parse().unwrap(),
+            gateway: (!connected).then_some(Ipv4Addr::new(192, 168, 9, 1)),
+            interface: interface.to_string(),
+            connected,
+        }
+    }
+
+    #[test]
+    fn host_routes_are_redistributed_with_the_metric_and_tag_of_their_source() {
+        let mut config = RedistributeConfig {
+            kernel: RedistributeSource {
+                enabled: true,
+                metric: 3,
+                tag: 100,
+            },
+            ..RedistributeConfig::default()
+        };
+        config.connected.enabled = true;
+        let host_routes = vec![
+            host_route("172.16.0.0/12", "eth2", false),
+            host_route("192.168.9.0/24", "eth2", true),
+            host_route("192.168.1.0/24", "eth0", true),
+        ];
+        let rip_interfaces = HashSet::from(["eth0".to_string()]);
+
+        let mut table = RoutingTable::new();
+        let wanted = desired(&config, &host_routes, &rip_interfaces);
+        assert_eq!(wanted.len(), 2);
+        assert_eq!(sync(&mut table, &wanted).len(), 2);
+        let kernel_route = table.get_route(Ipv4Addr::new(172, 16, 0, 0)).unwrap();
+        assert_eq!(
+            kernel_route.source,
+            RouteSource::Redistributed(RedistributeProtocol::Kernel)
+        );
+        assert_eq!((kernel_route.metric, kernel_route.tag), (3, 100));
+        assert!(sync(&mut table, &wanted).is_empty());
+
+        // A new metric replaces the route; a route that left the kernel is poisoned
+        config.kernel.metric = 5;
+        let wanted = desired(&config, &host_routes[..1], &rip_interfaces);
+        let changed = sync(&mut table, &wanted);
+        assert_eq!(changed.len(), 2);
+        assert_eq!(
+            table
+                .get_route(Ipv4Addr::new(172, 16, 0, 0))
+                .unwrap()
+                .metric,
+            5
+        );
+        assert_eq!(
+            table
+                .get_route(Ipv4Addr::new(192, 168, 9, 0))
+                .unwrap()
+                .metric,
+            table.timers().infinity
+        );
+    }
+}
@@ -0,0 +1,546 @@
+//! RIPv2 authentication of binary packets (RFC 2453, RFC 2082, RFC 4822).
+//!
+//! An interface listed in `rip.authentication` puts an authentication entry
+//! in front of the routes of every binary packet it sends and drops binary
+//! packets without a valid one. `plaintext` carries a password of up to 16
+//! bytes in the entry itself. The cryptographic modes put a key ID and a
+//! sequence number in the entry and a digest of the whole packet in a
+//! trailer: keyed MD5 as in RFC 2082, HMAC-SHA as in RFC 4822.
+//!
+//! Each interface has a key chain. A key may be limited to a validity
+//! window; packets are sent with the valid key that became valid last, and
+//! are accepted with any valid key, so a new key can be rolled out by
+//! giving it a start time shortly after every router has it, and the old
+//! key an end time a little later. Sequence numbers start from the wall
+//! clock in seconds; a packet with a lower sequence than the last one
+//! accepted from the same neighbor is dropped as a replay.
+//!
+//! JSON packets are signed by `rip.packet_auth` instead.
+
+use chrono::{DateTime, Utc};
+use hmac::{Mac, SimpleHmac};
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha1::digest::core_api::BlockSizeUser;
+use sha1::{Digest, Sha1};
+use sha2::{Sha256, Sha384, Sha512};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Mutex, RwLock};
+
+use crate::protocol::{RipAuthentication, RipPacket};
+use crate::{RustRouteError, RustRouteResult};
+
+/// Longest plaintext password, and longest keyed-MD5 key
+pub const PASSWORD_LENGTH: usize = 16;
+
+/// Bytes of the authentication entry, and of the trailer's header
+const ENTRY_LENGTH: usize = 20;
+const TRAILER_HEADER_LENGTH: usize = 4;
+
+/// Fill of the digest while an HMAC is computed (RFC 4822 section 2.5)
+const APAD: [u8; 4] = [0x87, 0x8F, 0xE1, 0xF3];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthMode {
+    Plaintext,
+    /// Keyed MD5 (RFC 2082)
+    Md5,
+    /// HMAC-SHA1 (RFC 4822)
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl AuthMode {
+    /// Bytes of digest in the trailer; plaintext has no trailer
+    pub fn digest_len(self) -> usize {
+        match self {
+            AuthMode::Plaintext => 0,
+            AuthMode::Md5 => 16,
+            AuthMode::Sha1 => 20,
+            AuthMode::Sha256 => 32,
+            AuthMode::Sha384 => 48,
+            AuthMode::Sha512 => 64,
+        }
+    }
+
+    /// Bytes authentication adds to a packet
+    pub fn overhead(self) -> usize {
+        match self {
+            AuthMode::Plaintext => ENTRY_LENGTH,
+            mode => ENTRY_LENGTH + TRAILER_HEADER_LENGTH + mode.digest_len(),
+        }
+    }
+}
+
+/// One key of a key chain
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuthKey {
+    /// Key ID sent with cryptographic authentication
+    pub id: u8,
+    /// The password, or the secret the digest is keyed with
+    pub key: String,
+    /// When the key starts being valid; always when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_from: Option<DateTime<Utc>>,
+    /// When the key stops being valid; never when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_until: Option<DateTime<Utc>>,
+}
+
+impl AuthKey {
+    pub fn is_valid(&self, now: DateTime<Utc>) -> bool {
+        self.valid_from.is_none_or(|from| from <= now)
+            && self.valid_until.is_none_or(|until| now < until)
+    }
+}
+
+/// Authentication of one interface, under `rip.authentication`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InterfaceAuthConfig {
+    pub interface: String,
+    pub mode: AuthMode,
+    pub keys: Vec<AuthKey>,
+}
+
+impl InterfaceAuthConfig {
+    /// Problems with the key chain, as configuration errors
+    pub fn errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.keys.is_empty() {
+            errors.push(format!(
+                "rip.authentication for {} has no keys",
+                self.interface
+            ));
+        }
+        let mut ids = HashSet::new();
+        for key in &self.keys {
+            if !ids.insert(key.id) {
+                errors.push(format!(
+                    "rip.authentication for {} has key {} twice",
+                    self.interface, key.id
+                ));
+            }
+            if key.key.is_empty() {
+                errors.push(format!(
+                    "rip.authentication for {}: key {} is empty",
+                    self.interface, key.id
+                ));
+            }
+            if matches!(self.mode, AuthMode::Plaintext | AuthMode::Md5)
+                && key.key.len() > PASSWORD_LENGTH
+            {
+                errors.push(format!(
+                    "rip.authentication for {}: key {} is longer than {} bytes",
+                    self.interface, key.id, PASSWORD_LENGTH
+                ));
+            }
+            if let (Some(from), Some(until)) = (key.valid_from, key.valid_until) {
+                if until <= from {
+                    errors.push(format!(
+                        "rip.authentication for {}: key {} expires before it becomes valid",
+                        self.interface, key.id
+                    ));
+                }
+            }
+        }
+        errors
+    }
+
+    /// The key packets are sent with at `now`: of the valid keys, the one
+    /// that became valid last
+    pub fn send_key(&self, now: DateTime<Utc>) -> Option<&AuthKey> {
+        self.keys
+            .iter()
+            .filter(|key| key.is_valid(now))
+            .max_by_key(|key| (key.valid_from, key.id))
+    }
+}
+
+/// Whether `packet` carries the kind of authentication `config` asks for;
+/// whether it is correct is only known from the datagram, see [`KeyChain`]
+pub fn is_authenticated(packet: &RipPacket, config: &InterfaceAuthConfig) -> bool {
+    match (&packet.authentication, config.mode) {
+        (Some(RipAuthentication::Plaintext { .. }), AuthMode::Plaintext) => true,
+        (Some(RipAuthentication::Cryptographic { .. }), mode) => mode != AuthMode::Plaintext,
+        _ => false,
+    }
+}
+
+/// An interface's key chain, the sequence number of its next packet and
+/// the last sequence number accepted from each neighbor
+pub struct KeyChain {
+    config: RwLock<Option<InterfaceAuthConfig>>,
+    sequence: AtomicU32,
+    last_seen: Mutex<HashMap<IpAddr, u32>>,
+}
+
+impl fmt::Debug for KeyChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyChain")
+            .field("enabled", &self.is_enabled())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for KeyChain {
+    fn default() -> Self {
+        Self {
+            config: RwLock::new(None),
+            sequence: AtomicU32::new(Utc::now().timestamp().clamp(0, u32::MAX as i64) as u32),
+            last_seen: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl KeyChain {
+    /// Replace the key chain; `None` sends and accepts packets without
+    /// authentication. Sequence numbers are kept.
+    pub fn configure(&self, config: Option<InterfaceAuthConfig>) {
+        *self
+            .config
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = config;
+    }
+
+    fn read_config(&self) -> std::sync::RwLockReadGuard<'_, Option<InterfaceAuthConfig>> {
+        self.config
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.read_config().is_some()
+    }
+
+    /// Bytes authentication adds to each packet
+    pub fn overhead(&self) -> usize {
+        self.read_config()
+            .as_ref()
+            .map_or(0, |config| config.mode.overhead())
+    }
+
+    /// `packet` in the binary encoding, authenticated with the current key
+    pub fn encode(&self, packet: &RipPacket) -> RustRouteResult<Vec<u8>> {
+        let config = self.read_config();
+        let Some(config) = config.as_ref() else {
+            return packet.to_bytes();
+        };
+        let key = config.send_key(Utc::now()).ok_or_else(|| {
+            auth_error(format!("no valid key to send on {} with", config.interface))
+        })?;
+        let sequence = self
+            .sequence
+            .fetch_add(1, Ordering::Relaxed)
+            .wrapping_add(1);
+        sign(packet, config.mode, key, sequence)
+    }
+
+    /// Check the authentication of `packet`, decoded from `datagram`, which
+    /// was received from `sender`
+    pub fn verify(
+        &self,
+        datagram: &[u8],
+        packet: &RipPacket,
+        sender: IpAddr,
+    ) -> RustRouteResult<()> {
+        let config = self.read_config();
+        let Some(config) = config.as_ref() else {
+            return Ok(());
+        };
+        let Some(sequence) = check(datagram, packet, config, Utc::now())
+            .map_err(|reason| auth_error(format!("{} from {}", reason, sender)))?
+        else {
+            return Ok(());
+        };
+
+        let mut last_seen = self
+            .last_seen
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let last = last_seen.entry(sender).or_insert(0);
+        if sequence < *last {
+            return Err(auth_error(format!(
+                "replayed packet from {} (sequence {} < {})",
+                sender, sequence, last
+            )));
+        }
+        *last = sequence;
+        Ok(())
+    }
+}
+
+fn auth_error(message: String) -> RustRouteError {
+    RustRouteError::ProtocolError(format!("Packet authentication failed: {}", message))
+}
+
+/// `packet` in the binary encoding, authenticated with `key`
+pub fn sign(
+    packet: &RipPacket,
+    mode: AuthMode,
+    key: &AuthKey,
+    sequence: u32,
+) -> RustRouteResult<Vec<u8>> {
+    let mut packet = packet.clone();
+    if mode == AuthMode::Plaintext {
+        packet.authentication = Some(RipAuthentication::Plaintext {
+            password: padded(key.key.as_bytes()),
+        });
+        return packet.to_bytes();
+    }
+
+    let digest_len = mode.digest_len();
+    packet.authentication = Some(RipAuthentication::Cryptographic {
+        packet_length: (4 + ENTRY_LENGTH * (packet.entries.len() + 1)) as u16,
+        key_id: key.id,
+        auth_data_len: digest_len as u8,
+        sequence,
+        digest: fill(mode, key.key.as_bytes()),
+    });
+    let mut bytes = packet.to_bytes()?;
+    let digest = digest(mode, key.key.as_bytes(), &bytes);
+    let start = bytes.len() - digest_len;
+    bytes[start..].copy_from_slice(&digest);
+    Ok(bytes)
+}
+
+/// Check `packet` against `config`, returning the sequence number of a
+/// cryptographically authenticated packet, or why it fails
+fn check(
+    datagram: &[u8],
+    packet: &RipPacket,
+    config: &InterfaceAuthConfig,
+    now: DateTime<Utc>,
+) -> Result<Option<u32>, String> {
+    let valid = || config.keys.iter().filter(move |key| key.is_valid(now));
+    match (&packet.authentication, config.mode) {
+        (None, _) => Err("unauthenticated packet".to_string()),
+        (Some(RipAuthentication::Plaintext { password }), AuthMode::Plaintext) => valid()
+            .any(|key| same(&padded(key.key.as_bytes()), password))
+            .then_some(None)
+            .ok_or_else(|| "wrong password".to_string()),
+        (
+            Some(RipAuthentication::Cryptographic {
+                packet_length,
+                key_id,
+                sequence,
+                digest: received,
+                ..
+            }),
+            mode,
+        ) if mode != AuthMode::Plaintext => {
+            if received.len() != mode.digest_len() {
+                return Err(format!(
+                    "{} byte digest where {:?} has {}",
+                    received.len(),
+                    mode,
+                    mode.digest_len()
+                ));
+            }
+            let key = valid()
+                .find(|key| key.id == *key_id)
+                .ok_or_else(|| format!("unknown or expired key {}", key_id))?;
+            let end = *packet_length as usize + TRAILER_HEADER_LENGTH;
+            let mut bytes = datagram[..end].to_vec();
+            bytes.extend_from_slice(&fill(mode, key.key.as_bytes()));
+            if !same(&digest(mode, key.key.as_bytes(), &bytes), received) {
+                return Err(format!("bad digest with key {}", key_id));
+            }
+            Ok(Some(*sequence))
+        }
+        _ => Err("wrong authentication type".to_string()),
+    }
+}
+
+fn padded(key: &[u8]) -> [u8; PASSWORD_LENGTH] {
+    let mut padded = [0; PASSWORD_LENGTH];
+    let len = key.len().min(PASSWORD_LENGTH);
+    padded[..len].copy_from_slice(&key[..len]);
+    padded
+}
+
+/// What the trailer holds while the digest is computed: the key itself for
+/// keyed MD5, Apad for HMAC
+fn fill(mode: AuthMode, key: &[u8]) -> Vec<u8> {
+    match mode {
+        AuthMode::Md5 => padded(key).to_vec(),
+        mode => APAD
+            .iter()
+            .copied()
+            .cycle()
+            .take(mode.digest_len())
+            .collect(),
+    }
+}
+
+fn digest(mode: AuthMode, key: &[u8], data: &[u8]) -> Vec<u8> {
+    match mode {
+        AuthMode::Plaintext => Vec::new(),
+        AuthMode::Md5 => Md5::digest(data).to_vec(),
+        AuthMode::Sha1 => keyed_hmac::<Sha1>(key, data),
+        AuthMode::Sha256 => keyed_hmac::<Sha256>(key, data),
+        AuthMode::Sha384 => keyed_hmac::<Sha384>(key, data),
+        AuthMode::Sha512 => keyed_hmac::<Sha512>(key, data),
+    }
+}
+
+/// HMAC keyed as RFC 4822 asks: keys longer than the digest are hashed first
+fn keyed_hmac<D: Digest + BlockSizeUser>(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let key = if key.len() > <D as Digest>::output_size() {
+        D::digest(key).to_vec()
+    } else {
+        key.to_vec()
+    };
+    let mut mac = SimpleHmac::<D>::new_from_slice(&key).expect("HMAC accepts any key length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Compare without stopping at the first difference
+fn same(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::RipEntry;
+    use chrono::Duration;
+    use std::net::Ipv4Addr;
+
+    fn packet() -> RipPacket {
+        RipPacket::new_response(vec![RipEntry::new(
+            Ipv4Addr::new(10, 1, 0, 0),
+            Ipv4Addr::new(255, 255, 0, 0),
+            Ipv4Addr::UNSPECIFIED,
+            2,
+        )])
+    }
+
+    fn chain(mode: AuthMode, keys: Vec<AuthKey>) -> KeyChain {
+        let chain = KeyChain::default();
+        chain.configure(Some(InterfaceAuthConfig {
+            interface: "eth0".to_string(),
+            mode,
+            keys,
+        }));
+        chain
+    }
+
+    fn key(id: u8, key: &str) -> AuthKey {
+        AuthKey {
+            id,
+            key: key.to_string(),
+            valid_from: None,
+            valid_until: None,
+        }
+    }
+
+    #[test]
+    fn every_mode_round_trips_and_rejects_tampering() {
+        let sender: IpAddr = "10.0.0.2".parse().unwrap();
+        for mode in [
+            AuthMode::Plaintext,
+            AuthMode::Md5,
+            AuthMode::Sha1,
+            AuthMode::Sha256,
+            AuthMode::Sha384,
+            AuthMode::Sha512,
+        ] {
+            let sending = chain(mode, vec![key(1, "secret")]);
+            let receiving = chain(mode, vec![key(1, "secret")]);
+            let data = sending.encode(&packet()).unwrap();
+            assert_eq!(data.len(), 4 + 20 + mode.overhead());
+
+            let decoded = RipPacket::from_bytes(&data).unwrap();
+            assert_eq!(decoded.entries, packet().entries);
+            assert!(is_authenticated(
+                &decoded,
+                &InterfaceAuthConfig {
+                    interface: "eth0".to_string(),
+                    mode,
+                    keys: Vec::new(),
+                }
+            ));
+            receiving.verify(&data, &decoded, sender).unwrap();
+
+            let wrong = chain(mode, vec![key(1, "other")]);
+            assert!(wrong.verify(&data, &decoded, sender).is_err(), "{:?}", mode);
+
+            if mode != AuthMode::Plaintext {
+                // A changed metric no longer matches the digest
+                let mut tampered = data.clone();
+                tampered[4 + 20 + 19] = 1;
+                let decoded = RipPacket::from_bytes(&tampered).unwrap();
+                assert!(receiving.verify(&tampered, &decoded, sender).is_err());
+            }
+        }
+
+        let unauthenticated = packet().to_bytes().unwrap();
+        let chain = chain(AuthMode::Sha256, vec![key(1, "secret")]);
+        assert!(chain.verify(&unauthenticated, &packet(), sender).is_err());
+    }
+
+    #[test]
+    fn lower_sequence_numbers_are_replays() {
+        let sender: IpAddr = "10.0.0.2".parse().unwrap();
+        let keys = vec![key(1, "secret")];
+        let receiving = chain(AuthMode::Md5, keys.clone());
+        let old = sign(&packet(), AuthMode::Md5, &keys[0], 100).unwrap();
+        let new = sign(&packet(), AuthMode::Md5, &keys[0], 101).unwrap();
+
+        receiving
+            .verify(&new, &RipPacket::from_bytes(&new).unwrap(), sender)
+            .unwrap();
+        receiving
+            .verify(&new, &RipPacket::from_bytes(&new).unwrap(), sender)
+            .unwrap();
+        assert!(receiving
+            .verify(&old, &RipPacket::from_bytes(&old).unwrap(), sender)
+            .is_err());
+    }
+
+    #[test]
+    fn keys_roll_over_by_validity() {
+        let now = Utc::now();
+        let config = InterfaceAuthConfig {
+            interface: "eth0".to_string(),
+            mode: AuthMode::Sha256,
+            keys: vec![
+                AuthKey {
+                    valid_until: Some(now + Duration::minutes(10)),
+                    ..key(1, "old")
+                },
+                AuthKey {
+                    valid_from: Some(now - Duration::minutes(1)),
+                    ..key(2, "new")
+                },
+                AuthKey {
+                    valid_from: Some(now + Duration::minutes(5)),
+                    ..key(3, "next")
+                },
+            ],
+        };
+        assert!(config.errors().is_empty());
+        assert_eq!(config.send_key(now).unwrap().id, 2);
+        assert_eq!(config.send_key(now + Duration::minutes(6)).unwrap().id, 3);
+
+        // Packets sent with the older key are still accepted
+        let data = sign(&packet(), config.mode, &config.keys[0], 1).unwrap();
+        let decoded = RipPacket::from_bytes(&data).unwrap();
+        assert_eq!(check(&data, &decoded, &config, now), Ok(Some(1)));
+        assert!(check(&data, &decoded, &config, now + Duration::minutes(11)).is_err());
+
+        let invalid = InterfaceAuthConfig {
+            interface: "eth0".to_string(),
+            mode: AuthMode::Plaintext,
+            keys: vec![key(1, "a password over sixteen bytes"), key(1, "")],
+        };
+        assert_eq!(invalid.errors().len(), 3);
+    }
+}
@@ -1,21 +1,57 @@
 //! Router implementation for RustRoute
 
-use crate::config_manager::{InterfaceConfig, RipConfig, RouterConfig};
+use crate::config_manager::{
+    InterfaceConfig, NextHopAction, RipConfig, RouterConfig, StaticRouteConfig,
+};
+use crate::consistency::{self, ConsistencyReport};
+use crate::decisions::{Decision, DecisionTrace, Reason};
+use crate::dns::ReverseDns;
+use crate::events::{
+    ActivityLevel, CountToInfinityEvent, EventBus, NeighborEvent, NeighborState, RouteEvent,
+    WebEvent,
+};
+use crate::last_error;
+use crate::link_cost::{self, InterfaceCosts};
+use crate::locale;
+use crate::loop_detector::LoopDetector;
 use crate::metrics::Metrics;
-use crate::network::{InterfaceConfig as NetInterfaceConfig, NetworkInterface};
-use crate::protocol::RipPacket;
-use crate::routing_table::{Route, RouteSource, RoutingTable, RoutingTableStatistics};
+use crate::network::{self, InterfaceConfig as NetInterfaceConfig, NetworkInterface};
+use crate::packet_auth::PacketAuth;
+use crate::plugins::PluginRegistry;
+use crate::policy::{
+    Advertisement, Direction, Maintenance, PolicyHandle, PolicyRoute, RoutePolicy,
+};
+use crate::probe::NextHopProbes;
+use crate::protocol::{RipCommand, RipEntry, RipPacket};
+use crate::redistribute;
+use crate::rip_auth;
+use crate::routing_table::{
+    Route, RouteSource, RoutingTable, RoutingTableStatistics, TableMemory, DEFAULT_DISTANCE,
+};
+use crate::sysstats;
+use crate::timers::RipTimers;
+use crate::watchdog::{Heartbeat, Watchdog};
 use crate::{RustRouteError, RustRouteResult};
+use chrono::{DateTime, Utc};
 use ipnet::{IpNet, Ipv4Net};
-use log::{debug, warn};
+use log::{debug, info, warn};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 use uuid::Uuid;
 
+/// How often receive loops report liveness while no packets are arriving
+pub const RECEIVE_HEARTBEAT: Duration = Duration::from_secs(5);
+/// How often the OS link state of each interface is checked
+pub const LINK_POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// How long a restart waits for the old sockets to be released
+const SOCKET_RELEASE_TIMEOUT: Duration = Duration::from_secs(2);
+/// Namespace of the UUIDs derived from router IDs that are not UUIDs
+pub const ROUTER_UUID_NAMESPACE: Uuid = Uuid::from_u128(0x3c6e_91d2_0f4b_5a87_9e13_6b2d_c84f_a750);
+
 #[derive(Debug, Clone)]
 pub struct NeighborInfo {
     pub address: IpAddr,
@@ -32,8 +68,31 @@ pub struct Router {
     metrics: Metrics,
     neighbors: Arc<RwLock<HashMap<IpAddr, NeighborInfo>>>,
     start_time: Instant,
+    config_applied_at: DateTime<Utc>,
     router_uuid: Uuid,
     interfaces: HashMap<String, Arc<NetworkInterface>>,
+    policy: PolicyHandle,
+    packet_auth: Arc<PacketAuth>,
+    loop_detector: LoopDetector,
+    decisions: DecisionTrace,
+    interface_costs: InterfaceCosts,
+    supervisor: Option<Supervisor>,
+    probes: NextHopProbes,
+    names: ReverseDns,
+}
+
+/// Task supervision the router uses to replace the receive loops of its
+/// interfaces on restart
+#[derive(Clone)]
+pub struct Supervisor {
+    pub watchdog: Watchdog,
+    pub events: EventBus,
+}
+
+impl std::fmt::Debug for Supervisor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Supervisor").finish_non_exhaustive()
+    }
 }
 
 impl Router {
@@ -43,27 +102,80 @@ impl Router {
         metrics: Metrics,
     ) -> RustRouteResult<Self> {
         let router_uuid = Self::derive_router_uuid(&config.router_id);
+        let policy = PolicyHandle::new(RoutePolicy::load(&config.policy)?);
+        policy.set_plugins(PluginRegistry::global().route_policies(&config.plugins));
+        policy.set_restrictions(config.rip.restricted_prefixes.clone());
+        policy.set_distribute_lists(&config.policy);
+        policy.set_offset_lists(&config.policy, config.rip.infinity_metric);
 
+        let packet_auth = Arc::new(PacketAuth::new(&config.rip.packet_auth));
         let interfaces = if config.rip.enabled {
-            Self::initialize_network_interfaces(&config).await?
+            Self::initialize_network_interfaces(&config, &packet_auth, &metrics).await?
         } else {
             HashMap::new()
         };
 
-        let mut router = Self {
+        let decisions = DecisionTrace::new(&config.rip.decision_trace);
+        let interface_costs = InterfaceCosts::default();
+        for (interface, cost) in link_cost::costs(&config) {
+            interface_costs.set(&interface, cost);
+        }
+        let router = Self {
             config,
             routing_table,
             metrics,
             neighbors: Arc::new(RwLock::new(HashMap::new())),
             start_time: Instant::now(),
+            config_applied_at: Utc::now(),
             router_uuid,
             interfaces,
+            policy,
+            packet_auth,
+            loop_detector: LoopDetector::default(),
+            decisions,
+            interface_costs,
+            supervisor: None,
+            probes: NextHopProbes::new(),
+            names: ReverseDns::new(),
         };
 
         router.rebuild_routing_table().await?;
         Ok(router)
     }
 
+    /// A router with a routing table and metrics of its own, for embedding
+    /// and tests; `new` shares them with the web server and other tasks
+    pub async fn with_config(config: RouterConfig) -> RustRouteResult<Self> {
+        Self::new(
+            config,
+            Arc::new(RwLock::new(RoutingTable::new())),
+            Metrics::new(),
+        )
+        .await
+    }
+
+    /// Add a static route to the routing table, returning whether the
+    /// installed route changed
+    pub async fn add_static_route(
+        &self,
+        destination: Ipv4Addr,
+        subnet_mask: Ipv4Addr,
+        next_hop: Ipv4Addr,
+        metric: u32,
+        interface: &str,
+    ) -> bool {
+        let added = self.routing_table.write().await.add_static_route(
+            destination,
+            subnet_mask,
+            next_hop,
+            metric,
+            interface.to_string(),
+        );
+        let count = self.routing_table.read().await.route_count();
+        self.metrics.update_route_count(count);
+        added
+    }
+
     pub fn router_id(&self) -> &str {
         &self.config.router_id
     }
@@ -72,10 +184,24 @@ impl Router {
         self.router_uuid
     }
 
+    /// Signing state and authentication failure counters
+    pub fn packet_auth(&self) -> Arc<PacketAuth> {
+        Arc::clone(&self.packet_auth)
+    }
+
+    /// Recent decisions on received routes, when `rip.decision_trace` is on
+    pub fn decisions(&self) -> DecisionTrace {
+        self.decisions.clone()
+    }
+
     pub fn metrics(&self) -> Metrics {
         self.metrics.clone()
     }
 
+    pub fn policy(&self) -> PolicyHandle {
+        self.policy.clone()
+    }
+
     pub fn routing_table(&self) -> Arc<RwLock<RoutingTable>> {
         Arc::clone(&self.routing_table)
     }
@@ -92,80 +218,921 @@ impl Router {
         Arc::clone(&self.neighbors)
     }
 
+    /// Reachability probe results of learned next hops
+    pub fn probes(&self) -> NextHopProbes {
+        self.probes.clone()
+    }
+
+    /// Cache of reverse DNS names for the API
+    pub fn names(&self) -> ReverseDns {
+        self.names.clone()
+    }
+
     pub fn network_interfaces(&self) -> Vec<Arc<NetworkInterface>> {
         self.interfaces.values().cloned().collect()
     }
 
+    /// Interfaces that should carry periodic updates: administratively up and not passive
+    pub fn advertising_interfaces(&self) -> Vec<Arc<NetworkInterface>> {
+        self.interfaces
+            .values()
+            .filter(|iface| iface.is_up())
+            .filter(|iface| !iface.update_destinations().is_empty())
+            .filter(|iface| {
+                !self
+                    .config
+                    .interfaces
+                    .iter()
+                    .any(|cfg| cfg.name == iface.config.name && cfg.passive)
+            })
+            .cloned()
+            .collect()
+    }
+
     pub fn rip_config(&self) -> &RipConfig {
         &self.config.rip
     }
 
+    pub fn timers(&self) -> RipTimers {
+        RipTimers::from_config(&self.config.rip)
+    }
+
     pub fn rip_enabled(&self) -> bool {
         self.config.rip.enabled && !self.interfaces.is_empty()
     }
 
+    /// Whether RIP is enabled and a supervised receive loop runs for every
+    /// interface; a router without a supervisor is never running
+    pub fn is_running(&self) -> bool {
+        let Some(supervisor) = &self.supervisor else {
+            return false;
+        };
+        self.rip_enabled()
+            && self
+                .interfaces
+                .keys()
+                .all(|name| supervisor.watchdog.contains(&receive_task_name(name)))
+    }
+
     pub fn uptime(&self) -> Duration {
         self.start_time.elapsed()
     }
 
+    /// Shared handles for the RIP packet processing tasks
+    pub fn rip_context(&self, events: EventBus) -> RipContext {
+        RipContext {
+            routing_table: Arc::clone(&self.routing_table),
+            neighbors: Arc::clone(&self.neighbors),
+            metrics: self.metrics.clone(),
+            rip_config: Arc::new(self.config.rip.clone()),
+            events,
+            router_uuid: self.router_uuid,
+            policy: self.policy.clone(),
+            loop_detector: self.loop_detector.clone(),
+            decisions: self.decisions.clone(),
+            interface_costs: self.interface_costs.clone(),
+            quiet_until: self
+                .quiet_remaining()
+                .map(|remaining| Instant::now() + remaining),
+        }
+    }
+
+    /// Time left of `rip.startup_quiet_period`, during which routes are
+    /// learned but not advertised
+    pub fn quiet_remaining(&self) -> Option<Duration> {
+        Duration::from_secs(self.config.rip.startup_quiet_period)
+            .checked_sub(self.start_time.elapsed())
+            .filter(|remaining| !remaining.is_zero())
+    }
+
     pub async fn apply_config(&mut self, config: RouterConfig) -> RustRouteResult<()> {
-        self.config = config;
+        self.packet_auth.configure(&config.rip.packet_auth);
+        self.decisions.configure(&config.rip.decision_trace);
+        self.policy.replace(RoutePolicy::load(&config.policy)?);
+        self.policy
+            .set_plugins(PluginRegistry::global().route_policies(&config.plugins));
+        self.policy
+            .set_restrictions(config.rip.restricted_prefixes.clone());
+        self.policy.set_distribute_lists(&config.policy);
+        self.policy
+            .set_offset_lists(&config.policy, config.rip.infinity_metric);
+        let previous = std::mem::replace(&mut self.config, config);
+        self.config_applied_at = Utc::now();
         self.router_uuid = Self::derive_router_uuid(&self.config.router_id);
 
         if self.config.rip.enabled {
-            warn!(
-                "Runtime interface reconfiguration is only partially supported; please restart after interface changes."
+            self.sync_interfaces(&previous.interfaces).await;
+        }
+        self.rebuild_routing_table().await?;
+        self.withdraw_hidden_subnets(&previous.interfaces).await;
+        self.withdraw_static_routes(&previous).await;
+        Ok(())
+    }
+
+    /// Poison the configured static routes that are gone, and advertise as
+    /// unreachable those no longer advertised, so neighbors drop them now
+    async fn withdraw_static_routes(&self, previous: &RouterConfig) {
+        let current: HashMap<Ipv4Net, &StaticRouteConfig> = self
+            .config
+            .active_static_routes()
+            .map(|route| (route.prefix.trunc(), route))
+            .collect();
+        let infinity = self.config.rip.infinity_metric;
+        let mut withdrawn = Vec::new();
+        {
+            let mut table = self.routing_table.write().await;
+            for old in previous.active_static_routes() {
+                let prefix = old.prefix.trunc();
+                match current.get(&prefix) {
+                    Some(route) if route.advertise || !old.advertise => {}
+                    Some(route) => withdrawn.push(Route::new(
+                        prefix.network(),
+                        prefix.netmask(),
+                        route.next_hop,
+                        infinity,
+                        route.interface.clone(),
+                        RouteSource::Static,
+                        Some(route.next_hop),
+                    )),
+                    None => {
+                        let installed = table
+                            .equal_cost_paths(prefix.network(), prefix.netmask())
+                            .first()
+                            .is_some_and(|route| route.source == RouteSource::Static);
+                        if installed {
+                            withdrawn
+                                .extend(table.poison_route(prefix.network(), prefix.netmask()));
+                        }
+                    }
+                }
+            }
+        }
+        if !withdrawn.is_empty() {
+            info!("Withdrawing {} static routes", withdrawn.len());
+            self.send_triggered_update(&withdrawn).await;
+        }
+    }
+
+    /// Poison the connected routes of interfaces that stopped advertising
+    /// them, so neighbors drop them now rather than when they time out
+    async fn withdraw_hidden_subnets(&self, previous: &[InterfaceConfig]) {
+        let hidden: HashSet<&str> = self
+            .config
+            .interfaces
+            .iter()
+            .filter(|iface| !iface.advertise_connected)
+            .filter(|iface| {
+                previous
+                    .iter()
+                    .any(|old| old.name == iface.name && old.advertise_connected)
+            })
+            .map(|iface| iface.name.as_str())
+            .collect();
+        if hidden.is_empty() {
+            return;
+        }
+        let infinity = self.config.rip.infinity_metric;
+        let withdrawn: Vec<Route> = self
+            .routing_table
+            .read()
+            .await
+            .get_all_routes()
+            .into_iter()
+            .filter(|route| {
+                route.source == RouteSource::Direct && hidden.contains(route.interface.as_str())
+            })
+            .map(|route| Route {
+                metric: infinity,
+                ..route.clone()
+            })
+            .collect();
+        if !withdrawn.is_empty() {
+            info!(
+                "Withdrawing {} connected routes no longer advertised",
+                withdrawn.len()
             );
+            self.send_triggered_update(&withdrawn).await;
         }
-        self.rebuild_routing_table().await
     }
 
-    pub async fn restart(&mut self) -> RustRouteResult<()> {
+    /// Align the administrative state of running interfaces with the configuration,
+    /// bringing up interfaces that were enabled since startup and rebinding
+    /// those whose address was edited since `previous`.
+    async fn sync_interfaces(&mut self, previous: &[InterfaceConfig]) {
+        let configured: Vec<InterfaceConfig> = self.config.interfaces.clone();
+        let mut disabled = Vec::new();
+        let mut brought_up = Vec::new();
+
+        for iface in &configured {
+            if let Some(running) = self.interfaces.get(&iface.name) {
+                running.set_sender_filter(iface.sender_filter());
+                running.set_pacing(iface.pacing);
+                running.set_key_chain(self.config.rip.authentication_for(&iface.name).cloned());
+                let was_up = running.set_admin_up(iface.enabled);
+                if was_up != iface.enabled {
+                    info!(
+                        "Interface {} administratively {}",
+                        iface.name,
+                        if iface.enabled { "enabled" } else { "disabled" }
+                    );
+                    if was_up {
+                        disabled.push(iface.name.clone());
+                    } else {
+                        brought_up.push(iface.name.clone());
+                    }
+                }
+                continue;
+            }
+
+            if !iface.enabled {
+                continue;
+            }
+
+            match Self::start_interface(iface, &self.config.rip, &self.packet_auth, &self.metrics)
+                .await
+            {
+                Ok(Some(interface)) => {
+                    info!("Interface {} brought up at runtime", iface.name);
+                    self.interfaces
+                        .insert(iface.name.clone(), Arc::new(interface));
+                    brought_up.push(iface.name.clone());
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    warn!(
+                        "Skipping interface {} ({}): {}",
+                        iface.name, iface.address, err
+                    );
+                    last_error::record(
+                        &last_error::interface(&iface.name),
+                        format!("Not started: {}", err),
+                    );
+                }
+            }
+        }
+
+        for (name, running) in &self.interfaces {
+            if !configured.iter().any(|iface| &iface.name == name) && running.set_admin_up(false) {
+                warn!(
+                    "Interface {} removed from configuration; disabling until restart",
+                    name
+                );
+                disabled.push(name.clone());
+            }
+        }
+
+        for name in disabled {
+            self.withdraw_interface(&name).await;
+        }
+        for name in brought_up {
+            self.request_tables(Some(&name)).await;
+        }
+
+        for iface in &configured {
+            let Some(running) = self.interfaces.get(&iface.name) else {
+                continue;
+            };
+            let Some(net) = parse_ipv4_net(iface).ok().flatten() else {
+                continue;
+            };
+            let edited = previous
+                .iter()
+                .find(|old| old.name == iface.name)
+                .is_some_and(|old| old.address.trim() != iface.address.trim());
+            if !edited || net.addr() == running.ip_address() {
+                continue;
+            }
+            if let Err(err) = running.rebind(net.addr(), net.netmask()).await {
+                warn!(
+                    "Keeping {} on {}: cannot rebind to {}: {}",
+                    iface.name,
+                    running.ip_address(),
+                    net,
+                    err
+                );
+                last_error::record(
+                    &last_error::interface(&iface.name),
+                    format!("Cannot rebind to {}: {}", net, err),
+                );
+            }
+        }
+    }
+
+    /// Rebind interfaces whose address disappeared from the OS, such as after
+    /// DHCP renumbered them, and move their connected routes along. Returns
+    /// the interfaces that moved with their old and new addresses.
+    pub async fn follow_os_addresses(&self) -> Vec<(String, Ipv4Addr, Ipv4Net)> {
+        let mut moved = Vec::new();
+        let Some(local) = network::utils::os_local_addresses() else {
+            return moved;
+        };
+        for iface in self.interfaces.values() {
+            // Bound to an address the host still has, even if on another interface
+            let current = iface.ip_address();
+            if local.contains(&current) {
+                continue;
+            }
+            let Some(assigned) = network::utils::os_ipv4_addresses(&iface.config.name) else {
+                continue;
+            };
+            if assigned.is_empty() {
+                continue;
+            }
+
+            let new = assigned[0];
+            match iface.rebind(new.addr(), new.netmask()).await {
+                Ok(()) => moved.push((iface.config.name.clone(), current, new)),
+                Err(err) => {
+                    warn!(
+                        "{} was renumbered to {} but cannot be rebound: {}",
+                        iface.config.name, new, err
+                    );
+                    last_error::record(
+                        &last_error::interface(&iface.config.name),
+                        format!("Cannot rebind to {}: {}", new, err),
+                    );
+                }
+            }
+        }
+
+        if !moved.is_empty() {
+            if let Err(err) = self.rebuild_routing_table().await {
+                warn!("Failed to update connected routes: {}", err);
+            }
+            for (name, _, _) in &moved {
+                self.request_tables(Some(name)).await;
+            }
+        }
+        moved
+    }
+
+    /// Let [`restart`](Self::restart) stop and respawn the receive loops
+    pub fn set_supervisor(&mut self, supervisor: Supervisor) {
+        self.supervisor = Some(supervisor);
+    }
+
+    /// Close and reopen every interface socket, forget neighbors and the
+    /// routes learned from them, and ask the neighbors for their tables
+    /// again. Without a supervisor only the table and counters are reset.
+    pub async fn restart(&mut self) -> RustRouteResult<RestartOutcome> {
+        if let Some(supervisor) = &self.supervisor {
+            for name in self.interfaces.keys() {
+                supervisor.watchdog.remove(&receive_task_name(name));
+            }
+            let released: Vec<_> = self
+                .interfaces
+                .drain()
+                .map(|(_, iface)| Arc::downgrade(&iface))
+                .collect();
+            // Aborted receive loops drop their handle on the socket shortly
+            let deadline = Instant::now() + SOCKET_RELEASE_TIMEOUT;
+            while released.iter().any(|iface| iface.strong_count() > 0) && Instant::now() < deadline
+            {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            if self.config.rip.enabled {
+                self.interfaces = Self::initialize_network_interfaces(
+                    &self.config,
+                    &self.packet_auth,
+                    &self.metrics,
+                )
+                .await?;
+            }
+        }
+
+        let neighbors_cleared = {
+            let mut neighbors = self.neighbors.write().await;
+            let count = neighbors.len();
+            neighbors.clear();
+            count
+        };
+        let routes_cleared = self
+            .routing_table
+            .write()
+            .await
+            .clear_source(RouteSource::Dynamic);
         self.metrics.reset();
         self.start_time = Instant::now();
-        self.rebuild_routing_table().await
+        self.rebuild_routing_table().await?;
+
+        let mut outcome = RestartOutcome {
+            interfaces: self.interfaces.len(),
+            neighbors_cleared,
+            routes_cleared,
+            requests_sent: 0,
+        };
+        if let Some(supervisor) = &self.supervisor {
+            if self.rip_enabled() {
+                let ctx = self.rip_context(supervisor.events.clone());
+                ensure_receive_tasks(&supervisor.watchdog, &self.network_interfaces(), &ctx);
+                outcome.requests_sent = self.request_tables(None).await;
+            }
+        }
+        info!(
+            "Router restarted: {} interfaces reopened, {} neighbors and {} learned routes cleared",
+            outcome.interfaces, outcome.neighbors_cleared, outcome.routes_cleared
+        );
+        Ok(outcome)
     }
 
     pub async fn statistics(&self) -> RouterStatistics {
         let routing_table = self.routing_table.read().await;
         let table_stats = routing_table.get_stats();
+        let routes_by_source = routing_table.count_by_source();
+        let table_memory = routing_table.memory_estimate();
         let neighbor_count = self.neighbors.read().await.len();
         let metrics_snapshot = self
             .metrics
             .snapshot(neighbor_count, table_stats.total_routes);
+        let memory_usage = sysstats::process_memory_bytes();
 
         RouterStatistics {
-            uptime: format_duration(metrics_snapshot.uptime_seconds),
+            uptime: self
+                .config
+                .locale
+                .format_duration(metrics_snapshot.uptime_seconds),
+            uptime_seconds: metrics_snapshot.uptime_seconds,
             packets_sent: metrics_snapshot.packets_sent,
             packets_received: metrics_snapshot.packets_received,
             route_count: table_stats.total_routes,
             neighbor_count,
-            memory_usage: current_process_memory_bytes(),
+            memory_usage,
+            memory_usage_human: locale::format_bytes(memory_usage),
             table_breakdown: table_stats,
+            table_memory,
+            routes_by_source,
+            last_config_change: Some(self.config_applied_at),
+            quiet_seconds_remaining: self
+                .quiet_remaining()
+                .map(|remaining| remaining.as_secs().max(1)),
         }
     }
 
-    async fn rebuild_routing_table(&mut self) -> RustRouteResult<()> {
-        let mut table = self.routing_table.write().await;
+    /// Record the link state of `interface` as reported by the OS. A link
+    /// going down withdraws its routes at once; one coming back reinstalls
+    /// its connected routes. Returns the routes withdrawn.
+    pub async fn set_link_state(&self, interface: &str, up: bool) -> RustRouteResult<Vec<Route>> {
+        let Some(running) = self.interfaces.get(interface) else {
+            return Ok(Vec::new());
+        };
+        let was_up = running.set_link_up(up);
+        match (was_up, up) {
+            (true, false) => {
+                warn!("Link on {} went down", interface);
+                Ok(self.withdraw_interface(interface).await)
+            }
+            (false, true) => {
+                info!("Link on {} came back up", interface);
+                self.rebuild_routing_table().await?;
+                self.request_tables(Some(interface)).await;
+                Ok(Vec::new())
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// Mark every connected and learned route out of `interface` unreachable
+    /// and send the poison out of the remaining interfaces straight away,
+    /// rather than leaving neighbors to age the routes out
+    pub async fn withdraw_interface(&self, interface: &str) -> Vec<Route> {
+        let withdrawn = {
+            let mut table = self.routing_table.write().await;
+            let mut withdrawn = table.poison_interface(interface, RouteSource::Direct);
+            withdrawn.extend(table.poison_interface(interface, RouteSource::Dynamic));
+            self.metrics.update_route_count(table.route_count());
+            withdrawn
+        };
+        if withdrawn.is_empty() {
+            return withdrawn;
+        }
+
+        info!(
+            "Withdrawing {} route(s) learned via {}",
+            withdrawn.len(),
+            interface
+        );
+        self.send_triggered_update(&withdrawn).await;
+        withdrawn
+    }
+
+    pub fn maintenance(&self) -> Option<Maintenance> {
+        self.policy.maintenance()
+    }
+
+    /// Enter maintenance mode, or leave it with `None`, and advertise the
+    /// whole table at once so neighbors react before the next periodic
+    /// update. Returns the interfaces the table was sent on.
+    pub async fn set_maintenance(&self, maintenance: Option<Maintenance>) -> usize {
+        self.policy.set_maintenance(maintenance);
+        let mut sent = 0;
+        if self.quiet_remaining().is_some() {
+            return sent;
+        }
+        let rip = &self.config.rip;
+        for iface in self.advertising_interfaces() {
+            let routes = self.routing_table.read().await.advertise(
+                &iface.config.name,
+                rip.split_horizon_mode(),
+                rip.infinity_metric,
+            );
+            let advertisement = self.policy.export(routes, &iface.config.name);
+            match send_advertisement(&iface, &advertisement).await {
+                Ok(false) => {}
+                Ok(true) => {
+                    iface.record_update_sent();
+                    self.metrics.increment_packets_sent();
+                    self.metrics.increment_routing_updates_sent();
+                    sent += 1;
+                }
+                Err(err) => warn!("Failed to send update on {}: {}", iface.config.name, err),
+            }
+        }
+        sent
+    }
 
-        // Remove previously derived direct routes before re-applying
-        table.clear_source(RouteSource::Direct);
+    /// Bring the redistributed routes in line with `desired` and advertise
+    /// the ones that changed at once, returning them
+    pub async fn redistribute(&self, desired: &BTreeMap<Ipv4Net, Route>) -> Vec<Route> {
+        let changed = {
+            let mut table = self.routing_table.write().await;
+            let changed = redistribute::sync(&mut table, desired);
+            self.metrics.update_route_count(table.route_count());
+            changed
+        };
+        if !changed.is_empty() {
+            self.send_triggered_update(&changed).await;
+        }
+        changed
+    }
 
+    /// Bring the cost of each interface in line with its configuration and
+    /// link speed, repricing the routes learned on those whose cost changed
+    /// and advertising them. Returns each changed interface with its old and
+    /// new cost and the routes that changed.
+    pub async fn update_interface_costs(&self) -> Vec<(String, u32, u32, Vec<Route>)> {
+        let mut changes = Vec::new();
+        for (interface, cost) in link_cost::costs(&self.config) {
+            let old = self.interface_costs.set(&interface, cost);
+            if old != cost {
+                let routes = self
+                    .routing_table
+                    .write()
+                    .await
+                    .reprice(&interface, old, cost);
+                changes.push((interface, old, cost, routes));
+            }
+        }
+        let changed: Vec<Route> = changes
+            .iter()
+            .flat_map(|(_, _, _, routes)| routes.iter().cloned())
+            .collect();
+        if !changed.is_empty() {
+            self.send_triggered_update(&changed).await;
+        }
+        changes
+    }
+
+    /// Process `entries` as a response received from `neighbor` on
+    /// `interface`, so convergence can be exercised without a second
+    /// router. Returns the routes that changed.
+    pub async fn inject_update(
+        &self,
+        events: EventBus,
+        interface: &str,
+        neighbor: Ipv4Addr,
+        entries: Vec<RipEntry>,
+    ) -> RustRouteResult<Vec<Route>> {
+        let configured = self
+            .config
+            .interfaces
+            .iter()
+            .any(|iface| iface.name == interface)
+            || self
+                .config
+                .tunnels
+                .iter()
+                .any(|tunnel| tunnel.name == interface);
+        if !configured {
+            return Err(RustRouteError::InvalidInput(format!(
+                "Unknown interface {}",
+                interface
+            )));
+        }
+        let ctx = self.rip_context(events);
+        let sender = SocketAddr::new(IpAddr::V4(neighbor), self.config.rip.port);
+        let known = ctx.neighbors.read().await.contains_key(&sender.ip());
+        let routes = handle_rip_response(
+            &ctx,
+            interface.to_string(),
+            RipPacket::new_response(entries),
+            sender,
+        )
+        .await?;
+        if !known {
+            ctx.events.publish(WebEvent::Neighbor(NeighborEvent::new(
+                sender.ip(),
+                Some(interface.to_string()),
+                NeighborState::Up,
+            )));
+        }
+        for route in &routes {
+            ctx.events
+                .publish(WebEvent::Route(RouteEvent::from_route(route)));
+        }
+        Ok(routes)
+    }
+
+    /// Advertise `routes` on every advertising interface, with split horizon
+    async fn send_triggered_update(&self, routes: &[Route]) {
+        if self.quiet_remaining().is_some() {
+            return;
+        }
+        let rip = &self.config.rip;
+        for iface in self.advertising_interfaces() {
+            let routes: Vec<Route> = routes
+                .iter()
+                .filter_map(|route| {
+                    rip.split_horizon_mode()
+                        .apply(route, &iface.config.name, rip.infinity_metric)
+                })
+                .collect();
+            if routes.is_empty() {
+                continue;
+            }
+            let advertisement = self.policy.export(routes, &iface.config.name);
+            match send_advertisement(&iface, &advertisement).await {
+                Ok(false) => {}
+                Ok(true) => {
+                    iface.record_update_sent();
+                    self.metrics.increment_packets_sent();
+                    self.metrics.increment_routing_updates_sent();
+                }
+                Err(err) => warn!(
+                    "Failed to send triggered update on {}: {}",
+                    iface.config.name, err
+                ),
+            }
+        }
+    }
+
+    /// The connected routes the enabled interfaces call for: their subnets,
+    /// secondary subnets and point-to-point peers outside them
+    pub fn connected_routes(&self) -> RustRouteResult<Vec<(Ipv4Addr, Ipv4Addr, String)>> {
+        let mut desired = Vec::new();
         for iface in &self.config.interfaces {
-            if !iface.enabled {
+            let link_up = self
+                .interfaces
+                .get(&iface.name)
+                .is_none_or(|running| running.is_link_up());
+            if !iface.enabled || !link_up {
                 continue;
             }
 
-            if let Some(net) = parse_ipv4_net(iface)? {
-                table.install_direct_route(net.network(), net.netmask(), iface.name.clone());
+            if let Some(configured) = parse_ipv4_net(iface)? {
+                // Connected routes follow the address the socket is bound to
+                let net = self
+                    .interfaces
+                    .get(&iface.name)
+                    .and_then(|running| {
+                        Ipv4Net::with_netmask(running.ip_address(), running.subnet_mask()).ok()
+                    })
+                    .unwrap_or(configured);
+                desired.push((net.network(), net.netmask(), iface.name.clone()));
+                for secondary in &iface.secondary_addresses {
+                    desired.push((secondary.network(), secondary.netmask(), iface.name.clone()));
+                }
+
+                // Peers outside a /32 are reachable only through the link itself
+                if let Some(peer) = iface.link_peer().filter(|peer| !net.contains(peer)) {
+                    desired.push((peer, Ipv4Addr::BROADCAST, iface.name.clone()));
+                }
             }
         }
+        Ok(desired)
+    }
+
+    /// Audit the routing table against the interfaces of the host and the
+    /// connected routes of the configuration
+    pub async fn check_consistency(&self) -> RustRouteResult<ConsistencyReport> {
+        let connected = self.connected_routes()?;
+        let mut interfaces: HashSet<String> = sysstats::interface_counters().into_keys().collect();
+        interfaces.extend(
+            self.config
+                .interfaces
+                .iter()
+                .map(|iface| iface.name.clone()),
+        );
+        let tunnels: HashSet<String> = self
+            .config
+            .tunnels
+            .iter()
+            .map(|tunnel| tunnel.name.clone())
+            .collect();
+        let table = self.routing_table.read().await;
+        Ok(consistency::check(
+            &table,
+            &connected,
+            &interfaces,
+            &tunnels,
+        ))
+    }
+
+    async fn rebuild_routing_table(&self) -> RustRouteResult<()> {
+        let desired = self.connected_routes()?;
+        let mut table = self.routing_table.write().await;
+        table.set_tie_break(self.config.rip.tie_break);
+        table.set_max_paths(self.config.rip.max_paths);
+        table.set_history_limit(self.config.rip.route_history);
+        table.set_timers(self.timers());
+        table.set_unadvertised_interfaces(
+            self.config
+                .interfaces
+                .iter()
+                .filter(|iface| !iface.advertise_connected)
+                .map(|iface| iface.name.clone())
+                .collect(),
+        );
+
+        // Poison connected routes that no longer belong to an enabled interface so
+        // neighbors learn about the withdrawal before garbage collection removes them
+        let wanted: HashSet<(Ipv4Addr, Ipv4Addr, &str)> = desired
+            .iter()
+            .map(|(network, mask, name)| (*network, *mask, name.as_str()))
+            .collect();
+        let stale: Vec<(Ipv4Addr, Ipv4Addr)> = table
+            .get_all_routes()
+            .into_iter()
+            .filter(|route| {
+                route.source == RouteSource::Direct
+                    && route.metric < self.config.rip.infinity_metric
+            })
+            .filter(|route| {
+                !wanted.contains(&(
+                    route.destination,
+                    route.subnet_mask,
+                    route.interface.as_str(),
+                ))
+            })
+            .map(|route| (route.destination, route.subnet_mask))
+            .collect();
+        for (destination, mask) in stale {
+            table.poison_route(destination, mask);
+        }
+
+        for (network, mask, name) in desired {
+            table.install_direct_route(network, mask, name);
+        }
+
+        for route in self.config.active_static_routes() {
+            table.add_static_route(
+                route.prefix.network(),
+                route.prefix.netmask(),
+                route.next_hop,
+                route.metric,
+                route.interface.clone(),
+            );
+        }
+        table.set_unadvertised_static(
+            self.config
+                .static_routes
+                .iter()
+                .filter(|route| !route.advertise)
+                .map(|route| route.prefix.trunc())
+                .collect(),
+        );
 
         self.metrics.update_route_count(table.route_count());
 
         Ok(())
     }
 
+    /// Flush routes of `source` and ask neighbors to resend their tables.
+    /// Connected routes follow interface state and cannot be cleared.
+    pub async fn clear_routes(&self, source: RouteSource) -> RustRouteResult<ClearOutcome> {
+        if source == RouteSource::Direct {
+            return Err(RustRouteError::InvalidInput(
+                "Connected routes follow interface state and cannot be cleared".to_string(),
+            ));
+        }
+
+        let routes_removed = {
+            let mut table = self.routing_table.write().await;
+            let removed = table.clear_source(source);
+            self.metrics.update_route_count(table.route_count());
+            removed
+        };
+
+        if source == RouteSource::Dynamic {
+            for neighbor in self.neighbors.write().await.values_mut() {
+                neighbor.learned_routes = 0;
+            }
+        }
+
+        let requests_sent = self.request_tables(None).await;
+        info!(
+            "Cleared {} {} routes; sent {} RIP requests",
+            routes_removed,
+            source.as_str(),
+            requests_sent
+        );
+
+        Ok(ClearOutcome {
+            routes_removed,
+            requests_sent,
+        })
+    }
+
+    /// Forget a neighbor and the routes learned from it, then ask it to resend its table.
+    /// Returns `None` when the neighbor is unknown.
+    pub async fn clear_neighbor(&self, address: IpAddr) -> Option<ClearOutcome> {
+        // A source locked out for failing authentication is no neighbor yet
+        let lifted = self.packet_auth.clear_failures(address);
+        let Some(neighbor) = self.neighbors.write().await.remove(&address) else {
+            return lifted.then_some(ClearOutcome {
+                routes_removed: 0,
+                requests_sent: 0,
+            });
+        };
+
+        let routes_removed = match address {
+            IpAddr::V4(v4) => {
+                let mut table = self.routing_table.write().await;
+                let removed = table.remove_learned_from(v4);
+                self.metrics.update_route_count(table.route_count());
+                removed
+            }
+            IpAddr::V6(_) => 0,
+        };
+
+        let target = SocketAddr::new(address, self.config.rip.port);
+        let requests_sent = self
+            .request_table_from(target, neighbor.interface.as_deref())
+            .await;
+        info!(
+            "Cleared neighbor {} ({} routes); sent {} RIP requests",
+            address, routes_removed, requests_sent
+        );
+
+        Some(ClearOutcome {
+            routes_removed,
+            requests_sent,
+        })
+    }
+
+    /// Send a whole-table RIP Request out of `interface`, or out of every
+    /// interface that advertises, so neighbors answer at once instead of at
+    /// their next periodic update. Returns the number of requests sent.
+    pub async fn request_tables(&self, interface: Option<&str>) -> usize {
+        let request = RipPacket::new_request();
+        let mut sent = 0;
+
+        for iface in self.advertising_interfaces() {
+            if interface.is_some_and(|name| name != iface.config.name) {
+                continue;
+            }
+            match iface.send_packet(&request).await {
+                Ok(()) => {
+                    iface.record_request_sent();
+                    self.metrics.increment_packets_sent();
+                    sent += 1;
+                }
+                Err(err) => warn!(
+                    "Failed to send RIP request on {}: {}",
+                    iface.config.name, err
+                ),
+            }
+        }
+
+        sent
+    }
+
+    /// Unicast a whole-table RIP Request to a single neighbor, on `interface`
+    /// when known. Returns the number of requests sent.
+    async fn request_table_from(&self, target: SocketAddr, interface: Option<&str>) -> usize {
+        let request = RipPacket::new_request();
+        let mut sent = 0;
+
+        for iface in self.interfaces.values() {
+            if !iface.is_up() || interface.is_some_and(|name| name != iface.config.name) {
+                continue;
+            }
+
+            match iface.send_packet_to(&request, target).await {
+                Ok(()) => {
+                    iface.record_request_sent();
+                    self.metrics.increment_packets_sent();
+                    sent += 1;
+                }
+                Err(err) => warn!(
+                    "Failed to send RIP request on {}: {}",
+                    iface.config.name, err
+                ),
+            }
+        }
+
+        sent
+    }
+
     pub async fn learn_neighbor(&self, address: IpAddr, interface: Option<String>, routes: usize) {
         let mut neighbors = self.neighbors.write().await;
         neighbors.insert(
@@ -179,21 +1146,34 @@ impl Router {
         );
     }
 
-    pub async fn cleanup_neighbors(&self, max_age: Duration) {
+    /// Forget neighbors not heard from within `max_age`, returning the removed entries
+    pub async fn cleanup_neighbors(&self, max_age: Duration) -> Vec<NeighborInfo> {
         let mut neighbors = self.neighbors.write().await;
-        neighbors.retain(|_, info| info.last_seen.elapsed() <= max_age);
+        let mut removed = Vec::new();
+        neighbors.retain(|_, info| {
+            let alive = info.last_seen.elapsed() <= max_age;
+            if !alive {
+                removed.push(info.clone());
+            }
+            alive
+        });
+        removed
     }
 
-    fn derive_router_uuid(router_id: &str) -> Uuid {
+    /// The router's identity: `router_id` itself when it is a UUID, else a
+    /// name-based (version 5) UUID of it in [`ROUTER_UUID_NAMESPACE`], so a
+    /// router keeps its identity across restarts and reloads
+    pub fn derive_router_uuid(router_id: &str) -> Uuid {
         if let Ok(uuid) = Uuid::parse_str(router_id) {
-            uuid
-        } else {
-            Uuid::new_v4()
+            return uuid;
         }
+        Uuid::new_v5(&ROUTER_UUID_NAMESPACE, router_id.as_bytes())
     }
 
     async fn initialize_network_interfaces(
         config: &RouterConfig,
+        packet_auth: &Arc<PacketAuth>,
+        metrics: &Metrics,
     ) -> RustRouteResult<HashMap<String, Arc<NetworkInterface>>> {
         let mut map = HashMap::new();
 
@@ -202,38 +1182,68 @@ impl Router {
                 continue;
             }
 
-            let Some(net) = parse_ipv4_net(iface)? else {
-                continue;
-            };
-
-            let host_ip = net.addr();
-            let subnet_mask = net.netmask();
-
-            let mut interface = NetworkInterface::new(NetInterfaceConfig {
-                name: iface.name.clone(),
-                ip_address: host_ip,
-                subnet_mask,
-                multicast_address: Ipv4Addr::new(224, 0, 0, 9),
-                port: config.rip.port,
-                mtu: 1500,
-                enabled: true,
-            });
+            // Surface malformed addresses as configuration errors
+            parse_ipv4_net(iface)?;
 
-            match interface.initialize().await {
-                Ok(_) => {
+            match Self::start_interface(iface, &config.rip, packet_auth, metrics).await {
+                Ok(Some(interface)) => {
                     map.insert(iface.name.clone(), Arc::new(interface));
                 }
+                Ok(None) => {}
                 Err(err) => {
                     warn!(
                         "Skipping interface {} ({}): {}",
                         iface.name, iface.address, err
                     );
+                    last_error::record(
+                        &last_error::interface(&iface.name),
+                        format!("Not started: {}", err),
+                    );
                 }
             }
         }
 
         Ok(map)
     }
+
+    /// Bind the RIP socket for a single IPv4 interface
+    async fn start_interface(
+        iface: &InterfaceConfig,
+        rip: &RipConfig,
+        packet_auth: &Arc<PacketAuth>,
+        metrics: &Metrics,
+    ) -> RustRouteResult<Option<NetworkInterface>> {
+        let Some(net) = parse_ipv4_net(iface)? else {
+            return Ok(None);
+        };
+
+        let mut interface = NetworkInterface::new(NetInterfaceConfig {
+            name: iface.name.clone(),
+            ip_address: net.addr(),
+            subnet_mask: net.netmask(),
+            multicast_address: Ipv4Addr::new(224, 0, 0, 9),
+            port: rip.port,
+            mtu: iface
+                .mtu
+                .or_else(|| network::utils::os_mtu(&iface.name))
+                .unwrap_or(1500),
+            enabled: true,
+            point_to_point: iface.is_point_to_point(),
+            peer: iface.link_peer(),
+            codec: iface.codec,
+            send_mode: iface.send_mode.clone(),
+            secondary_addresses: iface.secondary_addresses.clone(),
+            infinity_metric: rip.infinity_metric,
+        })
+        .with_auth(Arc::clone(packet_auth))
+        .with_metrics(metrics.clone());
+        interface.set_sender_filter(iface.sender_filter());
+        interface.set_pacing(iface.pacing);
+        interface.set_key_chain(rip.authentication_for(&iface.name).cloned());
+
+        interface.initialize().await?;
+        Ok(Some(interface))
+    }
 }
 
 fn parse_ipv4_net(interface: &InterfaceConfig) -> RustRouteResult<Option<Ipv4Net>> {
@@ -255,33 +1265,8 @@ fn parse_ipv4_net(interface: &InterfaceConfig) -> RustRouteResult<Option<Ipv4Net
     }
 }
 
-fn format_duration(seconds: u64) -> String {
-    let hours = seconds / 3600;
-    let minutes = (seconds % 3600) / 60;
-    let secs = seconds % 60;
-    format!("{}时{}分{}秒", hours, minutes, secs)
-}
-
-fn current_process_memory_bytes() -> u64 {
-    if let Ok(status) = std::fs::read_to_string("/proc/self/status") {
-        for line in status.lines() {
-            if let Some(rest) = line.strip_prefix("VmRSS:") {
-                if let Some(value) = rest.split_whitespace().next() {
-                    if let Ok(kb) = value.parse::<u64>() {
-                        return kb * 1024;
-                    }
-                }
-            }
-        }
-    }
-    0
-}
-
 pub async fn handle_rip_response(
-    routing_table: Arc<RwLock<RoutingTable>>,
-    neighbors: Arc<RwLock<HashMap<IpAddr, NeighborInfo>>>,
-    metrics: Metrics,
-    rip_config: Arc<RipConfig>,
+    ctx: &RipContext,
     interface_name: String,
     packet: RipPacket,
     sender: SocketAddr,
@@ -294,31 +1279,151 @@ pub async fn handle_rip_response(
         }
     };
 
+    let rip_config = &ctx.rip_config;
+    let tracing = ctx.decisions.is_enabled();
+    let trace = |entry: &RipEntry, metric: u32, reason: Reason| {
+        if tracing {
+            ctx.decisions.record(Decision::new(
+                entry.ip_address,
+                entry.subnet_mask,
+                sender_ip,
+                &interface_name,
+                metric,
+                reason,
+            ));
+        }
+    };
+
+    // The interface checked the digest; a packet without the kind of
+    // authentication its key chain asks for never counts, nor does JSON
+    // that was not signed under rip.packet_auth
+    if let Some(auth) = rip_config.authentication_for(&interface_name) {
+        let signed_json = packet.auth.is_some() && rip_config.packet_auth.enabled;
+        if !rip_auth::is_authenticated(&packet, auth) && !signed_json {
+            for entry in &packet.entries {
+                trace(entry, entry.metric, Reason::AuthenticationFailed);
+            }
+            return Err(RustRouteError::ProtocolError(format!(
+                "Packet authentication failed: unauthenticated response from {} on {}",
+                sender, interface_name
+            )));
+        }
+    }
+    let policy = ctx.policy.active(Direction::Import);
     let entries = packet.entries;
     let learned_count = entries.len();
     let mut updated = false;
     let mut updated_routes = Vec::new();
 
+    let neighbor_config = rip_config
+        .neighbors
+        .iter()
+        .find(|neighbor| neighbor.address == sender_ip);
+    let metric_offset = neighbor_config.map_or(0, |neighbor| neighbor.metric_offset);
+    let cost = ctx.interface_costs.get(&interface_name);
+    let distance = neighbor_config.map_or(DEFAULT_DISTANCE, |neighbor| neighbor.distance);
+
+    let validation = &rip_config.next_hop_validation;
+    let arp = (validation.require_arp && entries.iter().any(|e| !e.next_hop.is_unspecified()))
+        .then(network::utils::arp_neighbors);
+
     {
-        let mut table = routing_table.write().await;
+        let mut table = ctx.routing_table.write().await;
+        let connected: Vec<Ipv4Net> = table
+            .get_all_routes()
+            .into_iter()
+            .filter(|route| {
+                route.source == RouteSource::Direct
+                    && route.interface == interface_name
+                    && route.metric < rip_config.infinity_metric
+            })
+            .filter_map(|route| Ipv4Net::with_netmask(route.destination, route.subnet_mask).ok())
+            .collect();
 
         for entry in entries {
-            let mut metric = entry.metric.saturating_add(1);
+            if !ctx.policy.distributes(
+                Direction::Import,
+                &interface_name,
+                entry.ip_address,
+                entry.subnet_mask,
+            ) {
+                trace(&entry, entry.metric, Reason::DistributeList);
+                continue;
+            }
+
+            let offset = ctx.policy.offset(
+                Direction::Import,
+                &interface_name,
+                Some(sender_ip),
+                entry.ip_address,
+                entry.subnet_mask,
+            );
+            let mut metric = entry
+                .metric
+                .saturating_add(cost)
+                .saturating_add(metric_offset)
+                .saturating_add(offset);
             if metric > rip_config.infinity_metric {
                 metric = rip_config.infinity_metric;
             }
 
-            if metric >= rip_config.infinity_metric {
-                continue;
+            if let Some(report) = ctx.loop_detector.observe(
+                entry.ip_address,
+                entry.subnet_mask,
+                sender_ip,
+                metric,
+                rip_config.infinity_metric,
+            ) {
+                warn!(
+                    "Possible count-to-infinity for {}/{} via {}: metric rose on {} consecutive updates to {} (neighbors {:?})",
+                    report.destination,
+                    report.subnet_mask,
+                    interface_name,
+                    report.rises,
+                    report.metric,
+                    report.neighbors
+                );
+                ctx.metrics.increment_count_to_infinity();
+                ctx.events
+                    .publish(WebEvent::CountToInfinity(CountToInfinityEvent::new(
+                        &report,
+                        interface_name.clone(),
+                    )));
             }
 
-            let next_hop = if entry.next_hop.is_unspecified() {
+            // Unreachable entries still go to the table, where the neighbor a
+            // route was learned from withdraws it (RFC 2453 section 3.9.2)
+            let unreachable = metric >= rip_config.infinity_metric;
+            let next_hop = if unreachable
+                || entry.next_hop.is_unspecified()
+                || entry.next_hop == sender_ip
+            {
                 sender_ip
-            } else {
+            } else if next_hop_reachable(entry.next_hop, &connected, arp.as_ref()) {
                 entry.next_hop
+            } else {
+                ctx.metrics.increment_invalid_next_hops();
+                debug!(
+                    "Next hop {} for {}/{} from {} is not reachable on {}",
+                    entry.next_hop, entry.ip_address, entry.subnet_mask, sender_ip, interface_name
+                );
+                match validation.action {
+                    NextHopAction::Fallback => sender_ip,
+                    NextHopAction::Reject => {
+                        trace(&entry, metric, Reason::InvalidNextHop);
+                        continue;
+                    }
+                    NextHopAction::Flag => {
+                        warn!(
+                            "Installing {}/{} from {} with unreachable next hop {}",
+                            entry.ip_address, entry.subnet_mask, sender_ip, entry.next_hop
+                        );
+                        entry.next_hop
+                    }
+                }
             };
 
-            let route = Route::new(
+            let mut route = Route::new(
                 entry.ip_address,
                 entry.subnet_mask,
                 next_hop,
@@ -327,22 +1432,47 @@ pub async fn handle_rip_response(
                 RouteSource::Dynamic,
                 Some(sender_ip),
             );
+            route.tag = entry.route_tag;
+            route.distance = distance;
+
+            if let Some(policy) = policy.as_ref().filter(|_| !unreachable) {
+                let candidate = PolicyRoute::from_route(&route, &interface_name);
+                let Some(accepted) = policy.evaluate(Direction::Import, candidate) else {
+                    trace(&entry, metric, Reason::ImportPolicy);
+                    continue;
+                };
+                if accepted.metric == 0 || accepted.metric >= rip_config.infinity_metric {
+                    trace(&entry, accepted.metric, Reason::ImportPolicy);
+                    continue;
+                }
+                route.metric = accepted.metric;
+                route.next_hop = accepted.next_hop;
+                route.tag = accepted.tag;
+            }
 
-            if table.add_or_replace(route.clone()) {
+            let reason = table.import(route.clone());
+            trace(&entry, route.metric, reason);
+            if reason.changed() {
+                ctx.metrics.increment_route_changes();
                 updated = true;
-                updated_routes.push(route);
+                // A withdrawal may have failed over to an alternate
+                let installed = table
+                    .equal_cost_paths(route.destination, route.subnet_mask)
+                    .first()
+                    .map(|installed| (*installed).clone());
+                updated_routes.push(installed.unwrap_or(route));
             }
         }
 
         if updated {
-            metrics.increment_routing_updates_received();
+            ctx.metrics.increment_routing_updates_received();
         }
 
-        metrics.update_route_count(table.route_count());
+        ctx.metrics.update_route_count(table.route_count());
     }
 
     {
-        let mut neighbor_map = neighbors.write().await;
+        let mut neighbor_map = ctx.neighbors.write().await;
         neighbor_map.insert(
             IpAddr::V4(sender_ip),
             NeighborInfo {
@@ -364,14 +1494,342 @@ pub async fn handle_rip_response(
     Ok(updated_routes)
 }
 
+/// A next hop is usable when it sits on one of the receiving interface's
+/// connected subnets and, if required, has a resolved ARP entry
+fn next_hop_reachable(
+    next_hop: Ipv4Addr,
+    connected: &[Ipv4Net],
+    arp: Option<&HashSet<Ipv4Addr>>,
+) -> bool {
+    connected.iter().any(|net| {
+        net.contains(&next_hop)
+            && (net.prefix_len() >= 31
+                || (next_hop != net.network() && next_hop != net.broadcast()))
+    }) && arp.is_none_or(|resolved| resolved.contains(&next_hop))
+}
+
+/// What a restart reset
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestartOutcome {
+    /// Interfaces open after the restart
+    pub interfaces: usize,
+    pub neighbors_cleared: usize,
+    pub routes_cleared: usize,
+    pub requests_sent: usize,
+}
+
+/// Result of flushing learned state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClearOutcome {
+    pub routes_removed: usize,
+    pub requests_sent: usize,
+}
+
+/// Shared handles needed by the per-interface RIP tasks
+#[derive(Clone)]
+pub struct RipContext {
+    pub routing_table: Arc<RwLock<RoutingTable>>,
+    pub neighbors: Arc<RwLock<HashMap<IpAddr, NeighborInfo>>>,
+    pub metrics: Metrics,
+    pub rip_config: Arc<RipConfig>,
+    pub events: EventBus,
+    pub router_uuid: Uuid,
+    pub policy: PolicyHandle,
+    pub loop_detector: LoopDetector,
+    pub decisions: DecisionTrace,
+    /// Added to the metric of routes received on each interface
+    pub interface_costs: InterfaceCosts,
+    /// End of `rip.startup_quiet_period`
+    pub quiet_until: Option<Instant>,
+}
+
+impl RipContext {
+    /// Still in the startup quiet period, when nothing may be advertised
+    pub fn is_quiet(&self) -> bool {
+        self.quiet_until.is_some_and(|until| Instant::now() < until)
+    }
+}
+
+fn receive_task_name(interface: &str) -> String {
+    format!("receive-{}", interface)
+}
+
+/// Sleep while keeping the watchdog informed
+pub async fn pause(duration: Duration, heartbeat: &Heartbeat) {
+    let deadline = tokio::time::Instant::now() + duration;
+    while tokio::time::Instant::now() < deadline {
+        heartbeat.beat();
+        let remaining = deadline - tokio::time::Instant::now();
+        tokio::time::sleep(remaining.min(RECEIVE_HEARTBEAT / 2)).await;
+    }
+}
+
+/// Spawn supervised receive loops for interfaces that do not have one yet
+pub fn ensure_receive_tasks(
+    watchdog: &Watchdog,
+    interfaces: &[Arc<NetworkInterface>],
+    ctx: &RipContext,
+) {
+    for iface in interfaces {
+        let task_name = receive_task_name(&iface.config.name);
+        if watchdog.contains(&task_name) {
+            continue;
+        }
+
+        let iface = Arc::clone(iface);
+        let ctx = ctx.clone();
+        watchdog.spawn(&task_name, RECEIVE_HEARTBEAT, move |heartbeat| {
+            receive_loop(Arc::clone(&iface), ctx.clone(), heartbeat)
+        });
+    }
+}
+
+/// Process packets arriving on a single interface until the task is aborted
+pub async fn receive_loop(iface: Arc<NetworkInterface>, ctx: RipContext, heartbeat: Heartbeat) {
+    let iface_name = iface.config.name.clone();
+
+    loop {
+        heartbeat.beat();
+        let received = match tokio::time::timeout(RECEIVE_HEARTBEAT, iface.receive_packet()).await {
+            Ok(result) => result,
+            Err(_) => continue,
+        };
+
+        let (packet, sender) = match received {
+            Ok(received) => received,
+            Err(err) => {
+                if matches!(err, RustRouteError::ProtocolError(_)) {
+                    iface.record_bad_packet();
+                }
+                warn!("Error receiving packet on {}: {}", iface_name, err);
+                last_error::record(&last_error::interface(&iface_name), &err);
+                for alert in iface.take_auth_alerts() {
+                    warn!("{} on {}", alert, iface_name);
+                    ctx.events.publish_activity(
+                        ActivityLevel::Warn,
+                        format!("{} on {}", alert, iface_name),
+                    );
+                }
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+        };
+
+        ctx.metrics.increment_packets_received();
+        if !iface.is_admin_up() {
+            ctx.metrics.increment_packets_dropped();
+            debug!(
+                "Dropping packet from {} on administratively disabled {}",
+                sender, iface_name
+            );
+            continue;
+        }
+        if !iface.permits_sender(sender.ip()) {
+            ctx.metrics.increment_packets_dropped();
+            if iface.record_rejected_sender(sender.ip()) {
+                warn!(
+                    "Ignoring packets from {} on {}: sender not permitted by accept_from/reject_from",
+                    sender.ip(),
+                    iface_name
+                );
+                ctx.events.publish_activity(
+                    ActivityLevel::Warn,
+                    format!(
+                        "Rejected RIP packets from {} on {}",
+                        sender.ip(),
+                        iface_name
+                    ),
+                );
+            }
+            continue;
+        }
+        iface.record_packet_accepted(packet.command);
+
+        match packet.command {
+            RipCommand::Request if ctx.is_quiet() => {
+                debug!(
+                    "Not answering request from {} on {} during the startup quiet period",
+                    sender, iface_name
+                );
+            }
+            RipCommand::Request => {
+                let response = advertisement(&ctx, &iface_name, Some(sender.ip())).await;
+                if let Err(err) = iface.send_packet_to(&response, sender).await {
+                    warn!("Failed to reply RIP request on {}: {}", iface_name, err);
+                } else {
+                    iface.record_update_sent();
+                    ctx.metrics.increment_packets_sent();
+                    ctx.metrics.increment_routing_updates_sent();
+                    ctx.metrics.increment_queries_answered();
+                }
+            }
+            RipCommand::Response => {
+                ctx.metrics
+                    .increment_responses_received(iface.take_solicited(sender.ip()));
+                let mut packet = packet;
+                if iface.is_point_to_point() {
+                    clear_next_hops(&mut packet);
+                }
+                process_response(&ctx, &iface_name, packet, sender).await
+            }
+        }
+    }
+}
+
+/// Follow the OS link state of every running interface until the task is
+/// aborted, withdrawing routes as soon as a link drops
+pub async fn watch_links(router: Arc<RwLock<Router>>, events: EventBus, heartbeat: Heartbeat) {
+    let mut interval = tokio::time::interval(LINK_POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        heartbeat.beat();
+
+        let router = router.read().await;
+        for iface in router.network_interfaces() {
+            let name = &iface.config.name;
+            let Some(up) = network::utils::os_link_up(name) else {
+                continue;
+            };
+            if up == iface.is_link_up() {
+                continue;
+            }
+
+            match router.set_link_state(name, up).await {
+                Ok(withdrawn) => {
+                    let (level, state) = if up {
+                        (ActivityLevel::Info, "up")
+                    } else {
+                        (ActivityLevel::Warn, "down")
+                    };
+                    events.publish_activity(level, format!("Link on {} went {}", name, state));
+                    for route in withdrawn {
+                        events.publish(WebEvent::RouteWithdrawn(RouteEvent::from_route(&route)));
+                    }
+                }
+                Err(err) => warn!("Failed to apply link state of {}: {}", name, err),
+            }
+        }
+
+        for (name, old, new) in router.follow_os_addresses().await {
+            events.publish_activity(
+                ActivityLevel::Warn,
+                format!("Interface {} renumbered from {} to {}", name, old, new),
+            );
+        }
+    }
+}
+
+/// The sender is the only usable next hop across a point-to-point link
+pub fn clear_next_hops(packet: &mut RipPacket) {
+    for entry in &mut packet.entries {
+        entry.next_hop = Ipv4Addr::UNSPECIFIED;
+    }
+}
+
+/// Build the response advertised on `interface`, after split horizon and
+/// export policy, including the restricted prefixes `to` may receive. Empty
+/// during the startup quiet period.
+pub async fn advertisement(ctx: &RipContext, interface: &str, to: Option<IpAddr>) -> RipPacket {
+    if ctx.is_quiet() {
+        return RipPacket::new_response(Vec::new());
+    }
+    let routes = ctx.routing_table.read().await.advertise(
+        interface,
+        ctx.rip_config.split_horizon_mode(),
+        ctx.rip_config.infinity_metric,
+    );
+    let advertisement = ctx.policy.export(routes, interface);
+    RipPacket::new_response(match to {
+        Some(IpAddr::V4(neighbor)) => advertisement.for_neighbor(neighbor),
+        _ => advertisement.shared,
+    })
+}
+
+/// Send the shared part of `advertisement` as usual on `iface`, and each
+/// neighbor on the link allowed restricted prefixes those by unicast.
+/// Returns whether anything was sent.
+pub async fn send_advertisement(
+    iface: &NetworkInterface,
+    advertisement: &Advertisement,
+) -> RustRouteResult<bool> {
+    let mut sent = false;
+    if !advertisement.shared.is_empty() {
+        iface
+            .send_packet(&RipPacket::new_response(advertisement.shared.clone()))
+            .await?;
+        sent = true;
+    }
+    let destinations = iface.update_destinations();
+    for neighbor in advertisement.neighbors() {
+        if !iface.is_in_subnet(neighbor) && !destinations.contains(&neighbor) {
+            continue;
+        }
+        let packet = RipPacket::new_response(advertisement.restricted_to(neighbor));
+        let target = SocketAddr::new(IpAddr::V4(neighbor), iface.config.port);
+        iface.send_packet_to(&packet, target).await?;
+        sent = true;
+    }
+    Ok(sent)
+}
+
+/// Apply a received response and publish the resulting neighbor and route events
+pub async fn process_response(
+    ctx: &RipContext,
+    interface: &str,
+    packet: RipPacket,
+    sender: SocketAddr,
+) {
+    let known = ctx.neighbors.read().await.contains_key(&sender.ip());
+    match handle_rip_response(ctx, interface.to_string(), packet, sender).await {
+        Ok(routes) => {
+            if !known {
+                ctx.events.publish(WebEvent::Neighbor(NeighborEvent::new(
+                    sender.ip(),
+                    Some(interface.to_string()),
+                    NeighborState::Up,
+                )));
+            }
+            for route in routes {
+                ctx.events
+                    .publish(WebEvent::Route(RouteEvent::from_route(&route)));
+            }
+        }
+        Err(err) => {
+            warn!("Failed to process RIP response on {}: {}", interface, err);
+            ctx.events.publish_activity(
+                ActivityLevel::Warn,
+                format!("Failed to process RIP response on {}: {}", interface, err),
+            );
+        }
+    }
+}
+
 /// Router statistics for CLI display
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RouterStatistics {
+    /// Uptime formatted for the configured locale
     pub uptime: String,
+    #[serde(default)]
+    pub uptime_seconds: u64,
     pub packets_sent: u64,
     pub packets_received: u64,
     pub route_count: usize,
     pub neighbor_count: usize,
+    /// Resident memory in bytes
     pub memory_usage: u64,
+    #[serde(default)]
+    pub memory_usage_human: String,
     pub table_breakdown: RoutingTableStatistics,
+    /// Estimated memory held by the routing table, a subset of `memory_usage`
+    #[serde(default)]
+    pub table_memory: TableMemory,
+    /// Route counts keyed by source name
+    #[serde(default)]
+    pub routes_by_source: BTreeMap<String, usize>,
+    /// When the running configuration was last loaded or applied
+    #[serde(default)]
+    pub last_config_change: Option<DateTime<Utc>>,
+    /// Seconds until the startup quiet period ends and advertising begins
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quiet_seconds_remaining: Option<u64>,
 }
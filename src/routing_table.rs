@@ -1,12 +1,20 @@
 //! Routing table implementation for RIP protocol
 
+use chrono::{DateTime, Utc};
+use ipnet::{Ipv4Net, PrefixLenError};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::net::Ipv4Addr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-const DEFAULT_ROUTE_TIMEOUT: Duration = Duration::from_secs(180);
-const DEFAULT_GC_TIMEOUT: Duration = Duration::from_secs(240);
+use crate::decisions::Reason;
+use crate::prefix_trie::PrefixTrie;
+use crate::timers::{self, wall_clock, Clock, RipTimers, TimerState};
+
+/// Administrative distance of learned routes unless configured per neighbor
+pub const DEFAULT_DISTANCE: u8 = 120;
 
 /// Indicates where a route originated from
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -14,6 +22,20 @@ pub enum RouteSource {
     Direct,
     Static,
     Dynamic,
+    /// Imported from another routing source on the host
+    Redistributed(RedistributeProtocol),
+    /// Summary originated locally in place of its more specific routes
+    Aggregate,
+}
+
+/// Where a redistributed route was imported from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RedistributeProtocol {
+    /// The kernel routing table
+    Kernel,
+    /// Addresses of interfaces RIP does not run on
+    Connected,
 }
 
 impl RouteSource {
@@ -22,16 +44,111 @@ impl RouteSource {
             RouteSource::Direct => "direct",
             RouteSource::Static => "static",
             RouteSource::Dynamic => "dynamic",
+            RouteSource::Redistributed(RedistributeProtocol::Kernel) => "redistributed-kernel",
+            RouteSource::Redistributed(RedistributeProtocol::Connected) => {
+                "redistributed-connected"
+            }
+            RouteSource::Aggregate => "aggregate",
         }
     }
 
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "direct" => Some(RouteSource::Direct),
+            "static" => Some(RouteSource::Static),
+            "dynamic" => Some(RouteSource::Dynamic),
+            "redistributed-kernel" => {
+                Some(RouteSource::Redistributed(RedistributeProtocol::Kernel))
+            }
+            "redistributed-connected" => {
+                Some(RouteSource::Redistributed(RedistributeProtocol::Connected))
+            }
+            "aggregate" => Some(RouteSource::Aggregate),
+            _ => None,
+        }
+    }
+
+    /// Locally originated routes win over learned ones, whatever their metric
     fn priority(&self) -> u8 {
         match self {
-            RouteSource::Direct => 3,
-            RouteSource::Static => 2,
+            RouteSource::Direct => 5,
+            RouteSource::Static => 4,
+            RouteSource::Aggregate => 3,
+            RouteSource::Redistributed(_) => 2,
             RouteSource::Dynamic => 1,
         }
     }
+
+    /// Administrative distance a new route of this source starts with,
+    /// following the usual vendor defaults
+    pub fn default_distance(&self) -> u8 {
+        match self {
+            RouteSource::Direct => 0,
+            RouteSource::Static => 1,
+            RouteSource::Aggregate => 5,
+            RouteSource::Redistributed(_) => 20,
+            RouteSource::Dynamic => DEFAULT_DISTANCE,
+        }
+    }
+}
+
+/// How to choose between equal-metric routes to a prefix from different neighbors.
+/// Whatever the choice, an incumbent that has gone unrefreshed for half the
+/// route timeout yields to a fresh equal-metric route (RFC 2453 section 3.9.2).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TieBreak {
+    /// Keep the incumbent route
+    #[default]
+    PreferOldest,
+    /// Switch to the route from the numerically lowest neighbor
+    PreferLowestNeighborIp,
+    /// Keep equal-cost alternates and fail over to one when the route is lost
+    Ecmp,
+}
+
+/// What happens to routes out of the interface an update is sent on
+/// (RFC 2453 section 3.4.3)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SplitHorizon {
+    /// Advertise them like any other route
+    Disabled,
+    /// Leave them out
+    #[default]
+    Simple,
+    /// Advertise them as unreachable, so a neighbor that points back at us
+    /// drops the route at once instead of waiting for it to time out
+    PoisonReverse,
+}
+
+impl SplitHorizon {
+    /// The mode selected by the `rip.split_horizon` and `rip.poison_reverse`
+    /// flags; poison reverse implies split horizon
+    pub fn from_flags(split_horizon: bool, poison_reverse: bool) -> Self {
+        match (split_horizon, poison_reverse) {
+            (_, true) => SplitHorizon::PoisonReverse,
+            (true, false) => SplitHorizon::Simple,
+            (false, false) => SplitHorizon::Disabled,
+        }
+    }
+
+    /// `route` as advertised on `interface`: unchanged, with the `infinity`
+    /// metric, or not at all. The connected route of `interface` itself is
+    /// never sent back onto it unless split horizon is off; every router on
+    /// the link has it already.
+    pub fn apply(self, route: &Route, interface: &str, infinity: u32) -> Option<Route> {
+        if route.interface != interface {
+            return Some(route.clone());
+        }
+        match self {
+            SplitHorizon::Disabled => Some(route.clone()),
+            SplitHorizon::PoisonReverse if route.source != RouteSource::Direct => Some(Route {
+                metric: infinity,
+                ..route.clone()
+            }),
+            SplitHorizon::Simple | SplitHorizon::PoisonReverse => None,
+        }
+    }
 }
 
 /// Snapshot of a route suitable for serialization
@@ -45,6 +162,42 @@ pub struct RouteSnapshot {
     pub learned_from: Option<String>,
     pub age_seconds: u64,
     pub source: RouteSource,
+    /// When the route was first installed
+    #[serde(default)]
+    pub learned_at: Option<DateTime<Utc>>,
+    /// When the route was last refreshed or changed
+    #[serde(default)]
+    pub last_updated: Option<DateTime<Utc>>,
+    /// Every equal-cost path to the prefix, the installed route's first;
+    /// empty unless ECMP keeps alternates for it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub next_hops: Vec<EqualCostPath>,
+}
+
+/// One of the equal-cost paths to a prefix
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EqualCostPath {
+    pub next_hop: String,
+    pub interface: String,
+}
+
+/// Everything the table knows about one prefix
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteDetail {
+    #[serde(flatten)]
+    pub route: RouteSnapshot,
+    pub tag: u16,
+    pub distance: u8,
+    pub timer_state: TimerState,
+    /// Seconds until a learned route times out unless refreshed
+    pub expires_in_seconds: Option<u64>,
+    /// Seconds until the route is flushed, counting the garbage period
+    pub flush_in_seconds: Option<u64>,
+    /// Held by a pin or a table freeze
+    #[serde(default)]
+    pub pinned: bool,
+    /// Equal-cost alternates kept in ECMP mode
+    pub alternates: Vec<RouteSnapshot>,
 }
 
 /// A single route entry in the routing table
@@ -59,6 +212,11 @@ pub struct Route {
     pub last_updated: Instant,
     pub created_at: Instant,
     pub source: RouteSource,
+    /// RIPv2 route tag, carried unchanged unless a policy rewrites it
+    pub tag: u16,
+    /// Preference among routes of the same source; lower wins before metrics
+    /// are compared. Starts at the source's default distance.
+    pub distance: u8,
 }
 
 impl Route {
@@ -83,6 +241,8 @@ impl Route {
             last_updated: now,
             created_at: now,
             source,
+            tag: 0,
+            distance: source.default_distance(),
         }
     }
 
@@ -123,8 +283,8 @@ impl Route {
         u32::from(self.subnet_mask).count_ones()
     }
 
-    pub fn mark_unreachable(&mut self) {
-        self.metric = 16;
+    pub fn mark_unreachable(&mut self, infinity: u32) {
+        self.metric = infinity;
         self.last_updated = Instant::now();
     }
 
@@ -135,6 +295,14 @@ impl Route {
         self.learned_from = other.learned_from;
         self.last_updated = Instant::now();
         self.source = other.source;
+        self.tag = other.tag;
+        self.distance = other.distance;
+    }
+
+    /// Ordering key among routes of the same source: reachable first, then
+    /// by distance, then by metric
+    fn rank(&self, infinity: u32) -> (bool, u8, u32) {
+        (self.metric >= infinity, self.distance, self.metric)
     }
 
     pub fn to_snapshot(&self) -> RouteSnapshot {
@@ -147,67 +315,574 @@ impl Route {
             learned_from: self.learned_from_display(),
             age_seconds: self.age_seconds(),
             source: self.source,
+            learned_at: Some(wall_clock(self.created_at)),
+            last_updated: Some(wall_clock(self.last_updated)),
+            next_hops: Vec::new(),
         }
     }
 }
 
+/// Distance added to learned routes through a next hop that fails its probes
+pub const DEMOTION_PENALTY: u8 = 100;
+
+/// Paths kept per prefix in ECMP mode unless `rip.max_paths` says otherwise
+pub const DEFAULT_MAX_PATHS: usize = 4;
+
+/// Metric changes kept per prefix unless `rip.route_history` says otherwise
+pub const DEFAULT_ROUTE_HISTORY: usize = 20;
+/// Prefixes with a history; the one changed least recently is forgotten first
+const MAX_HISTORY_PREFIXES: usize = 4096;
+
+/// A change of the metric or advertising neighbor of an installed route
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetricChange {
+    pub timestamp: DateTime<Utc>,
+    /// `None` when the prefix was not in the table before
+    pub old_metric: Option<u32>,
+    pub new_metric: u32,
+    /// Neighbor the route is now learned from
+    pub neighbor: Option<Ipv4Addr>,
+}
+
+/// Estimated heap footprint of the routing table, from the sizes of its
+/// entries and the capacity its maps have grown to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TableMemory {
+    /// Installed routes and ECMP alternates
+    pub entries: usize,
+    pub history_entries: usize,
+    pub estimated_bytes: u64,
+    /// Bytes spent on repeated copies of interface names, which interning
+    /// them would save
+    pub interning_savings_bytes: u64,
+}
+
 /// Routing table that manages all routes
 #[derive(Debug, Clone)]
 pub struct RoutingTable {
-    routes: HashMap<String, Route>,
-    route_timeout: Duration,
-    garbage_collection_timeout: Duration,
+    routes: PrefixTrie<Route>,
+    /// Equal-cost alternates to the installed route, kept in ECMP mode
+    equal_cost: HashMap<Ipv4Net, Vec<Route>>,
+    /// Paths per prefix in ECMP mode, the installed route included
+    max_paths: usize,
+    /// Turns lookups over the equal-cost paths of a prefix; shared by clones
+    /// of the table, which only makes the spread coarser
+    next_path: Arc<AtomicUsize>,
+    timers: RipTimers,
+    clock: Arc<dyn Clock>,
+    tie_break: TieBreak,
+    /// Recent metric changes per prefix, kept after the route is flushed so a
+    /// flapping prefix shows each time it came back
+    history: HashMap<Ipv4Net, VecDeque<MetricChange>>,
+    history_limit: usize,
+    /// Prefixes held by an operator; RIP updates, timeouts and neighbor loss
+    /// leave them alone until they are unpinned
+    pinned: HashSet<Ipv4Net>,
+    /// Hold every route, as if each prefix were pinned
+    frozen: bool,
+    /// Next hops failing their reachability probes; learned routes through
+    /// them rank behind routes through other neighbors
+    demoted: HashSet<Ipv4Addr>,
+    /// Interfaces whose connected routes are kept out of advertisements
+    unadvertised: HashSet<String>,
+    /// Prefixes whose static routes are kept out of advertisements
+    unadvertised_static: HashSet<Ipv4Net>,
 }
 
 impl RoutingTable {
     /// Create a new routing table with default timers
     pub fn new() -> Self {
-        Self::with_timeouts(DEFAULT_ROUTE_TIMEOUT, DEFAULT_GC_TIMEOUT)
+        Self::with_clock(RipTimers::default(), timers::system_clock())
     }
 
     pub fn with_timeouts(route_timeout: Duration, garbage_collection_timeout: Duration) -> Self {
+        let timers = RipTimers {
+            timeout: route_timeout,
+            garbage: garbage_collection_timeout,
+            ..RipTimers::default()
+        };
+        Self::with_clock(timers, timers::system_clock())
+    }
+
+    pub fn with_clock(timers: RipTimers, clock: Arc<dyn Clock>) -> Self {
         Self {
-            routes: HashMap::new(),
-            route_timeout,
-            garbage_collection_timeout,
+            routes: PrefixTrie::new(),
+            equal_cost: HashMap::new(),
+            max_paths: DEFAULT_MAX_PATHS,
+            next_path: Arc::new(AtomicUsize::new(0)),
+            timers,
+            clock,
+            tie_break: TieBreak::default(),
+            history: HashMap::new(),
+            history_limit: DEFAULT_ROUTE_HISTORY,
+            pinned: HashSet::new(),
+            frozen: false,
+            demoted: HashSet::new(),
+            unadvertised: HashSet::new(),
+            unadvertised_static: HashSet::new(),
+        }
+    }
+
+    pub fn timers(&self) -> RipTimers {
+        self.timers
+    }
+
+    pub fn set_timers(&mut self, timers: RipTimers) {
+        self.timers = timers;
+    }
+
+    pub fn set_tie_break(&mut self, tie_break: TieBreak) {
+        if tie_break != TieBreak::Ecmp {
+            self.equal_cost.clear();
+        }
+        self.tie_break = tie_break;
+    }
+
+    /// Keep up to `max_paths` equal-cost paths per prefix in ECMP mode,
+    /// dropping the alternates beyond them
+    pub fn set_max_paths(&mut self, max_paths: usize) {
+        self.max_paths = max_paths.max(1);
+        let alternates = self.max_paths - 1;
+        self.equal_cost.retain(|_, paths| {
+            paths.truncate(alternates);
+            !paths.is_empty()
+        });
+    }
+
+    /// Keep the last `limit` metric changes per prefix; 0 stops recording
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.history_limit = limit;
+        if limit == 0 {
+            self.history.clear();
+        }
+        for changes in self.history.values_mut() {
+            while changes.len() > limit {
+                changes.pop_front();
+            }
+        }
+    }
+
+    /// Hold the route to a prefix as it is; false when there is no such route
+    pub fn pin(&mut self, destination: Ipv4Addr, subnet_mask: Ipv4Addr) -> bool {
+        let Ok(key) = Self::key(destination, subnet_mask) else {
+            return false;
+        };
+        if !self.routes.contains_key(&key) {
+            return false;
+        }
+        self.pinned.insert(key);
+        true
+    }
+
+    /// Release a pinned prefix, returning whether it was pinned. Its timers
+    /// restart so it does not expire the moment it is released.
+    pub fn unpin(&mut self, destination: Ipv4Addr, subnet_mask: Ipv4Addr) -> bool {
+        let Ok(key) = Self::key(destination, subnet_mask) else {
+            return false;
+        };
+        if !self.pinned.remove(&key) {
+            return false;
+        }
+        if !self.frozen {
+            self.restart_timers(Some(key));
+        }
+        true
+    }
+
+    /// Hold or release every route at once
+    pub fn set_frozen(&mut self, frozen: bool) {
+        if self.frozen && !frozen {
+            self.restart_timers(None);
+        }
+        self.frozen = frozen;
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// Pinned prefixes as `destination/mask`, sorted
+    pub fn pinned(&self) -> Vec<String> {
+        let mut pinned: Vec<String> = self
+            .pinned
+            .iter()
+            .map(|key| format!("{}/{}", key.network(), key.netmask()))
+            .collect();
+        pinned.sort();
+        pinned
+    }
+
+    /// Keep the connected routes of `interfaces` installed but unadvertised
+    pub fn set_unadvertised_interfaces(&mut self, interfaces: HashSet<String>) {
+        self.unadvertised = interfaces;
+    }
+
+    /// Keep the static routes to `prefixes` installed but unadvertised
+    pub fn set_unadvertised_static(&mut self, prefixes: HashSet<Ipv4Net>) {
+        self.unadvertised_static = prefixes;
+    }
+
+    /// Demote or restore the learned routes through `next_hop` by
+    /// [`DEMOTION_PENALTY`] of distance, returning how many routes changed.
+    /// Routes through other neighbors take over as they are advertised.
+    pub fn set_demoted(&mut self, next_hop: Ipv4Addr, demoted: bool) -> usize {
+        let changed = if demoted {
+            self.demoted.insert(next_hop)
+        } else {
+            self.demoted.remove(&next_hop)
+        };
+        if !changed {
+            return 0;
         }
+        let mut affected = 0;
+        let routes = self
+            .routes
+            .values_mut()
+            .chain(self.equal_cost.values_mut().flatten());
+        for route in routes
+            .filter(|route| route.source == RouteSource::Dynamic && route.next_hop == next_hop)
+        {
+            route.distance = if demoted {
+                route.distance.saturating_add(DEMOTION_PENALTY)
+            } else {
+                route.distance.saturating_sub(DEMOTION_PENALTY)
+            };
+            affected += 1;
+        }
+        affected
     }
 
-    fn key(destination: Ipv4Addr, subnet_mask: Ipv4Addr) -> String {
-        format!("{}/{}", destination, subnet_mask)
+    pub fn is_demoted(&self, next_hop: Ipv4Addr) -> bool {
+        self.demoted.contains(&next_hop)
     }
 
-    /// Add or replace a route entry based on source priority and metric
+    fn is_held(&self, key: &Ipv4Net) -> bool {
+        self.frozen || self.pinned.contains(key)
+    }
+
+    /// Refresh the held routes that are being released: `key`, or all of
+    /// them when unfreezing
+    fn restart_timers(&mut self, key: Option<Ipv4Net>) {
+        let now = self.clock.now();
+        for (route_key, route) in self.routes.iter_mut() {
+            let released = match key {
+                Some(key) => route_key == key,
+                None => !self.pinned.contains(&route_key),
+            };
+            if released {
+                route.last_updated = now;
+            }
+        }
+    }
+
+    /// Recorded metric changes of a prefix, oldest first
+    pub fn history(&self, destination: Ipv4Addr, subnet_mask: Ipv4Addr) -> Vec<MetricChange> {
+        Self::key(destination, subnet_mask)
+            .ok()
+            .and_then(|key| self.history.get(&key))
+            .map(|changes| changes.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// The prefix a route is stored under; host bits of `destination` are
+    /// ignored, and a mask that is not contiguous is an error
+    fn key(destination: Ipv4Addr, subnet_mask: Ipv4Addr) -> Result<Ipv4Net, PrefixLenError> {
+        Ipv4Net::with_netmask(destination, subnet_mask).map(|prefix| prefix.trunc())
+    }
+
+    /// Metric and neighbor of the route installed under `key`
+    fn installed(&self, key: &Ipv4Net) -> Option<(u32, Option<Ipv4Addr>)> {
+        self.routes
+            .get(key)
+            .map(|route| (route.metric, route.learned_from))
+    }
+
+    /// Record the route under `key` if it differs from `before`
+    fn record_change(&mut self, key: &Ipv4Net, before: Option<(u32, Option<Ipv4Addr>)>) {
+        let Some(after) = self.installed(key) else {
+            return;
+        };
+        if self.history_limit == 0 || before == Some(after) {
+            return;
+        }
+        if !self.history.contains_key(key) && self.history.len() >= MAX_HISTORY_PREFIXES {
+            let oldest = self
+                .history
+                .iter()
+                .min_by_key(|(_, changes)| changes.back().map(|change| change.timestamp))
+                .map(|(key, _)| *key);
+            if let Some(oldest) = oldest {
+                self.history.remove(&oldest);
+            }
+        }
+
+        let changes = self.history.entry(*key).or_default();
+        changes.push_back(MetricChange {
+            timestamp: wall_clock(self.clock.now()),
+            old_metric: before.map(|(metric, _)| metric),
+            new_metric: after.0,
+            neighbor: after.1,
+        });
+        while changes.len() > self.history_limit {
+            changes.pop_front();
+        }
+    }
+
+    /// Add or replace a route entry based on source priority, metric and the
+    /// tie-break rule. Returns whether the installed route changed; keeping
+    /// an equal-cost alternate does not count.
     pub fn add_or_replace(&mut self, route: Route) -> bool {
-        let key = Self::key(route.destination, route.subnet_mask);
+        self.import(route).changed()
+    }
+
+    /// Like `add_or_replace`, returning the rule that decided the outcome
+    pub fn import(&mut self, mut route: Route) -> Reason {
+        let Ok(key) = Self::key(route.destination, route.subnet_mask) else {
+            return Reason::InvalidMask;
+        };
+        if route.source == RouteSource::Dynamic
+            && self.is_held(&key)
+            && self.routes.contains_key(&key)
+        {
+            return Reason::HeldDown;
+        }
+        if route.source == RouteSource::Dynamic && self.demoted.contains(&route.next_hop) {
+            route.distance = route.distance.saturating_add(DEMOTION_PENALTY);
+        }
+        let before = self.installed(&key);
+        let reason = self.apply(key, route);
+        if reason.changed() {
+            self.record_change(&key, before);
+        }
+        reason
+    }
+
+    fn apply(&mut self, key: Ipv4Net, mut route: Route) -> Reason {
+        let now = self.clock.now();
+        route.last_updated = now;
+        let infinity = self.timers.infinity;
+
+        if route.source == RouteSource::Dynamic {
+            if let Some(reason) = self.apply_from_neighbor(key, &route) {
+                return reason;
+            }
+        }
 
         match self.routes.get_mut(&key) {
             Some(existing) => {
-                // Prefer higher priority sources (direct > static > dynamic)
+                // Prefer higher priority sources (direct > static > aggregate >
+                // redistributed > dynamic)
                 if route.source.priority() > existing.source.priority() {
                     *existing = route;
-                    return true;
+                    self.equal_cost.remove(&key);
+                    return Reason::PreferredSource;
+                }
+                // ...and only let a lower priority source take over once the
+                // installed route has become unreachable
+                if route.source.priority() < existing.source.priority()
+                    && existing.metric < infinity
+                {
+                    return Reason::LowerPrioritySource;
                 }
 
-                // For same source priority, keep better metric or update timestamp if same path
-                if route.metric < existing.metric
-                    || (route.metric == existing.metric
-                        && route.next_hop == existing.next_hop
-                        && route.interface == existing.interface)
+                // While held down, only the original neighbor can revive the route
+                if self.timers.state(existing, now) == TimerState::HoldDown
+                    && route.learned_from != existing.learned_from
                 {
+                    return Reason::HeldDown;
+                }
+
+                // For same source priority, keep the better route or update timestamp if same path
+                let same_path =
+                    route.next_hop == existing.next_hop && route.interface == existing.interface;
+                let (rank, existing_rank) = (route.rank(infinity), existing.rank(infinity));
+                if rank < existing_rank || (rank == existing_rank && same_path) {
+                    let reason = if rank < existing_rank {
+                        self.equal_cost.remove(&key);
+                        Reason::BetterMetric
+                    } else {
+                        Reason::Refreshed
+                    };
                     existing.update_from(&route);
-                    return true;
+                    existing.last_updated = now;
+                    return reason;
+                }
+
+                if rank != existing_rank || route.source != existing.source {
+                    // A worse advertisement means that path is no longer equal-cost
+                    if let Some(alternates) = self.equal_cost.get_mut(&key) {
+                        alternates.retain(|alt| !Self::same_path(alt, &route));
+                    }
+                    return if rank > existing_rank {
+                        Reason::WorseMetric
+                    } else {
+                        Reason::LowerPrioritySource
+                    };
                 }
 
-                false
+                let stale =
+                    now.saturating_duration_since(existing.last_updated) > self.timers.timeout / 2;
+                let switch = match self.tie_break {
+                    TieBreak::PreferOldest => stale,
+                    TieBreak::PreferLowestNeighborIp => {
+                        stale || Self::neighbor(&route) < Self::neighbor(existing)
+                    }
+                    TieBreak::Ecmp => {
+                        let alternates = self.equal_cost.entry(key).or_default();
+                        let full = alternates.len() + 1 >= self.max_paths;
+                        match alternates
+                            .iter_mut()
+                            .find(|alt| Self::same_path(alt, &route))
+                        {
+                            Some(alt) => {
+                                alt.update_from(&route);
+                                alt.last_updated = now;
+                            }
+                            None if !full => alternates.push(route),
+                            // Every path is taken; the route waits for one to go
+                            None => {
+                                if alternates.is_empty() {
+                                    self.equal_cost.remove(&key);
+                                }
+                                return Reason::TieBreakLost;
+                            }
+                        }
+                        return Reason::EqualCostAlternate;
+                    }
+                };
+                if !switch {
+                    return Reason::TieBreakLost;
+                }
+                existing.update_from(&route);
+                existing.last_updated = now;
+                Reason::TieBreak
             }
             None => {
                 self.routes.insert(key, route);
-                true
+                Reason::NewPrefix
             }
         }
     }
 
+    /// Learned updates the general rules do not cover (RFC 2453 section
+    /// 3.9.2): the neighbor the installed route came from is believed even
+    /// when its metric got worse, and its unreachable entries invalidate the
+    /// route at once. Unreachable entries from other neighbors only drop
+    /// their equal-cost alternate.
+    fn apply_from_neighbor(&mut self, key: Ipv4Net, route: &Route) -> Option<Reason> {
+        let infinity = self.timers.infinity;
+        let existing = self.routes.get(&key).cloned();
+        let from_installer = existing.as_ref().is_some_and(|existing| {
+            existing.source == RouteSource::Dynamic
+                && existing.learned_from.is_some()
+                && existing.learned_from == route.learned_from
+        });
+
+        if route.metric >= infinity {
+            if from_installer
+                && existing
+                    .as_ref()
+                    .is_some_and(|existing| existing.metric < infinity)
+            {
+                self.invalidate(vec![key]);
+                return Some(Reason::Withdrawn);
+            }
+            if existing
+                .as_ref()
+                .is_some_and(|existing| existing.source == RouteSource::Dynamic)
+            {
+                if let Some(alternates) = self.equal_cost.get_mut(&key) {
+                    alternates.retain(|alt| !Self::same_path(alt, route));
+                    if alternates.is_empty() {
+                        self.equal_cost.remove(&key);
+                    }
+                }
+            }
+            // Higher priority sources still decide below
+            return existing
+                .is_none_or(|existing| existing.source == RouteSource::Dynamic)
+                .then_some(Reason::Unreachable);
+        }
+
+        let existing = existing.filter(|_| from_installer)?;
+        if route.rank(infinity) <= existing.rank(infinity) {
+            return None;
+        }
+        // Alternates that tied with the old metric are now better
+        let installed = match self.promote_alternate(&key) {
+            Some(alternate) => alternate,
+            None => {
+                let mut updated = existing;
+                updated.update_from(route);
+                updated.last_updated = route.last_updated;
+                updated
+            }
+        };
+        self.routes.insert(key, installed);
+        Some(Reason::MetricRaised)
+    }
+
+    fn same_path(a: &Route, b: &Route) -> bool {
+        a.next_hop == b.next_hop && a.interface == b.interface
+    }
+
+    fn neighbor(route: &Route) -> Ipv4Addr {
+        route.learned_from.unwrap_or(route.next_hop)
+    }
+
+    /// Take the most recently refreshed live alternate for `key`, if any
+    fn promote_alternate(&mut self, key: &Ipv4Net) -> Option<Route> {
+        let alternates = self.equal_cost.get_mut(key)?;
+        let (timers, now) = (self.timers, self.clock.now());
+        alternates.retain(|alt| timers.state(alt, now) == TimerState::Valid);
+        let freshest = alternates
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, alt)| alt.last_updated)
+            .map(|(index, _)| index)?;
+        let promoted = alternates.swap_remove(freshest);
+        if alternates.is_empty() {
+            self.equal_cost.remove(key);
+        }
+        Some(promoted)
+    }
+
+    /// The installed route for exactly this prefix with its timers and alternates
+    pub fn detail(&self, destination: Ipv4Addr, subnet_mask: Ipv4Addr) -> Option<RouteDetail> {
+        let key = Self::key(destination, subnet_mask).ok()?;
+        let route = self.routes.get(&key)?;
+        let now = self.clock.now();
+        let (expires_in, flush_in) = self.timers.deadlines(route, now);
+        Some(RouteDetail {
+            route: route.to_snapshot(),
+            tag: route.tag,
+            distance: route.distance,
+            timer_state: self.timers.state(route, now),
+            expires_in_seconds: expires_in.map(|left| left.as_secs()),
+            flush_in_seconds: flush_in.map(|left| left.as_secs()),
+            pinned: self.is_held(&key),
+            alternates: self
+                .equal_cost
+                .get(&key)
+                .into_iter()
+                .flatten()
+                .map(Route::to_snapshot)
+                .collect(),
+        })
+    }
+
+    /// The installed route followed by its equal-cost alternates
+    pub fn equal_cost_paths(&self, destination: Ipv4Addr, subnet_mask: Ipv4Addr) -> Vec<&Route> {
+        let Ok(key) = Self::key(destination, subnet_mask) else {
+            return Vec::new();
+        };
+        self.routes
+            .get(&key)
+            .into_iter()
+            .chain(self.equal_cost.get(&key).into_iter().flatten())
+            .collect()
+    }
+
     pub fn install_direct_route(
         &mut self,
         destination: Ipv4Addr,
@@ -238,12 +913,127 @@ impl RoutingTable {
     }
 
     pub fn remove_route(&mut self, destination: Ipv4Addr, subnet_mask: Ipv4Addr) -> bool {
-        let key = Self::key(destination, subnet_mask);
+        let Ok(key) = Self::key(destination, subnet_mask) else {
+            return false;
+        };
+        self.equal_cost.remove(&key);
+        self.pinned.remove(&key);
         self.routes.remove(&key).is_some()
     }
 
+    /// Mark a single route unreachable, returning the updated entry
+    pub fn poison_route(&mut self, destination: Ipv4Addr, subnet_mask: Ipv4Addr) -> Option<Route> {
+        let key = Self::key(destination, subnet_mask).ok()?;
+        self.equal_cost.remove(&key);
+        let now = self.clock.now();
+        let before = self.installed(&key);
+        let route = self.routes.get_mut(&key)?;
+        route.mark_unreachable(self.timers.infinity);
+        route.last_updated = now;
+        let route = route.clone();
+        self.record_change(&key, before);
+        Some(route)
+    }
+
+    /// Shift the metric of the learned routes out of `interface` by the step
+    /// from `old_cost` to `new_cost`, poisoning those that reach infinity.
+    /// Equal-cost alternates out of the interface, and those beside its
+    /// routes, are dropped as they no longer cost the same. Returns the
+    /// routes that changed.
+    pub fn reprice(&mut self, interface: &str, old_cost: u32, new_cost: u32) -> Vec<Route> {
+        let infinity = self.timers.infinity;
+        let affected: Vec<Ipv4Net> = self
+            .routes
+            .iter()
+            .filter(|(_, route)| {
+                route.interface == interface
+                    && route.source == RouteSource::Dynamic
+                    && route.metric < infinity
+            })
+            .map(|(key, _)| key)
+            .collect();
+        for alternates in self.equal_cost.values_mut() {
+            alternates.retain(|alt| alt.interface != interface);
+        }
+        self.equal_cost
+            .retain(|_, alternates| !alternates.is_empty());
+
+        let mut changed = Vec::new();
+        for key in affected {
+            self.equal_cost.remove(&key);
+            let before = self.installed(&key);
+            let Some(route) = self.routes.get_mut(&key) else {
+                continue;
+            };
+            let metric = route
+                .metric
+                .saturating_add(new_cost)
+                .saturating_sub(old_cost)
+                .max(1);
+            if metric == route.metric {
+                continue;
+            }
+            if metric >= infinity {
+                route.mark_unreachable(infinity);
+            } else {
+                route.metric = metric;
+            }
+            changed.push(route.clone());
+            self.record_change(&key, before);
+        }
+        changed
+    }
+
+    /// Mark every reachable route of `source` out of `interface` unreachable,
+    /// returning the routes that were poisoned
+    pub fn poison_interface(&mut self, interface: &str, source: RouteSource) -> Vec<Route> {
+        for alternates in self.equal_cost.values_mut() {
+            alternates.retain(|alt| alt.interface != interface);
+        }
+        let affected: Vec<Ipv4Net> = self
+            .routes
+            .iter()
+            .filter(|(_, route)| {
+                route.interface == interface
+                    && route.source == source
+                    && route.metric < self.timers.infinity
+            })
+            .map(|(key, _)| key)
+            .collect();
+
+        self.invalidate(affected)
+    }
+
+    /// Fail each route over to an equal-cost alternate, or mark it
+    /// unreachable; returns the routes that became unreachable
+    fn invalidate(&mut self, keys: Vec<Ipv4Net>) -> Vec<Route> {
+        let now = self.clock.now();
+        let mut invalidated = Vec::new();
+        for key in keys {
+            let before = self.installed(&key);
+            let replacement = self.promote_alternate(&key);
+            let Some(route) = self.routes.get_mut(&key) else {
+                continue;
+            };
+            match replacement {
+                Some(alternate) => *route = alternate,
+                None => {
+                    route.mark_unreachable(self.timers.infinity);
+                    route.last_updated = now;
+                    invalidated.push(route.clone());
+                }
+            }
+            self.record_change(&key, before);
+        }
+        invalidated
+    }
+
+    /// The installed route to `destination`, leaving its equal-cost
+    /// alternates aside
     pub fn get_route(&self, destination: Ipv4Addr) -> Option<&Route> {
-        self.find_best_route(&destination)
+        self.routes
+            .longest_match(destination)
+            .map(|(_, route)| route)
     }
 
     pub fn get_all_routes(&self) -> Vec<&Route> {
@@ -251,82 +1041,211 @@ impl RoutingTable {
     }
 
     pub fn snapshot(&self) -> Vec<RouteSnapshot> {
-        self.routes.values().map(Route::to_snapshot).collect()
+        self.routes
+            .iter()
+            .map(|(key, route)| {
+                let mut snapshot = route.to_snapshot();
+                if let Some(alternates) = self.equal_cost.get(&key) {
+                    snapshot.next_hops = std::iter::once(route)
+                        .chain(alternates)
+                        .map(|path| EqualCostPath {
+                            next_hop: path.next_hop.to_string(),
+                            interface: path.interface.clone(),
+                        })
+                        .collect();
+                }
+                snapshot
+            })
+            .collect()
     }
 
+    /// Routes for an update on `outgoing_interface` under simple split horizon
     pub fn get_routes_for_advertising(&self, outgoing_interface: &str) -> Vec<&Route> {
-        self.routes
-            .values()
+        self.advertised_routes()
             .filter(|route| route.interface != outgoing_interface)
             .collect()
     }
 
-    /// Longest prefix match
-    pub fn find_best_route(&self, destination: &Ipv4Addr) -> Option<&Route> {
-        self.routes
-            .values()
-            .filter(|route| self.matches_network(destination, route))
-            .max_by_key(|route| route.prefix_length())
+    /// Routes for an update on `outgoing_interface`, with `split_horizon`
+    /// applied; poisoned routes carry the `infinity` metric
+    pub fn advertise(
+        &self,
+        outgoing_interface: &str,
+        split_horizon: SplitHorizon,
+        infinity: u32,
+    ) -> Vec<Route> {
+        self.advertised_routes()
+            .filter_map(|route| split_horizon.apply(route, outgoing_interface, infinity))
+            .collect()
     }
 
-    fn matches_network(&self, destination: &Ipv4Addr, route: &Route) -> bool {
-        let dest_octets = destination.octets();
-        let route_octets = route.destination.octets();
-        let mask_octets = route.subnet_mask.octets();
-
-        for i in 0..4 {
-            if (dest_octets[i] & mask_octets[i]) != (route_octets[i] & mask_octets[i]) {
-                return false;
-            }
-        }
+    /// Every route except the connected routes of unadvertised interfaces
+    /// and the unadvertised static routes
+    fn advertised_routes(&self) -> impl Iterator<Item = &Route> {
+        self.routes.values().filter(|route| match route.source {
+            RouteSource::Direct => !self.unadvertised.contains(&route.interface),
+            RouteSource::Static => Self::key(route.destination, route.subnet_mask)
+                .is_ok_and(|key| !self.unadvertised_static.contains(&key)),
+            _ => true,
+        })
+    }
 
-        true
+    /// Longest prefix match, taking the equal-cost paths of the prefix in
+    /// turn
+    pub fn find_best_route(&self, destination: &Ipv4Addr) -> Option<&Route> {
+        let (key, route) = self.routes.longest_match(*destination)?;
+        let Some(alternates) = self.equal_cost.get(&key) else {
+            return Some(route);
+        };
+        let turn = self.next_path.fetch_add(1, Ordering::Relaxed) % (alternates.len() + 1);
+        Some(if turn == 0 {
+            route
+        } else {
+            &alternates[turn - 1]
+        })
     }
 
     pub fn route_count(&self) -> usize {
         self.routes.len()
     }
 
+    pub fn memory_estimate(&self) -> TableMemory {
+        use std::mem::size_of;
+        // A hashbrown bucket holds the pair plus one control byte
+        fn buckets<K, V>(map: &HashMap<K, V>) -> usize {
+            map.capacity() * (size_of::<(K, V)>() + 1)
+        }
+
+        let routes: Vec<&Route> = self
+            .routes
+            .values()
+            .chain(self.equal_cost.values().flatten())
+            .collect();
+        let mut bytes =
+            self.routes.heap_bytes() + buckets(&self.equal_cost) + buckets(&self.history);
+        bytes += self
+            .equal_cost
+            .values()
+            .map(|alternates| alternates.capacity() * size_of::<Route>())
+            .sum::<usize>();
+        bytes += self
+            .history
+            .values()
+            .map(|changes| changes.capacity() * size_of::<MetricChange>())
+            .sum::<usize>();
+
+        let mut names: HashMap<&str, usize> = HashMap::new();
+        for route in &routes {
+            bytes += route.interface.capacity();
+            *names.entry(route.interface.as_str()).or_default() += 1;
+        }
+        let savings: usize = names
+            .iter()
+            .map(|(name, copies)| name.len() * (copies - 1))
+            .sum();
+
+        TableMemory {
+            entries: routes.len(),
+            history_entries: self.history.values().map(VecDeque::len).sum(),
+            estimated_bytes: bytes as u64,
+            interning_savings_bytes: savings as u64,
+        }
+    }
+
     pub fn get_stats(&self) -> RoutingTableStatistics {
-        let mut stats = RoutingTableStatistics::default();
-        stats.total_routes = self.routes.len();
+        let mut stats = RoutingTableStatistics {
+            total_routes: self.routes.len(),
+            ..Default::default()
+        };
 
         for route in self.routes.values() {
             match route.source {
                 RouteSource::Direct => stats.direct_routes += 1,
                 RouteSource::Static => stats.static_routes += 1,
                 RouteSource::Dynamic => stats.learned_routes += 1,
+                RouteSource::Redistributed(_) => stats.redistributed_routes += 1,
+                RouteSource::Aggregate => stats.aggregate_routes += 1,
             }
         }
 
         stats
     }
 
-    pub fn clear_source(&mut self, source: RouteSource) {
+    /// Number of routes of each source, keyed by source name
+    pub fn count_by_source(&self) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        for route in self.routes.values() {
+            *counts.entry(route.source.as_str().to_string()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Remove every route of `source`, returning how many were dropped
+    pub fn clear_source(&mut self, source: RouteSource) -> usize {
+        let before = self.routes.len();
         self.routes.retain(|_, route| route.source != source);
+        let routes = &self.routes;
+        self.equal_cost.retain(|key, _| routes.contains_key(key));
+        self.pinned.retain(|key| routes.contains_key(key));
+        before - self.routes.len()
     }
 
-    /// Update dynamic routes based on timeouts
-    pub fn process_timeouts(&mut self) {
-        let now = Instant::now();
-        for route in self.routes.values_mut() {
-            if route.source == RouteSource::Dynamic {
-                if now.duration_since(route.last_updated) > self.route_timeout {
-                    route.mark_unreachable();
+    /// Remove dynamic routes learned from `neighbor`, returning how many were dropped
+    pub fn remove_learned_from(&mut self, neighbor: Ipv4Addr) -> usize {
+        for alternates in self.equal_cost.values_mut() {
+            alternates.retain(|alt| alt.learned_from != Some(neighbor));
+        }
+        let affected: Vec<Ipv4Net> = self
+            .routes
+            .iter()
+            .filter(|(key, route)| {
+                route.source == RouteSource::Dynamic
+                    && route.learned_from == Some(neighbor)
+                    && !self.is_held(key)
+            })
+            .map(|(key, _)| key)
+            .collect();
+
+        let mut removed = 0;
+        for key in affected {
+            match self.promote_alternate(&key) {
+                Some(alternate) => {
+                    let before = self.installed(&key);
+                    self.routes.insert(key, alternate);
+                    self.record_change(&key, before);
+                }
+                None => {
+                    self.routes.remove(&key);
+                    removed += 1;
                 }
             }
         }
+        removed
     }
 
+    /// Mark expired dynamic routes unreachable, returning those that just expired
+    pub fn process_timeouts(&mut self) -> Vec<Route> {
+        let now = self.clock.now();
+        let affected: Vec<Ipv4Net> = self
+            .routes
+            .iter()
+            .filter(|(key, route)| {
+                self.timers.state(route, now) == TimerState::Expired && !self.is_held(key)
+            })
+            .map(|(key, _)| key)
+            .collect();
+        self.invalidate(affected)
+    }
+
+    /// Flush unreachable routes whose garbage-collection timer has run out
     pub fn garbage_collect(&mut self) {
-        let now = Instant::now();
-        self.routes.retain(|_, route| {
-            if route.source == RouteSource::Dynamic && route.metric >= 16 {
-                now.duration_since(route.last_updated) <= self.garbage_collection_timeout
-            } else {
-                true
-            }
+        let (timers, now) = (self.timers, self.clock.now());
+        let (frozen, pinned) = (self.frozen, &self.pinned);
+        self.routes.retain(|key, route| {
+            frozen || pinned.contains(key) || timers.state(route, now) != TimerState::Flush
         });
+        let routes = &self.routes;
+        self.equal_cost.retain(|key, _| routes.contains_key(key));
     }
 
     pub fn print_table(&self) {
@@ -364,11 +1283,16 @@ pub struct RoutingTableStatistics {
     pub direct_routes: usize,
     pub static_routes: usize,
     pub learned_routes: usize,
+    #[serde(default)]
+    pub redistributed_routes: usize,
+    #[serde(default)]
+    pub aggregate_routes: usize,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::timers::MockClock;
     use std::net::Ipv4Addr;
 
     #[test]
@@ -396,6 +1320,111 @@ mod tests {
         assert_eq!(route.metric, 1);
     }
 
+    #[test]
+    fn lookups_prefer_the_longest_prefix() {
+        let mut table = RoutingTable::new();
+        for (destination, mask, metric) in [
+            ([0, 0, 0, 0], [0, 0, 0, 0], 1),
+            ([10, 0, 0, 0], [255, 0, 0, 0], 2),
+            ([10, 1, 0, 0], [255, 255, 0, 0], 3),
+            ([10, 1, 2, 0], [255, 255, 255, 0], 4),
+        ] {
+            table.add_static_route(
+                destination.into(),
+                mask.into(),
+                Ipv4Addr::new(192, 168, 1, 2),
+                metric,
+                "eth0".to_string(),
+            );
+        }
+        let metric = |table: &RoutingTable, address: [u8; 4]| {
+            table.find_best_route(&address.into()).unwrap().metric
+        };
+        assert_eq!(metric(&table, [10, 1, 2, 3]), 4);
+        assert_eq!(metric(&table, [10, 1, 3, 3]), 3);
+        assert_eq!(metric(&table, [10, 9, 0, 1]), 2);
+        assert_eq!(metric(&table, [172, 16, 0, 1]), 1);
+
+        // Host bits of the destination do not make a separate prefix
+        assert!(table.remove_route(Ipv4Addr::new(10, 1, 2, 9), Ipv4Addr::new(255, 255, 255, 0)));
+        assert_eq!(metric(&table, [10, 1, 2, 3]), 3);
+        assert_eq!(table.snapshot().len(), 3);
+    }
+
+    #[test]
+    fn non_contiguous_masks_are_rejected() {
+        let mut table = RoutingTable::new();
+        table.add_static_route(
+            Ipv4Addr::new(10, 0, 0, 0),
+            Ipv4Addr::new(255, 255, 0, 0),
+            Ipv4Addr::new(192, 168, 1, 2),
+            1,
+            "eth0".to_string(),
+        );
+        // Same number of bits as /16, but no prefix
+        let (destination, mask) = (Ipv4Addr::new(10, 0, 0, 0), Ipv4Addr::new(255, 0, 255, 0));
+        let mut route = learned(0, 1);
+        route.destination = destination;
+        route.subnet_mask = mask;
+
+        assert_eq!(table.import(route), Reason::InvalidMask);
+        assert!(table.detail(destination, mask).is_none());
+        assert!(table.poison_route(destination, mask).is_none());
+        assert!(!table.remove_route(destination, mask));
+        assert_eq!(table.snapshot().len(), 1);
+    }
+
+    #[test]
+    fn poison_interface_marks_connected_routes() {
+        let mut table = RoutingTable::with_timeouts(Duration::from_secs(180), Duration::ZERO);
+        table.install_direct_route(
+            Ipv4Addr::new(10, 0, 1, 0),
+            Ipv4Addr::new(255, 255, 255, 0),
+            "eth1".to_string(),
+        );
+        table.install_direct_route(
+            Ipv4Addr::new(10, 0, 2, 0),
+            Ipv4Addr::new(255, 255, 255, 0),
+            "eth2".to_string(),
+        );
+
+        let poisoned = table.poison_interface("eth1", RouteSource::Direct);
+        assert_eq!(poisoned.len(), 1);
+        assert_eq!(poisoned[0].metric, 16);
+
+        std::thread::sleep(Duration::from_millis(5));
+        table.garbage_collect();
+        assert_eq!(table.route_count(), 1);
+        assert_eq!(
+            table
+                .get_route(Ipv4Addr::new(10, 0, 2, 1))
+                .unwrap()
+                .interface,
+            "eth2"
+        );
+    }
+
+    #[test]
+    fn remove_learned_from_only_drops_that_neighbor() {
+        let mut table = RoutingTable::new();
+        let mask = Ipv4Addr::new(255, 255, 255, 0);
+        for (octet, neighbor) in [(1, 1), (2, 1), (3, 2)] {
+            table.add_or_replace(Route::new(
+                Ipv4Addr::new(10, octet, 0, 0),
+                mask,
+                Ipv4Addr::new(192, 168, 0, neighbor),
+                2,
+                "eth0".to_string(),
+                RouteSource::Dynamic,
+                Some(Ipv4Addr::new(192, 168, 0, neighbor)),
+            ));
+        }
+
+        assert_eq!(table.remove_learned_from(Ipv4Addr::new(192, 168, 0, 1)), 2);
+        assert_eq!(table.route_count(), 1);
+        assert_eq!(table.clear_source(RouteSource::Dynamic), 1);
+    }
+
     #[test]
     fn snapshot_contains_expected_fields() {
         let mut table = RoutingTable::new();
@@ -415,4 +1444,446 @@ mod tests {
         assert_eq!(entry.destination, "10.0.0.0");
         assert_eq!(entry.source, RouteSource::Static);
     }
+
+    fn learned(third: u8, neighbor: u8) -> Route {
+        Route::new(
+            Ipv4Addr::new(10, 7, third, 0),
+            Ipv4Addr::new(255, 255, 255, 0),
+            Ipv4Addr::new(192, 168, 0, neighbor),
+            3,
+            "eth0".to_string(),
+            RouteSource::Dynamic,
+            Some(Ipv4Addr::new(192, 168, 0, neighbor)),
+        )
+    }
+
+    #[test]
+    fn equal_metric_tie_breaks() {
+        let dest = Ipv4Addr::new(10, 7, 0, 1);
+
+        let mut oldest = RoutingTable::new();
+        oldest.add_or_replace(learned(0, 5));
+        assert!(!oldest.add_or_replace(learned(0, 2)));
+        assert_eq!(oldest.get_route(dest).unwrap().next_hop.octets()[3], 5);
+
+        // An incumbent past half the timeout yields to a fresh equal route
+        let clock = Arc::new(MockClock::new());
+        let mut stale = RoutingTable::with_clock(RipTimers::default(), clock.clone());
+        stale.add_or_replace(learned(0, 5));
+        clock.advance(Duration::from_secs(91));
+        assert!(stale.add_or_replace(learned(0, 9)));
+        assert_eq!(stale.get_route(dest).unwrap().next_hop.octets()[3], 9);
+
+        let mut lowest = RoutingTable::new();
+        lowest.set_tie_break(TieBreak::PreferLowestNeighborIp);
+        lowest.add_or_replace(learned(0, 5));
+        assert!(!lowest.add_or_replace(learned(0, 7)));
+        assert!(lowest.add_or_replace(learned(0, 2)));
+        assert_eq!(lowest.get_route(dest).unwrap().next_hop.octets()[3], 2);
+    }
+
+    #[test]
+    fn ecmp_keeps_alternates_for_failover() {
+        let clock = Arc::new(MockClock::new());
+        let mut table = RoutingTable::with_clock(RipTimers::default(), clock.clone());
+        table.set_tie_break(TieBreak::Ecmp);
+        let dest = Ipv4Addr::new(10, 7, 0, 0);
+        let mask = Ipv4Addr::new(255, 255, 255, 0);
+
+        table.add_or_replace(learned(0, 1));
+        assert!(!table.add_or_replace(learned(0, 2)));
+        assert!(!table.add_or_replace(learned(0, 3)));
+        assert_eq!(table.equal_cost_paths(dest, mask).len(), 3);
+
+        // Losing the primary's neighbor fails over instead of dropping the prefix
+        assert_eq!(table.remove_learned_from(Ipv4Addr::new(192, 168, 0, 1)), 0);
+        assert_eq!(table.equal_cost_paths(dest, mask).len(), 2);
+
+        // Only the refreshed path survives the timeout
+        clock.advance(Duration::from_secs(150));
+        let survivor = table.equal_cost_paths(dest, mask)[1].clone();
+        table.add_or_replace(survivor.clone());
+        clock.advance(Duration::from_secs(100));
+        assert!(table.process_timeouts().is_empty());
+        let route = table.get_route(Ipv4Addr::new(10, 7, 0, 1)).unwrap();
+        assert_eq!(route.next_hop, survivor.next_hop);
+        assert_eq!(route.metric, 3);
+        assert_eq!(table.equal_cost_paths(dest, mask).len(), 1);
+
+        // A better path replaces the whole set
+        let mut better = learned(0, 4);
+        better.metric = 2;
+        assert!(table.add_or_replace(better));
+        assert_eq!(table.equal_cost_paths(dest, mask).len(), 1);
+    }
+
+    #[test]
+    fn ecmp_lookups_take_turns_over_at_most_max_paths() {
+        let mut table = RoutingTable::new();
+        table.set_tie_break(TieBreak::Ecmp);
+        table.set_max_paths(2);
+        let (dest, mask) = (Ipv4Addr::new(10, 7, 0, 0), Ipv4Addr::new(255, 255, 255, 0));
+
+        table.add_or_replace(learned(0, 1));
+        assert_eq!(table.import(learned(0, 2)), Reason::EqualCostAlternate);
+        assert_eq!(table.import(learned(0, 3)), Reason::TieBreakLost);
+        assert_eq!(table.equal_cost_paths(dest, mask).len(), 2);
+
+        let host = Ipv4Addr::new(10, 7, 0, 9);
+        let turns: Vec<u8> = (0..4)
+            .map(|_| table.find_best_route(&host).unwrap().next_hop.octets()[3])
+            .collect();
+        assert_eq!(turns, vec![1, 2, 1, 2]);
+        assert_eq!(table.get_route(host).unwrap().next_hop.octets()[3], 1);
+
+        let next_hops = &table.snapshot()[0].next_hops;
+        assert_eq!(
+            next_hops
+                .iter()
+                .map(|path| path.next_hop.as_str())
+                .collect::<Vec<_>>(),
+            vec!["192.168.0.1", "192.168.0.2"]
+        );
+
+        table.set_max_paths(1);
+        assert_eq!(table.equal_cost_paths(dest, mask).len(), 1);
+        assert!(table.snapshot()[0].next_hops.is_empty());
+    }
+
+    #[test]
+    fn repricing_an_interface_shifts_its_learned_routes() {
+        let mut table = RoutingTable::new();
+        table.install_direct_route(
+            Ipv4Addr::new(192, 168, 0, 0),
+            Ipv4Addr::new(255, 255, 255, 0),
+            "eth0".to_string(),
+        );
+        table.add_or_replace(learned(1, 2));
+        let mut far = learned(2, 2);
+        far.metric = 14;
+        table.add_or_replace(far);
+        let mut elsewhere = learned(3, 2);
+        elsewhere.interface = "eth1".to_string();
+        table.add_or_replace(elsewhere);
+
+        let changed = table.reprice("eth0", 1, 3);
+        assert_eq!(changed.len(), 2);
+        let metric = |table: &RoutingTable, third: u8| {
+            table
+                .get_route(Ipv4Addr::new(10, 7, third, 1))
+                .unwrap()
+                .metric
+        };
+        assert_eq!(metric(&table, 1), 5);
+        assert_eq!(metric(&table, 2), table.timers().infinity);
+        assert_eq!(metric(&table, 3), 3);
+        assert_eq!(
+            table
+                .get_route(Ipv4Addr::new(192, 168, 0, 1))
+                .unwrap()
+                .metric,
+            1
+        );
+
+        assert_eq!(table.reprice("eth0", 3, 2).len(), 1);
+        assert_eq!(metric(&table, 1), 4);
+    }
+
+    #[test]
+    fn metric_changes_are_recorded_per_prefix() {
+        let mut table = RoutingTable::new();
+        table.set_history_limit(3);
+        let (dest, mask) = (Ipv4Addr::new(10, 7, 1, 0), Ipv4Addr::new(255, 255, 255, 0));
+
+        table.add_or_replace(learned(1, 2));
+        // A refresh with the same metric is not a change
+        table.add_or_replace(learned(1, 2));
+        let mut better = learned(1, 3);
+        better.metric = 2;
+        table.add_or_replace(better);
+        table.poison_route(dest, mask);
+
+        let history = table.history(dest, mask);
+        let metrics: Vec<(Option<u32>, u32)> = history
+            .iter()
+            .map(|change| (change.old_metric, change.new_metric))
+            .collect();
+        assert_eq!(metrics, vec![(None, 3), (Some(3), 2), (Some(2), 16)]);
+        assert_eq!(history[1].neighbor, Some(Ipv4Addr::new(192, 168, 0, 3)));
+
+        // History outlives the route and is bounded by the limit
+        table.remove_route(dest, mask);
+        table.add_or_replace(learned(1, 2));
+        let history = table.history(dest, mask);
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[2].old_metric, None);
+        assert!(table.history(Ipv4Addr::new(10, 7, 2, 0), mask).is_empty());
+    }
+
+    #[test]
+    fn detail_reports_timers_and_alternates() {
+        let clock = Arc::new(MockClock::new());
+        let mut table = RoutingTable::with_clock(RipTimers::default(), clock.clone());
+        table.set_tie_break(TieBreak::Ecmp);
+        let (dest, mask) = (Ipv4Addr::new(10, 7, 1, 0), Ipv4Addr::new(255, 255, 255, 0));
+        let mut tagged = learned(1, 2);
+        tagged.tag = 7;
+        table.add_or_replace(tagged);
+        table.add_or_replace(learned(1, 3));
+        clock.advance(Duration::from_secs(30));
+
+        let detail = table.detail(dest, mask).unwrap();
+        assert_eq!(detail.tag, 7);
+        assert_eq!(detail.timer_state, TimerState::Valid);
+        assert_eq!(detail.expires_in_seconds, Some(150));
+        assert_eq!(detail.flush_in_seconds, Some(270));
+        assert_eq!(detail.alternates.len(), 1);
+        assert!(table.detail(dest, Ipv4Addr::new(255, 255, 0, 0)).is_none());
+
+        table.poison_route(dest, mask);
+        let detail = table.detail(dest, mask).unwrap();
+        assert_eq!(detail.timer_state, TimerState::Garbage);
+        assert_eq!(detail.expires_in_seconds, None);
+        assert_eq!(detail.flush_in_seconds, Some(120));
+    }
+
+    #[test]
+    fn memory_estimate_grows_with_entries() {
+        let mut table = RoutingTable::new();
+        let empty = table.memory_estimate();
+        assert_eq!(empty.entries, 0);
+        assert_eq!(empty.interning_savings_bytes, 0);
+
+        for third in 1..=3 {
+            table.add_or_replace(learned(third, 2));
+        }
+        let estimate = table.memory_estimate();
+        assert_eq!(estimate.entries, 3);
+        assert_eq!(estimate.history_entries, 3);
+        // Two of the three "eth0" copies are redundant
+        assert_eq!(estimate.interning_savings_bytes, 8);
+        assert!(estimate.estimated_bytes >= 3 * std::mem::size_of::<Route>() as u64);
+    }
+
+    #[test]
+    fn configured_infinity_bounds_reachability() {
+        let timers = RipTimers {
+            infinity: 32,
+            ..RipTimers::default()
+        };
+        let mut table = RoutingTable::with_clock(timers, timers::system_clock());
+        let mut far = learned(0, 1);
+        far.metric = 20;
+        assert!(table.add_or_replace(far));
+        let dest = Ipv4Addr::new(10, 7, 0, 0);
+        let mask = Ipv4Addr::new(255, 255, 255, 0);
+        assert_eq!(
+            table.detail(dest, mask).unwrap().timer_state,
+            TimerState::Valid
+        );
+
+        let poisoned = table.poison_route(dest, mask).unwrap();
+        assert_eq!(poisoned.metric, 32);
+    }
+
+    #[test]
+    fn redistributed_and_aggregate_sources_rank_between_static_and_learned() {
+        let kernel = RouteSource::Redistributed(RedistributeProtocol::Kernel);
+        let mut table = RoutingTable::new();
+        let dest = Ipv4Addr::new(10, 7, 0, 1);
+        table.add_or_replace(learned(0, 1));
+
+        let mut imported = learned(0, 2);
+        imported.source = kernel;
+        imported.distance = kernel.default_distance();
+        imported.metric = 5;
+        assert!(table.add_or_replace(imported.clone()));
+        assert_eq!(table.get_route(dest).unwrap().source, kernel);
+        assert!(!table.add_or_replace(learned(0, 1)));
+
+        let mut summary = learned(0, 3);
+        summary.source = RouteSource::Aggregate;
+        assert!(table.add_or_replace(summary));
+        assert!(!table.add_or_replace(imported));
+        assert_eq!(table.get_stats().aggregate_routes, 1);
+
+        for source in [kernel, RouteSource::Aggregate, RouteSource::Static] {
+            assert_eq!(RouteSource::parse(source.as_str()), Some(source));
+        }
+        assert_eq!(
+            serde_json::to_string(&kernel).unwrap(),
+            r#"{"Redistributed":"kernel"}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&RouteSource::Aggregate).unwrap(),
+            r#""Aggregate""#
+        );
+    }
+
+    #[test]
+    fn pinned_routes_ignore_updates_and_timeouts() {
+        let clock = Arc::new(MockClock::new());
+        let mut table = RoutingTable::with_clock(RipTimers::default(), clock.clone());
+        let dest = Ipv4Addr::new(10, 7, 0, 0);
+        let mask = Ipv4Addr::new(255, 255, 255, 0);
+        table.add_or_replace(learned(0, 1));
+        table.add_or_replace(learned(1, 1));
+        assert!(table.pin(dest, mask));
+        assert!(!table.pin(Ipv4Addr::new(10, 9, 0, 0), mask));
+
+        let mut better = learned(0, 2);
+        better.metric = 1;
+        assert!(!table.add_or_replace(better));
+        assert_eq!(table.remove_learned_from(Ipv4Addr::new(192, 168, 0, 1)), 1);
+        clock.advance(Duration::from_secs(400));
+        assert!(table.process_timeouts().is_empty());
+        table.garbage_collect();
+        assert!(table.detail(dest, mask).unwrap().pinned);
+        assert_eq!(table.pinned(), vec!["10.7.0.0/255.255.255.0".to_string()]);
+
+        // Released routes get a full timeout before they expire
+        assert!(table.unpin(dest, mask));
+        assert!(table.process_timeouts().is_empty());
+        assert!(!table.unpin(dest, mask));
+
+        table.set_frozen(true);
+        clock.advance(Duration::from_secs(400));
+        assert!(table.process_timeouts().is_empty());
+        table.set_frozen(false);
+        clock.advance(Duration::from_secs(181));
+        assert_eq!(table.process_timeouts().len(), 1);
+    }
+
+    #[test]
+    fn split_horizon_modes() {
+        let mut table = RoutingTable::new();
+        let mask = Ipv4Addr::new(255, 255, 255, 0);
+        table.install_direct_route(Ipv4Addr::new(192, 168, 0, 0), mask, "eth0".to_string());
+        table.install_direct_route(Ipv4Addr::new(10, 60, 0, 0), mask, "eth1".to_string());
+        table.add_or_replace(learned(0, 2));
+
+        let advertised = |mode| {
+            let mut routes: Vec<(Ipv4Addr, u32)> = table
+                .advertise("eth0", mode, 16)
+                .iter()
+                .map(|route| (route.destination, route.metric))
+                .collect();
+            routes.sort();
+            routes
+        };
+        let connected = (Ipv4Addr::new(10, 60, 0, 0), 1);
+        assert_eq!(advertised(SplitHorizon::Simple), vec![connected]);
+        assert_eq!(
+            advertised(SplitHorizon::PoisonReverse),
+            vec![(Ipv4Addr::new(10, 7, 0, 0), 16), connected]
+        );
+        assert_eq!(advertised(SplitHorizon::Disabled).len(), 3);
+        assert_eq!(table.get_routes_for_advertising("eth0").len(), 1);
+        assert_eq!(
+            SplitHorizon::from_flags(false, true),
+            SplitHorizon::PoisonReverse
+        );
+        assert_eq!(
+            SplitHorizon::from_flags(false, false),
+            SplitHorizon::Disabled
+        );
+    }
+
+    #[test]
+    fn poison_reverse_breaks_a_loop_between_two_tables() {
+        // Each router holds 10.7.0.0/24 through the other, as after the
+        // router that originated it went away
+        let (a, b) = (Ipv4Addr::new(192, 168, 0, 1), Ipv4Addr::new(192, 168, 0, 2));
+        let mut table_a = RoutingTable::new();
+        table_a.add_or_replace(learned(0, 2));
+        let mut table_b = RoutingTable::new();
+        table_b.add_or_replace(learned(0, 1));
+
+        // What B makes of an update A sends on eth0, with the hop added
+        let exchange = |mode, table_b: &mut RoutingTable| {
+            table_a
+                .advertise("eth0", mode, 16)
+                .into_iter()
+                .map(|route| {
+                    let metric = (route.metric + 1).min(16);
+                    table_b.import(Route::new(
+                        route.destination,
+                        route.subnet_mask,
+                        a,
+                        metric,
+                        "eth0".to_string(),
+                        RouteSource::Dynamic,
+                        Some(a),
+                    ))
+                })
+                .collect::<Vec<_>>()
+        };
+        let metric =
+            |table: &RoutingTable| table.get_route(Ipv4Addr::new(10, 7, 0, 1)).unwrap().metric;
+
+        assert!(exchange(SplitHorizon::Simple, &mut table_b).is_empty());
+        assert_eq!(metric(&table_b), 3);
+
+        assert_eq!(
+            exchange(SplitHorizon::PoisonReverse, &mut table_b),
+            vec![Reason::Withdrawn]
+        );
+        assert_eq!(metric(&table_b), 16);
+        assert_eq!(metric(&table_a), 3);
+        assert_eq!(
+            table_a
+                .get_route(Ipv4Addr::new(10, 7, 0, 1))
+                .unwrap()
+                .next_hop,
+            b
+        );
+    }
+
+    #[test]
+    fn unadvertised_interfaces_keep_their_connected_routes() {
+        let mut table = RoutingTable::new();
+        let mask = Ipv4Addr::new(255, 255, 255, 0);
+        table.install_direct_route(Ipv4Addr::new(10, 50, 0, 0), mask, "mgmt0".to_string());
+        table.install_direct_route(Ipv4Addr::new(10, 60, 0, 0), mask, "eth1".to_string());
+        let mut via_mgmt = learned(0, 2);
+        via_mgmt.interface = "mgmt0".to_string();
+        table.add_or_replace(via_mgmt);
+
+        table.set_unadvertised_interfaces(HashSet::from(["mgmt0".to_string()]));
+        let advertised: Vec<Ipv4Addr> = table
+            .get_routes_for_advertising("eth0")
+            .iter()
+            .map(|route| route.destination)
+            .collect();
+        assert_eq!(advertised.len(), 2);
+        assert!(!advertised.contains(&Ipv4Addr::new(10, 50, 0, 0)));
+        assert!(advertised.contains(&Ipv4Addr::new(10, 7, 0, 0)));
+        assert_eq!(
+            table
+                .find_best_route(&Ipv4Addr::new(10, 50, 0, 9))
+                .map(|route| route.interface.as_str()),
+            Some("mgmt0")
+        );
+    }
+
+    #[test]
+    fn demoted_next_hops_lose_to_other_neighbors() {
+        let mut table = RoutingTable::new();
+        let dest = Ipv4Addr::new(10, 7, 0, 1);
+        let (first, second) = (Ipv4Addr::new(192, 168, 0, 1), Ipv4Addr::new(192, 168, 0, 2));
+        table.add_or_replace(learned(0, 1));
+        assert_eq!(table.set_demoted(first, true), 1);
+        assert_eq!(table.set_demoted(first, true), 0);
+
+        let mut longer = learned(0, 2);
+        longer.metric = 5;
+        assert!(table.add_or_replace(longer));
+        assert_eq!(table.get_route(dest).unwrap().next_hop, second);
+        // Updates through the demoted next hop keep the penalty
+        assert!(!table.add_or_replace(learned(0, 1)));
+
+        table.set_demoted(first, false);
+        assert!(table.add_or_replace(learned(0, 1)));
+        assert_eq!(table.get_route(dest).unwrap().distance, 120);
+    }
 }
@@ -0,0 +1,186 @@
+//! Tuning of the async runtime the daemon runs on.
+//!
+//! The `runtime` section sizes tokio's worker and blocking thread pools and
+//! can restrict the process to a set of CPUs, so the same binary suits a
+//! single-core board and a lab server running dozens of routers. It is read
+//! once, before the runtime starts; changing it takes a restart.
+//! `GET /api/status` shows the values in effect.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// tokio's own limit of blocking threads, used when none is configured
+pub const DEFAULT_MAX_BLOCKING_THREADS: usize = 512;
+
+/// Highest CPU index the affinity mask can name
+pub const MAX_CPU: usize = 1023;
+
+static APPLIED: OnceLock<RuntimeStatus> = OnceLock::new();
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuntimeConfig {
+    /// Worker threads; one per available CPU when unset
+    pub worker_threads: Option<usize>,
+    /// Upper bound of the pool running blocking work such as file I/O
+    pub max_blocking_threads: Option<usize>,
+    /// CPUs the process may run on; empty leaves the affinity alone
+    pub cpu_affinity: Vec<usize>,
+}
+
+impl RuntimeConfig {
+    /// The `runtime` section of the configuration file at `path`, or the
+    /// defaults if it cannot be read; the full file is loaded later by the
+    /// configuration manager, which reports those errors
+    pub fn peek(path: &Path) -> Self {
+        #[derive(Deserialize)]
+        struct Partial {
+            #[serde(default)]
+            runtime: RuntimeConfig,
+        }
+
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<Partial>(&content).ok())
+            .map(|partial| partial.runtime)
+            .unwrap_or_default()
+    }
+
+    /// Problems with the settings, as configuration errors
+    pub fn errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.worker_threads == Some(0) {
+            errors.push("runtime.worker_threads must be at least 1".to_string());
+        }
+        if self.max_blocking_threads == Some(0) {
+            errors.push("runtime.max_blocking_threads must be at least 1".to_string());
+        }
+        if let Some(cpu) = self.cpu_affinity.iter().find(|cpu| **cpu > MAX_CPU) {
+            errors.push(format!(
+                "runtime.cpu_affinity names CPU {}; the highest is {}",
+                cpu, MAX_CPU
+            ));
+        }
+        errors
+    }
+}
+
+/// The runtime settings in effect
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RuntimeStatus {
+    pub worker_threads: usize,
+    pub max_blocking_threads: usize,
+    /// CPUs the process is pinned to; empty when not pinned
+    pub cpu_affinity: Vec<usize>,
+    /// CPUs the process may use
+    pub available_cpus: usize,
+}
+
+/// Settings `build` applied, if the daemon's runtime was built by it
+pub fn applied() -> Option<&'static RuntimeStatus> {
+    APPLIED.get()
+}
+
+fn available_cpus() -> usize {
+    std::thread::available_parallelism().map_or(1, |cpus| cpus.get())
+}
+
+/// Pin the process to `config.cpu_affinity` and build a multi-threaded
+/// runtime sized by `config`. Threads inherit the affinity of the thread
+/// that starts them, so the pinning is done before any runtime thread exists.
+pub fn build(config: &RuntimeConfig) -> std::io::Result<tokio::runtime::Runtime> {
+    let mut pinned = Vec::new();
+    if !config.cpu_affinity.is_empty() {
+        match set_affinity(&config.cpu_affinity) {
+            Ok(()) => {
+                pinned = config.cpu_affinity.clone();
+                pinned.sort_unstable();
+                pinned.dedup();
+            }
+            Err(err) => log::warn!("Not pinning to CPUs {:?}: {}", config.cpu_affinity, err),
+        }
+    }
+
+    let worker_threads = config
+        .worker_threads
+        .filter(|threads| *threads > 0)
+        .unwrap_or_else(available_cpus);
+    let max_blocking_threads = config
+        .max_blocking_threads
+        .filter(|threads| *threads > 0)
+        .unwrap_or(DEFAULT_MAX_BLOCKING_THREADS);
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(worker_threads)
+        .max_blocking_threads(max_blocking_threads)
+        .enable_all()
+        .build()?;
+
+    let _ = APPLIED.set(RuntimeStatus {
+        worker_threads,
+        max_blocking_threads,
+        cpu_affinity: pinned,
+        available_cpus: available_cpus(),
+    });
+    Ok(runtime)
+}
+
+#[cfg(target_os = "linux")]
+fn set_affinity(cpus: &[usize]) -> std::io::Result<()> {
+    // SAFETY: `cpu_set_t` is a plain bit mask, CPU_SET stays within it for
+    // indexes up to MAX_CPU and the kernel only reads `set`
+    let result = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        for &cpu in cpus.iter().filter(|cpu| **cpu <= MAX_CPU) {
+            libc::CPU_SET(cpu, &mut set);
+        }
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set)
+    };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_affinity(_cpus: &[usize]) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "CPU affinity is only supported on Linux",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peek_reads_only_the_runtime_section() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rust-route.json");
+        std::fs::write(
+            &path,
+            r#"{"router_id": "not checked here", "runtime": {"worker_threads": 2, "cpu_affinity": [0, 1]}}"#,
+        )
+        .unwrap();
+
+        let config = RuntimeConfig::peek(&path);
+        assert_eq!(config.worker_threads, Some(2));
+        assert_eq!(config.max_blocking_threads, None);
+        assert_eq!(config.cpu_affinity, vec![0, 1]);
+        assert!(config.errors().is_empty());
+        assert_eq!(
+            RuntimeConfig::peek(&dir.path().join("missing.json")),
+            RuntimeConfig::default()
+        );
+
+        let invalid = RuntimeConfig {
+            worker_threads: Some(0),
+            cpu_affinity: vec![MAX_CPU + 1],
+            ..RuntimeConfig::default()
+        };
+        assert_eq!(invalid.errors().len(), 2);
+    }
+}
@@ -0,0 +1,205 @@
+//! Built-in self-test of a router instance.
+//!
+//! The checks behind `rust-route test` and `POST /api/diagnostics/selftest`:
+//! routing table operations on a scratch table, validation of the running
+//! configuration, the metrics pipeline and the health of the RIP sockets.
+//! Each check reports on its own, so monitoring can poll the endpoint and
+//! alert on the one that fails.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::net::Ipv4Addr;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::config_manager::{ConfigManager, RouterConfig};
+use crate::metrics::Metrics;
+use crate::network::NetworkInterface;
+use crate::routing_table::RoutingTable;
+
+/// Names of the checks, in the order they run
+pub const CHECKS: [&str; 4] = ["routing_table", "config", "metrics", "sockets"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+    /// Did not apply to this instance
+    Skip,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+    pub duration_micros: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SelfTestReport {
+    /// No check failed
+    pub passed: bool,
+    pub started_at: DateTime<Utc>,
+    pub checks: Vec<CheckResult>,
+}
+
+impl SelfTestReport {
+    pub fn failures(&self) -> impl Iterator<Item = &CheckResult> {
+        self.checks
+            .iter()
+            .filter(|check| check.status == CheckStatus::Fail)
+    }
+}
+
+type Outcome = (CheckStatus, String);
+
+/// Run every check against `config` and the router's `interfaces`
+pub fn run(config: &RouterConfig, interfaces: &[Arc<NetworkInterface>]) -> SelfTestReport {
+    let started_at = Utc::now();
+    let checks: Vec<CheckResult> = CHECKS
+        .iter()
+        .map(|&name| {
+            let started = Instant::now();
+            let (status, detail) = match name {
+                "routing_table" => check_routing_table(),
+                "config" => check_config(config),
+                "metrics" => check_metrics(),
+                _ => check_sockets(config, interfaces),
+            };
+            CheckResult {
+                name,
+                status,
+                detail,
+                duration_micros: started.elapsed().as_micros() as u64,
+            }
+        })
+        .collect();
+    SelfTestReport {
+        passed: checks.iter().all(|check| check.status != CheckStatus::Fail),
+        started_at,
+        checks,
+    }
+}
+
+fn fail(detail: impl Into<String>) -> Outcome {
+    (CheckStatus::Fail, detail.into())
+}
+
+/// Install, look up and remove routes on a table of its own
+fn check_routing_table() -> Outcome {
+    let mut table = RoutingTable::new();
+    let mask = Ipv4Addr::new(255, 255, 255, 0);
+    table.install_direct_route(Ipv4Addr::new(192, 0, 2, 0), mask, "selftest0".to_string());
+    table.add_static_route(
+        Ipv4Addr::new(198, 51, 100, 0),
+        mask,
+        Ipv4Addr::new(192, 0, 2, 1),
+        2,
+        "selftest0".to_string(),
+    );
+    if table.route_count() != 2 {
+        return fail(format!("expected 2 routes, found {}", table.route_count()));
+    }
+    match table.find_best_route(&Ipv4Addr::new(198, 51, 100, 7)) {
+        Some(route) if route.next_hop == Ipv4Addr::new(192, 0, 2, 1) => {}
+        Some(route) => return fail(format!("lookup chose next hop {}", route.next_hop)),
+        None => return fail("lookup found no route"),
+    }
+    if !table.remove_route(Ipv4Addr::new(198, 51, 100, 0), mask) || table.route_count() != 1 {
+        return fail("route removal did not take effect");
+    }
+    (
+        CheckStatus::Pass,
+        "install, lookup and removal work".to_string(),
+    )
+}
+
+fn check_config(config: &RouterConfig) -> Outcome {
+    let validation = ConfigManager::validate_config(config);
+    if !validation.is_valid() {
+        return fail(validation.errors.join("; "));
+    }
+    let detail = match validation.warnings.as_slice() {
+        [] => "configuration is valid".to_string(),
+        warnings => format!("configuration is valid; warnings: {}", warnings.join("; ")),
+    };
+    (CheckStatus::Pass, detail)
+}
+
+/// Counters recorded on a fresh registry show up in its snapshot
+fn check_metrics() -> Outcome {
+    let metrics = Metrics::new();
+    metrics.increment_packets_sent();
+    metrics.increment_packets_received();
+    metrics.update_route_count(4);
+    let snapshot = metrics.snapshot(1, 4);
+    if snapshot.packets_sent != 1 || snapshot.packets_received != 1 || snapshot.route_count != 4 {
+        return fail("snapshot does not match the recorded counters");
+    }
+    (CheckStatus::Pass, "counters reach the snapshot".to_string())
+}
+
+/// Every interface that is up has its socket bound
+fn check_sockets(config: &RouterConfig, interfaces: &[Arc<NetworkInterface>]) -> Outcome {
+    if !config.rip.enabled {
+        return (CheckStatus::Skip, "RIP is disabled".to_string());
+    }
+    if interfaces.is_empty() {
+        return (CheckStatus::Skip, "no RIP interfaces".to_string());
+    }
+    let unbound: Vec<&str> = interfaces
+        .iter()
+        .filter(|iface| iface.is_up() && !iface.is_bound())
+        .map(|iface| iface.config.name.as_str())
+        .collect();
+    if !unbound.is_empty() {
+        return fail(format!("no socket bound on {}", unbound.join(", ")));
+    }
+    let up = interfaces.iter().filter(|iface| iface.is_up()).count();
+    (
+        CheckStatus::Pass,
+        format!(
+            "{} of {} interfaces up with a bound socket",
+            up,
+            interfaces.len()
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::InterfaceConfig;
+
+    #[test]
+    fn checks_report_individually() {
+        let config = RouterConfig::default();
+        let unbound = Arc::new(NetworkInterface::new(InterfaceConfig::default()));
+        let report = run(&config, &[unbound]);
+        let statuses: Vec<(&str, CheckStatus)> = report
+            .checks
+            .iter()
+            .map(|check| (check.name, check.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("routing_table", CheckStatus::Pass),
+                ("config", CheckStatus::Pass),
+                ("metrics", CheckStatus::Pass),
+                ("sockets", CheckStatus::Fail),
+            ]
+        );
+        assert!(!report.passed);
+        assert!(report.failures().next().unwrap().detail.contains("eth0"));
+
+        let mut invalid = config.clone();
+        invalid.rip.enabled = false;
+        invalid.logging.level = "loud".to_string();
+        let report = run(&invalid, &[]);
+        assert_eq!(report.checks[1].status, CheckStatus::Fail);
+        assert_eq!(report.checks[3].status, CheckStatus::Skip);
+    }
+}
@@ -0,0 +1,205 @@
+//! Cookie sessions for the web UI and the CSRF tokens that guard them.
+//!
+//! With `web.session.cookies` enabled, a successful login stores the token
+//! in an `HttpOnly` session cookie instead of returning it to the page. A
+//! browser attaches that cookie to cross-site requests as well, so every
+//! state-changing request authenticated by the cookie must also carry the
+//! CSRF token in the `X-CSRF-Token` header. The CSRF token is an HMAC of the
+//! session token under a per-process key, handed to the page in a readable
+//! cookie; another site can neither read it nor derive it. Requests that
+//! authenticate with an `Authorization` header are not affected.
+
+use axum::http::{HeaderMap, Method};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::sync::OnceLock;
+
+pub const SESSION_COOKIE: &str = "rustroute_session";
+pub const CSRF_COOKIE: &str = "rustroute_csrf";
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// `SameSite` attribute of the session cookies
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SameSite {
+    #[default]
+    Strict,
+    Lax,
+    None,
+}
+
+impl SameSite {
+    fn as_str(self) -> &'static str {
+        match self {
+            SameSite::Strict => "Strict",
+            SameSite::Lax => "Lax",
+            SameSite::None => "None",
+        }
+    }
+}
+
+/// Session settings, under `web.session`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionConfig {
+    /// Keep the login token in a cookie rather than handing it to the page
+    #[serde(default)]
+    pub cookies: bool,
+    #[serde(default)]
+    pub same_site: SameSite,
+    /// Only send the cookies over HTTPS
+    #[serde(default = "default_secure")]
+    pub secure: bool,
+}
+
+fn default_secure() -> bool {
+    true
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            cookies: false,
+            same_site: SameSite::default(),
+            secure: default_secure(),
+        }
+    }
+}
+
+impl SessionConfig {
+    /// `Set-Cookie` values for a new session lasting `max_age` seconds,
+    /// scoped to the routes below `prefix`
+    pub fn session_cookies(&self, token: &str, max_age: u64, prefix: &str) -> [String; 2] {
+        [
+            self.cookie(SESSION_COOKIE, token, max_age, true, prefix),
+            self.cookie(CSRF_COOKIE, &csrf_token(token), max_age, false, prefix),
+        ]
+    }
+
+    /// `Set-Cookie` values that end the session below `prefix`
+    pub fn clear_cookies(&self, prefix: &str) -> [String; 2] {
+        [
+            self.cookie(SESSION_COOKIE, "", 0, true, prefix),
+            self.cookie(CSRF_COOKIE, "", 0, false, prefix),
+        ]
+    }
+
+    fn cookie(
+        &self,
+        name: &str,
+        value: &str,
+        max_age: u64,
+        http_only: bool,
+        prefix: &str,
+    ) -> String {
+        let path = if prefix.is_empty() { "/" } else { prefix };
+        let mut cookie = format!(
+            "{}={}; Path={}; Max-Age={}; SameSite={}",
+            name,
+            value,
+            path,
+            max_age,
+            self.same_site.as_str()
+        );
+        if http_only {
+            cookie.push_str("; HttpOnly");
+        }
+        if self.secure {
+            cookie.push_str("; Secure");
+        }
+        cookie
+    }
+}
+
+fn csrf_key() -> &'static [u8; 32] {
+    static KEY: OnceLock<[u8; 32]> = OnceLock::new();
+    KEY.get_or_init(rand::random)
+}
+
+fn csrf_mac(session: &str) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(csrf_key()).expect("HMAC accepts keys of any length");
+    mac.update(session.as_bytes());
+    mac
+}
+
+/// CSRF token belonging to `session`
+pub fn csrf_token(session: &str) -> String {
+    csrf_mac(session)
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Whether `provided` is the CSRF token of `session`, compared in constant time
+pub fn verify_csrf(session: &str, provided: &str) -> bool {
+    let Some(provided) = crate::packet_auth::decode_hex(provided) else {
+        return false;
+    };
+    csrf_mac(session).verify_slice(&provided).is_ok()
+}
+
+/// Whether requests with `method` can change state and need a CSRF token
+pub fn requires_csrf(method: &Method) -> bool {
+    !matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+/// Value of cookie `name` in the request headers
+pub fn cookie_value<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(axum::http::header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+        .filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn cookies_carry_the_configured_flags() {
+        let config = SessionConfig {
+            cookies: true,
+            same_site: SameSite::Lax,
+            secure: false,
+        };
+        let [session, csrf] = config.session_cookies("tok", 3600, "");
+        assert_eq!(
+            session,
+            "rustroute_session=tok; Path=/; Max-Age=3600; SameSite=Lax; HttpOnly"
+        );
+        assert!(csrf.starts_with("rustroute_csrf="));
+        assert!(!csrf.contains("HttpOnly"));
+
+        let [cleared, _] = SessionConfig::default().clear_cookies("/routers/r1");
+        assert!(cleared.contains("Path=/routers/r1;"));
+        assert!(cleared.contains("Max-Age=0") && cleared.ends_with("; Secure"));
+    }
+
+    #[test]
+    fn csrf_tokens_are_bound_to_the_session() {
+        let token = csrf_token("session-a");
+        assert!(verify_csrf("session-a", &token));
+        assert!(!verify_csrf("session-b", &token));
+        assert!(!verify_csrf("session-a", "not-hex"));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            axum::http::header::COOKIE,
+            HeaderValue::from_static("theme=dark; rustroute_session=abc"),
+        );
+        assert_eq!(cookie_value(&headers, SESSION_COOKIE), Some("abc"));
+        assert_eq!(cookie_value(&headers, CSRF_COOKIE), None);
+        assert!(requires_csrf(&Method::DELETE));
+        assert!(!requires_csrf(&Method::GET));
+    }
+}
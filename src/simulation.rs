@@ -0,0 +1,436 @@
+//! In-memory RIP simulation for reproducible convergence scenarios.
+//!
+//! Each simulated router is a [`RoutingTable`] on one shared [`MockClock`],
+//! and links hand responses straight to the table on the far side, so a
+//! scenario of hours runs in milliseconds. Every random choice — the jitter
+//! on periodic updates, which updates a lossy link drops, and the order of
+//! updates arriving at the same instant, which decides equal-metric
+//! tie-breaks — is drawn from one RNG seeded with
+//! [`SimulationConfig::seed`]. The same seed replays the same run, down to
+//! every delivery in [`Simulation::trace`].
+
+use ipnet::Ipv4Net;
+use log::info;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::net::Ipv4Addr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::routing_table::{Route, RouteSource, RoutingTable, TieBreak};
+use crate::timers::{MockClock, RipTimers};
+
+/// Environment variable [`SimulationConfig::from_env`] reads the seed from
+pub const SEED_ENV: &str = "RUST_ROUTE_SIM_SEED";
+/// Interface name of the stub networks added with [`Simulation::add_network`]
+const STUB_INTERFACE: &str = "lo";
+
+#[derive(Debug, Clone)]
+pub struct SimulationConfig {
+    pub seed: u64,
+    /// Period between each router's full updates
+    pub update_interval: Duration,
+    /// Updates are sent up to this much before or after the period ends
+    pub jitter: Duration,
+    /// Chance that a link drops an update, unless set per link
+    pub loss: f64,
+    pub timers: RipTimers,
+    pub tie_break: TieBreak,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            update_interval: Duration::from_secs(30),
+            jitter: Duration::from_secs(5),
+            loss: 0.0,
+            timers: RipTimers::default(),
+            tie_break: TieBreak::default(),
+        }
+    }
+}
+
+impl SimulationConfig {
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            seed,
+            ..Self::default()
+        }
+    }
+
+    /// Seed from `RUST_ROUTE_SIM_SEED`, or a random one that is logged so a
+    /// failing run can be replayed
+    pub fn from_env() -> Self {
+        let seed = std::env::var(SEED_ENV)
+            .ok()
+            .and_then(|value| value.trim().parse().ok())
+            .unwrap_or_else(rand::random);
+        info!("Simulation seed {} (set {} to replay)", seed, SEED_ENV);
+        Self::with_seed(seed)
+    }
+}
+
+pub type RouterId = usize;
+pub type LinkId = usize;
+
+/// One update as it was delivered or dropped
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delivery {
+    /// Simulated time since the start
+    pub at: Duration,
+    pub link: LinkId,
+    pub from: RouterId,
+    pub to: RouterId,
+    /// Advertised prefixes and metrics, sorted by prefix
+    pub entries: Vec<(Ipv4Net, u32)>,
+    pub dropped: bool,
+}
+
+#[derive(Debug)]
+struct SimRouter {
+    name: String,
+    table: RoutingTable,
+    next_update: Duration,
+}
+
+#[derive(Debug, Clone)]
+struct LinkEnd {
+    router: RouterId,
+    interface: String,
+    address: Ipv4Addr,
+}
+
+#[derive(Debug, Clone)]
+struct Link {
+    ends: [LinkEnd; 2],
+    subnet: Ipv4Net,
+    up: bool,
+    loss: Option<f64>,
+}
+
+#[derive(Debug)]
+pub struct Simulation {
+    config: SimulationConfig,
+    clock: Arc<MockClock>,
+    elapsed: Duration,
+    next_sweep: Duration,
+    rng: StdRng,
+    routers: Vec<SimRouter>,
+    links: Vec<Link>,
+    trace: Vec<Delivery>,
+}
+
+impl Simulation {
+    pub fn new(config: SimulationConfig) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(config.seed),
+            next_sweep: config.timers.sweep_interval(),
+            config,
+            clock: Arc::new(MockClock::new()),
+            elapsed: Duration::ZERO,
+            routers: Vec::new(),
+            links: Vec::new(),
+            trace: Vec::new(),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.config.seed
+    }
+
+    /// Simulated time since the start
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Add a router; its first update goes out at a random point of the
+    /// first period, as routers started together would not stay in step
+    pub fn add_router(&mut self, name: &str) -> RouterId {
+        let mut table = RoutingTable::with_clock(self.config.timers, self.clock.clone());
+        table.set_tie_break(self.config.tie_break);
+        let first = self
+            .rng
+            .gen_range(Duration::ZERO..=self.config.update_interval);
+        self.routers.push(SimRouter {
+            name: name.to_string(),
+            table,
+            next_update: self.elapsed + first,
+        });
+        self.routers.len() - 1
+    }
+
+    pub fn router_name(&self, router: RouterId) -> &str {
+        &self.routers[router].name
+    }
+
+    /// Attach a stub network that `router` originates
+    pub fn add_network(&mut self, router: RouterId, network: Ipv4Net) {
+        self.routers[router].table.install_direct_route(
+            network.network(),
+            network.netmask(),
+            STUB_INTERFACE.to_string(),
+        );
+    }
+
+    /// Connect two routers with a point-to-point link numbered from
+    /// 169.254.0.0/30 upwards
+    pub fn connect(&mut self, a: RouterId, b: RouterId) -> LinkId {
+        let id = self.links.len();
+        let base = u32::from(Ipv4Addr::new(169, 254, 0, 0)) + (id as u32) * 4;
+        let subnet = Ipv4Net::new(Ipv4Addr::from(base), 30).expect("a /30 is a valid prefix");
+        let end = |router, host| LinkEnd {
+            router,
+            interface: format!("link{}", id),
+            address: Ipv4Addr::from(base + host),
+        };
+        self.links.push(Link {
+            ends: [end(a, 1), end(b, 2)],
+            subnet,
+            up: true,
+            loss: None,
+        });
+        self.install_link_routes(id);
+        id
+    }
+
+    /// Drop updates on `link` with probability `loss`, overriding the
+    /// configured default
+    pub fn set_loss(&mut self, link: LinkId, loss: f64) {
+        self.links[link].loss = Some(loss);
+    }
+
+    /// Take `link` down; both ends withdraw the routes through it at once
+    pub fn fail_link(&mut self, link: LinkId) {
+        self.links[link].up = false;
+        for end in self.links[link].ends.clone() {
+            let table = &mut self.routers[end.router].table;
+            table.poison_interface(&end.interface, RouteSource::Direct);
+            table.poison_interface(&end.interface, RouteSource::Dynamic);
+        }
+    }
+
+    pub fn restore_link(&mut self, link: LinkId) {
+        self.links[link].up = true;
+        self.install_link_routes(link);
+    }
+
+    fn install_link_routes(&mut self, link: LinkId) {
+        let Link { ends, subnet, .. } = self.links[link].clone();
+        for end in ends {
+            self.routers[end.router].table.install_direct_route(
+                subnet.network(),
+                subnet.netmask(),
+                end.interface,
+            );
+        }
+    }
+
+    pub fn table(&self, router: RouterId) -> &RoutingTable {
+        &self.routers[router].table
+    }
+
+    /// Metric of the reachable route `router` has to `network`, if any
+    pub fn metric(&self, router: RouterId, network: Ipv4Net) -> Option<u32> {
+        self.table(router)
+            .get_all_routes()
+            .into_iter()
+            .find(|route| {
+                route.destination == network.network() && route.subnet_mask == network.netmask()
+            })
+            .map(|route| route.metric)
+            .filter(|metric| *metric < self.config.timers.infinity)
+    }
+
+    /// Every update delivered or dropped so far, in order
+    pub fn trace(&self) -> &[Delivery] {
+        &self.trace
+    }
+
+    /// Advance simulated time by `duration`, exchanging updates and running
+    /// the route timers on the way
+    pub fn run_for(&mut self, duration: Duration) {
+        let end = self.elapsed + duration;
+        loop {
+            let next_update = self.routers.iter().map(|router| router.next_update).min();
+            let next = next_update.map_or(self.next_sweep, |at| at.min(self.next_sweep));
+            if next > end {
+                self.advance_to(end);
+                return;
+            }
+            self.advance_to(next);
+
+            if next == self.next_sweep {
+                for router in &mut self.routers {
+                    router.table.process_timeouts();
+                    router.table.garbage_collect();
+                }
+                self.next_sweep += self.config.timers.sweep_interval();
+            }
+
+            let mut deliveries = Vec::new();
+            for id in 0..self.routers.len() {
+                if self.routers[id].next_update == next {
+                    deliveries.extend(self.advertise(id));
+                    self.routers[id].next_update = next + self.next_period();
+                }
+            }
+            // Updates arriving together are processed in a seeded order
+            deliveries.shuffle(&mut self.rng);
+            for delivery in deliveries {
+                self.deliver(delivery);
+            }
+        }
+    }
+
+    fn advance_to(&mut self, at: Duration) {
+        if at > self.elapsed {
+            self.clock.advance(at - self.elapsed);
+            self.elapsed = at;
+        }
+    }
+
+    /// The update period with jitter applied
+    fn next_period(&mut self) -> Duration {
+        let jitter = self.config.jitter.min(self.config.update_interval / 2);
+        let offset = self.rng.gen_range(Duration::ZERO..=jitter * 2);
+        self.config.update_interval - jitter + offset
+    }
+
+    /// Full updates from `router` on each of its links that are up, with
+    /// split horizon
+    fn advertise(&self, router: RouterId) -> Vec<Delivery> {
+        let mut deliveries = Vec::new();
+        for (id, link) in self.links.iter().enumerate() {
+            if !link.up {
+                continue;
+            }
+            let Some(side) = link.ends.iter().position(|end| end.router == router) else {
+                continue;
+            };
+            let mut entries: Vec<(Ipv4Net, u32)> = self.routers[router]
+                .table
+                .get_routes_for_advertising(&link.ends[side].interface)
+                .into_iter()
+                .filter_map(|route| {
+                    Ipv4Net::with_netmask(route.destination, route.subnet_mask)
+                        .ok()
+                        .map(|net| (net, route.metric))
+                })
+                .collect();
+            entries.sort();
+            deliveries.push(Delivery {
+                at: self.elapsed,
+                link: id,
+                from: router,
+                to: link.ends[1 - side].router,
+                entries,
+                dropped: false,
+            });
+        }
+        deliveries
+    }
+
+    fn deliver(&mut self, mut delivery: Delivery) {
+        let link = &self.links[delivery.link];
+        let loss = link.loss.unwrap_or(self.config.loss);
+        delivery.dropped = loss > 0.0 && self.rng.gen_bool(loss.min(1.0));
+        if !delivery.dropped {
+            let sender = link.ends.iter().find(|end| end.router == delivery.from);
+            let receiver = link.ends.iter().find(|end| end.router == delivery.to);
+            if let (Some(sender), Some(receiver)) = (sender, receiver) {
+                let infinity = self.config.timers.infinity;
+                let table = &mut self.routers[delivery.to].table;
+                for (network, metric) in &delivery.entries {
+                    let metric = metric.saturating_add(1).min(infinity);
+                    table.add_or_replace(Route::new(
+                        network.network(),
+                        network.netmask(),
+                        sender.address,
+                        metric,
+                        receiver.interface.clone(),
+                        RouteSource::Dynamic,
+                        Some(sender.address),
+                    ));
+                }
+            }
+        }
+        self.trace.push(delivery);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn net(value: &str) -> Ipv4Net {
+        value.parse().unwrap()
+    }
+
+    /// Four routers in a square with lossy links; the far corner has two
+    /// equal-cost paths to the stub network
+    fn square(seed: u64) -> (Simulation, RouterId) {
+        let mut sim = Simulation::new(SimulationConfig {
+            loss: 0.3,
+            ..SimulationConfig::with_seed(seed)
+        });
+        let routers: Vec<RouterId> = ["a", "b", "c", "d"]
+            .iter()
+            .map(|name| sim.add_router(name))
+            .collect();
+        sim.add_network(routers[0], net("10.1.0.0/24"));
+        for (a, b) in [(0, 1), (1, 3), (0, 2), (2, 3)] {
+            sim.connect(routers[a], routers[b]);
+        }
+        sim.run_for(Duration::from_secs(600));
+        (sim, routers[3])
+    }
+
+    #[test]
+    fn same_seed_replays_the_same_run() {
+        let (first, far) = square(7);
+        let (second, _) = square(7);
+        assert_eq!(first.trace(), second.trace());
+        assert!(first.trace().iter().any(|delivery| delivery.dropped));
+        assert_eq!(
+            first
+                .table(far)
+                .get_route(Ipv4Addr::new(10, 1, 0, 1))
+                .map(|r| r.next_hop),
+            second
+                .table(far)
+                .get_route(Ipv4Addr::new(10, 1, 0, 1))
+                .map(|r| r.next_hop)
+        );
+
+        let (other, _) = square(8);
+        assert_ne!(first.trace(), other.trace());
+    }
+
+    #[test]
+    fn line_converges_and_forgets_a_failed_link() {
+        let mut sim = Simulation::new(SimulationConfig::with_seed(1));
+        let a = sim.add_router("a");
+        let b = sim.add_router("b");
+        let c = sim.add_router("c");
+        let stub = net("10.3.0.0/24");
+        sim.add_network(c, stub);
+        sim.connect(a, b);
+        let bc = sim.connect(b, c);
+
+        sim.run_for(Duration::from_secs(120));
+        assert_eq!(sim.metric(b, stub), Some(2));
+        assert_eq!(sim.metric(a, stub), Some(3));
+
+        // b withdraws at once; a waits for its own timeout
+        sim.fail_link(bc);
+        assert_eq!(sim.metric(b, stub), None);
+        sim.run_for(Duration::from_secs(240));
+        assert_eq!(sim.metric(a, stub), None);
+
+        sim.restore_link(bc);
+        sim.run_for(Duration::from_secs(120));
+        assert_eq!(sim.metric(a, stub), Some(3));
+    }
+}
@@ -0,0 +1,1488 @@
+//! SNMP agent exposing the RIPv2 MIB (RFC 1724) alongside the interface and
+//! route tables, so network management systems can poll RustRoute.
+//!
+//! The agent is read-only. SNMPv1/v2c requests are authorised by community;
+//! SNMPv3 requests use the user-based security model with authentication
+//! (HMAC-MD5-96 or HMAC-SHA-96) and no privacy.
+
+use hmac::{Hmac, Mac};
+use log::{debug, warn};
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr};
+use std::ops::Bound;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tokio::net::UdpSocket;
+use tokio::sync::RwLock;
+
+use crate::listen;
+use crate::metrics::Metrics;
+use crate::router::{Router, RECEIVE_HEARTBEAT};
+use crate::routing_table::{RouteSource, RoutingTable};
+use crate::watchdog::Heartbeat;
+use crate::{RustRouteError, RustRouteResult};
+
+/// Authentication protocol for an SNMPv3 user
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SnmpAuthProtocol {
+    Md5,
+    #[default]
+    Sha,
+}
+
+/// SNMPv3 user credentials
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnmpV3User {
+    pub username: String,
+    #[serde(default)]
+    pub auth_protocol: SnmpAuthProtocol,
+    pub auth_password: String,
+}
+
+/// SNMP agent configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnmpConfig {
+    pub enabled: bool,
+    pub bind_address: String,
+    pub port: u16,
+    /// Read-only community for v1/v2c; empty disables community access
+    pub community: String,
+    #[serde(default)]
+    pub v3_users: Vec<SnmpV3User>,
+}
+
+impl Default for SnmpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind_address: "127.0.0.1".to_string(),
+            port: 161,
+            community: "public".to_string(),
+            v3_users: Vec::new(),
+        }
+    }
+}
+
+pub type Oid = Vec<u32>;
+
+const OID_SYSTEM: &[u32] = &[1, 3, 6, 1, 2, 1, 1];
+const OID_INTERFACES: &[u32] = &[1, 3, 6, 1, 2, 1, 2];
+const OID_IP_ROUTE_ENTRY: &[u32] = &[1, 3, 6, 1, 2, 1, 4, 21, 1];
+const OID_RIP2: &[u32] = &[1, 3, 6, 1, 2, 1, 23];
+const OID_USM_STATS: &[u32] = &[1, 3, 6, 1, 6, 3, 15, 1, 1];
+
+const USM_UNSUPPORTED_SEC_LEVELS: u32 = 1;
+const USM_NOT_IN_TIME_WINDOWS: u32 = 2;
+const USM_UNKNOWN_USER_NAMES: u32 = 3;
+const USM_UNKNOWN_ENGINE_IDS: u32 = 4;
+const USM_WRONG_DIGESTS: u32 = 5;
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_NULL: u8 = 0x05;
+const TAG_OID: u8 = 0x06;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_IP_ADDRESS: u8 = 0x40;
+const TAG_COUNTER32: u8 = 0x41;
+const TAG_GAUGE32: u8 = 0x42;
+const TAG_TIMETICKS: u8 = 0x43;
+const TAG_COUNTER64: u8 = 0x46;
+const TAG_NO_SUCH_OBJECT: u8 = 0x80;
+const TAG_NO_SUCH_INSTANCE: u8 = 0x81;
+const TAG_END_OF_MIB_VIEW: u8 = 0x82;
+
+const PDU_GET: u8 = 0xA0;
+const PDU_GET_NEXT: u8 = 0xA1;
+const PDU_RESPONSE: u8 = 0xA2;
+const PDU_SET: u8 = 0xA3;
+const PDU_GET_BULK: u8 = 0xA5;
+const PDU_REPORT: u8 = 0xA8;
+
+const ERR_NO_SUCH_NAME: i64 = 2;
+const ERR_GEN_ERR: i64 = 5;
+const ERR_NO_ACCESS: i64 = 6;
+
+const FLAG_AUTH: u8 = 0x01;
+const FLAG_PRIV: u8 = 0x02;
+const FLAG_REPORTABLE: u8 = 0x04;
+
+const AUTH_PARAMS_LEN: usize = 12;
+const TIME_WINDOW_SECONDS: u64 = 150;
+const MAX_BULK_VARBINDS: usize = 500;
+/// engineBoots is derived from the start time so it increases across restarts
+const ENGINE_BOOTS_EPOCH: u64 = 1_577_836_800; // 2020-01-01T00:00:00Z
+
+/// A decoded SNMP value
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Integer(i64),
+    OctetString(Vec<u8>),
+    Null,
+    Oid(Oid),
+    IpAddress(Ipv4Addr),
+    Counter32(u32),
+    Gauge32(u32),
+    TimeTicks(u32),
+    Counter64(u64),
+    NoSuchObject,
+    NoSuchInstance,
+    EndOfMibView,
+}
+
+impl Value {
+    fn encode(&self) -> Vec<u8> {
+        match self {
+            Value::Integer(value) => tlv(TAG_INTEGER, &encode_integer(*value)),
+            Value::OctetString(bytes) => tlv(TAG_OCTET_STRING, bytes),
+            Value::Null => tlv(TAG_NULL, &[]),
+            Value::Oid(oid) => tlv(TAG_OID, &encode_oid(oid)),
+            Value::IpAddress(addr) => tlv(TAG_IP_ADDRESS, &addr.octets()),
+            Value::Counter32(value) => tlv(TAG_COUNTER32, &encode_unsigned(*value as u64)),
+            Value::Gauge32(value) => tlv(TAG_GAUGE32, &encode_unsigned(*value as u64)),
+            Value::TimeTicks(value) => tlv(TAG_TIMETICKS, &encode_unsigned(*value as u64)),
+            Value::Counter64(value) => tlv(TAG_COUNTER64, &encode_unsigned(*value)),
+            Value::NoSuchObject => tlv(TAG_NO_SUCH_OBJECT, &[]),
+            Value::NoSuchInstance => tlv(TAG_NO_SUCH_INSTANCE, &[]),
+            Value::EndOfMibView => tlv(TAG_END_OF_MIB_VIEW, &[]),
+        }
+    }
+
+    fn decode(tag: u8, content: &[u8]) -> RustRouteResult<Self> {
+        Ok(match tag {
+            TAG_INTEGER => Value::Integer(decode_integer(content)?),
+            TAG_OCTET_STRING => Value::OctetString(content.to_vec()),
+            TAG_NULL => Value::Null,
+            TAG_OID => Value::Oid(decode_oid(content)?),
+            TAG_IP_ADDRESS => {
+                let octets: [u8; 4] = content
+                    .try_into()
+                    .map_err(|_| ber_error("IpAddress must be 4 octets"))?;
+                Value::IpAddress(Ipv4Addr::from(octets))
+            }
+            TAG_COUNTER32 => Value::Counter32(decode_unsigned(content)? as u32),
+            TAG_GAUGE32 => Value::Gauge32(decode_unsigned(content)? as u32),
+            TAG_TIMETICKS => Value::TimeTicks(decode_unsigned(content)? as u32),
+            TAG_COUNTER64 => Value::Counter64(decode_unsigned(content)?),
+            TAG_NO_SUCH_OBJECT => Value::NoSuchObject,
+            TAG_NO_SUCH_INSTANCE => Value::NoSuchInstance,
+            TAG_END_OF_MIB_VIEW => Value::EndOfMibView,
+            other => return Err(ber_error(&format!("unsupported value tag 0x{:02x}", other))),
+        })
+    }
+}
+
+/// SNMP protocol data unit. For GetBulk, `error_status` and `error_index`
+/// carry non-repeaters and max-repetitions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pdu {
+    pub kind: u8,
+    pub request_id: i64,
+    pub error_status: i64,
+    pub error_index: i64,
+    pub varbinds: Vec<(Oid, Value)>,
+}
+
+impl Pdu {
+    fn encode(&self) -> Vec<u8> {
+        let mut bindings = Vec::new();
+        for (oid, value) in &self.varbinds {
+            let mut binding = tlv(TAG_OID, &encode_oid(oid));
+            binding.extend(value.encode());
+            bindings.extend(tlv(TAG_SEQUENCE, &binding));
+        }
+
+        let mut content = tlv(TAG_INTEGER, &encode_integer(self.request_id));
+        content.extend(tlv(TAG_INTEGER, &encode_integer(self.error_status)));
+        content.extend(tlv(TAG_INTEGER, &encode_integer(self.error_index)));
+        content.extend(tlv(TAG_SEQUENCE, &bindings));
+        tlv(self.kind, &content)
+    }
+
+    fn decode(reader: &mut Reader<'_>) -> RustRouteResult<Self> {
+        let (kind, content) = reader.read_any()?;
+        let mut pdu = Reader::new(content);
+        let request_id = pdu.read_integer()?;
+        let error_status = pdu.read_integer()?;
+        let error_index = pdu.read_integer()?;
+
+        let mut bindings = Reader::new(pdu.read(TAG_SEQUENCE)?);
+        let mut varbinds = Vec::new();
+        while !bindings.is_empty() {
+            let mut binding = Reader::new(bindings.read(TAG_SEQUENCE)?);
+            let oid = decode_oid(binding.read(TAG_OID)?)?;
+            let (tag, value) = binding.read_any()?;
+            varbinds.push((oid, Value::decode(tag, value)?));
+        }
+
+        Ok(Self {
+            kind,
+            request_id,
+            error_status,
+            error_index,
+            varbinds,
+        })
+    }
+
+    fn reply(&self, varbinds: Vec<(Oid, Value)>) -> Self {
+        Self {
+            kind: PDU_RESPONSE,
+            request_id: self.request_id,
+            error_status: 0,
+            error_index: 0,
+            varbinds,
+        }
+    }
+
+    fn error(&self, status: i64, index: usize) -> Self {
+        Self {
+            kind: PDU_RESPONSE,
+            request_id: self.request_id,
+            error_status: status,
+            error_index: index as i64,
+            varbinds: self.varbinds.clone(),
+        }
+    }
+}
+
+/// User-based security parameters carried in an SNMPv3 message
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UsmParams {
+    pub engine_id: Vec<u8>,
+    pub engine_boots: i64,
+    pub engine_time: i64,
+    pub username: Vec<u8>,
+}
+
+/// SNMPv3 global header fields
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct V3Header {
+    pub msg_id: i64,
+    pub max_size: i64,
+    pub flags: u8,
+}
+
+/// A decoded SNMP message
+#[derive(Debug, Clone)]
+pub enum Message {
+    Community {
+        version: i64,
+        community: Vec<u8>,
+        pdu: Pdu,
+    },
+    V3 {
+        header: V3Header,
+        usm: UsmParams,
+        auth_params: Vec<u8>,
+        /// Offset of the authentication parameters within the raw message
+        auth_offset: usize,
+        /// `None` when the scoped PDU is encrypted
+        scoped: Option<(Vec<u8>, Vec<u8>, Pdu)>,
+    },
+}
+
+impl Message {
+    pub fn decode(packet: &[u8]) -> RustRouteResult<Self> {
+        let mut outer = Reader::new(packet);
+        let mut message = Reader::new(outer.read(TAG_SEQUENCE)?);
+        let version = message.read_integer()?;
+
+        if version != 3 {
+            let community = message.read(TAG_OCTET_STRING)?.to_vec();
+            let pdu = Pdu::decode(&mut message)?;
+            return Ok(Message::Community {
+                version,
+                community,
+                pdu,
+            });
+        }
+
+        let mut global = Reader::new(message.read(TAG_SEQUENCE)?);
+        let msg_id = global.read_integer()?;
+        let max_size = global.read_integer()?;
+        let flags = *global
+            .read(TAG_OCTET_STRING)?
+            .first()
+            .ok_or_else(|| ber_error("empty msgFlags"))?;
+        if global.read_integer()? != 3 {
+            return Err(ber_error("unsupported security model"));
+        }
+
+        let mut security = Reader::new(message.read(TAG_OCTET_STRING)?);
+        let mut usm_reader = Reader::new(security.read(TAG_SEQUENCE)?);
+        let engine_id = usm_reader.read(TAG_OCTET_STRING)?.to_vec();
+        let engine_boots = usm_reader.read_integer()?;
+        let engine_time = usm_reader.read_integer()?;
+        let username = usm_reader.read(TAG_OCTET_STRING)?.to_vec();
+        let auth_slice = usm_reader.read(TAG_OCTET_STRING)?;
+        let auth_offset = auth_slice.as_ptr() as usize - packet.as_ptr() as usize;
+        let auth_params = auth_slice.to_vec();
+
+        let scoped = if flags & FLAG_PRIV != 0 {
+            None
+        } else {
+            let mut scoped = Reader::new(message.read(TAG_SEQUENCE)?);
+            let context_engine = scoped.read(TAG_OCTET_STRING)?.to_vec();
+            let context_name = scoped.read(TAG_OCTET_STRING)?.to_vec();
+            Some((context_engine, context_name, Pdu::decode(&mut scoped)?))
+        };
+
+        Ok(Message::V3 {
+            header: V3Header {
+                msg_id,
+                max_size,
+                flags,
+            },
+            usm: UsmParams {
+                engine_id,
+                engine_boots,
+                engine_time,
+                username,
+            },
+            auth_params,
+            auth_offset,
+            scoped,
+        })
+    }
+}
+
+/// Encode an SNMPv1/v2c message
+pub fn encode_community(version: i64, community: &[u8], pdu: &Pdu) -> Vec<u8> {
+    let mut content = tlv(TAG_INTEGER, &encode_integer(version));
+    content.extend(tlv(TAG_OCTET_STRING, community));
+    content.extend(pdu.encode());
+    tlv(TAG_SEQUENCE, &content)
+}
+
+/// Encode an SNMPv3 message, signing it when `auth` carries a localized key.
+/// The context engine is the authoritative engine named in `usm`.
+pub fn encode_v3(
+    header: V3Header,
+    usm: &UsmParams,
+    auth: Option<(SnmpAuthProtocol, &[u8])>,
+    context_name: &[u8],
+    pdu: &Pdu,
+) -> Vec<u8> {
+    let version = tlv(TAG_INTEGER, &encode_integer(3));
+
+    let mut global = tlv(TAG_INTEGER, &encode_integer(header.msg_id));
+    global.extend(tlv(TAG_INTEGER, &encode_integer(header.max_size)));
+    global.extend(tlv(TAG_OCTET_STRING, &[header.flags]));
+    global.extend(tlv(TAG_INTEGER, &encode_integer(3)));
+    let global = tlv(TAG_SEQUENCE, &global);
+
+    let mut usm_content = tlv(TAG_OCTET_STRING, &usm.engine_id);
+    usm_content.extend(tlv(TAG_INTEGER, &encode_integer(usm.engine_boots)));
+    usm_content.extend(tlv(TAG_INTEGER, &encode_integer(usm.engine_time)));
+    usm_content.extend(tlv(TAG_OCTET_STRING, &usm.username));
+    let auth_start = usm_content.len() + 2;
+    let placeholder = if auth.is_some() {
+        vec![0u8; AUTH_PARAMS_LEN]
+    } else {
+        Vec::new()
+    };
+    usm_content.extend(tlv(TAG_OCTET_STRING, &placeholder));
+    usm_content.extend(tlv(TAG_OCTET_STRING, &[]));
+    let usm_seq = tlv(TAG_SEQUENCE, &usm_content);
+    let auth_start = auth_start + header_len(&usm_seq, usm_content.len());
+    let security = tlv(TAG_OCTET_STRING, &usm_seq);
+    let auth_start = auth_start + header_len(&security, usm_seq.len());
+
+    let mut scoped = tlv(TAG_OCTET_STRING, &usm.engine_id);
+    scoped.extend(tlv(TAG_OCTET_STRING, context_name));
+    scoped.extend(pdu.encode());
+    let scoped = tlv(TAG_SEQUENCE, &scoped);
+
+    let mut body = version;
+    let auth_start = auth_start + body.len() + global.len();
+    body.extend(global);
+    body.extend(security);
+    body.extend(scoped);
+    let mut message = tlv(TAG_SEQUENCE, &body);
+    let auth_start = auth_start + header_len(&message, body.len());
+
+    if let Some((protocol, key)) = auth {
+        let digest = hmac_96(protocol, key, &message);
+        message[auth_start..auth_start + AUTH_PARAMS_LEN].copy_from_slice(&digest);
+    }
+    message
+}
+
+/// Derive the user key from a password (RFC 3414 A.2)
+pub fn password_to_key(protocol: SnmpAuthProtocol, password: &[u8]) -> Vec<u8> {
+    fn expand<D: Digest>(password: &[u8]) -> Vec<u8> {
+        let mut hasher = D::new();
+        if !password.is_empty() {
+            let mut chunk = [0u8; 64];
+            let mut index = 0;
+            for _ in 0..(1_048_576 / 64) {
+                for byte in chunk.iter_mut() {
+                    *byte = password[index % password.len()];
+                    index += 1;
+                }
+                hasher.update(chunk);
+            }
+        }
+        hasher.finalize().to_vec()
+    }
+
+    match protocol {
+        SnmpAuthProtocol::Md5 => expand::<Md5>(password),
+        SnmpAuthProtocol::Sha => expand::<Sha1>(password),
+    }
+}
+
+/// Localize a user key to an authoritative engine (RFC 3414 A.2)
+pub fn localize_key(protocol: SnmpAuthProtocol, key: &[u8], engine_id: &[u8]) -> Vec<u8> {
+    fn localize<D: Digest>(key: &[u8], engine_id: &[u8]) -> Vec<u8> {
+        let mut hasher = D::new();
+        hasher.update(key);
+        hasher.update(engine_id);
+        hasher.update(key);
+        hasher.finalize().to_vec()
+    }
+
+    match protocol {
+        SnmpAuthProtocol::Md5 => localize::<Md5>(key, engine_id),
+        SnmpAuthProtocol::Sha => localize::<Sha1>(key, engine_id),
+    }
+}
+
+fn hmac_96(protocol: SnmpAuthProtocol, key: &[u8], message: &[u8]) -> [u8; AUTH_PARAMS_LEN] {
+    let full = match protocol {
+        SnmpAuthProtocol::Md5 => {
+            let mut mac = Hmac::<Md5>::new_from_slice(key).expect("HMAC accepts any key length");
+            mac.update(message);
+            mac.finalize().into_bytes().to_vec()
+        }
+        SnmpAuthProtocol::Sha => {
+            let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts any key length");
+            mac.update(message);
+            mac.finalize().into_bytes().to_vec()
+        }
+    };
+
+    let mut truncated = [0u8; AUTH_PARAMS_LEN];
+    truncated.copy_from_slice(&full[..AUTH_PARAMS_LEN]);
+    truncated
+}
+
+/// Whether `digest` is the HMAC-96 of `message`, compared in constant time
+fn verify_hmac_96(
+    protocol: SnmpAuthProtocol,
+    key: &[u8],
+    message: &[u8],
+    digest: &[u8],
+) -> bool {
+    match protocol {
+        SnmpAuthProtocol::Md5 => {
+            let mut mac = Hmac::<Md5>::new_from_slice(key).expect("HMAC accepts any key length");
+            mac.update(message);
+            mac.verify_truncated_left(digest).is_ok()
+        }
+        SnmpAuthProtocol::Sha => {
+            let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts any key length");
+            mac.update(message);
+            mac.verify_truncated_left(digest).is_ok()
+        }
+    }
+}
+
+struct LocalizedUser {
+    name: Vec<u8>,
+    protocol: SnmpAuthProtocol,
+    key: Vec<u8>,
+}
+
+#[derive(Default)]
+struct UsmStats {
+    unsupported_sec_levels: AtomicU32,
+    not_in_time_windows: AtomicU32,
+    unknown_user_names: AtomicU32,
+    unknown_engine_ids: AtomicU32,
+    wrong_digests: AtomicU32,
+}
+
+impl UsmStats {
+    fn counter(&self, which: u32) -> &AtomicU32 {
+        match which {
+            USM_UNSUPPORTED_SEC_LEVELS => &self.unsupported_sec_levels,
+            USM_NOT_IN_TIME_WINDOWS => &self.not_in_time_windows,
+            USM_UNKNOWN_USER_NAMES => &self.unknown_user_names,
+            USM_UNKNOWN_ENGINE_IDS => &self.unknown_engine_ids,
+            _ => &self.wrong_digests,
+        }
+    }
+}
+
+/// Read-only SNMP agent backed by the live router state
+pub struct SnmpAgent {
+    config: SnmpConfig,
+    router: Arc<RwLock<Router>>,
+    routing_table: Arc<RwLock<RoutingTable>>,
+    metrics: Metrics,
+    engine_id: Vec<u8>,
+    engine_boots: i64,
+    engine_start: Instant,
+    users: Vec<LocalizedUser>,
+    usm_stats: UsmStats,
+}
+
+impl SnmpAgent {
+    pub async fn new(
+        config: SnmpConfig,
+        router: Arc<RwLock<Router>>,
+        routing_table: Arc<RwLock<RoutingTable>>,
+        metrics: Metrics,
+    ) -> Self {
+        let router_id = router.read().await.router_id().to_string();
+        let engine_id = engine_id_for(&router_id);
+        let engine_boots = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|now| now.as_secs().saturating_sub(ENGINE_BOOTS_EPOCH))
+            .unwrap_or(1)
+            .clamp(1, i32::MAX as u64) as i64;
+
+        let users = config
+            .v3_users
+            .iter()
+            .map(|user| {
+                let key = password_to_key(user.auth_protocol, user.auth_password.as_bytes());
+                LocalizedUser {
+                    name: user.username.as_bytes().to_vec(),
+                    protocol: user.auth_protocol,
+                    key: localize_key(user.auth_protocol, &key, &engine_id),
+                }
+            })
+            .collect();
+
+        Self {
+            config,
+            router,
+            routing_table,
+            metrics,
+            engine_id,
+            engine_boots,
+            engine_start: Instant::now(),
+            users,
+            usm_stats: UsmStats::default(),
+        }
+    }
+
+    pub fn engine_id(&self) -> &[u8] {
+        &self.engine_id
+    }
+
+    /// Bind the configured UDP endpoint
+    pub async fn bind(&self) -> RustRouteResult<UdpSocket> {
+        let addr = listen::socket_addr(&self.config.bind_address, self.config.port)
+            .map_err(|e| RustRouteError::ConfigError(format!("snmp.bind_address: {}", e)))?;
+        listen::udp(addr).map_err(|e| {
+            RustRouteError::NetworkError(format!("Failed to bind SNMP agent on {}: {}", addr, e))
+        })
+    }
+
+    /// Answer requests on `socket` until the task is aborted
+    pub async fn serve(self: Arc<Self>, socket: Arc<UdpSocket>, heartbeat: Heartbeat) {
+        let mut buffer = vec![0u8; 65_535];
+        loop {
+            heartbeat.beat();
+            let received = match tokio::time::timeout(
+                RECEIVE_HEARTBEAT,
+                socket.recv_from(&mut buffer),
+            )
+            .await
+            {
+                Ok(result) => result,
+                Err(_) => continue,
+            };
+
+            let (len, peer) = match received {
+                Ok(received) => received,
+                Err(err) => {
+                    warn!("SNMP receive error: {}", err);
+                    continue;
+                }
+            };
+
+            if let Some(reply) = self.handle(&buffer[..len]).await {
+                if let Err(err) = socket.send_to(&reply, peer).await {
+                    warn!("Failed to send SNMP response to {}: {}", peer, err);
+                }
+            }
+        }
+    }
+
+    /// Process one request datagram, returning the reply to send if any
+    pub async fn handle(&self, packet: &[u8]) -> Option<Vec<u8>> {
+        let message = match Message::decode(packet) {
+            Ok(message) => message,
+            Err(err) => {
+                debug!("Dropping malformed SNMP packet: {}", err);
+                return None;
+            }
+        };
+
+        match message {
+            Message::Community {
+                version,
+                community,
+                pdu,
+            } => {
+                if !(0..=1).contains(&version)
+                    || self.config.community.is_empty()
+                    || community != self.config.community.as_bytes()
+                {
+                    debug!("Dropping SNMP request with unknown community");
+                    return None;
+                }
+                let mib = self.collect().await;
+                let reply = self.process(&pdu, &mib, version == 0)?;
+                Some(encode_community(version, &community, &reply))
+            }
+            Message::V3 {
+                header,
+                usm,
+                auth_params,
+                auth_offset,
+                scoped,
+            } => {
+                self.handle_v3(packet, header, usm, auth_params, auth_offset, scoped)
+                    .await
+            }
+        }
+    }
+
+    async fn handle_v3(
+        &self,
+        packet: &[u8],
+        header: V3Header,
+        usm: UsmParams,
+        auth_params: Vec<u8>,
+        auth_offset: usize,
+        scoped: Option<(Vec<u8>, Vec<u8>, Pdu)>,
+    ) -> Option<Vec<u8>> {
+        let request_id = scoped
+            .as_ref()
+            .map(|(_, _, pdu)| pdu.request_id)
+            .unwrap_or(0);
+        let report = |which: u32, user: Option<&LocalizedUser>| {
+            if header.flags & FLAG_REPORTABLE == 0 {
+                return None;
+            }
+            Some(self.report(header, request_id, which, user))
+        };
+
+        if usm.engine_id != self.engine_id {
+            return report(USM_UNKNOWN_ENGINE_IDS, None);
+        }
+
+        let Some(user) = self.users.iter().find(|user| user.name == usm.username) else {
+            return report(USM_UNKNOWN_USER_NAMES, None);
+        };
+
+        if header.flags & FLAG_AUTH == 0 || header.flags & FLAG_PRIV != 0 {
+            return report(USM_UNSUPPORTED_SEC_LEVELS, None);
+        }
+
+        let mut unsigned = packet.to_vec();
+        if auth_params.len() != AUTH_PARAMS_LEN {
+            return report(USM_WRONG_DIGESTS, None);
+        }
+        unsigned[auth_offset..auth_offset + AUTH_PARAMS_LEN].fill(0);
+        if !verify_hmac_96(user.protocol, &user.key, &unsigned, &auth_params) {
+            return report(USM_WRONG_DIGESTS, None);
+        }
+
+        let engine_time = self.engine_time();
+        if usm.engine_boots != self.engine_boots
+            || usm.engine_time.abs_diff(engine_time) > TIME_WINDOW_SECONDS
+        {
+            return report(USM_NOT_IN_TIME_WINDOWS, Some(user));
+        }
+
+        let (_, context_name, pdu) = scoped?;
+        let mib = self.collect().await;
+        let reply = self.process(&pdu, &mib, false)?;
+        Some(encode_v3(
+            V3Header {
+                flags: FLAG_AUTH,
+                ..header
+            },
+            &self.usm_params(&user.name),
+            Some((user.protocol, &user.key)),
+            &context_name,
+            &reply,
+        ))
+    }
+
+    fn report(
+        &self,
+        header: V3Header,
+        request_id: i64,
+        which: u32,
+        user: Option<&LocalizedUser>,
+    ) -> Vec<u8> {
+        let count = self
+            .usm_stats
+            .counter(which)
+            .fetch_add(1, Ordering::Relaxed)
+            + 1;
+        let mut oid = OID_USM_STATS.to_vec();
+        oid.extend([which, 0]);
+
+        let pdu = Pdu {
+            kind: PDU_REPORT,
+            request_id,
+            error_status: 0,
+            error_index: 0,
+            varbinds: vec![(oid, Value::Counter32(count))],
+        };
+        let username = user.map(|user| user.name.clone()).unwrap_or_default();
+        let flags = if user.is_some() { FLAG_AUTH } else { 0 };
+        encode_v3(
+            V3Header { flags, ..header },
+            &self.usm_params(&username),
+            user.map(|user| (user.protocol, user.key.as_slice())),
+            &[],
+            &pdu,
+        )
+    }
+
+    fn usm_params(&self, username: &[u8]) -> UsmParams {
+        UsmParams {
+            engine_id: self.engine_id.clone(),
+            engine_boots: self.engine_boots,
+            engine_time: self.engine_time(),
+            username: username.to_vec(),
+        }
+    }
+
+    fn engine_time(&self) -> i64 {
+        self.engine_start.elapsed().as_secs().min(i32::MAX as u64) as i64
+    }
+
+    fn process(&self, pdu: &Pdu, mib: &BTreeMap<Oid, Value>, v1: bool) -> Option<Pdu> {
+        match pdu.kind {
+            PDU_GET => {
+                let mut varbinds = Vec::with_capacity(pdu.varbinds.len());
+                for (index, (oid, _)) in pdu.varbinds.iter().enumerate() {
+                    match mib.get(oid) {
+                        Some(value) => varbinds.push((oid.clone(), value.clone())),
+                        None if v1 => return Some(pdu.error(ERR_NO_SUCH_NAME, index + 1)),
+                        None => varbinds.push((oid.clone(), missing(mib, oid))),
+                    }
+                }
+                Some(pdu.reply(varbinds))
+            }
+            PDU_GET_NEXT => {
+                let mut varbinds = Vec::with_capacity(pdu.varbinds.len());
+                for (index, (oid, _)) in pdu.varbinds.iter().enumerate() {
+                    match next(mib, oid) {
+                        Some(found) => varbinds.push(found),
+                        None if v1 => return Some(pdu.error(ERR_NO_SUCH_NAME, index + 1)),
+                        None => varbinds.push((oid.clone(), Value::EndOfMibView)),
+                    }
+                }
+                Some(pdu.reply(varbinds))
+            }
+            PDU_GET_BULK if !v1 => {
+                let non_repeaters = pdu.error_status.clamp(0, pdu.varbinds.len() as i64) as usize;
+                let max_repetitions = pdu.error_index.max(0) as usize;
+                let (singles, repeaters) = pdu.varbinds.split_at(non_repeaters);
+
+                let mut varbinds = Vec::new();
+                for (oid, _) in singles {
+                    varbinds
+                        .push(next(mib, oid).unwrap_or_else(|| (oid.clone(), Value::EndOfMibView)));
+                }
+
+                let mut cursors: Vec<Oid> = repeaters.iter().map(|(oid, _)| oid.clone()).collect();
+                'rows: for _ in 0..max_repetitions {
+                    let mut exhausted = true;
+                    for cursor in cursors.iter_mut() {
+                        if varbinds.len() >= MAX_BULK_VARBINDS {
+                            break 'rows;
+                        }
+                        match next(mib, cursor) {
+                            Some((oid, value)) => {
+                                exhausted = false;
+                                *cursor = oid.clone();
+                                varbinds.push((oid, value));
+                            }
+                            None => varbinds.push((cursor.clone(), Value::EndOfMibView)),
+                        }
+                    }
+                    if exhausted {
+                        break;
+                    }
+                }
+                Some(pdu.reply(varbinds))
+            }
+            PDU_SET if v1 => Some(pdu.error(ERR_NO_SUCH_NAME, 1)),
+            PDU_SET => Some(pdu.error(ERR_NO_ACCESS, 1)),
+            PDU_GET_BULK => Some(pdu.error(ERR_GEN_ERR, 0)),
+            _ => None,
+        }
+    }
+
+    /// Build the MIB view from the current router state
+    async fn collect(&self) -> BTreeMap<Oid, Value> {
+        let (config, running, uptime) = {
+            let router = self.router.read().await;
+            (
+                router.config_snapshot(),
+                router.network_interfaces(),
+                router.uptime(),
+            )
+        };
+        let neighbors: Vec<(Ipv4Addr, Instant)> = {
+            let router = self.router.read().await;
+            let neighbors = router.neighbors();
+            let guard = neighbors.read().await;
+            guard
+                .values()
+                .filter_map(|info| match info.address {
+                    IpAddr::V4(addr) => Some((addr, info.last_seen)),
+                    IpAddr::V6(_) => None,
+                })
+                .collect()
+        };
+        let mut routes: Vec<_> = self
+            .routing_table
+            .read()
+            .await
+            .get_all_routes()
+            .into_iter()
+            .cloned()
+            .collect();
+        let metrics = self.metrics.snapshot(neighbors.len(), routes.len());
+        let uptime_ticks = centiseconds(uptime.as_millis());
+
+        let mut mib = BTreeMap::new();
+        let mut put = |base: &[u32], suffix: &[u32], value: Value| {
+            let mut oid = base.to_vec();
+            oid.extend_from_slice(suffix);
+            mib.entry(oid).or_insert(value);
+        };
+
+        // system
+        put(
+            OID_SYSTEM,
+            &[1, 0],
+            octets(&format!(
+                "RustRoute {} RIP router",
+                env!("CARGO_PKG_VERSION")
+            )),
+        );
+        put(OID_SYSTEM, &[2, 0], Value::Oid(vec![0, 0]));
+        put(OID_SYSTEM, &[3, 0], Value::TimeTicks(uptime_ticks));
+        put(OID_SYSTEM, &[5, 0], octets(&config.router_id));
+
+        // interfaces
+        put(
+            OID_INTERFACES,
+            &[1, 0],
+            Value::Integer(config.interfaces.len() as i64),
+        );
+        let mut if_index = std::collections::HashMap::new();
+        let mut rip_interfaces = Vec::new();
+        for (position, iface) in config.interfaces.iter().enumerate() {
+            let index = position as u32 + 1;
+            if_index.insert(iface.name.clone(), index);
+            let live = running.iter().find(|r| r.config.name == iface.name);
+            let oper_up = live.is_some_and(|live| live.is_up());
+            let if_type = if iface.name.starts_with("lo") { 24 } else { 6 };
+            let mtu = live.map(|live| live.config.mtu).unwrap_or(1500);
+
+            let table = [1, 3, 6, 1, 2, 1, 2, 2, 1];
+            put(&table, &[1, index], Value::Integer(index as i64));
+            put(&table, &[2, index], octets(&iface.name));
+            put(&table, &[3, index], Value::Integer(if_type));
+            put(&table, &[4, index], Value::Integer(mtu as i64));
+            put(&table, &[7, index], Value::Integer(up_down(iface.enabled)));
+            put(&table, &[8, index], Value::Integer(up_down(oper_up)));
+
+            if let Ok(ipnet::IpNet::V4(net)) = iface.address.trim().parse::<ipnet::IpNet>() {
+                rip_interfaces.push((iface, net.addr(), live));
+            }
+        }
+
+        // ipRouteTable, longest prefix first so it wins duplicate destinations
+        routes.sort_by_key(|route| std::cmp::Reverse(route.prefix_length()));
+        for route in &routes {
+            let dest = route.destination.octets().map(u32::from);
+            let route_type = if route.metric >= config.rip.infinity_metric {
+                2
+            } else if route.source == RouteSource::Direct {
+                3
+            } else {
+                4
+            };
+            let proto = match route.source {
+                RouteSource::Direct => 2,
+                RouteSource::Static => 3,
+                RouteSource::Dynamic => 8,
+                RouteSource::Aggregate => 2,
+                RouteSource::Redistributed(_) => 1,
+            };
+            let index = if_index.get(&route.interface).copied().unwrap_or(0);
+
+            let entry = OID_IP_ROUTE_ENTRY;
+            put(
+                entry,
+                &[&[1], &dest[..]].concat(),
+                Value::IpAddress(route.destination),
+            );
+            put(
+                entry,
+                &[&[2], &dest[..]].concat(),
+                Value::Integer(index as i64),
+            );
+            put(
+                entry,
+                &[&[3], &dest[..]].concat(),
+                Value::Integer(route.metric as i64),
+            );
+            put(
+                entry,
+                &[&[7], &dest[..]].concat(),
+                Value::IpAddress(route.next_hop),
+            );
+            put(
+                entry,
+                &[&[8], &dest[..]].concat(),
+                Value::Integer(route_type),
+            );
+            put(entry, &[&[9], &dest[..]].concat(), Value::Integer(proto));
+            put(
+                entry,
+                &[&[10], &dest[..]].concat(),
+                Value::Integer(route.age_seconds() as i64),
+            );
+            put(
+                entry,
+                &[&[11], &dest[..]].concat(),
+                Value::IpAddress(route.subnet_mask),
+            );
+        }
+
+        // rip2GlobalGroup
+        put(
+            OID_RIP2,
+            &[1, 1, 0],
+            Value::Counter32(metrics.route_changes as u32),
+        );
+        put(
+            OID_RIP2,
+            &[1, 2, 0],
+            Value::Counter32(metrics.queries_answered as u32),
+        );
+
+        for (iface, addr, live) in &rip_interfaces {
+            let ip = addr.octets().map(u32::from);
+            let stat = |column: u32| [&[2, 1, column], &ip[..]].concat();
+            put(OID_RIP2, &stat(1), Value::IpAddress(*addr));
+            put(
+                OID_RIP2,
+                &stat(2),
+                Value::Counter32(live.map(|l| l.bad_packets()).unwrap_or(0) as u32),
+            );
+            put(OID_RIP2, &stat(3), Value::Counter32(0));
+            put(
+                OID_RIP2,
+                &stat(4),
+                Value::Counter32(live.map(|l| l.updates_sent()).unwrap_or(0) as u32),
+            );
+            put(OID_RIP2, &stat(5), Value::Integer(1));
+
+            let send = if iface.enabled && !iface.passive {
+                4
+            } else {
+                1
+            };
+            let receive = if iface.enabled { 2 } else { 4 };
+            let conf = |column: u32| [&[3, 1, column], &ip[..]].concat();
+            put(OID_RIP2, &conf(1), Value::IpAddress(*addr));
+            put(OID_RIP2, &conf(2), Value::OctetString(vec![0, 0]));
+            put(OID_RIP2, &conf(3), Value::Integer(1));
+            put(OID_RIP2, &conf(4), Value::OctetString(Vec::new()));
+            put(OID_RIP2, &conf(5), Value::Integer(send));
+            put(OID_RIP2, &conf(6), Value::Integer(receive));
+            put(OID_RIP2, &conf(7), Value::Integer(0));
+            put(OID_RIP2, &conf(8), Value::Integer(1));
+            put(OID_RIP2, &conf(9), Value::IpAddress(*addr));
+        }
+
+        for (addr, last_seen) in &neighbors {
+            let ip = addr.octets().map(u32::from);
+            let peer = |column: u32| [&[4, 1, column], &ip[..], &[0, 0]].concat();
+            let last_update = uptime.saturating_sub(last_seen.elapsed());
+            put(OID_RIP2, &peer(1), Value::IpAddress(*addr));
+            put(OID_RIP2, &peer(2), Value::OctetString(vec![0, 0]));
+            put(
+                OID_RIP2,
+                &peer(3),
+                Value::TimeTicks(centiseconds(last_update.as_millis())),
+            );
+            put(OID_RIP2, &peer(4), Value::Integer(2));
+            put(OID_RIP2, &peer(5), Value::Counter32(0));
+            put(OID_RIP2, &peer(6), Value::Counter32(0));
+        }
+
+        // usmStats
+        for which in [
+            USM_UNSUPPORTED_SEC_LEVELS,
+            USM_NOT_IN_TIME_WINDOWS,
+            USM_UNKNOWN_USER_NAMES,
+            USM_UNKNOWN_ENGINE_IDS,
+            USM_WRONG_DIGESTS,
+        ] {
+            let count = self.usm_stats.counter(which).load(Ordering::Relaxed);
+            put(OID_USM_STATS, &[which, 0], Value::Counter32(count));
+        }
+
+        mib
+    }
+}
+
+impl std::fmt::Debug for SnmpAgent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SnmpAgent")
+            .field("bind_address", &self.config.bind_address)
+            .field("port", &self.config.port)
+            .finish_non_exhaustive()
+    }
+}
+
+/// RFC 3411 engine ID in the administratively assigned text format
+fn engine_id_for(router_id: &str) -> Vec<u8> {
+    let text = if router_id.is_empty() {
+        "rust-route"
+    } else {
+        router_id
+    };
+    let mut id = vec![0x80, 0x00, 0x00, 0x00, 0x04];
+    id.extend(text.bytes().take(27));
+    id
+}
+
+fn next(mib: &BTreeMap<Oid, Value>, oid: &Oid) -> Option<(Oid, Value)> {
+    mib.range::<Oid, _>((Bound::Excluded(oid), Bound::Unbounded))
+        .next()
+        .map(|(oid, value)| (oid.clone(), value.clone()))
+}
+
+fn missing(mib: &BTreeMap<Oid, Value>, oid: &Oid) -> Value {
+    let parent = &oid[..oid.len().saturating_sub(1)];
+    if !parent.is_empty() && mib.keys().any(|key| key.starts_with(parent)) {
+        Value::NoSuchInstance
+    } else {
+        Value::NoSuchObject
+    }
+}
+
+fn octets(text: &str) -> Value {
+    Value::OctetString(text.as_bytes().to_vec())
+}
+
+fn up_down(up: bool) -> i64 {
+    if up {
+        1
+    } else {
+        2
+    }
+}
+
+fn centiseconds(millis: u128) -> u32 {
+    (millis / 10).min(u32::MAX as u128) as u32
+}
+
+fn ber_error(message: &str) -> RustRouteError {
+    RustRouteError::ProtocolError(format!("Malformed SNMP message: {}", message))
+}
+
+fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(content.len() + 6);
+    out.push(tag);
+    let len = content.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes = len.to_be_bytes();
+        let skip = bytes.iter().take_while(|b| **b == 0).count();
+        out.push(0x80 | (bytes.len() - skip) as u8);
+        out.extend_from_slice(&bytes[skip..]);
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+fn header_len(encoded: &[u8], content_len: usize) -> usize {
+    encoded.len() - content_len
+}
+
+fn encode_integer(value: i64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let mut start = 0;
+    while start < 7 {
+        let redundant = (bytes[start] == 0x00 && bytes[start + 1] & 0x80 == 0)
+            || (bytes[start] == 0xFF && bytes[start + 1] & 0x80 != 0);
+        if !redundant {
+            break;
+        }
+        start += 1;
+    }
+    bytes[start..].to_vec()
+}
+
+fn encode_unsigned(value: u64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let skip = bytes.iter().take(7).take_while(|b| **b == 0).count();
+    let mut out = Vec::with_capacity(9);
+    if bytes[skip] & 0x80 != 0 {
+        out.push(0);
+    }
+    out.extend_from_slice(&bytes[skip..]);
+    out
+}
+
+fn decode_integer(content: &[u8]) -> RustRouteResult<i64> {
+    if content.is_empty() || content.len() > 8 {
+        return Err(ber_error("bad INTEGER length"));
+    }
+    let mut value: i64 = if content[0] & 0x80 != 0 { -1 } else { 0 };
+    for byte in content {
+        value = (value << 8) | *byte as i64;
+    }
+    Ok(value)
+}
+
+fn decode_unsigned(content: &[u8]) -> RustRouteResult<u64> {
+    if content.is_empty() || content.len() > 9 {
+        return Err(ber_error("bad unsigned length"));
+    }
+    Ok(content
+        .iter()
+        .fold(0u64, |value, byte| (value << 8) | *byte as u64))
+}
+
+fn encode_oid(oid: &[u32]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let (first, rest) = match oid {
+        [a, b, rest @ ..] => (a * 40 + b, rest),
+        [a] => (a * 40, &[][..]),
+        [] => (0, &[][..]),
+    };
+
+    for sub in std::iter::once(first).chain(rest.iter().copied()) {
+        let mut chunk = [0u8; 5];
+        let mut len = 0;
+        let mut value = sub;
+        loop {
+            chunk[4 - len] = (value & 0x7F) as u8 | if len == 0 { 0 } else { 0x80 };
+            len += 1;
+            value >>= 7;
+            if value == 0 {
+                break;
+            }
+        }
+        out.extend_from_slice(&chunk[5 - len..]);
+    }
+    out
+}
+
+fn decode_oid(content: &[u8]) -> RustRouteResult<Oid> {
+    let mut subs = Vec::new();
+    let mut value: u32 = 0;
+    for byte in content {
+        value = value
+            .checked_mul(128)
+            .ok_or_else(|| ber_error("OID arc overflow"))?
+            | (byte & 0x7F) as u32;
+        if byte & 0x80 == 0 {
+            subs.push(value);
+            value = 0;
+        }
+    }
+    if content.last().is_some_and(|byte| byte & 0x80 != 0) {
+        return Err(ber_error("truncated OID"));
+    }
+
+    let Some(first) = subs.first().copied() else {
+        return Ok(Vec::new());
+    };
+    let mut oid = vec![(first / 40).min(2), first - (first / 40).min(2) * 40];
+    oid.extend_from_slice(&subs[1..]);
+    Ok(oid)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn read_any(&mut self) -> RustRouteResult<(u8, &'a [u8])> {
+        let tag = *self
+            .data
+            .get(self.pos)
+            .ok_or_else(|| ber_error("unexpected end of data"))?;
+        let first = *self
+            .data
+            .get(self.pos + 1)
+            .ok_or_else(|| ber_error("missing length"))?;
+        let mut cursor = self.pos + 2;
+
+        let len = if first & 0x80 == 0 {
+            first as usize
+        } else {
+            let count = (first & 0x7F) as usize;
+            if count == 0 || count > 4 {
+                return Err(ber_error("unsupported length encoding"));
+            }
+            let bytes = self
+                .data
+                .get(cursor..cursor + count)
+                .ok_or_else(|| ber_error("truncated length"))?;
+            cursor += count;
+            bytes.iter().fold(0usize, |len, b| (len << 8) | *b as usize)
+        };
+
+        let content = self
+            .data
+            .get(cursor..cursor + len)
+            .ok_or_else(|| ber_error("truncated value"))?;
+        self.pos = cursor + len;
+        Ok((tag, content))
+    }
+
+    fn read(&mut self, expected: u8) -> RustRouteResult<&'a [u8]> {
+        let (tag, content) = self.read_any()?;
+        if tag != expected {
+            return Err(ber_error(&format!(
+                "expected tag 0x{:02x}, found 0x{:02x}",
+                expected, tag
+            )));
+        }
+        Ok(content)
+    }
+
+    fn read_integer(&mut self) -> RustRouteResult<i64> {
+        decode_integer(self.read(TAG_INTEGER)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_manager::RouterConfig;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    async fn agent(config: SnmpConfig) -> SnmpAgent {
+        let mut router_config = RouterConfig::default();
+        router_config.rip.enabled = false;
+        let routing_table = Arc::new(RwLock::new(RoutingTable::new()));
+        let metrics = Metrics::new();
+        let router = Router::new(router_config, Arc::clone(&routing_table), metrics.clone())
+            .await
+            .expect("router constructed");
+
+        SnmpAgent::new(
+            config,
+            Arc::new(RwLock::new(router)),
+            routing_table,
+            metrics,
+        )
+        .await
+    }
+
+    fn request(kind: u8, oids: &[&[u32]]) -> Pdu {
+        Pdu {
+            kind,
+            request_id: 42,
+            error_status: 0,
+            error_index: 0,
+            varbinds: oids.iter().map(|oid| (oid.to_vec(), Value::Null)).collect(),
+        }
+    }
+
+    #[test]
+    fn ber_encoding_matches_known_bytes() {
+        assert_eq!(
+            hex(&tlv(TAG_OID, &encode_oid(&[1, 3, 6, 1, 2, 1, 1, 1, 0]))),
+            "06082b06010201010100"
+        );
+        assert_eq!(hex(&encode_integer(-129)), "ff7f");
+        assert_eq!(hex(&encode_unsigned(0xFFFF_FFFF)), "00ffffffff");
+        assert_eq!(
+            decode_oid(&encode_oid(&[1, 3, 6, 1, 4, 1, 200_000])).unwrap(),
+            vec![1, 3, 6, 1, 4, 1, 200_000]
+        );
+    }
+
+    #[test]
+    fn key_localization_matches_rfc3414_vectors() {
+        let engine_id = [0u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+
+        let ku = password_to_key(SnmpAuthProtocol::Md5, b"maplesyrup");
+        assert_eq!(hex(&ku), "9faf3283884e92834ebc9847d8edd963");
+        assert_eq!(
+            hex(&localize_key(SnmpAuthProtocol::Md5, &ku, &engine_id)),
+            "526f5eed9fcce26f8964c2930787d82b"
+        );
+
+        let ku = password_to_key(SnmpAuthProtocol::Sha, b"maplesyrup");
+        assert_eq!(hex(&ku), "9fb5cc0381497b3793528939ff788d5d79145211");
+        assert_eq!(
+            hex(&localize_key(SnmpAuthProtocol::Sha, &ku, &engine_id)),
+            "6695febc9288e36282235fc7151f128497b38f3f"
+        );
+    }
+
+    #[tokio::test]
+    async fn v2c_get_and_walk_rip2_group() {
+        let agent = agent(SnmpConfig::default()).await;
+
+        let get = request(
+            PDU_GET,
+            &[&[1, 3, 6, 1, 2, 1, 1, 5, 0], &[1, 3, 6, 1, 2, 1, 1, 9, 0]],
+        );
+        let reply = agent
+            .handle(&encode_community(1, b"public", &get))
+            .await
+            .unwrap();
+        let Message::Community { pdu, .. } = Message::decode(&reply).unwrap() else {
+            panic!("expected community reply");
+        };
+        assert_eq!(pdu.kind, PDU_RESPONSE);
+        assert_eq!(pdu.varbinds[0].1, octets("192.168.1.1"));
+        assert_eq!(pdu.varbinds[1].1, Value::NoSuchObject);
+
+        let walk = request(PDU_GET_NEXT, &[OID_RIP2]);
+        let reply = agent
+            .handle(&encode_community(1, b"public", &walk))
+            .await
+            .unwrap();
+        let Message::Community { pdu, .. } = Message::decode(&reply).unwrap() else {
+            panic!("expected community reply");
+        };
+        assert_eq!(pdu.varbinds[0].0, vec![1, 3, 6, 1, 2, 1, 23, 1, 1, 0]);
+
+        let bulk = Pdu {
+            error_index: 5,
+            ..request(PDU_GET_BULK, &[&[1, 3, 6, 1, 2, 1, 23, 3, 1, 5]])
+        };
+        let reply = agent
+            .handle(&encode_community(1, b"public", &bulk))
+            .await
+            .unwrap();
+        let Message::Community { pdu, .. } = Message::decode(&reply).unwrap() else {
+            panic!("expected community reply");
+        };
+        // rip2IfConfSend for eth0 is ripVersion2
+        assert_eq!(
+            pdu.varbinds[0].0,
+            vec![1, 3, 6, 1, 2, 1, 23, 3, 1, 5, 192, 168, 1, 1]
+        );
+        assert_eq!(pdu.varbinds[0].1, Value::Integer(4));
+        assert_eq!(pdu.varbinds.len(), 5);
+
+        assert!(agent
+            .handle(&encode_community(1, b"private", &get))
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn v3_discovery_then_authenticated_get() {
+        let config = SnmpConfig {
+            v3_users: vec![SnmpV3User {
+                username: "monitor".to_string(),
+                auth_protocol: SnmpAuthProtocol::Sha,
+                auth_password: "maplesyrup".to_string(),
+            }],
+            ..SnmpConfig::default()
+        };
+        let agent = agent(config).await;
+        let get = request(PDU_GET, &[&[1, 3, 6, 1, 2, 1, 1, 5, 0]]);
+
+        let header = V3Header {
+            msg_id: 1,
+            max_size: 65_507,
+            flags: FLAG_REPORTABLE,
+        };
+        let discovery = encode_v3(header, &UsmParams::default(), None, &[], &get);
+        let reply = agent.handle(&discovery).await.unwrap();
+        let Message::V3 { usm, scoped, .. } = Message::decode(&reply).unwrap() else {
+            panic!("expected v3 reply");
+        };
+        let (_, _, report) = scoped.unwrap();
+        assert_eq!(report.kind, PDU_REPORT);
+        assert_eq!(
+            report.varbinds[0].0,
+            [OID_USM_STATS, &[USM_UNKNOWN_ENGINE_IDS, 0]].concat()
+        );
+        assert_eq!(usm.engine_id, agent.engine_id());
+
+        let key = localize_key(
+            SnmpAuthProtocol::Sha,
+            &password_to_key(SnmpAuthProtocol::Sha, b"maplesyrup"),
+            &usm.engine_id,
+        );
+        let params = UsmParams {
+            username: b"monitor".to_vec(),
+            ..usm.clone()
+        };
+        let header = V3Header {
+            msg_id: 2,
+            flags: FLAG_AUTH | FLAG_REPORTABLE,
+            ..header
+        };
+        let signed = encode_v3(
+            header,
+            &params,
+            Some((SnmpAuthProtocol::Sha, &key)),
+            &[],
+            &get,
+        );
+        let reply = agent.handle(&signed).await.unwrap();
+        let Message::V3 { header, scoped, .. } = Message::decode(&reply).unwrap() else {
+            panic!("expected v3 reply");
+        };
+        assert_eq!(header.flags, FLAG_AUTH);
+        let (_, _, pdu) = scoped.unwrap();
+        assert_eq!(pdu.kind, PDU_RESPONSE);
+        assert_eq!(pdu.varbinds[0].1, octets("192.168.1.1"));
+
+        let Message::V3 { auth_offset, .. } = Message::decode(&signed).unwrap() else {
+            panic!("expected v3 request");
+        };
+        let mut tampered = signed.clone();
+        tampered[auth_offset] ^= 0x01;
+        let reply = agent.handle(&tampered).await.unwrap();
+        let Message::V3 { scoped, .. } = Message::decode(&reply).unwrap() else {
+            panic!("expected v3 reply");
+        };
+        let (_, _, report) = scoped.unwrap();
+        assert_eq!(
+            report.varbinds[0].0,
+            [OID_USM_STATS, &[USM_WRONG_DIGESTS, 0]].concat()
+        );
+    }
+}
@@ -0,0 +1,431 @@
+//! Persistence layer for the router's durable state.
+//!
+//! User accounts and login tokens, the activity feed and the metrics history
+//! all go through a [`Storage`]: a key-value store for state that is written
+//! as a whole, and named append-only logs of JSON lines for records.
+//! `storage.backend` selects plain files under `storage.path` (the default),
+//! a single SQLite database in that directory (with the `sqlite` feature) or
+//! memory only, as in ephemeral mode.
+//!
+//! Keys and log names are relative paths such as `auth/users.json`; with the
+//! file backend an absolute name is used as is, so older configurations that
+//! point the activity log at a full path keep working.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::{RustRouteError, RustRouteResult};
+
+/// Where durable state is kept
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// One file per key or log under `storage.path`
+    #[default]
+    File,
+    /// `rust-route.db` under `storage.path`; needs the `sqlite` feature
+    Sqlite,
+    /// Nothing survives a restart
+    Memory,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StorageConfig {
+    pub backend: StorageBackend,
+    /// Directory holding the files or the database
+    pub path: String,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            backend: StorageBackend::default(),
+            path: "/var/lib/rust-route".to_string(),
+        }
+    }
+}
+
+/// Name of the database file of the SQLite backend
+pub const SQLITE_FILE: &str = "rust-route.db";
+
+/// Key-value entries and append-only logs of text records
+pub trait Storage: Send + Sync + fmt::Debug {
+    /// Value stored under `key`, if any
+    fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>>;
+    /// Store `value` under `key`, replacing what was there
+    fn put(&self, key: &str, value: &[u8]) -> io::Result<()>;
+    fn delete(&self, key: &str) -> io::Result<()>;
+    /// Add `record`, a single line, to the end of `log`
+    fn append(&self, log: &str, record: &str) -> io::Result<()>;
+    /// Records of `log`, oldest first; empty if it does not exist
+    fn read_log(&self, log: &str) -> io::Result<Vec<String>>;
+    /// Replace the records of `log` with `records`
+    fn rewrite_log(&self, log: &str, records: &[String]) -> io::Result<()>;
+}
+
+impl dyn Storage + '_ {
+    /// Value under `key` read as JSON
+    pub fn get_json<T: DeserializeOwned>(&self, key: &str) -> io::Result<Option<T>> {
+        match self.get(key)? {
+            Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Store `value` under `key` as JSON
+    pub fn put_json<T: Serialize + ?Sized>(&self, key: &str, value: &T) -> io::Result<()> {
+        self.put(key, &serde_json::to_vec_pretty(value)?)
+    }
+}
+
+/// Open the storage described by `config`
+pub fn open(config: &StorageConfig) -> RustRouteResult<Arc<dyn Storage>> {
+    match config.backend {
+        StorageBackend::File => Ok(Arc::new(FileStorage::new(&config.path))),
+        StorageBackend::Memory => Ok(Arc::new(MemoryStorage::default())),
+        #[cfg(feature = "sqlite")]
+        StorageBackend::Sqlite => {
+            let path = Path::new(&config.path).join(SQLITE_FILE);
+            let storage = SqliteStorage::open(&path).map_err(|err| {
+                RustRouteError::ConfigError(format!("Failed to open {}: {}", path.display(), err))
+            })?;
+            Ok(Arc::new(storage))
+        }
+        #[cfg(not(feature = "sqlite"))]
+        StorageBackend::Sqlite => Err(RustRouteError::ConfigError(
+            "storage.backend is sqlite but rust-route was built without the sqlite feature"
+                .to_string(),
+        )),
+    }
+}
+
+/// Keys and logs as files under a directory
+#[derive(Debug, Clone)]
+pub struct FileStorage {
+    root: PathBuf,
+}
+
+impl FileStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.root.join(name)
+    }
+}
+
+fn create_parent(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => fs::create_dir_all(parent),
+        _ => Ok(()),
+    }
+}
+
+/// Write `content` to a temporary file next to `path` and move it in place,
+/// so a crash leaves either the old or the new content; `private` files are
+/// readable by the owner only
+fn replace_file(path: &Path, content: &[u8], private: bool) -> io::Result<()> {
+    create_parent(path)?;
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    let temp = path.with_file_name(name);
+    fs::write(&temp, content)?;
+    #[cfg(unix)]
+    if private {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&temp, fs::Permissions::from_mode(0o600))?;
+    }
+    #[cfg(not(unix))]
+    let _ = private;
+    fs::rename(&temp, path)
+}
+
+impl Storage for FileStorage {
+    fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(self.path(key)) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn put(&self, key: &str, value: &[u8]) -> io::Result<()> {
+        // Entries include password hashes and login tokens
+        replace_file(&self.path(key), value, true)
+    }
+
+    fn delete(&self, key: &str) -> io::Result<()> {
+        match fs::remove_file(self.path(key)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    fn append(&self, log: &str, record: &str) -> io::Result<()> {
+        let path = self.path(log);
+        create_parent(&path)?;
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{}", record)
+    }
+
+    fn read_log(&self, log: &str) -> io::Result<Vec<String>> {
+        let content = match fs::read_to_string(self.path(log)) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+        Ok(content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+
+    fn rewrite_log(&self, log: &str, records: &[String]) -> io::Result<()> {
+        let mut content = String::new();
+        for record in records {
+            content.push_str(record);
+            content.push('\n');
+        }
+        replace_file(&self.path(log), content.as_bytes(), false)
+    }
+}
+
+/// Storage that only lives as long as the process
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    entries: Mutex<HashMap<String, Vec<u8>>>,
+    logs: Mutex<HashMap<String, Vec<String>>>,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl Storage for MemoryStorage {
+    fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        Ok(lock(&self.entries).get(key).cloned())
+    }
+
+    fn put(&self, key: &str, value: &[u8]) -> io::Result<()> {
+        lock(&self.entries).insert(key.to_string(), value.to_vec());
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> io::Result<()> {
+        lock(&self.entries).remove(key);
+        Ok(())
+    }
+
+    fn append(&self, log: &str, record: &str) -> io::Result<()> {
+        lock(&self.logs)
+            .entry(log.to_string())
+            .or_default()
+            .push(record.to_string());
+        Ok(())
+    }
+
+    fn read_log(&self, log: &str) -> io::Result<Vec<String>> {
+        Ok(lock(&self.logs).get(log).cloned().unwrap_or_default())
+    }
+
+    fn rewrite_log(&self, log: &str, records: &[String]) -> io::Result<()> {
+        lock(&self.logs).insert(log.to_string(), records.to_vec());
+        Ok(())
+    }
+}
+
+/// Keys and logs in one SQLite database
+#[cfg(feature = "sqlite")]
+#[derive(Debug)]
+pub struct SqliteStorage {
+    connection: Mutex<rusqlite::Connection>,
+}
+
+#[cfg(feature = "sqlite")]
+fn sqlite_error(err: rusqlite::Error) -> io::Error {
+    io::Error::other(err)
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteStorage {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        create_parent(path)?;
+        let connection = rusqlite::Connection::open(path).map_err(sqlite_error)?;
+        connection
+            .execute_batch(
+                "PRAGMA journal_mode = WAL;
+                 CREATE TABLE IF NOT EXISTS entries (
+                     key TEXT PRIMARY KEY,
+                     value BLOB NOT NULL
+                 );
+                 CREATE TABLE IF NOT EXISTS records (
+                     id INTEGER PRIMARY KEY AUTOINCREMENT,
+                     log TEXT NOT NULL,
+                     record TEXT NOT NULL
+                 );
+                 CREATE INDEX IF NOT EXISTS records_by_log ON records (log, id);",
+            )
+            .map_err(sqlite_error)?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl Storage for SqliteStorage {
+    fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        use rusqlite::OptionalExtension;
+
+        lock(&self.connection)
+            .query_row("SELECT value FROM entries WHERE key = ?1", [key], |row| {
+                row.get(0)
+            })
+            .optional()
+            .map_err(sqlite_error)
+    }
+
+    fn put(&self, key: &str, value: &[u8]) -> io::Result<()> {
+        lock(&self.connection)
+            .execute(
+                "INSERT INTO entries (key, value) VALUES (?1, ?2)
+                 ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+                rusqlite::params![key, value],
+            )
+            .map(|_| ())
+            .map_err(sqlite_error)
+    }
+
+    fn delete(&self, key: &str) -> io::Result<()> {
+        lock(&self.connection)
+            .execute("DELETE FROM entries WHERE key = ?1", [key])
+            .map(|_| ())
+            .map_err(sqlite_error)
+    }
+
+    fn append(&self, log: &str, record: &str) -> io::Result<()> {
+        lock(&self.connection)
+            .execute(
+                "INSERT INTO records (log, record) VALUES (?1, ?2)",
+                [log, record],
+            )
+            .map(|_| ())
+            .map_err(sqlite_error)
+    }
+
+    fn read_log(&self, log: &str) -> io::Result<Vec<String>> {
+        let connection = lock(&self.connection);
+        let mut statement = connection
+            .prepare("SELECT record FROM records WHERE log = ?1 ORDER BY id")
+            .map_err(sqlite_error)?;
+        let records = statement
+            .query_map([log], |row| row.get(0))
+            .map_err(sqlite_error)?
+            .collect::<Result<Vec<String>, _>>()
+            .map_err(sqlite_error)?;
+        Ok(records)
+    }
+
+    fn rewrite_log(&self, log: &str, records: &[String]) -> io::Result<()> {
+        let mut connection = lock(&self.connection);
+        let transaction = connection.transaction().map_err(sqlite_error)?;
+        transaction
+            .execute("DELETE FROM records WHERE log = ?1", [log])
+            .map_err(sqlite_error)?;
+        for record in records {
+            transaction
+                .execute(
+                    "INSERT INTO records (log, record) VALUES (?1, ?2)",
+                    [log, record.as_str()],
+                )
+                .map_err(sqlite_error)?;
+        }
+        transaction.commit().map_err(sqlite_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every backend has to pass the same checks
+    fn exercise(storage: &dyn Storage) {
+        assert_eq!(storage.get("auth/users.json").unwrap(), None);
+        storage.put_json("auth/users.json", &vec!["admin"]).unwrap();
+        storage
+            .put_json("auth/users.json", &vec!["admin", "ops"])
+            .unwrap();
+        let users: Option<Vec<String>> = storage.get_json("auth/users.json").unwrap();
+        assert_eq!(users.unwrap(), vec!["admin", "ops"]);
+        storage.delete("auth/users.json").unwrap();
+        storage.delete("auth/users.json").unwrap();
+        assert_eq!(storage.get("auth/users.json").unwrap(), None);
+
+        assert!(storage.read_log("activity.jsonl").unwrap().is_empty());
+        for record in ["{\"id\":1}", "{\"id\":2}", "{\"id\":3}"] {
+            storage.append("activity.jsonl", record).unwrap();
+        }
+        storage.append("metrics.jsonl", "{}").unwrap();
+        assert_eq!(storage.read_log("activity.jsonl").unwrap().len(), 3);
+        storage
+            .rewrite_log("activity.jsonl", &["{\"id\":3}".to_string()])
+            .unwrap();
+        storage.append("activity.jsonl", "{\"id\":4}").unwrap();
+        assert_eq!(
+            storage.read_log("activity.jsonl").unwrap(),
+            vec!["{\"id\":3}", "{\"id\":4}"]
+        );
+        assert_eq!(storage.read_log("metrics.jsonl").unwrap(), vec!["{}"]);
+    }
+
+    #[test]
+    fn file_storage() {
+        let dir = tempfile::tempdir().unwrap();
+        exercise(&FileStorage::new(dir.path()));
+        assert!(dir.path().join("activity.jsonl").exists());
+
+        // Absolute names ignore the root
+        let elsewhere = dir.path().join("elsewhere.jsonl");
+        let storage = FileStorage::new("/nonexistent");
+        storage
+            .append(&elsewhere.display().to_string(), "x")
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(elsewhere).unwrap(), "x\n");
+    }
+
+    #[test]
+    fn memory_storage() {
+        exercise(&MemoryStorage::default());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn sqlite_storage() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SQLITE_FILE);
+        exercise(&SqliteStorage::open(&path).unwrap());
+        let reopened = SqliteStorage::open(&path).unwrap();
+        assert_eq!(reopened.read_log("activity.jsonl").unwrap().len(), 2);
+    }
+
+    #[cfg(not(feature = "sqlite"))]
+    #[test]
+    fn sqlite_needs_the_feature() {
+        let config = StorageConfig {
+            backend: StorageBackend::Sqlite,
+            ..StorageConfig::default()
+        };
+        assert!(open(&config).is_err());
+    }
+}
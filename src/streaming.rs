@@ -0,0 +1,486 @@
+//! Event streaming sinks.
+//!
+//! Serializes `WebEvent`s from the event bus and ships them to a Kafka topic
+//! or NATS subject in batches, so data platforms can consume routing changes
+//! without holding an SSE connection to every router. Failed batches are
+//! retried with exponential backoff and stay buffered (up to `buffer_limit`
+//! records) until the broker accepts them.
+
+use chrono::{DateTime, Utc};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::events::{EventBus, WebEvent};
+use crate::router::RECEIVE_HEARTBEAT;
+use crate::watchdog::Heartbeat;
+use crate::{RustRouteError, RustRouteResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamBackend {
+    #[default]
+    Kafka,
+    Nats,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamEventKind {
+    Metrics,
+    Route,
+    Neighbor,
+    Activity,
+    Topology,
+}
+
+impl StreamEventKind {
+    fn of(event: &WebEvent) -> Self {
+        match event {
+            WebEvent::Metrics(_) => StreamEventKind::Metrics,
+            WebEvent::Route(_)
+            | WebEvent::RouteWithdrawn(_)
+            | WebEvent::RouteV6(_)
+            | WebEvent::RouteV6Withdrawn(_)
+            | WebEvent::CountToInfinity(_) => StreamEventKind::Route,
+            WebEvent::Neighbor(_) => StreamEventKind::Neighbor,
+            WebEvent::Activity(_) => StreamEventKind::Activity,
+            WebEvent::TopologyChanged(_) => StreamEventKind::Topology,
+        }
+    }
+}
+
+/// Event streaming configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventStreamConfig {
+    pub enabled: bool,
+    pub backend: StreamBackend,
+    /// Kafka bootstrap brokers (`host:port`) or NATS server URLs
+    pub servers: Vec<String>,
+    /// Kafka topic or NATS subject; `{router_id}` is replaced with the router ID
+    pub topic: String,
+    /// Kafka partition records are produced to
+    #[serde(default)]
+    pub partition: i32,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    pub batch_size: usize,
+    pub flush_interval_ms: u64,
+    pub max_retries: u32,
+    pub retry_backoff_ms: u64,
+    /// Records kept while the broker is unreachable; the oldest are dropped
+    pub buffer_limit: usize,
+    pub events: Vec<StreamEventKind>,
+}
+
+impl Default for EventStreamConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            backend: StreamBackend::Kafka,
+            servers: vec!["localhost:9092".to_string()],
+            topic: "rust-route.events".to_string(),
+            partition: 0,
+            username: None,
+            password: None,
+            batch_size: 100,
+            flush_interval_ms: 1000,
+            max_retries: 5,
+            retry_backoff_ms: 200,
+            buffer_limit: 10_000,
+            events: vec![
+                StreamEventKind::Metrics,
+                StreamEventKind::Route,
+                StreamEventKind::Neighbor,
+                StreamEventKind::Activity,
+                StreamEventKind::Topology,
+            ],
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Envelope<'a> {
+    router_id: &'a str,
+    timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    event: &'a WebEvent,
+}
+
+/// Serialize an event with the router ID and emission time
+pub fn encode_event(router_id: &str, event: &WebEvent) -> Vec<u8> {
+    serde_json::to_vec(&Envelope {
+        router_id,
+        timestamp: Utc::now(),
+        event,
+    })
+    .unwrap_or_default()
+}
+
+type SendFuture<'a> = Pin<Box<dyn Future<Output = RustRouteResult<()>> + Send + 'a>>;
+
+/// Destination for a batch of encoded records
+trait Sink: Send {
+    fn send<'a>(&'a mut self, records: &'a [Vec<u8>]) -> SendFuture<'a>;
+}
+
+struct KafkaSink {
+    servers: Vec<String>,
+    topic: String,
+    partition: i32,
+    key: Vec<u8>,
+    credentials: Option<(String, String)>,
+    client: Option<rskafka::client::partition::PartitionClient>,
+}
+
+impl KafkaSink {
+    async fn connect(&self) -> RustRouteResult<rskafka::client::partition::PartitionClient> {
+        let mut builder = rskafka::client::ClientBuilder::new(self.servers.clone());
+        if let Some((username, password)) = &self.credentials {
+            builder = builder.sasl_config(rskafka::client::SaslConfig::Plain(
+                rskafka::client::Credentials::new(username.clone(), password.clone()),
+            ));
+        }
+        let client = builder.build().await.map_err(kafka_error)?;
+        client
+            .partition_client(
+                self.topic.clone(),
+                self.partition,
+                rskafka::client::partition::UnknownTopicHandling::Error,
+            )
+            .await
+            .map_err(kafka_error)
+    }
+}
+
+impl Sink for KafkaSink {
+    fn send<'a>(&'a mut self, records: &'a [Vec<u8>]) -> SendFuture<'a> {
+        Box::pin(async move {
+            if self.client.is_none() {
+                self.client = Some(self.connect().await?);
+            }
+            let client = self.client.as_ref().expect("client connected above");
+
+            let now = rskafka::chrono::Utc::now();
+            let batch = records
+                .iter()
+                .map(|value| rskafka::record::Record {
+                    key: Some(self.key.clone()),
+                    value: Some(value.clone()),
+                    headers: Default::default(),
+                    timestamp: now,
+                })
+                .collect();
+
+            let result = client
+                .produce(
+                    batch,
+                    rskafka::client::partition::Compression::NoCompression,
+                )
+                .await;
+            if let Err(err) = result {
+                self.client = None;
+                return Err(kafka_error(err));
+            }
+            Ok(())
+        })
+    }
+}
+
+fn kafka_error(err: impl std::fmt::Display) -> RustRouteError {
+    RustRouteError::NetworkError(format!("Kafka: {}", err))
+}
+
+struct NatsSink {
+    servers: Vec<String>,
+    subject: String,
+    credentials: Option<(String, String)>,
+    client: Option<async_nats::Client>,
+}
+
+impl NatsSink {
+    async fn connect(&self) -> RustRouteResult<async_nats::Client> {
+        let servers = self
+            .servers
+            .iter()
+            .map(|server| server.parse::<async_nats::ServerAddr>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(nats_error)?;
+        let options = match &self.credentials {
+            Some((username, password)) => async_nats::ConnectOptions::with_user_and_password(
+                username.clone(),
+                password.clone(),
+            ),
+            None => async_nats::ConnectOptions::new(),
+        };
+        options
+            .connection_timeout(RECEIVE_HEARTBEAT)
+            .connect(servers)
+            .await
+            .map_err(nats_error)
+    }
+}
+
+impl Sink for NatsSink {
+    fn send<'a>(&'a mut self, records: &'a [Vec<u8>]) -> SendFuture<'a> {
+        Box::pin(async move {
+            if self.client.is_none() {
+                self.client = Some(self.connect().await?);
+            }
+            let client = self.client.as_ref().expect("client connected above");
+
+            for record in records {
+                client
+                    .publish(self.subject.clone(), record.clone().into())
+                    .await
+                    .map_err(nats_error)?;
+            }
+            client.flush().await.map_err(nats_error)
+        })
+    }
+}
+
+fn nats_error(err: impl std::fmt::Display) -> RustRouteError {
+    RustRouteError::NetworkError(format!("NATS: {}", err))
+}
+
+fn sink_for(config: &EventStreamConfig, router_id: &str) -> Box<dyn Sink> {
+    let topic = config.topic.replace("{router_id}", router_id);
+    let credentials = config
+        .username
+        .clone()
+        .map(|username| (username, config.password.clone().unwrap_or_default()));
+
+    match config.backend {
+        StreamBackend::Kafka => Box::new(KafkaSink {
+            servers: config.servers.clone(),
+            topic,
+            partition: config.partition,
+            key: router_id.as_bytes().to_vec(),
+            credentials,
+            client: None,
+        }),
+        StreamBackend::Nats => Box::new(NatsSink {
+            servers: config.servers.clone(),
+            subject: topic,
+            credentials,
+            client: None,
+        }),
+    }
+}
+
+/// Records waiting for delivery, oldest first
+struct Buffer {
+    pending: VecDeque<Vec<u8>>,
+    limit: usize,
+    dropped: u64,
+}
+
+impl Buffer {
+    fn new(limit: usize) -> Self {
+        Self {
+            pending: VecDeque::new(),
+            limit: limit.max(1),
+            dropped: 0,
+        }
+    }
+
+    fn push(&mut self, record: Vec<u8>) {
+        if self.pending.len() >= self.limit {
+            self.pending.pop_front();
+            self.dropped += 1;
+        }
+        self.pending.push_back(record);
+    }
+}
+
+/// Send one batch, retrying with exponential backoff
+async fn deliver(
+    sink: &mut dyn Sink,
+    batch: &[Vec<u8>],
+    config: &EventStreamConfig,
+    heartbeat: &Heartbeat,
+) -> RustRouteResult<()> {
+    let mut backoff = Duration::from_millis(config.retry_backoff_ms);
+    let mut attempt = 0;
+    loop {
+        heartbeat.beat();
+        match sink.send(batch).await {
+            Ok(()) => return Ok(()),
+            Err(err) if attempt >= config.max_retries => return Err(err),
+            Err(err) => {
+                attempt += 1;
+                debug!(
+                    "Event stream delivery failed (attempt {}): {}",
+                    attempt, err
+                );
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(RECEIVE_HEARTBEAT);
+            }
+        }
+    }
+}
+
+/// Drain the buffer in batches; stops at the first batch that cannot be delivered
+async fn flush(
+    sink: &mut dyn Sink,
+    buffer: &mut Buffer,
+    config: &EventStreamConfig,
+    heartbeat: &Heartbeat,
+) {
+    if buffer.dropped > 0 {
+        warn!(
+            "Event stream buffer full; dropped {} oldest records",
+            buffer.dropped
+        );
+        buffer.dropped = 0;
+    }
+
+    while !buffer.pending.is_empty() {
+        let count = buffer.pending.len().min(config.batch_size.max(1));
+        let batch: Vec<Vec<u8>> = buffer.pending.iter().take(count).cloned().collect();
+        match deliver(sink, &batch, config, heartbeat).await {
+            Ok(()) => {
+                buffer.pending.drain(..count);
+            }
+            Err(err) => {
+                warn!(
+                    "Event stream delivery failed; keeping {} records buffered: {}",
+                    buffer.pending.len(),
+                    err
+                );
+                return;
+            }
+        }
+    }
+}
+
+/// Stream events until the bus closes or the task is aborted
+pub async fn run(
+    config: EventStreamConfig,
+    router_id: String,
+    events: EventBus,
+    heartbeat: Heartbeat,
+) {
+    let mut sink = sink_for(&config, &router_id);
+    let mut buffer = Buffer::new(config.buffer_limit);
+    let mut receiver = events.subscribe("event-stream");
+    let mut ticker = tokio::time::interval(Duration::from_millis(config.flush_interval_ms.max(1)));
+    info!(
+        "Streaming events to {:?} {}",
+        config.backend,
+        config.topic.replace("{router_id}", &router_id)
+    );
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {
+                heartbeat.beat();
+                flush(sink.as_mut(), &mut buffer, &config, &heartbeat).await;
+            }
+            event = receiver.recv() => match event {
+                Ok(event) => {
+                    if !config.events.contains(&StreamEventKind::of(&event)) {
+                        continue;
+                    }
+                    buffer.push(encode_event(&router_id, &event));
+                    if buffer.pending.len() >= config.batch_size.max(1) {
+                        flush(sink.as_mut(), &mut buffer, &config, &heartbeat).await;
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Event stream fell behind; skipped {} events", skipped);
+                }
+                Err(RecvError::Closed) => {
+                    flush(sink.as_mut(), &mut buffer, &config, &heartbeat).await;
+                    return;
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::ActivityLevel;
+    use crate::watchdog::{Watchdog, WatchdogConfig};
+
+    struct FlakySink {
+        failures_left: u32,
+        delivered: Vec<Vec<u8>>,
+        calls: u32,
+    }
+
+    impl Sink for FlakySink {
+        fn send<'a>(&'a mut self, records: &'a [Vec<u8>]) -> SendFuture<'a> {
+            Box::pin(async move {
+                self.calls += 1;
+                if self.failures_left > 0 {
+                    self.failures_left -= 1;
+                    return Err(RustRouteError::NetworkError("broker down".to_string()));
+                }
+                self.delivered.extend_from_slice(records);
+                Ok(())
+            })
+        }
+    }
+
+    fn heartbeat() -> Heartbeat {
+        let watchdog = Watchdog::new(WatchdogConfig::default());
+        watchdog.register("event-stream", RECEIVE_HEARTBEAT)
+    }
+
+    #[test]
+    fn envelope_carries_router_id_and_event() {
+        let bus = EventBus::new(4);
+        let mut receiver = bus.subscribe("test");
+        bus.publish_activity(ActivityLevel::Info, "hello");
+        let event = receiver.try_recv().unwrap();
+
+        let value: serde_json::Value =
+            serde_json::from_slice(&encode_event("10.0.0.1", &event)).unwrap();
+        assert_eq!(value["router_id"], "10.0.0.1");
+        assert_eq!(value["type"], "Activity");
+        assert_eq!(value["data"]["message"], "hello");
+    }
+
+    #[tokio::test]
+    async fn failed_batches_are_retried_then_kept() {
+        let config = EventStreamConfig {
+            batch_size: 2,
+            max_retries: 2,
+            retry_backoff_ms: 1,
+            buffer_limit: 3,
+            ..EventStreamConfig::default()
+        };
+        let heartbeat = heartbeat();
+        let mut buffer = Buffer::new(config.buffer_limit);
+        for record in [b"a", b"b", b"c", b"d"] {
+            buffer.push(record.to_vec());
+        }
+        assert_eq!(buffer.dropped, 1);
+
+        let mut sink = FlakySink {
+            failures_left: 5,
+            delivered: Vec::new(),
+            calls: 0,
+        };
+        flush(&mut sink, &mut buffer, &config, &heartbeat).await;
+        assert_eq!(sink.calls, 3);
+        assert_eq!(buffer.pending.len(), 3);
+
+        flush(&mut sink, &mut buffer, &config, &heartbeat).await;
+        assert!(buffer.pending.is_empty());
+        assert_eq!(
+            sink.delivered,
+            vec![b"b".to_vec(), b"c".to_vec(), b"d".to_vec()]
+        );
+    }
+}
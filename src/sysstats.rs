@@ -0,0 +1,198 @@
+//! Host statistics for the dashboard: interface counters, CPU usage and the
+//! router's own memory footprint.
+//!
+//! Linux builds read `/proc` directly. Other platforms use the `sysinfo`
+//! crate, selected at compile time so Linux builds do not pull it in.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Time between the two samples CPU usage is computed from
+const CPU_SAMPLE_WINDOW: Duration = Duration::from_millis(150);
+
+/// Traffic counters of one OS interface since boot
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InterfaceCounters {
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_packets: u64,
+    pub tx_packets: u64,
+}
+
+#[cfg(target_os = "linux")]
+mod backend {
+    use super::*;
+
+    pub fn interface_counters() -> HashMap<String, InterfaceCounters> {
+        std::fs::read_to_string("/proc/net/dev")
+            .map(|content| parse_net_dev(&content))
+            .unwrap_or_default()
+    }
+
+    pub async fn cpu_usage_percent() -> f32 {
+        let first = read_cpu_times();
+        tokio::time::sleep(CPU_SAMPLE_WINDOW).await;
+        let second = read_cpu_times();
+
+        match (first, second) {
+            (Some((idle1, total1)), Some((idle2, total2))) if total2 > total1 && idle2 >= idle1 => {
+                let total_delta = total2 - total1;
+                let idle_delta = idle2 - idle1;
+                let usage = (total_delta - idle_delta) as f32 / total_delta as f32;
+                (usage * 100.0).clamp(0.0, 100.0)
+            }
+            _ => 0.0,
+        }
+    }
+
+    pub fn process_memory_bytes() -> u64 {
+        std::fs::read_to_string("/proc/self/status")
+            .ok()
+            .and_then(|status| parse_vm_rss(&status))
+            .unwrap_or(0)
+    }
+
+    pub(super) fn parse_net_dev(content: &str) -> HashMap<String, InterfaceCounters> {
+        let mut stats = HashMap::new();
+        for line in content.lines().skip(2) {
+            let Some((iface, data)) = line.split_once(':') else {
+                continue;
+            };
+            let parts: Vec<&str> = data.split_whitespace().collect();
+            if parts.len() >= 16 {
+                stats.insert(
+                    iface.trim().to_string(),
+                    InterfaceCounters {
+                        rx_bytes: parts[0].parse().unwrap_or(0),
+                        rx_packets: parts[1].parse().unwrap_or(0),
+                        tx_bytes: parts[8].parse().unwrap_or(0),
+                        tx_packets: parts[9].parse().unwrap_or(0),
+                    },
+                );
+            }
+        }
+        stats
+    }
+
+    pub(super) fn parse_vm_rss(status: &str) -> Option<u64> {
+        let line = status
+            .lines()
+            .find_map(|line| line.strip_prefix("VmRSS:"))?;
+        let kb: u64 = line.split_whitespace().next()?.parse().ok()?;
+        Some(kb * 1024)
+    }
+
+    /// Idle and total jiffies from the aggregate line of `/proc/stat`
+    fn read_cpu_times() -> Option<(u64, u64)> {
+        let content = std::fs::read_to_string("/proc/stat").ok()?;
+        parse_cpu_times(content.lines().next()?)
+    }
+
+    pub(super) fn parse_cpu_times(line: &str) -> Option<(u64, u64)> {
+        let values: Vec<u64> = line
+            .split_whitespace()
+            .skip(1)
+            .take(10)
+            .filter_map(|field| field.parse().ok())
+            .collect();
+        if values.len() < 4 {
+            return None;
+        }
+
+        let field = |index: usize| values.get(index).copied().unwrap_or(0);
+        // user nice system idle iowait irq softirq steal
+        let idle_all = field(3) + field(4);
+        let non_idle = field(0) + field(1) + field(2) + field(5) + field(6) + field(7);
+        Some((idle_all, idle_all + non_idle))
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod backend {
+    use super::*;
+    use sysinfo::{Networks, ProcessesToUpdate, System};
+
+    pub fn interface_counters() -> HashMap<String, InterfaceCounters> {
+        Networks::new_with_refreshed_list()
+            .iter()
+            .map(|(name, data)| {
+                (
+                    name.clone(),
+                    InterfaceCounters {
+                        rx_bytes: data.total_received(),
+                        tx_bytes: data.total_transmitted(),
+                        rx_packets: data.total_packets_received(),
+                        tx_packets: data.total_packets_transmitted(),
+                    },
+                )
+            })
+            .collect()
+    }
+
+    pub async fn cpu_usage_percent() -> f32 {
+        let mut system = System::new();
+        system.refresh_cpu_usage();
+        tokio::time::sleep(CPU_SAMPLE_WINDOW.max(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL)).await;
+        system.refresh_cpu_usage();
+        system.global_cpu_usage().clamp(0.0, 100.0)
+    }
+
+    pub fn process_memory_bytes() -> u64 {
+        let Ok(pid) = sysinfo::get_current_pid() else {
+            return 0;
+        };
+        let mut system = System::new();
+        system.refresh_processes(ProcessesToUpdate::Some(&[pid]), false);
+        system.process(pid).map_or(0, |process| process.memory())
+    }
+}
+
+/// Counters of every OS interface, keyed by interface name
+pub fn interface_counters() -> HashMap<String, InterfaceCounters> {
+    backend::interface_counters()
+}
+
+/// Host-wide CPU usage, sampled over a short window
+pub async fn cpu_usage_percent() -> f32 {
+    backend::cpu_usage_percent().await
+}
+
+/// Resident memory of this process in bytes, or 0 when unavailable
+pub fn process_memory_bytes() -> u64 {
+    backend::process_memory_bytes()
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::backend::*;
+
+    #[test]
+    fn parses_proc_net_dev() {
+        let content = "Inter-|   Receive                            |  Transmit\n \
+                       face |bytes    packets errs drop fifo frame compressed multicast|bytes packets errs drop fifo colls carrier compressed\n  \
+                       eth0: 1500 12 0 0 0 0 0 0 900 7 0 0 0 0 0 0\n";
+        let stats = parse_net_dev(content);
+        assert_eq!(
+            stats["eth0"],
+            super::InterfaceCounters {
+                rx_bytes: 1500,
+                tx_bytes: 900,
+                rx_packets: 12,
+                tx_packets: 7,
+            }
+        );
+    }
+
+    #[test]
+    fn parses_cpu_times_and_rss() {
+        assert_eq!(
+            parse_cpu_times("cpu  10 0 5 80 5 0 0 0 0 0"),
+            Some((85, 100))
+        );
+        assert_eq!(parse_cpu_times("cpu 1 2"), None);
+        assert_eq!(
+            parse_vm_rss("Name:\trust-route\nVmRSS:\t  2048 kB\n"),
+            Some(2048 * 1024)
+        );
+    }
+}
@@ -159,17 +159,18 @@ pub fn get_diagnosis_suggestions(target_ip: Ipv4Addr) -> Vec<String> {
     let mut suggestions = Vec::new();
 
     if is_private_ip(target_ip) {
-        suggestions.push("目标是私有IP，检查本地网络配置".to_string());
-        suggestions.push("确认网络接口配置正确".to_string());
-        suggestions.push("检查交换机/路由器设置".to_string());
+        suggestions
+            .push("Target is a private address; check the local network configuration".to_string());
+        suggestions.push("Confirm the network interface configuration".to_string());
+        suggestions.push("Check switch and router settings".to_string());
     } else {
-        suggestions.push("目标是公网IP，检查网关配置".to_string());
-        suggestions.push("确认DNS服务器设置".to_string());
-        suggestions.push("检查防火墙规则".to_string());
+        suggestions.push("Target is a public address; check the gateway configuration".to_string());
+        suggestions.push("Confirm the DNS server settings".to_string());
+        suggestions.push("Check firewall rules".to_string());
     }
 
-    suggestions.push("使用 ip route show 检查路由表".to_string());
-    suggestions.push("使用 ip addr show 检查接口状态".to_string());
+    suggestions.push("Run `ip route show` to inspect the routing table".to_string());
+    suggestions.push("Run `ip addr show` to inspect interface state".to_string());
 
     suggestions
 }
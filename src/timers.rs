@@ -0,0 +1,480 @@
+//! RIP route timers (RFC 2453 section 3.8).
+//!
+//! A learned route is valid until it goes `timeout` without a refresh. It is
+//! then marked unreachable and kept, advertised with metric 16, for the
+//! `garbage` period before being flushed. The optional hold-down covers the
+//! start of that period: while it runs, only the neighbor the route was
+//! learned from may bring it back. Neighbors expire on the same timeout as
+//! their routes.
+//!
+//! Periodic updates normally carry the whole table. Routes inside a prefix
+//! listed under `rip.slow_prefixes` are left out of them until their own
+//! interval has passed, see [`AdvertiseSchedule`].
+//!
+//! Timing decisions take the current instant from a [`Clock`], so tests can
+//! drive the timers with a [`MockClock`] instead of sleeping. Instants are
+//! only turned into wall-clock time for reporting, through [`wall_clock`].
+
+use chrono::{DateTime, Utc};
+use ipnet::Ipv4Net;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+use crate::config_manager::RipConfig;
+use crate::events::{EventBus, NeighborEvent, NeighborState, RouteEvent, WebEvent};
+use crate::protocol::DEFAULT_INFINITY;
+use crate::router::Router;
+use crate::routing_table::{Route, RouteSource};
+use crate::watchdog::Heartbeat;
+
+/// Period after which an unrefreshed route becomes unreachable
+pub const DEFAULT_ROUTE_TIMEOUT: Duration = Duration::from_secs(180);
+/// Period an unreachable route is kept before it is flushed
+pub const DEFAULT_GARBAGE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Source of the current instant for timer decisions
+pub trait Clock: fmt::Debug + Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+#[derive(Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Clock that only moves when told to
+#[derive(Debug)]
+pub struct MockClock {
+    base: Instant,
+    offset: Mutex<Duration>,
+}
+
+impl MockClock {
+    pub fn new() -> Self {
+        Self {
+            base: Instant::now(),
+            offset: Mutex::new(Duration::ZERO),
+        }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self
+            .offset
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) += by;
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.base
+            + *self
+                .offset
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+pub fn system_clock() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
+
+/// UTC time of `instant`, measured from one reference pair of readings per
+/// process so that every report of the same instant agrees. Later steps of
+/// the system clock are not reflected.
+pub fn wall_clock(instant: Instant) -> DateTime<Utc> {
+    static REFERENCE: OnceLock<(Instant, DateTime<Utc>)> = OnceLock::new();
+    let (reference, reference_utc) = *REFERENCE.get_or_init(|| (Instant::now(), Utc::now()));
+    let offset = |duration: Duration| chrono::Duration::from_std(duration).unwrap_or_default();
+    if instant >= reference {
+        reference_utc + offset(instant - reference)
+    } else {
+        reference_utc - offset(reference - instant)
+    }
+}
+
+/// Where a route stands in its timer lifecycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimerState {
+    /// Reachable and within its timeout
+    Valid,
+    /// Reachable but past its timeout; due to be marked unreachable
+    Expired,
+    /// Unreachable and ignoring other neighbors
+    HoldDown,
+    /// Unreachable and advertised as such until flushed
+    Garbage,
+    /// Due for removal
+    Flush,
+}
+
+/// Timer settings, shared by the routing table and the neighbor list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RipTimers {
+    pub timeout: Duration,
+    pub garbage: Duration,
+    pub holddown: Duration,
+    /// Metric at which a route counts as unreachable
+    pub infinity: u32,
+}
+
+impl Default for RipTimers {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_ROUTE_TIMEOUT,
+            garbage: DEFAULT_GARBAGE_TIMEOUT,
+            holddown: Duration::ZERO,
+            infinity: DEFAULT_INFINITY,
+        }
+    }
+}
+
+impl RipTimers {
+    pub fn from_config(config: &RipConfig) -> Self {
+        Self {
+            timeout: Duration::from_secs(config.route_timeout),
+            garbage: Duration::from_secs(config.garbage_collection_timeout),
+            holddown: Duration::from_secs(config.holddown),
+            infinity: config.infinity_metric,
+        }
+    }
+
+    /// Timer state of `route` at `now`. Direct and static routes never time
+    /// out; static routes are never flushed.
+    pub fn state(&self, route: &Route, now: Instant) -> TimerState {
+        let age = now.saturating_duration_since(route.last_updated);
+        if route.metric < self.infinity {
+            if route.source == RouteSource::Dynamic && age > self.timeout {
+                return TimerState::Expired;
+            }
+            return TimerState::Valid;
+        }
+
+        if route.source != RouteSource::Static && age > self.garbage {
+            TimerState::Flush
+        } else if route.source == RouteSource::Dynamic && age < self.holddown {
+            TimerState::HoldDown
+        } else {
+            TimerState::Garbage
+        }
+    }
+
+    /// Time left at `now` until `route` times out and until it is flushed;
+    /// `None` for timers that do not run for the route
+    pub fn deadlines(&self, route: &Route, now: Instant) -> (Option<Duration>, Option<Duration>) {
+        let age = now.saturating_duration_since(route.last_updated);
+        match (route.source, route.metric < self.infinity) {
+            (RouteSource::Dynamic, true) => {
+                let expiry = self.timeout.saturating_sub(age);
+                (Some(expiry), Some(expiry + self.garbage))
+            }
+            (RouteSource::Static, _) | (_, true) => (None, None),
+            (_, false) => (None, Some(self.garbage.saturating_sub(age))),
+        }
+    }
+
+    /// Whether a neighbor last heard at `last_seen` has gone silent
+    pub fn neighbor_expired(&self, last_seen: Instant, now: Instant) -> bool {
+        now.saturating_duration_since(last_seen) > self.timeout
+    }
+
+    /// How often timers are checked; a tenth of the shortest timer, within 1-10s
+    pub fn sweep_interval(&self) -> Duration {
+        (self.timeout.min(self.garbage) / 10).clamp(Duration::from_secs(1), Duration::from_secs(10))
+    }
+}
+
+/// Routes inside `prefix` advertised in periodic updates every `interval`
+/// seconds instead of every update
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SlowPrefix {
+    pub prefix: Ipv4Net,
+    pub interval: u64,
+}
+
+/// When each slow route was last put in a periodic update, per interface
+#[derive(Debug, Default)]
+pub struct AdvertiseSchedule {
+    last_sent: HashMap<(String, Ipv4Net), Instant>,
+}
+
+impl AdvertiseSchedule {
+    /// The routes of a periodic update on `interface` at `now`: routes inside
+    /// a slow prefix are dropped until its interval has passed, the most
+    /// specific prefix deciding. `period` is the update interval; a route
+    /// due within half of it goes out now rather than a whole period late.
+    /// Unreachable routes are always kept so withdrawals spread at once.
+    /// Prefixes missing from `routes` are forgotten.
+    pub fn due(
+        &mut self,
+        slow_prefixes: &[SlowPrefix],
+        interface: &str,
+        routes: Vec<Route>,
+        period: Duration,
+        now: Instant,
+        infinity: u32,
+    ) -> Vec<Route> {
+        if slow_prefixes.is_empty() {
+            self.last_sent.clear();
+            return routes;
+        }
+        let mut offered = HashSet::new();
+        let due = routes
+            .into_iter()
+            .filter(|route| {
+                let Ok(net) = Ipv4Net::with_netmask(route.destination, route.subnet_mask) else {
+                    return true;
+                };
+                let net = net.trunc();
+                offered.insert(net);
+                let Some(slow) = slow_prefixes
+                    .iter()
+                    .filter(|slow| slow.prefix.contains(&net))
+                    .max_by_key(|slow| slow.prefix.prefix_len())
+                else {
+                    return true;
+                };
+                let key = (interface.to_string(), net);
+                let interval = Duration::from_secs(slow.interval);
+                let due = route.metric >= infinity
+                    || self.last_sent.get(&key).is_none_or(|last| {
+                        now.saturating_duration_since(*last) + period / 2 >= interval
+                    });
+                if due {
+                    self.last_sent.insert(key, now);
+                }
+                due
+            })
+            .collect();
+        self.last_sent
+            .retain(|(name, net), _| name != interface || offered.contains(net));
+        due
+    }
+}
+
+/// Expire and flush routes and neighbors until the task is aborted
+pub async fn run(router: Arc<RwLock<Router>>, events: EventBus, heartbeat: Heartbeat) {
+    let (mut timers, routing_table, metrics) = {
+        let router = router.read().await;
+        (router.timers(), router.routing_table(), router.metrics())
+    };
+    let mut interval = tokio::time::interval(timers.sweep_interval());
+
+    loop {
+        interval.tick().await;
+        heartbeat.beat();
+
+        let current = router.read().await.timers();
+        if current != timers {
+            timers = current;
+            interval = tokio::time::interval(timers.sweep_interval());
+        }
+
+        let expired = {
+            let mut table = routing_table.write().await;
+            let expired = table.process_timeouts();
+            table.garbage_collect();
+            metrics.update_route_count(table.route_count());
+            expired
+        };
+        for route in expired {
+            events.publish(WebEvent::RouteWithdrawn(RouteEvent::from_route(&route)));
+        }
+
+        let removed = router.read().await.cleanup_neighbors(timers.timeout).await;
+        for neighbor in removed {
+            warn!("Neighbor {} timed out", neighbor.address);
+            events.publish(WebEvent::Neighbor(NeighborEvent::new(
+                neighbor.address,
+                neighbor.interface,
+                NeighborState::Down,
+            )));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routing_table::RoutingTable;
+    use std::net::Ipv4Addr;
+
+    fn learned(neighbor: u8) -> Route {
+        Route::new(
+            Ipv4Addr::new(10, 3, 0, 0),
+            Ipv4Addr::new(255, 255, 0, 0),
+            Ipv4Addr::new(192, 168, 0, neighbor),
+            2,
+            "eth0".to_string(),
+            RouteSource::Dynamic,
+            Some(Ipv4Addr::new(192, 168, 0, neighbor)),
+        )
+    }
+
+    #[test]
+    fn slow_prefixes_skip_periodic_updates_until_due() {
+        let slow = vec![
+            SlowPrefix {
+                prefix: "10.0.0.0/8".parse().unwrap(),
+                interval: 120,
+            },
+            SlowPrefix {
+                prefix: "10.3.0.0/16".parse().unwrap(),
+                interval: 60,
+            },
+        ];
+        let mut aggregate = learned(1);
+        aggregate.destination = Ipv4Addr::new(10, 0, 0, 0);
+        aggregate.subnet_mask = Ipv4Addr::new(255, 0, 0, 0);
+        let mut other = learned(1);
+        other.destination = Ipv4Addr::new(172, 16, 0, 0);
+        let routes = vec![aggregate, learned(1), other];
+
+        let period = Duration::from_secs(30);
+        let start = Instant::now();
+        let mut schedule = AdvertiseSchedule::default();
+        let sent = |schedule: &mut AdvertiseSchedule, at: u64, routes: Vec<Route>| {
+            schedule
+                .due(
+                    &slow,
+                    "eth0",
+                    routes,
+                    period,
+                    start + Duration::from_secs(at),
+                    16,
+                )
+                .iter()
+                .map(|route| route.destination.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(sent(&mut schedule, 0, routes.clone()).len(), 3);
+        assert_eq!(sent(&mut schedule, 30, routes.clone()), vec!["172.16.0.0"]);
+        // The /16 follows its own, more specific interval
+        assert_eq!(
+            sent(&mut schedule, 60, routes.clone()),
+            vec!["10.3.0.0", "172.16.0.0"]
+        );
+        // Within half a period of both the /8's and the /16's interval
+        assert_eq!(sent(&mut schedule, 105, routes.clone()).len(), 3);
+
+        let mut withdrawn = learned(1);
+        withdrawn.metric = 16;
+        assert_eq!(sent(&mut schedule, 110, vec![withdrawn]), vec!["10.3.0.0"]);
+    }
+
+    #[test]
+    fn the_schedule_forgets_prefixes_that_are_no_longer_advertised() {
+        let slow = vec![SlowPrefix {
+            prefix: "10.0.0.0/8".parse().unwrap(),
+            interval: 120,
+        }];
+        let period = Duration::from_secs(30);
+        let start = Instant::now();
+        let mut schedule = AdvertiseSchedule::default();
+
+        let sent = schedule.due(&slow, "eth0", vec![learned(1)], period, start, 16);
+        assert_eq!(sent.len(), 1);
+        schedule.due(&slow, "eth1", vec![learned(1)], period, start, 16);
+        assert_eq!(schedule.last_sent.len(), 2);
+
+        // Gone from eth0 only; a route that comes back goes out at once
+        schedule.due(&slow, "eth0", Vec::new(), period, start, 16);
+        assert_eq!(schedule.last_sent.len(), 1);
+        let later = start + Duration::from_secs(30);
+        let sent = schedule.due(&slow, "eth0", vec![learned(1)], period, later, 16);
+        assert_eq!(sent.len(), 1);
+
+        schedule.due(&[], "eth0", vec![learned(1)], period, later, 16);
+        assert!(schedule.last_sent.is_empty());
+    }
+
+    #[test]
+    fn routes_walk_through_timeout_and_flush() {
+        let clock = Arc::new(MockClock::new());
+        let mut table = RoutingTable::with_clock(RipTimers::default(), clock.clone());
+        let destination = Ipv4Addr::new(10, 3, 0, 1);
+        table.add_or_replace(learned(1));
+
+        clock.advance(Duration::from_secs(179));
+        assert!(table.process_timeouts().is_empty());
+
+        clock.advance(Duration::from_secs(2));
+        let expired = table.process_timeouts();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(table.get_route(destination).unwrap().metric, 16);
+
+        // Kept for the garbage period, then flushed
+        clock.advance(Duration::from_secs(119));
+        table.garbage_collect();
+        assert_eq!(table.route_count(), 1);
+        clock.advance(Duration::from_secs(2));
+        table.garbage_collect();
+        assert_eq!(table.route_count(), 0);
+    }
+
+    #[test]
+    fn holddown_ignores_other_neighbors() {
+        let clock = Arc::new(MockClock::new());
+        let timers = RipTimers {
+            holddown: Duration::from_secs(60),
+            ..RipTimers::default()
+        };
+        let mut table = RoutingTable::with_clock(timers, clock.clone());
+        let destination = Ipv4Addr::new(10, 3, 0, 1);
+        table.add_or_replace(learned(1));
+        clock.advance(Duration::from_secs(181));
+        table.process_timeouts();
+
+        assert!(!table.add_or_replace(learned(2)));
+        clock.advance(Duration::from_secs(61));
+        assert!(table.add_or_replace(learned(2)));
+        assert_eq!(
+            table.get_route(destination).unwrap().next_hop,
+            Ipv4Addr::new(192, 168, 0, 2)
+        );
+    }
+
+    #[test]
+    fn neighbors_expire_with_their_routes() {
+        let clock = MockClock::new();
+        let timers = RipTimers::default();
+        let heard = clock.now();
+        clock.advance(Duration::from_secs(180));
+        assert!(!timers.neighbor_expired(heard, clock.now()));
+        clock.advance(Duration::from_secs(1));
+        assert!(timers.neighbor_expired(heard, clock.now()));
+        assert_eq!(timers.sweep_interval(), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn instants_map_to_stable_wall_clock_times() {
+        let now = Instant::now();
+        let earlier = now - Duration::from_secs(90);
+        assert_eq!(wall_clock(now), wall_clock(now));
+        assert_eq!(
+            wall_clock(now) - wall_clock(earlier),
+            chrono::Duration::seconds(90)
+        );
+        assert!((Utc::now() - wall_clock(now)).num_seconds().abs() <= 1);
+
+        let snapshot = learned(2).to_snapshot();
+        assert!(snapshot.learned_at.unwrap() <= snapshot.last_updated.unwrap());
+    }
+}
@@ -0,0 +1,150 @@
+//! TLS configuration helpers shared by the TCP transports.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+use tokio_rustls::rustls::crypto::{ring, CryptoProvider};
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::{ClientConfig, RootCertStore, ServerConfig};
+use x509_parser::prelude::{FromDer, GeneralName, X509Certificate};
+
+use crate::{RustRouteError, RustRouteResult};
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+fn tls_error(context: &str, err: impl std::fmt::Display) -> RustRouteError {
+    RustRouteError::ConfigError(format!("{}: {}", context, err))
+}
+
+fn open(path: &str) -> RustRouteResult<BufReader<File>> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|e| tls_error(&format!("Failed to open {}", path), e))
+}
+
+/// Read every PEM certificate in `path`
+pub fn load_certs(path: &str) -> RustRouteResult<Vec<CertificateDer<'static>>> {
+    let certs = rustls_pemfile::certs(&mut open(path)?)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| tls_error(&format!("Invalid certificate in {}", path), e))?;
+    if certs.is_empty() {
+        return Err(RustRouteError::ConfigError(format!(
+            "No certificates found in {}",
+            path
+        )));
+    }
+    Ok(certs)
+}
+
+/// Read the first PEM private key in `path`
+pub fn load_key(path: &str) -> RustRouteResult<PrivateKeyDer<'static>> {
+    rustls_pemfile::private_key(&mut open(path)?)
+        .map_err(|e| tls_error(&format!("Invalid private key in {}", path), e))?
+        .ok_or_else(|| RustRouteError::ConfigError(format!("No private key found in {}", path)))
+}
+
+fn root_store(ca_file: &str) -> RustRouteResult<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(ca_file)? {
+        roots
+            .add(cert)
+            .map_err(|e| tls_error(&format!("Invalid CA certificate in {}", ca_file), e))?;
+    }
+    Ok(roots)
+}
+
+/// Which client certificates a server asks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientAuth<'a> {
+    None,
+    /// Verify certificates against the CA in this file, but admit clients without one
+    Optional(&'a str),
+    /// Refuse clients without a certificate issued by the CA in this file
+    Required(&'a str),
+}
+
+/// Server configuration; with `client_ca` set, clients must present a
+/// certificate issued by that CA
+pub fn server_config(
+    cert_file: &str,
+    key_file: &str,
+    client_ca: Option<&str>,
+) -> RustRouteResult<ServerConfig> {
+    let client_auth = client_ca.map_or(ClientAuth::None, ClientAuth::Required);
+    server_config_with(cert_file, key_file, client_auth)
+}
+
+/// Server configuration with an explicit client certificate policy
+pub fn server_config_with(
+    cert_file: &str,
+    key_file: &str,
+    client_auth: ClientAuth<'_>,
+) -> RustRouteResult<ServerConfig> {
+    let builder = ServerConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| tls_error("TLS setup failed", e))?;
+    let builder = match client_auth {
+        ClientAuth::Optional(ca_file) | ClientAuth::Required(ca_file) => {
+            let verifier = WebPkiClientVerifier::builder_with_provider(
+                Arc::new(root_store(ca_file)?),
+                provider(),
+            );
+            let verifier = match client_auth {
+                ClientAuth::Optional(_) => verifier.allow_unauthenticated(),
+                _ => verifier,
+            }
+            .build()
+            .map_err(|e| tls_error("Invalid client CA", e))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        ClientAuth::None => builder.with_no_client_auth(),
+    };
+    builder
+        .with_single_cert(load_certs(cert_file)?, load_key(key_file)?)
+        .map_err(|e| tls_error("Invalid server certificate", e))
+}
+
+/// Names a certificate identifies its holder by: the subject common names,
+/// then the DNS, email and URI subject alternative names
+pub fn certificate_identities(cert: &CertificateDer<'_>) -> Vec<String> {
+    let Ok((_, parsed)) = X509Certificate::from_der(cert.as_ref()) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = parsed
+        .subject()
+        .iter_common_name()
+        .filter_map(|cn| cn.as_str().ok())
+        .map(str::to_string)
+        .collect();
+    if let Ok(Some(san)) = parsed.subject_alternative_name() {
+        for name in &san.value.general_names {
+            match name {
+                GeneralName::DNSName(value)
+                | GeneralName::RFC822Name(value)
+                | GeneralName::URI(value) => names.push(value.to_string()),
+                _ => {}
+            }
+        }
+    }
+    names
+}
+
+/// Client configuration trusting `ca_file`, optionally presenting a client certificate
+pub fn client_config(
+    ca_file: &str,
+    client_cert: Option<(&str, &str)>,
+) -> RustRouteResult<ClientConfig> {
+    let builder = ClientConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| tls_error("TLS setup failed", e))?
+        .with_root_certificates(root_store(ca_file)?);
+    match client_cert {
+        Some((cert_file, key_file)) => builder
+            .with_client_auth_cert(load_certs(cert_file)?, load_key(key_file)?)
+            .map_err(|e| tls_error("Invalid client certificate", e)),
+        None => Ok(builder.with_no_client_auth()),
+    }
+}
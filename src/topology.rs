@@ -0,0 +1,332 @@
+//! Consolidated topology changes.
+//!
+//! Watches the route and neighbor events on the bus and, once they stop
+//! coming for `topology.debounce_ms`, publishes one `TopologyChanged` event
+//! with the neighbors that appeared or went away and the prefixes that
+//! became reachable or unreachable. Changes that revert within the window,
+//! such as a flapping neighbor, are left out, and a steady stream of changes
+//! is still reported every `topology.max_delay_ms`. Metric changes of a
+//! prefix that stays reachable are not topology changes.
+
+use chrono::{DateTime, Utc};
+use ipnet::Ipv4Net;
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::net::Ipv4Addr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::RwLock;
+use tokio::time::Instant;
+
+use crate::events::{EventBus, NeighborState, RouteEvent, TopologyChangedEvent, WebEvent};
+use crate::router::{Router, RECEIVE_HEARTBEAT};
+use crate::watchdog::Heartbeat;
+
+/// Topology change detection, under `topology`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TopologyConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Quiet time after the last change before the changes are reported
+    #[serde(default = "default_debounce_ms")]
+    pub debounce_ms: u64,
+    /// Longest a change waits to be reported while changes keep coming
+    #[serde(default = "default_max_delay_ms")]
+    pub max_delay_ms: u64,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_debounce_ms() -> u64 {
+    2000
+}
+
+fn default_max_delay_ms() -> u64 {
+    10_000
+}
+
+impl Default for TopologyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            debounce_ms: default_debounce_ms(),
+            max_delay_ms: default_max_delay_ms(),
+        }
+    }
+}
+
+/// Reported state and the changes to it not reported yet
+#[derive(Debug, Default)]
+pub struct TopologyTracker {
+    neighbors: HashSet<String>,
+    reachable: HashSet<String>,
+    /// New state of each changed neighbor, `true` for up
+    pending_neighbors: BTreeMap<String, bool>,
+    /// New state of each changed prefix, `true` for reachable
+    pending_prefixes: BTreeMap<String, bool>,
+    first_change: Option<(Instant, DateTime<Utc>)>,
+    last_change: Option<Instant>,
+}
+
+fn prefix(route: &RouteEvent) -> Option<String> {
+    let destination: Ipv4Addr = route.destination.parse().ok()?;
+    let mask: Ipv4Addr = route.subnet_mask.parse().ok()?;
+    Ipv4Net::with_netmask(destination, mask)
+        .ok()
+        .map(|net| net.to_string())
+}
+
+/// Record `state` as pending unless it is the reported one, returning
+/// whether the pending changes moved
+fn note(
+    reported: &HashSet<String>,
+    pending: &mut BTreeMap<String, bool>,
+    key: String,
+    state: bool,
+) -> bool {
+    if reported.contains(&key) == state {
+        pending.remove(&key).is_some()
+    } else {
+        pending.insert(key, state) != Some(state)
+    }
+}
+
+fn count(n: usize, singular: &str, plural: &str, what: &str) -> Option<String> {
+    match n {
+        0 => None,
+        1 => Some(format!("1 {} {}", singular, what)),
+        n => Some(format!("{} {} {}", n, plural, what)),
+    }
+}
+
+impl TopologyTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take note of `event`; routes at or above `infinity` are unreachable
+    pub fn observe(&mut self, event: &WebEvent, infinity: u32, now: Instant) {
+        let changed = match event {
+            WebEvent::Route(route) => prefix(route).is_some_and(|key| {
+                let state = route.metric < infinity;
+                note(&self.reachable, &mut self.pending_prefixes, key, state)
+            }),
+            WebEvent::RouteWithdrawn(route) => prefix(route)
+                .is_some_and(|key| note(&self.reachable, &mut self.pending_prefixes, key, false)),
+            WebEvent::RouteV6(route) => note(
+                &self.reachable,
+                &mut self.pending_prefixes,
+                route.prefix.to_string(),
+                route.metric < infinity,
+            ),
+            WebEvent::RouteV6Withdrawn(route) => note(
+                &self.reachable,
+                &mut self.pending_prefixes,
+                route.prefix.to_string(),
+                false,
+            ),
+            WebEvent::Neighbor(neighbor) => note(
+                &self.neighbors,
+                &mut self.pending_neighbors,
+                neighbor.address.clone(),
+                neighbor.state == NeighborState::Up,
+            ),
+            _ => false,
+        };
+        if !changed {
+            return;
+        }
+        if self.pending_neighbors.is_empty() && self.pending_prefixes.is_empty() {
+            // Everything reverted
+            self.first_change = None;
+            self.last_change = None;
+            return;
+        }
+        self.first_change.get_or_insert((now, Utc::now()));
+        self.last_change = Some(now);
+    }
+
+    /// When the pending changes are to be reported
+    pub fn due(&self, config: &TopologyConfig) -> Option<Instant> {
+        let (first, _) = self.first_change?;
+        let last = self.last_change?;
+        let settled = last + Duration::from_millis(config.debounce_ms);
+        let latest = first + Duration::from_millis(config.max_delay_ms.max(config.debounce_ms));
+        Some(settled.min(latest))
+    }
+
+    /// Report the pending changes, making them the reported state
+    pub fn take(&mut self) -> Option<TopologyChangedEvent> {
+        let (_, since) = self.first_change.take()?;
+        self.last_change = None;
+        let mut event = TopologyChangedEvent {
+            since,
+            timestamp: Utc::now(),
+            ..TopologyChangedEvent::default()
+        };
+        for (neighbor, up) in std::mem::take(&mut self.pending_neighbors) {
+            if up {
+                self.neighbors.insert(neighbor.clone());
+                event.neighbors_added.push(neighbor);
+            } else {
+                self.neighbors.remove(&neighbor);
+                event.neighbors_lost.push(neighbor);
+            }
+        }
+        for (prefix, reachable) in std::mem::take(&mut self.pending_prefixes) {
+            if reachable {
+                self.reachable.insert(prefix.clone());
+                event.prefixes_reachable.push(prefix);
+            } else {
+                self.reachable.remove(&prefix);
+                event.prefixes_unreachable.push(prefix);
+            }
+        }
+        let parts: Vec<String> = [
+            count(
+                event.neighbors_added.len(),
+                "neighbor",
+                "neighbors",
+                "added",
+            ),
+            count(event.neighbors_lost.len(), "neighbor", "neighbors", "lost"),
+            count(
+                event.prefixes_reachable.len(),
+                "prefix",
+                "prefixes",
+                "reachable",
+            ),
+            count(
+                event.prefixes_unreachable.len(),
+                "prefix",
+                "prefixes",
+                "unreachable",
+            ),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        event.summary = parts.join(", ");
+        Some(event)
+    }
+}
+
+/// Publish consolidated topology changes until the bus closes
+pub async fn run(router: Arc<RwLock<Router>>, events: EventBus, heartbeat: Heartbeat) {
+    let mut subscription = events.subscribe("topology");
+    let mut tracker = TopologyTracker::new();
+    loop {
+        heartbeat.beat();
+        let (config, infinity) = {
+            let router = router.read().await;
+            let config = router.config();
+            (config.topology.clone(), config.rip.infinity_metric)
+        };
+        let wake = tracker
+            .due(&config)
+            .unwrap_or_else(|| Instant::now() + RECEIVE_HEARTBEAT)
+            .min(Instant::now() + RECEIVE_HEARTBEAT);
+
+        tokio::select! {
+            event = subscription.recv() => match event {
+                Ok(event) => tracker.observe(&event, infinity, Instant::now()),
+                Err(RecvError::Lagged(skipped)) => {
+                    debug!("Topology detector skipped {} events", skipped);
+                }
+                Err(RecvError::Closed) => return,
+            },
+            _ = tokio::time::sleep_until(wake) => {}
+        }
+
+        if tracker
+            .due(&config)
+            .is_some_and(|due| due <= Instant::now())
+        {
+            let Some(change) = tracker.take() else {
+                continue;
+            };
+            if config.enabled {
+                info!("Topology changed: {}", change.summary);
+                events.publish(WebEvent::TopologyChanged(change));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::NeighborEvent;
+    use crate::routing_table::RouteSource;
+
+    fn route(third: u8, metric: u32) -> WebEvent {
+        WebEvent::Route(RouteEvent::from_parts(
+            Ipv4Addr::new(10, 0, third, 0),
+            Ipv4Addr::new(255, 255, 255, 0),
+            Ipv4Addr::new(192, 0, 2, 1),
+            metric,
+            "eth0".to_string(),
+            RouteSource::Dynamic,
+        ))
+    }
+
+    fn neighbor(state: NeighborState) -> WebEvent {
+        WebEvent::Neighbor(NeighborEvent::new(
+            "192.0.2.1".parse().unwrap(),
+            Some("eth0".to_string()),
+            state,
+        ))
+    }
+
+    #[test]
+    fn changes_are_debounced_into_one_net_event() {
+        let config = TopologyConfig::default();
+        let mut tracker = TopologyTracker::new();
+        let start = Instant::now();
+        tracker.observe(&neighbor(NeighborState::Up), 16, start);
+        tracker.observe(&route(1, 2), 16, start);
+        tracker.observe(&route(2, 2), 16, start + Duration::from_millis(500));
+        // A metric change of a reachable prefix is no topology change
+        tracker.observe(&route(1, 3), 16, start + Duration::from_millis(900));
+        assert_eq!(
+            tracker.due(&config),
+            Some(start + Duration::from_millis(2500))
+        );
+
+        let change = tracker.take().unwrap();
+        assert_eq!(change.neighbors_added, vec!["192.0.2.1"]);
+        assert_eq!(
+            change.prefixes_reachable,
+            vec!["10.0.1.0/24", "10.0.2.0/24"]
+        );
+        assert_eq!(change.summary, "1 neighbor added, 2 prefixes reachable");
+        assert!(tracker.take().is_none());
+
+        // A prefix lost and regained within the window is not reported
+        tracker.observe(&route(1, 16), 16, start);
+        tracker.observe(&route(1, 2), 16, start);
+        assert_eq!(tracker.due(&config), None);
+
+        tracker.observe(&neighbor(NeighborState::Down), 16, start);
+        tracker.observe(&route(2, 16), 16, start);
+        let change = tracker.take().unwrap();
+        assert_eq!(change.neighbors_lost, vec!["192.0.2.1"]);
+        assert_eq!(change.prefixes_unreachable, vec!["10.0.2.0/24"]);
+    }
+
+    #[test]
+    fn steady_changes_are_reported_by_the_deadline() {
+        let config = TopologyConfig::default();
+        let mut tracker = TopologyTracker::new();
+        let start = Instant::now();
+        for step in 0..20u8 {
+            let at = start + Duration::from_secs(step as u64);
+            tracker.observe(&route(step, 2), 16, at);
+        }
+        assert_eq!(tracker.due(&config), Some(start + Duration::from_secs(10)));
+    }
+}
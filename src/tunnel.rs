@@ -0,0 +1,576 @@
+//! RIP over TCP/TLS tunnels.
+//!
+//! Carries RIP packets over a persistent TCP connection, optionally wrapped
+//! in TLS, so routers on networks that block UDP 520 and multicast can still
+//! peer. Each tunnel behaves like a point-to-point interface named after the
+//! tunnel: the far end is the only neighbor, split horizon applies to routes
+//! learned through it, and full updates are sent every `update_interval`.
+//!
+//! Frames are a 4-byte big-endian length followed by the packet in the same
+//! JSON encoding used on UDP.
+
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+use crate::protocol::{RipCommand, RipPacket};
+use crate::router::{
+    advertisement, clear_next_hops, pause, process_response, RipContext, RECEIVE_HEARTBEAT,
+};
+use crate::tls;
+use crate::watchdog::Heartbeat;
+use crate::{RustRouteError, RustRouteResult};
+
+/// Largest frame accepted from a peer
+const MAX_FRAME: usize = 256 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TunnelMode {
+    /// Accept connections from the far end on `address`
+    Listen,
+    /// Dial the far end at `address`, reconnecting when the session drops
+    Connect,
+}
+
+/// One RIP tunnel
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TunnelConfig {
+    /// Interface name used for routes learned through the tunnel
+    pub name: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    pub mode: TunnelMode,
+    /// `host:port` to listen on or connect to
+    pub address: String,
+    /// Seconds between reconnection attempts
+    #[serde(default = "default_reconnect_interval")]
+    pub reconnect_interval: u64,
+    #[serde(default)]
+    pub tls: TunnelTlsConfig,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_reconnect_interval() -> u64 {
+    5
+}
+
+/// TLS settings. Listeners need `cert_file`/`key_file` and require client
+/// certificates when `ca_file` is set; connectors verify the listener
+/// against `ca_file` and present `cert_file`/`key_file` when configured.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct TunnelTlsConfig {
+    pub enabled: bool,
+    #[serde(default)]
+    pub cert_file: Option<String>,
+    #[serde(default)]
+    pub key_file: Option<String>,
+    #[serde(default)]
+    pub ca_file: Option<String>,
+    /// Name expected in the listener's certificate; defaults to the host in `address`
+    #[serde(default)]
+    pub server_name: Option<String>,
+}
+
+impl TunnelConfig {
+    /// Check the settings that can be verified without touching the network
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty() {
+            return Err("Tunnel name cannot be empty".to_string());
+        }
+        if self.address.is_empty() {
+            return Err(format!("Tunnel {} address cannot be empty", self.name));
+        }
+        if self.tls.cert_file.is_some() != self.tls.key_file.is_some() {
+            return Err(format!(
+                "Tunnel {} TLS certificate and key must be configured together",
+                self.name
+            ));
+        }
+        if self.tls.enabled {
+            match self.mode {
+                TunnelMode::Listen if self.tls.cert_file.is_none() => {
+                    return Err(format!(
+                        "Tunnel {} listener needs a TLS certificate and key",
+                        self.name
+                    ));
+                }
+                TunnelMode::Connect if self.tls.ca_file.is_none() => {
+                    return Err(format!(
+                        "Tunnel {} needs a CA file to verify its peer",
+                        self.name
+                    ));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn server_name(&self) -> RustRouteResult<ServerName<'static>> {
+        let name = match &self.tls.server_name {
+            Some(name) => name.clone(),
+            None => {
+                let host = self
+                    .address
+                    .rsplit_once(':')
+                    .map(|(host, _)| host)
+                    .unwrap_or(&self.address);
+                host.trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .to_string()
+            }
+        };
+        ServerName::try_from(name.clone())
+            .map_err(|_| RustRouteError::ConfigError(format!("Invalid TLS server name {}", name)))
+    }
+}
+
+fn io_error(err: std::io::Error) -> RustRouteError {
+    RustRouteError::NetworkError(format!("Tunnel I/O failed: {}", err))
+}
+
+/// Write one length-prefixed packet
+pub async fn write_frame<W>(writer: &mut W, packet: &RipPacket) -> RustRouteResult<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let payload = packet.to_json()?;
+    let length = u32::try_from(payload.len())
+        .map_err(|_| RustRouteError::ProtocolError("Packet too large".to_string()))?;
+    writer
+        .write_all(&length.to_be_bytes())
+        .await
+        .map_err(io_error)?;
+    writer
+        .write_all(payload.as_bytes())
+        .await
+        .map_err(io_error)?;
+    writer.flush().await.map_err(io_error)
+}
+
+/// Read one length-prefixed packet; `None` when the peer closed the stream
+pub async fn read_frame<R>(reader: &mut R) -> RustRouteResult<Option<RipPacket>>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut header = [0u8; 4];
+    match reader.read_exact(&mut header).await {
+        Ok(_) => {}
+        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(io_error(err)),
+    }
+
+    let length = u32::from_be_bytes(header) as usize;
+    if length > MAX_FRAME {
+        return Err(RustRouteError::ProtocolError(format!(
+            "Tunnel frame of {} bytes exceeds limit",
+            length
+        )));
+    }
+    let mut payload = vec![0u8; length];
+    reader.read_exact(&mut payload).await.map_err(io_error)?;
+    let json = String::from_utf8(payload)
+        .map_err(|e| RustRouteError::ProtocolError(format!("Invalid UTF-8 in frame: {}", e)))?;
+    RipPacket::from_json(&json).map(Some)
+}
+
+/// Exchange RIP packets with one peer until the connection drops
+async fn session<S>(
+    stream: S,
+    peer: SocketAddr,
+    name: &str,
+    ctx: &RipContext,
+    update_interval: Duration,
+    heartbeat: &Heartbeat,
+) -> RustRouteResult<()>
+where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    let (mut reader, mut writer) = tokio::io::split(stream);
+
+    // Reads run in their own task because a partially read frame cannot be
+    // resumed once a select! branch is dropped
+    let (frames, mut incoming) = mpsc::channel(16);
+    let read_task = tokio::spawn(async move {
+        loop {
+            let frame = read_frame(&mut reader).await;
+            let stop = !matches!(frame, Ok(Some(_)));
+            if frames.send(frame).await.is_err() || stop {
+                return;
+            }
+        }
+    });
+    struct AbortOnDrop(tokio::task::JoinHandle<()>);
+    impl Drop for AbortOnDrop {
+        fn drop(&mut self) {
+            self.0.abort();
+        }
+    }
+    let _read_task = AbortOnDrop(read_task);
+
+    write_frame(&mut writer, &RipPacket::new_request()).await?;
+    ctx.metrics.increment_packets_sent();
+
+    let mut updates = tokio::time::interval(update_interval);
+    let mut liveness = tokio::time::interval(RECEIVE_HEARTBEAT);
+
+    loop {
+        tokio::select! {
+            _ = liveness.tick() => heartbeat.beat(),
+            _ = updates.tick() => {
+                let update = advertisement(ctx, name, Some(peer.ip())).await;
+                if update.entries.is_empty() {
+                    continue;
+                }
+                write_frame(&mut writer, &update).await?;
+                ctx.metrics.increment_packets_sent();
+                ctx.metrics.increment_routing_updates_sent();
+            }
+            frame = incoming.recv() => {
+                let packet = match frame {
+                    Some(Ok(Some(packet))) => packet,
+                    Some(Ok(None)) | None => return Ok(()),
+                    Some(Err(err)) => return Err(err),
+                };
+                ctx.metrics.increment_packets_received();
+                if let Err(err) = packet.validate(ctx.rip_config.infinity_metric) {
+                    ctx.metrics.increment_packets_dropped();
+                    warn!("Invalid packet from {} on tunnel {}: {}", peer, name, err);
+                    continue;
+                }
+
+                match packet.command {
+                    // Nothing is advertised during the startup quiet period
+                    RipCommand::Request if ctx.is_quiet() => {}
+                    RipCommand::Request => {
+                        write_frame(&mut writer, &advertisement(ctx, name, Some(peer.ip())).await).await?;
+                        ctx.metrics.increment_packets_sent();
+                        ctx.metrics.increment_routing_updates_sent();
+                        ctx.metrics.increment_queries_answered();
+                    }
+                    RipCommand::Response => {
+                        let mut packet = packet;
+                        clear_next_hops(&mut packet);
+                        process_response(ctx, name, packet, peer).await
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn log_session_end(name: &str, peer: SocketAddr, result: RustRouteResult<()>) {
+    match result {
+        Ok(()) => info!("Tunnel {} peer {} disconnected", name, peer),
+        Err(err) => warn!("Tunnel {} session with {} failed: {}", name, peer, err),
+    }
+}
+
+/// Run the tunnel until the task is aborted
+pub async fn run(
+    config: TunnelConfig,
+    ctx: RipContext,
+    update_interval: Duration,
+    heartbeat: Heartbeat,
+) {
+    let name = config.name.clone();
+    let reconnect = Duration::from_secs(config.reconnect_interval.max(1));
+
+    match config.mode {
+        TunnelMode::Connect => {
+            let connector = if config.tls.enabled {
+                let client_cert = config
+                    .tls
+                    .cert_file
+                    .as_deref()
+                    .zip(config.tls.key_file.as_deref());
+                let tls = config
+                    .tls
+                    .ca_file
+                    .as_deref()
+                    .ok_or_else(|| RustRouteError::ConfigError("Missing CA file".to_string()))
+                    .and_then(|ca| tls::client_config(ca, client_cert))
+                    .and_then(|tls| Ok((TlsConnector::from(Arc::new(tls)), config.server_name()?)));
+                match tls {
+                    Ok(tls) => Some(tls),
+                    Err(err) => {
+                        warn!("Tunnel {} disabled: {}", name, err);
+                        return;
+                    }
+                }
+            } else {
+                None
+            };
+
+            loop {
+                heartbeat.beat();
+                let connected =
+                    tokio::time::timeout(RECEIVE_HEARTBEAT, TcpStream::connect(&config.address))
+                        .await;
+                match connected {
+                    Ok(Ok(stream)) => {
+                        let peer = match stream.peer_addr() {
+                            Ok(peer) => peer,
+                            Err(err) => {
+                                warn!("Tunnel {} lost its connection: {}", name, err);
+                                pause(reconnect, &heartbeat).await;
+                                continue;
+                            }
+                        };
+                        let _ = stream.set_nodelay(true);
+                        info!("Tunnel {} connected to {}", name, peer);
+                        let result = match &connector {
+                            Some((connector, server_name)) => {
+                                match connector.connect(server_name.clone(), stream).await {
+                                    Ok(stream) => {
+                                        session(
+                                            stream,
+                                            peer,
+                                            &name,
+                                            &ctx,
+                                            update_interval,
+                                            &heartbeat,
+                                        )
+                                        .await
+                                    }
+                                    Err(err) => Err(io_error(err)),
+                                }
+                            }
+                            None => {
+                                session(stream, peer, &name, &ctx, update_interval, &heartbeat)
+                                    .await
+                            }
+                        };
+                        log_session_end(&name, peer, result);
+                    }
+                    Ok(Err(err)) => {
+                        debug!("Tunnel {} cannot reach {}: {}", name, config.address, err)
+                    }
+                    Err(_) => debug!("Tunnel {} timed out connecting to {}", name, config.address),
+                }
+                pause(reconnect, &heartbeat).await;
+            }
+        }
+        TunnelMode::Listen => {
+            let acceptor = if config.tls.enabled {
+                let tls = match (&config.tls.cert_file, &config.tls.key_file) {
+                    (Some(cert), Some(key)) => {
+                        tls::server_config(cert, key, config.tls.ca_file.as_deref())
+                    }
+                    _ => Err(RustRouteError::ConfigError(
+                        "Missing certificate or key".to_string(),
+                    )),
+                };
+                match tls {
+                    Ok(tls) => Some(TlsAcceptor::from(Arc::new(tls))),
+                    Err(err) => {
+                        warn!("Tunnel {} disabled: {}", name, err);
+                        return;
+                    }
+                }
+            } else {
+                None
+            };
+
+            let listener = loop {
+                match TcpListener::bind(&config.address).await {
+                    Ok(listener) => break listener,
+                    Err(err) => {
+                        warn!(
+                            "Tunnel {} cannot listen on {}: {}",
+                            name, config.address, err
+                        );
+                        pause(reconnect, &heartbeat).await;
+                    }
+                }
+            };
+            info!("Tunnel {} listening on {}", name, config.address);
+
+            let mut liveness = tokio::time::interval(RECEIVE_HEARTBEAT);
+            loop {
+                let (stream, peer) = tokio::select! {
+                    _ = liveness.tick() => {
+                        heartbeat.beat();
+                        continue;
+                    }
+                    accepted = listener.accept() => match accepted {
+                        Ok(accepted) => accepted,
+                        Err(err) => {
+                            warn!("Tunnel {} accept failed: {}", name, err);
+                            continue;
+                        }
+                    },
+                };
+                let _ = stream.set_nodelay(true);
+                info!("Tunnel {} accepted peer {}", name, peer);
+                let result = match &acceptor {
+                    Some(acceptor) => match acceptor.accept(stream).await {
+                        Ok(stream) => {
+                            session(stream, peer, &name, &ctx, update_interval, &heartbeat).await
+                        }
+                        Err(err) => Err(io_error(err)),
+                    },
+                    None => session(stream, peer, &name, &ctx, update_interval, &heartbeat).await,
+                };
+                log_session_end(&name, peer, result);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decisions::DecisionTrace;
+    use crate::events::EventBus;
+    use crate::link_cost::InterfaceCosts;
+    use crate::loop_detector::LoopDetector;
+    use crate::metrics::Metrics;
+    use crate::policy::PolicyHandle;
+    use crate::protocol::RipEntry;
+    use crate::routing_table::{Route, RouteSource, RoutingTable};
+    use crate::watchdog::{Watchdog, WatchdogConfig};
+    use std::collections::HashMap;
+    use std::net::Ipv4Addr;
+    use tokio::sync::RwLock;
+
+    fn context(routes: Vec<Route>) -> RipContext {
+        let mut table = RoutingTable::new();
+        for route in routes {
+            table.add_or_replace(route);
+        }
+        RipContext {
+            routing_table: Arc::new(RwLock::new(table)),
+            neighbors: Arc::new(RwLock::new(HashMap::new())),
+            metrics: Metrics::new(),
+            rip_config: Arc::new(crate::config_manager::RouterConfig::default().rip),
+            events: EventBus::new(16),
+            router_uuid: uuid::Uuid::new_v4(),
+            policy: PolicyHandle::default(),
+            loop_detector: LoopDetector::default(),
+            decisions: DecisionTrace::default(),
+            interface_costs: InterfaceCosts::default(),
+            quiet_until: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn frames_round_trip_and_bound_length() {
+        let packet = RipPacket::new_response(vec![RipEntry {
+            address_family: 2,
+            route_tag: 7,
+            ip_address: Ipv4Addr::new(10, 1, 0, 0),
+            subnet_mask: Ipv4Addr::new(255, 255, 0, 0),
+            next_hop: Ipv4Addr::UNSPECIFIED,
+            metric: 3,
+        }]);
+        let mut buffer = Vec::new();
+        write_frame(&mut buffer, &packet).await.unwrap();
+
+        let mut reader = buffer.as_slice();
+        let decoded = read_frame(&mut reader).await.unwrap().unwrap();
+        assert_eq!(decoded.entries[0].route_tag, 7);
+        assert!(read_frame(&mut reader).await.unwrap().is_none());
+
+        let oversized = ((MAX_FRAME + 1) as u32).to_be_bytes();
+        assert!(read_frame(&mut oversized.as_slice()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn tunnel_peers_exchange_routes() {
+        let probe = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = probe.local_addr().unwrap().to_string();
+        drop(probe);
+
+        let listener_ctx = context(Vec::new());
+        let connector_ctx = context(vec![Route::new(
+            Ipv4Addr::new(172, 16, 5, 0),
+            Ipv4Addr::new(255, 255, 255, 0),
+            Ipv4Addr::UNSPECIFIED,
+            1,
+            "eth1".to_string(),
+            RouteSource::Static,
+            None,
+        )]);
+
+        let watchdog = Watchdog::new(WatchdogConfig::default());
+        let tunnel = |mode| TunnelConfig {
+            name: "wan0".to_string(),
+            enabled: true,
+            mode,
+            address: address.clone(),
+            reconnect_interval: 1,
+            tls: TunnelTlsConfig::default(),
+        };
+        let listen = tokio::spawn(run(
+            tunnel(TunnelMode::Listen),
+            listener_ctx.clone(),
+            Duration::from_secs(30),
+            watchdog.register("tunnel-listen", RECEIVE_HEARTBEAT),
+        ));
+        let connect = tokio::spawn(run(
+            tunnel(TunnelMode::Connect),
+            connector_ctx.clone(),
+            Duration::from_secs(30),
+            watchdog.register("tunnel-connect", RECEIVE_HEARTBEAT),
+        ));
+
+        let mut learned = None;
+        for _ in 0..50 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            learned = listener_ctx
+                .routing_table
+                .read()
+                .await
+                .find_best_route(&Ipv4Addr::new(172, 16, 5, 9))
+                .cloned();
+            if learned.is_some() {
+                break;
+            }
+        }
+        listen.abort();
+        connect.abort();
+
+        let learned = learned.expect("route learned through the tunnel");
+        assert_eq!(learned.interface, "wan0");
+        assert_eq!(learned.metric, 2);
+        assert_eq!(learned.next_hop, Ipv4Addr::LOCALHOST);
+        assert_eq!(listener_ctx.neighbors.read().await.len(), 1);
+    }
+
+    #[test]
+    fn tls_settings_are_checked() {
+        let mut config = TunnelConfig {
+            name: "wan0".to_string(),
+            enabled: true,
+            mode: TunnelMode::Connect,
+            address: "router.example.net:5520".to_string(),
+            reconnect_interval: 5,
+            tls: TunnelTlsConfig {
+                enabled: true,
+                ..TunnelTlsConfig::default()
+            },
+        };
+        assert!(config.validate().is_err());
+        config.tls.ca_file = Some("/etc/rust-route/ca.pem".to_string());
+        assert!(config.validate().is_ok());
+        assert_eq!(
+            config.server_name().unwrap(),
+            ServerName::try_from("router.example.net").unwrap()
+        );
+
+        config.mode = TunnelMode::Listen;
+        assert!(config.validate().is_err());
+    }
+}
@@ -0,0 +1,355 @@
+//! Runtime watchdog tracking liveness of the router's long-running tasks
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::AbortHandle;
+
+use crate::events::{ActivityLevel, EventBus};
+
+/// What the watchdog does when a task stops making progress
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum StallAction {
+    /// Only report the stall through `/readyz` and the activity feed
+    #[default]
+    Report,
+    /// Abort the stalled task and spawn a fresh instance
+    Restart,
+    /// Stop the process so an external supervisor can restart it
+    Exit,
+}
+
+/// Watchdog configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchdogConfig {
+    pub enabled: bool,
+    /// Seconds between liveness checks
+    pub check_interval: u64,
+    /// Minimum seconds without a heartbeat before a task counts as stalled
+    pub stall_timeout: u64,
+    pub action: StallAction,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            check_interval: 10,
+            stall_timeout: 60,
+            action: StallAction::Report,
+        }
+    }
+}
+
+/// Liveness of a single supervised task
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskHealth {
+    pub name: String,
+    pub healthy: bool,
+    pub running: bool,
+    pub seconds_since_heartbeat: u64,
+    pub stall_threshold_seconds: u64,
+    pub heartbeats: u64,
+    pub restarts: u32,
+}
+
+/// Aggregated readiness report served by `/readyz`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadinessReport {
+    pub ready: bool,
+    pub tasks: Vec<TaskHealth>,
+}
+
+type Respawn = Arc<dyn Fn(Heartbeat) -> AbortHandle + Send + Sync>;
+
+struct TaskEntry {
+    last_beat: Instant,
+    expected_interval: Duration,
+    heartbeats: u64,
+    restarts: u32,
+    handle: Option<AbortHandle>,
+    respawn: Option<Respawn>,
+}
+
+/// Handle given to a task so it can signal progress
+#[derive(Clone)]
+pub struct Heartbeat {
+    name: Arc<str>,
+    tasks: Arc<Mutex<HashMap<String, TaskEntry>>>,
+}
+
+impl Heartbeat {
+    /// Record that the task has made progress
+    pub fn beat(&self) {
+        let mut tasks = self.tasks.lock().expect("lock poisoned");
+        if let Some(entry) = tasks.get_mut(self.name.as_ref()) {
+            entry.last_beat = Instant::now();
+            entry.heartbeats += 1;
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Registry of critical tasks and their most recent heartbeats
+#[derive(Clone)]
+pub struct Watchdog {
+    config: WatchdogConfig,
+    tasks: Arc<Mutex<HashMap<String, TaskEntry>>>,
+}
+
+impl Watchdog {
+    pub fn new(config: WatchdogConfig) -> Self {
+        Self {
+            config,
+            tasks: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn config(&self) -> &WatchdogConfig {
+        &self.config
+    }
+
+    /// Register a task that is spawned elsewhere and only reports heartbeats
+    pub fn register(&self, name: &str, expected_interval: Duration) -> Heartbeat {
+        self.insert_entry(name, expected_interval, None, None);
+        self.heartbeat(name)
+    }
+
+    /// Spawn a supervised task; `factory` is invoked again whenever the task is restarted
+    pub fn spawn<F, Fut>(&self, name: &str, expected_interval: Duration, factory: F)
+    where
+        F: Fn(Heartbeat) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let respawn: Respawn =
+            Arc::new(move |heartbeat: Heartbeat| tokio::spawn(factory(heartbeat)).abort_handle());
+        self.insert_entry(name, expected_interval, None, Some(Arc::clone(&respawn)));
+
+        let handle = respawn(self.heartbeat(name));
+        let mut tasks = self.tasks.lock().expect("lock poisoned");
+        if let Some(entry) = tasks.get_mut(name) {
+            entry.handle = Some(handle);
+        }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.tasks.lock().expect("lock poisoned").contains_key(name)
+    }
+
+    /// Drop a task from supervision, aborting it if the watchdog owns it
+    pub fn remove(&self, name: &str) {
+        let mut tasks = self.tasks.lock().expect("lock poisoned");
+        if let Some(entry) = tasks.remove(name) {
+            if let Some(handle) = entry.handle {
+                handle.abort();
+            }
+        }
+    }
+
+    fn heartbeat(&self, name: &str) -> Heartbeat {
+        Heartbeat {
+            name: Arc::from(name),
+            tasks: Arc::clone(&self.tasks),
+        }
+    }
+
+    fn insert_entry(
+        &self,
+        name: &str,
+        expected_interval: Duration,
+        handle: Option<AbortHandle>,
+        respawn: Option<Respawn>,
+    ) {
+        let mut tasks = self.tasks.lock().expect("lock poisoned");
+        let restarts = tasks.get(name).map(|entry| entry.restarts).unwrap_or(0);
+        tasks.insert(
+            name.to_string(),
+            TaskEntry {
+                last_beat: Instant::now(),
+                expected_interval,
+                heartbeats: 0,
+                restarts,
+                handle,
+                respawn,
+            },
+        );
+    }
+
+    fn stall_threshold(&self, entry: &TaskEntry) -> Duration {
+        Duration::from_secs(self.config.stall_timeout).max(entry.expected_interval * 3)
+    }
+
+    /// Current liveness of every registered task, sorted by name
+    pub fn report(&self) -> ReadinessReport {
+        let tasks = self.tasks.lock().expect("lock poisoned");
+        let mut report: Vec<TaskHealth> = tasks
+            .iter()
+            .map(|(name, entry)| {
+                let threshold = self.stall_threshold(entry);
+                let silence = entry.last_beat.elapsed();
+                let running = entry
+                    .handle
+                    .as_ref()
+                    .map(|handle| !handle.is_finished())
+                    .unwrap_or(true);
+                TaskHealth {
+                    name: name.clone(),
+                    healthy: running && silence <= threshold,
+                    running,
+                    seconds_since_heartbeat: silence.as_secs(),
+                    stall_threshold_seconds: threshold.as_secs(),
+                    heartbeats: entry.heartbeats,
+                    restarts: entry.restarts,
+                }
+            })
+            .collect();
+        report.sort_by(|a, b| a.name.cmp(&b.name));
+
+        ReadinessReport {
+            ready: report.iter().all(|task| task.healthy),
+            tasks: report,
+        }
+    }
+
+    /// Check all tasks once, applying the configured stall action.
+    /// Returns the names of the tasks found stalled.
+    pub fn check(&self, events: &EventBus) -> Vec<String> {
+        let stalled: Vec<String> = self
+            .report()
+            .tasks
+            .into_iter()
+            .filter(|task| !task.healthy)
+            .map(|task| task.name)
+            .collect();
+
+        for name in &stalled {
+            log::warn!("Watchdog: task {} has stopped making progress", name);
+            events.publish_activity(
+                ActivityLevel::Error,
+                format!("Watchdog detected stalled task {}", name),
+            );
+
+            if self.config.action == StallAction::Restart {
+                self.restart(name, events);
+            }
+        }
+
+        stalled
+    }
+
+    fn restart(&self, name: &str, events: &EventBus) {
+        let respawn = {
+            let tasks = self.tasks.lock().expect("lock poisoned");
+            tasks.get(name).and_then(|entry| entry.respawn.clone())
+        };
+
+        let Some(respawn) = respawn else {
+            log::warn!("Watchdog: task {} cannot be restarted in-process", name);
+            return;
+        };
+
+        let handle = respawn(self.heartbeat(name));
+        let mut tasks = self.tasks.lock().expect("lock poisoned");
+        if let Some(entry) = tasks.get_mut(name) {
+            if let Some(old) = entry.handle.replace(handle) {
+                old.abort();
+            }
+            entry.last_beat = Instant::now();
+            entry.restarts += 1;
+        }
+        drop(tasks);
+
+        log::info!("Watchdog: restarted task {}", name);
+        events.publish_activity(
+            ActivityLevel::Warn,
+            format!("Watchdog restarted task {}", name),
+        );
+    }
+
+    /// Run the periodic liveness check. Resolves only when the configured
+    /// action is [`StallAction::Exit`] and a task stalls, yielding its name.
+    pub async fn monitor(&self, events: EventBus) -> String {
+        if !self.config.enabled {
+            return std::future::pending().await;
+        }
+
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(self.config.check_interval.max(1)));
+        loop {
+            interval.tick().await;
+            let stalled = self.check(&events);
+            if self.config.action == StallAction::Exit {
+                if let Some(name) = stalled.into_iter().next() {
+                    return name;
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for Watchdog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Watchdog")
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quick_config(action: StallAction) -> WatchdogConfig {
+        WatchdogConfig {
+            enabled: true,
+            check_interval: 1,
+            stall_timeout: 0,
+            action,
+        }
+    }
+
+    #[tokio::test]
+    async fn beating_task_is_healthy() {
+        let watchdog = Watchdog::new(quick_config(StallAction::Report));
+        let heartbeat = watchdog.register("update-loop", Duration::from_millis(50));
+        heartbeat.beat();
+
+        let report = watchdog.report();
+        assert!(report.ready);
+        assert_eq!(report.tasks[0].heartbeats, 1);
+    }
+
+    #[tokio::test]
+    async fn silent_task_is_reported_stalled() {
+        let watchdog = Watchdog::new(quick_config(StallAction::Report));
+        let _heartbeat = watchdog.register("receive-eth0", Duration::from_millis(10));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let events = EventBus::new(8);
+        let stalled = watchdog.check(&events);
+        assert_eq!(stalled, vec!["receive-eth0".to_string()]);
+        assert!(!watchdog.report().ready);
+    }
+
+    #[tokio::test]
+    async fn restart_action_respawns_task() {
+        let watchdog = Watchdog::new(quick_config(StallAction::Restart));
+        watchdog.spawn("stuck", Duration::from_millis(10), |_heartbeat| async {
+            std::future::pending::<()>().await;
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let events = EventBus::new(8);
+        watchdog.check(&events);
+
+        let report = watchdog.report();
+        assert_eq!(report.tasks[0].restarts, 1);
+        assert!(report.tasks[0].running);
+    }
+}
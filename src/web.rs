@@ -1,39 +1,131 @@
 use async_stream::stream;
 use axum::response::sse::{self, KeepAlive};
 use axum::{
-    extract::{Path, Query, State},
-    http::{header, HeaderMap, StatusCode},
-    response::{Html, Json, Sse},
+    extract::{ConnectInfo, Path, Query, Request, State},
+    handler::HandlerWithoutStateExt,
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode, Uri},
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Json, Redirect, Response, Sse},
     routing::{delete, get, post, put},
-    Router as AxumRouter,
+    Extension, Router as AxumRouter,
 };
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto::Builder as AutoBuilder;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, convert::Infallible, net::Ipv4Addr, sync::Arc, time::Duration};
-use tokio::sync::{broadcast::error::RecvError, Mutex, RwLock};
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::{Arc, Once},
+    time::Duration,
+};
+use tokio::sync::{Mutex, RwLock};
+use tokio_rustls::TlsAcceptor;
 use tower_http::{cors::CorsLayer, services::ServeDir};
 
 use crate::{
-    auth::{require_permission, AuthError, AuthManager, LoginRequest, LoginResponse, UserRole},
+    activity::{ActivityLog, ActivityPage, ActivityQuery, ActivityRecord},
+    assets,
+    auth::{
+        require_permission, AuthError, AuthManager, LoginRequest, LoginResponse, StreamTicket,
+        UserRole,
+    },
+    client::ApiClient,
+    clock::{self, ClockStatus},
+    compare::{self, RouteComparison},
     config_manager::{
-        ConfigDiff, ConfigHistoryEntry, ConfigManager, InterfaceConfig, RouterConfig,
+        ConfigDiff, ConfigHistoryEntry, ConfigManager, InterfaceConfig, PendingConfirm,
+        RouterConfig,
     },
-    events::{ActivityLevel, EventBus},
+    consistency::ConsistencyReport,
+    decisions::Decision,
+    diagnostics::Diagnostics,
+    dump::{self, DumpFile},
+    events::{ActivityLevel, Delivery, EventBus, EventClientsReport},
+    export::{self, ExportFormat},
+    fleet::{self, Fleet, FleetEvent, FleetRoutes, FleetStatus},
+    grafana::{self, SearchRequest, TimeSeries},
+    interop::{self, ConfigFormat},
+    last_error::{self, SubsystemError},
+    listen,
+    logging::{self, LogLevel},
     metrics::{Metrics, MetricsSnapshot},
-    router::{Router, RouterStatistics},
-    routing_table::{RouteSource, RoutingTable},
+    metrics_history::{MetricsHistory, MetricsSample},
+    network::{InterfaceCounters, SendMode},
+    packet_auth::AuthFailures,
+    policy::Maintenance,
+    probe::NextHopStats,
+    protocol::PacketCodec,
+    proxy::{self, PathPrefix},
+    rate_limit::{RateLimitConfig, RateLimiter},
+    router::{ClearOutcome, RestartOutcome, Router, RouterStatistics},
+    routing_table::{
+        EqualCostPath, MetricChange, Route, RouteDetail, RouteSnapshot, RouteSource, RoutingTable,
+    },
+    runtime::{self, RuntimeStatus},
+    selftest::{self, SelfTestReport},
+    session::{self, SessionConfig, CSRF_HEADER, SESSION_COOKIE},
+    sysstats,
+    tls::{self, ClientAuth},
+    watchdog::{Heartbeat, Watchdog},
+    RustRouteError, RustRouteResult,
 };
+#[cfg(feature = "debug-inject")]
+use crate::{events::RouteEvent, protocol::RipEntry};
+
+/// Interval at which the web server verifies its own listener is accepting connections
+const WEB_PROBE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Longest confirmation window `PUT /api/config?confirm=` accepts
+const MAX_CONFIRM_WINDOW: Duration = Duration::from_secs(3600);
+
+/// Names from the client certificate, set by the server and never trusted
+/// from the client
+const CLIENT_IDENTITY_HEADER: &str = "x-client-cert-identity";
 
 /// Web interface configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WebConfig {
     pub enabled: bool,
+    /// IPv4 or IPv6 address; `::` listens on both families
     pub bind_address: String,
     pub port: u16,
     pub auth_enabled: bool,
     pub admin_username: String,
     pub admin_password_hash: String,
+    /// Files here take precedence over the copies built into the binary
     #[serde(default = "default_static_dir")]
     pub static_dir: String,
+    #[serde(default)]
+    pub session: SessionConfig,
+    #[serde(default)]
+    pub tls: WebTlsConfig,
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+    /// Path prefix of every route, e.g. `/routers/r1`; empty for the root
+    #[serde(default)]
+    pub base_path: String,
+    /// Reverse proxies whose `X-Forwarded-*` headers are honoured
+    #[serde(default)]
+    pub trusted_proxies: Vec<ipnet::IpNet>,
+}
+
+/// HTTPS settings, under `web.tls`. With `client_ca_file` set, clients may
+/// authenticate with a certificate issued by that CA instead of a token;
+/// `auth.client_certificates` maps certificate names to roles.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WebTlsConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub cert_file: Option<String>,
+    #[serde(default)]
+    pub key_file: Option<String>,
+    #[serde(default)]
+    pub client_ca_file: Option<String>,
+    /// Refuse connections without a client certificate
+    #[serde(default)]
+    pub require_client_cert: bool,
 }
 
 fn default_static_dir() -> String {
@@ -50,6 +142,11 @@ impl Default for WebConfig {
             admin_username: "admin".to_string(),
             admin_password_hash: "$2b$12$dummy.hash.for.default.config".to_string(),
             static_dir: default_static_dir(),
+            session: SessionConfig::default(),
+            tls: WebTlsConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            base_path: String::new(),
+            trusted_proxies: Vec::new(),
         }
     }
 }
@@ -63,6 +160,11 @@ pub struct AppState {
     pub config_manager: Arc<ConfigManager>,
     pub events: EventBus,
     pub auth: Arc<Mutex<Option<AuthManager>>>,
+    pub watchdog: Watchdog,
+    pub activity: Arc<ActivityLog>,
+    pub metrics_history: Arc<MetricsHistory>,
+    pub diagnostics: Diagnostics,
+    pub fleet: Fleet,
 }
 
 #[derive(Debug, Serialize)]
@@ -103,6 +205,38 @@ pub struct RouteInfo {
     pub age_seconds: u64,
     pub source: RouteSource,
     pub learned_from: Option<String>,
+    /// Reverse DNS name of the next hop, with `reverse_dns.enabled`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_hop_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub learned_from_name: Option<String>,
+    #[serde(default)]
+    pub learned_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    pub last_updated: Option<chrono::DateTime<chrono::Utc>>,
+    /// Equal-cost paths in ECMP mode, the installed route's first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub next_hops: Vec<EqualCostPath>,
+}
+
+impl From<RouteSnapshot> for RouteInfo {
+    fn from(entry: RouteSnapshot) -> Self {
+        Self {
+            destination: entry.destination,
+            subnet_mask: entry.subnet_mask,
+            next_hop: entry.next_hop,
+            metric: entry.metric,
+            interface: entry.interface,
+            age_seconds: entry.age_seconds,
+            source: entry.source,
+            learned_from: entry.learned_from,
+            next_hop_name: None,
+            learned_from_name: None,
+            learned_at: entry.learned_at,
+            last_updated: entry.last_updated,
+            next_hops: entry.next_hops,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -110,6 +244,9 @@ pub struct SystemStatus {
     pub uptime_seconds: u64,
     pub version: String,
     pub router_id: String,
+    /// Stable identity derived from `router_id`
+    #[serde(default)]
+    pub router_uuid: uuid::Uuid,
     pub interfaces: Vec<InterfaceInfo>,
     pub route_count: usize,
     pub metrics: MetricsSnapshot,
@@ -117,6 +254,12 @@ pub struct SystemStatus {
     pub cpu_usage: f32,
     pub memory_usage: u64,
     pub auth_required: bool,
+    /// Thread pools and CPU affinity in effect
+    #[serde(default)]
+    pub runtime: Option<RuntimeStatus>,
+    /// Steps of the system clock since startup
+    #[serde(default)]
+    pub clock: ClockStatus,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -130,9 +273,59 @@ pub struct InterfaceInfo {
     pub bytes_received: u64,
 }
 
+/// One interface as configured and, while the router runs it, as bound
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InterfaceDetail {
+    pub name: String,
+    pub address: String,
+    pub secondary_addresses: Vec<ipnet::Ipv4Net>,
+    pub enabled: bool,
+    pub send_mode: SendMode,
+    pub codec: PacketCodec,
+    /// Whether the router has the interface open; the fields below are only
+    /// set when it has
+    pub running: bool,
+    pub bound_address: Option<Ipv4Addr>,
+    pub subnet_mask: Option<Ipv4Addr>,
+    pub socket_bound: bool,
+    pub link_up: Option<bool>,
+    pub mtu: Option<u16>,
+    pub counters: Option<InterfaceCounters>,
+}
+
 #[derive(Debug, Deserialize)]
 struct EventStreamParams {
+    /// Deprecated: tokens in URLs end up in access logs and proxy caches
     token: Option<String>,
+    /// One-time ticket from `POST /api/auth/stream-ticket`
+    ticket: Option<String>,
+    /// Comma-separated event types to receive, e.g. `Route,Neighbor`
+    types: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ActivityParams {
+    level: Option<String>,
+    since: Option<chrono::DateTime<chrono::Utc>>,
+    before: Option<u64>,
+    limit: Option<usize>,
+}
+
+/// Time range and format of a history export
+#[derive(Debug, Deserialize)]
+struct HistoryParams {
+    from: Option<chrono::DateTime<chrono::Utc>>,
+    to: Option<chrono::DateTime<chrono::Utc>>,
+    format: Option<String>,
+}
+
+impl HistoryParams {
+    fn format(&self) -> Result<ExportFormat, StatusCode> {
+        match self.format.as_deref() {
+            Some(value) => ExportFormat::parse(value).ok_or(StatusCode::BAD_REQUEST),
+            None => Ok(ExportFormat::Json),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -144,12 +337,139 @@ pub struct CreateRouteRequest {
     pub interface: String,
 }
 
+/// A prefix in the path, as `/api/routes/{destination}/{mask}`
 #[derive(Debug, Deserialize)]
-pub struct DeleteRouteParams {
+pub struct RoutePathParams {
     pub destination: String,
+    /// Dotted mask or prefix length, so `10.0.0.0/24` can be used as is
     pub mask: String,
 }
 
+impl RoutePathParams {
+    fn parse(&self) -> Option<(Ipv4Addr, Ipv4Addr)> {
+        let destination = self.destination.parse().ok()?;
+        let mask = match self.mask.parse::<u8>() {
+            Ok(len) => ipnet::Ipv4Net::new(destination, len).ok()?.netmask(),
+            Err(_) => self.mask.parse().ok()?,
+        };
+        Some((destination, mask))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RoutePins {
+    /// Every route is held
+    pub frozen: bool,
+    /// Individually pinned prefixes as `destination/mask`
+    pub pinned: Vec<String>,
+}
+
+/// A RIP neighbor with the probe results of its address as a next hop
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NeighborView {
+    pub address: IpAddr,
+    /// Reverse DNS name, with `reverse_dns.enabled`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub interface: Option<String>,
+    pub last_seen_seconds: u64,
+    pub learned_routes: usize,
+    pub probe: Option<NextHopStats>,
+    /// Packets from the neighbor that failed authentication
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_failures: Option<AuthFailures>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LogLevelRequest {
+    /// A level or `RUST_LOG` style directives
+    pub level: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RouteHistory {
+    pub destination: String,
+    pub subnet_mask: String,
+    /// Oldest first
+    pub changes: Vec<MetricChange>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct NamesQuery {
+    /// Override `reverse_dns.enabled` for this request
+    pub resolve: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CompareQuery {
+    /// Base URL of the other router's web API
+    pub peer: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ClearRoutesQuery {
+    pub source: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MaintenanceQuery {
+    /// Metric to advertise; two below infinity unless given, so the router
+    /// stays a last resort
+    pub metric: Option<u32>,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MaintenanceStatus {
+    pub enabled: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maintenance: Option<Maintenance>,
+    /// Interfaces that were sent the table with the new metrics
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interfaces_updated: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateConfigQuery {
+    /// Seconds to wait for `POST /api/config/confirm` before reverting
+    pub confirm: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DecisionsQuery {
+    /// A prefix such as `10.1.0.0/16`, matching it and the prefixes within it
+    pub prefix: Option<String>,
+    pub neighbor: Option<Ipv4Addr>,
+    pub limit: Option<usize>,
+}
+
+/// A synthetic response, processed as if `neighbor` had sent it on `interface`
+#[cfg(feature = "debug-inject")]
+#[derive(Debug, Deserialize)]
+pub struct InjectUpdateRequest {
+    pub neighbor: Ipv4Addr,
+    pub interface: String,
+    pub routes: Vec<InjectedRoute>,
+}
+
+#[cfg(feature = "debug-inject")]
+#[derive(Debug, Deserialize)]
+pub struct InjectedRoute {
+    pub prefix: ipnet::Ipv4Net,
+    /// 16 withdraws the route
+    pub metric: u32,
+    /// The sender when unset
+    #[serde(default)]
+    pub next_hop: Option<Ipv4Addr>,
+    #[serde(default)]
+    pub tag: u16,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    pub format: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 struct ConfigVersionPath {
     version: u32,
@@ -161,24 +481,7 @@ pub struct WebServer {
 }
 
 impl WebServer {
-    pub fn new(
-        router: Arc<RwLock<Router>>,
-        routing_table: Arc<RwLock<RoutingTable>>,
-        metrics: Metrics,
-        config_manager: Arc<ConfigManager>,
-        config: WebConfig,
-        events: EventBus,
-        auth: Arc<Mutex<Option<AuthManager>>>,
-    ) -> Self {
-        let state = AppState {
-            router,
-            routing_table,
-            metrics,
-            config_manager,
-            events,
-            auth,
-        };
-
+    pub fn new(state: AppState, config: WebConfig) -> Self {
         Self { state, config }
     }
 
@@ -189,33 +492,144 @@ impl WebServer {
         }
 
         let app = self.create_app();
-        let bind_addr = format!("{}:{}", self.config.bind_address, self.config.port);
-        log::info!("🌐 Starting web interface on http://{}", bind_addr);
-
-        let listener = tokio::net::TcpListener::bind(&bind_addr).await?;
-        axum::serve(listener, app).await?;
+        let acceptor = self.tls_acceptor()?;
+        let bind_addr = listen::socket_addr(&self.config.bind_address, self.config.port)?;
+        let scheme = if acceptor.is_some() { "https" } else { "http" };
+        log::info!("🌐 Starting web interface on {}://{}", scheme, bind_addr);
+
+        let listener = listen::tcp(bind_addr)?;
+        let heartbeat = self
+            .state
+            .watchdog
+            .register("web-server", WEB_PROBE_INTERVAL);
+        let probe = tokio::spawn(probe_listener(listener.local_addr()?, heartbeat));
+
+        let result = match acceptor {
+            Some(acceptor) => serve_tls(listener, app, acceptor).await,
+            None => {
+                let app = app.into_make_service_with_connect_info::<SocketAddr>();
+                axum::serve(listener, app).await
+            }
+        };
+        probe.abort();
+        result?;
         Ok(())
     }
 
+    fn tls_acceptor(&self) -> RustRouteResult<Option<TlsAcceptor>> {
+        let tls_config = &self.config.tls;
+        if !tls_config.enabled {
+            return Ok(None);
+        }
+        let (Some(cert_file), Some(key_file)) = (&tls_config.cert_file, &tls_config.key_file)
+        else {
+            return Err(RustRouteError::ConfigError(
+                "web.tls requires cert_file and key_file".to_string(),
+            ));
+        };
+        let client_auth = match tls_config.client_ca_file.as_deref() {
+            Some(ca_file) if tls_config.require_client_cert => ClientAuth::Required(ca_file),
+            Some(ca_file) => ClientAuth::Optional(ca_file),
+            None => ClientAuth::None,
+        };
+        let server_config = tls::server_config_with(cert_file, key_file, client_auth)?;
+        Ok(Some(TlsAcceptor::from(Arc::new(server_config))))
+    }
+
     fn create_app(&self) -> AxumRouter {
-        AxumRouter::new()
-            .nest_service("/static", ServeDir::new(&self.config.static_dir))
+        let base_path = proxy::normalize(&self.config.base_path).unwrap_or_else(|err| {
+            log::warn!("Ignoring web.base_path: {}", err);
+            String::new()
+        });
+        let app = self.routes();
+        let app = if base_path.is_empty() {
+            app
+        } else {
+            AxumRouter::new()
+                .route(
+                    "/",
+                    get(|Extension(prefix): Extension<PathPrefix>| async move {
+                        Redirect::temporary(&prefix.0)
+                    }),
+                )
+                .nest(&base_path, app)
+        };
+        app.layer(middleware::from_fn_with_state(
+            Arc::new(ProxySettings {
+                base_path,
+                trusted_proxies: self.config.trusted_proxies.clone(),
+            }),
+            forwarded_headers,
+        ))
+    }
+
+    fn routes(&self) -> AxumRouter {
+        let routes = AxumRouter::new()
+            .nest_service(
+                "/static",
+                ServeDir::new(&self.config.static_dir).fallback(embedded_asset.into_service()),
+            )
             .route("/", get(dashboard_handler))
             .route("/dashboard", get(dashboard_handler))
             .route("/routes", get(routes_page_handler))
             .route("/config", get(config_page_handler))
             .route("/metrics", get(metrics_page_handler))
+            .route("/readyz", get(readiness))
             .route("/api/status", get(get_system_status))
+            .route("/api/status/errors", get(get_status_errors))
             .route("/api/auth/login", post(login))
             .route("/api/auth/logout", post(logout))
+            .route("/api/auth/stream-ticket", post(issue_stream_ticket))
             .route("/api/events", get(events_stream))
+            .route("/api/events/clients", get(get_event_clients))
+            .route("/api/fleet/status", get(get_fleet_status))
+            .route("/api/fleet/routes", get(get_fleet_routes))
+            .route("/api/fleet/events", get(fleet_events_stream))
+            .route("/api/activity", get(get_activity))
+            .route("/api/activity/export", get(export_activity))
             .route("/api/routes", get(get_routes))
             .route("/api/routes", post(create_route))
+            .route("/api/routes/clear", post(clear_routes))
+            .route("/api/routes/pins", get(get_route_pins))
+            .route("/api/routes/compare", get(compare_routes))
+            .route("/api/routes/freeze", post(freeze_routes))
+            .route("/api/routes/freeze", delete(unfreeze_routes))
+            .route("/api/routes/:destination/:mask", get(get_route))
             .route("/api/routes/:destination/:mask", delete(delete_route))
+            .route("/api/routes/:destination/:mask/pin", post(pin_route))
+            .route("/api/routes/:destination/:mask/pin", delete(unpin_route))
+            .route(
+                "/api/routes/:destination/:mask/history",
+                get(get_route_history),
+            )
+            .route("/api/neighbors", get(get_neighbors))
+            .route(
+                "/api/neighbors/auth-failures",
+                get(get_neighbor_auth_failures),
+            )
+            .route("/api/neighbors/:ip/clear", post(clear_neighbor))
             .route("/api/interfaces", get(get_interfaces))
+            .route("/api/interfaces/:name", get(get_interface))
+            .route(
+                "/api/interfaces/:name/counters/clear",
+                post(clear_interface_counters),
+            )
+            .route("/api/interfaces/:name/enable", post(enable_interface))
+            .route("/api/interfaces/:name/disable", post(disable_interface))
             .route("/api/metrics", get(get_metrics))
+            .route("/api/metrics/history", get(get_metrics_history))
+            .route("/api/grafana", get(grafana_health))
+            .route("/api/grafana/search", post(grafana_search))
+            .route("/api/grafana/query", post(grafana_query))
             .route("/api/config", get(get_config))
             .route("/api/config", put(update_config))
+            .route("/api/config/confirm", get(get_pending_confirm))
+            .route("/api/config/confirm", post(confirm_config))
+            .route(
+                "/api/config/profile/:name/activate",
+                post(activate_profile),
+            )
+            .route("/api/export", get(export_config))
             .route("/api/config/history", get(get_config_history))
             .route("/api/config/history/:version/diff", get(get_config_diff))
             .route(
@@ -223,64 +637,378 @@ impl WebServer {
                 post(rollback_config),
             )
             .route("/api/router/restart", post(restart_router))
+            .route("/api/maintenance", get(get_maintenance))
+            .route("/api/maintenance", post(enter_maintenance))
+            .route("/api/maintenance", delete(exit_maintenance))
+            .route("/api/diagnostics/selftest", post(run_selftest))
+            .route("/api/diagnostics/check-table", post(check_table))
+            .route("/api/debug/dump", post(write_state_dump))
+            .route("/api/debug/decisions", get(get_decisions))
+            .route("/api/logging/level", get(get_log_level))
+            .route("/api/logging/level", put(set_log_level));
+        #[cfg(feature = "debug-inject")]
+        let routes = routes.route("/api/debug/inject-update", post(inject_update));
+        routes
+            .layer(middleware::from_fn(record_server_errors))
+            .layer(middleware::from_fn(csrf_guard))
+            .layer(middleware::from_fn_with_state(
+                self.state.clone(),
+                rate_limit,
+            ))
+            .layer(middleware::from_fn(client_identity))
+            .layer(Extension(self.config.session.clone()))
+            .layer(Extension(Arc::new(RateLimiter::new(
+                self.config.rate_limit.clone(),
+            ))))
             .layer(CorsLayer::permissive())
             .with_state(self.state.clone())
     }
 }
 
-async fn dashboard_handler() -> Html<&'static str> {
-    Html(include_str!("../web/templates/dashboard.html"))
+/// The built-in copy of a file missing from `web.static_dir`
+async fn embedded_asset(uri: Uri) -> Response {
+    match assets::get(uri.path()) {
+        Some(asset) => ([(header::CONTENT_TYPE, asset.content_type)], asset.body).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+async fn dashboard_handler(Extension(prefix): Extension<PathPrefix>) -> Html<String> {
+    Html(proxy::render_page(
+        include_str!("../web/templates/dashboard.html"),
+        &prefix,
+    ))
 }
 
-async fn routes_page_handler() -> Html<&'static str> {
-    Html(include_str!("../web/templates/routes.html"))
+async fn routes_page_handler(Extension(prefix): Extension<PathPrefix>) -> Html<String> {
+    Html(proxy::render_page(
+        include_str!("../web/templates/routes.html"),
+        &prefix,
+    ))
 }
 
-async fn config_page_handler() -> Html<&'static str> {
-    Html(include_str!("../web/templates/config.html"))
+async fn config_page_handler(Extension(prefix): Extension<PathPrefix>) -> Html<String> {
+    Html(proxy::render_page(
+        include_str!("../web/templates/config.html"),
+        &prefix,
+    ))
 }
 
-async fn metrics_page_handler() -> Html<&'static str> {
-    Html(include_str!("../web/templates/metrics.html"))
+async fn metrics_page_handler(Extension(prefix): Extension<PathPrefix>) -> Html<String> {
+    Html(proxy::render_page(
+        include_str!("../web/templates/metrics.html"),
+        &prefix,
+    ))
 }
 
 async fn events_stream(
     State(state): State<AppState>,
+    headers: HeaderMap,
     Query(params): Query<EventStreamParams>,
 ) -> Result<Sse<impl futures_core::Stream<Item = Result<sse::Event, Infallible>>>, StatusCode> {
-    ensure_permission(&state, None, params.token.clone(), Some(UserRole::ReadOnly)).await?;
-    let mut receiver = state.events.subscribe();
+    match params.ticket.as_deref() {
+        Some(ticket) => {
+            ensure_ticket_permission(&state, ticket, "GET /api/events", UserRole::ReadOnly).await?
+        }
+        None => {
+            if params.token.is_some() {
+                static DEPRECATED: Once = Once::new();
+                DEPRECATED.call_once(|| {
+                    log::warn!(
+                        "GET /api/events?token= is deprecated; use a ticket from POST /api/auth/stream-ticket or a session cookie"
+                    )
+                });
+            }
+            ensure_permission(
+                &state,
+                Some(&headers),
+                params.token.clone(),
+                "GET /api/events",
+                UserRole::ReadOnly,
+            )
+            .await?
+        }
+    }
+    let filters = params
+        .types
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|kind| !kind.is_empty())
+        .map(str::to_string)
+        .collect();
+    let bus_config = state.config_manager.get_config().await.event_bus;
+    let mut client = state
+        .events
+        .subscribe_client(filters, bus_config.lag_policy);
     let stream = stream! {
-        loop {
-            match receiver.recv().await {
-                Ok(event) => match serde_json::to_string(&event) {
+        while let Some(delivery) = client.next().await {
+            match delivery {
+                Delivery::Event(event) => match serde_json::to_string(&event) {
                     Ok(payload) => yield Ok(sse::Event::default().data(payload)),
                     Err(err) => {
                         log::error!("Failed to serialize event: {}", err);
                     }
                 },
-                Err(RecvError::Lagged(skipped)) => {
+                Delivery::Lagged(skipped) => {
                     log::warn!("Event stream lagged; skipped {} messages", skipped);
+                    let notice = serde_json::json!({ "skipped": skipped });
+                    yield Ok(sse::Event::default().event("lagged").data(notice.to_string()));
                 }
-                Err(RecvError::Closed) => {
-                    break;
-                }
             }
         }
     };
 
     Ok(Sse::new(stream).keep_alive(
         KeepAlive::new()
-            .interval(Duration::from_secs(15))
+            .interval(Duration::from_secs(bus_config.heartbeat_interval.max(1)))
+            .text("keepalive"),
+    ))
+}
+
+async fn fleet_enabled(state: &AppState) -> bool {
+    state.config_manager.get_config().await.fleet.enabled
+}
+
+async fn get_fleet_status(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<FleetStatus>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "GET /api/fleet/status",
+        UserRole::ReadOnly,
+    )
+    .await?;
+    if !fleet_enabled(&state).await {
+        return Ok(Json(ApiResponse::error(
+            "Fleet mode is disabled".to_string(),
+        )));
+    }
+    Ok(Json(ApiResponse::success(fleet::status(&state).await)))
+}
+
+async fn get_fleet_routes(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<FleetRoutes>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "GET /api/fleet/routes",
+        UserRole::ReadOnly,
+    )
+    .await?;
+    if !fleet_enabled(&state).await {
+        return Ok(Json(ApiResponse::error(
+            "Fleet mode is disabled".to_string(),
+        )));
+    }
+    Ok(Json(ApiResponse::success(fleet::routes(&state).await)))
+}
+
+/// Our events and those relayed from the fleet peers, each tagged with its router
+async fn fleet_events_stream(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<EventStreamParams>,
+) -> Result<Sse<impl futures_core::Stream<Item = Result<sse::Event, Infallible>>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        params.token.clone(),
+        "GET /api/fleet/events",
+        UserRole::ReadOnly,
+    )
+    .await?;
+    let config = state.config_manager.get_config().await;
+    if !config.fleet.enabled {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let filters: Vec<String> = params
+        .types
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|kind| !kind.is_empty())
+        .map(str::to_string)
+        .collect();
+    let router_id = config.router_id.clone();
+    let mut local = state
+        .events
+        .subscribe_client(filters.clone(), config.event_bus.lag_policy);
+    let mut relayed = state.fleet.subscribe();
+    let stream = stream! {
+        loop {
+            let event = tokio::select! {
+                delivery = local.next() => match delivery {
+                    Some(Delivery::Event(event)) => match serde_json::to_value(&event) {
+                        Ok(event) => FleetEvent { router: router_id.clone(), event },
+                        Err(err) => {
+                            log::error!("Failed to serialize event: {}", err);
+                            continue;
+                        }
+                    },
+                    Some(Delivery::Lagged(skipped)) => {
+                        log::warn!("Fleet event stream lagged; skipped {} local messages", skipped);
+                        continue;
+                    }
+                    None => break,
+                },
+                peer_event = relayed.recv() => match peer_event {
+                    Ok(event) => event,
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                        log::warn!("Fleet event stream lagged; skipped {} peer messages", skipped);
+                        continue;
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                },
+            };
+            let wanted = filters.is_empty()
+                || event.event["type"]
+                    .as_str()
+                    .is_some_and(|kind| filters.iter().any(|filter| filter == kind));
+            if !wanted {
+                continue;
+            }
+            match serde_json::to_string(&event) {
+                Ok(payload) => yield Ok(sse::Event::default().data(payload)),
+                Err(err) => log::error!("Failed to serialize fleet event: {}", err),
+            }
+        }
+    };
+
+    Ok(Sse::new(stream).keep_alive(
+        KeepAlive::new()
+            .interval(Duration::from_secs(
+                config.event_bus.heartbeat_interval.max(1),
+            ))
             .text("keepalive"),
     ))
 }
 
+async fn get_event_clients(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<EventClientsReport>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "GET /api/events/clients",
+        UserRole::ReadOnly,
+    )
+    .await?;
+    Ok(Json(ApiResponse::success(state.events.clients())))
+}
+
+async fn get_activity(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<ActivityParams>,
+) -> Result<Json<ApiResponse<ActivityPage>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "GET /api/activity",
+        UserRole::ReadOnly,
+    )
+    .await?;
+    let level = match params.level.as_deref().filter(|level| !level.is_empty()) {
+        Some(level) => Some(ActivityLevel::parse(level).ok_or(StatusCode::BAD_REQUEST)?),
+        None => None,
+    };
+    let query = ActivityQuery {
+        level,
+        since: params.since,
+        before: params.before,
+        limit: params.limit.unwrap_or(50),
+    };
+    Ok(Json(ApiResponse::success(state.activity.query(&query))))
+}
+
+/// CSV, or JSON records, of the activity feed between `from` and `to`
+async fn export_activity(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<HistoryParams>,
+) -> Result<Response, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "GET /api/activity/export",
+        UserRole::ReadOnly,
+    )
+    .await?;
+    let format = params.format()?;
+    let records: Vec<ActivityRecord> = state.activity.between(params.from, params.to);
+    Ok(match format {
+        ExportFormat::Csv => csv_response(export::activity_csv(&records)),
+        ExportFormat::Json => Json(ApiResponse::success(records)).into_response(),
+    })
+}
+
+fn csv_response(body: String) -> Response {
+    ([(header::CONTENT_TYPE, "text/csv; charset=utf-8")], body).into_response()
+}
+
+/// Periodically connect to our own listener so the watchdog knows the server accepts clients
+async fn probe_listener(local_addr: SocketAddr, heartbeat: Heartbeat) {
+    let target = match local_addr {
+        SocketAddr::V4(addr) if addr.ip().is_unspecified() => {
+            SocketAddr::new(Ipv4Addr::LOCALHOST.into(), addr.port())
+        }
+        SocketAddr::V6(addr) if addr.ip().is_unspecified() => {
+            SocketAddr::new(Ipv6Addr::LOCALHOST.into(), addr.port())
+        }
+        other => other,
+    };
+
+    let mut interval = tokio::time::interval(WEB_PROBE_INTERVAL);
+    loop {
+        interval.tick().await;
+        match tokio::time::timeout(
+            Duration::from_secs(2),
+            tokio::net::TcpStream::connect(target),
+        )
+        .await
+        {
+            Ok(Ok(_)) => heartbeat.beat(),
+            Ok(Err(err)) => log::warn!("Web server self-probe to {} failed: {}", target, err),
+            Err(_) => log::warn!("Web server self-probe to {} timed out", target),
+        }
+    }
+}
+
+async fn readiness(State(state): State<AppState>) -> impl IntoResponse {
+    let report = state.watchdog.report();
+    let status = if report.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(ApiResponse::success(report)))
+}
+
 async fn get_system_status(
     State(state): State<AppState>,
     headers: HeaderMap,
 ) -> Result<Json<ApiResponse<SystemStatus>>, StatusCode> {
-    ensure_permission(&state, Some(&headers), None, Some(UserRole::ReadOnly)).await?;
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "GET /api/status",
+        UserRole::ReadOnly,
+    )
+    .await?;
     let router_stats = {
         let router_guard = state.router.read().await;
         router_guard.statistics().await
@@ -290,13 +1018,13 @@ async fn get_system_status(
         .metrics
         .snapshot(router_stats.neighbor_count, router_stats.route_count);
 
-    let config = {
+    let (config, router_uuid) = {
         let router_guard = state.router.read().await;
-        router_guard.config_snapshot()
+        (router_guard.config_snapshot(), router_guard.router_uuid())
     };
 
     let interfaces = collect_interface_info(&config.interfaces).await;
-    let cpu_usage = cpu_usage_percent().await;
+    let cpu_usage = sysstats::cpu_usage_percent().await;
 
     let auth_required = config.auth.enabled && config.web.auth_enabled;
     let memory_usage = router_stats.memory_usage;
@@ -305,6 +1033,7 @@ async fn get_system_status(
         uptime_seconds: metrics_snapshot.uptime_seconds,
         version: env!("CARGO_PKG_VERSION").to_string(),
         router_id: config.router_id.clone(),
+        router_uuid,
         interfaces,
         route_count: router_stats.route_count,
         metrics: metrics_snapshot,
@@ -312,33 +1041,125 @@ async fn get_system_status(
         cpu_usage,
         memory_usage,
         auth_required,
+        runtime: runtime::applied().cloned(),
+        clock: clock::status(),
     };
 
     Ok(Json(ApiResponse::success(status)))
 }
 
+async fn get_status_errors(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<Vec<SubsystemError>>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "GET /api/status/errors",
+        UserRole::ReadOnly,
+    )
+    .await?;
+    Ok(Json(ApiResponse::success(last_error::snapshot())))
+}
+
 async fn get_routes(
     State(state): State<AppState>,
     headers: HeaderMap,
+    Query(query): Query<NamesQuery>,
 ) -> Result<Json<ApiResponse<Vec<RouteInfo>>>, StatusCode> {
-    ensure_permission(&state, Some(&headers), None, Some(UserRole::ReadOnly)).await?;
-    let routing_table = state.routing_table.read().await;
-    let routes = routing_table
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "GET /api/routes",
+        UserRole::ReadOnly,
+    )
+    .await?;
+    let mut routes: Vec<RouteInfo> = {
+        let routing_table = state.routing_table.read().await;
+        routing_table
+            .snapshot()
+            .into_iter()
+            .map(RouteInfo::from)
+            .collect()
+    };
+
+    let addresses = routes.iter().flat_map(|route| {
+        [Some(&route.next_hop), route.learned_from.as_ref()]
+            .into_iter()
+            .flatten()
+            .filter_map(|address| address.parse::<IpAddr>().ok())
+            .filter(|address| !address.is_unspecified())
+    });
+    let names = resolve_names(&state, &query, addresses.collect::<Vec<_>>()).await;
+    if !names.is_empty() {
+        let name = |address: &str| {
+            let address: IpAddr = address.parse().ok()?;
+            names.get(&address).cloned()
+        };
+        for route in &mut routes {
+            route.next_hop_name = name(&route.next_hop);
+            route.learned_from_name = route.learned_from.as_deref().and_then(name);
+        }
+    }
+
+    Ok(Json(ApiResponse::success(routes)))
+}
+
+/// Our routes against those of the router whose API is at `peer`
+async fn compare_routes(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<CompareQuery>,
+) -> Result<Json<ApiResponse<RouteComparison>>, StatusCode> {
+    // The router makes a request of its own, so reading is not enough
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "GET /api/routes/compare",
+        UserRole::Operator,
+    )
+    .await?;
+    if let Err(err) = compare::validate_peer_url(&query.peer) {
+        return Ok(Json(ApiResponse::error(err.to_string())));
+    }
+
+    let peer_routes = match compare::fetch_routes(&ApiClient::new(&query.peer, None)).await {
+        Ok(routes) => routes,
+        Err(err) => return Ok(Json(ApiResponse::error(err.to_string()))),
+    };
+    let local: Vec<RouteInfo> = state
+        .routing_table
+        .read()
+        .await
         .snapshot()
         .into_iter()
-        .map(|entry| RouteInfo {
-            destination: entry.destination,
-            subnet_mask: entry.subnet_mask,
-            next_hop: entry.next_hop,
-            metric: entry.metric,
-            interface: entry.interface,
-            age_seconds: entry.age_seconds,
-            source: entry.source,
-            learned_from: entry.learned_from,
-        })
+        .map(RouteInfo::from)
         .collect();
 
-    Ok(Json(ApiResponse::success(routes)))
+    Ok(Json(ApiResponse::success(compare::compare(
+        &local,
+        &peer_routes,
+        &query.peer,
+    ))))
+}
+
+/// Reverse DNS names of `addresses`, unless disabled for this request
+async fn resolve_names(
+    state: &AppState,
+    query: &NamesQuery,
+    addresses: Vec<IpAddr>,
+) -> HashMap<IpAddr, String> {
+    let (mut config, names) = {
+        let router = state.router.read().await;
+        (router.config().reverse_dns.clone(), router.names())
+    };
+    if let Some(resolve) = query.resolve {
+        config.enabled = resolve;
+    }
+    names.names(&config, addresses).await
 }
 
 async fn create_route(
@@ -346,7 +1167,14 @@ async fn create_route(
     headers: HeaderMap,
     Json(request): Json<CreateRouteRequest>,
 ) -> Result<Json<ApiResponse<()>>, StatusCode> {
-    ensure_permission(&state, Some(&headers), None, Some(UserRole::Operator)).await?;
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "POST /api/routes",
+        UserRole::Operator,
+    )
+    .await?;
     let destination: Ipv4Addr = request
         .destination
         .parse()
@@ -368,17 +1196,63 @@ async fn create_route(
     Ok(Json(ApiResponse::success(())))
 }
 
+async fn get_route(
+    Path(params): Path<RoutePathParams>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<RouteDetail>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "GET /api/routes/:destination/:mask",
+        UserRole::ReadOnly,
+    )
+    .await?;
+    let (destination, mask) = params.parse().ok_or(StatusCode::BAD_REQUEST)?;
+    match state.routing_table.read().await.detail(destination, mask) {
+        Some(detail) => Ok(Json(ApiResponse::success(detail))),
+        None => Ok(Json(ApiResponse::error("Route not found".to_string()))),
+    }
+}
+
+async fn get_route_history(
+    Path(params): Path<RoutePathParams>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<RouteHistory>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "GET /api/routes/:destination/:mask/history",
+        UserRole::ReadOnly,
+    )
+    .await?;
+    let (destination, mask) = params.parse().ok_or(StatusCode::BAD_REQUEST)?;
+    let changes = state.routing_table.read().await.history(destination, mask);
+
+    Ok(Json(ApiResponse::success(RouteHistory {
+        destination: destination.to_string(),
+        subnet_mask: mask.to_string(),
+        changes,
+    })))
+}
+
 async fn delete_route(
-    Path(params): Path<DeleteRouteParams>,
+    Path(params): Path<RoutePathParams>,
     State(state): State<AppState>,
     headers: HeaderMap,
 ) -> Result<Json<ApiResponse<()>>, StatusCode> {
-    ensure_permission(&state, Some(&headers), None, Some(UserRole::Operator)).await?;
-    let destination: Ipv4Addr = params
-        .destination
-        .parse()
-        .map_err(|_| StatusCode::BAD_REQUEST)?;
-    let mask: Ipv4Addr = params.mask.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "DELETE /api/routes/:destination/:mask",
+        UserRole::Operator,
+    )
+    .await?;
+    let (destination, mask) = params.parse().ok_or(StatusCode::BAD_REQUEST)?;
 
     let mut table = state.routing_table.write().await;
     if !table.remove_route(destination, mask) {
@@ -392,44 +1266,675 @@ async fn delete_route(
     Ok(Json(ApiResponse::success(())))
 }
 
-async fn get_interfaces(
+async fn get_route_pins(
     State(state): State<AppState>,
     headers: HeaderMap,
-) -> Result<Json<ApiResponse<Vec<InterfaceInfo>>>, StatusCode> {
-    ensure_permission(&state, Some(&headers), None, Some(UserRole::ReadOnly)).await?;
-    let config = {
-        let router = state.router.read().await;
-        router.config_snapshot()
-    };
-
-    let interfaces = collect_interface_info(&config.interfaces).await;
-    Ok(Json(ApiResponse::success(interfaces)))
+) -> Result<Json<ApiResponse<RoutePins>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "GET /api/routes/pins",
+        UserRole::ReadOnly,
+    )
+    .await?;
+    let table = state.routing_table.read().await;
+
+    Ok(Json(ApiResponse::success(RoutePins {
+        frozen: table.is_frozen(),
+        pinned: table.pinned(),
+    })))
 }
 
-async fn get_metrics(
+async fn pin_route(
+    Path(params): Path<RoutePathParams>,
     State(state): State<AppState>,
     headers: HeaderMap,
-) -> Result<Json<ApiResponse<MetricsSnapshot>>, StatusCode> {
-    ensure_permission(&state, Some(&headers), None, Some(UserRole::ReadOnly)).await?;
-    let table_count = state.routing_table.read().await.route_count();
-    let metric_snapshot = state.metrics.snapshot(0, table_count);
-    Ok(Json(ApiResponse::success(metric_snapshot)))
+) -> Result<Json<ApiResponse<()>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "POST /api/routes/:destination/:mask/pin",
+        UserRole::Operator,
+    )
+    .await?;
+    let (destination, mask) = params.parse().ok_or(StatusCode::BAD_REQUEST)?;
+
+    if !state.routing_table.write().await.pin(destination, mask) {
+        return Ok(Json(ApiResponse::error("Route not found".to_string())));
+    }
+    state.events.publish_activity(
+        ActivityLevel::Warn,
+        format!(
+            "Pinned route {}/{}; updates for it are ignored until unpinned",
+            destination,
+            u32::from(mask).count_ones()
+        ),
+    );
+
+    Ok(Json(ApiResponse::success(())))
+}
+
+async fn unpin_route(
+    Path(params): Path<RoutePathParams>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<()>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "DELETE /api/routes/:destination/:mask/pin",
+        UserRole::Operator,
+    )
+    .await?;
+    let (destination, mask) = params.parse().ok_or(StatusCode::BAD_REQUEST)?;
+
+    if !state.routing_table.write().await.unpin(destination, mask) {
+        return Ok(Json(ApiResponse::error("Route is not pinned".to_string())));
+    }
+    state.events.publish_activity(
+        ActivityLevel::Info,
+        format!(
+            "Unpinned route {}/{}",
+            destination,
+            u32::from(mask).count_ones()
+        ),
+    );
+
+    Ok(Json(ApiResponse::success(())))
+}
+
+async fn freeze_routes(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<()>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "POST /api/routes/freeze",
+        UserRole::Operator,
+    )
+    .await?;
+    state.routing_table.write().await.set_frozen(true);
+    state.events.publish_activity(
+        ActivityLevel::Warn,
+        "Routing table frozen; route updates and timeouts are suspended".to_string(),
+    );
+
+    Ok(Json(ApiResponse::success(())))
+}
+
+async fn unfreeze_routes(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<()>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "DELETE /api/routes/freeze",
+        UserRole::Operator,
+    )
+    .await?;
+    state.routing_table.write().await.set_frozen(false);
+    state
+        .events
+        .publish_activity(ActivityLevel::Info, "Routing table unfrozen".to_string());
+
+    Ok(Json(ApiResponse::success(())))
+}
+
+async fn get_maintenance(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<MaintenanceStatus>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "GET /api/maintenance",
+        UserRole::ReadOnly,
+    )
+    .await?;
+    let maintenance = state.router.read().await.maintenance();
+    Ok(Json(ApiResponse::success(MaintenanceStatus {
+        enabled: maintenance.is_some(),
+        maintenance,
+        interfaces_updated: None,
+    })))
+}
+
+async fn enter_maintenance(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<MaintenanceQuery>,
+) -> Result<Json<ApiResponse<MaintenanceStatus>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "POST /api/maintenance",
+        UserRole::Operator,
+    )
+    .await?;
+    let router = state.router.read().await;
+    let infinity = router.rip_config().infinity_metric;
+    let metric = query
+        .metric
+        .unwrap_or_else(|| Maintenance::default_metric(infinity));
+    if metric == 0 || metric > infinity {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let maintenance = Maintenance {
+        metric,
+        since: chrono::Utc::now(),
+        reason: query.reason.filter(|reason| !reason.trim().is_empty()),
+    };
+    let updated = router.set_maintenance(Some(maintenance.clone())).await;
+    drop(router);
+
+    state.events.publish_activity(
+        ActivityLevel::Warn,
+        format!(
+            "Entered maintenance mode: advertising every route at metric {}{}",
+            metric,
+            maintenance
+                .reason
+                .as_deref()
+                .map(|reason| format!(" ({})", reason))
+                .unwrap_or_default()
+        ),
+    );
+    Ok(Json(ApiResponse::success(MaintenanceStatus {
+        enabled: true,
+        maintenance: Some(maintenance),
+        interfaces_updated: Some(updated),
+    })))
+}
+
+async fn exit_maintenance(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<MaintenanceStatus>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "DELETE /api/maintenance",
+        UserRole::Operator,
+    )
+    .await?;
+    let router = state.router.read().await;
+    if router.maintenance().is_none() {
+        return Ok(Json(ApiResponse::success(MaintenanceStatus {
+            enabled: false,
+            maintenance: None,
+            interfaces_updated: Some(0),
+        })));
+    }
+    let updated = router.set_maintenance(None).await;
+    drop(router);
+
+    state.events.publish_activity(
+        ActivityLevel::Info,
+        "Left maintenance mode; advertising normal metrics".to_string(),
+    );
+    Ok(Json(ApiResponse::success(MaintenanceStatus {
+        enabled: false,
+        maintenance: None,
+        interfaces_updated: Some(updated),
+    })))
+}
+
+async fn clear_routes(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<ClearRoutesQuery>,
+) -> Result<Json<ApiResponse<ClearOutcome>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "POST /api/routes/clear",
+        UserRole::Operator,
+    )
+    .await?;
+    let source = match query.source.as_deref() {
+        Some(value) => RouteSource::parse(value).ok_or(StatusCode::BAD_REQUEST)?,
+        None => RouteSource::Dynamic,
+    };
+
+    let outcome = state
+        .router
+        .read()
+        .await
+        .clear_routes(source)
+        .await
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    state.events.publish_activity(
+        ActivityLevel::Info,
+        format!(
+            "Cleared {} {} routes via API",
+            outcome.routes_removed,
+            source.as_str()
+        ),
+    );
+
+    Ok(Json(ApiResponse::success(outcome)))
+}
+
+async fn get_neighbors(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<NamesQuery>,
+) -> Result<Json<ApiResponse<Vec<NeighborView>>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "GET /api/neighbors",
+        UserRole::ReadOnly,
+    )
+    .await?;
+    let (neighbors, probes, packet_auth) = {
+        let router = state.router.read().await;
+        (router.neighbors(), router.probes(), router.packet_auth())
+    };
+    let mut views: Vec<NeighborView> = neighbors
+        .read()
+        .await
+        .values()
+        .map(|neighbor| NeighborView {
+            address: neighbor.address,
+            name: None,
+            interface: neighbor.interface.clone(),
+            last_seen_seconds: neighbor.last_seen.elapsed().as_secs(),
+            learned_routes: neighbor.learned_routes,
+            probe: match neighbor.address {
+                IpAddr::V4(address) => probes.get(address),
+                IpAddr::V6(_) => None,
+            },
+            auth_failures: packet_auth.failures_of(neighbor.address),
+        })
+        .collect();
+    views.sort_by_key(|view| view.address);
+    let names = resolve_names(
+        &state,
+        &query,
+        views.iter().map(|view| view.address).collect(),
+    )
+    .await;
+    for view in &mut views {
+        view.name = names.get(&view.address).cloned();
+    }
+    Ok(Json(ApiResponse::success(views)))
+}
+
+/// Every source whose packets failed authentication, neighbor or not
+async fn get_neighbor_auth_failures(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<Vec<AuthFailures>>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "GET /api/neighbors/auth-failures",
+        UserRole::ReadOnly,
+    )
+    .await?;
+    let packet_auth = state.router.read().await.packet_auth();
+    Ok(Json(ApiResponse::success(packet_auth.all_failures())))
+}
+
+async fn clear_neighbor(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(ip): Path<String>,
+) -> Result<Json<ApiResponse<ClearOutcome>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "POST /api/neighbors/:ip/clear",
+        UserRole::Operator,
+    )
+    .await?;
+    let address: IpAddr = ip.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    let outcome = state
+        .router
+        .read()
+        .await
+        .clear_neighbor(address)
+        .await
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    state.events.publish_activity(
+        ActivityLevel::Info,
+        format!(
+            "Cleared neighbor {} and {} learned routes via API",
+            address, outcome.routes_removed
+        ),
+    );
+
+    Ok(Json(ApiResponse::success(outcome)))
+}
+
+async fn get_interfaces(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<Vec<InterfaceInfo>>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "GET /api/interfaces",
+        UserRole::ReadOnly,
+    )
+    .await?;
+    let config = {
+        let router = state.router.read().await;
+        router.config_snapshot()
+    };
+
+    let interfaces = collect_interface_info(&config.interfaces).await;
+    Ok(Json(ApiResponse::success(interfaces)))
+}
+
+async fn get_interface(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+) -> Result<Json<ApiResponse<InterfaceDetail>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "GET /api/interfaces/:name",
+        UserRole::ReadOnly,
+    )
+    .await?;
+    let (config, running) = {
+        let router = state.router.read().await;
+        (router.config_snapshot(), router.network_interfaces())
+    };
+    let iface = config
+        .interfaces
+        .into_iter()
+        .find(|iface| iface.name == name)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let live = running.iter().find(|live| live.config.name == name);
+
+    Ok(Json(ApiResponse::success(InterfaceDetail {
+        name: iface.name,
+        address: iface.address,
+        secondary_addresses: iface.secondary_addresses,
+        enabled: iface.enabled,
+        send_mode: iface.send_mode,
+        codec: iface.codec,
+        running: live.is_some(),
+        bound_address: live.map(|live| live.ip_address()),
+        subnet_mask: live.map(|live| live.subnet_mask()),
+        socket_bound: live.is_some_and(|live| live.is_bound()),
+        link_up: live.map(|live| live.is_link_up()),
+        mtu: live.map(|live| live.config.mtu),
+        counters: live.map(|live| live.counters()),
+    })))
+}
+
+async fn clear_interface_counters(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+) -> Result<Json<ApiResponse<()>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "POST /api/interfaces/:name/counters/clear",
+        UserRole::Operator,
+    )
+    .await?;
+    let live = state
+        .router
+        .read()
+        .await
+        .network_interfaces()
+        .into_iter()
+        .find(|live| live.config.name == name)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    live.clear_counters();
+
+    state.events.publish_activity(
+        ActivityLevel::Info,
+        format!("Counters of interface {} cleared", name),
+    );
+    Ok(Json(ApiResponse::success(())))
+}
+
+async fn enable_interface(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+) -> Result<Json<ApiResponse<()>>, StatusCode> {
+    set_interface_admin_state(state, headers, name, true).await
+}
+
+async fn disable_interface(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+) -> Result<Json<ApiResponse<()>>, StatusCode> {
+    set_interface_admin_state(state, headers, name, false).await
+}
+
+async fn set_interface_admin_state(
+    state: AppState,
+    headers: HeaderMap,
+    name: String,
+    enabled: bool,
+) -> Result<Json<ApiResponse<()>>, StatusCode> {
+    let endpoint = if enabled {
+        "POST /api/interfaces/:name/enable"
+    } else {
+        "POST /api/interfaces/:name/disable"
+    };
+    ensure_permission(&state, Some(&headers), None, endpoint, UserRole::Operator).await?;
+
+    let found = state
+        .config_manager
+        .set_interface_enabled(&name, enabled)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to update interface {}: {}", name, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    if !found {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    state.events.publish_activity(
+        ActivityLevel::Info,
+        format!(
+            "Interface {} {} via API",
+            name,
+            if enabled { "enabled" } else { "disabled" }
+        ),
+    );
+
+    Ok(Json(ApiResponse::success(())))
+}
+
+async fn get_metrics(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<MetricsSnapshot>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "GET /api/metrics",
+        UserRole::ReadOnly,
+    )
+    .await?;
+    let (table_count, table_memory) = {
+        let table = state.routing_table.read().await;
+        (table.route_count(), table.memory_estimate())
+    };
+    let mut metric_snapshot = state.metrics.snapshot(0, table_count);
+    metric_snapshot.table_memory = Some(table_memory);
+    metric_snapshot.runtime = Some(Box::new(
+        state.diagnostics.collect(&state.watchdog, &state.events),
+    ));
+    let (probes_enabled, probes) = {
+        let router = state.router.read().await;
+        (router.config().probes.enabled, router.probes())
+    };
+    metric_snapshot.next_hops = probes_enabled.then(|| probes.all().into_boxed_slice());
+    Ok(Json(ApiResponse::success(metric_snapshot)))
+}
+
+/// CSV, or JSON samples, of the metrics history between `from` and `to`
+async fn get_metrics_history(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<HistoryParams>,
+) -> Result<Response, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "GET /api/metrics/history",
+        UserRole::ReadOnly,
+    )
+    .await?;
+    let format = params.format()?;
+    let samples: Vec<MetricsSample> = state.metrics_history.between(params.from, params.to);
+    Ok(match format {
+        ExportFormat::Csv => csv_response(export::metrics_csv(&samples)),
+        ExportFormat::Json => Json(ApiResponse::success(samples)).into_response(),
+    })
+}
+
+/// Connection test of the Grafana JSON datasource
+async fn grafana_health(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<()>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "GET /api/grafana",
+        UserRole::ReadOnly,
+    )
+    .await?;
+    Ok(Json(ApiResponse::success(())))
+}
+
+/// Series names for the Grafana query editor; a bare array as Grafana expects
+async fn grafana_search(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Option<Json<SearchRequest>>,
+) -> Result<Json<Vec<&'static str>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "POST /api/grafana/search",
+        UserRole::ReadOnly,
+    )
+    .await?;
+    let request = body.map(|Json(request)| request).unwrap_or_default();
+    Ok(Json(grafana::search(&request)))
+}
+
+/// Time series from the metrics history for a Grafana panel
+async fn grafana_query(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<grafana::QueryRequest>,
+) -> Result<Json<Vec<TimeSeries>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "POST /api/grafana/query",
+        UserRole::ReadOnly,
+    )
+    .await?;
+    let samples = state
+        .metrics_history
+        .between(Some(request.range.from), Some(request.range.to));
+    Ok(Json(grafana::query(&request, &samples)))
 }
 
 async fn get_config(
     State(state): State<AppState>,
     headers: HeaderMap,
 ) -> Result<Json<ApiResponse<RouterConfig>>, StatusCode> {
-    ensure_permission(&state, Some(&headers), None, Some(UserRole::Operator)).await?;
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "GET /api/config",
+        UserRole::Operator,
+    )
+    .await?;
     let config = state.config_manager.get_config().await;
     Ok(Json(ApiResponse::success(config)))
 }
 
+async fn export_config(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<ExportQuery>,
+) -> Result<impl IntoResponse, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "GET /api/export",
+        UserRole::Operator,
+    )
+    .await?;
+    let format = match query.format.as_deref() {
+        Some(value) => ConfigFormat::parse(value).ok_or(StatusCode::BAD_REQUEST)?,
+        None => ConfigFormat::Frr,
+    };
+
+    let config = state.router.read().await.config_snapshot();
+    let routes: Vec<Route> = state
+        .routing_table
+        .read()
+        .await
+        .get_all_routes()
+        .into_iter()
+        .cloned()
+        .collect();
+
+    let body = interop::export_config(&config, &routes, format);
+    Ok(([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], body))
+}
+
 async fn get_config_history(
     State(state): State<AppState>,
     headers: HeaderMap,
 ) -> Result<Json<ApiResponse<Vec<ConfigHistoryEntry>>>, StatusCode> {
-    ensure_permission(&state, Some(&headers), None, Some(UserRole::Operator)).await?;
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "GET /api/config/history",
+        UserRole::Operator,
+    )
+    .await?;
     let history = state.config_manager.list_history().await;
     Ok(Json(ApiResponse::success(history)))
 }
@@ -439,7 +1944,14 @@ async fn get_config_diff(
     State(state): State<AppState>,
     headers: HeaderMap,
 ) -> Result<Json<ApiResponse<ConfigDiff>>, StatusCode> {
-    ensure_permission(&state, Some(&headers), None, Some(UserRole::Operator)).await?;
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "GET /api/config/history/:version/diff",
+        UserRole::Operator,
+    )
+    .await?;
     match state.config_manager.diff(path.version).await {
         Ok(diff) => Ok(Json(ApiResponse::success(diff))),
         Err(err) => {
@@ -463,7 +1975,14 @@ async fn rollback_config(
     State(state): State<AppState>,
     headers: HeaderMap,
 ) -> Result<Json<ApiResponse<()>>, StatusCode> {
-    ensure_permission(&state, Some(&headers), None, Some(UserRole::Admin)).await?;
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "POST /api/config/history/:version/rollback",
+        UserRole::Admin,
+    )
+    .await?;
     match state.config_manager.rollback_to(path.version).await {
         Ok(_) => {
             state.events.publish_activity(
@@ -491,71 +2010,449 @@ async fn rollback_config(
 async fn update_config(
     State(state): State<AppState>,
     headers: HeaderMap,
+    Query(query): Query<UpdateConfigQuery>,
     Json(request): Json<RouterConfig>,
-) -> Result<Json<ApiResponse<()>>, StatusCode> {
-    ensure_permission(&state, Some(&headers), None, Some(UserRole::Admin)).await?;
-    state
+) -> Result<Json<ApiResponse<Option<PendingConfirm>>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "PUT /api/config",
+        UserRole::Admin,
+    )
+    .await?;
+    let pending = apply_config(&state, request, query.confirm, "Configuration updated via API")
+        .await?;
+    Ok(Json(ApiResponse::success(pending)))
+}
+
+/// Lay a named profile over the running configuration, optionally with a
+/// confirmation window like `PUT /api/config`
+async fn activate_profile(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    Query(query): Query<UpdateConfigQuery>,
+) -> Result<Json<ApiResponse<Option<PendingConfirm>>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "POST /api/config/profile/:name/activate",
+        UserRole::Admin,
+    )
+    .await?;
+    let current = state.config_manager.get_config().await;
+    if !current.profiles.contains_key(&name) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let config = current.with_profile(&name).map_err(|e| {
+        log::warn!("Cannot activate profile {}: {:#}", name, e);
+        StatusCode::BAD_REQUEST
+    })?;
+    let pending = apply_config(
+        &state,
+        config,
+        query.confirm,
+        &format!("Profile {} activated via API", name),
+    )
+    .await?;
+    Ok(Json(ApiResponse::success(pending)))
+}
+
+/// Apply `config`, or with `confirm` seconds apply it and revert unless it
+/// is confirmed in time
+async fn apply_config(
+    state: &AppState,
+    config: RouterConfig,
+    confirm: Option<u64>,
+    activity: &str,
+) -> Result<Option<PendingConfirm>, StatusCode> {
+    let failed = |e: anyhow::Error| {
+        log::error!("Failed to update config via API: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+
+    let Some(seconds) = confirm else {
+        state
+            .config_manager
+            .update_config(config)
+            .await
+            .map_err(failed)?;
+        // A plain update shows the operator still has access
+        state.config_manager.confirm().await;
+        state
+            .events
+            .publish_activity(ActivityLevel::Info, activity.to_string());
+        return Ok(None);
+    };
+
+    if !(1..=MAX_CONFIRM_WINDOW.as_secs()).contains(&seconds) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let window = Duration::from_secs(seconds);
+    let pending = state
         .config_manager
-        .update_config(request)
+        .update_config_confirmed(config, window)
         .await
-        .map_err(|e| {
-            log::error!("Failed to update config via API: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        .map_err(failed)?;
+    state.events.publish_activity(
+        ActivityLevel::Info,
+        format!(
+            "Configuration version {} applied; reverts to version {} unless confirmed within {}s",
+            pending.version, pending.rollback_version, seconds
+        ),
+    );
 
-    state
-        .events
-        .publish_activity(ActivityLevel::Info, "Configuration updated via API");
+    let (manager, events, version) = (
+        state.config_manager.clone(),
+        state.events.clone(),
+        pending.version,
+    );
+    tokio::spawn(async move {
+        tokio::time::sleep(window).await;
+        match manager.revert_unconfirmed(version).await {
+            Ok(Some(reverted)) => events.publish_activity(
+                ActivityLevel::Warn,
+                format!(
+                    "Configuration version {} was not confirmed; reverted to version {}",
+                    reverted.version, reverted.rollback_version
+                ),
+            ),
+            Ok(None) => {}
+            Err(e) => log::error!("Failed to revert unconfirmed configuration: {}", e),
+        }
+    });
 
-    Ok(Json(ApiResponse::success(())))
+    Ok(Some(pending))
+}
+
+async fn get_pending_confirm(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<Option<PendingConfirm>>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "GET /api/config/confirm",
+        UserRole::ReadOnly,
+    )
+    .await?;
+    let pending = state.config_manager.pending_confirm().await;
+    Ok(Json(ApiResponse::success(pending)))
+}
+
+async fn confirm_config(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<PendingConfirm>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "POST /api/config/confirm",
+        UserRole::Admin,
+    )
+    .await?;
+    let Some(confirmed) = state.config_manager.confirm().await else {
+        return Ok(Json(ApiResponse::error(
+            "No configuration change awaits confirmation".to_string(),
+        )));
+    };
+    state.events.publish_activity(
+        ActivityLevel::Info,
+        format!("Configuration version {} confirmed", confirmed.version),
+    );
+    Ok(Json(ApiResponse::success(confirmed)))
+}
+
+async fn run_selftest(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<SelfTestReport>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "POST /api/diagnostics/selftest",
+        UserRole::ReadOnly,
+    )
+    .await?;
+    let (config, interfaces) = {
+        let router = state.router.read().await;
+        (router.config_snapshot(), router.network_interfaces())
+    };
+    let report = selftest::run(&config, &interfaces);
+    for check in report.failures() {
+        state.events.publish_activity(
+            ActivityLevel::Warn,
+            format!("Self-test {} failed: {}", check.name, check.detail),
+        );
+    }
+    Ok(Json(ApiResponse::success(report)))
+}
+
+/// Audit the routing table now instead of waiting for the periodic check
+async fn check_table(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<ConsistencyReport>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "POST /api/diagnostics/check-table",
+        UserRole::ReadOnly,
+    )
+    .await?;
+    match state.router.read().await.check_consistency().await {
+        Ok(report) => Ok(Json(ApiResponse::success(report))),
+        Err(err) => Ok(Json(ApiResponse::error(err.to_string()))),
+    }
+}
+
+/// Write a diagnostic dump on the router's host, as `SIGUSR1` does
+async fn write_state_dump(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<DumpFile>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "POST /api/debug/dump",
+        UserRole::Admin,
+    )
+    .await?;
+    match dump::dump(&state).await {
+        Ok(file) => Ok(Json(ApiResponse::success(file))),
+        Err(err) => Ok(Json(ApiResponse::error(format!(
+            "Failed to write state dump: {}",
+            err
+        )))),
+    }
+}
+
+async fn get_decisions(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<DecisionsQuery>,
+) -> Result<Json<ApiResponse<Vec<Decision>>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "GET /api/debug/decisions",
+        UserRole::ReadOnly,
+    )
+    .await?;
+    let prefix = match query.prefix.as_deref() {
+        Some(prefix) => Some(
+            prefix
+                .parse::<ipnet::Ipv4Net>()
+                .or_else(|_| prefix.parse::<Ipv4Addr>().map(ipnet::Ipv4Net::from))
+                .map_err(|_| StatusCode::BAD_REQUEST)?
+                .trunc(),
+        ),
+        None => None,
+    };
+    let decisions = state.router.read().await.decisions();
+    if !decisions.is_enabled() {
+        return Ok(Json(ApiResponse::error(
+            "Decision trace is off; set rip.decision_trace.enabled".to_string(),
+        )));
+    }
+    let limit = query.limit.unwrap_or(100);
+    Ok(Json(ApiResponse::success(decisions.query(
+        prefix,
+        query.neighbor,
+        limit,
+    ))))
+}
+
+/// Feed a synthetic response into the router, for demos and tests
+#[cfg(feature = "debug-inject")]
+async fn inject_update(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<InjectUpdateRequest>,
+) -> Result<Json<ApiResponse<Vec<RouteEvent>>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "POST /api/debug/inject-update",
+        UserRole::Admin,
+    )
+    .await?;
+    let entries = request
+        .routes
+        .iter()
+        .map(|route| RipEntry {
+            address_family: 2,
+            route_tag: route.tag,
+            ip_address: route.prefix.network(),
+            subnet_mask: route.prefix.netmask(),
+            next_hop: route.next_hop.unwrap_or(Ipv4Addr::UNSPECIFIED),
+            metric: route.metric,
+        })
+        .collect();
+    let result = state
+        .router
+        .read()
+        .await
+        .inject_update(
+            state.events.clone(),
+            &request.interface,
+            request.neighbor,
+            entries,
+        )
+        .await;
+    match result {
+        Ok(routes) => {
+            state.events.publish_activity(
+                ActivityLevel::Info,
+                format!(
+                    "Injected an update from {} on {}: {} of {} routes changed",
+                    request.neighbor,
+                    request.interface,
+                    routes.len(),
+                    request.routes.len()
+                ),
+            );
+            Ok(Json(ApiResponse::success(
+                routes.iter().map(RouteEvent::from_route).collect(),
+            )))
+        }
+        Err(err) => Ok(Json(ApiResponse::error(err.to_string()))),
+    }
+}
+
+async fn get_log_level(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<LogLevel>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "GET /api/logging/level",
+        UserRole::ReadOnly,
+    )
+    .await?;
+    Ok(Json(ApiResponse::success(logging::current())))
+}
+
+/// Change the log filter until the next change or restart; the
+/// configuration file is left alone
+async fn set_log_level(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<LogLevelRequest>,
+) -> Result<Json<ApiResponse<LogLevel>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "PUT /api/logging/level",
+        UserRole::Operator,
+    )
+    .await?;
+    match logging::set_level(&request.level) {
+        Ok(level) => {
+            state.events.publish_activity(
+                ActivityLevel::Info,
+                format!("Log level set to {} via API", level.level),
+            );
+            Ok(Json(ApiResponse::success(level)))
+        }
+        Err(err) => Ok(Json(ApiResponse::error(err.to_string()))),
+    }
 }
 
 async fn restart_router(
     State(state): State<AppState>,
     headers: HeaderMap,
-) -> Result<Json<ApiResponse<()>>, StatusCode> {
-    ensure_permission(&state, Some(&headers), None, Some(UserRole::Admin)).await?;
+) -> Result<Json<ApiResponse<RestartOutcome>>, StatusCode> {
+    ensure_permission(
+        &state,
+        Some(&headers),
+        None,
+        "POST /api/router/restart",
+        UserRole::Admin,
+    )
+    .await?;
     let mut router = state.router.write().await;
-    router.restart().await.map_err(|e| {
+    let outcome = router.restart().await.map_err(|e| {
         log::error!("Failed to restart router: {}", e);
         StatusCode::INTERNAL_SERVER_ERROR
     })?;
+    drop(router);
 
-    Ok(Json(ApiResponse::success(())))
+    state.events.publish_activity(
+        ActivityLevel::Warn,
+        format!(
+            "Router restarted via API: {} interfaces reopened, {} neighbors cleared",
+            outcome.interfaces, outcome.neighbors_cleared
+        ),
+    );
+    Ok(Json(ApiResponse::success(outcome)))
 }
 
 async fn login(
     State(state): State<AppState>,
+    Extension(sessions): Extension<SessionConfig>,
+    Extension(prefix): Extension<PathPrefix>,
     Json(request): Json<LoginRequest>,
-) -> Result<Json<ApiResponse<LoginResponse>>, StatusCode> {
+) -> (HeaderMap, Json<ApiResponse<LoginResponse>>) {
+    let mut cookies = HeaderMap::new();
     let mut guard = state.auth.lock().await;
     let manager = match guard.as_mut() {
         Some(manager) => manager,
         None => {
-            return Ok(Json(ApiResponse::<LoginResponse>::error(
-                "Authentication disabled".to_string(),
-            )))
+            return (
+                cookies,
+                Json(ApiResponse::<LoginResponse>::error(
+                    "Authentication disabled".to_string(),
+                )),
+            )
         }
     };
 
-    let response = manager.authenticate(request).await;
-    if response.success {
-        if let Some(user) = response.user.as_ref() {
-            state.events.publish_activity(
-                ActivityLevel::Info,
-                format!("User {} logged in", user.username),
+    let mut response = manager.authenticate(request).await;
+    if !response.success {
+        return (
+            cookies,
+            Json(ApiResponse::<LoginResponse>::error(response.message)),
+        );
+    }
+
+    if let Some(user) = response.user.as_ref() {
+        state.events.publish_activity(
+            ActivityLevel::Info,
+            format!("User {} logged in", user.username),
+        );
+    }
+    // Cookie sessions keep the token out of reach of page scripts
+    if sessions.cookies {
+        if let Some(token) = response.token.take() {
+            let max_age = response.expires_in.unwrap_or(0);
+            append_cookies(
+                &mut cookies,
+                sessions.session_cookies(&token, max_age, &prefix.0),
             );
         }
-        Ok(Json(ApiResponse::success(response)))
-    } else {
-        Ok(Json(ApiResponse::<LoginResponse>::error(response.message)))
     }
+    (cookies, Json(ApiResponse::success(response)))
 }
 
 async fn logout(
     State(state): State<AppState>,
+    Extension(sessions): Extension<SessionConfig>,
+    Extension(prefix): Extension<PathPrefix>,
     headers: HeaderMap,
-) -> Result<Json<ApiResponse<()>>, StatusCode> {
+) -> Result<(HeaderMap, Json<ApiResponse<()>>), StatusCode> {
     let token = extract_token(&headers).ok_or(StatusCode::UNAUTHORIZED)?;
 
     let mut guard = state.auth.lock().await;
@@ -573,12 +2470,265 @@ async fn logout(
         format!("User {} logged out", claims.sub),
     );
 
-    Ok(Json(ApiResponse::success(())))
+    let mut cookies = HeaderMap::new();
+    if session::cookie_value(&headers, SESSION_COOKIE).is_some() {
+        append_cookies(&mut cookies, sessions.clear_cookies(&prefix.0));
+    }
+    Ok((cookies, Json(ApiResponse::success(()))))
+}
+
+/// Trade the token of the request for a one-time event stream ticket
+async fn issue_stream_ticket(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<StreamTicket>>, StatusCode> {
+    let token = extract_token(&headers).ok_or(StatusCode::UNAUTHORIZED)?;
+
+    let mut guard = state.auth.lock().await;
+    let manager = guard.as_mut().ok_or(StatusCode::SERVICE_UNAVAILABLE)?;
+    let ticket = manager
+        .issue_stream_ticket(&token)
+        .map_err(|_| StatusCode::UNAUTHORIZED)?;
+    Ok(Json(ApiResponse::success(ticket)))
 }
 
 // Utility helpers
 
+fn append_cookies(headers: &mut HeaderMap, cookies: [String; 2]) {
+    for cookie in cookies {
+        match HeaderValue::from_str(&cookie) {
+            Ok(value) => {
+                headers.append(header::SET_COOKIE, value);
+            }
+            Err(err) => log::error!("Invalid session cookie: {}", err),
+        }
+    }
+}
+
+/// Names from the verified client certificate of a TLS connection
+#[derive(Debug, Clone, Default)]
+struct PeerIdentity(Vec<String>);
+
+/// Serve HTTPS, tagging each request with its connection's client certificate
+async fn serve_tls(
+    listener: tokio::net::TcpListener,
+    app: AxumRouter,
+    acceptor: TlsAcceptor,
+) -> std::io::Result<()> {
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(err) => {
+                log::warn!("Failed to accept web connection: {}", err);
+                continue;
+            }
+        };
+        let acceptor = acceptor.clone();
+        let app = app.clone();
+        tokio::spawn(async move {
+            let stream = match acceptor.accept(stream).await {
+                Ok(stream) => stream,
+                Err(err) => {
+                    log::debug!("TLS handshake with {} failed: {}", peer, err);
+                    return;
+                }
+            };
+            let identity = PeerIdentity(
+                stream
+                    .get_ref()
+                    .1
+                    .peer_certificates()
+                    .and_then(|chain| chain.first())
+                    .map(tls::certificate_identities)
+                    .unwrap_or_default(),
+            );
+            let service =
+                hyper::service::service_fn(move |mut request: Request<hyper::body::Incoming>| {
+                    request.extensions_mut().insert(identity.clone());
+                    request.extensions_mut().insert(ConnectInfo(peer));
+                    tower::Service::call(&mut app.clone(), request)
+                });
+            if let Err(err) = AutoBuilder::new(TokioExecutor::new())
+                .serve_connection_with_upgrades(TokioIo::new(stream), service)
+                .await
+            {
+                log::debug!("Web connection from {} ended: {}", peer, err);
+            }
+        });
+    }
+}
+
+/// `web.base_path` and `web.trusted_proxies`, as `forwarded_headers` needs them
+struct ProxySettings {
+    base_path: String,
+    trusted_proxies: Vec<ipnet::IpNet>,
+}
+
+/// Take the client address and stripped prefix from the forwarding headers
+/// of a trusted proxy, and tag the request with the prefix its client sees
+async fn forwarded_headers(
+    State(settings): State<Arc<ProxySettings>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let mut prefix = settings.base_path.clone();
+    let proxy_peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(peer)| *peer)
+        .filter(|peer| proxy::is_trusted(&settings.trusted_proxies, peer.ip()));
+    if proxy_peer.is_some() {
+        if let Some(client) = proxy::forwarded_client(request.headers(), &settings.trusted_proxies)
+        {
+            request
+                .extensions_mut()
+                .insert(ConnectInfo(SocketAddr::new(client, 0)));
+        }
+        if let Some(stripped) = proxy::forwarded_prefix(request.headers()) {
+            prefix = format!("{}{}", stripped, prefix);
+        }
+    }
+    request.extensions_mut().insert(PathPrefix(prefix));
+    next.run(request).await
+}
+
+/// Replace any client-supplied identity header with the names from the
+/// connection's verified certificate, so handlers can trust the header
+async fn client_identity(mut request: Request, next: Next) -> Response {
+    let identities = request
+        .extensions()
+        .get::<PeerIdentity>()
+        .cloned()
+        .unwrap_or_default();
+    let headers = request.headers_mut();
+    headers.remove(CLIENT_IDENTITY_HEADER);
+    for name in identities.0 {
+        if let Ok(value) = HeaderValue::from_str(&name) {
+            headers.append(CLIENT_IDENTITY_HEADER, value);
+        }
+    }
+    next.run(request).await
+}
+
+fn client_identities(headers: &HeaderMap) -> Vec<String> {
+    headers
+        .get_all(CLIENT_IDENTITY_HEADER)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Keep the last failed request as the web server's error
+async fn record_server_errors(request: Request, next: Next) -> Response {
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let response = next.run(request).await;
+    if response.status().is_server_error() {
+        last_error::record(
+            last_error::WEB,
+            format!("{} {} returned {}", method, path, response.status()),
+        );
+    }
+    response
+}
+
+/// Apply `web.rate_limit` to API requests
+async fn rate_limit(
+    State(state): State<AppState>,
+    Extension(limiter): Extension<Arc<RateLimiter>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !limiter.enabled() || !request.uri().path().starts_with("/api/") {
+        return next.run(request).await;
+    }
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(peer)| *peer);
+    let client = rate_limit_client(&state, request.headers(), peer).await;
+    let decision = limiter.check(&client);
+    let mut response = if decision.allowed {
+        next.run(request).await
+    } else {
+        log::debug!(
+            "Rate limited {} {} from {}",
+            request.method(),
+            request.uri().path(),
+            client
+        );
+        (
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(ApiResponse::<()>::error("Rate limit exceeded".to_string())),
+        )
+            .into_response()
+    };
+    decision.apply_headers(response.headers_mut());
+    response
+}
+
+/// The user of a valid token, else the client certificate, else the peer
+/// address, so clients cannot reset their limit with a new login
+async fn rate_limit_client(
+    state: &AppState,
+    headers: &HeaderMap,
+    peer: Option<SocketAddr>,
+) -> String {
+    if let Some(token) = extract_token(headers) {
+        let guard = state.auth.lock().await;
+        if let Some(claims) = guard
+            .as_ref()
+            .and_then(|manager| manager.validate_token(&token).ok())
+        {
+            return format!("user:{}", claims.sub);
+        }
+    }
+    if let Some(identity) = client_identities(headers).into_iter().next() {
+        return format!("cert:{}", identity);
+    }
+    peer.map(|peer| format!("addr:{}", peer.ip().to_canonical()))
+        .unwrap_or_else(|| "anonymous".to_string())
+}
+
+/// Reject state-changing requests that authenticate with the session cookie
+/// but lack its CSRF token
+async fn csrf_guard(request: Request, next: Next) -> Result<Response, StatusCode> {
+    if csrf_rejected(request.method(), request.uri().path(), request.headers()) {
+        log::warn!(
+            "Rejected {} {} without a valid CSRF token",
+            request.method(),
+            request.uri().path()
+        );
+        return Err(StatusCode::FORBIDDEN);
+    }
+    Ok(next.run(request).await)
+}
+
+fn csrf_rejected(method: &Method, path: &str, headers: &HeaderMap) -> bool {
+    if !session::requires_csrf(method) || path == "/api/auth/login" {
+        return false;
+    }
+    if header_token(headers).is_some() {
+        return false;
+    }
+    let Some(session) = session::cookie_value(headers, SESSION_COOKIE) else {
+        return false;
+    };
+    let provided = headers
+        .get(CSRF_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    !session::verify_csrf(session, provided)
+}
+
+/// Token from the request headers, falling back to the session cookie
 fn extract_token(headers: &HeaderMap) -> Option<String> {
+    header_token(headers)
+        .or_else(|| session::cookie_value(headers, SESSION_COOKIE).map(str::to_string))
+}
+
+fn header_token(headers: &HeaderMap) -> Option<String> {
     if let Some(value) = headers.get(header::AUTHORIZATION) {
         if let Ok(text) = value.to_str() {
             if let Some(stripped) = text.trim().strip_prefix("Bearer ") {
@@ -594,13 +2744,17 @@ fn extract_token(headers: &HeaderMap) -> Option<String> {
         .get("x-auth-token")
         .and_then(|value| value.to_str().ok())
         .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty())
 }
 
+/// Authorize a request to `endpoint`, the handler's route as `"METHOD /path"`.
+/// Where `auth.permissions` has no matching rule, `default_role` applies.
 async fn ensure_permission(
     state: &AppState,
     headers: Option<&HeaderMap>,
     token_override: Option<String>,
-    required_role: Option<UserRole>,
+    endpoint: &str,
+    default_role: UserRole,
 ) -> Result<(), StatusCode> {
     let mut guard = state.auth.lock().await;
     let manager = match guard.as_mut() {
@@ -608,31 +2762,67 @@ async fn ensure_permission(
         None => return Ok(()),
     };
 
-    let token = if let Some(token) = token_override {
-        token
-    } else if let Some(headers) = headers {
-        extract_token(headers).ok_or(StatusCode::UNAUTHORIZED)?
-    } else {
-        return Err(StatusCode::UNAUTHORIZED);
+    let required = manager.required_role(endpoint, default_role);
+    let token = token_override.or_else(|| headers.and_then(extract_token));
+
+    // A token takes precedence; without one, fall back to the client certificate
+    let Some(token) = token else {
+        let identities = headers.map(client_identities).unwrap_or_default();
+        let role = manager
+            .certificate_role(&identities)
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+        return if role.satisfies(&required) {
+            Ok(())
+        } else {
+            Err(StatusCode::FORBIDDEN)
+        };
     };
 
-    let claims = manager
-        .validate_token(&token)
-        .map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let claims = manager.validate_token(&token).map_err(|err| {
+        last_error::record(
+            last_error::AUTH,
+            format!("Rejected token for {}: {}", endpoint, err),
+        );
+        StatusCode::UNAUTHORIZED
+    })?;
 
-    if let Some(role) = required_role {
-        let checker = require_permission(role);
-        checker(&claims).map_err(|err| match err {
-            AuthError::InsufficientPermissions => StatusCode::FORBIDDEN,
-            _ => StatusCode::UNAUTHORIZED,
-        })?;
-    }
+    let checker = require_permission(required);
+    checker(&claims).map_err(|err| match err {
+        AuthError::InsufficientPermissions => StatusCode::FORBIDDEN,
+        _ => StatusCode::UNAUTHORIZED,
+    })?;
 
     Ok(())
 }
 
+/// Authorize an event stream opened with a ticket, which is used up
+async fn ensure_ticket_permission(
+    state: &AppState,
+    ticket: &str,
+    endpoint: &str,
+    default_role: UserRole,
+) -> Result<(), StatusCode> {
+    let mut guard = state.auth.lock().await;
+    let manager = match guard.as_mut() {
+        Some(manager) => manager,
+        None => return Ok(()),
+    };
+
+    let required = manager.required_role(endpoint, default_role);
+    let claims = manager.redeem_stream_ticket(ticket).map_err(|err| {
+        last_error::record(
+            last_error::AUTH,
+            format!("Rejected stream ticket for {}: {}", endpoint, err),
+        );
+        StatusCode::UNAUTHORIZED
+    })?;
+
+    let checker = require_permission(required);
+    checker(&claims).map_err(|_| StatusCode::FORBIDDEN)
+}
+
 async fn collect_interface_info(interfaces: &[InterfaceConfig]) -> Vec<InterfaceInfo> {
-    let stats = read_network_stats();
+    let stats = sysstats::interface_counters();
 
     interfaces
         .iter()
@@ -653,99 +2843,52 @@ async fn collect_interface_info(interfaces: &[InterfaceConfig]) -> Vec<Interface
         .collect()
 }
 
-async fn cpu_usage_percent() -> f32 {
-    let first = read_cpu_times();
-    tokio::time::sleep(Duration::from_millis(150)).await;
-    let second = read_cpu_times();
-
-    match (first, second) {
-        (Some((idle1, total1)), Some((idle2, total2))) if total2 > total1 && idle2 >= idle1 => {
-            let total_delta = total2 - total1;
-            let idle_delta = idle2 - idle1;
-            if total_delta == 0 {
-                0.0
-            } else {
-                let usage = (total_delta - idle_delta) as f32 / total_delta as f32;
-                (usage * 100.0).clamp(0.0, 100.0)
-            }
-        }
-        _ => 0.0,
-    }
-}
-
-#[derive(Default)]
-struct NetStats {
-    rx_bytes: u64,
-    tx_bytes: u64,
-    rx_packets: u64,
-    tx_packets: u64,
-}
-
-fn read_network_stats() -> HashMap<String, NetStats> {
-    let mut stats = HashMap::new();
-
-    if let Ok(content) = std::fs::read_to_string("/proc/net/dev") {
-        for line in content.lines().skip(2) {
-            if let Some((iface, data)) = line.split_once(':') {
-                let parts: Vec<&str> = data.split_whitespace().collect();
-                if parts.len() >= 16 {
-                    let rx_bytes = parts[0].parse().unwrap_or(0);
-                    let rx_packets = parts[1].parse().unwrap_or(0);
-                    let tx_bytes = parts[8].parse().unwrap_or(0);
-                    let tx_packets = parts[9].parse().unwrap_or(0);
-                    stats.insert(
-                        iface.trim().to_string(),
-                        NetStats {
-                            rx_bytes,
-                            tx_bytes,
-                            rx_packets,
-                            tx_packets,
-                        },
-                    );
-                }
-            }
-        }
-    }
-
-    stats
-}
-
-fn read_cpu_times() -> Option<(u64, u64)> {
-    let content = std::fs::read_to_string("/proc/stat").ok()?;
-    let mut fields = content.lines().next()?.split_whitespace();
-    let _cpu = fields.next()?; // skip label
-
-    let mut values = Vec::new();
-    for field in fields.take(10) {
-        if let Ok(value) = field.parse::<u64>() {
-            values.push(value);
-        }
-    }
-
-    if values.len() < 4 {
-        return None;
-    }
-
-    let user = values.get(0).copied().unwrap_or(0);
-    let nice = values.get(1).copied().unwrap_or(0);
-    let system = values.get(2).copied().unwrap_or(0);
-    let idle = values.get(3).copied().unwrap_or(0);
-    let iowait = values.get(4).copied().unwrap_or(0);
-    let irq = values.get(5).copied().unwrap_or(0);
-    let softirq = values.get(6).copied().unwrap_or(0);
-    let steal = values.get(7).copied().unwrap_or(0);
-
-    let idle_all = idle + iowait;
-    let non_idle = user + nice + system + irq + softirq + steal;
-    let total = idle_all + non_idle;
-
-    Some((idle_all, total))
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::config_manager::InterfaceConfig;
+    use crate::network::{PacingConfig, SendMode};
+    use crate::protocol::PacketCodec;
+
+    #[test]
+    fn cookie_sessions_need_a_csrf_token() {
+        let session_cookie = |extra: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                header::COOKIE,
+                HeaderValue::from_str(&format!("{}=tok{}", SESSION_COOKIE, extra)).unwrap(),
+            );
+            headers
+        };
+
+        let mut headers = session_cookie("");
+        assert!(csrf_rejected(&Method::POST, "/api/routes", &headers));
+        assert!(!csrf_rejected(&Method::GET, "/api/routes", &headers));
+        assert!(!csrf_rejected(&Method::POST, "/api/auth/login", &headers));
+
+        headers.insert(
+            CSRF_HEADER,
+            HeaderValue::from_str(&session::csrf_token("tok")).unwrap(),
+        );
+        assert!(!csrf_rejected(&Method::DELETE, "/api/routes/x/y", &headers));
+
+        // A token for another session does not pass
+        let mut forged = session_cookie("2");
+        forged.insert(
+            CSRF_HEADER,
+            HeaderValue::from_str(&session::csrf_token("tok")).unwrap(),
+        );
+        assert!(csrf_rejected(&Method::PUT, "/api/config", &forged));
+
+        // Header tokens cannot be sent by another site, so need no CSRF token
+        let mut bearer = session_cookie("");
+        bearer.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer tok"),
+        );
+        assert!(!csrf_rejected(&Method::POST, "/api/routes", &bearer));
+        assert_eq!(extract_token(&session_cookie("")).as_deref(), Some("tok"));
+    }
 
     #[tokio::test]
     async fn api_response_success_wraps_data() {
@@ -761,6 +2904,18 @@ mod tests {
             address: "127.0.0.1/8".to_string(),
             enabled: true,
             cost: 1,
+            passive: false,
+            point_to_point: false,
+            peer: None,
+            codec: PacketCodec::default(),
+            mtu: None,
+            secondary_addresses: Vec::new(),
+            send_mode: SendMode::default(),
+            accept_from: Vec::new(),
+            reject_from: Vec::new(),
+            pacing: PacingConfig::default(),
+            advertise_connected: true,
+            auto_cost: false,
         }];
 
         let results = collect_interface_info(&interfaces).await;
@@ -0,0 +1,290 @@
+//! A `rust-route` process started from a temporary configuration, and a
+//! client for its web API.
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+use reqwest::{Method, StatusCode};
+use rust_route::fleet::SseParser;
+use rust_route::{InterfaceConfig, RouterConfig};
+use serde_json::{json, Value};
+use tempfile::TempDir;
+use tokio::process::{Child, Command};
+
+/// Cargo builds the binary before running the tests, so this only covers startup
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+const EVENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub const ADMIN_USERNAME: &str = "admin";
+/// Password of the account created when no users exist
+pub const ADMIN_PASSWORD: &str = "admin123";
+
+fn free_tcp_port() -> u16 {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().port()
+}
+
+fn free_udp_port() -> u16 {
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.local_addr().unwrap().port()
+}
+
+/// A configuration that stays on loopback and inside `home`: a passive RIP
+/// interface on `lo`, and the web API with authentication on a free port
+pub fn config(home: &Path) -> RouterConfig {
+    let mut config = RouterConfig::default();
+    let interface = config.interfaces.remove(0);
+    config.interfaces = vec![InterfaceConfig {
+        name: "lo".to_string(),
+        address: "127.0.0.1/8".to_string(),
+        passive: true,
+        ..interface
+    }];
+    config.rip.port = free_udp_port();
+    config.web.bind_address = "127.0.0.1".to_string();
+    config.web.port = free_tcp_port();
+    config.web.auth_enabled = true;
+    config.storage.path = home.join("data").display().to_string();
+    config.backup.backup_directory = home.join("backups").display().to_string();
+    config.logging.file_path = None;
+    config
+}
+
+/// The router binary, killed when dropped
+pub struct RouterProcess {
+    base_url: String,
+    client: reqwest::Client,
+    token: Option<String>,
+    child: Child,
+    log: PathBuf,
+    _home: TempDir,
+}
+
+impl RouterProcess {
+    pub async fn start() -> Self {
+        Self::start_with(|_| {}).await
+    }
+
+    /// Start with the harness configuration as changed by `customize`, and
+    /// wait until `/readyz` reports ready
+    pub async fn start_with(customize: impl FnOnce(&mut RouterConfig)) -> Self {
+        let home = tempfile::tempdir().unwrap();
+        let mut config = config(home.path());
+        customize(&mut config);
+        let config_path = home.path().join("rust-route.json");
+        std::fs::write(&config_path, serde_json::to_string_pretty(&config).unwrap()).unwrap();
+
+        let log = home.path().join("rust-route.log");
+        let output = std::fs::File::create(&log).unwrap();
+        let child = Command::new(env!("CARGO_BIN_EXE_rust-route"))
+            .arg("start")
+            .arg("--ephemeral")
+            .arg("--config")
+            .arg(&config_path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::from(output.try_clone().unwrap()))
+            .stderr(Stdio::from(output))
+            .kill_on_drop(true)
+            .spawn()
+            .expect("failed to launch rust-route");
+
+        let mut router = Self {
+            base_url: format!("http://127.0.0.1:{}", config.web.port),
+            client: reqwest::Client::new(),
+            token: None,
+            child,
+            log,
+            _home: home,
+        };
+        router.wait_ready().await;
+        router
+    }
+
+    async fn wait_ready(&mut self) {
+        let deadline = Instant::now() + STARTUP_TIMEOUT;
+        loop {
+            if let Some(status) = self.child.try_wait().unwrap() {
+                panic!(
+                    "rust-route exited with {} before it was ready:\n{}",
+                    status,
+                    self.log()
+                );
+            }
+            if let Ok(response) = self.client.get(self.url("/readyz")).send().await {
+                if response.status() == StatusCode::OK {
+                    return;
+                }
+            }
+            assert!(
+                Instant::now() < deadline,
+                "rust-route was not ready within {:?}:\n{}",
+                STARTUP_TIMEOUT,
+                self.log()
+            );
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    /// Everything the process wrote to stdout and stderr so far
+    pub fn log(&self) -> String {
+        std::fs::read_to_string(&self.log).unwrap_or_default()
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    /// Log in, sending the token with every later request when it succeeds
+    pub async fn login(&mut self, username: &str, password: &str) -> Value {
+        let (status, body) = self
+            .request(
+                Method::POST,
+                "/api/auth/login",
+                Some(json!({ "username": username, "password": password })),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "login returned {}", body);
+        if let Some(token) = body["data"]["token"].as_str() {
+            self.token = Some(token.to_string());
+        }
+        body
+    }
+
+    pub async fn login_as_admin(&mut self) {
+        let body = self.login(ADMIN_USERNAME, ADMIN_PASSWORD).await;
+        assert!(self.token.is_some(), "admin login failed: {}", body);
+    }
+
+    /// Send a request and return its status and JSON body, or `null` for
+    /// bodies that are not JSON
+    pub async fn request(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let mut request = self.client.request(method.clone(), self.url(path));
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request
+            .send()
+            .await
+            .unwrap_or_else(|err| panic!("{} {} failed: {}\n{}", method, path, err, self.log()));
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        (status, serde_json::from_str(&text).unwrap_or(Value::Null))
+    }
+
+    /// The body of a request expected to return a success status
+    async fn call(&self, method: Method, path: &str, body: Option<Value>) -> Value {
+        let (status, body) = self.request(method.clone(), path, body).await;
+        assert!(
+            status.is_success(),
+            "{} {} returned {}: {}",
+            method,
+            path,
+            status,
+            body
+        );
+        body
+    }
+
+    pub async fn get(&self, path: &str) -> Value {
+        self.call(Method::GET, path, None).await
+    }
+
+    pub async fn post(&self, path: &str, body: Value) -> Value {
+        self.call(Method::POST, path, Some(body)).await
+    }
+
+    pub async fn put(&self, path: &str, body: Value) -> Value {
+        self.call(Method::PUT, path, Some(body)).await
+    }
+
+    pub async fn delete(&self, path: &str) -> Value {
+        self.call(Method::DELETE, path, None).await
+    }
+
+    /// The status of a GET of `path` sent without the login token, as an
+    /// `EventSource` would; the body is not read, since streams never end
+    pub async fn anonymous_status(&self, path: &str) -> StatusCode {
+        self.client
+            .get(self.url(path))
+            .send()
+            .await
+            .unwrap_or_else(|err| panic!("GET {} failed: {}\n{}", path, err, self.log()))
+            .status()
+    }
+
+    /// Subscribe to `/api/events` for the comma-separated event `types`;
+    /// events published once this returns are delivered
+    pub async fn events(&self, types: &str) -> EventStream {
+        let mut request = self
+            .client
+            .get(self.url("/api/events"))
+            .query(&[("types", types)]);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        let response = request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .unwrap_or_else(|err| panic!("event stream failed: {}\n{}", err, self.log()));
+        EventStream {
+            response,
+            parser: SseParser::default(),
+            received: VecDeque::new(),
+        }
+    }
+}
+
+/// Events from `/api/events`, decoded as JSON
+pub struct EventStream {
+    response: reqwest::Response,
+    parser: SseParser,
+    received: VecDeque<Value>,
+}
+
+impl EventStream {
+    /// The next event `predicate` accepts, skipping the others
+    pub async fn wait_for(&mut self, predicate: impl Fn(&Value) -> bool) -> Value {
+        let next = async {
+            loop {
+                while let Some(event) = self.received.pop_front() {
+                    if predicate(&event) {
+                        return event;
+                    }
+                }
+                let chunk = self
+                    .response
+                    .chunk()
+                    .await
+                    .expect("event stream failed")
+                    .expect("event stream closed");
+                for (_, data) in self.parser.push(&chunk) {
+                    if let Ok(event) = serde_json::from_str(&data) {
+                        self.received.push_back(event);
+                    }
+                }
+            }
+        };
+        tokio::time::timeout(EVENT_TIMEOUT, next)
+            .await
+            .expect("no matching event within the timeout")
+    }
+
+    /// The next activity entry with `message`
+    pub async fn activity(&mut self, message: &str) -> Value {
+        self.wait_for(|event| {
+            event["type"] == "Activity" && event["data"]["message"].as_str() == Some(message)
+        })
+        .await
+    }
+}
@@ -0,0 +1,144 @@
+//! End-to-end tests against the `rust-route` binary: each test starts the
+//! router with a temporary configuration and drives it over its web API.
+
+mod harness;
+
+use harness::{RouterProcess, ADMIN_PASSWORD, ADMIN_USERNAME};
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value};
+
+fn has_route(routes: &Value, destination: &str, mask: &str) -> bool {
+    routes["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|route| route["destination"] == destination && route["subnet_mask"] == mask)
+}
+
+#[tokio::test]
+async fn the_api_needs_a_login() {
+    let mut router = RouterProcess::start().await;
+
+    let (status, _) = router.request(Method::GET, "/api/routes", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let refused = router.login(ADMIN_USERNAME, "not-the-password").await;
+    assert_eq!(refused["success"], false);
+    let (status, _) = router.request(Method::GET, "/api/routes", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let accepted = router.login(ADMIN_USERNAME, ADMIN_PASSWORD).await;
+    assert_eq!(accepted["success"], true);
+    assert_eq!(accepted["data"]["user"]["username"], ADMIN_USERNAME);
+    let routes = router.get("/api/routes").await;
+    assert_eq!(routes["success"], true);
+}
+
+#[tokio::test]
+async fn static_routes_can_be_added_read_and_deleted() {
+    let mut router = RouterProcess::start().await;
+    router.login_as_admin().await;
+
+    let created = router
+        .post(
+            "/api/routes",
+            json!({
+                "destination": "10.20.0.0",
+                "mask": "255.255.0.0",
+                "next_hop": "127.0.0.2",
+                "metric": 3,
+                "interface": "lo",
+            }),
+        )
+        .await;
+    assert_eq!(created["success"], true);
+    assert!(has_route(
+        &router.get("/api/routes").await,
+        "10.20.0.0",
+        "255.255.0.0"
+    ));
+
+    let route = router.get("/api/routes/10.20.0.0/16").await;
+    assert_eq!(route["success"], true, "{}", route);
+
+    let deleted = router.delete("/api/routes/10.20.0.0/255.255.0.0").await;
+    assert_eq!(deleted["success"], true, "{}", deleted);
+    assert!(!has_route(
+        &router.get("/api/routes").await,
+        "10.20.0.0",
+        "255.255.0.0"
+    ));
+    let missing = router.get("/api/routes/10.20.0.0/16").await;
+    assert_eq!(missing["success"], false);
+    assert_eq!(missing["message"], "Route not found");
+}
+
+#[tokio::test]
+async fn configuration_changes_can_be_rolled_back() {
+    let mut router = RouterProcess::start().await;
+    router.login_as_admin().await;
+    let mut events = router.events("Activity").await;
+
+    let original = router.get("/api/config").await["data"].clone();
+    assert_eq!(original["rip"]["update_interval"], 30);
+    let mut changed = original.clone();
+    changed["rip"]["update_interval"] = json!(20);
+    let updated = router.put("/api/config", changed).await;
+    assert_eq!(updated["success"], true, "{}", updated);
+    events.activity("Configuration updated via API").await;
+    assert_eq!(
+        router.get("/api/config").await["data"]["rip"]["update_interval"],
+        20
+    );
+
+    let history = router.get("/api/config/history").await;
+    let versions: Vec<u64> = history["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["version"].as_u64().unwrap())
+        .collect();
+    assert!(versions.contains(&1) && versions.len() >= 2, "{}", history);
+
+    let rolled_back = router
+        .post("/api/config/history/1/rollback", json!({}))
+        .await;
+    assert_eq!(rolled_back["success"], true, "{}", rolled_back);
+    let activity = events
+        .activity("Configuration rolled back to version 1")
+        .await;
+    assert_eq!(activity["data"]["level"], "Warn");
+    assert_eq!(
+        router.get("/api/config").await["data"]["rip"]["update_interval"],
+        30
+    );
+}
+
+#[tokio::test]
+async fn events_are_only_streamed_to_logged_in_clients() {
+    let router = RouterProcess::start().await;
+    let (status, _) = router.request(Method::GET, "/api/events", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn stream_tickets_open_one_event_stream() {
+    let mut router = RouterProcess::start().await;
+    router.login_as_admin().await;
+
+    let issued = router.post("/api/auth/stream-ticket", json!({})).await;
+    let path = format!(
+        "/api/events?ticket={}",
+        issued["data"]["ticket"].as_str().unwrap()
+    );
+    assert_eq!(router.anonymous_status(&path).await, StatusCode::OK);
+    // A reconnect must fetch a new ticket
+    assert_eq!(
+        router.anonymous_status(&path).await,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        router.anonymous_status("/api/events?ticket=forged").await,
+        StatusCode::UNAUTHORIZED
+    );
+}
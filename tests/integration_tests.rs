@@ -2,12 +2,24 @@ use std::collections::HashMap;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4};
 use std::sync::Arc;
 
-use rust_route::config_manager::RouterConfig;
+use chrono::Utc;
+use rust_route::config_manager::{NeighborConfig, NextHopAction, RouterConfig, StaticRouteConfig};
+use rust_route::decisions::{DecisionTrace, DecisionTraceConfig, Reason};
+use rust_route::events::{EventBus, WebEvent};
+use rust_route::link_cost::InterfaceCosts;
+use rust_route::loop_detector::LoopDetector;
 use rust_route::metrics::Metrics;
+use rust_route::policy::{Maintenance, PolicyConfig, PolicyHandle};
 use rust_route::protocol::{RipCommand, RipEntry, RipPacket};
-use rust_route::router::{handle_rip_response, NeighborInfo, Router};
-use rust_route::routing_table::{RouteSource, RoutingTable};
+use rust_route::rip_auth::{self, AuthKey, AuthMode, InterfaceAuthConfig};
+use rust_route::router::{
+    advertisement, ensure_receive_tasks, handle_rip_response, NeighborInfo, RipContext, Router,
+    RouterStatistics, Supervisor,
+};
+use rust_route::routing_table::{Route, RouteSource, RoutingTable};
+use rust_route::watchdog::{Watchdog, WatchdogConfig};
 use tokio::sync::RwLock;
+use uuid::Uuid;
 
 #[tokio::test]
 async fn handle_rip_response_adds_dynamic_route() {
@@ -30,21 +42,29 @@ async fn handle_rip_response_adds_dynamic_route() {
             next_hop: Ipv4Addr::UNSPECIFIED,
             metric: 1,
         }],
+        auth: None,
+        authentication: None,
     };
 
     let sender = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(192, 168, 10, 1), 520));
 
-    let routes = handle_rip_response(
-        Arc::clone(&routing_table),
-        Arc::clone(&neighbors),
-        metrics.clone(),
+    let ctx = RipContext {
+        routing_table: Arc::clone(&routing_table),
+        neighbors: Arc::clone(&neighbors),
+        metrics: metrics.clone(),
         rip_config,
-        "eth0".to_string(),
-        packet,
-        sender,
-    )
-    .await
-    .expect("processing response succeeds");
+        events: EventBus::new(16),
+        router_uuid: Uuid::new_v4(),
+        policy: PolicyHandle::default(),
+        loop_detector: LoopDetector::default(),
+        decisions: DecisionTrace::default(),
+        interface_costs: InterfaceCosts::default(),
+        quiet_until: None,
+    };
+
+    let routes = handle_rip_response(&ctx, "eth0".to_string(), packet, sender)
+        .await
+        .expect("processing response succeeds");
 
     assert_eq!(routes.len(), 1);
 
@@ -68,6 +88,384 @@ async fn handle_rip_response_adds_dynamic_route() {
     assert_eq!(neighbor.interface.as_deref(), Some("eth0"));
 }
 
+#[tokio::test]
+async fn handle_rip_response_adds_the_interface_cost() {
+    let routing_table = Arc::new(RwLock::new(RoutingTable::new()));
+    let interface_costs = InterfaceCosts::default();
+    interface_costs.set("eth0", 3);
+    let ctx = RipContext {
+        routing_table: Arc::clone(&routing_table),
+        neighbors: Arc::new(RwLock::new(HashMap::new())),
+        metrics: Metrics::new(),
+        rip_config: Arc::new(RouterConfig::default().rip),
+        events: EventBus::new(16),
+        router_uuid: Uuid::new_v4(),
+        policy: PolicyHandle::default(),
+        loop_detector: LoopDetector::default(),
+        decisions: DecisionTrace::default(),
+        interface_costs,
+        quiet_until: None,
+    };
+    let packet = |metric| RipPacket {
+        command: RipCommand::Response,
+        version: 2,
+        reserved: 0,
+        entries: vec![RipEntry {
+            address_family: 2,
+            route_tag: 0,
+            ip_address: Ipv4Addr::new(10, 1, 0, 0),
+            subnet_mask: Ipv4Addr::new(255, 255, 255, 0),
+            next_hop: Ipv4Addr::UNSPECIFIED,
+            metric,
+        }],
+        auth: None,
+        authentication: None,
+    };
+    let sender = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(192, 168, 10, 1), 520));
+
+    handle_rip_response(&ctx, "eth0".to_string(), packet(2), sender)
+        .await
+        .unwrap();
+    let metric = |table: &RoutingTable| {
+        table
+            .get_route(Ipv4Addr::new(10, 1, 0, 1))
+            .map(|r| r.metric)
+    };
+    assert_eq!(metric(&*routing_table.read().await), Some(5));
+
+    // Tunnels and interfaces without a cost add 1, a better path here
+    handle_rip_response(&ctx, "hq".to_string(), packet(2), sender)
+        .await
+        .unwrap();
+    assert_eq!(metric(&*routing_table.read().await), Some(3));
+}
+
+#[tokio::test]
+async fn handle_rip_response_applies_inbound_distribute_lists() {
+    let routing_table = Arc::new(RwLock::new(RoutingTable::new()));
+    let policy = PolicyHandle::default();
+    let config: PolicyConfig = serde_json::from_value(serde_json::json!({
+        "max_operations": 1000,
+        "prefix_lists": [{
+            "name": "no-ten",
+            "rules": [
+                { "action": "deny", "prefix": "10.0.0.0/8", "le": 32 },
+                { "action": "permit", "prefix": "0.0.0.0/0", "le": 32 }
+            ]
+        }],
+        "distribute_lists": [{ "prefix_list": "no-ten", "direction": "in", "interface": "eth1" }]
+    }))
+    .unwrap();
+    policy.set_distribute_lists(&config);
+    let ctx = RipContext {
+        routing_table: Arc::clone(&routing_table),
+        neighbors: Arc::new(RwLock::new(HashMap::new())),
+        metrics: Metrics::new(),
+        rip_config: Arc::new(RouterConfig::default().rip),
+        events: EventBus::new(16),
+        router_uuid: Uuid::new_v4(),
+        policy,
+        loop_detector: LoopDetector::default(),
+        decisions: DecisionTrace::default(),
+        interface_costs: InterfaceCosts::default(),
+        quiet_until: None,
+    };
+    let entry = |ip_address: Ipv4Addr| RipEntry {
+        address_family: 2,
+        route_tag: 0,
+        ip_address,
+        subnet_mask: Ipv4Addr::new(255, 255, 0, 0),
+        next_hop: Ipv4Addr::UNSPECIFIED,
+        metric: 1,
+    };
+    let packet = RipPacket {
+        command: RipCommand::Response,
+        version: 2,
+        reserved: 0,
+        entries: vec![
+            entry(Ipv4Addr::new(10, 1, 0, 0)),
+            entry(Ipv4Addr::new(172, 16, 0, 0)),
+        ],
+        auth: None,
+        authentication: None,
+    };
+    let sender = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(192, 168, 20, 1), 520));
+
+    let installed = handle_rip_response(&ctx, "eth1".to_string(), packet.clone(), sender)
+        .await
+        .unwrap();
+    assert_eq!(installed.len(), 1);
+    assert_eq!(installed[0].destination, Ipv4Addr::new(172, 16, 0, 0));
+    assert!(routing_table
+        .read()
+        .await
+        .get_route(Ipv4Addr::new(10, 1, 0, 1))
+        .is_none());
+
+    // The list only applies to eth1
+    let installed = handle_rip_response(&ctx, "eth0".to_string(), packet, sender)
+        .await
+        .unwrap();
+    assert!(installed
+        .iter()
+        .any(|route| route.destination == Ipv4Addr::new(10, 1, 0, 0)));
+}
+
+#[tokio::test]
+async fn handle_rip_response_applies_inbound_offset_lists() {
+    let routing_table = Arc::new(RwLock::new(RoutingTable::new()));
+    let policy = PolicyHandle::default();
+    let config: PolicyConfig = serde_json::from_value(serde_json::json!({
+        "max_operations": 1000,
+        "offset_lists": [{ "direction": "in", "offset": 4, "neighbor": "192.168.30.1" }]
+    }))
+    .unwrap();
+    policy.set_offset_lists(&config, 16);
+    let ctx = RipContext {
+        routing_table: Arc::clone(&routing_table),
+        neighbors: Arc::new(RwLock::new(HashMap::new())),
+        metrics: Metrics::new(),
+        rip_config: Arc::new(RouterConfig::default().rip),
+        events: EventBus::new(16),
+        router_uuid: Uuid::new_v4(),
+        policy,
+        loop_detector: LoopDetector::default(),
+        decisions: DecisionTrace::default(),
+        interface_costs: InterfaceCosts::default(),
+        quiet_until: None,
+    };
+    let packet = RipPacket {
+        command: RipCommand::Response,
+        version: 2,
+        reserved: 0,
+        entries: vec![RipEntry {
+            address_family: 2,
+            route_tag: 0,
+            ip_address: Ipv4Addr::new(10, 4, 0, 0),
+            subnet_mask: Ipv4Addr::new(255, 255, 0, 0),
+            next_hop: Ipv4Addr::UNSPECIFIED,
+            metric: 2,
+        }],
+        auth: None,
+        authentication: None,
+    };
+    let from = |last| SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(192, 168, 30, last), 520));
+
+    let installed = handle_rip_response(&ctx, "eth0".to_string(), packet.clone(), from(1))
+        .await
+        .unwrap();
+    assert_eq!(installed[0].metric, 7);
+
+    // Another neighbor offers the same route without the offset
+    let installed = handle_rip_response(&ctx, "eth0".to_string(), packet, from(2))
+        .await
+        .unwrap();
+    assert_eq!(installed[0].metric, 3);
+}
+
+#[tokio::test]
+async fn decision_trace_explains_each_received_entry() {
+    let routing_table = Arc::new(RwLock::new(RoutingTable::new()));
+    let rip_config = Arc::new(RouterConfig::default().rip);
+    let decisions = DecisionTrace::new(&DecisionTraceConfig {
+        enabled: true,
+        capacity: 16,
+    });
+    let ctx = RipContext {
+        routing_table: Arc::clone(&routing_table),
+        neighbors: Arc::new(RwLock::new(HashMap::new())),
+        metrics: Metrics::new(),
+        rip_config,
+        events: EventBus::new(16),
+        router_uuid: Uuid::new_v4(),
+        policy: PolicyHandle::default(),
+        loop_detector: LoopDetector::default(),
+        decisions: decisions.clone(),
+        interface_costs: InterfaceCosts::default(),
+        quiet_until: None,
+    };
+    let entry = |third: u8, metric: u32| {
+        RipEntry::new(
+            Ipv4Addr::new(10, 1, third, 0),
+            Ipv4Addr::new(255, 255, 255, 0),
+            Ipv4Addr::UNSPECIFIED,
+            metric,
+        )
+    };
+    let first = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(192, 168, 10, 1), 520));
+    let second = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(192, 168, 10, 2), 520));
+
+    handle_rip_response(
+        &ctx,
+        "eth0".to_string(),
+        RipPacket::new_response(vec![entry(1, 1), entry(2, 16)]),
+        first,
+    )
+    .await
+    .unwrap();
+    handle_rip_response(
+        &ctx,
+        "eth0".to_string(),
+        RipPacket::new_response(vec![entry(1, 4)]),
+        second,
+    )
+    .await
+    .unwrap();
+
+    let reasons = |prefix: &str| -> Vec<Reason> {
+        decisions
+            .query(Some(prefix.parse().unwrap()), None, 10)
+            .iter()
+            .map(|decision| decision.reason)
+            .collect()
+    };
+    assert_eq!(
+        reasons("10.1.1.0/24"),
+        vec![Reason::WorseMetric, Reason::NewPrefix]
+    );
+    assert_eq!(reasons("10.1.2.0/24"), vec![Reason::Unreachable]);
+    assert_eq!(reasons("10.1.0.0/16").len(), 3);
+    let rejected = &decisions.query(None, Some(Ipv4Addr::new(192, 168, 10, 2)), 10)[0];
+    assert!(!rejected.accepted);
+    assert_eq!(rejected.metric, 5);
+}
+
+#[tokio::test]
+async fn a_neighbor_withdraws_the_routes_it_advertised_at_once() {
+    let routing_table = Arc::new(RwLock::new(RoutingTable::new()));
+    let ctx = RipContext {
+        routing_table: Arc::clone(&routing_table),
+        neighbors: Arc::new(RwLock::new(HashMap::new())),
+        metrics: Metrics::new(),
+        rip_config: Arc::new(RouterConfig::default().rip),
+        events: EventBus::new(16),
+        router_uuid: Uuid::new_v4(),
+        policy: PolicyHandle::default(),
+        loop_detector: LoopDetector::default(),
+        decisions: DecisionTrace::default(),
+        interface_costs: InterfaceCosts::default(),
+        quiet_until: None,
+    };
+    let (destination, mask) = (Ipv4Addr::new(10, 1, 1, 0), Ipv4Addr::new(255, 255, 255, 0));
+    let advertise = |metric: u32, last: u8| {
+        handle_rip_response(
+            &ctx,
+            "eth0".to_string(),
+            RipPacket::new_response(vec![RipEntry::new(
+                destination,
+                mask,
+                Ipv4Addr::UNSPECIFIED,
+                metric,
+            )]),
+            SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(192, 168, 10, last), 520)),
+        )
+    };
+    let metric = || async {
+        routing_table
+            .read()
+            .await
+            .get_route(destination)
+            .unwrap()
+            .metric
+    };
+
+    advertise(2, 1).await.unwrap();
+    assert_eq!(metric().await, 3);
+    // Another neighbor cannot poison it, and a worse metric from the
+    // neighbor it came from is believed
+    assert!(advertise(16, 2).await.unwrap().is_empty());
+    assert_eq!(advertise(5, 1).await.unwrap()[0].metric, 6);
+
+    let withdrawn = advertise(16, 1).await.unwrap();
+    assert_eq!(withdrawn[0].metric, 16);
+    let detail = routing_table
+        .read()
+        .await
+        .detail(destination, mask)
+        .unwrap();
+    assert_eq!(detail.route.metric, 16);
+    // Flushed after the garbage-collection period, not the 180 s timeout
+    assert!(detail
+        .flush_in_seconds
+        .is_some_and(|left| (100..=120).contains(&left)));
+
+    // Repeated poison does not restart the garbage-collection timer
+    assert!(advertise(16, 1).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn a_peer_moves_traffic_off_a_router_in_maintenance() {
+    let routing_table = Arc::new(RwLock::new(RoutingTable::new()));
+    let ctx = RipContext {
+        routing_table: Arc::clone(&routing_table),
+        neighbors: Arc::new(RwLock::new(HashMap::new())),
+        metrics: Metrics::new(),
+        rip_config: Arc::new(RouterConfig::default().rip),
+        events: EventBus::new(16),
+        router_uuid: Uuid::new_v4(),
+        policy: PolicyHandle::default(),
+        loop_detector: LoopDetector::default(),
+        decisions: DecisionTrace::default(),
+        interface_costs: InterfaceCosts::default(),
+        quiet_until: None,
+    };
+    let (destination, mask) = (Ipv4Addr::new(10, 1, 1, 0), Ipv4Addr::new(255, 255, 255, 0));
+    let (drained, alternate) = (
+        Ipv4Addr::new(192, 168, 10, 1),
+        Ipv4Addr::new(192, 168, 10, 2),
+    );
+    // The router to be drained is attached to the destination; the
+    // alternate path is three hops longer
+    let drained_policy = PolicyHandle::default();
+    let receive = |entries: Vec<RipEntry>, from: Ipv4Addr| {
+        handle_rip_response(
+            &ctx,
+            "eth0".to_string(),
+            RipPacket::new_response(entries),
+            SocketAddr::V4(SocketAddrV4::new(from, 520)),
+        )
+    };
+    let from_drained = || {
+        drained_policy.export_entries(
+            vec![Route::new_direct(destination, mask, "eth1".to_string())],
+            "eth0",
+        )
+    };
+    let from_alternate = || vec![RipEntry::new(destination, mask, Ipv4Addr::UNSPECIFIED, 4)];
+    let installed = || async {
+        let route = routing_table
+            .read()
+            .await
+            .get_route(destination)
+            .unwrap()
+            .clone();
+        (route.next_hop, route.metric)
+    };
+
+    receive(from_drained(), drained).await.unwrap();
+    assert!(receive(from_alternate(), alternate)
+        .await
+        .unwrap()
+        .is_empty());
+    assert_eq!(installed().await, (drained, 2));
+
+    drained_policy.set_maintenance(Some(Maintenance {
+        metric: Maintenance::default_metric(ctx.rip_config.infinity_metric),
+        since: Utc::now(),
+        reason: None,
+    }));
+    // Still reachable through the drained router, so traffic keeps
+    // flowing until the alternate is heard
+    receive(from_drained(), drained).await.unwrap();
+    assert_eq!(installed().await, (drained, 15));
+    receive(from_alternate(), alternate).await.unwrap();
+    assert_eq!(installed().await, (alternate, 5));
+
+    drained_policy.set_maintenance(None);
+    receive(from_drained(), drained).await.unwrap();
+    assert_eq!(installed().await, (drained, 2));
+}
+
 #[tokio::test]
 async fn router_initializes_without_rip() {
     let mut config = RouterConfig::default();
@@ -83,3 +481,613 @@ async fn router_initializes_without_rip() {
     assert!(!router.rip_enabled());
     assert!(router.network_interfaces().is_empty());
 }
+
+#[tokio::test]
+async fn router_with_config_creates_its_own_routing_table() {
+    let mut config = RouterConfig::default();
+    config.rip.enabled = false;
+
+    let router = Router::with_config(config)
+        .await
+        .expect("router constructed");
+    assert!(!router.is_running());
+
+    assert!(
+        router
+            .add_static_route(
+                Ipv4Addr::new(10, 0, 0, 0),
+                Ipv4Addr::new(255, 0, 0, 0),
+                Ipv4Addr::new(192, 168, 1, 254),
+                2,
+                "eth0",
+            )
+            .await
+    );
+    assert_eq!(router.routing_table().read().await.route_count(), 2);
+}
+
+#[tokio::test]
+async fn router_uuid_is_stable_for_a_router_id() {
+    let mut config = RouterConfig::default();
+    config.rip.enabled = false;
+    let first = Router::with_config(config.clone())
+        .await
+        .expect("router constructed");
+    let mut second = Router::with_config(config.clone())
+        .await
+        .expect("router constructed");
+
+    let uuid = first.router_uuid();
+    assert_eq!(second.router_uuid(), uuid);
+    assert_eq!(uuid.get_version_num(), 5);
+    second.apply_config(config.clone()).await.unwrap();
+    assert_eq!(second.router_uuid(), uuid);
+
+    config.router_id = "192.168.1.2".to_string();
+    assert_ne!(Router::derive_router_uuid(&config.router_id), uuid);
+    let explicit = "9b2f6c1e-8d4a-4f3b-a1c7-2e5d9f8b6a40";
+    assert_eq!(Router::derive_router_uuid(explicit).to_string(), explicit);
+}
+
+#[tokio::test]
+async fn statistics_carry_raw_and_formatted_values() {
+    let mut config = RouterConfig::default();
+    config.rip.enabled = false;
+
+    let router = Router::with_config(config)
+        .await
+        .expect("router constructed");
+    let stats = router.statistics().await;
+
+    assert_eq!(stats.routes_by_source.get("direct"), Some(&1));
+    assert!(stats.uptime.ends_with('s'));
+    assert!(stats.last_config_change.is_some());
+
+    // Consumers of the older shape still deserialize
+    let mut json = serde_json::to_value(&stats).unwrap();
+    let object = json.as_object_mut().unwrap();
+    for field in [
+        "uptime_seconds",
+        "memory_usage_human",
+        "routes_by_source",
+        "last_config_change",
+    ] {
+        object.remove(field);
+    }
+    let legacy: RouterStatistics = serde_json::from_value(json).unwrap();
+    assert!(legacy.routes_by_source.is_empty());
+    assert!(legacy.last_config_change.is_none());
+}
+
+#[tokio::test]
+async fn startup_quiet_period_holds_back_advertisements() {
+    let mut config = RouterConfig::default();
+    config.rip.enabled = false;
+    config.rip.startup_quiet_period = 60;
+
+    let router = Router::with_config(config.clone())
+        .await
+        .expect("router constructed");
+    assert!(router.quiet_remaining().is_some());
+    assert!(router.statistics().await.quiet_seconds_remaining.is_some());
+    let ctx = router.rip_context(EventBus::new(16));
+    assert!(ctx.is_quiet());
+    assert!(advertisement(&ctx, "wan0", None).await.entries.is_empty());
+
+    config.rip.startup_quiet_period = 0;
+    let router = Router::with_config(config)
+        .await
+        .expect("router constructed");
+    assert_eq!(router.quiet_remaining(), None);
+    let ctx = router.rip_context(EventBus::new(16));
+    assert_eq!(advertisement(&ctx, "wan0", None).await.entries.len(), 1);
+}
+
+#[tokio::test]
+async fn disabling_interface_poisons_connected_route() {
+    let mut config = RouterConfig::default();
+    config.rip.enabled = false;
+
+    let routing_table = Arc::new(RwLock::new(RoutingTable::new()));
+    let mut router = Router::new(config.clone(), Arc::clone(&routing_table), Metrics::new())
+        .await
+        .expect("router constructed");
+
+    let iface = config.interfaces.first_mut().expect("default interface");
+    iface.enabled = false;
+    let iface_name = iface.name.clone();
+    router.apply_config(config).await.expect("config applied");
+
+    let table = routing_table.read().await;
+    let routes = table.get_all_routes();
+    let connected = routes
+        .iter()
+        .find(|route| route.interface == iface_name && route.source == RouteSource::Direct)
+        .expect("connected route retained for withdrawal");
+    assert_eq!(connected.metric, 16);
+}
+
+#[tokio::test]
+async fn withdrawing_interface_poisons_learned_routes() {
+    let mut config = RouterConfig::default();
+    config.rip.enabled = false;
+
+    let routing_table = Arc::new(RwLock::new(RoutingTable::new()));
+    let router = Router::new(config, Arc::clone(&routing_table), Metrics::new())
+        .await
+        .expect("router constructed");
+    let ctx = router.rip_context(EventBus::new(16));
+
+    let sender = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(192, 168, 1, 2), 520));
+    let packet = RipPacket::new_response(vec![RipEntry::new(
+        Ipv4Addr::new(10, 7, 0, 0),
+        Ipv4Addr::new(255, 255, 0, 0),
+        Ipv4Addr::UNSPECIFIED,
+        1,
+    )]);
+    handle_rip_response(&ctx, "eth0".to_string(), packet, sender)
+        .await
+        .expect("processing response succeeds");
+
+    let withdrawn = router.withdraw_interface("eth0").await;
+    assert!(withdrawn
+        .iter()
+        .any(|route| route.source == RouteSource::Dynamic));
+    assert!(withdrawn.iter().all(|route| route.metric == 16));
+
+    let table = routing_table.read().await;
+    let learned = table
+        .get_all_routes()
+        .into_iter()
+        .find(|route| route.destination == Ipv4Addr::new(10, 7, 0, 0))
+        .expect("learned route kept for withdrawal");
+    assert_eq!(learned.metric, 16);
+}
+
+#[tokio::test]
+async fn injected_updates_are_processed_like_received_ones() {
+    let mut config = RouterConfig::default();
+    config.rip.enabled = false;
+
+    let routing_table = Arc::new(RwLock::new(RoutingTable::new()));
+    let router = Router::new(config, Arc::clone(&routing_table), Metrics::new())
+        .await
+        .expect("router constructed");
+    let neighbor = Ipv4Addr::new(192, 168, 1, 2);
+    let entries = |metric| {
+        vec![RipEntry::new(
+            Ipv4Addr::new(10, 8, 0, 0),
+            Ipv4Addr::new(255, 255, 0, 0),
+            Ipv4Addr::UNSPECIFIED,
+            metric,
+        )]
+    };
+
+    let changed = router
+        .inject_update(EventBus::new(16), "eth0", neighbor, entries(2))
+        .await
+        .expect("update injected");
+    assert_eq!(changed.len(), 1);
+    let route = routing_table
+        .read()
+        .await
+        .get_route(Ipv4Addr::new(10, 8, 0, 1))
+        .cloned()
+        .expect("injected route installed");
+    assert_eq!((route.metric, route.learned_from), (3, Some(neighbor)));
+
+    assert!(router
+        .inject_update(EventBus::new(16), "eth9", neighbor, entries(2))
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn configured_static_routes_are_installed_and_withdrawn() {
+    let mut config = RouterConfig::default();
+    config.rip.enabled = false;
+    let static_route = |prefix: &str, advertise| StaticRouteConfig {
+        prefix: prefix.parse().unwrap(),
+        next_hop: Ipv4Addr::new(192, 168, 1, 254),
+        interface: "eth0".to_string(),
+        metric: 2,
+        advertise,
+    };
+    config.static_routes = vec![
+        static_route("10.20.0.0/16", true),
+        static_route("10.30.0.0/16", false),
+    ];
+
+    let routing_table = Arc::new(RwLock::new(RoutingTable::new()));
+    let mut router = Router::new(config.clone(), Arc::clone(&routing_table), Metrics::new())
+        .await
+        .expect("router constructed");
+    {
+        let table = routing_table.read().await;
+        let route = table
+            .get_route(Ipv4Addr::new(10, 20, 0, 1))
+            .expect("static route installed");
+        assert_eq!(route.source, RouteSource::Static);
+        assert_eq!(route.metric, 2);
+        assert!(table.get_route(Ipv4Addr::new(10, 30, 0, 1)).is_some());
+    }
+    let advertised: Vec<Ipv4Addr> =
+        advertisement(&router.rip_context(EventBus::new(16)), "wan0", None)
+            .await
+            .entries
+            .iter()
+            .map(|entry| entry.ip_address)
+            .collect();
+    assert!(advertised.contains(&Ipv4Addr::new(10, 20, 0, 0)));
+    assert!(!advertised.contains(&Ipv4Addr::new(10, 30, 0, 0)));
+
+    config.static_routes.remove(0);
+    router.apply_config(config).await.expect("config applied");
+    let table = routing_table.read().await;
+    assert_eq!(
+        table
+            .get_route(Ipv4Addr::new(10, 20, 0, 1))
+            .map(|route| route.metric),
+        Some(16)
+    );
+}
+
+#[tokio::test]
+async fn climbing_metric_is_flagged_as_count_to_infinity() {
+    let mut config = RouterConfig::default();
+    config.rip.enabled = false;
+
+    let metrics = Metrics::new();
+    let router = Router::new(
+        config,
+        Arc::new(RwLock::new(RoutingTable::new())),
+        metrics.clone(),
+    )
+    .await
+    .expect("router constructed");
+    let events = EventBus::new(64);
+    let mut receiver = events.subscribe("test");
+    let ctx = router.rip_context(events);
+
+    let sender = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(192, 168, 10, 1), 520));
+    for metric in [2, 4, 6, 8] {
+        let packet = RipPacket::new_response(vec![RipEntry::new(
+            Ipv4Addr::new(10, 9, 0, 0),
+            Ipv4Addr::new(255, 255, 0, 0),
+            Ipv4Addr::UNSPECIFIED,
+            metric,
+        )]);
+        handle_rip_response(&ctx, "eth0".to_string(), packet, sender)
+            .await
+            .expect("processing response succeeds");
+    }
+
+    assert_eq!(metrics.snapshot(1, 1).count_to_infinity_detected, 1);
+    let mut flagged = None;
+    while let Ok(event) = receiver.try_recv() {
+        if let WebEvent::CountToInfinity(event) = event {
+            flagged = Some(event);
+        }
+    }
+    let flagged = flagged.expect("count-to-infinity event published");
+    assert_eq!(flagged.destination, "10.9.0.0");
+    assert_eq!(flagged.neighbors, vec!["192.168.10.1".to_string()]);
+    assert_eq!(flagged.metric, 9);
+}
+
+#[tokio::test]
+async fn off_link_next_hops_are_replaced_or_rejected() {
+    let mut table = RoutingTable::new();
+    table.add_or_replace(Route::new_direct(
+        Ipv4Addr::new(192, 168, 10, 0),
+        Ipv4Addr::new(255, 255, 255, 0),
+        "eth0".to_string(),
+    ));
+    let routing_table = Arc::new(RwLock::new(table));
+    let metrics = Metrics::new();
+    let mut rip_config = RouterConfig::default().rip;
+    let mut ctx = RipContext {
+        routing_table: Arc::clone(&routing_table),
+        neighbors: Arc::new(RwLock::new(HashMap::new())),
+        metrics: metrics.clone(),
+        rip_config: Arc::new(rip_config.clone()),
+        events: EventBus::new(16),
+        router_uuid: Uuid::new_v4(),
+        policy: PolicyHandle::default(),
+        loop_detector: LoopDetector::default(),
+        decisions: DecisionTrace::default(),
+        interface_costs: InterfaceCosts::default(),
+        quiet_until: None,
+    };
+
+    let sender_ip = Ipv4Addr::new(192, 168, 10, 1);
+    let sender = SocketAddr::V4(SocketAddrV4::new(sender_ip, 520));
+    let packet = || {
+        RipPacket::new_response(vec![
+            RipEntry::new(
+                Ipv4Addr::new(10, 1, 0, 0),
+                Ipv4Addr::new(255, 255, 0, 0),
+                Ipv4Addr::new(192, 168, 10, 7),
+                1,
+            ),
+            RipEntry::new(
+                Ipv4Addr::new(10, 2, 0, 0),
+                Ipv4Addr::new(255, 255, 0, 0),
+                Ipv4Addr::new(203, 0, 113, 9),
+                1,
+            ),
+        ])
+    };
+
+    handle_rip_response(&ctx, "eth0".to_string(), packet(), sender)
+        .await
+        .expect("processing response succeeds");
+    {
+        let table = routing_table.read().await;
+        let on_link = table.find_best_route(&Ipv4Addr::new(10, 1, 0, 1)).unwrap();
+        assert_eq!(on_link.next_hop, Ipv4Addr::new(192, 168, 10, 7));
+        let off_link = table.find_best_route(&Ipv4Addr::new(10, 2, 0, 1)).unwrap();
+        assert_eq!(off_link.next_hop, sender_ip);
+    }
+    assert_eq!(metrics.snapshot(1, 3).invalid_next_hops, 1);
+
+    rip_config.next_hop_validation.action = NextHopAction::Reject;
+    ctx.rip_config = Arc::new(rip_config);
+    routing_table
+        .write()
+        .await
+        .clear_source(RouteSource::Dynamic);
+    let routes = handle_rip_response(&ctx, "eth0".to_string(), packet(), sender)
+        .await
+        .expect("processing response succeeds");
+    assert_eq!(routes.len(), 1);
+    assert!(routing_table
+        .read()
+        .await
+        .find_best_route(&Ipv4Addr::new(10, 2, 0, 1))
+        .is_none());
+}
+
+#[tokio::test]
+async fn backup_neighbor_routes_wait_for_the_primary_to_disappear() {
+    let primary = Ipv4Addr::new(192, 168, 10, 1);
+    let backup = Ipv4Addr::new(192, 168, 10, 2);
+    let mut rip_config = RouterConfig::default().rip;
+    rip_config.neighbors = vec![
+        NeighborConfig {
+            address: primary,
+            metric_offset: 0,
+            distance: 100,
+        },
+        NeighborConfig {
+            address: backup,
+            metric_offset: 2,
+            distance: 120,
+        },
+    ];
+    let routing_table = Arc::new(RwLock::new(RoutingTable::new()));
+    let ctx = RipContext {
+        routing_table: Arc::clone(&routing_table),
+        neighbors: Arc::new(RwLock::new(HashMap::new())),
+        metrics: Metrics::new(),
+        rip_config: Arc::new(rip_config),
+        events: EventBus::new(16),
+        router_uuid: Uuid::new_v4(),
+        policy: PolicyHandle::default(),
+        loop_detector: LoopDetector::default(),
+        decisions: DecisionTrace::default(),
+        interface_costs: InterfaceCosts::default(),
+        quiet_until: None,
+    };
+
+    let destination = Ipv4Addr::new(10, 5, 0, 0);
+    let mask = Ipv4Addr::new(255, 255, 0, 0);
+    let advertise = |from: Ipv4Addr, metric: u32| {
+        let packet = RipPacket::new_response(vec![RipEntry::new(
+            destination,
+            mask,
+            Ipv4Addr::UNSPECIFIED,
+            metric,
+        )]);
+        handle_rip_response(
+            &ctx,
+            "eth0".to_string(),
+            packet,
+            SocketAddr::V4(SocketAddrV4::new(from, 520)),
+        )
+    };
+
+    advertise(primary, 4).await.unwrap();
+    // A lower metric from the backup does not beat the primary's distance
+    assert!(advertise(backup, 1).await.unwrap().is_empty());
+    let best = |table: &RoutingTable| table.get_route(destination).cloned().unwrap();
+    assert_eq!(best(&*routing_table.read().await).next_hop, primary);
+
+    routing_table.write().await.poison_route(destination, mask);
+    let installed = advertise(backup, 1).await.unwrap();
+    assert_eq!(installed.len(), 1);
+    let route = best(&*routing_table.read().await);
+    assert_eq!(route.next_hop, backup);
+    assert_eq!(route.metric, 4);
+    assert_eq!(route.distance, 120);
+}
+
+#[tokio::test]
+async fn editing_an_interface_address_rebinds_its_socket() {
+    let mut config = RouterConfig::default();
+    config.rip.port = 0;
+    config.interfaces.truncate(1);
+    config.interfaces[0].name = "lo".to_string();
+    config.interfaces[0].address = "127.0.0.1/24".to_string();
+
+    let routing_table = Arc::new(RwLock::new(RoutingTable::new()));
+    let mut router = Router::new(config.clone(), Arc::clone(&routing_table), Metrics::new())
+        .await
+        .expect("router constructed");
+
+    config.interfaces[0].address = "127.0.1.1/24".to_string();
+    router.apply_config(config).await.expect("config applied");
+
+    let interfaces = router.network_interfaces();
+    assert_eq!(interfaces[0].ip_address(), Ipv4Addr::new(127, 0, 1, 1));
+
+    let table = routing_table.read().await;
+    let connected = |network: Ipv4Addr| {
+        table
+            .get_all_routes()
+            .into_iter()
+            .find(|route| route.source == RouteSource::Direct && route.destination == network)
+            .map(|route| route.metric)
+    };
+    assert!(connected(Ipv4Addr::new(127, 0, 1, 0)).is_some_and(|metric| metric < 16));
+    assert_eq!(connected(Ipv4Addr::new(127, 0, 0, 0)), Some(16));
+}
+
+#[tokio::test]
+async fn router_runs_once_its_receive_loops_are_supervised() {
+    let mut config = RouterConfig::default();
+    config.rip.port = 0;
+    config.interfaces.truncate(1);
+    config.interfaces[0].name = "lo".to_string();
+    config.interfaces[0].address = "127.0.0.1/24".to_string();
+
+    let mut router = Router::with_config(config)
+        .await
+        .expect("router constructed");
+    let watchdog = Watchdog::new(WatchdogConfig::default());
+    let events = EventBus::new(16);
+    router.set_supervisor(Supervisor {
+        watchdog: watchdog.clone(),
+        events: events.clone(),
+    });
+    assert!(!router.is_running());
+
+    ensure_receive_tasks(
+        &watchdog,
+        &router.network_interfaces(),
+        &router.rip_context(events),
+    );
+    assert!(router.is_running());
+}
+
+#[tokio::test]
+async fn restart_reopens_interfaces_and_forgets_neighbors() {
+    let mut config = RouterConfig::default();
+    config.rip.port = 0;
+    config.interfaces.truncate(1);
+    config.interfaces[0].name = "lo".to_string();
+    config.interfaces[0].address = "127.0.0.1/24".to_string();
+
+    let routing_table = Arc::new(RwLock::new(RoutingTable::new()));
+    let mut router = Router::new(config, Arc::clone(&routing_table), Metrics::new())
+        .await
+        .expect("router constructed");
+    let watchdog = Watchdog::new(WatchdogConfig::default());
+    let events = EventBus::new(16);
+    router.set_supervisor(Supervisor {
+        watchdog: watchdog.clone(),
+        events: events.clone(),
+    });
+    ensure_receive_tasks(
+        &watchdog,
+        &router.network_interfaces(),
+        &router.rip_context(events),
+    );
+
+    let neighbor = Ipv4Addr::new(127, 0, 0, 2);
+    router.neighbors().write().await.insert(
+        IpAddr::V4(neighbor),
+        NeighborInfo {
+            address: IpAddr::V4(neighbor),
+            interface: Some("lo".to_string()),
+            last_seen: std::time::Instant::now(),
+            learned_routes: 1,
+        },
+    );
+    routing_table.write().await.add_or_replace(Route::new(
+        Ipv4Addr::new(10, 9, 0, 0),
+        Ipv4Addr::new(255, 255, 0, 0),
+        neighbor,
+        2,
+        "lo".to_string(),
+        RouteSource::Dynamic,
+        Some(neighbor),
+    ));
+    let before = Arc::downgrade(&router.network_interfaces()[0]);
+
+    let outcome = router.restart().await.expect("restarted");
+    assert_eq!(outcome.interfaces, 1);
+    assert_eq!(outcome.neighbors_cleared, 1);
+    assert_eq!(outcome.routes_cleared, 1);
+
+    // The old interface and its socket are gone, not just reset
+    assert!(before.upgrade().is_none());
+    assert_eq!(router.network_interfaces().len(), 1);
+    assert!(watchdog.contains("receive-lo"));
+    assert!(router.neighbors().read().await.is_empty());
+    let table = routing_table.read().await;
+    assert!(table
+        .get_all_routes()
+        .iter()
+        .all(|route| route.source == RouteSource::Direct));
+}
+
+#[tokio::test]
+async fn responses_on_authenticated_interfaces_need_authentication() {
+    let mut rip_config = RouterConfig::default().rip;
+    rip_config.authentication = vec![InterfaceAuthConfig {
+        interface: "eth0".to_string(),
+        mode: AuthMode::Sha256,
+        keys: vec![AuthKey {
+            id: 1,
+            key: "secret".to_string(),
+            valid_from: None,
+            valid_until: None,
+        }],
+    }];
+    let routing_table = Arc::new(RwLock::new(RoutingTable::new()));
+    let ctx = RipContext {
+        routing_table: Arc::clone(&routing_table),
+        neighbors: Arc::new(RwLock::new(HashMap::new())),
+        metrics: Metrics::new(),
+        rip_config: Arc::new(rip_config.clone()),
+        events: EventBus::new(16),
+        router_uuid: Uuid::new_v4(),
+        policy: PolicyHandle::default(),
+        loop_detector: LoopDetector::default(),
+        decisions: DecisionTrace::default(),
+        interface_costs: InterfaceCosts::default(),
+        quiet_until: None,
+    };
+    let sender = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(192, 168, 10, 1), 520));
+    let packet = RipPacket::new_response(vec![RipEntry::new(
+        Ipv4Addr::new(10, 1, 0, 0),
+        Ipv4Addr::new(255, 255, 0, 0),
+        Ipv4Addr::UNSPECIFIED,
+        1,
+    )]);
+
+    assert!(
+        handle_rip_response(&ctx, "eth0".to_string(), packet.clone(), sender)
+            .await
+            .is_err()
+    );
+    assert!(routing_table.read().await.get_all_routes().is_empty());
+
+    let auth = &rip_config.authentication[0];
+    let signed = rip_auth::sign(&packet, auth.mode, &auth.keys[0], 1).unwrap();
+    let routes = handle_rip_response(
+        &ctx,
+        "eth0".to_string(),
+        RipPacket::from_bytes(&signed).unwrap(),
+        sender,
+    )
+    .await
+    .expect("authenticated response is processed");
+    assert_eq!(routes.len(), 1);
+
+    // Other interfaces are unaffected
+    handle_rip_response(&ctx, "eth1".to_string(), packet, sender)
+        .await
+        .expect("interface without a key chain accepts the response");
+}
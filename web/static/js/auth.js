@@ -1,6 +1,18 @@
+// Prefix of every route when the UI is served under `web.base_path`
+const RUST_ROUTE_BASE_PATH =
+    document.querySelector('meta[name="base-path"]')?.getAttribute('content') || '';
+
+window.appUrl = function appUrl(path) {
+    return `${RUST_ROUTE_BASE_PATH}${path}`;
+};
+
 class RustRouteAuthClient {
     constructor() {
-        this.tokenKey = 'rustroute_token';
+        // Routers behind one proxy host share the origin, so keep their tokens apart
+        this.tokenKey = RUST_ROUTE_BASE_PATH
+            ? `rustroute_token:${RUST_ROUTE_BASE_PATH}`
+            : 'rustroute_token';
+        this.csrfCookie = 'rustroute_csrf';
     }
 
     getToken() {
@@ -15,6 +27,33 @@ class RustRouteAuthClient {
 
     clearToken() {
         localStorage.removeItem(this.tokenKey);
+        document.cookie = `${this.csrfCookie}=; Max-Age=0; Path=${RUST_ROUTE_BASE_PATH || '/'}`;
+    }
+
+    // Set alongside the HttpOnly session cookie when cookie sessions are enabled
+    getCsrfToken() {
+        const prefix = `${this.csrfCookie}=`;
+        const entry = document.cookie
+            .split(';')
+            .map((part) => part.trim())
+            .find((part) => part.startsWith(prefix));
+        return entry ? entry.slice(prefix.length) || null : null;
+    }
+
+    hasSession() {
+        return Boolean(this.getToken() || this.getCsrfToken());
+    }
+
+    applyHeaders(headers) {
+        const token = this.getToken();
+        if (token) {
+            headers.set('Authorization', `Bearer ${token}`);
+        }
+        const csrf = this.getCsrfToken();
+        if (csrf) {
+            headers.set('X-CSRF-Token', csrf);
+        }
+        return headers;
     }
 }
 
@@ -70,7 +109,7 @@ class RustRouteAuthUI {
         };
 
         try {
-            const response = await fetch('/api/auth/login', {
+            const response = await fetch(window.appUrl('/api/auth/login'), {
                 method: 'POST',
                 headers: {
                     'Content-Type': 'application/json',
@@ -80,7 +119,8 @@ class RustRouteAuthUI {
             });
 
             const body = await response.json();
-            if (!body.success || !body.data || !body.data.token) {
+            const hasToken = body.data && (body.data.token || window.authClient.getCsrfToken());
+            if (!body.success || !hasToken) {
                 throw new Error(body.message || '登录失败');
             }
 
@@ -98,15 +138,13 @@ class RustRouteAuthUI {
     }
 
     async logout() {
-        const token = window.authClient.getToken();
-        if (token) {
+        if (window.authClient.hasSession()) {
             try {
-                await fetch('/api/auth/logout', {
+                await fetch(window.appUrl('/api/auth/logout'), {
                     method: 'POST',
-                    headers: {
-                        Authorization: `Bearer ${token}`,
-                        Accept: 'application/json',
-                    },
+                    headers: window.authClient.applyHeaders(
+                        new Headers({ Accept: 'application/json' })
+                    ),
                 });
             } catch (error) {
                 console.warn('Logout request failed:', error);
@@ -171,7 +209,7 @@ class RustRouteAuthUI {
     }
 
     updateUI() {
-        const token = window.authClient.getToken();
+        const token = window.authClient.hasSession();
 
         if (this.logoutButton) {
             this.logoutButton.classList.toggle('hidden', !this.authRequired || !token);
@@ -215,10 +253,7 @@ window.fetchWithAuth = async function fetchWithAuth(url, options = {}, config =
         headers.set('Accept', 'application/json');
     }
 
-    const token = window.authClient?.getToken?.();
-    if (token) {
-        headers.set('Authorization', `Bearer ${token}`);
-    }
+    window.authClient?.applyHeaders?.(headers);
 
     const response = await fetch(url, { ...options, headers });
 
@@ -241,6 +276,30 @@ window.fetchWithAuth = async function fetchWithAuth(url, options = {}, config =
     return response;
 };
 
+// EventSource cannot send headers, so a page holding a token trades it for a
+// one-time ticket rather than putting the token in the URL. Cookie sessions
+// and open routers need neither.
+window.eventStreamUrl = async function eventStreamUrl(path) {
+    if (!window.authClient?.getToken?.()) {
+        return window.appUrl(path);
+    }
+    try {
+        const response = await window.fetchWithAuth(
+            window.appUrl('/api/auth/stream-ticket'),
+            { method: 'POST' },
+            { silent: true }
+        );
+        const body = await response.json();
+        if (body.success && body.data?.ticket) {
+            const separator = path.includes('?') ? '&' : '?';
+            return window.appUrl(`${path}${separator}ticket=${encodeURIComponent(body.data.ticket)}`);
+        }
+    } catch (error) {
+        console.warn('Failed to obtain an event stream ticket:', error);
+    }
+    return window.appUrl(path);
+};
+
 document.addEventListener('DOMContentLoaded', () => {
     window.authUI.init();
 });
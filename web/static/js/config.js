@@ -13,7 +13,7 @@ class ConfigPage {
 
     async syncAuthRequirement() {
         try {
-            const response = await window.fetchWithAuth('/api/status', {}, { silent: true });
+            const response = await window.fetchWithAuth(window.appUrl('/api/status'), {}, { silent: true });
             const body = await response.json();
             if (body.success) {
                 window.authUI?.setAuthRequired(Boolean(body.data?.auth_required));
@@ -28,7 +28,7 @@ class ConfigPage {
     async loadConfig() {
         const target = document.getElementById('config-json');
         try {
-            const response = await window.fetchWithAuth('/api/config');
+            const response = await window.fetchWithAuth(window.appUrl('/api/config'));
             const body = await response.json();
             if (body.success) {
                 target.textContent = JSON.stringify(body.data, null, 2);
@@ -54,7 +54,7 @@ class ConfigPage {
         }
 
         try {
-            const response = await window.fetchWithAuth('/api/config/history');
+            const response = await window.fetchWithAuth(window.appUrl('/api/config/history'));
             const body = await response.json();
             if (!body.success) {
                 throw new Error(body.message || 'Failed to load history');
@@ -88,7 +88,7 @@ class ConfigPage {
         currentTarget.textContent = 'Loading diff...';
 
         try {
-            const response = await window.fetchWithAuth(`/api/config/history/${version}/diff`);
+            const response = await window.fetchWithAuth(window.appUrl(`/api/config/history/${version}/diff`));
             const body = await response.json();
             if (!body.success) {
                 throw new Error(body.message || 'Failed to fetch diff');
@@ -123,7 +123,7 @@ class ConfigPage {
 
         try {
             const response = await window.fetchWithAuth(
-                `/api/config/history/${version}/rollback`,
+                window.appUrl(`/api/config/history/${version}/rollback`),
                 {
                     method: 'POST',
                 },
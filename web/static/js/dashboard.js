@@ -1,7 +1,7 @@
 // Dashboard JavaScript
 class RustRouteDashboard {
     constructor() {
-        this.apiBase = '/api';
+        this.apiBase = window.appUrl('/api');
         this.updateInterval = 5000; // 5 seconds
         this.charts = {};
         this.maxDataPoints = 20;
@@ -12,23 +12,22 @@ class RustRouteDashboard {
         };
         this.lastMetricSnapshot = null;
         this.eventSource = null;
+        this.eventReconnectTimer = null;
     }
 
     initialize() {
         this.initializeCharts();
-        this.refreshEventStream();
+        // Show earlier activity before live events start arriving
+        this.loadActivity().finally(() => this.refreshEventStream());
         this.startRealTimeUpdates();
         if (window.authUI) {
             window.authUI.attachDashboard(this);
         }
     }
 
-    initializeEventStream() {
+    async initializeEventStream() {
         try {
-            const token = window.authClient?.getToken?.();
-            const url = token
-                ? `/api/events?token=${encodeURIComponent(token)}`
-                : '/api/events';
+            const url = await window.eventStreamUrl('/api/events');
             this.eventSource = new EventSource(url);
             this.eventSource.onmessage = (event) => {
                 try {
@@ -38,20 +37,40 @@ class RustRouteDashboard {
                     console.error('Failed to parse event payload', err);
                 }
             };
+            this.eventSource.addEventListener('lagged', () => this.loadSystemStatus());
+            // A ticket is redeemed once, so reconnect with a fresh one
+            // rather than letting EventSource retry with the spent URL
             this.eventSource.onerror = (error) => {
                 console.warn('Event stream error:', error);
+                this.scheduleReconnect();
             };
         } catch (error) {
             console.error('Failed to initialize event stream:', error);
+            this.scheduleReconnect();
         }
     }
 
     refreshEventStream() {
+        this.closeEventStream();
+        this.initializeEventStream();
+    }
+
+    closeEventStream() {
         if (this.eventSource) {
             this.eventSource.close();
             this.eventSource = null;
         }
-        this.initializeEventStream();
+        if (this.eventReconnectTimer) {
+            clearTimeout(this.eventReconnectTimer);
+            this.eventReconnectTimer = null;
+        }
+    }
+
+    scheduleReconnect() {
+        this.closeEventStream();
+        this.eventReconnectTimer = setTimeout(() => {
+            this.initializeEventStream();
+        }, 5000);
     }
 
     handleEvent(event) {
@@ -67,6 +86,23 @@ class RustRouteDashboard {
             case 'Activity':
                 this.handleActivityEvent(event.data);
                 break;
+            case 'CountToInfinity':
+                if (event.data) {
+                    const neighbors = (event.data.neighbors || []).join(', ');
+                    this.handleActivityEvent({
+                        level: 'Warn',
+                        message: `Count-to-infinity suspected for ${event.data.destination}/${event.data.subnet_mask} (metric ${event.data.metric}, via ${neighbors})`,
+                    });
+                }
+                break;
+            case 'TopologyChanged':
+                if (event.data) {
+                    this.handleActivityEvent({
+                        level: 'Info',
+                        message: `Topology changed: ${event.data.summary}`,
+                    });
+                }
+                break;
             default:
                 console.debug('Unhandled event type', event);
         }
@@ -95,6 +131,25 @@ class RustRouteDashboard {
         }
     }
 
+    async loadActivity() {
+        try {
+            const response = await this.fetchAPI('/activity?limit=10');
+            if (!response.success || !response.data) return;
+            [...response.data.entries].reverse().forEach((entry) => {
+                this.addActivityItem({
+                    time: this.formatTime(new Date(entry.timestamp)),
+                    level: entry.level.toLowerCase(),
+                    message: entry.message,
+                });
+            });
+        } catch (error) {
+            if (error instanceof window.RustRouteAuthError) {
+                return;
+            }
+            console.error('Failed to load activity:', error);
+        }
+    }
+
     updateSystemStatus(status) {
         if (window.authUI?.setAuthRequired) {
             window.authUI.setAuthRequired(Boolean(status.auth_required));
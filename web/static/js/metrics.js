@@ -15,7 +15,7 @@ class MetricsPage {
 
     async syncAuthRequirement() {
         try {
-            const response = await window.fetchWithAuth('/api/status', {}, { silent: true });
+            const response = await window.fetchWithAuth(window.appUrl('/api/status'), {}, { silent: true });
             const body = await response.json();
             if (body.success) {
                 window.authUI?.setAuthRequired(Boolean(body.data?.auth_required));
@@ -30,7 +30,7 @@ class MetricsPage {
     async loadMetrics() {
         const target = document.getElementById('metrics-json');
         try {
-            const response = await window.fetchWithAuth('/api/metrics');
+            const response = await window.fetchWithAuth(window.appUrl('/api/metrics'));
             const body = await response.json();
             if (!body.success) {
                 throw new Error(body.message || 'Unable to load metrics');
@@ -54,12 +54,9 @@ class MetricsPage {
         });
     }
 
-    initializeEventStream() {
+    async initializeEventStream() {
         this.closeEventStream();
-        const token = window.authClient?.getToken?.();
-        const url = token
-            ? `/api/events?token=${encodeURIComponent(token)}`
-            : '/api/events';
+        const url = await window.eventStreamUrl('/api/events?types=Metrics');
 
         try {
             this.eventSource = new EventSource(url);
@@ -18,7 +18,7 @@ class RoutesPage {
 
     async syncAuthRequirement() {
         try {
-            const response = await window.fetchWithAuth('/api/status', {}, { silent: true });
+            const response = await window.fetchWithAuth(window.appUrl('/api/status'), {}, { silent: true });
             const body = await response.json();
             if (body.success) {
                 window.authUI?.setAuthRequired(Boolean(body.data?.auth_required));
@@ -32,7 +32,7 @@ class RoutesPage {
 
     async loadRoutes() {
         try {
-            const response = await window.fetchWithAuth('/api/routes');
+            const response = await window.fetchWithAuth(window.appUrl('/api/routes'));
             const body = await response.json();
             if (!body.success) {
                 throw new Error(body.message || 'Failed to load routes');
@@ -94,13 +94,10 @@ class RoutesPage {
         }, 15000);
     }
 
-    initializeEventStream() {
+    async initializeEventStream() {
         this.closeEventStream();
 
-        const token = window.authClient?.getToken?.();
-        const url = token
-            ? `/api/events?token=${encodeURIComponent(token)}`
-            : '/api/events';
+        const url = await window.eventStreamUrl('/api/events');
 
         try {
             this.eventSource = new EventSource(url);
@@ -119,6 +116,9 @@ class RoutesPage {
             }
         };
 
+        // Events were dropped while we lagged behind; the table may be stale
+        this.eventSource.addEventListener('lagged', () => this.loadRoutes());
+
         this.eventSource.onerror = (error) => {
             console.warn('Event stream error:', error);
             this.scheduleEventReconnect();
@@ -146,6 +146,7 @@ class RoutesPage {
     handleEvent(event) {
         switch (event.type) {
             case 'Route':
+            case 'RouteWithdrawn':
                 // Refresh the route list when we receive an update
                 this.loadRoutes();
                 break;
@@ -205,7 +206,11 @@ class RoutesPage {
             const metric = Number(route.metric) || 0;
             const iface = escapeHtml(route.interface);
             const age = this.formatAge(route.age_seconds ?? route.age ?? 0);
+            const updatedAt = route.last_updated
+                ? escapeHtml(new Date(route.last_updated).toLocaleString())
+                : '';
             const learnedFrom = escapeHtml(route.learned_from) || '—';
+            const source = escapeHtml(this.formatSource(route.source));
             const encodedDestination = encodeURIComponent(route.destination ?? '');
             const encodedMask = encodeURIComponent(route.subnet_mask ?? '');
 
@@ -217,8 +222,9 @@ class RoutesPage {
                         <span class="metric-badge ${this.getMetricClass(metric)}">${metric}</span>
                     </td>
                     <td><code>${iface}</code></td>
-                    <td>${age}</td>
+                    <td title="${updatedAt}">${age}</td>
                     <td><code>${learnedFrom}</code></td>
+                    <td>${source}</td>
                     <td>
                         <div class="route-actions">
                             <button
@@ -297,7 +303,7 @@ class RoutesPage {
         };
 
         try {
-            const response = await window.fetchWithAuth('/api/routes', {
+            const response = await window.fetchWithAuth(window.appUrl('/api/routes'), {
                 method: 'POST',
                 headers: {
                     'Content-Type': 'application/json',
@@ -336,7 +342,9 @@ class RoutesPage {
 
         try {
             const response = await window.fetchWithAuth(
-                `/api/routes/${encodeURIComponent(decodedDestination)}/${encodeURIComponent(decodedMask)}`,
+                window.appUrl(
+                    `/api/routes/${encodeURIComponent(decodedDestination)}/${encodeURIComponent(decodedMask)}`
+                ),
                 { method: 'DELETE' },
             );
             const body = await response.json();
@@ -362,6 +370,14 @@ class RoutesPage {
         return 'metric-high';
     }
 
+    // Unit sources serialize as "Direct"; redistributed ones as
+    // {"Redistributed": "kernel"}
+    formatSource(source) {
+        if (typeof source === 'string') return source.toLowerCase();
+        if (source && source.Redistributed) return `redistributed (${source.Redistributed})`;
+        return '—';
+    }
+
     formatAge(seconds) {
         const totalSeconds = Number(seconds) || 0;
         if (totalSeconds < 60) return `${totalSeconds}s`;
@@ -377,7 +393,7 @@ class RoutesPage {
         }
         tbody.innerHTML = `
             <tr>
-                <td colspan="7" style="text-align: center; padding: 2rem;">${escapeHtml(message)}</td>
+                <td colspan="8" style="text-align: center; padding: 2rem;">${escapeHtml(message)}</td>
             </tr>
         `;
     }